@@ -1 +1,2 @@
+pub mod method;
 pub use rtsp_common::version;
@@ -0,0 +1,121 @@
+use std::{
+    convert::{Infallible, TryFrom},
+    error::Error,
+    fmt::{Display, Formatter, Result as FormatterResult},
+};
+
+/// Methods of RTSP/1.0 ([RFC2326](https://tools.ietf.org/html/rfc2326)) that were removed in
+/// RTSP/2.0 but are still used by publishing agents.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum Method {
+    Announce,
+    Record,
+}
+
+impl Method {
+    pub fn as_encoded(&self) -> &'static [u8] {
+        self.as_str().as_bytes()
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        use self::Method::*;
+
+        match self {
+            Announce => "ANNOUNCE",
+            Record => "RECORD",
+        }
+    }
+
+    pub fn try_decode(value: &[u8]) -> Result<Self, DecodeError> {
+        use self::Method::*;
+
+        if value.eq_ignore_ascii_case(b"ANNOUNCE") {
+            Ok(Announce)
+        } else if value.eq_ignore_ascii_case(b"RECORD") {
+            Ok(Record)
+        } else {
+            Err(DecodeError::Unknown)
+        }
+    }
+}
+
+impl Display for Method {
+    fn fmt(&self, formatter: &mut Formatter) -> FormatterResult {
+        formatter.write_str(self.as_str())
+    }
+}
+
+impl From<Method> for &'static [u8] {
+    fn from(value: Method) -> Self {
+        value.as_encoded()
+    }
+}
+
+impl From<Method> for &'static str {
+    fn from(value: Method) -> Self {
+        value.as_str()
+    }
+}
+
+impl<'method> TryFrom<&'method [u8]> for Method {
+    type Error = DecodeError;
+
+    fn try_from(value: &'method [u8]) -> Result<Self, Self::Error> {
+        Self::try_decode(value)
+    }
+}
+
+impl<'method> TryFrom<&'method str> for Method {
+    type Error = DecodeError;
+
+    fn try_from(value: &'method str) -> Result<Self, Self::Error> {
+        Self::try_decode(value.as_bytes())
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum DecodeError {
+    Unknown,
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, formatter: &mut Formatter) -> FormatterResult {
+        use self::DecodeError::*;
+
+        match self {
+            Unknown => formatter.write_str("unknown RTSP/1.0 method"),
+        }
+    }
+}
+
+impl Error for DecodeError {}
+
+impl From<Infallible> for DecodeError {
+    fn from(_: Infallible) -> Self {
+        unreachable!()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::{DecodeError, Method};
+
+    #[test]
+    fn test_as_encoded() {
+        assert_eq!(Method::Announce.as_encoded(), b"ANNOUNCE");
+        assert_eq!(Method::Record.as_encoded(), b"RECORD");
+    }
+
+    #[test]
+    fn test_try_decode() {
+        assert_eq!(Method::try_decode(b"ANNOUNCE"), Ok(Method::Announce));
+        assert_eq!(Method::try_decode(b"announce"), Ok(Method::Announce));
+        assert_eq!(Method::try_decode(b"RECORD"), Ok(Method::Record));
+        assert_eq!(Method::try_decode(b"Record"), Ok(Method::Record));
+
+        assert_eq!(Method::try_decode(b""), Err(DecodeError::Unknown));
+        assert_eq!(Method::try_decode(b"PLAY"), Err(DecodeError::Unknown));
+    }
+}
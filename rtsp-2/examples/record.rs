@@ -0,0 +1,270 @@
+//! Records a camera stream into segment files.
+//!
+//! ```text
+//! cargo run --example record -- rtsp://127.0.0.1:554/stream [directory] [mp4|mkv] [seconds]
+//! ```
+//!
+//! The streams are received interleaved in the RTSP connection and written in one minute segments
+//! until the given number of seconds has passed or the server ends the session.
+
+use std::{
+    convert::TryFrom,
+    net::{IpAddr, SocketAddr},
+    time::{Duration, Instant},
+};
+
+use bytes::BytesMut;
+use futures::Stream;
+use rtsp_2::{
+    client::Client,
+    header::{
+        map::HeaderMapExtension,
+        name::HeaderName,
+        types::{range::NPTTime, RTPInfo, Range, Session},
+        value::HeaderValue,
+    },
+    media::{
+        depacketizer::{self, Reassembler},
+        jitter::Config as JitterBufferConfig,
+        record::{Config, ContainerFormat, Recorder},
+        rtp::Packet,
+        sdp::{SessionDescription, SDP_CONTENT_TYPE},
+        timeline::Timeline,
+    },
+    method::Method,
+    request::Request,
+    response::Response,
+    uri::{request::URI, Host, RTSP_DEFAULT_PORT},
+};
+use tokio::runtime::Runtime;
+
+/// The duration of each segment file.
+const SEGMENT_DURATION: Duration = Duration::from_secs(60);
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let uri_string = args
+        .next()
+        .unwrap_or_else(|| String::from("rtsp://127.0.0.1:10500"));
+    let directory = args.next().unwrap_or_else(|| String::from("."));
+    let format = match args.next().as_deref() {
+        Some("mkv") => ContainerFormat::Matroska,
+        _ => ContainerFormat::MP4,
+    };
+    let seconds = args.next().and_then(|seconds| seconds.parse::<u64>().ok());
+
+    let uri = URI::try_from(uri_string.as_str()).expect("Invalid URI");
+    let address = match uri.host() {
+        Some(Host::IPv4Address(ip)) => IpAddr::V4(*ip),
+        Some(Host::IPv6Address(ip)) => IpAddr::V6(*ip),
+        host => {
+            eprintln!(
+                "Please provide ip address. Hostname not supported: {:?}",
+                host
+            );
+            std::process::exit(1);
+        }
+    };
+    let address = SocketAddr::new(address, uri.port().unwrap_or(RTSP_DEFAULT_PORT));
+
+    let mut runtime = Runtime::new().unwrap();
+    let mut client = runtime
+        .block_on(Client::connect(address))
+        .expect("error connecting to server");
+    println!("Connected to server: {}", client.server_address());
+
+    // Describe the presentation to learn about its streams.
+
+    let request = Request::<()>::builder()
+        .with_method(Method::Describe)
+        .with_uri(uri.clone())
+        .with_header(
+            HeaderName::Accept,
+            HeaderValue::try_from(SDP_CONTENT_TYPE).unwrap(),
+        )
+        .with_body(BytesMut::new())
+        .build()
+        .unwrap();
+    let response = send(&mut runtime, &mut client, request);
+    let description = SessionDescription::try_from(&*String::from_utf8_lossy(response.body()))
+        .expect("invalid session description");
+    let base = response
+        .headers()
+        .get(&HeaderName::ContentBase)
+        .map(|base| base.as_str().to_string())
+        .unwrap_or_else(|| uri.to_string());
+
+    // Set up every stream that can be recorded, each on its own pair of interleaved channels.
+
+    let config = Config::builder()
+        .with_format(format)
+        .with_segment_duration(Some(SEGMENT_DURATION))
+        .build();
+    let mut recorder = Recorder::with_config(directory, "recording", config);
+    let mut streams = Vec::new();
+    let mut session: Option<Session> = None;
+
+    for media in description.media() {
+        let depacketizer =
+            match depacketizer::from_format(media.format(), media.format_parameters()) {
+                Ok(depacketizer) => depacketizer,
+                Err(_) => continue,
+            };
+        let track = match recorder.add_track(media.format(), media.format_parameters()) {
+            Ok(track) => track,
+            Err(_) => continue,
+        };
+        let control = control_uri(&base, media.control());
+        let channel = (streams.len() * 2) as u8;
+        let transport = format!(
+            "RTP/AVP/TCP;unicast;interleaved={}-{}",
+            channel,
+            channel + 1
+        );
+
+        let mut builder = Request::<()>::builder()
+            .with_method(Method::Setup)
+            .with_uri(control.clone())
+            .with_header(
+                HeaderName::Transport,
+                HeaderValue::try_from(transport.as_str()).unwrap(),
+            );
+
+        if let Some(session) = session.clone() {
+            builder = builder.with_typed_header(session);
+        }
+
+        let response = send(
+            &mut runtime,
+            &mut client,
+            builder.with_body(BytesMut::new()).build().unwrap(),
+        );
+        session = response.headers().typed_get::<Session>();
+        println!("Set up {} ({})", control, media.format());
+
+        let reassembler = Reassembler::new(depacketizer, JitterBufferConfig::default());
+        streams.push((control, media.format().clock_rate(), reassembler, track));
+    }
+
+    let session = session.expect("no streams could be set up");
+    let session = Session::without_timeout(session.id().as_str()).unwrap();
+    let interleaved_data = client.interleaved_data();
+
+    // Play the presentation, anchoring the timestamps of all streams onto the played range so they
+    // are recorded in sync.
+
+    let request = Request::<()>::builder()
+        .with_method(Method::Play)
+        .with_uri(uri.clone())
+        .with_typed_header(session.clone())
+        .with_body(BytesMut::new())
+        .build()
+        .unwrap();
+    let response = send(&mut runtime, &mut client, request);
+    let start = match response
+        .headers()
+        .typed_get::<Range>()
+        .and_then(|range| range.start())
+    {
+        Some(NPTTime::Time(start)) => start,
+        _ => Duration::from_secs(0),
+    };
+
+    if let Some(rtp_info) = response.headers().typed_get::<RTPInfo>() {
+        for (control, clock_rate, reassembler, _) in &mut streams {
+            let timeline = rtp_info
+                .iter()
+                .find(|info| info.uri() == control)
+                .and_then(|info| info.ssrc_infos().first())
+                .and_then(|info| Timeline::from_rtp_info(*clock_rate, info, start));
+
+            if let Some(timeline) = timeline {
+                reassembler.set_timeline(timeline);
+            }
+        }
+    }
+
+    println!("Recording...");
+
+    let deadline = seconds.map(|seconds| Instant::now() + Duration::from_secs(seconds));
+
+    // The client keeps the session alive while recording.
+    for data in interleaved_data.wait() {
+        let data = match data {
+            Ok(data) => data,
+            Err(_) => break,
+        };
+        let now = Instant::now();
+
+        if deadline.map_or(false, |deadline| now >= deadline) {
+            break;
+        }
+
+        // Odd channels carry RTCP, which is not needed for recording.
+        if data.channel() % 2 != 0 {
+            continue;
+        }
+
+        let (_, _, reassembler, track) = match streams.get_mut(usize::from(data.channel() / 2)) {
+            Some(stream) => stream,
+            None => continue,
+        };
+        let packet = match Packet::decode(data.into_payload()) {
+            Ok(packet) => packet,
+            Err(_) => continue,
+        };
+        reassembler.push(packet, now);
+
+        loop {
+            match reassembler.poll(now) {
+                Ok(Some(frame)) => recorder.push(*track, frame).expect("error recording"),
+                Ok(None) => break,
+                Err(error) => eprintln!("error depacketizing: {}", error),
+            }
+        }
+    }
+
+    recorder.finish().expect("error recording");
+
+    let request = Request::<()>::builder()
+        .with_method(Method::Teardown)
+        .with_uri(uri)
+        .with_typed_header(session)
+        .with_body(BytesMut::new())
+        .build()
+        .unwrap();
+    let _ = runtime.block_on(client.send_request(request));
+
+    for segment in recorder.segments() {
+        println!("Wrote {}", segment.display());
+    }
+}
+
+/// Returns the URI of a stream given the base URI of the presentation and the stream control.
+fn control_uri(base: &str, control: &str) -> URI {
+    if let Ok(uri) = URI::try_from(control) {
+        return uri;
+    }
+
+    let uri = format!("{}/{}", base.trim_end_matches('/'), control);
+    URI::try_from(uri.as_str()).expect("invalid stream control")
+}
+
+/// Sends a request and waits for a successful response.
+fn send(
+    runtime: &mut Runtime,
+    client: &mut Client,
+    request: Request<BytesMut>,
+) -> Response<BytesMut> {
+    let method = request.method().clone();
+    let response = runtime
+        .block_on(client.send_request(request))
+        .expect("error sending request");
+
+    if !response.status_code().is_success() {
+        eprintln!("{} failed: {}", method, response.status_code());
+        std::process::exit(1);
+    }
+
+    response
+}
@@ -0,0 +1,44 @@
+//! Decodes a stream of responses fed to the decoder in chunks, using both strictness extremes.
+//!
+//! The first byte of the input determines the chunk size, so that partial decoding is exercised.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rtsp::protocol::codec::decoder::{
+    response::{Config, Decoder},
+    DecodeResult, Strictness,
+};
+
+fuzz_target!(|data: &[u8]| {
+    let (chunk_size, stream) = match data.split_first() {
+        Some((&chunk_size, stream)) => (usize::from(chunk_size) + 1, stream),
+        None => return,
+    };
+
+    for strictness in [Strictness::strict(), Strictness::lenient()] {
+        let config = Config::builder()
+            .with_rtsp_1_0_allowed(true)
+            .with_strictness(strictness)
+            .build();
+        let mut decoder = Decoder::with_config(config);
+        let mut start = 0;
+        let mut end = 0;
+
+        while end < stream.len() {
+            end = (end + chunk_size).min(stream.len());
+
+            // Complete responses are decoded one at a time, so keep decoding the available bytes.
+            loop {
+                let (result, bytes_decoded) = decoder.decode(&stream[start..end]);
+                start += bytes_decoded;
+
+                match result {
+                    DecodeResult::Complete(_) => continue,
+                    DecodeResult::Error(_) => return,
+                    DecodeResult::Incomplete => break,
+                }
+            }
+        }
+    }
+});
@@ -0,0 +1,50 @@
+//! Decodes every typed header from a list of header values.
+//!
+//! The input is split into header values on line feeds, skipping the ones that are not valid header
+//! values.
+
+#![no_main]
+
+use std::convert::TryFrom;
+
+use libfuzzer_sys::fuzz_target;
+use rtsp::header::{
+    map::TypedHeader,
+    types::{
+        Accept, AcceptRanges, CSeq, ContentLength, Date, Expires, Public, RTPInfo, Range, Session,
+        Transport, Via,
+    },
+    value::HeaderValue,
+};
+
+/// Decodes the given typed header types from the given header values.
+macro_rules! decode {
+    ($values:expr, $($header:ty),+) => {
+        $(
+            let _ = <$header>::decode(&mut $values.iter());
+        )+
+    };
+}
+
+fuzz_target!(|data: &[u8]| {
+    let values = data
+        .split(|&byte| byte == b'\n')
+        .filter_map(|value| HeaderValue::try_from(value).ok())
+        .collect::<Vec<_>>();
+
+    decode!(
+        values,
+        Accept,
+        AcceptRanges,
+        CSeq,
+        ContentLength,
+        Date,
+        Expires,
+        Public,
+        RTPInfo,
+        Range,
+        Session,
+        Transport,
+        Via
+    );
+});
@@ -0,0 +1,12 @@
+//! Parses a request URI.
+
+#![no_main]
+
+use std::convert::TryFrom;
+
+use libfuzzer_sys::fuzz_target;
+use rtsp::uri::request::URI;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = URI::try_from(data);
+});
@@ -0,0 +1,50 @@
+//! Decodes a request, encodes it and decodes the encoding again, which must yield the same request.
+
+#![no_main]
+
+use bytes::BytesMut;
+use libfuzzer_sys::fuzz_target;
+use rtsp::{
+    header::name::HeaderName,
+    protocol::codec::{
+        decoder::{
+            request::{Config, Decoder},
+            DecodeResult, Strictness,
+        },
+        encoder::request as encoder,
+    },
+};
+
+fuzz_target!(|data: &[u8]| {
+    for strictness in [Strictness::strict(), Strictness::lenient()] {
+        let config = Config::builder()
+            .with_rtsp_1_0_allowed(true)
+            .with_strictness(strictness)
+            .build();
+        let mut request = match Decoder::with_config(config).decode(data) {
+            (DecodeResult::Complete(request), _) => request,
+            _ => continue,
+        };
+
+        let mut buffer = BytesMut::new();
+        encoder::encode(&request, &mut buffer);
+
+        // Encoded requests only deviate from the syntax in ways tolerated by default.
+        let config = Config::builder().with_rtsp_1_0_allowed(true).build();
+        let (result, bytes_decoded) = Decoder::with_config(config).decode(&buffer);
+        assert_eq!(bytes_decoded, buffer.len());
+
+        let mut decoded_request = match result {
+            DecodeResult::Complete(decoded_request) => decoded_request,
+            result => panic!("unexpected decode result: {:?}", result),
+        };
+
+        // The encoder replaces the `"Content-Length"` header, omitting it for empty bodies.
+        request.headers_mut().remove(&HeaderName::ContentLength);
+        decoded_request
+            .headers_mut()
+            .remove(&HeaderName::ContentLength);
+
+        assert_eq!(decoded_request, request);
+    }
+});
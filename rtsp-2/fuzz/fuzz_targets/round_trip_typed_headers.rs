@@ -0,0 +1,65 @@
+//! Decodes every typed header from a list of header values, encodes the ones that could be decoded
+//! and decodes the encoding again, which must succeed and encode to the same header values.
+//! Encodings are compared rather than typed headers, since encoding may canonicalize them, such as
+//! by leaving out a default session timeout.
+//!
+//! The input is split into header values on line feeds, skipping the ones that are not valid header
+//! values.
+
+#![no_main]
+
+use std::convert::TryFrom;
+
+use libfuzzer_sys::fuzz_target;
+use rtsp::header::{
+    map::TypedHeader,
+    types::{
+        Accept, AcceptRanges, CSeq, ContentLength, Date, Expires, Public, RTPInfo, Range, Session,
+        Transport, Via,
+    },
+    value::HeaderValue,
+};
+
+/// Round-trips the given typed header types through the given header values.
+macro_rules! round_trip {
+    ($values:expr, $($header:ty),+) => {
+        $(
+            if let Ok(Some(header)) = <$header>::decode(&mut $values.iter()) {
+                let mut encoded_values = Vec::new();
+                header.encode(&mut encoded_values);
+
+                let decoded_header = match <$header>::decode(&mut encoded_values.iter()) {
+                    Ok(Some(decoded_header)) => decoded_header,
+                    result => panic!("{}: {:?}", stringify!($header), result),
+                };
+                let mut reencoded_values = Vec::new();
+                decoded_header.encode(&mut reencoded_values);
+
+                assert_eq!(reencoded_values, encoded_values, "{}", stringify!($header));
+            }
+        )+
+    };
+}
+
+fuzz_target!(|data: &[u8]| {
+    let values = data
+        .split(|&byte| byte == b'\n')
+        .filter_map(|value| HeaderValue::try_from(value).ok())
+        .collect::<Vec<_>>();
+
+    round_trip!(
+        values,
+        Accept,
+        AcceptRanges,
+        CSeq,
+        ContentLength,
+        Date,
+        Expires,
+        Public,
+        RTPInfo,
+        Range,
+        Session,
+        Transport,
+        Via
+    );
+});
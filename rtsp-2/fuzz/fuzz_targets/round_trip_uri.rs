@@ -0,0 +1,16 @@
+//! Parses a request URI, formats it and parses the formatted URI again, which must yield the same
+//! URI.
+
+#![no_main]
+
+use std::convert::TryFrom;
+
+use libfuzzer_sys::fuzz_target;
+use rtsp::uri::request::URI;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(uri) = URI::try_from(data) {
+        let formatted = uri.to_string();
+        assert_eq!(URI::try_from(formatted.as_str()), Ok(uri));
+    }
+});
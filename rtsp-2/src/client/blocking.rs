@@ -0,0 +1,650 @@
+//! Blocking Client
+//!
+//! This module contains a synchronous [`Client`] for programs that do not otherwise use futures.
+//! It runs the asynchronous [`client::Client`](crate::client::Client) on a runtime of its own,
+//! blocking the calling thread until every operation completes.
+//!
+//! Streams set up through [`Client::setup`] are received interleaved on the connection, and the
+//! frames reassembled from them are iterated with [`Client::frames`].
+
+use std::{
+    convert::TryFrom,
+    error::Error,
+    fmt::{self, Display, Formatter},
+    io,
+    net::SocketAddr,
+    str,
+    sync::mpsc::{self, Receiver, RecvTimeoutError, TrySendError},
+    time::{Duration, Instant},
+};
+
+use bytes::BytesMut;
+use futures::{Future, Stream};
+use tokio::runtime::Runtime;
+use tokio_timer::Timeout;
+
+use crate::{
+    client::Client as AsyncClient,
+    header::{
+        map::HeaderMapExtension,
+        name::HeaderName,
+        types::{range::NPTTime, RTPInfo, Range, Session},
+        value::HeaderValue,
+    },
+    media::{
+        depacketizer::{self, Reassembler},
+        frame::Frame,
+        jitter::Config as JitterBufferConfig,
+        rtp::Packet,
+        sdp::{MediaDescription, SessionDescription, SDP_CONTENT_TYPE},
+        timeline::Timeline,
+    },
+    method::Method,
+    protocol::{
+        codec::interleaved::InterleavedData,
+        connection::{OperationError, RequestOptions},
+    },
+    request::Request,
+    response::Response,
+    session::SessionID,
+    status::StatusCode,
+    uri::request::URI,
+};
+
+/// The default duration to wait for connecting, for the response to a request, or for media.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How much interleaved data is buffered until the frames are iterated, after which further data
+/// is dropped.
+const INTERLEAVED_DATA_CAPACITY: usize = 1024;
+
+/// How far ahead of time buffered packets are released once the connection closes, which is far
+/// enough to release all of them.
+const FLUSH_HORIZON: Duration = Duration::from_secs(3600);
+
+/// A client connected to an RTSP server, blocking on every operation.
+///
+/// Requests for the presentation carry the session set up by [`Client::setup`], if any, and the
+/// session is kept alive in the background until it is torn down.
+pub struct Client {
+    /// The URI stream controls are relative to, as given by the latest `DESCRIBE` response.
+    base: Option<String>,
+
+    /// The asynchronous client running on the runtime.
+    client: AsyncClient,
+
+    /// The interleaved data received on the connection.
+    rx_interleaved_data: Receiver<InterleavedData>,
+
+    /// The runtime the connection runs on.
+    runtime: Runtime,
+
+    /// The session set up, if any.
+    session: Option<SessionID>,
+
+    /// The streams set up, in the order of their interleaved channels.
+    streams: Vec<SetupStream>,
+
+    /// The duration to wait for the response to a request, or for media.
+    timeout: Option<Duration>,
+}
+
+impl Client {
+    /// Connects to the server with the given address, waiting up to [`DEFAULT_TIMEOUT`].
+    pub fn connect(server_address: SocketAddr) -> io::Result<Self> {
+        let mut runtime = Runtime::new()?;
+        let connect =
+            Timeout::new(AsyncClient::connect(server_address), DEFAULT_TIMEOUT).map_err(|error| {
+                match error.into_inner() {
+                    Some(error) => error,
+                    None => io::ErrorKind::TimedOut.into(),
+                }
+            });
+        let mut client = runtime.block_on(connect)?;
+
+        // Interleaved data is forwarded to a channel, so that it can be received while blocking.
+        // The channel is disconnected once the connection shuts down, which ends iterating frames.
+        // Data is dropped while the channel is full, so that frames not iterated do not accumulate.
+        let (tx_interleaved_data, rx_interleaved_data) =
+            mpsc::sync_channel(INTERLEAVED_DATA_CAPACITY);
+        runtime.spawn(
+            client
+                .interleaved_data()
+                .for_each(move |data| match tx_interleaved_data.try_send(data) {
+                    Ok(()) | Err(TrySendError::Full(_)) => Ok(()),
+                    Err(TrySendError::Disconnected(_)) => Err(()),
+                })
+                .select(client.shutdown_receiver())
+                .then(|_| Ok(())),
+        );
+
+        Ok(Client {
+            base: None,
+            client,
+            rx_interleaved_data,
+            runtime,
+            session: None,
+            streams: Vec::new(),
+            timeout: Some(DEFAULT_TIMEOUT),
+        })
+    }
+
+    /// Describes the presentation with the given URI, returning its session description.
+    ///
+    /// The stream controls of the description are resolved against the `"Content-Base"` of the
+    /// response when the streams are set up, or against the given URI if there is none.
+    pub fn describe(&mut self, uri: &URI) -> Result<SessionDescription, ClientError> {
+        let request = Request::<()>::builder()
+            .with_method(Method::Describe)
+            .with_uri(uri.clone())
+            .with_header(
+                HeaderName::Accept,
+                HeaderValue::try_from(SDP_CONTENT_TYPE).unwrap(),
+            )
+            .with_body(BytesMut::new())
+            .build()
+            .unwrap();
+        let response = self.send_successful_request(request)?;
+        let description = str::from_utf8(response.body())
+            .ok()
+            .and_then(|body| SessionDescription::try_from(body).ok())
+            .ok_or(ClientError::InvalidDescription)?;
+
+        self.base = Some(
+            response
+                .headers()
+                .get(&HeaderName::ContentBase)
+                .map(|base| base.as_str().to_string())
+                .unwrap_or_else(|| uri.to_string()),
+        );
+
+        Ok(description)
+    }
+
+    /// Returns an iterator over the frames reassembled from the streams set up, along with the
+    /// index of their stream in the order the streams were set up.
+    ///
+    /// Streams whose payload format cannot be depacketized produce no frames. If nothing is
+    /// received for the timeout, [`ClientError::MediaTimedOut`] is returned, after which iterating
+    /// can continue. Iteration ends once the connection closes.
+    pub fn frames(&mut self) -> Frames<'_> {
+        Frames {
+            client: self,
+            last_received: Instant::now(),
+        }
+    }
+
+    /// Plays the presentation with the given URI in the session set up.
+    ///
+    /// The presentation times of the frames of each stream are anchored to the range played, as
+    /// given by the `"Range"` and `"RTP-Info"` headers of the response.
+    pub fn play(&mut self, uri: &URI) -> Result<Response<BytesMut>, ClientError> {
+        let request = self.session_request(Method::Play, uri)?;
+        let response = self.send_successful_request(request)?;
+        let start = match response
+            .headers()
+            .typed_get::<Range>()
+            .and_then(|range| range.start())
+        {
+            Some(NPTTime::Time(start)) => start,
+            _ => Duration::from_secs(0),
+        };
+
+        if let Some(rtp_info) = response.headers().typed_get::<RTPInfo>() {
+            for stream in &mut self.streams {
+                let timeline = rtp_info
+                    .iter()
+                    .find(|info| info.uri() == &stream.uri)
+                    .and_then(|info| info.ssrc_infos().first())
+                    .and_then(|info| Timeline::from_rtp_info(stream.clock_rate, info, start));
+
+                if let (Some(reassembler), Some(timeline)) = (stream.reassembler.as_mut(), timeline)
+                {
+                    reassembler.set_timeline(timeline);
+                }
+            }
+        }
+
+        Ok(response)
+    }
+
+    /// Sends the given request, waiting for its response up to the timeout.
+    ///
+    /// Running out of time results in [`OperationError::RequestTimedOut`].
+    pub fn send_request<R, B>(&mut self, request: R) -> Result<Response<BytesMut>, OperationError>
+    where
+        R: Into<Request<B>>,
+        B: AsRef<[u8]>,
+    {
+        let options = RequestOptions::builder()
+            .max_timeout_duration(self.timeout)
+            .timeout_duration(self.timeout)
+            .build();
+        // The request is converted first, so that the future sent does not borrow its body.
+        let request = request.into().map(|body| BytesMut::from(body.as_ref()));
+        let response = self.client.send_request_with_options(request, options);
+
+        self.runtime.block_on(response)
+    }
+
+    /// Returns the address of the server the client is connected to.
+    pub fn server_address(&self) -> &SocketAddr {
+        self.client.server_address()
+    }
+
+    /// Returns the identifier of the session set up, if any.
+    pub fn session(&self) -> Option<&SessionID> {
+        self.session.as_ref()
+    }
+
+    /// Sets the duration to wait for the response to a request, or for media, or
+    /// [`Option::None`] to wait indefinitely.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) -> &mut Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets up the given stream of the presentation described last, to be received interleaved
+    /// on the connection.
+    ///
+    /// The first stream set up creates the session, which the following streams are added to.
+    pub fn setup(&mut self, media: &MediaDescription) -> Result<Response<BytesMut>, ClientError> {
+        let uri = control_uri(self.base.as_deref(), media.control())?;
+        let channel = self.streams.len() * 2;
+        let transport = format!(
+            "RTP/AVP/TCP;unicast;interleaved={}-{}",
+            channel,
+            channel + 1
+        );
+        let mut builder = Request::<()>::builder()
+            .with_method(Method::Setup)
+            .with_uri(uri.clone())
+            .with_header(
+                HeaderName::Transport,
+                HeaderValue::try_from(transport.as_str()).unwrap(),
+            );
+
+        if let Some(session) = self.session_header() {
+            builder = builder.with_typed_header(session);
+        }
+
+        let response =
+            self.send_successful_request(builder.with_body(BytesMut::new()).build().unwrap())?;
+
+        if let Some(session) = response.headers().typed_get::<Session>() {
+            self.session = Some(session.id().clone());
+        }
+
+        let reassembler = depacketizer::from_format(media.format(), media.format_parameters())
+            .ok()
+            .map(|depacketizer| Reassembler::new(depacketizer, JitterBufferConfig::default()));
+        self.streams.push(SetupStream {
+            clock_rate: media.format().clock_rate(),
+            reassembler,
+            uri,
+        });
+
+        Ok(response)
+    }
+
+    /// Tears down the session set up for the presentation with the given URI.
+    pub fn teardown(&mut self, uri: &URI) -> Result<Response<BytesMut>, ClientError> {
+        let request = self.session_request(Method::Teardown, uri)?;
+        let response = self.send_successful_request(request)?;
+
+        self.session = None;
+        self.streams.clear();
+        Ok(response)
+    }
+
+    /// Returns the duration to wait for the response to a request, or for media, if any.
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    /// Sets the duration to wait for the response to a request, or for media.
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.set_timeout(timeout);
+        self
+    }
+
+    /// Sends the given request, failing unless it gets a successful response.
+    fn send_successful_request(
+        &mut self,
+        request: Request<BytesMut>,
+    ) -> Result<Response<BytesMut>, ClientError> {
+        let response = self.send_request(request)?;
+
+        if response.status_code().is_success() {
+            Ok(response)
+        } else {
+            Err(ClientError::Status(response.status_code()))
+        }
+    }
+
+    /// Returns the `"Session"` header to send in requests for the session set up, if any.
+    fn session_header(&self) -> Option<Session> {
+        self.session.as_ref().map(|id| {
+            Session::without_timeout(id.as_str())
+                .expect("received session identifiers should be valid")
+        })
+    }
+
+    /// Returns a request with the given method for the presentation with the given URI, in the
+    /// session set up.
+    fn session_request(&self, method: Method, uri: &URI) -> Result<Request<BytesMut>, ClientError> {
+        let session = self.session_header().ok_or(ClientError::NoSession)?;
+
+        Ok(Request::<()>::builder()
+            .with_method(method)
+            .with_uri(uri.clone())
+            .with_typed_header(session)
+            .with_body(BytesMut::new())
+            .build()
+            .unwrap())
+    }
+}
+
+/// An error type for when an operation of a blocking [`Client`] failed.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum ClientError {
+    /// The control of a stream could not be resolved to a URI.
+    InvalidControl,
+
+    /// The description of the presentation could not be parsed.
+    InvalidDescription,
+
+    /// No media was received for the timeout while iterating frames.
+    MediaTimedOut,
+
+    /// A request needing a session was made before one was set up.
+    NoSession,
+
+    /// The request failed on the connection, such as by timing out.
+    Operation(OperationError),
+
+    /// The server responded with an unsuccessful status code.
+    Status(StatusCode),
+}
+
+impl Display for ClientError {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        use self::ClientError::*;
+
+        match self {
+            InvalidControl => write!(formatter, "invalid stream control"),
+            InvalidDescription => write!(formatter, "invalid session description"),
+            MediaTimedOut => write!(formatter, "timed out waiting for media"),
+            NoSession => write!(formatter, "no session set up"),
+            Operation(error) => write!(formatter, "request failed: {}", error),
+            Status(status_code) => write!(formatter, "server responded with {}", status_code),
+        }
+    }
+}
+
+impl Error for ClientError {}
+
+impl From<OperationError> for ClientError {
+    fn from(value: OperationError) -> Self {
+        ClientError::Operation(value)
+    }
+}
+
+/// An iterator over the frames received by a blocking [`Client`], see [`Client::frames`].
+pub struct Frames<'client> {
+    /// The client receiving the frames.
+    client: &'client mut Client,
+
+    /// When interleaved data was last received.
+    last_received: Instant,
+}
+
+impl Frames<'_> {
+    /// Returns the next frame that can be released at the given instant, with the index of its
+    /// stream.
+    fn poll(&mut self, now: Instant) -> Option<(usize, Frame)> {
+        for (index, stream) in self.client.streams.iter_mut().enumerate() {
+            if let Some(reassembler) = stream.reassembler.as_mut() {
+                loop {
+                    match reassembler.poll(now) {
+                        Ok(Some(frame)) => return Some((index, frame)),
+                        Ok(None) => break,
+                        // The packet that could not be depacketized is dropped.
+                        Err(_) => continue,
+                    }
+                }
+            }
+        }
+
+        None
+    }
+}
+
+impl Iterator for Frames<'_> {
+    type Item = Result<(usize, Frame), ClientError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let now = Instant::now();
+
+            if let Some(frame) = self.poll(now) {
+                return Some(Ok(frame));
+            }
+
+            let deadline = self
+                .client
+                .streams
+                .iter()
+                .filter_map(|stream| stream.reassembler.as_ref()?.next_deadline())
+                .min();
+            let timeout = self
+                .client
+                .timeout
+                .map(|timeout| self.last_received + timeout);
+            let wake = match (deadline, timeout) {
+                (Some(deadline), Some(timeout)) => Some(deadline.min(timeout)),
+                (deadline, timeout) => deadline.or(timeout),
+            };
+            let received = match wake {
+                Some(wake) => self
+                    .client
+                    .rx_interleaved_data
+                    .recv_timeout(wake.saturating_duration_since(now)),
+                None => self
+                    .client
+                    .rx_interleaved_data
+                    .recv()
+                    .map_err(|_| RecvTimeoutError::Disconnected),
+            };
+
+            match received {
+                Ok(data) => {
+                    self.last_received = Instant::now();
+
+                    // Odd channels carry RTCP, which is not needed for reassembling frames.
+                    if !data.channel().is_multiple_of(2) {
+                        continue;
+                    }
+
+                    let stream = self.client.streams.get_mut(usize::from(data.channel() / 2));
+                    let reassembler = stream.and_then(|stream| stream.reassembler.as_mut());
+
+                    if let (Some(reassembler), Ok(packet)) =
+                        (reassembler, Packet::decode(data.into_payload()))
+                    {
+                        reassembler.push(packet, self.last_received);
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    if timeout.is_some_and(|timeout| Instant::now() >= timeout) {
+                        self.last_received = Instant::now();
+                        return Some(Err(ClientError::MediaTimedOut));
+                    }
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    // The packets still buffered are released before iteration ends.
+                    return self.poll(Instant::now() + FLUSH_HORIZON).map(Ok);
+                }
+            }
+        }
+    }
+}
+
+/// A stream set up by a blocking [`Client`].
+struct SetupStream {
+    /// The clock rate of the RTP timestamps of the stream.
+    clock_rate: u32,
+
+    /// The reassembler of the frames of the stream, if its payload format can be depacketized.
+    reassembler: Option<Reassembler>,
+
+    /// The URI of the stream.
+    uri: URI,
+}
+
+/// Returns the URI of a stream given the base URI of the presentation, if any, and the stream
+/// control.
+fn control_uri(base: Option<&str>, control: &str) -> Result<URI, ClientError> {
+    if let Ok(uri) = URI::try_from(control) {
+        return Ok(uri);
+    }
+
+    let base = base
+        .ok_or(ClientError::InvalidControl)?
+        .trim_end_matches('/');
+
+    if control.is_empty() || control == "*" {
+        return URI::try_from(base).map_err(|_| ClientError::InvalidControl);
+    }
+
+    let uri = format!("{}/{}", base, control);
+    URI::try_from(uri.as_str()).map_err(|_| ClientError::InvalidControl)
+}
+
+#[cfg(test)]
+mod test {
+    use std::thread;
+
+    use super::*;
+    use crate::testing::mock::{Expectation, MockServer};
+
+    const SDP: &str = "v=0\r\n\
+                       o=- 1 1 IN IP4 127.0.0.1\r\n\
+                       s=Camera\r\n\
+                       t=0 0\r\n\
+                       m=video 0 RTP/AVP 96\r\n\
+                       a=rtpmap:96 H264/90000\r\n\
+                       a=control:trackID=1\r\n";
+    const SESSION: &str = "12345678";
+
+    fn response(headers: &[(HeaderName, &str)], body: &str) -> Response<BytesMut> {
+        let mut builder = Response::<()>::builder().with_status_code(StatusCode::OK);
+
+        for (name, value) in headers {
+            builder = builder.with_header(name.clone(), HeaderValue::try_from(*value).unwrap());
+        }
+
+        builder
+            .with_body(BytesMut::from(body.as_bytes()))
+            .build()
+            .unwrap()
+    }
+
+    fn session_expectation(method: Method) -> Expectation {
+        Expectation::new(method)
+            .with_header(HeaderName::Session, HeaderValue::try_from(SESSION).unwrap())
+    }
+
+    #[test]
+    fn test_blocking_client() {
+        let mut packet = BytesMut::new();
+        Packet::new(96, 0, 3000, 0, true, vec![0x65, 0xAA]).encode(&mut packet);
+
+        let (address, handle) = MockServer::new()
+            .with_expectation(Expectation::new(Method::Describe).with_response(response(
+                &[(HeaderName::ContentBase, "rtsp://127.0.0.1/camera/")],
+                SDP,
+            )))
+            .with_expectation(
+                Expectation::new(Method::Setup)
+                    .with_uri(URI::try_from("rtsp://127.0.0.1/camera/trackID=1").unwrap())
+                    .with_response(response(&[(HeaderName::Session, SESSION)], "")),
+            )
+            .with_expectation(session_expectation(Method::Play))
+            .with_interleaved_data(InterleavedData::new(0, packet.freeze()))
+            .with_expectation(session_expectation(Method::Teardown))
+            .listen()
+            .unwrap();
+        let uri = URI::try_from("rtsp://127.0.0.1/camera").unwrap();
+
+        let mut client = Client::connect(address).unwrap();
+        assert_eq!(client.session(), None);
+        assert_eq!(client.play(&uri).unwrap_err(), ClientError::NoSession);
+
+        let description = client.describe(&uri).unwrap();
+        client.setup(&description.media()[0]).unwrap();
+        assert_eq!(client.session().unwrap().as_str(), SESSION);
+        client.play(&uri).unwrap();
+
+        let (index, frame) = client.frames().next().unwrap().unwrap();
+        assert_eq!(index, 0);
+        assert_eq!(&frame.data()[..], &[0, 0, 0, 1, 0x65, 0xAA][..]);
+        assert_eq!(frame.timestamp(), 3000);
+
+        // Without media, iterating times out.
+        client.set_timeout(Some(Duration::from_millis(100)));
+        assert_eq!(
+            client.frames().next(),
+            Some(Err(ClientError::MediaTimedOut))
+        );
+
+        client.set_timeout(Some(DEFAULT_TIMEOUT));
+        client.teardown(&uri).unwrap();
+        assert_eq!(client.session(), None);
+
+        // The mock server marks expectations as met before responding to them.
+        assert!(handle.verify().is_ok());
+    }
+
+    #[test]
+    fn test_blocking_client_frames_end_when_connection_closes() {
+        let mut packet = BytesMut::new();
+        Packet::new(96, 0, 3000, 0, true, vec![0x65, 0xAA]).encode(&mut packet);
+
+        let (address, _handle) = MockServer::new()
+            .with_expectation(Expectation::new(Method::Describe).with_response(response(
+                &[(HeaderName::ContentBase, "rtsp://127.0.0.1/camera/")],
+                SDP,
+            )))
+            .with_expectation(
+                Expectation::new(Method::Setup)
+                    .with_response(response(&[(HeaderName::Session, SESSION)], "")),
+            )
+            .with_expectation(session_expectation(Method::Play))
+            .with_interleaved_data(InterleavedData::new(0, packet.freeze()))
+            .with_close()
+            .listen()
+            .unwrap();
+        let uri = URI::try_from("rtsp://127.0.0.1/camera").unwrap();
+
+        let mut client = Client::connect(address).unwrap().with_timeout(None);
+        let description = client.describe(&uri).unwrap();
+        client.setup(&description.media()[0]).unwrap();
+        client.play(&uri).unwrap();
+
+        // Without a timeout, iterating would block forever if the closed connection went unseen.
+        let (tx_frames, rx_frames) = mpsc::channel();
+        thread::spawn(move || {
+            let frames = client.frames().collect::<Vec<_>>();
+            tx_frames.send(frames).unwrap();
+        });
+
+        let frames = rx_frames.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(frames.len(), 1);
+
+        let (index, frame) = frames[0].as_ref().unwrap();
+        assert_eq!(*index, 0);
+        assert_eq!(frame.timestamp(), 3000);
+    }
+}
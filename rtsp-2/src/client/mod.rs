@@ -0,0 +1,960 @@
+pub mod blocking;
+pub mod pool;
+pub mod reconnect;
+
+use std::{
+    collections::HashMap,
+    io,
+    net::SocketAddr,
+    sync::{Arc, Mutex, Weak},
+    time::{Duration, Instant},
+};
+
+use bytes::BytesMut;
+use futures::{
+    future::{self, Either, Future},
+    sync::mpsc::UnboundedReceiver,
+    task::{self, Task},
+    Async, Poll,
+};
+use tokio_executor::{DefaultExecutor, Executor};
+use tokio_io::{AsyncRead, AsyncWrite};
+use tokio_tcp::TcpStream;
+use tokio_timer::Delay;
+
+use crate::{
+    header::{
+        map::HeaderMapExtension,
+        types::{
+            transport::{Transport, TransportSpec},
+            PipelinedRequests, Public, Session,
+        },
+    },
+    method::Method,
+    protocol::{
+        codec::interleaved::InterleavedData,
+        connection::{
+            Connection, ConnectionHandle, ConnectionShutdownReceiver, OperationError,
+            RequestOptions,
+        },
+        service::EmptyService,
+        tunnel, websocket,
+    },
+    request::Request,
+    response::Response,
+    session::{SessionID, DEFAULT_SESSION_TIMEOUT},
+    status::StatusCode,
+    uri::request::URI,
+};
+
+/// Sessions are kept alive once they have been idle for their timeout divided by this, leaving
+/// the keepalive enough time to reach the server before the session expires.
+const KEEPALIVE_TIMEOUT_DIVISOR: u32 = 2;
+
+/// A client connected to an RTSP server.
+///
+/// Sessions that the server sets up through the client are kept alive automatically until they
+/// are torn down or the connection closes. Once a session has been idle for half of the timeout
+/// given in its `"Session"` header, or for the interval set with
+/// [`Client::set_keepalive_interval`], the client sends a `GET_PARAMETER` request if the server
+/// listed it in its latest `"Public"` header, or an `OPTIONS` request otherwise. Requests carrying
+/// the session count as activity, as does RTCP if the server is known to count it as liveness.
+#[derive(Clone)]
+pub struct Client {
+    handle: ConnectionHandle,
+
+    /// The sessions being kept alive, shared by all clones of the client.
+    keepalive: Arc<Mutex<Keepalive>>,
+
+    server_address: SocketAddr,
+}
+
+impl Client {
+    pub fn connect(server_address: SocketAddr) -> impl Future<Item = Client, Error = io::Error> {
+        TcpStream::connect(&server_address)
+            .map(move |tcp_stream| Client::spawn(tcp_stream, server_address))
+    }
+
+    /// Connects to the server with the given address, tunneling RTSP over HTTP through the given
+    /// path, such as `"/live"`.
+    ///
+    /// This reaches servers through proxies and firewalls only letting HTTP through, provided the
+    /// server allows tunneling on the address. See [`tunnel`](crate::protocol::tunnel).
+    pub fn connect_tunneled(
+        server_address: SocketAddr,
+        path: &str,
+    ) -> impl Future<Item = Client, Error = io::Error> {
+        let host = server_address.to_string();
+        let path = path.to_string();
+
+        TcpStream::connect(&server_address)
+            .join(TcpStream::connect(&server_address))
+            .and_then(move |(get, post)| tunnel::connect(get, post, &host, &path))
+            .map(move |tunnel| Client::spawn(tunnel, server_address))
+    }
+
+    /// Connects to the server with the given address over WebSocket, requesting the given path,
+    /// such as `"/live"`.
+    ///
+    /// The server must allow WebSocket on the listener at the address. See
+    /// [`websocket`](crate::protocol::websocket).
+    pub fn connect_websocket(
+        server_address: SocketAddr,
+        path: &str,
+    ) -> impl Future<Item = Client, Error = io::Error> {
+        let host = server_address.to_string();
+        let path = path.to_string();
+
+        TcpStream::connect(&server_address)
+            .and_then(move |tcp_stream| websocket::connect(tcp_stream, &host, &path))
+            .map(move |socket| Client::spawn(socket, server_address))
+    }
+
+    /// Returns a stream of the interleaved data, such as RTP packets, that the server sends on
+    /// this connection.
+    ///
+    /// Only the most recently returned stream receives data.
+    pub fn interleaved_data(&mut self) -> UnboundedReceiver<InterleavedData> {
+        self.handle.interleaved_data()
+    }
+
+    /// Returns whether the server counts RTCP packets received for a session as activity keeping
+    /// it alive.
+    ///
+    /// Servers are not required to, and RTSP gives them no way to tell whether they do, so this is
+    /// `false` by default and must be set by the caller.
+    pub fn is_rtcp_liveness_supported(&self) -> bool {
+        self.keepalive.lock().unwrap().rtcp_liveness_supported
+    }
+
+    /// Returns how long sessions may be idle before they are kept alive, if it overrides half of
+    /// their timeout.
+    pub fn keepalive_interval(&self) -> Option<Duration> {
+        self.keepalive.lock().unwrap().interval
+    }
+
+    /// Records that RTCP packets were sent to the server outside of this connection, such as over
+    /// UDP, for the sessions of this client.
+    ///
+    /// If the server counts RTCP as liveness, this postpones the keepalives of the sessions whose
+    /// `SETUP` responses negotiated a transport carrying RTCP.
+    pub fn record_rtcp(&self) {
+        self.keepalive.lock().unwrap().record_rtcp();
+    }
+
+    /// Sends the given interleaved data, such as RTCP packets, to the server on this connection.
+    ///
+    /// Data sent on odd channels is RTCP, which is recorded as with [`Client::record_rtcp`].
+    pub fn send_interleaved_data(&self, data: InterleavedData) -> Result<(), OperationError> {
+        let is_rtcp = !data.channel().is_multiple_of(2);
+        self.handle.send_interleaved_data(data)?;
+
+        if is_rtcp {
+            self.record_rtcp();
+        }
+
+        Ok(())
+    }
+
+    pub fn server_address(&self) -> &SocketAddr {
+        &self.server_address
+    }
+
+    /// Runs a connection over the given transport to the server with the given address.
+    fn spawn<TTransport>(transport: TTransport, server_address: SocketAddr) -> Client
+    where
+        TTransport: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        let mut executor = DefaultExecutor::current();
+        let (connection, handler, handle) = Connection::new::<EmptyService>(transport, None);
+
+        executor.spawn(Box::new(connection)).unwrap();
+
+        if let Some(handler) = handler {
+            executor.spawn(Box::new(handler)).unwrap();
+        }
+
+        Client {
+            keepalive: Arc::new(Mutex::new(Keepalive::new(handle.clone()))),
+            handle,
+            server_address,
+        }
+    }
+
+    /// Sends the given requests as one pipeline, without waiting for the response to one request
+    /// before sending the next, resolving to their responses in order.
+    ///
+    /// The requests are given the same `"Pipelined-Requests"` header, so that the server executes
+    /// those sent before the session exists in the session created by the first one. This saves a
+    /// round trip per request when setting up and playing a presentation, such as with a `SETUP`
+    /// request for every stream followed by a `PLAY` request, none of which carry a session.
+    pub fn send_pipeline<I, R, B>(
+        &mut self,
+        requests: I,
+    ) -> impl Future<Item = Vec<Response<BytesMut>>, Error = OperationError>
+    where
+        I: IntoIterator<Item = R>,
+        R: Into<Request<B>>,
+        B: AsRef<[u8]>,
+    {
+        let pipeline = PipelinedRequests::random();
+        let responses = requests
+            .into_iter()
+            .map(|request| {
+                let mut request = request.into();
+                request.headers_mut().typed_insert(pipeline);
+                self.send_request(request)
+            })
+            .collect::<Vec<_>>();
+
+        future::join_all(responses)
+    }
+
+    pub fn send_request<R, B>(
+        &mut self,
+        request: R,
+    ) -> impl Future<Item = Response<BytesMut>, Error = OperationError>
+    where
+        R: Into<Request<B>>,
+        B: AsRef<[u8]>,
+    {
+        self.send_request_with_optional_options(request.into(), None)
+    }
+
+    /// Sends the given request with the given options, such as a timeout other than the default
+    /// one of the connection.
+    pub fn send_request_with_options<R, B>(
+        &mut self,
+        request: R,
+        options: RequestOptions,
+    ) -> impl Future<Item = Response<BytesMut>, Error = OperationError>
+    where
+        R: Into<Request<B>>,
+        B: AsRef<[u8]>,
+    {
+        self.send_request_with_optional_options(request.into(), Some(options))
+    }
+
+    /// Sends the given request with the given options, or the default ones of the connection,
+    /// recording it and its response for the sessions being kept alive.
+    fn send_request_with_optional_options<B>(
+        &mut self,
+        request: Request<B>,
+        options: Option<RequestOptions>,
+    ) -> impl Future<Item = Response<BytesMut>, Error = OperationError>
+    where
+        B: AsRef<[u8]>,
+    {
+        let method = request.method().clone();
+        let uri = request.uri().clone();
+        let id = request
+            .headers()
+            .typed_get::<Session>()
+            .map(|session| session.id().clone());
+
+        if let Some(id) = id.as_ref() {
+            self.keepalive.lock().unwrap().record_request(id, &uri);
+        }
+
+        let keepalive = self.keepalive.clone();
+
+        let response = match options {
+            Some(options) => Either::A(self.handle.send_request_with_options(request, options)),
+            None => Either::B(self.handle.send_request(request)),
+        };
+
+        response.then(move |result| {
+            Keepalive::record_response(&keepalive, &method, uri, id, &result);
+            result
+        })
+    }
+
+    /// Sets how long sessions may be idle before they are kept alive, overriding half of their
+    /// timeout, or [`Option::None`] to use half of their timeout.
+    ///
+    /// This applies to all clones of this client, and to the sessions already being kept alive.
+    pub fn set_keepalive_interval(&mut self, interval: Option<Duration>) -> &mut Self {
+        self.keepalive.lock().unwrap().set_interval(interval);
+        self
+    }
+
+    /// Sets whether the server counts RTCP packets received for a session as activity keeping it
+    /// alive, in which case sending RTCP postpones keepalives.
+    ///
+    /// RTSP gives servers no way to tell whether they do, so this is never derived from their
+    /// responses. Even when set, RTCP only postpones the keepalives of sessions whose `SETUP`
+    /// responses negotiated an RTP transport with RTCP, that is, one not limited to a single
+    /// interleaved channel or server port unless it multiplexes RTCP with RTP.
+    ///
+    /// This applies to all clones of this client.
+    pub fn set_rtcp_liveness_supported(&mut self, supported: bool) -> &mut Self {
+        self.keepalive.lock().unwrap().rtcp_liveness_supported = supported;
+        self
+    }
+
+    /// Returns a future finishing once the connection to the server has been shut down, such as
+    /// when the server closes it.
+    pub fn shutdown_receiver(&mut self) -> ConnectionShutdownReceiver {
+        self.handle.shutdown_receiver()
+    }
+
+    /// Sets how long sessions may be idle before they are kept alive, overriding half of their
+    /// timeout.
+    pub fn with_keepalive_interval(mut self, interval: Option<Duration>) -> Self {
+        self.set_keepalive_interval(interval);
+        self
+    }
+
+    /// Sets whether the server counts RTCP packets received for a session as activity keeping it
+    /// alive.
+    pub fn with_rtcp_liveness_supported(mut self, supported: bool) -> Self {
+        self.set_rtcp_liveness_supported(supported);
+        self
+    }
+}
+
+/// The sessions of a client being kept alive.
+struct Keepalive {
+    /// The handle used to send keepalives.
+    handle: ConnectionHandle,
+
+    /// How long sessions may be idle before they are kept alive, if it overrides half of their
+    /// timeout.
+    interval: Option<Duration>,
+
+    /// The methods listed in the latest `"Public"` header received from the server, if any.
+    methods: Option<Public>,
+
+    /// Whether the server counts RTCP as activity.
+    rtcp_liveness_supported: bool,
+
+    /// Whether a [`KeepaliveTask`] is running.
+    running: bool,
+
+    /// The sessions being kept alive.
+    sessions: HashMap<SessionID, KeptSession>,
+
+    /// The running task, to be notified when a keepalive may be due earlier than it expects.
+    task: Option<Task>,
+}
+
+impl Keepalive {
+    /// Constructs a new state keeping no sessions alive.
+    fn new(handle: ConnectionHandle) -> Self {
+        Keepalive {
+            handle,
+            interval: None,
+            methods: None,
+            rtcp_liveness_supported: false,
+            running: false,
+            sessions: HashMap::new(),
+            task: None,
+        }
+    }
+
+    /// Notifies the running task, if any, that the keepalives are to be rescheduled.
+    fn notify(&mut self) {
+        if let Some(task) = self.task.as_ref() {
+            task.notify();
+        }
+    }
+
+    /// Records the response to a keepalive sent with the given method for the given session.
+    fn record_keepalive_response(
+        &mut self,
+        method: &Method,
+        id: &SessionID,
+        result: &Result<Response<BytesMut>, OperationError>,
+    ) {
+        let status_code = match result {
+            Ok(response) => response.status_code(),
+            Err(OperationError::Closed) | Err(OperationError::RequestCancelled) => {
+                self.sessions.clear();
+                self.notify();
+                return;
+            }
+            Err(_) => return,
+        };
+
+        if status_code == StatusCode::SessionNotFound {
+            self.sessions.remove(id);
+        } else if *method == Method::GetParameter
+            && (status_code == StatusCode::MethodNotAllowed
+                || status_code == StatusCode::NotImplemented)
+        {
+            // The server listed `GET_PARAMETER` but does not accept it for keepalives, so the
+            // session is kept alive with `OPTIONS` right away instead.
+            if let Some(methods) = self.methods.as_mut() {
+                methods.remove(&Method::GetParameter);
+            }
+
+            if let Some(session) = self.sessions.get_mut(id) {
+                session.next_keepalive = Instant::now();
+            }
+        }
+
+        self.notify();
+    }
+
+    /// Records that the given request was sent for the given session, which counts as activity.
+    fn record_request(&mut self, id: &SessionID, uri: &URI) {
+        if let Some(session) = self.sessions.get_mut(id) {
+            session.record_activity();
+
+            if !uri.is_asterisk() {
+                session.uri = uri.clone();
+            }
+        }
+    }
+
+    /// Records the result of a request sent with the given method to the given URI, and for the
+    /// given session, if any.
+    ///
+    /// Sessions given by successful responses are kept alive from then on, until a response says
+    /// they no longer exist.
+    fn record_response(
+        state: &Arc<Mutex<Keepalive>>,
+        method: &Method,
+        uri: URI,
+        id: Option<SessionID>,
+        result: &Result<Response<BytesMut>, OperationError>,
+    ) {
+        let mut keepalive = state.lock().unwrap();
+        let response = match result {
+            Ok(response) => response,
+            Err(OperationError::Closed) | Err(OperationError::RequestCancelled) => {
+                keepalive.sessions.clear();
+                keepalive.notify();
+                return;
+            }
+            Err(_) => return,
+        };
+
+        if let Some(methods) = response.headers().typed_get::<Public>() {
+            keepalive.methods = Some(methods);
+        }
+
+        let session = response.headers().typed_get::<Session>();
+        let status_code = response.status_code();
+
+        if status_code == StatusCode::SessionNotFound
+            || (*method == Method::Teardown && status_code.is_success() && session.is_none())
+        {
+            if let Some(id) = id {
+                keepalive.sessions.remove(&id);
+            }
+
+            return;
+        }
+
+        let session = match session {
+            Some(session) if status_code.is_success() => session,
+            _ => return,
+        };
+        let timeout = session.timeout().unwrap_or(DEFAULT_SESSION_TIMEOUT);
+        let interval = keepalive
+            .interval
+            .unwrap_or(timeout / KEEPALIVE_TIMEOUT_DIVISOR);
+        let kept_session = keepalive
+            .sessions
+            .entry(session.id().clone())
+            .or_insert_with(|| KeptSession::new(interval, timeout, uri));
+        kept_session.interval = interval;
+        kept_session.timeout = timeout;
+        kept_session.record_activity();
+
+        if *method == Method::Setup {
+            kept_session.carries_rtcp |= response
+                .headers()
+                .typed_get::<Transport>()
+                .is_some_and(|transport| transport.iter().any(carries_rtcp));
+        }
+
+        if keepalive.running {
+            keepalive.notify();
+        } else {
+            let task = KeepaliveTask::new(Arc::downgrade(state));
+
+            // Without an executor, the next response starts the keepalives instead.
+            keepalive.running = DefaultExecutor::current().spawn(Box::new(task)).is_ok();
+        }
+    }
+
+    /// Records that RTCP was sent for the sessions, postponing the keepalives of those carrying
+    /// RTCP if the server counts RTCP as activity.
+    fn record_rtcp(&mut self) {
+        if self.rtcp_liveness_supported {
+            self.sessions
+                .values_mut()
+                .filter(|session| session.carries_rtcp)
+                .for_each(KeptSession::record_activity);
+        }
+    }
+
+    /// Sends the keepalives that are due, returning when the next one is due, if any session is
+    /// left.
+    fn send_keepalives(&mut self, state: &Weak<Mutex<Keepalive>>) -> Option<Instant> {
+        let now = Instant::now();
+        let due = self
+            .sessions
+            .iter_mut()
+            .filter(|(_, session)| session.next_keepalive <= now)
+            .map(|(id, session)| {
+                session.record_activity();
+                (id.clone(), session.uri.clone())
+            })
+            .collect::<Vec<_>>();
+
+        for (id, uri) in due {
+            let method = if self
+                .methods
+                .as_ref()
+                .is_some_and(|methods| methods.contains(&Method::GetParameter))
+            {
+                Method::GetParameter
+            } else {
+                Method::Options
+            };
+            let request = Request::<()>::builder()
+                .with_method(method.clone())
+                .with_uri(uri)
+                .with_typed_header(
+                    Session::without_timeout(id.as_str())
+                        .expect("received session identifiers should be valid"),
+                )
+                .with_body(BytesMut::new())
+                .build()
+                .unwrap();
+            let state = state.clone();
+
+            tokio::spawn(self.handle.send_request(request).then(move |result| {
+                if let Some(state) = state.upgrade() {
+                    state
+                        .lock()
+                        .unwrap()
+                        .record_keepalive_response(&method, &id, &result);
+                }
+
+                Ok(())
+            }));
+        }
+
+        self.sessions
+            .values()
+            .map(|session| session.next_keepalive)
+            .min()
+    }
+
+    /// Sets how long sessions may be idle before they are kept alive, rescheduling the keepalives
+    /// of the sessions.
+    fn set_interval(&mut self, interval: Option<Duration>) {
+        self.interval = interval;
+
+        for session in self.sessions.values_mut() {
+            session.interval = interval.unwrap_or(session.timeout / KEEPALIVE_TIMEOUT_DIVISOR);
+            session.record_activity();
+        }
+
+        self.notify();
+    }
+}
+
+impl Drop for Keepalive {
+    fn drop(&mut self) {
+        // The running task stops once it notices that the client is gone.
+        self.notify();
+    }
+}
+
+/// A future sending keepalives for the sessions of a client, until none are left or the client
+/// is dropped.
+#[must_use = "futures do nothing unless polled"]
+struct KeepaliveTask {
+    /// The delay until the next keepalive is due.
+    delay: Delay,
+
+    /// The sessions being kept alive.
+    state: Weak<Mutex<Keepalive>>,
+}
+
+impl KeepaliveTask {
+    /// Constructs a new task keeping alive the sessions in the given state.
+    fn new(state: Weak<Mutex<Keepalive>>) -> Self {
+        KeepaliveTask {
+            delay: Delay::new(Instant::now()),
+            state,
+        }
+    }
+}
+
+impl Future for KeepaliveTask {
+    type Item = ();
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        loop {
+            let state = match self.state.upgrade() {
+                Some(state) => state,
+                None => return Ok(Async::Ready(())),
+            };
+            let mut keepalive = state.lock().unwrap();
+
+            match keepalive.send_keepalives(&self.state) {
+                Some(next_keepalive) => self.delay.reset(next_keepalive),
+                None => {
+                    keepalive.running = false;
+                    keepalive.task = None;
+                    return Ok(Async::Ready(()));
+                }
+            }
+
+            match self.delay.poll() {
+                Ok(Async::Ready(())) => (),
+                Ok(Async::NotReady) => {
+                    keepalive.task = Some(task::current());
+                    return Ok(Async::NotReady);
+                }
+                Err(_) => {
+                    keepalive.running = false;
+                    keepalive.task = None;
+                    return Err(());
+                }
+            }
+        }
+    }
+}
+
+/// A session being kept alive.
+struct KeptSession {
+    /// Whether a transport carrying RTCP was negotiated for the session.
+    carries_rtcp: bool,
+
+    /// How long the session may be idle before it is kept alive.
+    interval: Duration,
+
+    /// When the next keepalive is due, unless there is activity on the session before.
+    next_keepalive: Instant,
+
+    /// The timeout of the session, after which the server expires it without activity.
+    timeout: Duration,
+
+    /// The URI of the latest request sent for the session, which keepalives are sent to.
+    uri: URI,
+}
+
+impl KeptSession {
+    /// Constructs a new session with the given keepalive interval and timeout, kept alive through
+    /// the given URI.
+    fn new(interval: Duration, timeout: Duration, uri: URI) -> Self {
+        KeptSession {
+            carries_rtcp: false,
+            interval,
+            next_keepalive: Instant::now() + interval,
+            timeout,
+            uri,
+        }
+    }
+
+    /// Records activity on the session, postponing its next keepalive.
+    fn record_activity(&mut self) {
+        self.next_keepalive = Instant::now() + self.interval;
+    }
+}
+
+/// Returns whether the given negotiated transport carries RTCP, that is, whether it is RTP and
+/// RTCP is either multiplexed with RTP or given its own interleaved channel or server port.
+fn carries_rtcp(transport: &TransportSpec) -> bool {
+    if !transport.protocol().starts_with("RTP/") {
+        return false;
+    }
+
+    if transport.contains("RTCP-mux") {
+        return true;
+    }
+
+    let single_channel = transport
+        .interleaved()
+        .is_some_and(|channels| channels.start() == channels.end());
+    let single_port = transport
+        .server_port()
+        .is_some_and(|ports| ports.start() == ports.end());
+    !single_channel && !single_port
+}
+
+#[cfg(test)]
+mod test {
+    use std::{convert::TryFrom, thread};
+
+    use tokio::runtime::Runtime;
+
+    use super::*;
+    use crate::{
+        header::{name::HeaderName, value::HeaderValue},
+        testing::mock::{Expectation, MockError, MockServer, MockServerHandle},
+    };
+
+    /// The interval at which sessions are kept alive, short enough for the tests to be quick while
+    /// leaving the tests plenty of time to act between two keepalives.
+    const KEEPALIVE_INTERVAL: Duration = Duration::from_millis(200);
+
+    const SESSION: &str = "12345678";
+
+    /// A transport carrying RTCP on the second interleaved channel.
+    const TRANSPORT: &str = "RTP/AVP/TCP;unicast;interleaved=0-1";
+
+    fn request(method: Method) -> Request<BytesMut> {
+        let mut builder = Request::<()>::builder()
+            .with_method(method.clone())
+            .with_uri(URI::try_from("rtsp://example.com/stream").unwrap());
+
+        if method != Method::Options && method != Method::Setup {
+            builder =
+                builder.with_header(HeaderName::Session, HeaderValue::try_from(SESSION).unwrap());
+        }
+
+        builder.with_body(BytesMut::new()).build().unwrap()
+    }
+
+    /// Sends the given requests, checking that they succeed.
+    fn send(runtime: &mut Runtime, client: &mut Client, methods: Vec<Method>) {
+        for method in methods {
+            let response = runtime
+                .block_on(client.send_request(request(method)))
+                .unwrap();
+            assert_eq!(response.status_code(), StatusCode::OK);
+        }
+    }
+
+    fn session_expectation(method: Method) -> Expectation {
+        Expectation::new(method)
+            .with_header(HeaderName::Session, HeaderValue::try_from(SESSION).unwrap())
+    }
+
+    fn setup_expectation(transport: &str) -> Expectation {
+        let response = Response::<()>::builder()
+            .with_status_code(StatusCode::OK)
+            .with_header(
+                HeaderName::Session,
+                HeaderValue::try_from("12345678;timeout=60").unwrap(),
+            )
+            .with_header(
+                HeaderName::Transport,
+                HeaderValue::try_from(transport).unwrap(),
+            )
+            .with_body(BytesMut::new())
+            .build()
+            .unwrap();
+
+        Expectation::new(Method::Setup).with_response(response)
+    }
+
+    /// Waits until the mock server has received all but the given number of the requests it
+    /// expects, failing if it receives one it does not expect.
+    fn wait_for_requests(handle: &MockServerHandle, unmet: usize) {
+        let deadline = Instant::now() + Duration::from_secs(10);
+
+        loop {
+            match handle.verify() {
+                Ok(()) if unmet == 0 => return,
+                Err(MockError::UnmetExpectations(count)) if count == unmet => return,
+                Err(MockError::UnmetExpectations(count)) if count > unmet => (),
+                result => panic!("unexpected requests: {:?}", result),
+            }
+
+            assert!(Instant::now() < deadline, "expected requests not received");
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    /// Waits until the server receives the keepalive it expects before the teardown, tears the
+    /// session down, then checks that no keepalive follows.
+    fn teardown_after_keepalive(
+        runtime: &mut Runtime,
+        mut client: Client,
+        handle: MockServerHandle,
+    ) {
+        wait_for_requests(&handle, 1);
+        send(runtime, &mut client, vec![Method::Teardown]);
+
+        thread::sleep(KEEPALIVE_INTERVAL * 2);
+        assert!(handle.verify().is_ok());
+
+        drop(client);
+    }
+
+    #[test]
+    fn test_bounds() {
+        fn check_bounds<T: Send + Send>() {}
+
+        check_bounds::<Client>();
+    }
+
+    #[test]
+    fn test_keepalive_get_parameter() {
+        let response = Response::<()>::builder()
+            .with_status_code(StatusCode::OK)
+            .with_header(
+                HeaderName::Public,
+                HeaderValue::try_from("OPTIONS, SETUP, GET_PARAMETER, TEARDOWN").unwrap(),
+            )
+            .with_body(BytesMut::new())
+            .build()
+            .unwrap();
+        let (address, handle) = MockServer::new()
+            .with_expectation(Expectation::new(Method::Options).with_response(response))
+            .with_expectation(setup_expectation(TRANSPORT))
+            .with_expectation(session_expectation(Method::GetParameter))
+            .with_expectation(session_expectation(Method::Teardown))
+            .listen()
+            .unwrap();
+
+        let mut runtime = Runtime::new().unwrap();
+        let mut client = runtime
+            .block_on(Client::connect(address))
+            .unwrap()
+            .with_keepalive_interval(Some(KEEPALIVE_INTERVAL));
+        send(
+            &mut runtime,
+            &mut client,
+            vec![Method::Options, Method::Setup],
+        );
+        teardown_after_keepalive(&mut runtime, client, handle);
+        runtime.shutdown_on_idle().wait().unwrap();
+    }
+
+    #[test]
+    fn test_keepalive_interval() {
+        let (address, handle) = MockServer::new()
+            .with_expectation(setup_expectation(TRANSPORT))
+            .listen()
+            .unwrap();
+
+        let mut runtime = Runtime::new().unwrap();
+        let mut client = runtime.block_on(Client::connect(address)).unwrap();
+        assert_eq!(client.keepalive_interval(), None);
+        send(&mut runtime, &mut client, vec![Method::Setup]);
+        assert!(handle.verify().is_ok());
+
+        // By default, the session is kept alive after half of its timeout.
+        let next_keepalive = |client: &Client| {
+            client.keepalive.lock().unwrap().sessions[&SessionID::try_from(SESSION).unwrap()]
+                .next_keepalive
+        };
+        assert!(next_keepalive(&client) > Instant::now() + Duration::from_secs(29));
+
+        // Changing the interval reschedules the sessions already kept alive.
+        client.set_keepalive_interval(Some(KEEPALIVE_INTERVAL));
+        assert_eq!(client.keepalive_interval(), Some(KEEPALIVE_INTERVAL));
+        assert!(next_keepalive(&client) <= Instant::now() + KEEPALIVE_INTERVAL);
+
+        drop(client);
+        runtime.shutdown_now().wait().unwrap();
+    }
+
+    #[test]
+    fn test_keepalive_options() {
+        let (address, handle) = MockServer::new()
+            .with_expectation(setup_expectation(TRANSPORT))
+            .with_expectation(session_expectation(Method::Options))
+            .with_expectation(session_expectation(Method::Teardown))
+            .listen()
+            .unwrap();
+
+        let mut runtime = Runtime::new().unwrap();
+        let mut client = runtime
+            .block_on(Client::connect(address))
+            .unwrap()
+            .with_keepalive_interval(Some(KEEPALIVE_INTERVAL));
+        send(&mut runtime, &mut client, vec![Method::Setup]);
+        teardown_after_keepalive(&mut runtime, client, handle);
+        runtime.shutdown_on_idle().wait().unwrap();
+    }
+
+    #[test]
+    fn test_keepalive_rtcp_liveness() {
+        let (address, handle) = MockServer::new()
+            .with_expectation(setup_expectation(TRANSPORT))
+            .with_expectation(session_expectation(Method::Teardown))
+            .listen()
+            .unwrap();
+
+        let mut runtime = Runtime::new().unwrap();
+        let mut client = runtime
+            .block_on(Client::connect(address))
+            .unwrap()
+            .with_keepalive_interval(Some(KEEPALIVE_INTERVAL))
+            .with_rtcp_liveness_supported(true);
+        send(&mut runtime, &mut client, vec![Method::Setup]);
+
+        // Sending RTCP much more often than the interval postpones every keepalive.
+        for _ in 0..30 {
+            client
+                .send_interleaved_data(InterleavedData::new(1, vec![0x80, 0xC9, 0x00, 0x01]))
+                .unwrap();
+            thread::sleep(KEEPALIVE_INTERVAL / 10);
+        }
+
+        assert!(matches!(
+            handle.verify(),
+            Err(MockError::UnmetExpectations(1))
+        ));
+        send(&mut runtime, &mut client, vec![Method::Teardown]);
+        assert!(handle.verify().is_ok());
+
+        drop(client);
+        runtime.shutdown_on_idle().wait().unwrap();
+    }
+
+    #[test]
+    fn test_keepalive_rtcp_liveness_unsupported() {
+        check_rtcp_not_postponing(TRANSPORT, false);
+    }
+
+    #[test]
+    fn test_keepalive_rtcp_liveness_without_rtcp() {
+        check_rtcp_not_postponing("RTP/AVP;unicast;client_port=5000;server_port=6000", true);
+    }
+
+    #[test]
+    fn test_transport_carries_rtcp() {
+        let carries_rtcp = |transport| carries_rtcp(&TransportSpec::try_from(transport).unwrap());
+
+        assert!(carries_rtcp(TRANSPORT));
+        assert!(carries_rtcp("RTP/AVP;unicast;server_port=6000-6001"));
+        assert!(carries_rtcp("RTP/AVP/TCP;unicast;interleaved=0;RTCP-mux"));
+        assert!(!carries_rtcp("RTP/AVP/TCP;unicast;interleaved=0"));
+        assert!(!carries_rtcp("RTP/AVP;unicast;server_port=6000"));
+        assert!(!carries_rtcp("MP2T/H2221/TCP;unicast;interleaved=0-1"));
+    }
+
+    /// Checks that RTCP recorded for a session set up with the given transport does not postpone
+    /// its keepalives, with the given RTCP liveness support.
+    fn check_rtcp_not_postponing(transport: &str, rtcp_liveness_supported: bool) {
+        let (address, handle) = MockServer::new()
+            .with_expectation(setup_expectation(transport))
+            .with_expectation(session_expectation(Method::Options))
+            .with_expectation(session_expectation(Method::Teardown))
+            .listen()
+            .unwrap();
+
+        let mut runtime = Runtime::new().unwrap();
+        let mut client = runtime
+            .block_on(Client::connect(address))
+            .unwrap()
+            .with_keepalive_interval(Some(KEEPALIVE_INTERVAL))
+            .with_rtcp_liveness_supported(rtcp_liveness_supported);
+        send(&mut runtime, &mut client, vec![Method::Setup]);
+
+        // Recording RTCP much more often than the interval does not prevent the keepalive.
+        let deadline = Instant::now() + Duration::from_secs(10);
+
+        while !matches!(handle.verify(), Err(MockError::UnmetExpectations(1))) {
+            assert!(Instant::now() < deadline, "keepalive not sent");
+            client.record_rtcp();
+            thread::sleep(KEEPALIVE_INTERVAL / 10);
+        }
+
+        teardown_after_keepalive(&mut runtime, client, handle);
+        runtime.shutdown_on_idle().wait().unwrap();
+    }
+}
@@ -0,0 +1,1051 @@
+//! Connection Pooling
+//!
+//! This module contains [`Pool`], which shares connections between the requests of an application
+//! talking to many servers, such as cameras, instead of connecting anew for every operation.
+//! Connections are keyed by the scheme and authority of request URIs, see [`PoolKey`].
+//!
+//! A request is sent on an unused connection to its server if there is one, or on a new connection
+//! as long as the server has fewer connections than the maximum allowed. Otherwise, it is sent on
+//! the least used connection, as RTSP allows many requests to be pending on a connection at once.
+//!
+//! Connections are closed once they have been unused and have had no sessions for the idle
+//! timeout, and are removed from the pool as soon as they shut down, such as when the server
+//! closes them. Requests carrying a session are sent on the connection that created it.
+//!
+//! A connection is used while a request is pending on it through the pool, or while a
+//! [`PooledClient`] checked out with [`Pool::client`] is kept.
+
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt::{self, Display, Formatter},
+    io,
+    net::{IpAddr, SocketAddr, ToSocketAddrs},
+    ops::{Deref, DerefMut},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, Weak,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use bytes::BytesMut;
+use futures::{
+    future::{self, Either},
+    sync::oneshot,
+    task::{self, Task},
+    Async, Future, Poll,
+};
+use tokio_timer::Delay;
+
+use crate::{
+    client::Client,
+    header::{map::HeaderMapExtension, types::Session},
+    protocol::connection::{OperationError, ShutdownType},
+    request::Request,
+    response::Response,
+    session::SessionID,
+    uri::request::URI,
+};
+
+/// The default duration after which unused connections without sessions are closed.
+pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The default maximum number of connections to a server.
+pub const DEFAULT_MAX_CONNECTIONS_PER_HOST: usize = 4;
+
+/// Idle connections are looked for this many times per idle timeout, so that they are closed at
+/// most a fraction of it late.
+const IDLE_SWEEPS_PER_TIMEOUT: u32 = 4;
+
+/// A future resolving to a new connection to a server.
+type ConnectFuture = Box<dyn Future<Item = Client, Error = io::Error> + Send>;
+
+/// Connects to the server identified by a key.
+type Connector = dyn Fn(&PoolKey) -> ConnectFuture + Send + Sync;
+
+/// A pool of connections to RTSP servers.
+///
+/// Clones of the pool share its connections.
+#[derive(Clone)]
+pub struct Pool {
+    state: Arc<Mutex<State>>,
+}
+
+impl Pool {
+    /// Returns a connection to the server of the given URI, connecting to it if needed.
+    ///
+    /// The connection counts as used for as long as the returned client, or any clone of it, is
+    /// kept, such as to receive interleaved data.
+    pub fn client(&self, uri: &URI) -> impl Future<Item = PooledClient, Error = PoolError> {
+        match PoolKey::from_uri(uri) {
+            Some(key) => Either::A(checkout(&self.state, key, None)),
+            None => Either::B(future::err(PoolError::InvalidURI)),
+        }
+    }
+
+    /// Constructs a new pool connecting to servers with the given function.
+    ///
+    /// This connects through any transport, such as with [`Client::connect_tunneled`].
+    pub fn from_connector<TConnect, TFuture>(connect: TConnect) -> Self
+    where
+        TConnect: Fn(&PoolKey) -> TFuture + Send + Sync + 'static,
+        TFuture: Future<Item = Client, Error = io::Error> + Send + 'static,
+    {
+        let connect: Arc<Connector> = Arc::new(move |key| Box::new(connect(key)) as ConnectFuture);
+
+        Pool {
+            state: Arc::new(Mutex::new(State {
+                connect,
+                hosts: HashMap::new(),
+                idle_timeout: DEFAULT_IDLE_TIMEOUT,
+                max_connections_per_host: DEFAULT_MAX_CONNECTIONS_PER_HOST,
+                next_id: 0,
+                reaper: None,
+                reaping: false,
+                session_affinity_required: false,
+            })),
+        }
+    }
+
+    /// Returns the duration after which unused connections without sessions are closed.
+    pub fn idle_timeout(&self) -> Duration {
+        self.state.lock().unwrap().idle_timeout
+    }
+
+    /// Returns whether requests carrying a session fail once the connection that created the
+    /// session has closed, rather than being sent on another connection.
+    pub fn is_session_affinity_required(&self) -> bool {
+        self.state.lock().unwrap().session_affinity_required
+    }
+
+    /// Returns the maximum number of connections to a server.
+    pub fn max_connections_per_host(&self) -> usize {
+        self.state.lock().unwrap().max_connections_per_host
+    }
+
+    /// Constructs a new pool connecting to servers over TCP, resolving their hosts with the
+    /// system resolver on a thread of its own, since it blocks.
+    ///
+    /// Only the `rtsp` scheme is supported. Connecting for other schemes, such as `rtsps`, fails
+    /// with [`io::ErrorKind::InvalidInput`] rather than falling back to an unencrypted connection.
+    /// Use [`Pool::from_connector`] to connect with TLS.
+    pub fn new() -> Self {
+        Pool::from_connector(|key: &PoolKey| {
+            if key.scheme() != "rtsp" {
+                return Either::A(future::err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("no connector for the {} scheme", key.scheme()),
+                )));
+            }
+
+            Either::B(resolve(key.host(), key.port()).and_then(Client::connect))
+        })
+    }
+
+    /// Sends the given request on a connection to the server of its URI.
+    ///
+    /// If the request carries a session created by a connection of the pool that is still open,
+    /// it is sent on that connection.
+    pub fn send_request<R, B>(
+        &self,
+        request: R,
+    ) -> impl Future<Item = Response<BytesMut>, Error = PoolError>
+    where
+        R: Into<Request<B>>,
+        B: AsRef<[u8]> + Send + 'static,
+    {
+        let request = request.into();
+        let key = match PoolKey::from_uri(request.uri()) {
+            Some(key) => key,
+            None => return Either::B(future::err(PoolError::InvalidURI)),
+        };
+        let id = request
+            .headers()
+            .typed_get::<Session>()
+            .map(|session| session.id().clone());
+
+        // The connection is used until the response is received.
+        Either::A(checkout(&self.state, key, id).and_then(move |mut client| {
+            client.send_request(request).then(move |result| {
+                drop(client);
+                result.map_err(PoolError::from)
+            })
+        }))
+    }
+
+    /// Sets the duration after which unused connections without sessions are closed.
+    pub fn set_idle_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.state.lock().unwrap().idle_timeout = timeout;
+        self
+    }
+
+    /// Sets the maximum number of connections to a server, which is at least one.
+    pub fn set_max_connections_per_host(&mut self, max_connections: usize) -> &mut Self {
+        self.state.lock().unwrap().max_connections_per_host = max_connections.max(1);
+        self
+    }
+
+    /// Sets whether requests carrying a session fail once the connection that created the session
+    /// has closed, rather than being sent on another connection.
+    ///
+    /// This is needed for servers binding sessions to connections, such as ones delivering media
+    /// interleaved on the connection.
+    pub fn set_session_affinity_required(&mut self, required: bool) -> &mut Self {
+        self.state.lock().unwrap().session_affinity_required = required;
+        self
+    }
+
+    /// Sets the duration after which unused connections without sessions are closed.
+    pub fn with_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.set_idle_timeout(timeout);
+        self
+    }
+
+    /// Sets the maximum number of connections to a server.
+    pub fn with_max_connections_per_host(mut self, max_connections: usize) -> Self {
+        self.set_max_connections_per_host(max_connections);
+        self
+    }
+
+    /// Sets whether requests carrying a session fail once the connection that created the session
+    /// has closed.
+    pub fn with_session_affinity_required(mut self, required: bool) -> Self {
+        self.set_session_affinity_required(required);
+        self
+    }
+}
+
+impl Default for Pool {
+    fn default() -> Self {
+        Pool::new()
+    }
+}
+
+/// An error type for when a request could not be sent through a [`Pool`].
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub enum PoolError {
+    /// The request URI has no host, such as an asterisk.
+    InvalidURI,
+
+    /// An I/O error occurred while connecting to the server.
+    IO(Arc<io::Error>),
+
+    /// The request failed on the connection.
+    Operation(OperationError),
+
+    /// The request carried a session whose connection has closed, while session affinity is
+    /// required.
+    SessionConnectionClosed,
+}
+
+impl Display for PoolError {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        use self::PoolError::*;
+
+        match self {
+            InvalidURI => write!(formatter, "request URI without host"),
+            IO(error) => write!(formatter, "connection I/O error: {}", error),
+            Operation(error) => write!(formatter, "request failed: {}", error),
+            SessionConnectionClosed => write!(formatter, "connection of session closed"),
+        }
+    }
+}
+
+impl Error for PoolError {}
+
+impl From<io::Error> for PoolError {
+    fn from(value: io::Error) -> Self {
+        PoolError::IO(Arc::new(value))
+    }
+}
+
+impl From<OperationError> for PoolError {
+    fn from(value: OperationError) -> Self {
+        PoolError::Operation(value)
+    }
+}
+
+/// A connection checked out of a [`Pool`], see [`Pool::client`].
+///
+/// The connection counts as used until this and all of its clones are dropped. Clones of the
+/// underlying [`Client`] do not count.
+pub struct PooledClient {
+    /// The client of the connection.
+    client: Client,
+
+    /// The number of checkouts of the connection.
+    uses: Arc<AtomicUsize>,
+}
+
+impl PooledClient {
+    /// Checks out the given client, counting it in the given number of checkouts.
+    fn new(client: Client, uses: Arc<AtomicUsize>) -> Self {
+        uses.fetch_add(1, Ordering::SeqCst);
+        PooledClient { client, uses }
+    }
+}
+
+impl Clone for PooledClient {
+    fn clone(&self) -> Self {
+        PooledClient::new(self.client.clone(), self.uses.clone())
+    }
+}
+
+impl Deref for PooledClient {
+    type Target = Client;
+
+    fn deref(&self) -> &Self::Target {
+        &self.client
+    }
+}
+
+impl DerefMut for PooledClient {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.client
+    }
+}
+
+impl Drop for PooledClient {
+    fn drop(&mut self) {
+        self.uses.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// The server a connection of a [`Pool`] is to, as given by the scheme and authority of request
+/// URIs.
+///
+/// Hosts are compared case insensitively, and URIs without a port use the default port of their
+/// scheme, so `"rtsp://Camera.local/a"` and `"rtsp://camera.local:554/b"` share connections.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct PoolKey {
+    host: String,
+    port: u16,
+    scheme: String,
+}
+
+impl PoolKey {
+    /// Returns the key of the server of the given URI, or [`Option::None`] if it has no host.
+    pub fn from_uri(uri: &URI) -> Option<Self> {
+        let host = uri.host()?.to_string().to_ascii_lowercase();
+        let port = uri.port().or_else(|| uri.default_port())?;
+        let scheme = uri.scheme()?.as_str().to_ascii_lowercase();
+
+        Some(PoolKey { host, port, scheme })
+    }
+
+    /// Returns the host of the server.
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    /// Returns the port of the server.
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// Returns the scheme used to reach the server, such as `"rtsp"`.
+    pub fn scheme(&self) -> &str {
+        &self.scheme
+    }
+}
+
+impl Display for PoolKey {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(formatter, "{}://{}:{}", self.scheme, self.host, self.port)
+    }
+}
+
+/// The connections to a server.
+#[derive(Default)]
+struct Host {
+    /// The number of connections being established.
+    connecting: usize,
+
+    /// The established connections.
+    connections: Vec<PooledConnection>,
+
+    /// The senders through which requests waiting for a connection to be established receive it.
+    waiters: Vec<oneshot::Sender<Result<PooledClient, PoolError>>>,
+}
+
+impl Host {
+    /// Returns whether the server has as many connections, established or not, as allowed.
+    fn is_full(&self, max_connections: usize) -> bool {
+        self.connections.len() + self.connecting >= max_connections
+    }
+}
+
+/// A connection of a pool.
+struct PooledConnection {
+    /// The client of the connection.
+    client: Client,
+
+    /// The identifier of the connection, unique within the pool.
+    id: u64,
+
+    /// When the connection was last seen used or with sessions.
+    last_used: Instant,
+
+    /// The number of checkouts of the connection not yet dropped.
+    uses: Arc<AtomicUsize>,
+}
+
+impl PooledConnection {
+    /// Checks out the connection, marking it as used now.
+    fn checkout(&mut self) -> PooledClient {
+        self.last_used = Instant::now();
+        PooledClient::new(self.client.clone(), self.uses.clone())
+    }
+
+    /// Returns whether the connection created the session with the given identifier, which is
+    /// still being kept alive.
+    fn has_session(&self, id: &SessionID) -> bool {
+        self.client
+            .keepalive
+            .lock()
+            .unwrap()
+            .sessions
+            .contains_key(id)
+    }
+
+    /// Returns whether the connection is unused and has no sessions.
+    fn is_idle(&self) -> bool {
+        self.uses() == 0 && self.client.keepalive.lock().unwrap().sessions.is_empty()
+    }
+
+    /// Returns the number of checkouts of the connection not yet dropped.
+    fn uses(&self) -> usize {
+        self.uses.load(Ordering::SeqCst)
+    }
+}
+
+/// A future closing the idle connections of a pool, until it has no connections left or is
+/// dropped.
+#[must_use = "futures do nothing unless polled"]
+struct Reaper {
+    /// The delay until idle connections are next looked for.
+    delay: Delay,
+
+    /// The pool.
+    state: Weak<Mutex<State>>,
+}
+
+impl Future for Reaper {
+    type Item = ();
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        loop {
+            let state = match self.state.upgrade() {
+                Some(state) => state,
+                None => return Ok(Async::Ready(())),
+            };
+            let mut state = state.lock().unwrap();
+
+            match self.delay.poll() {
+                Ok(Async::Ready(())) => (),
+                Ok(Async::NotReady) => {
+                    state.reaper = Some(task::current());
+                    return Ok(Async::NotReady);
+                }
+                Err(_) => {
+                    state.reaper = None;
+                    state.reaping = false;
+                    return Err(());
+                }
+            }
+
+            state.close_idle_connections();
+
+            if state.hosts.is_empty() {
+                state.reaper = None;
+                state.reaping = false;
+                return Ok(Async::Ready(()));
+            }
+
+            self.delay
+                .reset(Instant::now() + state.idle_timeout / IDLE_SWEEPS_PER_TIMEOUT);
+        }
+    }
+}
+
+/// The shared state of a pool.
+struct State {
+    /// Connects to servers.
+    connect: Arc<Connector>,
+
+    /// The connections to every server.
+    hosts: HashMap<PoolKey, Host>,
+
+    /// The duration after which unused connections without sessions are closed.
+    idle_timeout: Duration,
+
+    /// The maximum number of connections to a server.
+    max_connections_per_host: usize,
+
+    /// The identifier of the next connection established.
+    next_id: u64,
+
+    /// The running [`Reaper`] task, if it is waiting.
+    reaper: Option<Task>,
+
+    /// Whether a [`Reaper`] is running.
+    reaping: bool,
+
+    /// Whether requests carrying a session fail once the connection of the session has closed.
+    session_affinity_required: bool,
+}
+
+impl State {
+    /// Closes the connections that have been idle for the idle timeout, and forgets the servers
+    /// left without connections.
+    fn close_idle_connections(&mut self) {
+        let now = Instant::now();
+        let idle_timeout = self.idle_timeout;
+
+        for host in self.hosts.values_mut() {
+            host.connections.retain_mut(|connection| {
+                if !connection.is_idle() {
+                    connection.last_used = now;
+                    true
+                } else if now.duration_since(connection.last_used) < idle_timeout {
+                    true
+                } else {
+                    connection.client.handle.shutdown(ShutdownType::Immediate);
+                    false
+                }
+            });
+        }
+
+        self.remove_empty_hosts();
+    }
+
+    /// Removes the connection with the given identifier to the server with the given key.
+    fn remove_connection(&mut self, key: &PoolKey, id: u64) {
+        if let Some(host) = self.hosts.get_mut(key) {
+            host.connections.retain(|connection| connection.id != id);
+        }
+
+        self.remove_empty_hosts();
+    }
+
+    /// Forgets the servers without connections, established or not.
+    fn remove_empty_hosts(&mut self) {
+        self.hosts
+            .retain(|_, host| !host.connections.is_empty() || host.connecting > 0);
+    }
+}
+
+impl Drop for State {
+    fn drop(&mut self) {
+        // The reaper stops once it notices that the pool is gone.
+        if let Some(reaper) = self.reaper.take() {
+            reaper.notify();
+        }
+    }
+}
+
+/// Returns a connection to the server with the given key for a request carrying the session with
+/// the given identifier, if any.
+fn checkout(
+    state: &Arc<Mutex<State>>,
+    key: PoolKey,
+    id: Option<SessionID>,
+) -> Box<dyn Future<Item = PooledClient, Error = PoolError> + Send> {
+    let mut locked_state = state.lock().unwrap();
+    let max_connections = locked_state.max_connections_per_host;
+    let session_affinity_required = locked_state.session_affinity_required;
+    let host = locked_state.hosts.entry(key.clone()).or_default();
+
+    if let Some(id) = id {
+        if let Some(connection) = host
+            .connections
+            .iter_mut()
+            .find(|connection| connection.has_session(&id))
+        {
+            return Box::new(future::ok(connection.checkout()));
+        }
+
+        if session_affinity_required {
+            locked_state.remove_empty_hosts();
+            return Box::new(future::err(PoolError::SessionConnectionClosed));
+        }
+    }
+
+    let is_full = host.is_full(max_connections);
+
+    if let Some(connection) = host
+        .connections
+        .iter_mut()
+        .min_by_key(|connection| connection.uses())
+        .filter(|connection| connection.uses() == 0 || is_full)
+    {
+        return Box::new(future::ok(connection.checkout()));
+    }
+
+    if is_full {
+        // Every connection allowed is being established.
+        let (tx_client, rx_client) = oneshot::channel();
+        host.waiters.push(tx_client);
+
+        return Box::new(
+            rx_client
+                .map_err(|_| PoolError::Operation(OperationError::Closed))
+                .and_then(|result| result),
+        );
+    }
+
+    host.connecting += 1;
+
+    // Connecting may take a while to start, so it is not done while other checkouts wait for the
+    // lock.
+    let connect = locked_state.connect.clone();
+    drop(locked_state);
+
+    let state = state.clone();
+
+    Box::new(connect(&key).then(move |result| {
+        let mut locked_state = state.lock().unwrap();
+        let id = locked_state.next_id;
+        let host = locked_state.hosts.entry(key.clone()).or_default();
+        host.connecting -= 1;
+
+        let waiters = host.waiters.drain(..).collect::<Vec<_>>();
+
+        match result {
+            Ok(client) => {
+                let mut connection = PooledConnection {
+                    client: client.clone(),
+                    id,
+                    last_used: Instant::now(),
+                    uses: Arc::new(AtomicUsize::new(0)),
+                };
+
+                for waiter in waiters {
+                    let _ = waiter.send(Ok(connection.checkout()));
+                }
+
+                let checked_out = connection.checkout();
+                host.connections.push(connection);
+                locked_state.next_id += 1;
+
+                watch(&state, key, id, client);
+
+                if !locked_state.reaping {
+                    locked_state.reaping = true;
+                    tokio::spawn(Reaper {
+                        delay: Delay::new(
+                            Instant::now() + locked_state.idle_timeout / IDLE_SWEEPS_PER_TIMEOUT,
+                        ),
+                        state: Arc::downgrade(&state),
+                    });
+                }
+
+                Ok(checked_out)
+            }
+            Err(error) => {
+                let error = PoolError::from(error);
+                locked_state.remove_empty_hosts();
+
+                for waiter in waiters {
+                    let _ = waiter.send(Err(error.clone()));
+                }
+
+                Err(error)
+            }
+        }
+    }))
+}
+
+/// Resolves the given host to the first of its addresses.
+///
+/// The system resolver blocks, so it is run on a thread of its own rather than on the executor.
+/// Hosts that are IP addresses are not looked up.
+fn resolve(host: &str, port: u16) -> Box<dyn Future<Item = SocketAddr, Error = io::Error> + Send> {
+    if let Ok(address) = host
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse::<IpAddr>()
+    {
+        return Box::new(future::ok(SocketAddr::new(address, port)));
+    }
+
+    let (tx_address, rx_address) = oneshot::channel();
+    let host = host.to_string();
+
+    thread::spawn(move || {
+        let address = (host.as_str(), port)
+            .to_socket_addrs()
+            .and_then(|mut addresses| {
+                addresses
+                    .next()
+                    .ok_or_else(|| io::ErrorKind::AddrNotAvailable.into())
+            });
+        let _ = tx_address.send(address);
+    });
+
+    Box::new(
+        rx_address
+            .map_err(|_| io::ErrorKind::Other.into())
+            .and_then(|result| result),
+    )
+}
+
+/// Removes the connection with the given identifier from the pool once it shuts down.
+fn watch(state: &Arc<Mutex<State>>, key: PoolKey, id: u64, mut client: Client) {
+    let state = Arc::downgrade(state);
+
+    tokio::spawn(client.shutdown_receiver().then(move |_| {
+        if let Some(state) = state.upgrade() {
+            state.lock().unwrap().remove_connection(&key, id);
+        }
+
+        Ok(())
+    }));
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::VecDeque, convert::TryFrom, thread};
+
+    use tokio::runtime::Runtime;
+
+    use super::*;
+    use crate::{
+        header::{name::HeaderName, value::HeaderValue},
+        method::Method,
+        status::StatusCode,
+        testing::{
+            duplex::DuplexStream,
+            mock::{Expectation, MockServer, MockServerHandle},
+        },
+    };
+
+    const SESSION: &str = "12345678";
+
+    fn request(method: Method, session: Option<&str>) -> Request<BytesMut> {
+        let mut builder = Request::<()>::builder()
+            .with_method(method)
+            .with_uri(URI::try_from("rtsp://camera.local/stream").unwrap());
+
+        if let Some(session) = session {
+            builder =
+                builder.with_header(HeaderName::Session, HeaderValue::try_from(session).unwrap());
+        }
+
+        builder.with_body(BytesMut::new()).build().unwrap()
+    }
+
+    fn setup_expectation() -> Expectation {
+        let response = Response::<()>::builder()
+            .with_status_code(StatusCode::OK)
+            .with_header(HeaderName::Session, HeaderValue::try_from(SESSION).unwrap())
+            .with_body(BytesMut::new())
+            .build()
+            .unwrap();
+
+        Expectation::new(Method::Setup).with_response(response)
+    }
+
+    /// Constructs a pool connecting over the given transports in order, returning it with the
+    /// number of connections established so far.
+    fn pool(transports: Vec<(DuplexStream, MockServerHandle)>) -> (Pool, Arc<Mutex<usize>>) {
+        let transports = Arc::new(Mutex::new(VecDeque::from(transports)));
+        let connections = Arc::new(Mutex::new(0));
+        let counter = connections.clone();
+        let address = "127.0.0.1:554".parse().unwrap();
+
+        let pool = Pool::from_connector(move |key: &PoolKey| {
+            assert_eq!(key.to_string(), "rtsp://camera.local:554");
+
+            let transports = transports.clone();
+            let counter = counter.clone();
+
+            future::lazy(move || match transports.lock().unwrap().pop_front() {
+                Some((transport, _)) => {
+                    *counter.lock().unwrap() += 1;
+                    Ok(Client::spawn(transport, address))
+                }
+                None => Err(io::ErrorKind::NotConnected.into()),
+            })
+        });
+
+        (pool, connections)
+    }
+
+    #[test]
+    fn test_bounds() {
+        fn check_bounds<T: Send + Sync>() {}
+
+        check_bounds::<Pool>();
+        check_bounds::<PooledClient>();
+    }
+
+    #[test]
+    fn test_pool_closes_idle_and_dead_connections() {
+        let (first, first_handle) = MockServer::new()
+            .with_expectation(Expectation::new(Method::Options))
+            .duplex();
+        let (second, second_handle) = MockServer::new()
+            .with_expectation(Expectation::new(Method::Options))
+            .with_close()
+            .duplex();
+        let (third, third_handle) = MockServer::new()
+            .with_expectation(Expectation::new(Method::Options))
+            .duplex();
+        let (pool, connections) = pool(vec![
+            (first, first_handle.clone()),
+            (second, second_handle.clone()),
+            (third, third_handle.clone()),
+        ]);
+        let pool = pool.with_idle_timeout(Duration::from_millis(100));
+
+        let mut runtime = Runtime::new().unwrap();
+
+        // The first connection is closed once idle.
+        let response = runtime
+            .block_on(pool.send_request(request(Method::Options, None)))
+            .unwrap();
+        assert_eq!(response.status_code(), StatusCode::OK);
+        thread::sleep(Duration::from_millis(300));
+
+        // The second connection is closed by the server.
+        let response = runtime
+            .block_on(pool.send_request(request(Method::Options, None)))
+            .unwrap();
+        assert_eq!(response.status_code(), StatusCode::OK);
+        thread::sleep(Duration::from_millis(50));
+
+        let response = runtime
+            .block_on(pool.send_request(request(Method::Options, None)))
+            .unwrap();
+        assert_eq!(response.status_code(), StatusCode::OK);
+        assert_eq!(*connections.lock().unwrap(), 3);
+
+        assert!(first_handle.verify().is_ok());
+        assert!(second_handle.verify().is_ok());
+        assert!(third_handle.verify().is_ok());
+
+        drop(pool);
+        runtime.shutdown_on_idle().wait().unwrap();
+    }
+
+    #[test]
+    fn test_pool_counts_checkouts() {
+        let (first, first_handle) = MockServer::new()
+            .with_expectation(Expectation::new(Method::Options))
+            .duplex();
+        let (second, second_handle) = MockServer::new()
+            .with_expectation(Expectation::new(Method::Options))
+            .duplex();
+        let (pool, connections) = pool(vec![
+            (first, first_handle.clone()),
+            (second, second_handle.clone()),
+        ]);
+        let uri = URI::try_from("rtsp://camera.local/stream").unwrap();
+
+        let mut runtime = Runtime::new().unwrap();
+
+        // Clones of the underlying client do not keep the connection used.
+        let client = runtime.block_on(pool.client(&uri)).unwrap();
+        let unpooled = Client::clone(&client);
+        drop(client);
+        let response = runtime
+            .block_on(pool.send_request(request(Method::Options, None)))
+            .unwrap();
+        assert_eq!(response.status_code(), StatusCode::OK);
+        assert_eq!(*connections.lock().unwrap(), 1);
+
+        // Clones of the checked out client do.
+        let client = runtime.block_on(pool.client(&uri)).unwrap();
+        let checked_out = client.clone();
+        drop(client);
+        let response = runtime
+            .block_on(pool.send_request(request(Method::Options, None)))
+            .unwrap();
+        assert_eq!(response.status_code(), StatusCode::OK);
+        assert_eq!(*connections.lock().unwrap(), 2);
+
+        assert!(first_handle.verify().is_ok());
+        assert!(second_handle.verify().is_ok());
+
+        drop(checked_out);
+        drop(unpooled);
+        drop(pool);
+        runtime.shutdown_on_idle().wait().unwrap();
+    }
+
+    #[test]
+    fn test_pool_key() {
+        let key = PoolKey::from_uri(&URI::try_from("rtsp://Camera.local/a").unwrap()).unwrap();
+        assert_eq!(key.host(), "camera.local");
+        assert_eq!(key.port(), 554);
+        assert_eq!(key.scheme(), "rtsp");
+        assert_eq!(
+            PoolKey::from_uri(&URI::try_from("rtsp://camera.local:554/b").unwrap()),
+            Some(key.clone())
+        );
+        assert_ne!(
+            PoolKey::from_uri(&URI::try_from("rtsps://camera.local:554/b").unwrap()),
+            Some(key)
+        );
+        assert_eq!(PoolKey::from_uri(&URI::asterisk()), None);
+    }
+
+    #[test]
+    fn test_pool_connects_without_the_lock() {
+        let (transport, handle) = MockServer::new()
+            .with_expectation(Expectation::new(Method::Options))
+            .duplex();
+        let transport = Arc::new(Mutex::new(Some(transport)));
+        let state = Arc::new(Mutex::new(Weak::<Mutex<State>>::new()));
+        let pool_state = state.clone();
+        let address = "127.0.0.1:554".parse().unwrap();
+
+        // Other checkouts can proceed while a connection is being established.
+        let pool = Pool::from_connector(move |_: &PoolKey| {
+            let state = pool_state.lock().unwrap().upgrade().unwrap();
+            assert!(state.try_lock().is_ok());
+
+            let transport = transport.lock().unwrap().take().unwrap();
+            future::lazy(move || Ok(Client::spawn(transport, address)))
+        });
+        *state.lock().unwrap() = Arc::downgrade(&pool.state);
+
+        let mut runtime = Runtime::new().unwrap();
+        let response = runtime
+            .block_on(pool.send_request(request(Method::Options, None)))
+            .unwrap();
+        assert_eq!(response.status_code(), StatusCode::OK);
+        assert!(handle.verify().is_ok());
+
+        drop(pool);
+        runtime.shutdown_on_idle().wait().unwrap();
+    }
+
+    #[test]
+    fn test_pool_refuses_unsupported_schemes() {
+        let request = Request::<()>::builder()
+            .with_method(Method::Options)
+            .with_uri(URI::try_from("rtsps://127.0.0.1/stream").unwrap())
+            .with_body(BytesMut::new())
+            .build()
+            .unwrap();
+
+        let mut runtime = Runtime::new().unwrap();
+        let error = runtime
+            .block_on(Pool::new().send_request(request))
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            PoolError::IO(error) if error.kind() == io::ErrorKind::InvalidInput
+        ));
+        runtime.shutdown_on_idle().wait().unwrap();
+    }
+
+    #[test]
+    fn test_resolve() {
+        let address = resolve("127.0.0.1", 554).wait().unwrap();
+        assert_eq!(address, "127.0.0.1:554".parse().unwrap());
+
+        let address = resolve("[::1]", 8554).wait().unwrap();
+        assert_eq!(address, "[::1]:8554".parse().unwrap());
+
+        let address = resolve("localhost", 554).wait().unwrap();
+        assert!(address.ip().is_loopback());
+        assert_eq!(address.port(), 554);
+    }
+
+    #[test]
+    fn test_pool_reuses_connections() {
+        let (first, first_handle) = MockServer::new()
+            .with_expectation(Expectation::new(Method::Options))
+            .with_expectation(Expectation::new(Method::Options))
+            .with_expectation(setup_expectation())
+            .with_expectation(
+                Expectation::new(Method::Play)
+                    .with_header(HeaderName::Session, HeaderValue::try_from(SESSION).unwrap()),
+            )
+            .with_expectation(Expectation::new(Method::Options))
+            .duplex();
+        let (second, second_handle) = MockServer::new()
+            .with_expectation(Expectation::new(Method::Options))
+            .duplex();
+        let (pool, connections) = pool(vec![
+            (first, first_handle.clone()),
+            (second, second_handle.clone()),
+        ]);
+        let pool = pool.with_max_connections_per_host(2);
+        let uri = URI::try_from("rtsp://camera.local/stream").unwrap();
+
+        let mut runtime = Runtime::new().unwrap();
+
+        for method in [Method::Options, Method::Options, Method::Setup] {
+            let response = runtime
+                .block_on(pool.send_request(request(method, None)))
+                .unwrap();
+            assert_eq!(response.status_code(), StatusCode::OK);
+        }
+
+        assert_eq!(*connections.lock().unwrap(), 1);
+
+        // While the first connection is used, another one is established.
+        let first_client = runtime.block_on(pool.client(&uri)).unwrap();
+        let response = runtime
+            .block_on(pool.send_request(request(Method::Options, None)))
+            .unwrap();
+        assert_eq!(response.status_code(), StatusCode::OK);
+        assert_eq!(*connections.lock().unwrap(), 2);
+
+        // Requests carrying the session are sent on the connection that created it.
+        let response = runtime
+            .block_on(pool.send_request(request(Method::Play, Some(SESSION))))
+            .unwrap();
+        assert_eq!(response.status_code(), StatusCode::OK);
+
+        // Once every connection allowed is used, the least used one is shared.
+        let second_client = runtime.block_on(pool.client(&uri)).unwrap();
+        let response = runtime
+            .block_on(pool.send_request(request(Method::Options, None)))
+            .unwrap();
+        assert_eq!(response.status_code(), StatusCode::OK);
+        assert_eq!(*connections.lock().unwrap(), 2);
+
+        assert!(first_handle.verify().is_ok());
+        assert!(second_handle.verify().is_ok());
+
+        drop(first_client);
+        drop(second_client);
+        drop(pool);
+        runtime.shutdown_on_idle().wait().unwrap();
+    }
+
+    #[test]
+    fn test_pool_session_affinity() {
+        let (transport, handle) = MockServer::new()
+            .with_expectation(setup_expectation())
+            .with_close()
+            .duplex();
+        let (pool, _) = pool(vec![(transport, handle.clone())]);
+        let pool = pool.with_session_affinity_required(true);
+
+        let mut runtime = Runtime::new().unwrap();
+        let response = runtime
+            .block_on(pool.send_request(request(Method::Setup, None)))
+            .unwrap();
+        assert_eq!(response.status_code(), StatusCode::OK);
+        thread::sleep(Duration::from_millis(50));
+
+        let error = runtime
+            .block_on(pool.send_request(request(Method::Play, Some(SESSION))))
+            .unwrap_err();
+        assert!(matches!(error, PoolError::SessionConnectionClosed));
+        assert!(handle.verify().is_ok());
+
+        drop(pool);
+        runtime.shutdown_on_idle().wait().unwrap();
+    }
+}
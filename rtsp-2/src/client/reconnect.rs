@@ -0,0 +1,845 @@
+//! Reconnection
+//!
+//! This module contains [`ReconnectingClient`], which wraps a [`Client`] to survive the connection
+//! to the server dropping, such as when a camera reboots. Once the connection closes, it reconnects
+//! with exponential backoff and jitter, then replays the `DESCRIBE`, `SETUP` and `PLAY` requests
+//! that last succeeded so that the presentation is received again.
+//!
+//! Playback of seekable presentations resumes from the last position delivered before the
+//! connection dropped, while live presentations resume from the live position. Requests carrying
+//! the original session keep working, as they are sent with the session that replaced it.
+
+use std::{
+    io,
+    net::SocketAddr,
+    sync::{Arc, Mutex, Weak},
+    time::{Duration, Instant},
+};
+
+use bytes::BytesMut;
+use futures::{
+    future::{self, Either, Loop},
+    sync::mpsc::{self, UnboundedReceiver, UnboundedSender},
+    Future, Stream,
+};
+use rand::random;
+use tokio_timer::Delay;
+
+use crate::{
+    client::Client,
+    header::{
+        map::HeaderMapExtension,
+        name::HeaderName,
+        types::{
+            range::{NPTTime, Range},
+            Session,
+        },
+    },
+    method::Method,
+    protocol::{codec::interleaved::InterleavedData, connection::OperationError},
+    request::Request,
+    response::Response,
+    session::SessionID,
+};
+
+/// The default delay before the first reconnection attempt.
+pub const DEFAULT_INITIAL_DELAY: Duration = Duration::from_millis(500);
+
+/// The default maximum delay between reconnection attempts.
+pub const DEFAULT_MAX_DELAY: Duration = Duration::from_secs(30);
+
+/// A future resolving to a new connection to the server.
+type ConnectFuture = Box<dyn Future<Item = Client, Error = io::Error> + Send>;
+
+/// Connects to the server, every time the connection is established again.
+type Connector = dyn Fn() -> ConnectFuture + Send + Sync;
+
+/// How long to wait between reconnection attempts.
+///
+/// The delay before each attempt is the delay before the previous one times the multiplier, up to
+/// the maximum delay. A random fraction of each delay, up to the jitter, is subtracted from it, so
+/// that clients disconnected at once do not reconnect at once.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Backoff {
+    /// The delay before the first attempt.
+    initial_delay: Duration,
+
+    /// The maximum fraction of each delay subtracted from it at random, between 0 and 1.
+    jitter: f64,
+
+    /// The number of attempts after which reconnecting is abandoned, if any.
+    max_attempts: Option<u32>,
+
+    /// The maximum delay between attempts.
+    max_delay: Duration,
+
+    /// The factor by which the delay grows after every attempt.
+    multiplier: u32,
+}
+
+impl Backoff {
+    /// Returns the delay before the given attempt, counting from 1, with jitter applied.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let delay = self
+            .multiplier
+            .checked_pow(attempt.saturating_sub(1))
+            .and_then(|factor| self.initial_delay.checked_mul(factor))
+            .map_or(self.max_delay, |delay| delay.min(self.max_delay));
+
+        delay.mul_f64(1.0 - self.jitter * random::<f64>())
+    }
+
+    /// Returns the delay before the first attempt.
+    pub fn initial_delay(&self) -> Duration {
+        self.initial_delay
+    }
+
+    /// Returns the maximum fraction of each delay subtracted from it at random.
+    pub fn jitter(&self) -> f64 {
+        self.jitter
+    }
+
+    /// Returns the number of attempts after which reconnecting is abandoned, if any.
+    pub fn max_attempts(&self) -> Option<u32> {
+        self.max_attempts
+    }
+
+    /// Returns the maximum delay between attempts.
+    pub fn max_delay(&self) -> Duration {
+        self.max_delay
+    }
+
+    /// Returns the factor by which the delay grows after every attempt.
+    pub fn multiplier(&self) -> u32 {
+        self.multiplier
+    }
+
+    /// Constructs a new backoff with the default settings: a delay starting at
+    /// [`DEFAULT_INITIAL_DELAY`] and doubling up to [`DEFAULT_MAX_DELAY`], with up to half of it
+    /// as jitter, and attempts that never stop.
+    pub fn new() -> Self {
+        Backoff::default()
+    }
+
+    /// Sets the delay before the first attempt.
+    pub fn set_initial_delay(&mut self, delay: Duration) -> &mut Self {
+        self.initial_delay = delay;
+        self
+    }
+
+    /// Sets the maximum fraction of each delay subtracted from it at random, which is clamped
+    /// between 0 and 1. Values that are not finite, such as `NaN`, disable jitter.
+    pub fn set_jitter(&mut self, jitter: f64) -> &mut Self {
+        self.jitter = if jitter.is_finite() {
+            jitter.clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        self
+    }
+
+    /// Sets the number of attempts after which reconnecting is abandoned, if any.
+    pub fn set_max_attempts(&mut self, max_attempts: Option<u32>) -> &mut Self {
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    /// Sets the maximum delay between attempts.
+    pub fn set_max_delay(&mut self, delay: Duration) -> &mut Self {
+        self.max_delay = delay;
+        self
+    }
+
+    /// Sets the factor by which the delay grows after every attempt.
+    pub fn set_multiplier(&mut self, multiplier: u32) -> &mut Self {
+        self.multiplier = multiplier;
+        self
+    }
+
+    /// Sets the delay before the first attempt.
+    pub fn with_initial_delay(mut self, delay: Duration) -> Self {
+        self.set_initial_delay(delay);
+        self
+    }
+
+    /// Sets the maximum fraction of each delay subtracted from it at random.
+    pub fn with_jitter(mut self, jitter: f64) -> Self {
+        self.set_jitter(jitter);
+        self
+    }
+
+    /// Sets the number of attempts after which reconnecting is abandoned, if any.
+    pub fn with_max_attempts(mut self, max_attempts: Option<u32>) -> Self {
+        self.set_max_attempts(max_attempts);
+        self
+    }
+
+    /// Sets the maximum delay between attempts.
+    pub fn with_max_delay(mut self, delay: Duration) -> Self {
+        self.set_max_delay(delay);
+        self
+    }
+
+    /// Sets the factor by which the delay grows after every attempt.
+    pub fn with_multiplier(mut self, multiplier: u32) -> Self {
+        self.set_multiplier(multiplier);
+        self
+    }
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Backoff {
+            initial_delay: DEFAULT_INITIAL_DELAY,
+            jitter: 0.5,
+            max_attempts: None,
+            max_delay: DEFAULT_MAX_DELAY,
+            multiplier: 2,
+        }
+    }
+}
+
+/// A change in the connection of a [`ReconnectingClient`].
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum ReconnectEvent {
+    /// The connection to the server closed.
+    Disconnected,
+
+    /// Reconnecting was abandoned after the given number of attempts, as allowed by the backoff.
+    /// The client stays disconnected.
+    Failed { attempts: u32 },
+
+    /// An attempt to reconnect starts after the given delay.
+    Reconnecting { attempt: u32, delay: Duration },
+
+    /// The connection was established again and the presentation replayed. If the presentation
+    /// was playing and is seekable, playback resumed from the given position.
+    Resumed { position: Option<Duration> },
+}
+
+/// A client reconnecting to the server whenever the connection closes, re-establishing the
+/// presentation it was receiving.
+///
+/// See the [module documentation](self) for how the presentation is re-established. Requests sent
+/// while reconnecting fail as they would with a closed [`Client`].
+#[derive(Clone)]
+pub struct ReconnectingClient {
+    /// The state shared with the tasks reconnecting and forwarding interleaved data.
+    state: Arc<Mutex<State>>,
+}
+
+impl ReconnectingClient {
+    /// Connects to the server with the given address, reconnecting with the given backoff.
+    ///
+    /// The future fails if the first connection cannot be established.
+    pub fn connect(
+        server_address: SocketAddr,
+        backoff: Backoff,
+    ) -> impl Future<Item = ReconnectingClient, Error = io::Error> {
+        ReconnectingClient::connect_with(move || Client::connect(server_address), backoff)
+    }
+
+    /// Connects to the server with the given function, reconnecting through it with the given
+    /// backoff.
+    ///
+    /// This reconnects through any transport, such as with [`Client::connect_tunneled`].
+    pub fn connect_with<TConnect, TFuture>(
+        connect: TConnect,
+        backoff: Backoff,
+    ) -> impl Future<Item = ReconnectingClient, Error = io::Error>
+    where
+        TConnect: Fn() -> TFuture + Send + Sync + 'static,
+        TFuture: Future<Item = Client, Error = io::Error> + Send + 'static,
+    {
+        let connect: Arc<Connector> = Arc::new(move || Box::new(connect()) as ConnectFuture);
+
+        connect().map(move |mut client| {
+            let rx_interleaved_data = client.interleaved_data();
+            let state = Arc::new(Mutex::new(State {
+                backoff,
+                client: client.clone(),
+                connect,
+                presentation: Presentation::default(),
+                tx_event: None,
+                tx_interleaved_data: None,
+            }));
+
+            attach(&state, client, rx_interleaved_data);
+            ReconnectingClient { state }
+        })
+    }
+
+    /// Returns a stream of the changes in the connection.
+    ///
+    /// Only the most recently returned stream receives events.
+    pub fn events(&mut self) -> UnboundedReceiver<ReconnectEvent> {
+        let (tx_event, rx_event) = mpsc::unbounded();
+        self.state.lock().unwrap().tx_event = Some(tx_event);
+        rx_event
+    }
+
+    /// Returns a stream of the interleaved data, such as RTP packets, that the server sends, on
+    /// every connection established.
+    ///
+    /// Only the most recently returned stream receives data.
+    pub fn interleaved_data(&mut self) -> UnboundedReceiver<InterleavedData> {
+        let (tx_interleaved_data, rx_interleaved_data) = mpsc::unbounded();
+        self.state.lock().unwrap().tx_interleaved_data = Some(tx_interleaved_data);
+        rx_interleaved_data
+    }
+
+    /// Sends the given request on the current connection, recording it to be replayed after
+    /// reconnecting if it is a successful `DESCRIBE`, `SETUP` or `PLAY` request.
+    pub fn send_request<R, B>(
+        &mut self,
+        request: R,
+    ) -> impl Future<Item = Response<BytesMut>, Error = OperationError>
+    where
+        R: Into<Request<B>>,
+        B: AsRef<[u8]>,
+    {
+        let mut request = request.into().map(|body| BytesMut::from(body.as_ref()));
+        let mut client = {
+            let state = self.state.lock().unwrap();
+            state.presentation.replace_session(&mut request);
+            state.client.clone()
+        };
+        let state = self.state.clone();
+
+        client
+            .send_request(request.clone())
+            .inspect(move |response| {
+                state
+                    .lock()
+                    .unwrap()
+                    .presentation
+                    .record(&request, response);
+            })
+    }
+}
+
+/// The state of a [`ReconnectingClient`].
+struct State {
+    /// How long to wait between reconnection attempts.
+    backoff: Backoff,
+
+    /// The client of the current connection.
+    client: Client,
+
+    /// Connects to the server again.
+    connect: Arc<Connector>,
+
+    /// The presentation to re-establish after reconnecting.
+    presentation: Presentation,
+
+    /// The sender through which events are sent, if anyone is listening.
+    tx_event: Option<UnboundedSender<ReconnectEvent>>,
+
+    /// The sender through which interleaved data is forwarded, if anyone is listening.
+    tx_interleaved_data: Option<UnboundedSender<InterleavedData>>,
+}
+
+impl State {
+    /// Sends the given event, if anyone is listening.
+    fn send_event(&mut self, event: ReconnectEvent) {
+        if let Some(tx_event) = self.tx_event.as_ref() {
+            if tx_event.unbounded_send(event).is_err() {
+                self.tx_event = None;
+            }
+        }
+    }
+}
+
+/// The requests establishing a presentation, as recorded from successful responses.
+#[derive(Default)]
+struct Presentation {
+    /// The latest `DESCRIBE` request.
+    describe: Option<Request<BytesMut>>,
+
+    /// The session the server first set up, which requests may keep referring to.
+    original_session: Option<SessionID>,
+
+    /// The latest `PLAY` request, without session, if playing.
+    play: Option<Request<BytesMut>>,
+
+    /// The progress of playback, if playing.
+    playback: Option<Playback>,
+
+    /// The position to resume playback from after reconnecting, if any.
+    resume_position: Option<Duration>,
+
+    /// The session set up on the current connection, if any.
+    session: Option<SessionID>,
+
+    /// The `SETUP` requests, without session, in the order they were sent.
+    setups: Vec<Request<BytesMut>>,
+}
+
+impl Presentation {
+    /// Records the given request, answered with the given response.
+    fn record(&mut self, request: &Request<BytesMut>, response: &Response<BytesMut>) {
+        if !response.status_code().is_success() {
+            return;
+        }
+
+        let mut recorded = request.clone();
+        recorded.headers_mut().remove(&HeaderName::Session);
+
+        match request.method() {
+            Method::Describe => self.describe = Some(recorded),
+            Method::Pause => {
+                self.play = None;
+                self.playback = None;
+            }
+            Method::Play => {
+                self.play = Some(recorded);
+                self.playback = Some(Playback::new(request, response));
+            }
+            Method::Setup => {
+                self.setups.push(recorded);
+
+                if self.session.is_none() {
+                    self.session = response
+                        .headers()
+                        .typed_get::<Session>()
+                        .map(|session| session.id().clone());
+                    self.original_session = self.session.clone();
+                }
+            }
+            // Tearing down part of an aggregate session leaves the rest of it, in which case the
+            // response still carries the session.
+            Method::Teardown if !response.headers().contains_key(&HeaderName::Session) => {
+                *self = Presentation::default();
+            }
+            _ => (),
+        }
+    }
+
+    /// Records that the connection closed, remembering the position to resume playback from.
+    fn record_disconnection(&mut self) {
+        self.resume_position = self.playback.as_ref().and_then(Playback::position);
+    }
+
+    /// Replaces the original session in the given request by the current one.
+    fn replace_session(&self, request: &mut Request<BytesMut>) {
+        let (original_session, session) = match (&self.original_session, &self.session) {
+            (Some(original_session), Some(session)) => (original_session, session),
+            _ => return,
+        };
+
+        let is_original = request
+            .headers()
+            .typed_get::<Session>()
+            .is_some_and(|header| header.id() == original_session);
+
+        if is_original {
+            request.headers_mut().typed_insert(session_header(session));
+        }
+    }
+}
+
+/// The progress of playback, from which the position delivered so far is estimated.
+struct Playback {
+    /// When interleaved data was last delivered, if any was.
+    last_delivery: Option<Instant>,
+
+    /// The position playback started from, if the presentation is seekable.
+    start: Option<Duration>,
+
+    /// When playback started.
+    started_at: Instant,
+}
+
+impl Playback {
+    /// Constructs the progress of playback started by the given request, answered with the given
+    /// response.
+    ///
+    /// The presentation is seekable if the range played starts at a time rather than at the live
+    /// position, as given by the response or, failing that, the request.
+    fn new(request: &Request<BytesMut>, response: &Response<BytesMut>) -> Self {
+        let start = response
+            .headers()
+            .typed_get::<Range>()
+            .or_else(|| request.headers().typed_get::<Range>())
+            .and_then(|range| range.start())
+            .and_then(|start| match start {
+                NPTTime::Now => None,
+                NPTTime::Time(start) => Some(start),
+            });
+
+        Playback {
+            last_delivery: None,
+            start,
+            started_at: Instant::now(),
+        }
+    }
+
+    /// Returns the position delivered so far, if the presentation is seekable.
+    ///
+    /// It is estimated from the time elapsed between the start of playback and the last delivery,
+    /// assuming the presentation plays at normal speed.
+    fn position(&self) -> Option<Duration> {
+        let elapsed = self.last_delivery.map_or(Duration::ZERO, |last_delivery| {
+            last_delivery.saturating_duration_since(self.started_at)
+        });
+
+        self.start.map(|start| start + elapsed)
+    }
+}
+
+/// Makes the given client the current one, forwarding its interleaved data from the given stream
+/// and reconnecting once its connection closes.
+fn attach(
+    state: &Arc<Mutex<State>>,
+    mut client: Client,
+    rx_interleaved_data: UnboundedReceiver<InterleavedData>,
+) {
+    let shutdown = client.shutdown_receiver();
+    state.lock().unwrap().client = client;
+
+    let forwarding_state = Arc::downgrade(state);
+
+    tokio::spawn(rx_interleaved_data.for_each(move |data| {
+        let state = forwarding_state.upgrade().ok_or(())?;
+        let mut state = state.lock().unwrap();
+
+        if let Some(playback) = state.presentation.playback.as_mut() {
+            playback.last_delivery = Some(Instant::now());
+        }
+
+        if let Some(tx_interleaved_data) = state.tx_interleaved_data.as_ref() {
+            if tx_interleaved_data.unbounded_send(data).is_err() {
+                state.tx_interleaved_data = None;
+            }
+        }
+
+        Ok(())
+    }));
+
+    let state = Arc::downgrade(state);
+    tokio::spawn(shutdown.then(move |_| reconnect(state)));
+}
+
+/// Reconnects to the server with backoff and re-establishes the presentation, until it succeeds,
+/// the attempts are exhausted or the reconnecting client is dropped.
+fn reconnect(state: Weak<Mutex<State>>) -> impl Future<Item = (), Error = ()> + Send {
+    let (backoff, connect) = match state.upgrade() {
+        Some(state) => {
+            let mut state = state.lock().unwrap();
+            state.presentation.record_disconnection();
+            state.send_event(ReconnectEvent::Disconnected);
+            (state.backoff.clone(), state.connect.clone())
+        }
+        None => return Either::A(future::ok(())),
+    };
+
+    Either::B(future::loop_fn(1, move |attempt| {
+        let state_ref = match state.upgrade() {
+            Some(state) => state,
+            None => return Either::A(future::ok(Loop::Break(()))),
+        };
+
+        if backoff.max_attempts().is_some_and(|max| attempt > max) {
+            let event = ReconnectEvent::Failed {
+                attempts: attempt - 1,
+            };
+            state_ref.lock().unwrap().send_event(event);
+            return Either::A(future::ok(Loop::Break(())));
+        }
+
+        let delay = backoff.delay(attempt);
+        let event = ReconnectEvent::Reconnecting { attempt, delay };
+        state_ref.lock().unwrap().send_event(event);
+
+        let state = state.clone();
+        let connect = connect.clone();
+
+        Either::B(
+            Delay::new(Instant::now() + delay)
+                .map_err(|_| ())
+                .and_then(move |_| connect().map_err(|_| ()))
+                .and_then(move |client| resume(state, client))
+                .then(move |result| match result {
+                    Ok(()) => Ok(Loop::Break(())),
+                    Err(()) => Ok(Loop::Continue(attempt + 1)),
+                }),
+        )
+    }))
+}
+
+/// Re-establishes the recorded presentation on the given new connection, then makes it the
+/// current one.
+fn resume(state: Weak<Mutex<State>>, mut client: Client) -> impl Future<Item = (), Error = ()> {
+    let rx_interleaved_data = client.interleaved_data();
+    let (describe, setups, play, position) = match state.upgrade() {
+        Some(state) => {
+            let state = state.lock().unwrap();
+            let presentation = &state.presentation;
+            (
+                presentation.describe.clone(),
+                presentation.setups.clone(),
+                presentation.play.clone(),
+                presentation.resume_position,
+            )
+        }
+        None => return Either::A(future::err(())),
+    };
+
+    let describe = match describe {
+        Some(describe) => Either::A(send(client, describe).map(|(client, _)| client)),
+        None => Either::B(future::ok(client)),
+    };
+
+    let future = describe
+        .and_then(move |client| {
+            future::loop_fn(
+                (client, setups.into_iter(), None),
+                |(client, mut setups, session): (_, _, Option<SessionID>)| {
+                    let mut setup = match setups.next() {
+                        Some(setup) => setup,
+                        None => return Either::A(future::ok(Loop::Break((client, session)))),
+                    };
+
+                    if let Some(session) = session.as_ref() {
+                        setup.headers_mut().typed_insert(session_header(session));
+                    }
+
+                    Either::B(send(client, setup).map(move |(client, response)| {
+                        let session = session.or_else(|| {
+                            response
+                                .headers()
+                                .typed_get::<Session>()
+                                .map(|session| session.id().clone())
+                        });
+                        Loop::Continue((client, setups, session))
+                    }))
+                },
+            )
+        })
+        .and_then(move |(client, session)| {
+            let mut play = match play {
+                Some(play) => play,
+                None => return Either::A(future::ok((client, session, None))),
+            };
+
+            if let Some(session) = session.as_ref() {
+                play.headers_mut().typed_insert(session_header(session));
+            }
+
+            if let Some(position) = position {
+                let end = play
+                    .headers()
+                    .typed_get::<Range>()
+                    .and_then(|range| range.end());
+                play.headers_mut()
+                    .typed_insert(Range::new(NPTTime::Time(position), end));
+            }
+
+            Either::B(send(client, play.clone()).map(move |(client, response)| {
+                let playback = Playback::new(&play, &response);
+                (client, session, Some(playback))
+            }))
+        })
+        .and_then(move |(client, session, playback)| {
+            let state = state.upgrade().ok_or(())?;
+
+            {
+                let mut state = state.lock().unwrap();
+                let presentation = &mut state.presentation;
+                let position = playback.as_ref().and_then(|playback| playback.start);
+                presentation.playback = playback;
+                presentation.resume_position = None;
+                presentation.session = session;
+                state.send_event(ReconnectEvent::Resumed { position });
+            }
+
+            attach(&state, client, rx_interleaved_data);
+            Ok(())
+        });
+
+    Either::B(future)
+}
+
+/// Sends the given request, failing unless the response is successful.
+fn send(
+    mut client: Client,
+    request: Request<BytesMut>,
+) -> impl Future<Item = (Client, Response<BytesMut>), Error = ()> {
+    client
+        .send_request(request)
+        .map_err(|_| ())
+        .and_then(move |response| {
+            if response.status_code().is_success() {
+                Ok((client, response))
+            } else {
+                Err(())
+            }
+        })
+}
+
+/// Returns the `"Session"` header to send in requests for the given session.
+fn session_header(session: &SessionID) -> Session {
+    Session::without_timeout(session.as_str())
+        .expect("received session identifiers should be valid")
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::VecDeque, convert::TryFrom};
+
+    use tokio::runtime::Runtime;
+
+    use super::*;
+    use crate::{
+        header::value::HeaderValue,
+        status::StatusCode,
+        testing::mock::{Expectation, MockServer},
+        uri::request::URI,
+    };
+
+    fn request(method: Method, session: Option<&str>) -> Request<BytesMut> {
+        let mut builder = Request::<()>::builder()
+            .with_method(method)
+            .with_uri(URI::try_from("rtsp://example.com/stream").unwrap());
+
+        if let Some(session) = session {
+            builder =
+                builder.with_header(HeaderName::Session, HeaderValue::try_from(session).unwrap());
+        }
+
+        builder.with_body(BytesMut::new()).build().unwrap()
+    }
+
+    fn response(headers: &[(HeaderName, &str)]) -> Response<BytesMut> {
+        let mut builder = Response::<()>::builder().with_status_code(StatusCode::OK);
+
+        for (name, value) in headers {
+            builder = builder.with_header(name.clone(), HeaderValue::try_from(*value).unwrap());
+        }
+
+        builder.with_body(BytesMut::new()).build().unwrap()
+    }
+
+    fn script(session: &str, play_range: &str) -> MockServer {
+        MockServer::new()
+            .with_expectation(Expectation::new(Method::Describe))
+            .with_expectation(
+                Expectation::new(Method::Setup)
+                    .with_response(response(&[(HeaderName::Session, session)])),
+            )
+            .with_expectation(
+                Expectation::new(Method::Play)
+                    .with_header(HeaderName::Session, HeaderValue::try_from(session).unwrap())
+                    .with_response(response(&[(HeaderName::Range, play_range)])),
+            )
+    }
+
+    #[test]
+    fn test_backoff_delay() {
+        let backoff = Backoff::new()
+            .with_initial_delay(Duration::from_millis(100))
+            .with_jitter(0.0)
+            .with_max_delay(Duration::from_secs(1));
+
+        assert_eq!(backoff.delay(1), Duration::from_millis(100));
+        assert_eq!(backoff.delay(2), Duration::from_millis(200));
+        assert_eq!(backoff.delay(4), Duration::from_millis(800));
+        assert_eq!(backoff.delay(5), Duration::from_secs(1));
+        assert_eq!(backoff.delay(u32::MAX), Duration::from_secs(1));
+
+        let backoff = backoff.with_jitter(0.5);
+
+        for _ in 0..100 {
+            let delay = backoff.delay(2);
+            assert!(delay > Duration::from_millis(100) && delay <= Duration::from_millis(200));
+        }
+
+        assert_eq!(Backoff::new().with_jitter(2.0).jitter(), 1.0);
+        assert_eq!(Backoff::new().with_jitter(f64::NAN).jitter(), 0.0);
+        assert_eq!(
+            backoff.with_jitter(f64::NAN).delay(2),
+            Duration::from_millis(200)
+        );
+        assert_eq!(Backoff::new().with_jitter(f64::INFINITY).jitter(), 0.0);
+    }
+
+    #[test]
+    fn test_reconnect_resumes_playback() {
+        let (first, first_handle) = script("11111111", "npt=10-")
+            .with_interleaved_data(InterleavedData::new(0, vec![0x80, 0x60]))
+            .with_close()
+            .duplex();
+        let (second, second_handle) = script("22222222", "npt=10-")
+            .with_expectation(Expectation::new(Method::Teardown).with_header(
+                HeaderName::Session,
+                HeaderValue::try_from("22222222").unwrap(),
+            ))
+            .duplex();
+        let transports = Arc::new(Mutex::new(VecDeque::from(vec![first, second])));
+        let address = "127.0.0.1:554".parse().unwrap();
+        let backoff = Backoff::new()
+            .with_initial_delay(Duration::from_millis(10))
+            .with_max_attempts(Some(3));
+
+        let mut runtime = Runtime::new().unwrap();
+        let mut client = runtime
+            .block_on(ReconnectingClient::connect_with(
+                move || {
+                    let transports = transports.clone();
+
+                    future::lazy(move || match transports.lock().unwrap().pop_front() {
+                        Some(transport) => Ok(Client::spawn(transport, address)),
+                        None => Err(io::ErrorKind::NotConnected.into()),
+                    })
+                },
+                backoff,
+            ))
+            .unwrap();
+        let events = client.events();
+        let interleaved_data = client.interleaved_data();
+
+        for method in [Method::Describe, Method::Setup, Method::Play] {
+            let session = if method == Method::Play {
+                Some("11111111")
+            } else {
+                None
+            };
+            let response = runtime
+                .block_on(client.send_request(request(method, session)))
+                .unwrap();
+            assert_eq!(response.status_code(), StatusCode::OK);
+        }
+
+        let (data, _) = runtime
+            .block_on(interleaved_data.into_future().map_err(|_| ()))
+            .unwrap();
+        assert_eq!(data.unwrap().channel(), 0);
+
+        let events = runtime.block_on(events.take(3).collect()).unwrap();
+        assert_eq!(events[0], ReconnectEvent::Disconnected);
+        assert!(matches!(
+            events[1],
+            ReconnectEvent::Reconnecting { attempt: 1, .. }
+        ));
+
+        match events[2] {
+            ReconnectEvent::Resumed {
+                position: Some(position),
+            } => assert!(position >= Duration::from_secs(10)),
+            ref event => panic!("unexpected event {:?}", event),
+        }
+
+        // Requests still referring to the original session are sent with the new one.
+        let response = runtime
+            .block_on(client.send_request(request(Method::Teardown, Some("11111111"))))
+            .unwrap();
+        assert_eq!(response.status_code(), StatusCode::OK);
+
+        assert!(first_handle.verify().is_ok());
+        assert!(second_handle.verify().is_ok());
+
+        drop(client);
+        runtime.shutdown_on_idle().wait().unwrap();
+    }
+}
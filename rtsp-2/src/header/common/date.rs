@@ -163,7 +163,7 @@ fn parse_day(value: &str) -> Result<(u32, &str), DateTimeError> {
 }
 
 fn parse_day_of_week(value: &str) -> Result<(Weekday, &str), DateTimeError> {
-    if value.len() < 3 {
+    if value.len() < 3 || !value.is_char_boundary(3) {
         return Err(DateTimeError);
     }
 
@@ -235,7 +235,7 @@ fn parse_folding_whitespace(value: &str) -> Result<(&str, &str), DateTimeError>
 }
 
 fn parse_month(value: &str) -> Result<(u32, &str), DateTimeError> {
-    if value.len() < 3 {
+    if value.len() < 3 || !value.is_char_boundary(3) {
         return Err(DateTimeError);
     }
 
@@ -504,6 +504,7 @@ mod test {
         assert_eq!(parse_day_of_week("sAturDay"), Ok((Weekday::Sat, "urDay")));
         assert!(parse_day_of_week("").is_err());
         assert!(parse_day_of_week("test").is_err());
+        assert!(parse_day_of_week("Mo\u{e9}").is_err());
     }
 
     #[test]
@@ -531,6 +532,7 @@ mod test {
         assert_eq!(parse_month("jUly"), Ok((7, "y")));
         assert!(parse_month("").is_err());
         assert!(parse_month("test").is_err());
+        assert!(parse_month("J\u{1F600}n").is_err());
     }
 
     #[test]
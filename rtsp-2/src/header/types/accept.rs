@@ -124,12 +124,18 @@ impl TypedHeader for Accept {
         let mut accept = LinkedHashSet::new();
         let mut present = false;
         for value in values {
+            present = true;
+
+            // The syntax allows an empty list of media types.
+            if syntax::trim_whitespace(value.as_str()).is_empty() {
+                continue;
+            }
+
             let media = value.as_str().split(',');
             for mtype in media {
                 let media_type = MediaType::try_from(syntax::trim_whitespace(mtype))?;
                 accept.insert(media_type);
             }
-            present = true;
         }
         if present {
             Ok(Some(Accept(accept)))
@@ -211,6 +217,7 @@ impl<'media_type> TryFrom<&'media_type [u8]> for MediaType {
     fn try_from(value: &'media_type [u8]) -> Result<Self, Self::Error> {
         let mut split = value.splitn(2, |&element| element == b';');
         let mediatype = split.next().ok_or(AcceptError::InvalidSyntax)?;
+        let mediatype = syntax::trim_bytes_whitespace(mediatype);
         let raw_utf8 = str::from_utf8(mediatype).map_err(|_| AcceptError::InvalidUtf8)?;
         let mime = raw_utf8.parse().map_err(|_| AcceptError::InvalidSyntax)?;
         let m_type = mime;
@@ -249,7 +256,7 @@ impl<'quality> TryFrom<&'quality [u8]> for QualityParam {
         let stringify = str::from_utf8(value).map_err(|_| AcceptError::InvalidUtf8)?;
         let mut val = stringify.rsplitn(2, "=");
         let quality = val.next().ok_or(AcceptError::InvalidSyntax)?;
-        let quality_param = quality
+        let quality_param = syntax::trim_whitespace(quality)
             .parse::<f32>()
             .map_err(|_| AcceptError::InvalidSyntax)?;
         return Ok(QualityParam::new(quality_param));
@@ -286,3 +293,36 @@ impl fmt::Display for QualityParam {
         write!(f, "q={}", q_val.to_string())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::{convert::TryFrom, iter::FromIterator};
+
+    use crate::header::{map::TypedHeader, types::Accept, value::HeaderValue};
+
+    #[test]
+    fn test_accept_round_trip() {
+        let raw_header = vec![HeaderValue::try_from("application/sdp;q=0.5").unwrap()];
+        let typed_header = Accept::decode(&mut raw_header.iter()).unwrap().unwrap();
+
+        let mut encoded = vec![];
+        typed_header.encode(&mut encoded);
+        assert_eq!(
+            encoded,
+            vec![HeaderValue::try_from("application/sdp ;q=0.5").unwrap()]
+        );
+        assert_eq!(
+            Accept::decode(&mut encoded.iter()).unwrap(),
+            Some(typed_header)
+        );
+    }
+
+    #[test]
+    fn test_accept_decode_empty() {
+        let raw_header = vec![HeaderValue::try_from("").unwrap()];
+        assert_eq!(
+            Accept::decode(&mut raw_header.iter()).unwrap(),
+            Some(Accept::from_iter(vec![]))
+        );
+    }
+}
@@ -4,11 +4,18 @@ pub mod content_length;
 pub mod cseq;
 pub mod date;
 pub mod expires;
+pub mod pipelined_requests;
 pub mod public;
+pub mod range;
+#[cfg(test)]
+mod round_trip;
+pub mod rtp_info;
 pub mod session;
 pub mod transport;
+pub mod via;
 
 pub use self::{
     accept::Accept, accept_ranges::AcceptRanges, content_length::ContentLength, cseq::CSeq,
-    date::Date, expires::Expires, public::Public, session::Session,
+    date::Date, expires::Expires, pipelined_requests::PipelinedRequests, public::Public,
+    range::Range, rtp_info::RTPInfo, session::Session, transport::Transport, via::Via,
 };
@@ -0,0 +1,212 @@
+use core::num::IntErrorKind;
+use std::{
+    convert::{Infallible, TryFrom},
+    error::Error,
+    fmt::{self, Display, Formatter},
+    iter::once,
+    ops::Deref,
+};
+
+use rand::{self, Rng};
+
+use crate::header::{map::TypedHeader, name::HeaderName, value::HeaderValue};
+
+/// The maximum size the startup identifier of a pipeline can be.
+pub const MAX_STARTUP_ID: u32 = 99_999_999;
+
+/// The `"Pipelined-Requests"` typed header as described by
+/// [RFC7826](https://tools.ietf.org/html/rfc7826#section-18.33).
+///
+/// Requests carrying the same startup identifier form a pipeline: the ones sent before the
+/// session exists are executed in the context of the session created by the first of them.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct PipelinedRequests(u32);
+
+impl PipelinedRequests {
+    /// Constructs a random startup identifier, so that pipelines of different clients are
+    /// unlikely to share it.
+    pub fn random() -> Self {
+        let startup_id = rand::thread_rng().gen_range(0, MAX_STARTUP_ID + 1);
+        PipelinedRequests(startup_id)
+    }
+}
+
+impl Deref for PipelinedRequests {
+    type Target = u32;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl TryFrom<u32> for PipelinedRequests {
+    type Error = PipelinedRequestsError;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        if value > MAX_STARTUP_ID {
+            Err(PipelinedRequestsError::ExceedsMaximumLength)
+        } else {
+            Ok(PipelinedRequests(value))
+        }
+    }
+}
+
+impl TypedHeader for PipelinedRequests {
+    type DecodeError = PipelinedRequestsError;
+
+    /// Converts the raw header values to the [`PipelinedRequests`] header type. Based on the syntax
+    /// provided by [RFC7826](https://tools.ietf.org/html/rfc7826#section-20), this header has the
+    /// following syntax:
+    ///
+    /// ```text
+    /// DIGIT = %x30-39 ; any US-ASCII digit "0".."9"
+    /// CR = %x0D ; US-ASCII CR, carriage return (13)
+    /// LF = %x0A  ; US-ASCII LF, linefeed (10)
+    /// SP = %x20  ; US-ASCII SP, space (32)
+    /// HT = %x09  ; US-ASCII HT, horizontal-tab (9)
+    /// CRLF = CR LF
+    /// LWS = [CRLF] 1*( SP / HT ) ; Line-breaking whitespace
+    /// SWS = [LWS] ; Separating whitespace
+    /// HCOLON = *( SP / HT ) ":" SWS
+    /// Pipelined-Requests = "Pipelined-Requests" HCOLON startup-id
+    /// startup-id = 1*8DIGIT
+    /// ```
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::convert::TryFrom;
+    ///
+    /// use rtsp::header::map::TypedHeader;
+    /// use rtsp::header::types::PipelinedRequests;
+    /// use rtsp::header::value::HeaderValue;
+    ///
+    /// let raw_header: Vec<HeaderValue> = vec![];
+    /// assert_eq!(PipelinedRequests::decode(&mut raw_header.iter()).unwrap(), None);
+    ///
+    /// let typed_header = PipelinedRequests::try_from(7122).unwrap();
+    /// let raw_header = vec![HeaderValue::try_from("7122").unwrap()];
+    /// assert_eq!(
+    ///     PipelinedRequests::decode(&mut raw_header.iter()).unwrap(),
+    ///     Some(typed_header)
+    /// );
+    ///
+    /// let raw_header = vec![HeaderValue::try_from("123456789").unwrap()];
+    /// assert!(PipelinedRequests::decode(&mut raw_header.iter()).is_err());
+    /// ```
+    fn decode<'header, Iter>(values: &mut Iter) -> Result<Option<Self>, Self::DecodeError>
+    where
+        Iter: Iterator<Item = &'header HeaderValue>,
+    {
+        let value = match values.next() {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+
+        if values.next().is_some() {
+            return Err(PipelinedRequestsError::MoreThanOneHeader);
+        }
+
+        let startup_id = value
+            .as_str()
+            .parse::<u32>()
+            .map_err(|error| PipelinedRequestsError::try_from(*error.kind()).unwrap())?;
+        PipelinedRequests::try_from(startup_id).map(Some)
+    }
+
+    /// Converts the [`PipelinedRequests`] type to raw header values.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::convert::TryFrom;
+    ///
+    /// use rtsp::header::map::TypedHeader;
+    /// use rtsp::header::types::PipelinedRequests;
+    /// use rtsp::header::value::HeaderValue;
+    ///
+    /// let typed_header = PipelinedRequests::try_from(7122).unwrap();
+    /// let expected_raw_header = vec![HeaderValue::try_from("7122").unwrap()];
+    /// let mut raw_header = vec![];
+    /// typed_header.encode(&mut raw_header);
+    /// assert_eq!(raw_header, expected_raw_header);
+    /// ```
+    fn encode<Target>(&self, values: &mut Target)
+    where
+        Target: Extend<HeaderValue>,
+    {
+        // Unsafe: In order for this to be safe, we must ensure that `value` contains no unprintable
+        // ASCII-US characters and that all linebreaks of the form `"\r\n"` are followed by a space
+        // or tab. Since [`PipelinedRequests`] serializes into a number, it satisfies the
+        // constraints.
+
+        values.extend(once(unsafe {
+            HeaderValue::from_string_unchecked(self.0.to_string())
+        }))
+    }
+
+    /// Returns the statically assigned [`HeaderName`] for this header.
+    fn header_name() -> &'static HeaderName {
+        &HeaderName::PipelinedRequests
+    }
+}
+
+/// A possible error value when converting to a [`PipelinedRequests`] from [`HeaderName`]s.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum PipelinedRequestsError {
+    /// The `"Pipelined-Requests"` header was empty.
+    Empty,
+
+    /// The `"Pipelined-Requests"` header was parsed, but the startup identifier exceeds the
+    /// maximum length it can be.
+    ExceedsMaximumLength,
+
+    /// The `"Pipelined-Requests"` header contained an invalid digit.
+    InvalidDigit,
+
+    /// There was more than one `"Pipelined-Requests"` header.
+    MoreThanOneHeader,
+
+    /// The `"Pipelined-Requests"` value could not be parsed as it overflowed.
+    Overflow,
+}
+
+impl Display for PipelinedRequestsError {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        use self::PipelinedRequestsError::*;
+
+        match self {
+            Empty => write!(formatter, "empty startup identifier"),
+            ExceedsMaximumLength => {
+                write!(formatter, "startup identifier exceeds maximum length")
+            }
+            InvalidDigit => write!(formatter, "invalid startup identifier digit"),
+            MoreThanOneHeader => write!(formatter, "more than one pipelined requests header"),
+            Overflow => write!(formatter, "startup identifier overflow"),
+        }
+    }
+}
+
+impl Error for PipelinedRequestsError {}
+
+impl From<Infallible> for PipelinedRequestsError {
+    fn from(_: Infallible) -> Self {
+        PipelinedRequestsError::Empty
+    }
+}
+
+impl TryFrom<IntErrorKind> for PipelinedRequestsError {
+    type Error = ();
+
+    fn try_from(value: IntErrorKind) -> Result<Self, Self::Error> {
+        use self::PipelinedRequestsError::*;
+
+        match value {
+            IntErrorKind::Empty => Ok(Empty),
+            IntErrorKind::InvalidDigit => Ok(InvalidDigit),
+            IntErrorKind::PosOverflow => Ok(Overflow),
+            _ => Err(()),
+        }
+    }
+}
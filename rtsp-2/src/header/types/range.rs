@@ -0,0 +1,363 @@
+use std::{
+    convert::{Infallible, TryFrom},
+    error::Error,
+    fmt::{self, Display, Formatter},
+    iter::once,
+    time::Duration,
+};
+
+use crate::{
+    header::{map::TypedHeader, name::HeaderName, value::HeaderValue},
+    syntax,
+};
+
+/// The `"Range"` typed header as described by
+/// [RFC7826](https://tools.ietf.org/html/rfc7826#section-18.40).
+///
+/// Only ranges in the Normal Play Time (NPT) format are supported, which is the format used by
+/// on-demand presentations. Either the start or the end of the range may be omitted, but not both.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Range {
+    /// The end of the range, if given.
+    end: Option<NPTTime>,
+
+    /// The start of the range, if given.
+    start: Option<NPTTime>,
+}
+
+impl Range {
+    /// Returns the end of the range, if given.
+    pub fn end(&self) -> Option<NPTTime> {
+        self.end
+    }
+
+    /// Constructs a new range starting at the given time and ending at the given time, or at the
+    /// end of the presentation if no end is given.
+    pub fn new(start: NPTTime, end: Option<NPTTime>) -> Self {
+        Range {
+            end,
+            start: Some(start),
+        }
+    }
+
+    /// Returns the start of the range, if given.
+    pub fn start(&self) -> Option<NPTTime> {
+        self.start
+    }
+
+    /// Constructs a new range from the current position up to the given time.
+    pub fn until(end: NPTTime) -> Self {
+        Range {
+            end: Some(end),
+            start: None,
+        }
+    }
+}
+
+impl Display for Range {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        formatter.write_str("npt=")?;
+
+        if let Some(start) = self.start {
+            start.fmt(formatter)?;
+        }
+
+        formatter.write_str("-")?;
+
+        if let Some(end) = self.end {
+            end.fmt(formatter)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl TypedHeader for Range {
+    type DecodeError = RangeError;
+
+    /// Converts the raw header values to the [`Range`] header type. Based on the syntax provided
+    /// by [RFC7826](https://tools.ietf.org/html/rfc7826#section-20), this header has the following
+    /// syntax for NPT ranges:
+    ///
+    /// ```text
+    /// Range = "Range" HCOLON ranges-spec
+    /// ranges-spec = npt-range / utc-range / smpte-range / range-ext
+    /// npt-range = "npt" [EQUAL npt-range-spec]
+    /// npt-range-spec = ( npt-time "-" [ npt-time ] ) / ( "-" npt-time )
+    /// npt-time = "now" / npt-sec / npt-hhmmss / npt-hhmmss-comp
+    /// npt-sec = 1*19DIGIT [ "." 1*9DIGIT ]
+    /// npt-hhmmss = npt-hh ":" npt-mm ":" npt-ss [ "." 1*9DIGIT ]
+    /// npt-hh = 2*19DIGIT ; any positive number
+    /// npt-mm = 2*2DIGIT ; 0-59
+    /// npt-ss = 2*2DIGIT ; 0-59
+    /// ```
+    ///
+    /// The `"time"` parameter defined by [RFC2326](https://tools.ietf.org/html/rfc2326#section-12.29)
+    /// is accepted but ignored.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::convert::TryFrom;
+    /// use std::time::Duration;
+    ///
+    /// use rtsp::header::map::TypedHeader;
+    /// use rtsp::header::types::range::{NPTTime, Range};
+    /// use rtsp::header::value::HeaderValue;
+    ///
+    /// let raw_header = vec![HeaderValue::try_from("npt=0:01:05.5-").unwrap()];
+    /// let typed_header = Range::decode(&mut raw_header.iter()).unwrap().unwrap();
+    /// assert_eq!(typed_header.start(), Some(NPTTime::Time(Duration::from_millis(65500))));
+    /// assert_eq!(typed_header.end(), None);
+    /// ```
+    fn decode<'header, Iter>(values: &mut Iter) -> Result<Option<Self>, Self::DecodeError>
+    where
+        Iter: Iterator<Item = &'header HeaderValue>,
+    {
+        let value = match values.next() {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+
+        if values.next().is_some() {
+            return Err(RangeError::MultipleRanges);
+        }
+
+        Range::try_from(value.as_str()).map(Some)
+    }
+
+    /// Converts the [`Range`] type to raw header values.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::convert::TryFrom;
+    /// use std::time::Duration;
+    ///
+    /// use rtsp::header::map::TypedHeader;
+    /// use rtsp::header::types::range::{NPTTime, Range};
+    /// use rtsp::header::value::HeaderValue;
+    ///
+    /// let typed_header = Range::new(
+    ///     NPTTime::Time(Duration::from_millis(1500)),
+    ///     Some(NPTTime::Time(Duration::from_secs(20))),
+    /// );
+    /// let mut raw_header = vec![];
+    /// typed_header.encode(&mut raw_header);
+    /// assert_eq!(raw_header, vec![HeaderValue::try_from("npt=1.5-20").unwrap()]);
+    /// ```
+    fn encode<Target>(&self, values: &mut Target)
+    where
+        Target: Extend<HeaderValue>,
+    {
+        // Unsafe Justification
+        //
+        // Header values must be valid UTF-8, and since the range is formatted only from digits and
+        // the characters `"npt=-.:"`, it satisfies the constraints.
+        values.extend(once(unsafe {
+            HeaderValue::from_string_unchecked(self.to_string())
+        }));
+    }
+
+    /// Returns the statically assigned [`HeaderName`] for this header.
+    fn header_name() -> &'static HeaderName {
+        &HeaderName::Range
+    }
+}
+
+impl<'range> TryFrom<&'range str> for Range {
+    type Error = RangeError;
+
+    fn try_from(value: &'range str) -> Result<Self, Self::Error> {
+        let value = syntax::trim_whitespace(value.split(';').next().unwrap_or(""));
+        let mut parts = value.splitn(2, '=');
+        let unit = syntax::trim_whitespace(parts.next().unwrap_or(""));
+
+        if !unit.eq_ignore_ascii_case("npt") {
+            return Err(RangeError::UnsupportedFormat);
+        }
+
+        let specification = syntax::trim_whitespace(parts.next().ok_or(RangeError::InvalidRange)?);
+        let separator = specification.find('-').ok_or(RangeError::InvalidRange)?;
+        let start = syntax::trim_whitespace(&specification[..separator]);
+        let end = syntax::trim_whitespace(&specification[separator + 1..]);
+        let start = if start.is_empty() {
+            None
+        } else {
+            Some(NPTTime::try_from(start)?)
+        };
+        let end = if end.is_empty() {
+            None
+        } else {
+            Some(NPTTime::try_from(end)?)
+        };
+
+        match (start, end) {
+            (None, None) => Err(RangeError::InvalidRange),
+            (Some(NPTTime::Time(start)), Some(NPTTime::Time(end))) if start > end => {
+                Err(RangeError::InvalidRange)
+            }
+            _ => Ok(Range { end, start }),
+        }
+    }
+}
+
+/// A position in Normal Play Time, relative to the beginning of the presentation.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum NPTTime {
+    /// The current position of a live presentation.
+    Now,
+
+    /// An offset from the beginning of the presentation.
+    Time(Duration),
+}
+
+impl Display for NPTTime {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        match self {
+            NPTTime::Now => formatter.write_str("now"),
+            NPTTime::Time(time) => {
+                write!(formatter, "{}", time.as_secs())?;
+
+                if time.subsec_nanos() > 0 {
+                    let fraction = format!("{:09}", time.subsec_nanos());
+                    write!(formatter, ".{}", fraction.trim_end_matches('0'))?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+impl<'time> TryFrom<&'time str> for NPTTime {
+    type Error = RangeError;
+
+    fn try_from(value: &'time str) -> Result<Self, Self::Error> {
+        if value.eq_ignore_ascii_case("now") {
+            return Ok(NPTTime::Now);
+        }
+
+        let (whole, fraction) = match value.find('.') {
+            Some(index) => (&value[..index], Some(&value[index + 1..])),
+            None => (value, None),
+        };
+        let mut seconds = 0u64;
+
+        for (index, part) in whole.split(':').enumerate() {
+            // Seconds are given by the last part, the hours may be of any length.
+            if index > 2 || part.is_empty() || !part.bytes().all(|byte| byte.is_ascii_digit()) {
+                return Err(RangeError::InvalidNPTTime);
+            }
+
+            let part = part
+                .parse::<u64>()
+                .map_err(|_| RangeError::InvalidNPTTime)?;
+
+            if index > 0 && part > 59 {
+                return Err(RangeError::InvalidNPTTime);
+            }
+
+            seconds = seconds
+                .checked_mul(60)
+                .and_then(|seconds| seconds.checked_add(part))
+                .ok_or(RangeError::InvalidNPTTime)?;
+        }
+
+        let nanoseconds = match fraction {
+            Some(fraction) => {
+                if fraction.is_empty()
+                    || fraction.len() > 9
+                    || !fraction.bytes().all(|byte| byte.is_ascii_digit())
+                {
+                    return Err(RangeError::InvalidNPTTime);
+                }
+
+                format!("{:0<9}", fraction)
+                    .parse::<u32>()
+                    .map_err(|_| RangeError::InvalidNPTTime)?
+            }
+            None => 0,
+        };
+
+        Ok(NPTTime::Time(Duration::new(seconds, nanoseconds)))
+    }
+}
+
+/// Possible errors that can occur when decoding the `"Range"` header.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum RangeError {
+    /// A time in the range was not a valid NPT time.
+    InvalidNPTTime,
+
+    /// The range did not have a start or an end, or the start was after the end.
+    InvalidRange,
+
+    /// The header was given more than once.
+    MultipleRanges,
+
+    /// The range was given in a format other than NPT.
+    UnsupportedFormat,
+}
+
+impl Display for RangeError {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        use self::RangeError::*;
+
+        match self {
+            InvalidNPTTime => write!(formatter, "invalid NPT time"),
+            InvalidRange => write!(formatter, "invalid range"),
+            MultipleRanges => write!(formatter, "multiple ranges"),
+            UnsupportedFormat => write!(formatter, "unsupported format"),
+        }
+    }
+}
+
+impl Error for RangeError {}
+
+impl From<Infallible> for RangeError {
+    fn from(value: Infallible) -> Self {
+        match value {}
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{convert::TryFrom, time::Duration};
+
+    use crate::header::types::range::{NPTTime, Range, RangeError};
+
+    #[test]
+    fn test_range_npt() {
+        let range = Range::try_from("npt=now-").unwrap();
+        assert_eq!(range.start(), Some(NPTTime::Now));
+        assert_eq!(range.end(), None);
+        assert_eq!(range.to_string(), "npt=now-");
+
+        let range = Range::try_from("npt=-12.25;time=19970123T153600Z").unwrap();
+        assert_eq!(range.start(), None);
+        assert_eq!(
+            range.end(),
+            Some(NPTTime::Time(Duration::from_millis(12250)))
+        );
+        assert_eq!(range.to_string(), "npt=-12.25");
+
+        let range = Range::try_from("npt=01:00:00-").unwrap();
+        assert_eq!(
+            range.start(),
+            Some(NPTTime::Time(Duration::from_secs(3600)))
+        );
+
+        assert_eq!(Range::try_from("npt=-"), Err(RangeError::InvalidRange));
+        assert_eq!(Range::try_from("npt=20-10"), Err(RangeError::InvalidRange));
+        assert_eq!(
+            Range::try_from("npt=0:60:00-"),
+            Err(RangeError::InvalidNPTTime)
+        );
+        assert_eq!(
+            Range::try_from("smpte=10:07:00-"),
+            Err(RangeError::UnsupportedFormat)
+        );
+    }
+}
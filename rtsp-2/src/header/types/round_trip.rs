@@ -0,0 +1,546 @@
+//! Property-based round-trip tests shared by all typed headers.
+//!
+//! Every typed header implements [`RoundTrip`] below and registers a test with the
+//! `round_trip_tests!` invocation at the bottom of this module. The registered test checks that
+//! decoding an encoded header yields the header again, and that the encoding still decodes to the
+//! same header after optional whitespace is inserted around its separators and, for headers whose
+//! syntax is case-insensitive, after the case of its letters is varied.
+
+use std::{
+    convert::TryFrom,
+    fmt::{Debug, Display},
+    time::Duration,
+};
+
+use chrono::{TimeZone, Utc};
+use itertools::Itertools;
+use proptest::{collection::vec, option, prelude::*, sample::select};
+
+use crate::{
+    header::{
+        map::TypedHeader,
+        types::{
+            accept::{MediaType, QualityParam},
+            accept_ranges::RangeFormat,
+            transport::{
+                Address, Connection, DeliveryType, Interleaved, Layers, Mode, Setup, TransportSpec,
+                MIKEY,
+            },
+            Accept, AcceptRanges, CSeq, Date, Expires, PipelinedRequests, Public, Session,
+            Transport,
+        },
+        value::HeaderValue,
+    },
+    method::Method,
+    session::DEFAULT_SESSION_TIMEOUT,
+};
+
+/// A typed header that can be checked by the shared round-trip harness.
+pub(crate) trait RoundTrip: TypedHeader + Clone + Debug + PartialEq + Sized {
+    /// Whether the letter case of an encoded header is insignificant when decoding it.
+    const CASE_INSENSITIVE: bool;
+
+    /// The separators around which optional whitespace is allowed in an encoded header.
+    const SEPARATORS: &'static [char];
+
+    /// Returns a strategy generating arbitrary headers.
+    fn arbitrary() -> BoxedStrategy<Self>;
+}
+
+impl RoundTrip for Accept {
+    const CASE_INSENSITIVE: bool = true;
+    const SEPARATORS: &'static [char] = &[',', ';', '='];
+
+    fn arbitrary() -> BoxedStrategy<Self> {
+        let m_type = prop_oneof![
+            Just("*/*".to_string()),
+            (
+                select(&["application", "audio", "image", "text", "video"][..]),
+                "\\*|[a-z][a-z0-9.+-]{0,15}"
+            )
+                .prop_map(|(m_type, m_subtype)| format!("{}/{}", m_type, m_subtype)),
+        ];
+        let quality = option::of(
+            (0u32..=1000).prop_map(|q_value| QualityParam::new(q_value as f32 / 1000.0)),
+        );
+        let media_type = (m_type, quality)
+            .prop_map(|(m_type, quality)| MediaType::new(m_type.parse().unwrap(), quality));
+        vec(media_type, 0..5)
+            .prop_map(|media_types| media_types.into_iter().collect())
+            .boxed()
+    }
+}
+
+impl RoundTrip for AcceptRanges {
+    const CASE_INSENSITIVE: bool = true;
+    const SEPARATORS: &'static [char] = &[','];
+
+    fn arbitrary() -> BoxedStrategy<Self> {
+        vec(range_format(), 1..5)
+            .prop_map(|range_formats| range_formats.into_iter().collect())
+            .boxed()
+    }
+}
+
+impl RoundTrip for CSeq {
+    const CASE_INSENSITIVE: bool = false;
+    const SEPARATORS: &'static [char] = &[];
+
+    fn arbitrary() -> BoxedStrategy<Self> {
+        (0..=999_999_999u32)
+            .prop_map(|cseq| CSeq::try_from(cseq).unwrap())
+            .boxed()
+    }
+}
+
+impl RoundTrip for Date {
+    const CASE_INSENSITIVE: bool = true;
+    const SEPARATORS: &'static [char] = &[',', ' '];
+
+    fn arbitrary() -> BoxedStrategy<Self> {
+        timestamp()
+            .prop_map(|timestamp| Date::from(Utc.timestamp_opt(timestamp, 0).unwrap()))
+            .boxed()
+    }
+}
+
+impl RoundTrip for Expires {
+    const CASE_INSENSITIVE: bool = true;
+    const SEPARATORS: &'static [char] = &[',', ' '];
+
+    fn arbitrary() -> BoxedStrategy<Self> {
+        timestamp()
+            .prop_map(|timestamp| Expires::from(Utc.timestamp_opt(timestamp, 0).unwrap()))
+            .boxed()
+    }
+}
+
+impl RoundTrip for PipelinedRequests {
+    const CASE_INSENSITIVE: bool = false;
+    const SEPARATORS: &'static [char] = &[];
+
+    fn arbitrary() -> BoxedStrategy<Self> {
+        (0..=99_999_999u32)
+            .prop_map(|startup_id| PipelinedRequests::try_from(startup_id).unwrap())
+            .boxed()
+    }
+}
+
+impl RoundTrip for Public {
+    const CASE_INSENSITIVE: bool = true;
+    const SEPARATORS: &'static [char] = &[','];
+
+    fn arbitrary() -> BoxedStrategy<Self> {
+        vec(method(), 1..5)
+            .prop_map(|methods| methods.into_iter().collect())
+            .boxed()
+    }
+}
+
+impl RoundTrip for Session {
+    const CASE_INSENSITIVE: bool = false;
+    const SEPARATORS: &'static [char] = &[';', '='];
+
+    fn arbitrary() -> BoxedStrategy<Self> {
+        // The default timeout is omitted when encoding, so it decodes as no timeout at all.
+        let timeout = option::of(
+            (0..=u64::from(u32::MAX))
+                .prop_filter("default timeout", |&timeout| {
+                    timeout != DEFAULT_SESSION_TIMEOUT.as_secs()
+                })
+                .prop_map(Duration::from_secs),
+        );
+        ("[a-zA-Z0-9$_.+-]{8,64}", timeout)
+            .prop_map(|(id, timeout)| match timeout {
+                Some(timeout) => Session::with_timeout(id.as_str(), timeout).unwrap(),
+                None => Session::without_timeout(id.as_str()).unwrap(),
+            })
+            .boxed()
+    }
+}
+
+impl RoundTrip for Transport {
+    const CASE_INSENSITIVE: bool = false;
+    const SEPARATORS: &'static [char] = &[',', ';'];
+
+    fn arbitrary() -> BoxedStrategy<Self> {
+        vec(transport_spec(), 1..4)
+            .prop_map(|specs| {
+                let mut transport = Transport::new();
+                transport.extend(specs);
+                transport
+            })
+            .boxed()
+    }
+}
+
+/// Encodes the given header into strings.
+fn encode<H>(header: &H) -> Vec<String>
+where
+    H: TypedHeader,
+{
+    let mut values = Vec::<HeaderValue>::new();
+    header.encode(&mut values);
+    values
+        .iter()
+        .map(|value| value.as_str().to_string())
+        .collect()
+}
+
+/// Returns a strategy generating a header along with its encoding, varied as allowed by the
+/// header's syntax.
+fn round_trip<H>() -> impl Strategy<Value = (H, Vec<String>)>
+where
+    H: RoundTrip,
+{
+    H::arbitrary().prop_flat_map(|header| {
+        let variations = encode(&header)
+            .into_iter()
+            .map(|value| vary(value, H::CASE_INSENSITIVE, H::SEPARATORS))
+            .collect::<Vec<_>>();
+        (Just(header), variations)
+    })
+}
+
+/// Checks that the header decodes from both its encoding and the given variation of it.
+fn assert_round_trip<H>(header: &H, variation: &[String]) -> Result<(), TestCaseError>
+where
+    H: RoundTrip,
+    H::DecodeError: Debug + PartialEq,
+{
+    let encoded = encode(header);
+
+    for values in &[encoded, variation.to_vec()] {
+        let values = values
+            .iter()
+            .map(|value| HeaderValue::try_from(value.as_str()).unwrap())
+            .collect::<Vec<_>>();
+        prop_assert_eq!(H::decode(&mut values.iter()), Ok(Some(header.clone())));
+    }
+
+    Ok(())
+}
+
+/// Checks that the parameter parses from its display representation and from the given variation
+/// of it.
+fn assert_parameter_round_trip<T>(parameter: &T, variation: &str) -> Result<(), TestCaseError>
+where
+    T: Debug + Display + PartialEq + for<'value> TryFrom<&'value str>,
+{
+    for value in &[parameter.to_string().as_str(), variation] {
+        match T::try_from(value) {
+            Ok(decoded) => prop_assert_eq!(&decoded, parameter),
+            Err(_) => prop_assert!(false, "failed to parse {:?}", value),
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns a strategy generating a parameter along with its display representation, varied as
+/// allowed by the parameter's syntax.
+fn parameter_round_trip<T, S>(
+    parameter: S,
+    case_insensitive: bool,
+    separators: &'static [char],
+) -> impl Strategy<Value = (T, String)>
+where
+    S: Strategy<Value = T>,
+    T: Clone + Debug + Display,
+{
+    parameter.prop_flat_map(move |parameter| {
+        let variation = vary(parameter.to_string(), case_insensitive, separators);
+        (Just(parameter), variation)
+    })
+}
+
+/// Returns a strategy inserting optional whitespace around the unquoted separators of the value
+/// and, if requested, varying the case of its letters.
+fn vary(
+    value: String,
+    case_insensitive: bool,
+    separators: &'static [char],
+) -> impl Strategy<Value = String> {
+    let mut quoted = false;
+    let positions = value
+        .char_indices()
+        .filter(|&(_, char_)| {
+            if char_ == '"' {
+                quoted = !quoted;
+            }
+
+            !quoted && separators.contains(&char_)
+        })
+        .map(|(index, _)| index)
+        .collect::<Vec<_>>();
+    let paddings = vec(("[ \t]{0,2}", "[ \t]{0,2}"), positions.len());
+    let flips = vec(any::<bool>(), value.len());
+
+    (paddings, flips).prop_map(move |(paddings, flips)| {
+        let mut varied = String::with_capacity(value.len());
+        let mut paddings = positions.iter().zip(paddings).peekable();
+
+        for ((index, char_), flip) in value.char_indices().zip(flips) {
+            let char_ = match char_ {
+                char_ if case_insensitive && flip && char_.is_ascii_lowercase() => {
+                    char_.to_ascii_uppercase()
+                }
+                char_ if case_insensitive && flip && char_.is_ascii_uppercase() => {
+                    char_.to_ascii_lowercase()
+                }
+                char_ => char_,
+            };
+
+            match paddings.peek() {
+                Some(&(&position, _)) if position == index => {
+                    let (_, (before, after)) = paddings.next().unwrap();
+                    varied.push_str(&before);
+                    varied.push(char_);
+                    varied.push_str(&after);
+                }
+                _ => varied.push(char_),
+            }
+        }
+
+        varied
+    })
+}
+
+fn address() -> impl Strategy<Value = Address> {
+    let port = option::of(any::<u16>());
+    let host = prop_oneof![
+        any::<[u8; 4]>()
+            .prop_map(|octets| format!("{}.{}.{}.{}", octets[0], octets[1], octets[2], octets[3])),
+        "[a-z][a-z0-9-]{0,10}(\\.[a-z][a-z0-9-]{0,10}){0,3}",
+        Just(String::new()),
+    ];
+    let host_port = (host, port)
+        .prop_filter("empty address", |(host, port)| {
+            !host.is_empty() || port.is_some()
+        })
+        .prop_map(|(host, port)| match port {
+            Some(port) => format!("{}:{}", host, port),
+            None => host,
+        });
+    let extension = "[a-z][a-z0-9 !#-\\[\\]-~]{0,20}:[a-z]+";
+
+    prop_oneof![host_port, extension]
+        .prop_map(|address| Address::try_from(address.as_str()).unwrap())
+}
+
+fn connection() -> impl Strategy<Value = Connection> {
+    select(&[Connection::Existing, Connection::New][..])
+}
+
+fn delivery_type() -> impl Strategy<Value = DeliveryType> {
+    select(&[DeliveryType::Multicast, DeliveryType::Unicast][..])
+}
+
+fn interleaved() -> impl Strategy<Value = Interleaved> {
+    (any::<u8>(), any::<u8>())
+        .prop_map(|(start, end)| Interleaved::new(start.min(end)..=start.max(end)))
+}
+
+fn method() -> impl Strategy<Value = Method> {
+    prop_oneof![
+        select(
+            &[
+                "DESCRIBE",
+                "GET_PARAMETER",
+                "OPTIONS",
+                "PAUSE",
+                "PLAY",
+                "PLAY_NOTIFY",
+                "REDIRECT",
+                "SET_PARAMETER",
+                "SETUP",
+                "TEARDOWN",
+            ][..]
+        )
+        .prop_map(str::to_string),
+        "[!#-'*+.0-9A-Z^_a-z|~-]{1,16}",
+    ]
+    .prop_filter_map("invalid method", |method| {
+        Method::try_from(method.as_str()).ok()
+    })
+}
+
+fn mode() -> impl Strategy<Value = Mode> {
+    prop_oneof![Just("PLAY".to_string()), "[!#-'*+.0-9A-Z^_a-z|~-]{1,16}"]
+        .prop_filter_map("reserved mode", |mode| Mode::try_from(mode.as_str()).ok())
+}
+
+fn range_format() -> impl Strategy<Value = RangeFormat> {
+    prop_oneof![
+        select(&["clock", "npt", "smpte", "smpte-25", "smpte-30-drop"][..])
+            .prop_map(str::to_string),
+        "[!#-'*+.0-9A-Z^_a-z|~-]{1,16}",
+    ]
+    .prop_map(|range_format| RangeFormat::try_from(range_format.as_str()).unwrap())
+}
+
+fn setup() -> impl Strategy<Value = Setup> {
+    select(&[Setup::Active, Setup::ActPass, Setup::Passive][..])
+}
+
+/// Returns a strategy generating timestamps with years between 1000 and 9999, the range of years
+/// that are encoded with exactly four digits.
+fn timestamp() -> impl Strategy<Value = i64> {
+    -30_610_224_000i64..253_402_300_800
+}
+
+fn transport_spec() -> impl Strategy<Value = TransportSpec> {
+    let protocol = select(
+        &[
+            "RTP/AVP",
+            "RTP/AVP/TCP",
+            "RTP/AVPF",
+            "RTP/SAVP",
+            "RTP/SAVP/UDP",
+        ][..],
+    );
+    let port_range = (any::<u16>(), any::<u16>())
+        .prop_map(|(start, end)| format!("{}-{}", start.min(end), start.max(end)));
+    let parameters = (
+        option::of(delivery_type()),
+        option::of(address()),
+        option::of(port_range.clone()),
+        option::of(port_range),
+        option::of(interleaved()),
+        option::of(any::<u32>()),
+        option::of(vec(mode(), 1..3)),
+        option::of(setup()),
+        option::of(connection()),
+        option::of(1..=9u8),
+        option::of(vec(any::<u8>(), 1..32)),
+    );
+
+    (protocol, parameters).prop_map(
+        |(
+            protocol,
+            (
+                delivery_type,
+                address,
+                client_port,
+                server_port,
+                interleaved,
+                ssrc,
+                modes,
+                setup,
+                connection,
+                layers,
+                mikey,
+            ),
+        )| {
+            let mut spec = TransportSpec::new(protocol);
+
+            if let Some(delivery_type) = delivery_type {
+                spec.insert(delivery_type, None::<String>);
+            }
+
+            let parameters = vec![
+                (
+                    "dest_addr",
+                    address.map(|address| format!("\"{}\"", address)),
+                ),
+                ("client_port", client_port),
+                ("server_port", server_port),
+                ("interleaved", interleaved.map(String::from)),
+                ("ssrc", ssrc.map(|ssrc| format!("{:08X}", ssrc))),
+                (
+                    "mode",
+                    modes.map(|modes| format!("\"{}\"", modes.iter().join(", "))),
+                ),
+                ("setup", setup.map(String::from)),
+                ("connection", connection.map(String::from)),
+                (
+                    "layers",
+                    layers.map(|layers| Layers::try_from(layers).unwrap().to_string()),
+                ),
+                ("MIKEY", mikey.map(|mikey| base64::encode(&mikey))),
+            ];
+
+            for (name, value) in parameters {
+                if value.is_some() {
+                    spec.insert(name, value);
+                }
+            }
+
+            spec
+        },
+    )
+}
+
+macro_rules! round_trip_tests {
+    ($($test:ident => $header:ty),* $(,)?) => {
+        proptest! {
+            $(
+                #[test]
+                fn $test((header, variation) in round_trip::<$header>()) {
+                    assert_round_trip(&header, &variation)?;
+                }
+            )*
+        }
+    };
+}
+
+round_trip_tests! {
+    test_accept_round_trip => Accept,
+    test_accept_ranges_round_trip => AcceptRanges,
+    test_cseq_round_trip => CSeq,
+    test_date_round_trip => Date,
+    test_expires_round_trip => Expires,
+    test_pipelined_requests_round_trip => PipelinedRequests,
+    test_public_round_trip => Public,
+    test_session_round_trip => Session,
+    test_transport_round_trip => Transport,
+}
+
+proptest! {
+    #[test]
+    fn test_address_round_trip((address, variation) in parameter_round_trip(address(), false, &[])) {
+        assert_parameter_round_trip(&address, &variation)?;
+    }
+
+    #[test]
+    fn test_connection_round_trip(
+        (connection, variation) in parameter_round_trip(connection(), true, &[])
+    ) {
+        assert_parameter_round_trip(&connection, &variation)?;
+    }
+
+    #[test]
+    fn test_delivery_type_round_trip(
+        (delivery_type, variation) in parameter_round_trip(delivery_type(), true, &[])
+    ) {
+        assert_parameter_round_trip(&delivery_type, &variation)?;
+    }
+
+    #[test]
+    fn test_interleaved_round_trip(
+        (interleaved, variation) in parameter_round_trip(interleaved(), false, &['-'])
+    ) {
+        assert_parameter_round_trip(&interleaved, &variation)?;
+    }
+
+    #[test]
+    fn test_layers_round_trip(layers in 1..=9u8) {
+        prop_assert_eq!(*Layers::try_from(layers).unwrap(), layers);
+    }
+
+    #[test]
+    fn test_mikey_round_trip(message in vec(any::<u8>(), 0..64)) {
+        let mikey = MIKEY::try_from(base64::encode(&message).as_bytes()).unwrap();
+        prop_assert_eq!(&*mikey, &message[..]);
+    }
+
+    #[test]
+    fn test_mode_round_trip((mode, variation) in parameter_round_trip(mode(), true, &[])) {
+        assert_parameter_round_trip(&mode, &variation)?;
+    }
+
+    #[test]
+    fn test_setup_round_trip((setup, variation) in parameter_round_trip(setup(), true, &[])) {
+        assert_parameter_round_trip(&setup, &variation)?;
+    }
+}
@@ -0,0 +1,468 @@
+use std::{
+    convert::{Infallible, TryFrom},
+    error::Error,
+    fmt::{self, Display, Formatter},
+    iter::once,
+    ops::{Deref, DerefMut},
+};
+
+use itertools::Itertools;
+
+use crate::{
+    header::{map::TypedHeader, name::HeaderName, value::HeaderValue},
+    syntax,
+    uri::request::{URIError, URI},
+};
+
+/// The `"RTP-Info"` typed header as described by
+/// [RFC7826](https://tools.ietf.org/html/rfc7826#section-18.45).
+///
+/// For each stream being played, this header gives the RTP sequence number and timestamp that
+/// correspond to the start of the requested range. Clients use it to map RTP timestamps onto the
+/// presentation timeline.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct RTPInfo(Vec<StreamInfo>);
+
+impl RTPInfo {
+    /// Constructs a new header with no stream information by default.
+    pub fn new() -> Self {
+        RTPInfo::default()
+    }
+
+    /// Returns the stream information for the given stream URI, if present.
+    pub fn get(&self, uri: &URI) -> Option<&StreamInfo> {
+        self.0.iter().find(|info| info.uri() == uri)
+    }
+}
+
+impl Deref for RTPInfo {
+    type Target = Vec<StreamInfo>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for RTPInfo {
+    fn deref_mut(&mut self) -> &mut Vec<StreamInfo> {
+        &mut self.0
+    }
+}
+
+impl TypedHeader for RTPInfo {
+    type DecodeError = RTPInfoError;
+
+    /// Converts the raw header values to the [`RTPInfo`] header type. Based on the syntax provided
+    /// by [RFC7826](https://tools.ietf.org/html/rfc7826#section-20), this header has the following
+    /// syntax:
+    ///
+    /// ```text
+    /// RTP-Info = "RTP-Info" HCOLON rtsp-info-spec
+    ///            *(COMMA rtsp-info-spec)
+    /// rtsp-info-spec = stream-url 1*ssrc-parameter
+    /// stream-url = "url" EQUAL DQ2URI
+    /// ssrc-parameter = LWS "ssrc" EQUAL ssrc HCOLON
+    ///                  ri-parameter *(SEMI ri-parameter)
+    /// ri-parameter = ("seq" EQUAL 1*5(DIGIT))
+    ///              / ("rtptime" EQUAL 1*10(DIGIT))
+    ///              / generic-param
+    /// ```
+    ///
+    /// The [RFC2326](https://tools.ietf.org/html/rfc2326#section-12.33) form, in which the URL is
+    /// not quoted and the parameters directly follow it without an SSRC, is also accepted since it
+    /// is still what most devices send.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::convert::TryFrom;
+    ///
+    /// use rtsp::header::map::TypedHeader;
+    /// use rtsp::header::types::RTPInfo;
+    /// use rtsp::header::value::HeaderValue;
+    ///
+    /// let raw_header = vec![HeaderValue::try_from(
+    ///     "url=\"rtsp://example.com/foo/audio\" ssrc=0A13C760:seq=45102;rtptime=12345678"
+    /// ).unwrap()];
+    /// let typed_header = RTPInfo::decode(&mut raw_header.iter()).unwrap().unwrap();
+    /// let ssrc_info = &typed_header[0].ssrc_infos()[0];
+    /// assert_eq!(ssrc_info.ssrc(), Some(0x0A13_C760));
+    /// assert_eq!(ssrc_info.sequence_number(), Some(45102));
+    /// assert_eq!(ssrc_info.rtp_timestamp(), Some(12_345_678));
+    /// ```
+    fn decode<'header, Iter>(values: &mut Iter) -> Result<Option<Self>, Self::DecodeError>
+    where
+        Iter: Iterator<Item = &'header HeaderValue>,
+    {
+        let mut stream_infos = Vec::new();
+        let mut present = false;
+
+        for value in values {
+            for part in syntax::split_unquoted(value.as_str(), ',') {
+                let part = syntax::trim_whitespace(part);
+
+                if !part.is_empty() {
+                    stream_infos.push(StreamInfo::try_from(part)?);
+                }
+            }
+
+            present = true;
+        }
+
+        if present {
+            Ok(Some(RTPInfo(stream_infos)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Converts the [`RTPInfo`] type to raw header values.
+    fn encode<Target>(&self, values: &mut Target)
+    where
+        Target: Extend<HeaderValue>,
+    {
+        // Unsafe Justification
+        //
+        // The only component that could contain invalid characters is the URI, but URIs cannot
+        // contain unprintable characters, quotes or linebreaks. Everything else is numeric or a
+        // fixed token.
+
+        let value = self.iter().map(StreamInfo::to_string).join(", ");
+        values.extend(once(unsafe { HeaderValue::from_string_unchecked(value) }));
+    }
+
+    /// Returns the statically assigned [`HeaderName`] for this header.
+    fn header_name() -> &'static HeaderName {
+        &HeaderName::RTPInfo
+    }
+}
+
+/// The RTP information for a single stream.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StreamInfo {
+    /// The per-SSRC information for the stream.
+    ssrc_infos: Vec<SSRCInfo>,
+
+    /// The URI of the stream this information applies to.
+    uri: URI,
+}
+
+impl StreamInfo {
+    /// Constructs new stream information for the given stream URI.
+    pub fn new(uri: URI, ssrc_infos: Vec<SSRCInfo>) -> Self {
+        StreamInfo { ssrc_infos, uri }
+    }
+
+    /// Returns the per-SSRC information for the stream.
+    pub fn ssrc_infos(&self) -> &[SSRCInfo] {
+        &self.ssrc_infos
+    }
+
+    /// Returns the URI of the stream.
+    pub fn uri(&self) -> &URI {
+        &self.uri
+    }
+}
+
+impl Display for StreamInfo {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(formatter, "url=\"{}\"", self.uri)?;
+
+        for ssrc_info in &self.ssrc_infos {
+            write!(formatter, " {}", ssrc_info)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<'info> TryFrom<&'info str> for StreamInfo {
+    type Error = RTPInfoError;
+
+    fn try_from(value: &'info str) -> Result<Self, Self::Error> {
+        let value = strip_parameter_name(value, "url").ok_or(RTPInfoError::MissingURL)?;
+
+        if value.starts_with('"') {
+            let end = value[1..].find('"').ok_or(RTPInfoError::InvalidURL)? + 1;
+            let uri = URI::try_from(&value[1..end])?;
+            let mut ssrc_infos = Vec::new();
+
+            for part in value[end + 1..].split_whitespace() {
+                ssrc_infos.push(SSRCInfo::try_from(part)?);
+            }
+
+            if ssrc_infos.is_empty() {
+                return Err(RTPInfoError::MissingSSRC);
+            }
+
+            Ok(StreamInfo { ssrc_infos, uri })
+        } else {
+            let mut parts = value.splitn(2, ';');
+            let uri = URI::try_from(syntax::trim_whitespace(parts.next().unwrap_or("")))?;
+            let ssrc_info = match parts.next() {
+                Some(parameters) => SSRCInfo::parse_parameters(None, parameters)?,
+                None => SSRCInfo::default(),
+            };
+
+            Ok(StreamInfo {
+                ssrc_infos: vec![ssrc_info],
+                uri,
+            })
+        }
+    }
+}
+
+/// The RTP sequence number and timestamp of the first packet of a single synchronization source
+/// after the start of playback.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct SSRCInfo {
+    /// The RTP timestamp corresponding to the start of the range.
+    rtp_timestamp: Option<u32>,
+
+    /// The sequence number of the first packet sent after the start of the range.
+    sequence_number: Option<u16>,
+
+    /// The synchronization source this information applies to. This is only absent for headers
+    /// received in the [RFC2326](https://tools.ietf.org/html/rfc2326) form.
+    ssrc: Option<u32>,
+}
+
+impl SSRCInfo {
+    /// Constructs new information for the given synchronization source.
+    pub fn new(ssrc: u32, sequence_number: Option<u16>, rtp_timestamp: Option<u32>) -> Self {
+        SSRCInfo {
+            rtp_timestamp,
+            sequence_number,
+            ssrc: Some(ssrc),
+        }
+    }
+
+    /// Parses the `";"` separated parameters following the SSRC.
+    fn parse_parameters(ssrc: Option<u32>, value: &str) -> Result<Self, RTPInfoError> {
+        let mut info = SSRCInfo {
+            rtp_timestamp: None,
+            sequence_number: None,
+            ssrc,
+        };
+
+        for parameter in value.split(';') {
+            let mut parts = parameter.splitn(2, '=').map(syntax::trim_whitespace);
+            let name = parts.next().unwrap_or("");
+            let value = parts.next();
+
+            if name.eq_ignore_ascii_case("seq") {
+                let value = value.ok_or(RTPInfoError::InvalidSequenceNumber)?;
+                info.sequence_number = Some(
+                    value
+                        .parse::<u16>()
+                        .map_err(|_| RTPInfoError::InvalidSequenceNumber)?,
+                );
+            } else if name.eq_ignore_ascii_case("rtptime") {
+                let value = value.ok_or(RTPInfoError::InvalidRTPTimestamp)?;
+                info.rtp_timestamp = Some(
+                    value
+                        .parse::<u32>()
+                        .map_err(|_| RTPInfoError::InvalidRTPTimestamp)?,
+                );
+            } else if !name.is_empty() && !syntax::is_token(name.as_bytes()) {
+                return Err(RTPInfoError::InvalidParameter);
+            }
+        }
+
+        Ok(info)
+    }
+
+    /// Returns the RTP timestamp corresponding to the start of the range.
+    pub fn rtp_timestamp(&self) -> Option<u32> {
+        self.rtp_timestamp
+    }
+
+    /// Returns the sequence number of the first packet sent after the start of the range.
+    pub fn sequence_number(&self) -> Option<u16> {
+        self.sequence_number
+    }
+
+    /// Returns the synchronization source this information applies to.
+    pub fn ssrc(&self) -> Option<u32> {
+        self.ssrc
+    }
+}
+
+impl Display for SSRCInfo {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(formatter, "ssrc={:08X}:", self.ssrc.unwrap_or(0))?;
+
+        let parameters = self
+            .sequence_number
+            .map(|sequence_number| format!("seq={}", sequence_number))
+            .into_iter()
+            .chain(
+                self.rtp_timestamp
+                    .map(|rtp_timestamp| format!("rtptime={}", rtp_timestamp)),
+            )
+            .join(";");
+        formatter.write_str(&parameters)
+    }
+}
+
+impl<'info> TryFrom<&'info str> for SSRCInfo {
+    type Error = RTPInfoError;
+
+    fn try_from(value: &'info str) -> Result<Self, Self::Error> {
+        let value = strip_parameter_name(value, "ssrc").ok_or(RTPInfoError::MissingSSRC)?;
+        let mut parts = value.splitn(2, ':');
+        let ssrc = parts.next().unwrap_or("");
+
+        if ssrc.is_empty() || ssrc.len() > 8 {
+            return Err(RTPInfoError::InvalidSSRC);
+        }
+
+        let ssrc = u32::from_str_radix(ssrc, 16).map_err(|_| RTPInfoError::InvalidSSRC)?;
+        SSRCInfo::parse_parameters(Some(ssrc), parts.next().unwrap_or(""))
+    }
+}
+
+/// A possible error value when converting to an [`RTPInfo`] from [`HeaderValue`]s.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum RTPInfoError {
+    /// A parameter name was not a valid token.
+    InvalidParameter,
+
+    /// The `"rtptime"` parameter was not a valid 32-bit integer.
+    InvalidRTPTimestamp,
+
+    /// The `"seq"` parameter was not a valid 16-bit integer.
+    InvalidSequenceNumber,
+
+    /// The SSRC was not a valid 32-bit hexadecimal integer.
+    InvalidSSRC,
+
+    /// The stream URL was not a valid URI.
+    InvalidURL,
+
+    /// A stream specification did not contain any SSRC information.
+    MissingSSRC,
+
+    /// A stream specification did not start with the `"url"` parameter.
+    MissingURL,
+}
+
+impl Display for RTPInfoError {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        use self::RTPInfoError::*;
+
+        match self {
+            InvalidParameter => write!(formatter, "invalid RTP info parameter"),
+            InvalidRTPTimestamp => write!(formatter, "invalid RTP info RTP timestamp"),
+            InvalidSequenceNumber => write!(formatter, "invalid RTP info sequence number"),
+            InvalidSSRC => write!(formatter, "invalid RTP info SSRC"),
+            InvalidURL => write!(formatter, "invalid RTP info URL"),
+            MissingSSRC => write!(formatter, "missing RTP info SSRC"),
+            MissingURL => write!(formatter, "missing RTP info URL"),
+        }
+    }
+}
+
+impl Error for RTPInfoError {}
+
+impl From<Infallible> for RTPInfoError {
+    fn from(_: Infallible) -> Self {
+        RTPInfoError::InvalidParameter
+    }
+}
+
+impl From<URIError> for RTPInfoError {
+    fn from(_: URIError) -> Self {
+        RTPInfoError::InvalidURL
+    }
+}
+
+/// Strips the given case-insensitive parameter name and the following `"="` from the value.
+fn strip_parameter_name<'value>(value: &'value str, name: &str) -> Option<&'value str> {
+    let value = syntax::trim_whitespace(value);
+
+    match value.get(..name.len()) {
+        Some(prefix) if prefix.eq_ignore_ascii_case(name) => (),
+        _ => return None,
+    }
+
+    let value = syntax::trim_whitespace_left(&value[name.len()..]);
+
+    if value.starts_with('=') {
+        Some(syntax::trim_whitespace_left(&value[1..]))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::convert::TryFrom;
+
+    use crate::header::{
+        map::TypedHeader,
+        types::rtp_info::{RTPInfo, RTPInfoError},
+        value::HeaderValue,
+    };
+
+    #[test]
+    fn test_rtp_info_decode_multiple_streams() {
+        let raw_header = vec![HeaderValue::try_from(
+            "url=\"rtsp://example.com/foo/audio\" ssrc=0A13C760:seq=45102;rtptime=12345678, \
+             url=\"rtsp://example.com/foo/video\" ssrc=9A9DE123:seq=30211;rtptime=29567112",
+        )
+        .unwrap()];
+        let rtp_info = RTPInfo::decode(&mut raw_header.iter()).unwrap().unwrap();
+
+        assert_eq!(rtp_info.len(), 2);
+        assert_eq!(rtp_info[1].uri().path().to_string(), "/foo/video");
+        assert_eq!(rtp_info[1].ssrc_infos()[0].ssrc(), Some(0x9A9D_E123));
+        assert_eq!(
+            rtp_info[1].ssrc_infos()[0].rtp_timestamp(),
+            Some(29_567_112)
+        );
+    }
+
+    #[test]
+    fn test_rtp_info_decode_non_ascii_parameter_name() {
+        let raw_header = vec![HeaderValue::try_from("u\u{1F600}l=rtsp://example.com/").unwrap()];
+        assert_eq!(
+            RTPInfo::decode(&mut raw_header.iter()),
+            Err(RTPInfoError::MissingURL)
+        );
+    }
+
+    #[test]
+    fn test_rtp_info_decode_rfc2326() {
+        let raw_header = vec![HeaderValue::try_from(
+            "url=rtsp://example.com/track1;seq=9810092;rtptime=3450012",
+        )
+        .unwrap()];
+        assert_eq!(
+            RTPInfo::decode(&mut raw_header.iter()),
+            Err(RTPInfoError::InvalidSequenceNumber)
+        );
+
+        let raw_header =
+            vec![
+                HeaderValue::try_from("url=rtsp://example.com/track1;seq=32;rtptime=3450012")
+                    .unwrap(),
+            ];
+        let rtp_info = RTPInfo::decode(&mut raw_header.iter()).unwrap().unwrap();
+        assert_eq!(rtp_info[0].ssrc_infos()[0].ssrc(), None);
+        assert_eq!(rtp_info[0].ssrc_infos()[0].sequence_number(), Some(32));
+    }
+
+    #[test]
+    fn test_rtp_info_round_trip() {
+        let raw_header = vec![HeaderValue::try_from(
+            "url=\"rtsp://example.com/foo/audio\" ssrc=0A13C760:seq=45102;rtptime=12345678",
+        )
+        .unwrap()];
+        let rtp_info = RTPInfo::decode(&mut raw_header.iter()).unwrap().unwrap();
+        let mut encoded = vec![];
+        rtp_info.encode(&mut encoded);
+        assert_eq!(encoded, raw_header);
+    }
+}
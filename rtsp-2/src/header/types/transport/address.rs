@@ -47,8 +47,7 @@ impl Address {
             return Err(AddressError);
         }
 
-        // Unsafe: The function above [`syntax::is_token`] ensures that the value is valid
-        // ASCII-US.
+        // Unsafe: The function above [`syntax::is_qdtext`] ensures that the value is valid UTF-8.
         let value = unsafe { str::from_utf8_unchecked(value) }.to_lowercase();
         Ok(Address::Extension(ExtensionAddress(value)))
     }
@@ -22,6 +22,13 @@ use crate::syntax;
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub struct Interleaved(RangeInclusive<u8>);
 
+impl Interleaved {
+    /// Constructs a new interleaved parameter for the given channel range.
+    pub fn new(channels: RangeInclusive<u8>) -> Self {
+        Interleaved(channels)
+    }
+}
+
 impl Deref for Interleaved {
     type Target = RangeInclusive<u8>;
 
@@ -7,6 +7,16 @@ mod mikey;
 mod mode;
 mod setup;
 
+use std::{
+    convert::{Infallible, TryFrom},
+    error::Error,
+    fmt::{self, Display, Formatter},
+    iter::once,
+    ops::{Deref, DerefMut, RangeInclusive},
+};
+
+use itertools::Itertools;
+
 pub use self::{
     address::{Address, AddressError, ExtensionAddress, HostPort},
     connection::{Connection, ConnectionError},
@@ -17,3 +27,450 @@ pub use self::{
     mode::{Mode, ModeError},
     setup::{Setup, SetupError},
 };
+use crate::{
+    header::{map::TypedHeader, name::HeaderName, value::HeaderValue},
+    syntax,
+};
+
+/// The `"Transport"` typed header as described by
+/// [RFC7826](https://tools.ietf.org/html/rfc7826#section-18.54).
+///
+/// In a `"SETUP"` request, the header lists the transport specifications acceptable to the client
+/// in order of preference. In the response, the server echoes back the single specification it
+/// selected with the parameters it chose filled in.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Transport(Vec<TransportSpec>);
+
+impl Transport {
+    /// Constructs a new header with no transport specifications by default.
+    pub fn new() -> Self {
+        Transport::default()
+    }
+}
+
+impl Deref for Transport {
+    type Target = Vec<TransportSpec>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for Transport {
+    fn deref_mut(&mut self) -> &mut Vec<TransportSpec> {
+        &mut self.0
+    }
+}
+
+impl From<TransportSpec> for Transport {
+    fn from(value: TransportSpec) -> Self {
+        Transport(vec![value])
+    }
+}
+
+impl TypedHeader for Transport {
+    type DecodeError = TransportError;
+
+    /// Converts the raw header values to the [`Transport`] header type. Based on the syntax
+    /// provided by [RFC7826](https://tools.ietf.org/html/rfc7826#section-20), this header has the
+    /// following syntax:
+    ///
+    /// ```text
+    /// Transport = "Transport" HCOLON transport-spec
+    ///             *(COMMA transport-spec)
+    /// transport-spec = transport-id *tr-parameter
+    /// transport-id = trans-id-rtp / other-trans
+    /// trans-id-rtp = "RTP/" profile ["/" lower-transport]
+    /// other-trans = token *("/" token)
+    /// tr-parameter = SEMI ( ... / tr-gen-param )
+    /// tr-gen-param = token [EQUAL (token / quoted-string)]
+    /// ```
+    ///
+    /// Parameters are kept as given so that parameters not known to this implementation, as well
+    /// as the [RFC2326](https://tools.ietf.org/html/rfc2326#section-12.39) `"client_port"` and
+    /// `"server_port"` parameters most devices still use, survive a round trip.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::convert::TryFrom;
+    ///
+    /// use rtsp::header::map::TypedHeader;
+    /// use rtsp::header::types::transport::Transport;
+    /// use rtsp::header::value::HeaderValue;
+    ///
+    /// let raw_header = vec![HeaderValue::try_from(
+    ///     "RTP/AVP/TCP;unicast;interleaved=0-1, RTP/AVP;unicast;client_port=4588-4589"
+    /// ).unwrap()];
+    /// let typed_header = Transport::decode(&mut raw_header.iter()).unwrap().unwrap();
+    /// assert_eq!(typed_header.len(), 2);
+    /// assert!(typed_header[0].is_interleaved());
+    /// assert_eq!(typed_header[1].client_port(), Some(4588..=4589));
+    /// ```
+    fn decode<'header, Iter>(values: &mut Iter) -> Result<Option<Self>, Self::DecodeError>
+    where
+        Iter: Iterator<Item = &'header HeaderValue>,
+    {
+        let mut specs = Vec::new();
+        let mut present = false;
+
+        for value in values {
+            for part in syntax::split_unquoted(value.as_str(), ',') {
+                let part = syntax::trim_whitespace(part);
+
+                if !part.is_empty() {
+                    specs.push(TransportSpec::try_from(part)?);
+                }
+            }
+
+            present = true;
+        }
+
+        if !present {
+            Ok(None)
+        } else if specs.is_empty() {
+            Err(TransportError::Empty)
+        } else {
+            Ok(Some(Transport(specs)))
+        }
+    }
+
+    /// Converts the [`Transport`] type to raw header values.
+    fn encode<Target>(&self, values: &mut Target)
+    where
+        Target: Extend<HeaderValue>,
+    {
+        // Unsafe Justification
+        //
+        // Transport specifications can only be constructed from tokens and parameter values that
+        // were checked to not contain unprintable characters or linebreaks.
+
+        let value = self.iter().map(TransportSpec::to_string).join(", ");
+        values.extend(once(unsafe { HeaderValue::from_string_unchecked(value) }));
+    }
+
+    /// Returns the statically assigned [`HeaderName`] for this header.
+    fn header_name() -> &'static HeaderName {
+        &HeaderName::Transport
+    }
+}
+
+/// A single transport specification, such as `"RTP/AVP/TCP;unicast;interleaved=0-1"`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TransportSpec {
+    /// The parameters in the order they were given. Names are stored in lowercase and flag
+    /// parameters such as `"unicast"` have no value.
+    parameters: Vec<(String, Option<String>)>,
+
+    /// The transport identifier, such as `"RTP/AVP/TCP"`. This is stored in uppercase.
+    protocol: String,
+}
+
+impl TransportSpec {
+    /// Returns whether the given parameter is present.
+    pub fn contains(&self, name: &str) -> bool {
+        self.parameters
+            .iter()
+            .any(|(parameter_name, _)| parameter_name.eq_ignore_ascii_case(name))
+    }
+
+    /// Returns the `"client_port"` parameter from
+    /// [RFC2326](https://tools.ietf.org/html/rfc2326#section-12.39), if present and valid.
+    pub fn client_port(&self) -> Option<RangeInclusive<u16>> {
+        self.get("client_port").and_then(parse_port_range)
+    }
+
+    /// Returns the delivery type, if one was given.
+    pub fn delivery_type(&self) -> Option<DeliveryType> {
+        self.parameters
+            .iter()
+            .find_map(|(name, value)| match value {
+                None => DeliveryType::try_from(name.as_str()).ok(),
+                Some(_) => None,
+            })
+    }
+
+    /// Returns the value of the given parameter. Flag parameters have an empty value.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.parameters
+            .iter()
+            .find(|(parameter_name, _)| parameter_name.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_ref().map(String::as_str).unwrap_or(""))
+    }
+
+    /// Inserts a parameter, replacing any existing value.
+    ///
+    /// Values are not validated beyond what is needed to keep the header encodable, so quoted
+    /// values must include their quotes.
+    pub fn insert<TName, TValue>(&mut self, name: TName, value: Option<TValue>)
+    where
+        TName: AsRef<str>,
+        TValue: Into<String>,
+    {
+        let name = name.as_ref().to_ascii_lowercase();
+        let value = value.map(Into::into);
+        debug_assert!(syntax::is_token(name.as_bytes()));
+        debug_assert!(value
+            .as_ref()
+            .map_or(true, |value| is_parameter_value(value)));
+
+        match self
+            .parameters
+            .iter_mut()
+            .find(|(parameter_name, _)| *parameter_name == name)
+        {
+            Some((_, existing_value)) => *existing_value = value,
+            None => self.parameters.push((name, value)),
+        }
+    }
+
+    /// Returns the `"interleaved"` parameter, if present and valid.
+    pub fn interleaved(&self) -> Option<Interleaved> {
+        self.get("interleaved")
+            .and_then(|value| Interleaved::try_from(value).ok())
+    }
+
+    /// Returns whether media is to be interleaved with the control connection, that is, whether the
+    /// lower transport is TCP and the `"interleaved"` parameter is present.
+    pub fn is_interleaved(&self) -> bool {
+        self.lower_transport().eq_ignore_ascii_case("TCP") && self.contains("interleaved")
+    }
+
+    /// Returns the lower transport, defaulting to `"UDP"` if not given.
+    pub fn lower_transport(&self) -> &str {
+        let mut parts = self.protocol.split('/');
+
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some("RTP"), Some(_), Some(lower_transport)) => lower_transport,
+            _ => "UDP",
+        }
+    }
+
+    /// Constructs a new transport specification for the given transport identifier.
+    pub fn new<TProtocol>(protocol: TProtocol) -> Self
+    where
+        TProtocol: AsRef<str>,
+    {
+        let protocol = protocol.as_ref().to_ascii_uppercase();
+        debug_assert!(protocol
+            .split('/')
+            .all(|part| syntax::is_token(part.as_bytes())));
+
+        TransportSpec {
+            parameters: Vec::new(),
+            protocol,
+        }
+    }
+
+    /// Returns an iterator over all parameters in the order they were given.
+    pub fn parameters(&self) -> impl Iterator<Item = (&str, Option<&str>)> {
+        self.parameters
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_ref().map(String::as_str)))
+    }
+
+    /// Returns the transport identifier, such as `"RTP/AVP/TCP"`.
+    pub fn protocol(&self) -> &str {
+        &self.protocol
+    }
+
+    /// Removes the given parameter, returning whether it was present.
+    pub fn remove(&mut self, name: &str) -> bool {
+        let length = self.parameters.len();
+        self.parameters
+            .retain(|(parameter_name, _)| !parameter_name.eq_ignore_ascii_case(name));
+        self.parameters.len() != length
+    }
+
+    /// Returns the `"server_port"` parameter from
+    /// [RFC2326](https://tools.ietf.org/html/rfc2326#section-12.39), if present and valid.
+    pub fn server_port(&self) -> Option<RangeInclusive<u16>> {
+        self.get("server_port").and_then(parse_port_range)
+    }
+
+    /// Returns the `"ssrc"` parameter, if present and valid. Only the first SSRC is returned if
+    /// several are given.
+    pub fn ssrc(&self) -> Option<u32> {
+        self.get("ssrc")
+            .and_then(|value| value.split('/').next())
+            .and_then(|ssrc| u32::from_str_radix(ssrc, 16).ok())
+    }
+}
+
+impl Display for TransportSpec {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        formatter.write_str(&self.protocol)?;
+
+        for (name, value) in &self.parameters {
+            match value {
+                Some(value) => write!(formatter, ";{}={}", name, value)?,
+                None => write!(formatter, ";{}", name)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<'spec> TryFrom<&'spec str> for TransportSpec {
+    type Error = TransportError;
+
+    fn try_from(value: &'spec str) -> Result<Self, Self::Error> {
+        let mut parts = syntax::split_unquoted(value, ';').into_iter();
+        let protocol = syntax::trim_whitespace(parts.next().unwrap_or(""));
+
+        if protocol.is_empty()
+            || !protocol
+                .split('/')
+                .all(|part| syntax::is_token(part.as_bytes()))
+        {
+            return Err(TransportError::InvalidProtocol);
+        }
+
+        let mut spec = TransportSpec::new(protocol);
+
+        for part in parts {
+            let part = syntax::trim_whitespace(part);
+
+            if part.is_empty() {
+                continue;
+            }
+
+            let mut parameter = part.splitn(2, '=').map(syntax::trim_whitespace);
+            let name = parameter.next().unwrap_or("");
+            let value = parameter.next();
+
+            if !syntax::is_token(name.as_bytes())
+                || !value.map_or(true, |value| is_parameter_value(value))
+            {
+                return Err(TransportError::InvalidParameter);
+            }
+
+            spec.insert(name, value);
+        }
+
+        Ok(spec)
+    }
+}
+
+/// A possible error value when converting to a [`Transport`] from [`HeaderValue`]s.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum TransportError {
+    /// The header was present but did not contain any transport specifications.
+    Empty,
+
+    /// A parameter had an invalid name or value.
+    InvalidParameter,
+
+    /// The transport identifier was missing or contained invalid characters.
+    InvalidProtocol,
+}
+
+impl Display for TransportError {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        use self::TransportError::*;
+
+        match self {
+            Empty => write!(formatter, "empty transport header"),
+            InvalidParameter => write!(formatter, "invalid transport parameter"),
+            InvalidProtocol => write!(formatter, "invalid transport protocol"),
+        }
+    }
+}
+
+impl Error for TransportError {}
+
+impl From<Infallible> for TransportError {
+    fn from(_: Infallible) -> Self {
+        TransportError::Empty
+    }
+}
+
+/// Returns whether the value is a token, a port range or a quoted string without linebreaks.
+fn is_parameter_value(value: &str) -> bool {
+    // Unbalanced quotes would swallow the separators following the value once encoded.
+    !value.is_empty()
+        && value
+            .bytes()
+            .all(|byte| byte == b'\t' || (byte >= b' ' && byte != 0x7F))
+        && value.bytes().filter(|&byte| byte == b'"').count() % 2 == 0
+}
+
+/// Parses a port range such as `"4588-4589"` or a single port such as `"4588"`.
+fn parse_port_range(value: &str) -> Option<RangeInclusive<u16>> {
+    let mut parts = value.splitn(2, '-').map(syntax::trim_whitespace);
+    let start = parts.next()?.parse::<u16>().ok()?;
+    let end = match parts.next() {
+        Some(end) => end.parse::<u16>().ok()?,
+        None => start,
+    };
+
+    if start <= end {
+        Some(start..=end)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::convert::TryFrom;
+
+    use crate::header::{
+        map::TypedHeader,
+        types::transport::{DeliveryType, Transport, TransportError, TransportSpec},
+        value::HeaderValue,
+    };
+
+    #[test]
+    fn test_transport_round_trip() {
+        let raw_header = vec![HeaderValue::try_from(
+            "RTP/AVP;unicast;client_port=4588-4589;ssrc=0A13C760;mode=\"PLAY\"",
+        )
+        .unwrap()];
+        let typed_header = Transport::decode(&mut raw_header.iter()).unwrap().unwrap();
+        let spec = &typed_header[0];
+        assert_eq!(spec.lower_transport(), "UDP");
+        assert_eq!(spec.delivery_type(), Some(DeliveryType::Unicast));
+        assert_eq!(spec.client_port(), Some(4588..=4589));
+        assert_eq!(spec.ssrc(), Some(0x0A13_C760));
+        assert_eq!(spec.get("mode"), Some("\"PLAY\""));
+
+        let mut encoded = vec![];
+        typed_header.encode(&mut encoded);
+        assert_eq!(encoded, raw_header);
+    }
+
+    #[test]
+    fn test_transport_spec_insert() {
+        let mut spec = TransportSpec::new("rtp/avp/tcp");
+        spec.insert("unicast", None::<String>);
+        spec.insert("interleaved", Some("0-1"));
+        assert!(spec.is_interleaved());
+        assert_eq!(*spec.interleaved().unwrap(), 0..=1);
+        assert_eq!(spec.to_string(), "RTP/AVP/TCP;unicast;interleaved=0-1");
+    }
+
+    #[test]
+    fn test_transport_decode_invalid() {
+        let raw_header = vec![HeaderValue::try_from("RTP/AVP;=1").unwrap()];
+        assert_eq!(
+            Transport::decode(&mut raw_header.iter()),
+            Err(TransportError::InvalidParameter)
+        );
+
+        let raw_header =
+            vec![HeaderValue::try_from("RTP/AVP;src_addr=\"1.2.3.4, RTP/AVP").unwrap()];
+        assert_eq!(
+            Transport::decode(&mut raw_header.iter()),
+            Err(TransportError::InvalidParameter)
+        );
+
+        let raw_header = vec![HeaderValue::try_from(";unicast").unwrap()];
+        assert_eq!(
+            TransportSpec::try_from(raw_header[0].as_str()),
+            Err(TransportError::InvalidProtocol)
+        );
+    }
+}
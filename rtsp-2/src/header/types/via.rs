@@ -0,0 +1,583 @@
+use std::{
+    convert::{Infallible, TryFrom},
+    error::Error,
+    fmt::{self, Display, Formatter},
+    iter::{once, FromIterator},
+    ops::{Deref, DerefMut},
+};
+
+use itertools::Itertools;
+
+use crate::{
+    header::{map::TypedHeader, name::HeaderName, value::HeaderValue},
+    syntax,
+    uri::{Authority, Host, RTSP_DEFAULT_PORT},
+    version::Version,
+};
+
+/// The `"Via"` typed header as described by
+/// [RFC7826](https://tools.ietf.org/html/rfc7826#section-18.57).
+///
+/// Each entry records an intermediary a message passed through, in the order they were passed
+/// through.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Via(Vec<ViaEntry>);
+
+impl Via {
+    /// Returns whether a message with this header has already passed through the intermediary
+    /// identified by the given entry, meaning that forwarding it again would create a loop.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::convert::TryFrom;
+    ///
+    /// use rtsp::header::types::via::{Via, ViaEntry};
+    ///
+    /// let via = Via::try_from("RTSP/2.0 proxy.example.com, RTSP/2.0 camera.local:8554").unwrap();
+    /// assert!(via.detects_loop(&ViaEntry::try_from("RTSP/1.0 proxy.example.com:554").unwrap()));
+    /// assert!(!via.detects_loop(&ViaEntry::try_from("RTSP/2.0 camera.local").unwrap()));
+    /// ```
+    pub fn detects_loop(&self, entry: &ViaEntry) -> bool {
+        self.iter().any(|other| other.is_received_by(entry))
+    }
+
+    /// Constructs a new header with no entries by default.
+    pub fn new() -> Self {
+        Via::default()
+    }
+
+    /// Removes every entry received by the same intermediary as the given entry, returning whether
+    /// any were removed.
+    pub fn strip(&mut self, entry: &ViaEntry) -> bool {
+        let length = self.len();
+        self.retain(|other| !other.is_received_by(entry));
+        self.len() != length
+    }
+}
+
+impl Deref for Via {
+    type Target = Vec<ViaEntry>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for Via {
+    fn deref_mut(&mut self) -> &mut Vec<ViaEntry> {
+        &mut self.0
+    }
+}
+
+impl FromIterator<ViaEntry> for Via {
+    fn from_iter<TIterator>(iterator: TIterator) -> Self
+    where
+        TIterator: IntoIterator<Item = ViaEntry>,
+    {
+        Via(Vec::from_iter(iterator))
+    }
+}
+
+impl<'value> TryFrom<&'value str> for Via {
+    type Error = ViaError;
+
+    fn try_from(value: &'value str) -> Result<Self, Self::Error> {
+        split_entries(value)
+            .into_iter()
+            .map(ViaEntry::try_from)
+            .collect()
+    }
+}
+
+impl TypedHeader for Via {
+    type DecodeError = ViaError;
+
+    /// Converts the raw header values to the [`Via`] header type. Based on the syntax provided by
+    /// [RFC7826](https://tools.ietf.org/html/rfc7826#section-20), along with the comments allowed
+    /// by [RFC7230](https://tools.ietf.org/html/rfc7230#section-5.7.1) which RTSP/1.0 agents
+    /// send, this header has the following syntax:
+    ///
+    /// ```text
+    /// Via              =  "Via" HCOLON via-parm *(COMMA via-parm)
+    /// via-parm         =  sent-protocol LWS sent-by *( SEMI via-extension ) [ LWS comment ]
+    /// via-extension    =  generic-param
+    /// sent-protocol    =  [ protocol-name SLASH ] protocol-version [ SLASH transport-prot ]
+    /// protocol-name    =  "RTSP" / token
+    /// protocol-version =  token
+    /// transport-prot   =  "UDP" / "TCP" / "TLS" / other-transport
+    /// other-transport  =  token
+    /// sent-by          =  host [ COLON port ]
+    /// comment          =  "(" *( ctext / quoted-pair / comment ) ")"
+    /// ```
+    ///
+    /// Only RTSP protocol versions known by [`Version`] are supported.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::convert::TryFrom;
+    ///
+    /// use rtsp::header::map::TypedHeader;
+    /// use rtsp::header::types::Via;
+    /// use rtsp::header::value::HeaderValue;
+    ///
+    /// let raw_header: Vec<HeaderValue> = vec![];
+    /// assert_eq!(Via::decode(&mut raw_header.iter()).unwrap(), None);
+    ///
+    /// let raw_header = vec![
+    ///     HeaderValue::try_from("RTSP/2.0/TCP proxy.example.com:8554").unwrap(),
+    ///     HeaderValue::try_from("1.0 camera.local (Camera, firmware 1.2)").unwrap(),
+    /// ];
+    /// let typed_header = Via::decode(&mut raw_header.iter()).unwrap().unwrap();
+    /// assert_eq!(typed_header.len(), 2);
+    /// assert_eq!(typed_header[0].port(), Some(8554));
+    /// assert_eq!(typed_header[1].comment(), Some("Camera, firmware 1.2"));
+    /// ```
+    fn decode<'header, Iter>(values: &mut Iter) -> Result<Option<Self>, Self::DecodeError>
+    where
+        Iter: Iterator<Item = &'header HeaderValue>,
+    {
+        let mut entries = Vec::new();
+        let mut present = false;
+
+        for value in values {
+            entries.extend(Via::try_from(value.as_str())?.0);
+            present = true;
+        }
+
+        if present {
+            Ok(Some(Via(entries)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Converts the [`Via`] type to raw header values.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::convert::TryFrom;
+    ///
+    /// use rtsp::header::map::TypedHeader;
+    /// use rtsp::header::types::Via;
+    /// use rtsp::header::value::HeaderValue;
+    ///
+    /// let typed_header = Via::try_from("RTSP/2.0/TCP  proxy.example.com ,1.0 camera").unwrap();
+    /// let expected_raw_header = vec![
+    ///     HeaderValue::try_from("RTSP/2.0/TCP proxy.example.com, RTSP/1.0 camera").unwrap()
+    /// ];
+    /// let mut raw_header = vec![];
+    /// typed_header.encode(&mut raw_header);
+    /// assert_eq!(raw_header, expected_raw_header);
+    /// ```
+    fn encode<Target>(&self, values: &mut Target)
+    where
+        Target: Extend<HeaderValue>,
+    {
+        // Unsafe Justification
+        //
+        // Every part of an entry was either decoded from a header value or validated when the
+        // entry was constructed, so none of them contain unprintable ASCII-US characters or line
+        // breaks.
+
+        let value = self.iter().join(", ");
+        values.extend(once(unsafe { HeaderValue::from_string_unchecked(value) }));
+    }
+
+    /// Returns the statically assigned [`HeaderName`] for this header.
+    fn header_name() -> &'static HeaderName {
+        &HeaderName::Via
+    }
+}
+
+/// A single entry of the `"Via"` header, identifying an intermediary by the protocol it received
+/// the message with and the host it received the message as.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ViaEntry {
+    comment: Option<String>,
+    host: Host<'static>,
+    parameters: Vec<(String, Option<String>)>,
+    port: Option<u16>,
+    transport: Option<String>,
+    version: Version,
+}
+
+impl ViaEntry {
+    /// Returns the comment identifying the software of the intermediary, if any.
+    pub fn comment(&self) -> Option<&str> {
+        self.comment.as_deref()
+    }
+
+    /// Returns the host the message was received as.
+    pub fn host(&self) -> &Host<'static> {
+        &self.host
+    }
+
+    /// Returns whether this entry and the given entry identify the same intermediary, ignoring the
+    /// protocol each was received with. A missing port is the default RTSP port.
+    pub fn is_received_by(&self, other: &ViaEntry) -> bool {
+        self.host == other.host
+            && self.port.unwrap_or(RTSP_DEFAULT_PORT) == other.port.unwrap_or(RTSP_DEFAULT_PORT)
+    }
+
+    /// Constructs a new entry for an intermediary that received a message with the given protocol
+    /// version as the given host and port.
+    pub fn new(version: Version, host: Host<'static>, port: Option<u16>) -> Self {
+        ViaEntry {
+            comment: None,
+            host,
+            parameters: Vec::new(),
+            port,
+            transport: None,
+            version,
+        }
+    }
+
+    /// Returns the extension parameters of the entry, such as `("ttl", Some("64"))`.
+    pub fn parameters(&self) -> &[(String, Option<String>)] {
+        &self.parameters
+    }
+
+    /// Returns the port the message was received on, if given.
+    pub fn port(&self) -> Option<u16> {
+        self.port
+    }
+
+    /// Returns the transport protocol the message was received over, such as `"TCP"`, if given.
+    pub fn transport(&self) -> Option<&str> {
+        self.transport.as_deref()
+    }
+
+    /// Returns the protocol version the message was received with.
+    pub fn version(&self) -> Version {
+        self.version
+    }
+
+    /// Sets the comment identifying the software of the intermediary.
+    ///
+    /// Comments cannot contain unbalanced parentheses or line breaks.
+    pub fn with_comment<TComment>(mut self, comment: TComment) -> Result<Self, ViaError>
+    where
+        TComment: Into<String>,
+    {
+        let comment = comment.into();
+
+        if !is_comment_text(&comment) {
+            return Err(ViaError::InvalidComment);
+        }
+
+        self.comment = Some(comment);
+        Ok(self)
+    }
+
+    /// Sets the transport protocol the message was received over, such as `"TCP"`.
+    pub fn with_transport<TTransport>(mut self, transport: TTransport) -> Result<Self, ViaError>
+    where
+        TTransport: Into<String>,
+    {
+        let transport = transport.into();
+
+        if !syntax::is_token(transport.as_bytes()) {
+            return Err(ViaError::InvalidTransport);
+        }
+
+        self.transport = Some(transport);
+        Ok(self)
+    }
+}
+
+impl Display for ViaEntry {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        formatter.write_str(self.version.as_str())?;
+
+        if let Some(transport) = &self.transport {
+            write!(formatter, "/{}", transport)?;
+        }
+
+        write!(formatter, " {}", self.host)?;
+
+        if let Some(port) = self.port {
+            write!(formatter, ":{}", port)?;
+        }
+
+        for (name, value) in &self.parameters {
+            match value {
+                Some(value) => write!(formatter, ";{}={}", name, value)?,
+                None => write!(formatter, ";{}", name)?,
+            }
+        }
+
+        if let Some(comment) = &self.comment {
+            write!(formatter, " ({})", comment)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<'value> TryFrom<&'value str> for ViaEntry {
+    type Error = ViaError;
+
+    fn try_from(value: &'value str) -> Result<Self, Self::Error> {
+        let value = syntax::trim_whitespace(value);
+        let (protocol, rest) = match value.find([' ', '\t']) {
+            Some(index) => (&value[..index], syntax::trim_whitespace(&value[index..])),
+            None => return Err(ViaError::MissingReceivedBy),
+        };
+
+        let (received_by, comment) = match rest.find('(') {
+            Some(index) => {
+                let comment = &rest[index..];
+
+                if !comment.ends_with(')') || !is_comment_text(&comment[1..comment.len() - 1]) {
+                    return Err(ViaError::InvalidComment);
+                }
+
+                (
+                    syntax::trim_whitespace(&rest[..index]),
+                    Some(comment[1..comment.len() - 1].to_string()),
+                )
+            }
+            None => (rest, None),
+        };
+
+        let mut protocol_parts = protocol.split('/').collect::<Vec<_>>();
+        let transport = if protocol_parts.len() == 3 {
+            protocol_parts.pop().map(str::to_string)
+        } else {
+            None
+        };
+        let version = match protocol_parts.as_slice() {
+            [version] => *version,
+            [name, version] if name.eq_ignore_ascii_case("RTSP") => *version,
+            [_, _] => return Err(ViaError::UnsupportedProtocol),
+            _ => return Err(ViaError::InvalidVersion),
+        };
+        let version = Version::try_from(format!("RTSP/{}", version).as_bytes())
+            .map_err(|_| ViaError::InvalidVersion)?;
+
+        if let Some(transport) = &transport {
+            if !syntax::is_token(transport.as_bytes()) {
+                return Err(ViaError::InvalidTransport);
+            }
+        }
+
+        let mut received_by_parts = received_by.split(';').map(syntax::trim_whitespace);
+        let sent_by = received_by_parts.next().unwrap();
+
+        if sent_by.is_empty() {
+            return Err(ViaError::MissingReceivedBy);
+        }
+
+        let (username, _, host, port) = Authority::try_from(sent_by)
+            .map_err(|_| ViaError::InvalidReceivedBy)?
+            .into_parts();
+
+        if username.is_some() {
+            return Err(ViaError::InvalidReceivedBy);
+        }
+
+        let parameters = received_by_parts
+            .map(|parameter| {
+                let mut parts = parameter.splitn(2, '=').map(syntax::trim_whitespace);
+                let name = parts.next().unwrap();
+                let value = parts.next();
+
+                if !syntax::is_token(name.as_bytes())
+                    || value.map_or(false, |value| value.is_empty() || value.contains(','))
+                {
+                    return Err(ViaError::InvalidParameter);
+                }
+
+                Ok((name.to_string(), value.map(str::to_string)))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(ViaEntry {
+            comment,
+            host: host.into_owned(),
+            parameters,
+            port,
+            transport,
+            version,
+        })
+    }
+}
+
+/// A possible error value when converting to a [`Via`] from [`HeaderName`]s.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum ViaError {
+    /// The comment of an entry was not enclosed in balanced parentheses.
+    InvalidComment,
+
+    /// An extension parameter of an entry was invalid.
+    InvalidParameter,
+
+    /// The host or port an entry was received by was invalid.
+    InvalidReceivedBy,
+
+    /// The transport protocol of an entry was not a token.
+    InvalidTransport,
+
+    /// The protocol version of an entry was invalid or unknown.
+    InvalidVersion,
+
+    /// An entry was missing the host it was received by.
+    MissingReceivedBy,
+
+    /// The protocol of an entry was not RTSP.
+    UnsupportedProtocol,
+}
+
+impl Display for ViaError {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        use self::ViaError::*;
+
+        match self {
+            InvalidComment => write!(formatter, "invalid via header comment"),
+            InvalidParameter => write!(formatter, "invalid via header parameter"),
+            InvalidReceivedBy => write!(formatter, "invalid via header received by"),
+            InvalidTransport => write!(formatter, "invalid via header transport"),
+            InvalidVersion => write!(formatter, "invalid via header version"),
+            MissingReceivedBy => write!(formatter, "missing via header received by"),
+            UnsupportedProtocol => write!(formatter, "unsupported via header protocol"),
+        }
+    }
+}
+
+impl Error for ViaError {}
+
+impl From<Infallible> for ViaError {
+    fn from(_: Infallible) -> Self {
+        ViaError::InvalidReceivedBy
+    }
+}
+
+/// Returns whether the given value can be the text of a comment, which requires its parentheses
+/// to be balanced.
+fn is_comment_text(value: &str) -> bool {
+    let mut depth = 0usize;
+
+    for c in value.chars() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth == 0 => return false,
+            ')' => depth -= 1,
+            c if c.is_control() => return false,
+            _ => (),
+        }
+    }
+
+    depth == 0
+}
+
+/// Splits the given header value into its entries, ignoring the commas within comments.
+fn split_entries(value: &str) -> Vec<&str> {
+    let mut depth = 0usize;
+    let mut entries = Vec::new();
+    let mut start = 0;
+
+    for (index, c) in value.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                entries.push(&value[start..index]);
+                start = index + 1;
+            }
+            _ => (),
+        }
+    }
+
+    entries.push(&value[start..]);
+    entries
+}
+
+#[cfg(test)]
+mod test {
+    use std::convert::TryFrom;
+
+    use crate::{
+        header::types::via::{Via, ViaEntry, ViaError},
+        uri::Host,
+        version::Version,
+    };
+
+    #[test]
+    fn test_via_entry_try_from() {
+        let entry =
+            ViaEntry::try_from("RTSP/2.0/TCP [::1]:8554;ttl=64;hidden (Proxy (beta))").unwrap();
+        assert_eq!(entry.version(), Version::Rtsp2_0);
+        assert_eq!(entry.transport(), Some("TCP"));
+        assert_eq!(entry.host(), &Host::try_from("[::1]").unwrap());
+        assert_eq!(entry.port(), Some(8554));
+        assert_eq!(
+            entry.parameters(),
+            &[
+                (String::from("ttl"), Some(String::from("64"))),
+                (String::from("hidden"), None)
+            ]
+        );
+        assert_eq!(entry.comment(), Some("Proxy (beta)"));
+        assert_eq!(
+            entry.to_string(),
+            "RTSP/2.0/TCP [::1]:8554;ttl=64;hidden (Proxy (beta))"
+        );
+
+        let entry = ViaEntry::try_from("1.0 camera").unwrap();
+        assert_eq!(entry.version(), Version::Rtsp1_0);
+        assert_eq!(entry.transport(), None);
+        assert_eq!(entry.port(), None);
+
+        assert_eq!(
+            ViaEntry::try_from("RTSP/2.0"),
+            Err(ViaError::MissingReceivedBy)
+        );
+        assert_eq!(
+            ViaEntry::try_from("HTTP/1.1 proxy"),
+            Err(ViaError::UnsupportedProtocol)
+        );
+        assert_eq!(
+            ViaEntry::try_from("RTSP/3.0 proxy"),
+            Err(ViaError::InvalidVersion)
+        );
+        assert_eq!(
+            ViaEntry::try_from("RTSP/2.0 user@proxy"),
+            Err(ViaError::InvalidReceivedBy)
+        );
+        assert_eq!(
+            ViaEntry::try_from("RTSP/2.0 proxy (unbalanced"),
+            Err(ViaError::InvalidComment)
+        );
+        assert_eq!(
+            ViaEntry::try_from("RTSP/2.0 proxy;ttl="),
+            Err(ViaError::InvalidParameter)
+        );
+    }
+
+    #[test]
+    fn test_via_loops_and_stripping() {
+        let mut via = Via::try_from(
+            "RTSP/2.0 proxy (Relay, version 1), RTSP/2.0 camera:8554, RTSP/1.0 proxy:554",
+        )
+        .unwrap();
+        assert_eq!(via.len(), 3);
+
+        let proxy = ViaEntry::new(
+            Version::Rtsp2_0,
+            Host::try_from("PROXY").unwrap().into_owned(),
+            None,
+        );
+        let camera = ViaEntry::try_from("RTSP/2.0 camera").unwrap();
+        assert!(via.detects_loop(&proxy));
+        assert!(!via.detects_loop(&camera));
+
+        assert!(via.strip(&proxy));
+        assert!(!via.strip(&proxy));
+        assert_eq!(via.len(), 1);
+        assert_eq!(via[0].port(), Some(8554));
+    }
+}
@@ -1,15 +1,24 @@
+#[macro_use]
+mod trace;
+
 mod syntax;
 
 pub mod client;
 pub mod header;
+pub mod media;
 pub mod method;
+pub mod metrics;
+pub mod middleware;
 pub mod protocol;
 pub mod reason;
+pub mod relay;
 pub mod request;
 pub mod response;
 pub mod server;
 pub mod session;
 pub mod status;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 pub mod uri;
 
 pub use rtsp_common::version;
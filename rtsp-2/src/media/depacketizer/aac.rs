@@ -0,0 +1,517 @@
+//! AAC Depacketizer
+//!
+//! Reassembles AAC frames from RTP packets using the `"mpeg4-generic"` payload format described by
+//! [RFC3640](https://tools.ietf.org/html/rfc3640) in the `"AAC-hbr"` and `"AAC-lbr"` modes, which
+//! is what virtually all RTSP sources use for AAC audio.
+
+use std::{
+    collections::VecDeque,
+    convert::TryFrom,
+    error::Error,
+    fmt::{self, Display, Formatter},
+};
+
+use bytes::{Bytes, BytesMut};
+
+use crate::media::{
+    depacketizer::{DepacketizeError, Depacketizer},
+    format::{FormatError, FormatParameters},
+    frame::Frame,
+    rtp::Packet,
+};
+
+/// The number of samples in an AAC frame unless stated otherwise by `"constantDuration"`.
+pub const AAC_DEFAULT_FRAME_DURATION: u32 = 1024;
+
+/// The sampling frequencies addressable by the sampling frequency index of an
+/// `AudioSpecificConfig`.
+pub const AAC_SAMPLING_FREQUENCIES: [u32; 13] = [
+    96000, 88200, 64000, 48000, 44100, 32000, 24000, 22050, 16000, 12000, 11025, 8000, 7350,
+];
+
+/// A depacketizer for AAC audio.
+#[derive(Debug)]
+pub struct AACDepacketizer {
+    /// The decoder configuration given by the `"config"` format parameter.
+    config: Option<AudioSpecificConfig>,
+
+    /// The access unit currently being reassembled from fragments, along with its expected size.
+    fragment: Option<(BytesMut, usize)>,
+
+    /// The number of RTP timestamp ticks each access unit spans.
+    frame_duration: u32,
+
+    /// Completely reassembled frames waiting to be pulled.
+    frames: VecDeque<Frame>,
+
+    /// The number of bits used for the index of the first AU header.
+    index_length: u32,
+
+    /// The number of bits used for the index delta of subsequent AU headers.
+    index_delta_length: u32,
+
+    /// The number of bits used for the size of each access unit.
+    size_length: u32,
+}
+
+impl AACDepacketizer {
+    /// Returns the decoder configuration given by the `"config"` format parameter.
+    pub fn config(&self) -> Option<&AudioSpecificConfig> {
+        self.config.as_ref()
+    }
+
+    /// Constructs a new depacketizer from the clock rate and `"a=fmtp"` parameters of the stream.
+    pub fn from_parameters(
+        clock_rate: u32,
+        parameters: &FormatParameters,
+    ) -> Result<Self, FormatError> {
+        let mode = parameters.get("mode").unwrap_or("");
+
+        if !mode.eq_ignore_ascii_case("AAC-hbr") && !mode.eq_ignore_ascii_case("AAC-lbr") {
+            return Err(FormatError::Unsupported);
+        }
+
+        if parameters.get("sizelength").is_none() {
+            return Err(FormatError::MissingParameter("sizelength"));
+        }
+
+        let size_length = parameters
+            .get_integer::<u32>("sizelength")
+            .filter(|&length| length > 0 && length <= 16)
+            .ok_or(FormatError::InvalidParameterValue("sizelength"))?;
+        let index_length = parameters.get_integer::<u32>("indexlength").unwrap_or(0);
+        let index_delta_length = parameters
+            .get_integer::<u32>("indexdeltalength")
+            .unwrap_or(0);
+
+        if index_length > 16 || index_delta_length > 16 {
+            return Err(FormatError::InvalidParameterValue("indexlength"));
+        }
+
+        let config = match parameters.get("config") {
+            Some(value) => Some(
+                decode_hex(value)
+                    .and_then(|config| AudioSpecificConfig::try_from(&config[..]).ok())
+                    .ok_or(FormatError::InvalidParameterValue("config"))?,
+            ),
+            None => None,
+        };
+
+        // The RTP clock rate is normally the sampling frequency, in which case one frame spans
+        // exactly its number of samples.
+        let frame_duration = parameters
+            .get_integer::<u32>("constantduration")
+            .unwrap_or_else(|| match config {
+                Some(config) if config.sampling_frequency() != clock_rate => {
+                    (u64::from(AAC_DEFAULT_FRAME_DURATION) * u64::from(clock_rate)
+                        / u64::from(config.sampling_frequency())) as u32
+                }
+                _ => AAC_DEFAULT_FRAME_DURATION,
+            });
+
+        Ok(AACDepacketizer {
+            config,
+            fragment: None,
+            frame_duration,
+            frames: VecDeque::new(),
+            index_length,
+            index_delta_length,
+            size_length,
+        })
+    }
+
+    /// Returns the number of RTP timestamp ticks each access unit spans.
+    pub fn frame_duration(&self) -> u32 {
+        self.frame_duration
+    }
+
+    /// Parses the AU header section at the start of the payload, returning the size and index of
+    /// each access unit along with the remaining payload.
+    fn parse_au_headers(
+        &self,
+        payload: &Bytes,
+    ) -> Result<(Vec<(usize, u32)>, Bytes), DepacketizeError> {
+        if payload.len() < 2 {
+            return Err(DepacketizeError::Truncated);
+        }
+
+        let headers_length = ((usize::from(payload[0]) << 8) | usize::from(payload[1])) as u32;
+        let headers_size = (headers_length as usize + 7) / 8;
+
+        if payload.len() < 2 + headers_size {
+            return Err(DepacketizeError::Truncated);
+        }
+
+        let mut reader = BitReader::new(&payload[2..2 + headers_size]);
+        let mut headers = Vec::new();
+        let mut consumed = 0;
+        let mut index = 0;
+
+        while consumed < headers_length {
+            let index_bits = if headers.is_empty() {
+                self.index_length
+            } else {
+                self.index_delta_length
+            };
+
+            let size = reader
+                .read(self.size_length)
+                .ok_or(DepacketizeError::InvalidAUHeader)? as usize;
+            let value = reader
+                .read(index_bits)
+                .ok_or(DepacketizeError::InvalidAUHeader)?;
+
+            index = if headers.is_empty() {
+                0
+            } else {
+                index + value + 1
+            };
+
+            headers.push((size, index));
+            consumed += self.size_length + index_bits;
+        }
+
+        if consumed != headers_length || headers.is_empty() {
+            return Err(DepacketizeError::InvalidAUHeader);
+        }
+
+        Ok((headers, payload.slice_from(2 + headers_size)))
+    }
+}
+
+impl Depacketizer for AACDepacketizer {
+    fn discontinuity(&mut self) {
+        self.fragment = None;
+    }
+
+    fn pull(&mut self) -> Option<Frame> {
+        self.frames.pop_front()
+    }
+
+    fn push(&mut self, packet: Packet) -> Result<(), DepacketizeError> {
+        let (headers, mut data) = self.parse_au_headers(packet.payload())?;
+
+        // A single access unit that is larger than the rest of the packet is a fragment. The
+        // remaining fragments carry the same AU header and the last one has the marker bit set.
+        if headers.len() == 1 && headers[0].0 > data.len() {
+            let size = headers[0].0;
+            let (fragment, expected_size) = self
+                .fragment
+                .get_or_insert_with(|| (BytesMut::with_capacity(size), size));
+
+            if *expected_size != size {
+                self.fragment = None;
+                return Err(DepacketizeError::UnexpectedFragment);
+            }
+
+            fragment.extend_from_slice(&data);
+
+            if fragment.len() > size {
+                self.fragment = None;
+                return Err(DepacketizeError::InvalidAUHeader);
+            }
+
+            if packet.marker() {
+                let (fragment, _) = self.fragment.take().expect("fragment should be present");
+
+                if fragment.len() != size {
+                    return Err(DepacketizeError::Truncated);
+                }
+
+                self.frames
+                    .push_back(Frame::new(fragment.freeze(), packet.timestamp(), true));
+            }
+
+            return Ok(());
+        }
+
+        if self.fragment.take().is_some() {
+            return Err(DepacketizeError::UnexpectedFragment);
+        }
+
+        for (size, index) in headers {
+            if data.len() < size {
+                return Err(DepacketizeError::Truncated);
+            }
+
+            let timestamp = packet
+                .timestamp()
+                .wrapping_add(index.wrapping_mul(self.frame_duration));
+            self.frames
+                .push_back(Frame::new(data.split_to(size), timestamp, true));
+        }
+
+        Ok(())
+    }
+}
+
+/// The MPEG-4 audio decoder configuration as described by ISO/IEC 14496-3, Section 1.6.2.1.
+///
+/// Only the fields common to all audio object types are decoded.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct AudioSpecificConfig {
+    /// The MPEG-4 audio object type, e.g. `2` for AAC-LC.
+    audio_object_type: u8,
+
+    /// The channel configuration, which is the number of channels for values `1` to `6`.
+    channel_configuration: u8,
+
+    /// The sampling frequency in Hz.
+    sampling_frequency: u32,
+}
+
+impl AudioSpecificConfig {
+    /// Returns the MPEG-4 audio object type.
+    pub fn audio_object_type(&self) -> u8 {
+        self.audio_object_type
+    }
+
+    /// Returns the channel configuration.
+    pub fn channel_configuration(&self) -> u8 {
+        self.channel_configuration
+    }
+
+    /// Encodes the configuration into its two to five byte binary form.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut writer = BitWriter::default();
+
+        if self.audio_object_type >= 31 {
+            writer.write(31, 5);
+            writer.write(u32::from(self.audio_object_type - 32), 6);
+        } else {
+            writer.write(u32::from(self.audio_object_type), 5);
+        }
+
+        match AAC_SAMPLING_FREQUENCIES
+            .iter()
+            .position(|&frequency| frequency == self.sampling_frequency)
+        {
+            Some(index) => writer.write(index as u32, 4),
+            None => {
+                writer.write(15, 4);
+                writer.write(self.sampling_frequency, 24);
+            }
+        }
+
+        writer.write(u32::from(self.channel_configuration), 4);
+        writer.finish()
+    }
+
+    /// Constructs a new configuration.
+    pub fn new(audio_object_type: u8, sampling_frequency: u32, channel_configuration: u8) -> Self {
+        AudioSpecificConfig {
+            audio_object_type,
+            channel_configuration,
+            sampling_frequency,
+        }
+    }
+
+    /// Returns the index of the sampling frequency in [`AAC_SAMPLING_FREQUENCIES`], if it is one of
+    /// the standard frequencies.
+    pub fn sampling_frequency_index(&self) -> Option<u8> {
+        AAC_SAMPLING_FREQUENCIES
+            .iter()
+            .position(|&frequency| frequency == self.sampling_frequency)
+            .map(|index| index as u8)
+    }
+
+    /// Returns the sampling frequency in Hz.
+    pub fn sampling_frequency(&self) -> u32 {
+        self.sampling_frequency
+    }
+}
+
+impl<'config> TryFrom<&'config [u8]> for AudioSpecificConfig {
+    type Error = AudioSpecificConfigError;
+
+    /// Decodes a binary `AudioSpecificConfig`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::convert::TryFrom;
+    ///
+    /// use rtsp::media::depacketizer::aac::AudioSpecificConfig;
+    ///
+    /// let config = AudioSpecificConfig::try_from(&[0x12, 0x10][..]).unwrap();
+    /// assert_eq!(config.audio_object_type(), 2);
+    /// assert_eq!(config.sampling_frequency(), 44100);
+    /// assert_eq!(config.channel_configuration(), 2);
+    /// assert_eq!(config.encode(), vec![0x12, 0x10]);
+    /// ```
+    fn try_from(value: &'config [u8]) -> Result<Self, Self::Error> {
+        let mut reader = BitReader::new(value);
+        let mut audio_object_type = reader.read(5).ok_or(AudioSpecificConfigError)? as u8;
+
+        if audio_object_type == 31 {
+            audio_object_type = 32 + reader.read(6).ok_or(AudioSpecificConfigError)? as u8;
+        }
+
+        let sampling_frequency = match reader.read(4).ok_or(AudioSpecificConfigError)? {
+            15 => reader.read(24).ok_or(AudioSpecificConfigError)?,
+            index => *AAC_SAMPLING_FREQUENCIES
+                .get(index as usize)
+                .ok_or(AudioSpecificConfigError)?,
+        };
+
+        let channel_configuration = reader.read(4).ok_or(AudioSpecificConfigError)? as u8;
+
+        Ok(AudioSpecificConfig {
+            audio_object_type,
+            channel_configuration,
+            sampling_frequency,
+        })
+    }
+}
+
+/// An error type for when an `AudioSpecificConfig` was truncated or used a reserved sampling
+/// frequency index.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub struct AudioSpecificConfigError;
+
+impl Display for AudioSpecificConfigError {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(formatter, "invalid audio specific config")
+    }
+}
+
+impl Error for AudioSpecificConfigError {}
+
+/// A reader for big-endian bit fields.
+struct BitReader<'buffer> {
+    /// The buffer being read.
+    buffer: &'buffer [u8],
+
+    /// The index of the next bit to be read.
+    position: usize,
+}
+
+impl<'buffer> BitReader<'buffer> {
+    /// Constructs a new reader at the start of the buffer.
+    fn new(buffer: &'buffer [u8]) -> Self {
+        BitReader {
+            buffer,
+            position: 0,
+        }
+    }
+
+    /// Reads the given number of bits, up to 32, returning [`Option::None`] if not enough bits are
+    /// left.
+    fn read(&mut self, bits: u32) -> Option<u32> {
+        let mut value = 0;
+
+        for _ in 0..bits {
+            let byte = self.buffer.get(self.position / 8)?;
+            let bit = (byte >> (7 - self.position % 8)) & 1;
+            value = (value << 1) | u32::from(bit);
+            self.position += 1;
+        }
+
+        Some(value)
+    }
+}
+
+/// A writer for big-endian bit fields.
+#[derive(Default)]
+pub(crate) struct BitWriter {
+    /// The bytes written so far, with the last one possibly partially filled.
+    buffer: Vec<u8>,
+
+    /// The number of bits written so far.
+    position: usize,
+}
+
+impl BitWriter {
+    /// Returns the written bytes, with the last byte padded with zero bits.
+    pub(crate) fn finish(self) -> Vec<u8> {
+        self.buffer
+    }
+
+    /// Writes the lowest given number of bits of the value.
+    pub(crate) fn write(&mut self, value: u32, bits: u32) {
+        for shift in (0..bits).rev() {
+            if self.position % 8 == 0 {
+                self.buffer.push(0);
+            }
+
+            let bit = ((value >> shift) & 1) as u8;
+            let last = self.buffer.len() - 1;
+            self.buffer[last] |= bit << (7 - self.position % 8);
+            self.position += 1;
+        }
+    }
+}
+
+/// Decodes a hexadecimal string such as the `"config"` format parameter.
+fn decode_hex(value: &str) -> Option<Vec<u8>> {
+    if value.len() % 2 != 0 || !value.is_ascii() {
+        return None;
+    }
+
+    (0..value.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(&value[index..index + 2], 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use std::convert::TryFrom;
+
+    use crate::media::{
+        depacketizer::{aac::AACDepacketizer, Depacketizer},
+        format::FormatParameters,
+        rtp::Packet,
+    };
+
+    fn depacketizer() -> AACDepacketizer {
+        let parameters = FormatParameters::try_from(
+            "97 streamtype=5;profile-level-id=15;mode=AAC-hbr;config=1210;\
+             SizeLength=13;IndexLength=3;IndexDeltaLength=3",
+        )
+        .unwrap();
+        AACDepacketizer::from_parameters(44100, &parameters).unwrap()
+    }
+
+    #[test]
+    fn test_aac_depacketizer_multiple_access_units() {
+        let mut depacketizer = depacketizer();
+        assert_eq!(depacketizer.config().unwrap().sampling_frequency(), 44100);
+
+        // Two AU headers (32 bits): size 2 with index 0 and size 1 with index delta 0.
+        let payload = [0x00, 0x20, 0x00, 0x10, 0x00, 0x08, 0xAA, 0xBB, 0xCC];
+        depacketizer
+            .push(Packet::new(97, 0, 1000, 0, true, payload.to_vec()))
+            .unwrap();
+
+        let frame = depacketizer.pull().unwrap();
+        assert_eq!(&frame.data()[..], &[0xAA, 0xBB]);
+        assert_eq!(frame.timestamp(), 1000);
+
+        let frame = depacketizer.pull().unwrap();
+        assert_eq!(&frame.data()[..], &[0xCC]);
+        assert_eq!(frame.timestamp(), 2024);
+    }
+
+    #[test]
+    fn test_aac_depacketizer_fragmented_access_unit() {
+        let mut depacketizer = depacketizer();
+
+        // One AU header (16 bits) announcing an access unit of size 4.
+        let first = [0x00, 0x10, 0x00, 0x20, 0x01, 0x02];
+        let second = [0x00, 0x10, 0x00, 0x20, 0x03, 0x04];
+        depacketizer
+            .push(Packet::new(97, 0, 0, 0, false, first.to_vec()))
+            .unwrap();
+        assert_eq!(depacketizer.pull(), None);
+        depacketizer
+            .push(Packet::new(97, 1, 0, 0, true, second.to_vec()))
+            .unwrap();
+
+        assert_eq!(
+            &depacketizer.pull().unwrap().data()[..],
+            &[0x01, 0x02, 0x03, 0x04]
+        );
+    }
+}
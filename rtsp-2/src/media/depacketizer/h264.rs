@@ -0,0 +1,290 @@
+//! H.264 Depacketizer
+//!
+//! Reassembles access units from RTP packets using the payload format described by
+//! [RFC6184](https://tools.ietf.org/html/rfc6184). Single NAL unit packets, STAP-A aggregation
+//! packets and FU-A fragmentation units are supported, which covers packetization modes 0 and 1.
+//! The interleaved packetization mode is not supported.
+
+use std::{collections::VecDeque, mem};
+
+use bytes::{Bytes, BytesMut};
+
+use crate::media::{
+    depacketizer::{self, DepacketizeError, Depacketizer},
+    format::{FormatError, FormatParameters},
+    frame::Frame,
+    rtp::Packet,
+};
+
+/// The NAL unit type of an IDR slice.
+const NAL_UNIT_TYPE_IDR: u8 = 5;
+
+/// The NAL unit type of a sequence parameter set.
+const NAL_UNIT_TYPE_SPS: u8 = 7;
+
+/// The NAL unit type of a picture parameter set.
+const NAL_UNIT_TYPE_PPS: u8 = 8;
+
+/// The NAL unit type of a STAP-A aggregation packet.
+const NAL_UNIT_TYPE_STAP_A: u8 = 24;
+
+/// The NAL unit type of an FU-A fragmentation unit.
+const NAL_UNIT_TYPE_FU_A: u8 = 28;
+
+/// A depacketizer for H.264 video.
+#[derive(Debug, Default)]
+pub struct H264Depacketizer {
+    /// The NAL units of the access unit currently being reassembled.
+    access_unit: Vec<Bytes>,
+
+    /// The NAL unit currently being reassembled from fragmentation units.
+    fragment: Option<BytesMut>,
+
+    /// Completely reassembled frames waiting to be pulled.
+    frames: VecDeque<Frame>,
+
+    /// Whether the current access unit contains in-band parameter sets.
+    has_parameter_sets: bool,
+
+    /// Whether the current access unit contains an IDR slice.
+    keyframe: bool,
+
+    /// The out-of-band parameter sets given by the `"sprop-parameter-sets"` format parameter.
+    /// These are prepended to keyframes that do not carry their own.
+    parameter_sets: Vec<Bytes>,
+
+    /// The RTP timestamp of the access unit currently being reassembled.
+    timestamp: u32,
+}
+
+impl H264Depacketizer {
+    /// Adds a complete NAL unit to the current access unit.
+    fn add_nal_unit(&mut self, nal_unit: Bytes) {
+        match nal_unit[0] & 0x1F {
+            NAL_UNIT_TYPE_IDR => self.keyframe = true,
+            NAL_UNIT_TYPE_SPS | NAL_UNIT_TYPE_PPS => self.has_parameter_sets = true,
+            _ => (),
+        }
+
+        self.access_unit.push(nal_unit);
+    }
+
+    /// Finishes the current access unit, if it contains any NAL units.
+    fn finish_access_unit(&mut self) {
+        if self.access_unit.is_empty() {
+            return;
+        }
+
+        let nal_units = mem::take(&mut self.access_unit);
+        let data = if self.keyframe && !self.has_parameter_sets {
+            depacketizer::annex_b(self.parameter_sets.iter().chain(nal_units.iter()))
+        } else {
+            depacketizer::annex_b(nal_units.iter())
+        };
+
+        self.frames
+            .push_back(Frame::new(data, self.timestamp, self.keyframe));
+        self.has_parameter_sets = false;
+        self.keyframe = false;
+    }
+
+    /// Constructs a new depacketizer from the `"a=fmtp"` parameters of the stream.
+    pub fn from_parameters(parameters: &FormatParameters) -> Result<Self, FormatError> {
+        match parameters.get("packetization-mode") {
+            None | Some("0") | Some("1") => (),
+            Some(_) => return Err(FormatError::Unsupported),
+        }
+
+        let parameter_sets = match parameters.get("sprop-parameter-sets") {
+            Some(value) => depacketizer::decode_parameter_sets(value, "sprop-parameter-sets")?,
+            None => Vec::new(),
+        };
+
+        Ok(H264Depacketizer::with_parameter_sets(parameter_sets))
+    }
+
+    /// Constructs a new depacketizer without out-of-band parameter sets.
+    pub fn new() -> Self {
+        H264Depacketizer::default()
+    }
+
+    /// Returns the out-of-band parameter sets.
+    pub fn parameter_sets(&self) -> &[Bytes] {
+        &self.parameter_sets
+    }
+
+    /// Processes an FU-A fragmentation unit.
+    fn push_fragment(&mut self, payload: Bytes) -> Result<(), DepacketizeError> {
+        if payload.len() < 3 {
+            return Err(DepacketizeError::Truncated);
+        }
+
+        let indicator = payload[0];
+        let header = payload[1];
+        let is_start = header & 0x80 != 0;
+        let is_end = header & 0x40 != 0;
+
+        if is_start {
+            let mut fragment = BytesMut::with_capacity(payload.len() * 4);
+            fragment.extend_from_slice(&[(indicator & 0xE0) | (header & 0x1F)]);
+            fragment.extend_from_slice(&payload[2..]);
+            self.fragment = Some(fragment);
+        } else {
+            match self.fragment.as_mut() {
+                Some(fragment) => fragment.extend_from_slice(&payload[2..]),
+                None => return Err(DepacketizeError::UnexpectedFragment),
+            }
+        }
+
+        if is_end {
+            let fragment = self.fragment.take().expect("fragment should be present");
+            self.add_nal_unit(fragment.freeze());
+        }
+
+        Ok(())
+    }
+
+    /// Constructs a new depacketizer with the given out-of-band parameter sets.
+    pub fn with_parameter_sets(parameter_sets: Vec<Bytes>) -> Self {
+        H264Depacketizer {
+            parameter_sets,
+            ..H264Depacketizer::default()
+        }
+    }
+}
+
+impl Depacketizer for H264Depacketizer {
+    fn discontinuity(&mut self) {
+        self.access_unit.clear();
+        self.fragment = None;
+        self.has_parameter_sets = false;
+        self.keyframe = false;
+    }
+
+    fn pull(&mut self) -> Option<Frame> {
+        self.frames.pop_front()
+    }
+
+    fn push(&mut self, packet: Packet) -> Result<(), DepacketizeError> {
+        if packet.timestamp() != self.timestamp {
+            self.finish_access_unit();
+            self.fragment = None;
+            self.timestamp = packet.timestamp();
+        }
+
+        let marker = packet.marker();
+        let payload = packet.payload().clone();
+
+        if payload.is_empty() {
+            return Err(DepacketizeError::Truncated);
+        }
+
+        match payload[0] & 0x1F {
+            1..=23 => self.add_nal_unit(payload),
+            NAL_UNIT_TYPE_STAP_A => {
+                for nal_unit in depacketizer::split_aggregation(payload.slice_from(1), |_| 0)? {
+                    self.add_nal_unit(nal_unit);
+                }
+            }
+            NAL_UNIT_TYPE_FU_A => self.push_fragment(payload)?,
+            25..=27 | 29 => return Err(DepacketizeError::Unsupported),
+            nal_unit_type => return Err(DepacketizeError::InvalidNALUnitType(nal_unit_type)),
+        }
+
+        if marker {
+            self.finish_access_unit();
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::convert::TryFrom;
+
+    use crate::media::{
+        depacketizer::{h264::H264Depacketizer, DepacketizeError, Depacketizer},
+        format::{FormatError, FormatParameters},
+        rtp::Packet,
+    };
+
+    fn packet(sequence_number: u16, timestamp: u32, marker: bool, payload: &[u8]) -> Packet {
+        Packet::new(96, sequence_number, timestamp, 0, marker, payload.to_vec())
+    }
+
+    #[test]
+    fn test_h264_depacketizer_fu_a() {
+        let parameters = FormatParameters::try_from(
+            "96 packetization-mode=1;sprop-parameter-sets=Z0IAKeKQ,aM4xUg==",
+        )
+        .unwrap();
+        let mut depacketizer = H264Depacketizer::from_parameters(&parameters).unwrap();
+
+        depacketizer
+            .push(packet(0, 3000, false, &[0x7C, 0x85, 0xAA]))
+            .unwrap();
+        depacketizer
+            .push(packet(1, 3000, true, &[0x7C, 0x45, 0xBB]))
+            .unwrap();
+
+        let frame = depacketizer.pull().unwrap();
+        assert!(frame.is_keyframe());
+        assert_eq!(frame.timestamp(), 3000);
+        assert_eq!(
+            &frame.data()[..],
+            &[
+                0, 0, 0, 1, 0x67, 0x42, 0x00, 0x29, 0xE2, 0x90, 0, 0, 0, 1, 0x68, 0xCE, 0x31, 0x52,
+                0, 0, 0, 1, 0x65, 0xAA, 0xBB
+            ][..]
+        );
+    }
+
+    #[test]
+    fn test_h264_depacketizer_invalid_parameter_sets() {
+        let parameters = FormatParameters::try_from(
+            "96 packetization-mode=1;sprop-parameter-sets=Z0I!,aM4xUg==",
+        )
+        .unwrap();
+        assert_eq!(
+            H264Depacketizer::from_parameters(&parameters).unwrap_err(),
+            FormatError::InvalidParameterValue("sprop-parameter-sets")
+        );
+    }
+
+    #[test]
+    fn test_h264_depacketizer_stap_a() {
+        let mut depacketizer = H264Depacketizer::new();
+
+        depacketizer
+            .push(packet(
+                0,
+                0,
+                false,
+                &[0x18, 0x00, 0x02, 0x09, 0xF0, 0x00, 0x02, 0x41, 0x01],
+            ))
+            .unwrap();
+        assert_eq!(depacketizer.pull(), None);
+
+        depacketizer
+            .push(packet(1, 3000, false, &[0x41, 0x02]))
+            .unwrap();
+
+        let frame = depacketizer.pull().unwrap();
+        assert!(!frame.is_keyframe());
+        assert_eq!(
+            &frame.data()[..],
+            &[0, 0, 0, 1, 0x09, 0xF0, 0, 0, 0, 1, 0x41, 0x01][..]
+        );
+    }
+
+    #[test]
+    fn test_h264_depacketizer_unexpected_fragment() {
+        let mut depacketizer = H264Depacketizer::new();
+
+        assert_eq!(
+            depacketizer.push(packet(0, 0, true, &[0x7C, 0x45, 0xBB])),
+            Err(DepacketizeError::UnexpectedFragment)
+        );
+    }
+}
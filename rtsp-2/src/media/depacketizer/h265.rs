@@ -0,0 +1,275 @@
+//! H.265 Depacketizer
+//!
+//! Reassembles access units from RTP packets using the payload format described by
+//! [RFC7798](https://tools.ietf.org/html/rfc7798). Single NAL unit packets, aggregation packets and
+//! fragmentation units are supported, including the decoding order number fields that are present
+//! when `"sprop-max-don-diff"` is greater than zero. PACI packets are not supported.
+
+use std::{collections::VecDeque, mem};
+
+use bytes::{Bytes, BytesMut};
+
+use crate::media::{
+    depacketizer::{self, DepacketizeError, Depacketizer},
+    format::{FormatError, FormatParameters},
+    frame::Frame,
+    rtp::Packet,
+};
+
+/// The NAL unit type of a video parameter set.
+const NAL_UNIT_TYPE_VPS: u8 = 32;
+
+/// The NAL unit type of a sequence parameter set.
+const NAL_UNIT_TYPE_SPS: u8 = 33;
+
+/// The NAL unit type of a picture parameter set.
+const NAL_UNIT_TYPE_PPS: u8 = 34;
+
+/// The NAL unit type of an aggregation packet.
+const NAL_UNIT_TYPE_AP: u8 = 48;
+
+/// The NAL unit type of a fragmentation unit.
+const NAL_UNIT_TYPE_FU: u8 = 49;
+
+/// The NAL unit type of a PACI packet.
+const NAL_UNIT_TYPE_PACI: u8 = 50;
+
+/// A depacketizer for H.265 video.
+#[derive(Debug, Default)]
+pub struct H265Depacketizer {
+    /// The NAL units of the access unit currently being reassembled.
+    access_unit: Vec<Bytes>,
+
+    /// The NAL unit currently being reassembled from fragmentation units.
+    fragment: Option<BytesMut>,
+
+    /// Completely reassembled frames waiting to be pulled.
+    frames: VecDeque<Frame>,
+
+    /// Whether decoding order number fields are present in aggregation packets and the first
+    /// fragmentation unit of a NAL unit.
+    has_decoding_order_numbers: bool,
+
+    /// Whether the current access unit contains in-band parameter sets.
+    has_parameter_sets: bool,
+
+    /// Whether the current access unit contains an intra random access point picture.
+    keyframe: bool,
+
+    /// The out-of-band parameter sets given by the `"sprop-vps"`, `"sprop-sps"` and `"sprop-pps"`
+    /// format parameters. These are prepended to keyframes that do not carry their own.
+    parameter_sets: Vec<Bytes>,
+
+    /// The RTP timestamp of the access unit currently being reassembled.
+    timestamp: u32,
+}
+
+impl H265Depacketizer {
+    /// Adds a complete NAL unit to the current access unit.
+    fn add_nal_unit(&mut self, nal_unit: Bytes) {
+        match nal_unit_type(&nal_unit) {
+            16..=21 => self.keyframe = true,
+            NAL_UNIT_TYPE_VPS | NAL_UNIT_TYPE_SPS | NAL_UNIT_TYPE_PPS => {
+                self.has_parameter_sets = true
+            }
+            _ => (),
+        }
+
+        self.access_unit.push(nal_unit);
+    }
+
+    /// Finishes the current access unit, if it contains any NAL units.
+    fn finish_access_unit(&mut self) {
+        if self.access_unit.is_empty() {
+            return;
+        }
+
+        let nal_units = mem::take(&mut self.access_unit);
+        let data = if self.keyframe && !self.has_parameter_sets {
+            depacketizer::annex_b(self.parameter_sets.iter().chain(nal_units.iter()))
+        } else {
+            depacketizer::annex_b(nal_units.iter())
+        };
+
+        self.frames
+            .push_back(Frame::new(data, self.timestamp, self.keyframe));
+        self.has_parameter_sets = false;
+        self.keyframe = false;
+    }
+
+    /// Constructs a new depacketizer from the `"a=fmtp"` parameters of the stream.
+    pub fn from_parameters(parameters: &FormatParameters) -> Result<Self, FormatError> {
+        let mut parameter_sets = Vec::new();
+
+        for &name in &["sprop-vps", "sprop-sps", "sprop-pps"] {
+            if let Some(value) = parameters.get(name) {
+                parameter_sets.extend(depacketizer::decode_parameter_sets(value, name)?);
+            }
+        }
+
+        Ok(H265Depacketizer {
+            has_decoding_order_numbers: parameters
+                .get_integer::<u32>("sprop-max-don-diff")
+                .unwrap_or(0)
+                > 0,
+            parameter_sets,
+            ..H265Depacketizer::default()
+        })
+    }
+
+    /// Constructs a new depacketizer without out-of-band parameter sets or decoding order numbers.
+    pub fn new() -> Self {
+        H265Depacketizer::default()
+    }
+
+    /// Returns the out-of-band parameter sets.
+    pub fn parameter_sets(&self) -> &[Bytes] {
+        &self.parameter_sets
+    }
+
+    /// Processes a fragmentation unit.
+    fn push_fragment(&mut self, payload: Bytes) -> Result<(), DepacketizeError> {
+        if payload.len() < 4 {
+            return Err(DepacketizeError::Truncated);
+        }
+
+        let header = payload[2];
+        let is_start = header & 0x80 != 0;
+        let is_end = header & 0x40 != 0;
+
+        if is_start {
+            let offset = if self.has_decoding_order_numbers {
+                5
+            } else {
+                3
+            };
+
+            if payload.len() <= offset {
+                return Err(DepacketizeError::Truncated);
+            }
+
+            let mut fragment = BytesMut::with_capacity(payload.len() * 4);
+            fragment.extend_from_slice(&[(payload[0] & 0x81) | ((header & 0x3F) << 1), payload[1]]);
+            fragment.extend_from_slice(&payload[offset..]);
+            self.fragment = Some(fragment);
+        } else {
+            match self.fragment.as_mut() {
+                Some(fragment) => fragment.extend_from_slice(&payload[3..]),
+                None => return Err(DepacketizeError::UnexpectedFragment),
+            }
+        }
+
+        if is_end {
+            let fragment = self.fragment.take().expect("fragment should be present");
+            self.add_nal_unit(fragment.freeze());
+        }
+
+        Ok(())
+    }
+}
+
+impl Depacketizer for H265Depacketizer {
+    fn discontinuity(&mut self) {
+        self.access_unit.clear();
+        self.fragment = None;
+        self.has_parameter_sets = false;
+        self.keyframe = false;
+    }
+
+    fn pull(&mut self) -> Option<Frame> {
+        self.frames.pop_front()
+    }
+
+    fn push(&mut self, packet: Packet) -> Result<(), DepacketizeError> {
+        if packet.timestamp() != self.timestamp {
+            self.finish_access_unit();
+            self.fragment = None;
+            self.timestamp = packet.timestamp();
+        }
+
+        let marker = packet.marker();
+        let payload = packet.payload().clone();
+
+        if payload.len() < 2 {
+            return Err(DepacketizeError::Truncated);
+        }
+
+        match nal_unit_type(&payload) {
+            0..=47 => self.add_nal_unit(payload),
+            NAL_UNIT_TYPE_AP => {
+                let has_decoding_order_numbers = self.has_decoding_order_numbers;
+                let nal_units = depacketizer::split_aggregation(payload.slice_from(2), |first| {
+                    match (has_decoding_order_numbers, first) {
+                        (false, _) => 0,
+                        (true, true) => 2,
+                        (true, false) => 1,
+                    }
+                })?;
+
+                for nal_unit in nal_units {
+                    self.add_nal_unit(nal_unit);
+                }
+            }
+            NAL_UNIT_TYPE_FU => self.push_fragment(payload)?,
+            NAL_UNIT_TYPE_PACI => return Err(DepacketizeError::Unsupported),
+            nal_unit_type => return Err(DepacketizeError::InvalidNALUnitType(nal_unit_type)),
+        }
+
+        if marker {
+            self.finish_access_unit();
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the type of the NAL unit with the given header.
+fn nal_unit_type(nal_unit: &[u8]) -> u8 {
+    (nal_unit[0] >> 1) & 0x3F
+}
+
+#[cfg(test)]
+mod test {
+    use crate::media::{
+        depacketizer::{h265::H265Depacketizer, Depacketizer},
+        rtp::Packet,
+    };
+
+    fn packet(sequence_number: u16, timestamp: u32, marker: bool, payload: &[u8]) -> Packet {
+        Packet::new(96, sequence_number, timestamp, 0, marker, payload.to_vec())
+    }
+
+    #[test]
+    fn test_h265_depacketizer_aggregation_and_fragmentation() {
+        let mut depacketizer = H265Depacketizer::new();
+
+        // An aggregation packet containing a VPS and an SPS, followed by an IDR_W_RADL slice
+        // fragmented across two fragmentation units.
+        depacketizer
+            .push(packet(
+                0,
+                90,
+                false,
+                &[
+                    0x60, 0x01, 0x00, 0x03, 0x40, 0x01, 0xAA, 0x00, 0x03, 0x42, 0x01, 0xBB,
+                ],
+            ))
+            .unwrap();
+        depacketizer
+            .push(packet(1, 90, false, &[0x62, 0x01, 0x93, 0xCC]))
+            .unwrap();
+        depacketizer
+            .push(packet(2, 90, true, &[0x62, 0x01, 0x53, 0xDD]))
+            .unwrap();
+
+        let frame = depacketizer.pull().unwrap();
+        assert!(frame.is_keyframe());
+        assert_eq!(
+            &frame.data()[..],
+            &[
+                0, 0, 0, 1, 0x40, 0x01, 0xAA, 0, 0, 0, 1, 0x42, 0x01, 0xBB, 0, 0, 0, 1, 0x26, 0x01,
+                0xCC, 0xDD
+            ][..]
+        );
+    }
+}
@@ -0,0 +1,261 @@
+//! Depacketizers
+//!
+//! A depacketizer turns a sequence of RTP packets of a single stream into complete [`Frame`]s
+//! according to the payload format of the stream. Packets must be given in sequence number order,
+//! which is what the [`Reassembler`] takes care of by running packets through a [`JitterBuffer`]
+//! first.
+
+pub mod aac;
+pub mod h264;
+pub mod h265;
+
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+    time::Instant,
+};
+
+use bytes::{Bytes, BytesMut};
+
+pub use self::{aac::AACDepacketizer, h264::H264Depacketizer, h265::H265Depacketizer};
+use crate::media::{
+    format::{FormatError, FormatParameters, RTPMap},
+    frame::Frame,
+    jitter::{Config as JitterBufferConfig, Insertion, JitterBuffer},
+    rtp::Packet,
+    timeline::Timeline,
+};
+
+/// The start code preceding each NAL unit in Annex B formatted video frames.
+pub const ANNEX_B_START_CODE: [u8; 4] = [0, 0, 0, 1];
+
+/// A trait for types that reassemble frames from the RTP packets of a single stream.
+pub trait Depacketizer {
+    /// Notifies the depacketizer that packets were lost before the next pushed packet, so any
+    /// partially reassembled data must be discarded.
+    fn discontinuity(&mut self);
+
+    /// Returns the next completely reassembled frame, if there is one.
+    fn pull(&mut self) -> Option<Frame>;
+
+    /// Feeds the next packet of the stream in sequence number order.
+    fn push(&mut self, packet: Packet) -> Result<(), DepacketizeError>;
+}
+
+/// Constructs the depacketizer appropriate for the given payload format.
+///
+/// The supported encoding names are `"H264"`, `"H265"` and `"MPEG4-GENERIC"` (AAC).
+pub fn from_format(
+    rtpmap: &RTPMap,
+    parameters: Option<&FormatParameters>,
+) -> Result<Box<dyn Depacketizer + Send>, FormatError> {
+    let empty_parameters = FormatParameters::new(rtpmap.payload_type());
+    let parameters = parameters.unwrap_or(&empty_parameters);
+    let encoding_name = rtpmap.encoding_name();
+
+    if encoding_name.eq_ignore_ascii_case("H264") {
+        Ok(Box::new(H264Depacketizer::from_parameters(parameters)?))
+    } else if encoding_name.eq_ignore_ascii_case("H265") {
+        Ok(Box::new(H265Depacketizer::from_parameters(parameters)?))
+    } else if encoding_name.eq_ignore_ascii_case("MPEG4-GENERIC") {
+        Ok(Box::new(AACDepacketizer::from_parameters(
+            rtpmap.clock_rate(),
+            parameters,
+        )?))
+    } else {
+        Err(FormatError::Unsupported)
+    }
+}
+
+/// Combines a jitter buffer, a depacketizer and an optional timeline to turn RTP packets of a
+/// single stream, as they arrive, into frames with presentation times.
+pub struct Reassembler {
+    /// The depacketizer for the payload format of the stream.
+    depacketizer: Box<dyn Depacketizer + Send>,
+
+    /// The jitter buffer used to reorder incoming packets.
+    jitter_buffer: JitterBuffer,
+
+    /// The sequence number of the last packet given to the depacketizer, used to detect loss.
+    last_sequence_number: Option<u16>,
+
+    /// The timeline used to assign presentation times to frames.
+    timeline: Option<Timeline>,
+}
+
+impl Reassembler {
+    /// Returns the jitter buffer used to reorder incoming packets.
+    pub fn jitter_buffer(&self) -> &JitterBuffer {
+        &self.jitter_buffer
+    }
+
+    /// Constructs a new reassembler using the given depacketizer and jitter buffer configuration.
+    pub fn new(depacketizer: Box<dyn Depacketizer + Send>, config: JitterBufferConfig) -> Self {
+        Reassembler {
+            depacketizer,
+            jitter_buffer: JitterBuffer::with_config(config),
+            last_sequence_number: None,
+            timeline: None,
+        }
+    }
+
+    /// Returns the instant at which [`Reassembler::poll`] should next be called, if any packets are
+    /// waiting in the jitter buffer.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.jitter_buffer.next_deadline()
+    }
+
+    /// Returns the next frame that can be produced at the given instant.
+    ///
+    /// An error is returned if a released packet could not be depacketized. The packet is dropped
+    /// in that case and polling can continue.
+    pub fn poll(&mut self, now: Instant) -> Result<Option<Frame>, DepacketizeError> {
+        loop {
+            if let Some(mut frame) = self.depacketizer.pull() {
+                if let Some(timeline) = self.timeline.as_mut() {
+                    *frame.presentation_time_mut() = timeline.presentation_time(frame.timestamp());
+                }
+
+                return Ok(Some(frame));
+            }
+
+            let packet = match self.jitter_buffer.pop(now) {
+                Some(packet) => packet,
+                None => return Ok(None),
+            };
+
+            if let Some(last_sequence_number) = self.last_sequence_number {
+                if packet.sequence_number() != last_sequence_number.wrapping_add(1) {
+                    self.depacketizer.discontinuity();
+                }
+            }
+
+            self.last_sequence_number = Some(packet.sequence_number());
+
+            if let Err(error) = self.depacketizer.push(packet) {
+                self.depacketizer.discontinuity();
+                return Err(error);
+            }
+        }
+    }
+
+    /// Inserts a packet that arrived at the given instant.
+    pub fn push(&mut self, packet: Packet, arrival: Instant) -> Insertion {
+        self.jitter_buffer.push(packet, arrival)
+    }
+
+    /// Sets the timeline used to assign presentation times, typically after a PLAY response.
+    ///
+    /// Any buffered packets are kept since they may belong to the new range already.
+    pub fn set_timeline(&mut self, timeline: Timeline) {
+        self.timeline = Some(timeline);
+    }
+}
+
+/// An error type for when a packet could not be depacketized.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum DepacketizeError {
+    /// An AU header section was malformed or did not match the packet size.
+    InvalidAUHeader,
+
+    /// The NAL unit type is reserved or not allowed in the payload format.
+    InvalidNALUnitType(u8),
+
+    /// The packet was shorter than the payload format requires.
+    Truncated,
+
+    /// A continuation fragment arrived without the fragment that starts it.
+    UnexpectedFragment,
+
+    /// The packet uses a feature of the payload format that is not supported, such as interleaved
+    /// packetization.
+    Unsupported,
+}
+
+impl Display for DepacketizeError {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        use self::DepacketizeError::*;
+
+        match self {
+            InvalidAUHeader => write!(formatter, "invalid AU header"),
+            InvalidNALUnitType(nal_unit_type) => {
+                write!(formatter, "invalid NAL unit type {}", nal_unit_type)
+            }
+            Truncated => write!(formatter, "truncated payload"),
+            UnexpectedFragment => write!(formatter, "unexpected fragment"),
+            Unsupported => write!(formatter, "unsupported payload"),
+        }
+    }
+}
+
+impl Error for DepacketizeError {}
+
+/// Joins the given NAL units into an Annex B formatted buffer.
+pub(crate) fn annex_b<'nal, TIterator>(nal_units: TIterator) -> Bytes
+where
+    TIterator: IntoIterator<Item = &'nal Bytes>,
+{
+    let mut buffer = BytesMut::new();
+
+    for nal_unit in nal_units {
+        buffer.extend_from_slice(&ANNEX_B_START_CODE);
+        buffer.extend_from_slice(nal_unit);
+    }
+
+    buffer.freeze()
+}
+
+/// Decodes a comma separated list of base64 encoded parameter sets as used by the
+/// `"sprop-parameter-sets"` family of format parameters.
+pub(crate) fn decode_parameter_sets(
+    value: &str,
+    name: &'static str,
+) -> Result<Vec<Bytes>, FormatError> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|parameter_set| !parameter_set.is_empty())
+        .map(|parameter_set| {
+            base64::decode(parameter_set)
+                .map(Bytes::from)
+                .map_err(|_| FormatError::InvalidParameterValue(name))
+        })
+        .collect()
+}
+
+/// Splits an aggregation packet payload into its `u16` length prefixed NAL units. The `skip`
+/// function returns the number of bytes to skip before each length, given whether it is the first.
+pub(crate) fn split_aggregation<TSkip>(
+    mut payload: Bytes,
+    skip: TSkip,
+) -> Result<Vec<Bytes>, DepacketizeError>
+where
+    TSkip: Fn(bool) -> usize,
+{
+    let mut nal_units = Vec::new();
+
+    while !payload.is_empty() {
+        let skip = skip(nal_units.is_empty());
+
+        if payload.len() < skip + 2 {
+            return Err(DepacketizeError::Truncated);
+        }
+
+        payload.advance(skip);
+        let size = (usize::from(payload[0]) << 8) | usize::from(payload[1]);
+        payload.advance(2);
+
+        if size == 0 || payload.len() < size {
+            return Err(DepacketizeError::Truncated);
+        }
+
+        nal_units.push(payload.split_to(size));
+    }
+
+    if nal_units.is_empty() {
+        Err(DepacketizeError::Truncated)
+    } else {
+        Ok(nal_units)
+    }
+}
@@ -0,0 +1,433 @@
+//! Fan-Out
+//!
+//! A [`FanOut`] drives a single [`MediaStream`], packetizing each of its frames and delivering the
+//! resulting RTP packets to every destination currently added through its [`FanOutHandle`].
+//! Destinations are either interleaved channels of an RTSP connection or UDP addresses, matching
+//! the transports a session can negotiate.
+//!
+//! Live streams are shared by all sessions through a single fan-out, while each session playing an
+//! on-demand stream gets a fan-out of its own, which can be paused and seeked independently.
+
+use std::{
+    collections::HashMap,
+    fmt::{self, Debug, Formatter},
+    net::{SocketAddr, UdpSocket},
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
+};
+
+use bytes::{Bytes, BytesMut};
+use futures::{task::AtomicTask, try_ready, Async, Future, Poll};
+
+use crate::{
+    media::{
+        format::FormatError,
+        frame::Frame,
+        packetizer::{self, Config, Packetizer},
+        MediaStream, SeekError,
+    },
+    protocol::{
+        codec::interleaved::InterleavedData,
+        connection::{ConnectionHandle, OperationError},
+    },
+};
+
+/// A future that delivers the packets of a media stream to all of its destinations.
+///
+/// The future completes once the media stream ends or the fan-out is closed, and resolves to an
+/// error if the media stream fails. Frames that cannot be packetized are skipped.
+#[must_use = "futures do nothing unless polled"]
+pub struct FanOut {
+    /// A buffer reused for encoding each packet.
+    buffer: BytesMut,
+
+    /// The packetizer for the frames of the stream.
+    packetizer: Box<dyn Packetizer + Send>,
+
+    /// The state shared with all handles.
+    state: Arc<Mutex<State>>,
+
+    /// The media stream being delivered, shared with all handles so they can seek it.
+    stream: Arc<Mutex<Box<dyn MediaStream + Send>>>,
+
+    /// The task of the fan-out, notified whenever a handle changes its state.
+    task: Arc<AtomicTask>,
+}
+
+impl FanOut {
+    /// Constructs a new fan-out for the given media stream, along with a handle used to manage its
+    /// destinations.
+    ///
+    /// An error is returned if no packetizer exists for the payload format of the stream.
+    pub fn new(
+        stream: Box<dyn MediaStream + Send>,
+        config: Config,
+    ) -> Result<(Self, FanOutHandle), FormatError> {
+        let packetizer =
+            packetizer::from_format(stream.format(), stream.format_parameters(), config)?;
+        let state = Arc::new(Mutex::new(State {
+            clock_rate: stream.format().clock_rate(),
+            closed: false,
+            destinations: HashMap::new(),
+            end: None,
+            next_destination_id: 0,
+            next_sequence_number: packetizer.next_sequence_number(),
+            paused: false,
+            pending_frame: None,
+            position: None,
+            rtp_timestamp: None,
+            ssrc: packetizer.ssrc(),
+        }));
+        let stream = Arc::new(Mutex::new(stream));
+        let task = Arc::new(AtomicTask::new());
+        let fan_out = FanOut {
+            buffer: BytesMut::new(),
+            packetizer,
+            state: state.clone(),
+            stream: stream.clone(),
+            task: task.clone(),
+        };
+        let handle = FanOutHandle {
+            state,
+            stream,
+            task,
+        };
+
+        Ok((fan_out, handle))
+    }
+
+    /// Returns the next frame to deliver, or [`Option::None`] if delivery is paused.
+    fn poll_next_frame(&mut self) -> Poll<Option<Frame>, ()> {
+        {
+            let mut state = self
+                .state
+                .lock()
+                .expect("`FanOut.state` should not be poisoned");
+
+            if state.closed {
+                return Ok(Async::Ready(None));
+            }
+
+            if state.paused {
+                return Ok(Async::NotReady);
+            }
+
+            if let Some(frame) = state.pending_frame.take() {
+                return Ok(Async::Ready(Some(frame)));
+            }
+        }
+
+        self.stream
+            .lock()
+            .expect("`FanOut.stream` should not be poisoned")
+            .poll_frame()
+            .map_err(|_| ())
+    }
+}
+
+impl Future for FanOut {
+    type Item = ();
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        self.task.register();
+
+        loop {
+            let frame = match try_ready!(self.poll_next_frame()) {
+                Some(frame) => frame,
+                None => return Ok(Async::Ready(())),
+            };
+            let mut state = self
+                .state
+                .lock()
+                .expect("`FanOut.state` should not be poisoned");
+
+            // Keep the frame past the end of the range so that resuming without seeking continues
+            // from it.
+            if let (Some(end), Some(position)) = (state.end, frame.presentation_time()) {
+                if position >= end {
+                    state.end = None;
+                    state.paused = true;
+                    state.pending_frame = Some(frame);
+                    continue;
+                }
+            }
+
+            let packets = match self.packetizer.packetize(&frame) {
+                Ok(packets) => packets,
+                Err(_) => continue,
+            };
+
+            for packet in packets {
+                self.buffer.reserve(packet.encoded_len());
+                packet.encode(&mut self.buffer);
+                let data = self.buffer.take().freeze();
+                state
+                    .destinations
+                    .retain(|_, destination| destination.send(data.clone()).is_ok());
+            }
+
+            state.next_sequence_number = self.packetizer.next_sequence_number();
+            state.position = frame.presentation_time().or(state.position);
+            state.rtp_timestamp = Some(frame.timestamp());
+        }
+    }
+}
+
+/// A handle to a [`FanOut`] used to manage its destinations and playback.
+#[derive(Clone)]
+pub struct FanOutHandle {
+    /// The state shared with the fan-out.
+    state: Arc<Mutex<State>>,
+
+    /// The media stream being delivered.
+    stream: Arc<Mutex<Box<dyn MediaStream + Send>>>,
+
+    /// The task of the fan-out.
+    task: Arc<AtomicTask>,
+}
+
+impl FanOutHandle {
+    /// Adds a destination that all subsequent packets will be sent to.
+    pub fn add_destination(&self, destination: Destination) -> DestinationID {
+        let mut state = self.lock();
+        let id = DestinationID(state.next_destination_id);
+        state.next_destination_id += 1;
+        state.destinations.insert(id, destination);
+        id
+    }
+
+    /// Returns the clock rate of the RTP timestamps.
+    pub fn clock_rate(&self) -> u32 {
+        self.lock().clock_rate
+    }
+
+    /// Stops the fan-out, completing its future.
+    pub fn close(&self) {
+        self.lock().closed = true;
+        self.task.notify();
+    }
+
+    /// Returns the number of destinations packets are currently sent to.
+    pub fn destination_count(&self) -> usize {
+        self.lock().destinations.len()
+    }
+
+    /// Returns the duration of the stream, if it is known.
+    pub fn duration(&self) -> Option<Duration> {
+        self.stream
+            .lock()
+            .expect("`FanOutHandle.stream` should not be poisoned")
+            .duration()
+    }
+
+    /// Returns whether delivery is paused.
+    pub fn is_paused(&self) -> bool {
+        self.lock().paused
+    }
+
+    /// Locks the shared state.
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state
+            .lock()
+            .expect("`FanOutHandle.state` should not be poisoned")
+    }
+
+    /// Returns the sequence number the next packet will have.
+    pub fn next_sequence_number(&self) -> u16 {
+        self.lock().next_sequence_number
+    }
+
+    /// Stops taking frames from the stream until [`FanOutHandle::resume`] is called.
+    pub fn pause(&self) {
+        self.lock().paused = true;
+    }
+
+    /// Returns the presentation time of the most recently sent frame, if any frame with a
+    /// presentation time has been sent.
+    pub fn position(&self) -> Option<Duration> {
+        self.lock().position
+    }
+
+    /// Removes the destination with the given identifier, returning whether it existed.
+    ///
+    /// Destinations are also removed automatically once their connection has closed.
+    pub fn remove_destination(&self, id: DestinationID) -> bool {
+        self.lock().destinations.remove(&id).is_some()
+    }
+
+    /// Resumes taking frames from the stream, pausing again once a frame at or past the given end
+    /// position is reached.
+    pub fn resume(&self, end: Option<Duration>) {
+        let mut stream = self
+            .stream
+            .lock()
+            .expect("`FanOutHandle.stream` should not be poisoned");
+        let mut state = self.lock();
+
+        if state.paused {
+            stream.resume();
+        }
+
+        state.end = end;
+        state.paused = false;
+        self.task.notify();
+    }
+
+    /// Returns the RTP timestamp of the most recently sent frame, if any.
+    pub fn rtp_timestamp(&self) -> Option<u32> {
+        self.lock().rtp_timestamp
+    }
+
+    /// Seeks the stream to the given position, returning the position actually seeked to.
+    ///
+    /// See [`MediaStream::seek`] for more information.
+    pub fn seek(&self, position: Duration) -> Result<Duration, SeekError> {
+        let mut stream = self
+            .stream
+            .lock()
+            .expect("`FanOutHandle.stream` should not be poisoned");
+        let position = stream.seek(position)?;
+        let mut state = self.lock();
+        state.pending_frame = None;
+        state.position = Some(position);
+        self.task.notify();
+        Ok(position)
+    }
+
+    /// Returns the synchronization source of all packets.
+    pub fn ssrc(&self) -> u32 {
+        self.lock().ssrc
+    }
+}
+
+impl Debug for FanOutHandle {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        formatter.debug_tuple("FanOutHandle").finish()
+    }
+}
+
+/// A location packets are delivered to.
+#[derive(Clone, Debug)]
+pub enum Destination {
+    /// Packets are sent as interleaved data on the given channel of an RTSP connection.
+    Interleaved {
+        channel: u8,
+        connection: ConnectionHandle,
+    },
+
+    /// Packets are sent as datagrams to the given address from the given non-blocking socket.
+    UDP {
+        address: SocketAddr,
+        socket: Arc<UdpSocket>,
+    },
+}
+
+impl Destination {
+    /// Sends the given encoded packet to the destination.
+    ///
+    /// An error is only returned if the destination can no longer receive packets. Datagrams that
+    /// cannot be sent immediately are dropped, since waiting would delay every other destination.
+    fn send(&self, data: Bytes) -> Result<(), OperationError> {
+        match self {
+            Destination::Interleaved {
+                channel,
+                connection,
+            } => connection.send_interleaved_data(InterleavedData::new(*channel, data)),
+            Destination::UDP { address, socket } => {
+                // Errors are ignored since a full socket buffer only affects this datagram and an
+                // unreachable port says nothing about whether the client is still there.
+                let _ = socket.send_to(&data, address);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// An identifier for a destination of a [`FanOut`].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct DestinationID(u64);
+
+/// The state of a [`FanOut`] shared with its handles.
+struct State {
+    /// The clock rate of the RTP timestamps.
+    clock_rate: u32,
+
+    /// Whether the fan-out has been closed.
+    closed: bool,
+
+    /// The destinations packets are currently sent to.
+    destinations: HashMap<DestinationID, Destination>,
+
+    /// The position at which delivery pauses.
+    end: Option<Duration>,
+
+    /// The identifier the next destination will be given.
+    next_destination_id: u64,
+
+    /// The sequence number the next packet will have.
+    next_sequence_number: u16,
+
+    /// Whether frames are currently not taken from the stream.
+    paused: bool,
+
+    /// A frame taken from the stream that has not been delivered because the end of the range was
+    /// reached.
+    pending_frame: Option<Frame>,
+
+    /// The presentation time of the most recently sent frame.
+    position: Option<Duration>,
+
+    /// The RTP timestamp of the most recently sent frame.
+    rtp_timestamp: Option<u32>,
+
+    /// The synchronization source of all packets.
+    ssrc: u32,
+}
+
+#[cfg(test)]
+mod test {
+    use std::{net::UdpSocket, sync::Arc};
+
+    use futures::{Async, Future};
+
+    use crate::media::{
+        fanout::{Destination, FanOut},
+        format::RTPMap,
+        frame::Frame,
+        live::LiveStream,
+        packetizer::Config,
+        rtp::Packet,
+    };
+
+    #[test]
+    fn test_fan_out_udp() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let (stream, mut tx_frame) = LiveStream::new(RTPMap::new(96, "H264", 90000, None), None);
+        let config = Config::builder().with_ssrc(7).build();
+        let (mut fan_out, handle) = FanOut::new(Box::new(stream), config).unwrap();
+        handle.add_destination(Destination::UDP {
+            address: receiver.local_addr().unwrap(),
+            socket: Arc::new(sender),
+        });
+
+        tx_frame
+            .send(Frame::new(vec![0, 0, 0, 1, 0x65, 0x88], 3000, true))
+            .unwrap();
+        futures::future::lazy(|| {
+            assert_eq!(fan_out.poll(), Ok(Async::NotReady));
+            Ok::<_, ()>(())
+        })
+        .wait()
+        .unwrap();
+
+        let mut buffer = [0; 1500];
+        let length = receiver.recv(&mut buffer).unwrap();
+        let packet = Packet::decode(&buffer[..length]).unwrap();
+        assert_eq!(packet.ssrc(), 7);
+        assert_eq!(packet.timestamp(), 3000);
+        assert!(packet.marker());
+        assert_eq!(handle.rtp_timestamp(), Some(3000));
+    }
+}
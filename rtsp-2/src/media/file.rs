@@ -0,0 +1,574 @@
+//! File Streams
+//!
+//! A [`FileStream`] serves an elementary stream file on demand. H.264 files in Annex B format
+//! (`.h264` or `.264`) and AAC files with ADTS headers (`.aac`) are supported. The entire file is
+//! indexed into frames up front, so seeking to the nearest keyframe is cheap and copies of the
+//! stream share the same frames. Frames are released in real time relative to when playback
+//! started or last seeked.
+
+use std::{
+    error::Error,
+    ffi::OsStr,
+    fmt::{self, Display, Formatter},
+    fs, io,
+    path::Path,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use bytes::{BufMut, Bytes, BytesMut};
+use futures::{Async, Future, Poll};
+use tokio_timer::Delay;
+
+use crate::media::{
+    depacketizer::aac::{
+        AudioSpecificConfig, AAC_DEFAULT_FRAME_DURATION, AAC_SAMPLING_FREQUENCIES,
+    },
+    format::{FormatParameters, RTPMap},
+    frame::Frame,
+    packetizer::{self, AACPacketizer, DEFAULT_PACKETIZER_PAYLOAD_TYPE},
+    MediaContentModification, MediaRetention, MediaSeeking, MediaStream, MediaUsage, Presentation,
+    SeekError,
+};
+
+/// The default frame rate assumed for H.264 files, since elementary streams do not reliably carry
+/// timing information.
+pub const DEFAULT_VIDEO_FRAME_RATE: u32 = 25;
+
+/// The control path of the single stream of each presentation created from a file.
+pub const FILE_STREAM_CONTROL: &str = "trackID=0";
+
+/// The clock rate of H.264 RTP timestamps.
+const H264_CLOCK_RATE: u32 = 90000;
+
+/// A media stream reading frames from an elementary stream file.
+#[derive(Debug)]
+pub struct FileStream {
+    /// The instant at which playback started along with the position it started from. This is
+    /// reset whenever the stream is seeked or resumed.
+    clock: Option<(Instant, Duration)>,
+
+    /// The timer waiting for the next frame to become due.
+    delay: Option<Delay>,
+
+    /// The index of the next frame.
+    index: usize,
+
+    /// The indexed contents of the file, shared between copies of the stream.
+    track: Arc<Track>,
+}
+
+impl FileStream {
+    /// Constructs a new stream from the contents of an ADTS AAC file.
+    pub fn from_adts<TData>(data: TData) -> Result<Self, FileError>
+    where
+        TData: Into<Bytes>,
+    {
+        let data = data.into();
+        let mut frames = Vec::new();
+        let mut config = None;
+        let mut offset = 0;
+
+        while offset < data.len() {
+            let header = &data[offset..];
+
+            if header.len() < 7 || header[0] != 0xFF || header[1] & 0xF6 != 0xF0 {
+                return Err(FileError::InvalidData);
+            }
+
+            let protection_absent = header[1] & 0x01 == 1;
+            let audio_object_type = (header[2] >> 6) + 1;
+            let sampling_frequency = *AAC_SAMPLING_FREQUENCIES
+                .get(usize::from((header[2] >> 2) & 0x0F))
+                .ok_or(FileError::InvalidData)?;
+            let channel_configuration = ((header[2] & 0x01) << 2) | (header[3] >> 6);
+            let frame_length = (usize::from(header[3] & 0x03) << 11)
+                | (usize::from(header[4]) << 3)
+                | (usize::from(header[5]) >> 5);
+            let header_length = if protection_absent { 7 } else { 9 };
+
+            // Frames carrying several raw data blocks would need to be split apart, which encoders
+            // practically never require.
+            if header[6] & 0x03 != 0
+                || frame_length <= header_length
+                || offset + frame_length > data.len()
+            {
+                return Err(FileError::InvalidData);
+            }
+
+            let frame_config = AudioSpecificConfig::new(
+                audio_object_type,
+                sampling_frequency,
+                channel_configuration,
+            );
+
+            match config {
+                Some(ref config) if *config != frame_config => return Err(FileError::InvalidData),
+                Some(_) => (),
+                None => config = Some(frame_config),
+            }
+
+            let timestamp = frames.len() as u64 * u64::from(AAC_DEFAULT_FRAME_DURATION);
+            let payload = data.slice(offset + header_length, offset + frame_length);
+            frames.push(file_frame(payload, timestamp, sampling_frequency, true));
+            offset += frame_length;
+        }
+
+        let config = config.ok_or(FileError::Empty)?;
+        let duration = ticks_to_duration(
+            frames.len() as u64 * u64::from(AAC_DEFAULT_FRAME_DURATION),
+            config.sampling_frequency(),
+        );
+        let format = RTPMap::new(
+            DEFAULT_PACKETIZER_PAYLOAD_TYPE,
+            "MPEG4-GENERIC",
+            config.sampling_frequency(),
+            Some(u16::from(config.channel_configuration())),
+        );
+        let format_parameters =
+            AACPacketizer::format_parameters(DEFAULT_PACKETIZER_PAYLOAD_TYPE, &config);
+
+        Ok(FileStream::from_track(Track {
+            duration,
+            format,
+            format_parameters: Some(format_parameters),
+            frames,
+        }))
+    }
+
+    /// Constructs a new stream from the contents of an Annex B H.264 file played at the given
+    /// frame rate.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the frame rate is zero.
+    pub fn from_h264<TData>(data: TData, frame_rate: u32) -> Result<Self, FileError>
+    where
+        TData: Into<Bytes>,
+    {
+        assert!(frame_rate > 0, "frame rate must be non-zero");
+
+        let mut access_units = Vec::new();
+        let mut access_unit = Vec::new();
+        let mut has_slice = false;
+        let mut sequence_parameter_set = None;
+        let mut picture_parameter_set = None;
+
+        for nal_unit in packetizer::split_annex_b(&data.into()) {
+            let nal_unit_type = nal_unit[0] & 0x1F;
+
+            // A new access unit starts with any of these NAL units following a slice, or with a
+            // slice whose `first_mb_in_slice` is zero, which is encoded as a single set bit.
+            let is_first_slice = (nal_unit_type == 1 || nal_unit_type == 5)
+                && nal_unit.get(1).map_or(false, |byte| byte & 0x80 != 0);
+            let starts_access_unit = match nal_unit_type {
+                6..=9 | 14..=18 => true,
+                _ => is_first_slice,
+            };
+
+            if has_slice && starts_access_unit {
+                access_units.push(access_unit.split_off(0));
+                has_slice = false;
+            }
+
+            match nal_unit_type {
+                1..=5 => has_slice = true,
+                7 if sequence_parameter_set.is_none() => {
+                    sequence_parameter_set = Some(nal_unit.clone())
+                }
+                8 if picture_parameter_set.is_none() => {
+                    picture_parameter_set = Some(nal_unit.clone())
+                }
+                _ => (),
+            }
+
+            access_unit.push(nal_unit);
+        }
+
+        if has_slice {
+            access_units.push(access_unit);
+        }
+
+        if access_units.is_empty() {
+            return Err(FileError::Empty);
+        }
+
+        // The parameter sets are needed for the session description, and the profile and level
+        // are given by the three bytes following the NAL unit header.
+        let sequence_parameter_set = sequence_parameter_set
+            .filter(|sequence_parameter_set| sequence_parameter_set.len() >= 4)
+            .ok_or(FileError::InvalidData)?;
+        let picture_parameter_set = picture_parameter_set.ok_or(FileError::InvalidData)?;
+
+        let frame_duration = u64::from(H264_CLOCK_RATE / frame_rate);
+        let frames = access_units
+            .iter()
+            .enumerate()
+            .map(|(index, nal_units)| {
+                let length = nal_units.iter().map(|nal_unit| nal_unit.len() + 4).sum();
+                let mut data = BytesMut::with_capacity(length);
+
+                for nal_unit in nal_units {
+                    data.put_slice(&[0, 0, 0, 1]);
+                    data.put_slice(nal_unit);
+                }
+
+                let keyframe = nal_units.iter().any(|nal_unit| nal_unit[0] & 0x1F == 5);
+                file_frame(
+                    data.freeze(),
+                    index as u64 * frame_duration,
+                    H264_CLOCK_RATE,
+                    keyframe,
+                )
+            })
+            .collect::<Vec<_>>();
+        let duration = ticks_to_duration(frames.len() as u64 * frame_duration, H264_CLOCK_RATE);
+        let format = RTPMap::new(
+            DEFAULT_PACKETIZER_PAYLOAD_TYPE,
+            "H264",
+            H264_CLOCK_RATE,
+            None,
+        );
+        let mut format_parameters = FormatParameters::new(DEFAULT_PACKETIZER_PAYLOAD_TYPE);
+        format_parameters.insert("packetization-mode", "1");
+        format_parameters.insert(
+            "profile-level-id",
+            sequence_parameter_set[1..4]
+                .iter()
+                .map(|byte| format!("{:02X}", byte))
+                .collect::<String>(),
+        );
+        format_parameters.insert(
+            "sprop-parameter-sets",
+            format!(
+                "{},{}",
+                base64::encode(&sequence_parameter_set),
+                base64::encode(&picture_parameter_set)
+            ),
+        );
+
+        Ok(FileStream::from_track(Track {
+            duration,
+            format,
+            format_parameters: Some(format_parameters),
+            frames,
+        }))
+    }
+
+    /// Constructs a new stream from an indexed track, starting from the beginning.
+    fn from_track(track: Track) -> Self {
+        FileStream {
+            clock: None,
+            delay: None,
+            index: 0,
+            track: Arc::new(track),
+        }
+    }
+
+    /// Reads the given file, choosing the format from its extension.
+    ///
+    /// H.264 files are played at [`DEFAULT_VIDEO_FRAME_RATE`]; use [`FileStream::from_h264`] to
+    /// play them at a different rate.
+    pub fn open<TPath>(path: TPath) -> Result<Self, FileError>
+    where
+        TPath: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let extension = path
+            .extension()
+            .and_then(OsStr::to_str)
+            .map(str::to_ascii_lowercase);
+
+        match extension.as_deref() {
+            Some("264") | Some("h264") => {
+                FileStream::from_h264(fs::read(path)?, DEFAULT_VIDEO_FRAME_RATE)
+            }
+            Some("aac") => FileStream::from_adts(fs::read(path)?),
+            _ => Err(FileError::Unsupported),
+        }
+    }
+}
+
+impl MediaStream for FileStream {
+    fn content_modification(&self) -> MediaContentModification {
+        MediaContentModification::Immutable
+    }
+
+    fn duration(&self) -> Option<Duration> {
+        Some(self.track.duration)
+    }
+
+    fn format(&self) -> &RTPMap {
+        &self.track.format
+    }
+
+    fn format_parameters(&self) -> Option<&FormatParameters> {
+        self.track.format_parameters.as_ref()
+    }
+
+    fn poll_frame(&mut self) -> Poll<Option<Frame>, Box<dyn Error + Send + 'static>> {
+        let frame = match self.track.frames.get(self.index) {
+            Some(frame) => frame,
+            None => return Ok(Async::Ready(None)),
+        };
+        let position = frame.presentation_time().unwrap_or_default();
+        let now = Instant::now();
+        let (start_instant, start_position) = *self.clock.get_or_insert((now, position));
+        let due = start_instant + position.checked_sub(start_position).unwrap_or_default();
+
+        if due > now {
+            if self
+                .delay
+                .as_ref()
+                .map_or(true, |delay| delay.deadline() != due)
+            {
+                self.delay = Some(Delay::new(due));
+            }
+
+            let delay = self.delay.as_mut().expect("delay should have been set");
+
+            if let Async::NotReady = delay
+                .poll()
+                .map_err(|error| Box::new(error) as Box<dyn Error + Send + 'static>)?
+            {
+                return Ok(Async::NotReady);
+            }
+        }
+
+        self.delay = None;
+        self.index += 1;
+        Ok(Async::Ready(Some(frame.clone())))
+    }
+
+    fn resume(&mut self) {
+        self.clock = None;
+        self.delay = None;
+    }
+
+    fn retention(&self) -> MediaRetention {
+        MediaRetention::Unlimited
+    }
+
+    fn seek(&mut self, position: Duration) -> Result<Duration, SeekError> {
+        if position > self.track.duration {
+            return Err(SeekError::OutOfRange);
+        }
+
+        let track = self.track.clone();
+        let frames = &track.frames;
+        self.index = frames
+            .iter()
+            .rposition(|frame| {
+                frame.is_keyframe() && frame.presentation_time().unwrap_or_default() <= position
+            })
+            .unwrap_or(0);
+        self.resume();
+        Ok(frames
+            .get(self.index)
+            .and_then(Frame::presentation_time)
+            .unwrap_or_default())
+    }
+
+    fn seeking(&self) -> MediaSeeking {
+        MediaSeeking::RandomAccess
+    }
+
+    fn try_clone(&self) -> Option<Box<dyn MediaStream + Send>> {
+        Some(Box::new(FileStream {
+            clock: None,
+            delay: None,
+            index: 0,
+            track: self.track.clone(),
+        }))
+    }
+}
+
+/// The indexed contents of a file.
+#[derive(Debug)]
+struct Track {
+    /// The total duration of all frames.
+    duration: Duration,
+
+    /// The payload format of the stream.
+    format: RTPMap,
+
+    /// The format specific parameters of the stream.
+    format_parameters: Option<FormatParameters>,
+
+    /// The frames of the file, each with its presentation time.
+    frames: Vec<Frame>,
+}
+
+/// Reads every supported file in the given directory into an on-demand presentation named after
+/// the file, such as `"clip.h264"`.
+///
+/// Each presentation has a single stream with the control path [`FILE_STREAM_CONTROL`]. Files
+/// with unsupported extensions are skipped, while supported files that cannot be read are errors.
+pub fn open_directory<TPath>(directory: TPath) -> Result<Vec<(String, Presentation)>, FileError>
+where
+    TPath: AsRef<Path>,
+{
+    let mut entries = fs::read_dir(directory)?.collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+    let mut presentations = Vec::new();
+
+    for entry in entries {
+        let name = match entry.file_name().into_string() {
+            Ok(name) => name,
+            Err(_) => continue,
+        };
+        let stream = match FileStream::open(entry.path()) {
+            Ok(stream) => stream,
+            Err(FileError::Unsupported) => continue,
+            Err(error) => return Err(error),
+        };
+        let presentation = Presentation::new()
+            .with_usage(MediaUsage::OnDemand)
+            .with_stream(FILE_STREAM_CONTROL, stream);
+        presentations.push((name, presentation));
+    }
+
+    Ok(presentations)
+}
+
+/// An error type for when a file could not be served.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum FileError {
+    /// The file did not contain any frames.
+    Empty,
+
+    /// The file is not a valid elementary stream of its format.
+    InvalidData,
+
+    /// The file could not be read.
+    IO(io::ErrorKind),
+
+    /// The file extension does not correspond to a supported format.
+    Unsupported,
+}
+
+impl Display for FileError {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        use self::FileError::*;
+
+        match self {
+            Empty => write!(formatter, "empty file"),
+            InvalidData => write!(formatter, "invalid file data"),
+            IO(kind) => write!(formatter, "file IO error: {:?}", kind),
+            Unsupported => write!(formatter, "unsupported file"),
+        }
+    }
+}
+
+impl Error for FileError {}
+
+impl From<io::Error> for FileError {
+    fn from(value: io::Error) -> Self {
+        FileError::IO(value.kind())
+    }
+}
+
+/// Constructs a frame of a file with the given timestamp in units of the given clock rate.
+fn file_frame(data: Bytes, timestamp: u64, clock_rate: u32, keyframe: bool) -> Frame {
+    let mut frame = Frame::new(data, timestamp as u32, keyframe);
+    *frame.presentation_time_mut() = Some(ticks_to_duration(timestamp, clock_rate));
+    frame
+}
+
+/// Converts a number of ticks of the given clock rate to a duration.
+fn ticks_to_duration(ticks: u64, clock_rate: u32) -> Duration {
+    Duration::from_nanos((u128::from(ticks) * 1_000_000_000 / u128::from(clock_rate)) as u64)
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use crate::media::{
+        file::{FileError, FileStream},
+        MediaStream, SeekError,
+    };
+
+    /// Returns an H.264 elementary stream of the given number of frames, with a keyframe every
+    /// `keyframe_interval` frames.
+    fn h264_stream(frames: usize, keyframe_interval: usize) -> Vec<u8> {
+        let mut data = vec![0, 0, 0, 1, 0x67, 0x42, 0xC0, 0x1F, 0, 0, 0, 1, 0x68, 0xCE];
+
+        for index in 0..frames {
+            let nal_unit_type = if index % keyframe_interval == 0 {
+                0x65
+            } else {
+                0x41
+            };
+            data.extend_from_slice(&[0, 0, 0, 1, nal_unit_type, 0x88, 0x84]);
+        }
+
+        data
+    }
+
+    #[test]
+    fn test_file_stream_h264() {
+        let stream = FileStream::from_h264(h264_stream(50, 10), 25).unwrap();
+        assert_eq!(stream.duration(), Some(Duration::from_secs(2)));
+        assert_eq!(stream.format().encoding_name(), "H264");
+
+        let parameters = stream.format_parameters().unwrap();
+        assert_eq!(parameters.get("profile-level-id"), Some("42C01F"));
+        assert_eq!(
+            parameters.get("sprop-parameter-sets"),
+            Some("Z0LAHw==,aM4=")
+        );
+
+        let frames = &stream.track.frames;
+        assert_eq!(frames.len(), 50);
+        assert!(frames[0].is_keyframe());
+        assert!(!frames[1].is_keyframe());
+        assert_eq!(frames[1].timestamp(), 3600);
+        assert_eq!(
+            frames[1].presentation_time(),
+            Some(Duration::from_millis(40))
+        );
+    }
+
+    #[test]
+    fn test_file_stream_seek_to_keyframe() {
+        let mut stream = FileStream::from_h264(h264_stream(50, 10), 25).unwrap();
+        assert_eq!(
+            stream.seek(Duration::from_millis(1300)),
+            Ok(Duration::from_millis(1200))
+        );
+        assert_eq!(stream.index, 30);
+        assert_eq!(
+            stream.seek(Duration::from_secs(3)),
+            Err(SeekError::OutOfRange)
+        );
+    }
+
+    #[test]
+    fn test_file_stream_adts() {
+        // AAC-LC, 44.1 kHz, stereo, frames of 10 bytes including the header.
+        let frame = [0xFF, 0xF1, 0x50, 0x80, 0x01, 0x5F, 0xFC, 0x21, 0x10, 0x04];
+        let data = frame
+            .iter()
+            .cycle()
+            .take(frame.len() * 3)
+            .cloned()
+            .collect::<Vec<_>>();
+        let stream = FileStream::from_adts(data).unwrap();
+        assert_eq!(stream.format().clock_rate(), 44100);
+        assert_eq!(stream.format().channels(), Some(2));
+        assert_eq!(
+            stream.format_parameters().unwrap().get("config"),
+            Some("1210")
+        );
+        assert_eq!(stream.track.frames.len(), 3);
+        assert_eq!(&stream.track.frames[2].data()[..], &[0x21, 0x10, 0x04]);
+        assert_eq!(stream.track.frames[2].timestamp(), 2048);
+
+        assert_eq!(
+            FileStream::from_adts(vec![0xFF, 0xF1, 0x50]).err(),
+            Some(FileError::InvalidData)
+        );
+    }
+}
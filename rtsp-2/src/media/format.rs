@@ -0,0 +1,333 @@
+//! Media Formats
+//!
+//! This module contains types describing the payload format of an RTP stream as signalled by the
+//! `"a=rtpmap"` and `"a=fmtp"` SDP attributes described by
+//! [RFC4566](https://tools.ietf.org/html/rfc4566#section-6).
+
+use std::{
+    convert::TryFrom,
+    error::Error,
+    fmt::{self, Display, Formatter},
+    str::FromStr,
+};
+
+use itertools::Itertools;
+
+use crate::syntax;
+
+/// The payload format of an RTP stream as given by the `"a=rtpmap"` attribute.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct RTPMap {
+    /// The number of audio channels, if given.
+    channels: Option<u16>,
+
+    /// The clock rate of the RTP timestamps in Hz.
+    clock_rate: u32,
+
+    /// The encoding name, such as `"H264"` or `"MPEG4-GENERIC"`.
+    encoding_name: String,
+
+    /// The payload type this mapping applies to.
+    payload_type: u8,
+}
+
+impl RTPMap {
+    /// Returns the number of audio channels, if given.
+    pub fn channels(&self) -> Option<u16> {
+        self.channels
+    }
+
+    /// Returns the clock rate of the RTP timestamps in Hz.
+    pub fn clock_rate(&self) -> u32 {
+        self.clock_rate
+    }
+
+    /// Returns the encoding name. The comparison of encoding names is case-insensitive.
+    pub fn encoding_name(&self) -> &str {
+        &self.encoding_name
+    }
+
+    /// Constructs a new payload format mapping.
+    pub fn new<TName>(
+        payload_type: u8,
+        encoding_name: TName,
+        clock_rate: u32,
+        channels: Option<u16>,
+    ) -> Self
+    where
+        TName: Into<String>,
+    {
+        RTPMap {
+            channels,
+            clock_rate,
+            encoding_name: encoding_name.into(),
+            payload_type,
+        }
+    }
+
+    /// Returns the payload type this mapping applies to.
+    pub fn payload_type(&self) -> u8 {
+        self.payload_type
+    }
+}
+
+impl Display for RTPMap {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "{} {}/{}",
+            self.payload_type, self.encoding_name, self.clock_rate
+        )?;
+
+        if let Some(channels) = self.channels {
+            write!(formatter, "/{}", channels)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<'rtpmap> TryFrom<&'rtpmap str> for RTPMap {
+    type Error = FormatError;
+
+    /// Parses the value of an `"a=rtpmap"` attribute, that is, everything after the colon.
+    ///
+    /// ```text
+    /// a=rtpmap:<payload type> <encoding name>/<clock rate>[/<encoding parameters>]
+    /// ```
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::convert::TryFrom;
+    ///
+    /// use rtsp::media::format::RTPMap;
+    ///
+    /// let rtpmap = RTPMap::try_from("97 MPEG4-GENERIC/44100/2").unwrap();
+    /// assert_eq!(rtpmap.payload_type(), 97);
+    /// assert_eq!(rtpmap.encoding_name(), "MPEG4-GENERIC");
+    /// assert_eq!(rtpmap.clock_rate(), 44100);
+    /// assert_eq!(rtpmap.channels(), Some(2));
+    /// ```
+    fn try_from(value: &'rtpmap str) -> Result<Self, Self::Error> {
+        let (payload_type, value) = split_payload_type(value)?;
+        let mut parts = value.split('/').map(syntax::trim_whitespace);
+        let encoding_name = parts
+            .next()
+            .filter(|name| !name.is_empty())
+            .ok_or(FormatError::InvalidEncodingName)?;
+        let clock_rate = parts
+            .next()
+            .and_then(|clock_rate| clock_rate.parse::<u32>().ok())
+            .filter(|&clock_rate| clock_rate > 0)
+            .ok_or(FormatError::InvalidClockRate)?;
+        let channels = match parts.next() {
+            Some(channels) => Some(
+                channels
+                    .parse::<u16>()
+                    .map_err(|_| FormatError::InvalidChannels)?,
+            ),
+            None => None,
+        };
+
+        Ok(RTPMap {
+            channels,
+            clock_rate,
+            encoding_name: encoding_name.to_string(),
+            payload_type,
+        })
+    }
+}
+
+/// The format specific parameters of an RTP stream as given by the `"a=fmtp"` attribute.
+///
+/// Parameter names are compared case-insensitively and are stored in lowercase, while values are
+/// kept as given.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct FormatParameters {
+    /// The parameters in the order they were given.
+    parameters: Vec<(String, String)>,
+
+    /// The payload type these parameters apply to.
+    payload_type: u8,
+}
+
+impl FormatParameters {
+    /// Returns the value of the given parameter, if present.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.parameters
+            .iter()
+            .find(|(parameter_name, _)| parameter_name.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Returns the value of the given parameter parsed as an integer, if present and valid.
+    pub fn get_integer<TInteger>(&self, name: &str) -> Option<TInteger>
+    where
+        TInteger: FromStr,
+    {
+        self.get(name).and_then(|value| value.parse().ok())
+    }
+
+    /// Inserts a parameter, replacing any existing value.
+    pub fn insert<TName, TValue>(&mut self, name: TName, value: TValue)
+    where
+        TName: AsRef<str>,
+        TValue: Into<String>,
+    {
+        let name = name.as_ref().to_ascii_lowercase();
+        let value = value.into();
+
+        match self
+            .parameters
+            .iter_mut()
+            .find(|(parameter_name, _)| *parameter_name == name)
+        {
+            Some((_, existing_value)) => *existing_value = value,
+            None => self.parameters.push((name, value)),
+        }
+    }
+
+    /// Returns an iterator over all parameters in the order they were given.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.parameters
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+
+    /// Constructs a new set of parameters for the given payload type.
+    pub fn new(payload_type: u8) -> Self {
+        FormatParameters {
+            parameters: Vec::new(),
+            payload_type,
+        }
+    }
+
+    /// Returns the payload type these parameters apply to.
+    pub fn payload_type(&self) -> u8 {
+        self.payload_type
+    }
+}
+
+impl Display for FormatParameters {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "{} {}",
+            self.payload_type,
+            self.iter()
+                .map(|(name, value)| format!("{}={}", name, value))
+                .join(";")
+        )
+    }
+}
+
+impl<'fmtp> TryFrom<&'fmtp str> for FormatParameters {
+    type Error = FormatError;
+
+    /// Parses the value of an `"a=fmtp"` attribute, that is, everything after the colon.
+    ///
+    /// ```text
+    /// a=fmtp:<payload type> <name>=<value>[;<name>=<value>]*
+    /// ```
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::convert::TryFrom;
+    ///
+    /// use rtsp::media::format::FormatParameters;
+    ///
+    /// let parameters =
+    ///     FormatParameters::try_from("96 packetization-mode=1; profile-level-id=42e01f").unwrap();
+    /// assert_eq!(parameters.payload_type(), 96);
+    /// assert_eq!(parameters.get("Packetization-Mode"), Some("1"));
+    /// assert_eq!(parameters.get_integer::<u8>("packetization-mode"), Some(1));
+    /// ```
+    fn try_from(value: &'fmtp str) -> Result<Self, Self::Error> {
+        let (payload_type, value) = split_payload_type(value)?;
+        let mut parameters = FormatParameters::new(payload_type);
+
+        for parameter in value.split(';') {
+            let parameter = syntax::trim_whitespace(parameter);
+
+            if parameter.is_empty() {
+                continue;
+            }
+
+            let mut parts = parameter.splitn(2, '=').map(syntax::trim_whitespace);
+            let name = parts.next().unwrap_or("");
+
+            if name.is_empty() {
+                return Err(FormatError::InvalidParameter);
+            }
+
+            parameters.insert(name, parts.next().unwrap_or(""));
+        }
+
+        Ok(parameters)
+    }
+}
+
+/// A possible error value when parsing a payload format attribute.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum FormatError {
+    /// The number of channels was not a valid integer.
+    InvalidChannels,
+
+    /// The clock rate was missing, zero or not a valid integer.
+    InvalidClockRate,
+
+    /// The encoding name was missing.
+    InvalidEncodingName,
+
+    /// A format parameter had an empty name.
+    InvalidParameter,
+
+    /// A parameter of the payload format was given, but its value was invalid.
+    InvalidParameterValue(&'static str),
+
+    /// The payload type was missing or not in the range `0..=127`.
+    InvalidPayloadType,
+
+    /// A parameter required by the payload format was missing.
+    MissingParameter(&'static str),
+
+    /// The payload format or one of its modes is not supported.
+    Unsupported,
+}
+
+impl Display for FormatError {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        use self::FormatError::*;
+
+        match self {
+            InvalidChannels => write!(formatter, "invalid format channels"),
+            InvalidClockRate => write!(formatter, "invalid format clock rate"),
+            InvalidEncodingName => write!(formatter, "invalid format encoding name"),
+            InvalidParameter => write!(formatter, "invalid format parameter"),
+            InvalidParameterValue(name) => {
+                write!(formatter, "invalid value of format parameter {}", name)
+            }
+            InvalidPayloadType => write!(formatter, "invalid format payload type"),
+            MissingParameter(name) => write!(formatter, "missing format parameter {}", name),
+            Unsupported => write!(formatter, "unsupported format"),
+        }
+    }
+}
+
+impl Error for FormatError {}
+
+/// Splits the leading payload type off of an attribute value.
+fn split_payload_type(value: &str) -> Result<(u8, &str), FormatError> {
+    let value = syntax::trim_whitespace(value);
+    let index = value.find(' ').unwrap_or(value.len());
+    let payload_type = value[..index]
+        .parse::<u8>()
+        .ok()
+        .filter(|&payload_type| payload_type < 128)
+        .ok_or(FormatError::InvalidPayloadType)?;
+
+    Ok((payload_type, syntax::trim_whitespace(&value[index..])))
+}
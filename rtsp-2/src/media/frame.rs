@@ -0,0 +1,72 @@
+use std::time::Duration;
+
+use bytes::Bytes;
+
+/// A complete unit of media, such as a video access unit or an audio frame.
+///
+/// Video frames are stored in Annex B format, meaning each NAL unit is preceded by a four byte
+/// start code. Audio frames are stored as raw codec frames without any transport framing.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Frame {
+    /// The encoded media data.
+    data: Bytes,
+
+    /// Whether the frame can be decoded without reference to previous frames.
+    keyframe: bool,
+
+    /// The presentation time of the frame, if it is known.
+    presentation_time: Option<Duration>,
+
+    /// The RTP timestamp of the frame.
+    timestamp: u32,
+}
+
+impl Frame {
+    /// Returns the encoded media data.
+    pub fn data(&self) -> &Bytes {
+        &self.data
+    }
+
+    /// Returns whether the frame can be decoded without reference to previous frames.
+    pub fn is_keyframe(&self) -> bool {
+        self.keyframe
+    }
+
+    /// Returns a mutable reference to whether the frame is a keyframe.
+    pub fn keyframe_mut(&mut self) -> &mut bool {
+        &mut self.keyframe
+    }
+
+    /// Constructs a new frame with the given RTP timestamp and no presentation time.
+    pub fn new<TData>(data: TData, timestamp: u32, keyframe: bool) -> Self
+    where
+        TData: Into<Bytes>,
+    {
+        Frame {
+            data: data.into(),
+            keyframe,
+            presentation_time: None,
+            timestamp,
+        }
+    }
+
+    /// Returns the presentation time of the frame, if it is known.
+    pub fn presentation_time(&self) -> Option<Duration> {
+        self.presentation_time
+    }
+
+    /// Returns a mutable reference to the presentation time of the frame.
+    pub fn presentation_time_mut(&mut self) -> &mut Option<Duration> {
+        &mut self.presentation_time
+    }
+
+    /// Returns the RTP timestamp of the frame.
+    pub fn timestamp(&self) -> u32 {
+        self.timestamp
+    }
+
+    /// Returns a mutable reference to the RTP timestamp of the frame.
+    pub fn timestamp_mut(&mut self) -> &mut u32 {
+        &mut self.timestamp
+    }
+}
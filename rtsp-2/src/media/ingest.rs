@@ -0,0 +1,188 @@
+//! Ingest
+//!
+//! An [`Ingest`] turns the RTP packets a publishing agent sends for each of its streams back into
+//! frames and pushes them into [`LiveStream`]s, from which they can be served like any other live
+//! media.
+//!
+//! [`LiveStream`]: crate::media::live::LiveStream
+
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use futures::{sync::mpsc::UnboundedReceiver, Async, Future, Poll, Stream};
+use tokio_timer::Delay;
+
+use crate::media::{depacketizer::Reassembler, live::LiveStreamSender, rtp::Packet};
+
+/// How far ahead of the current instant packets are released once no more packets will arrive,
+/// which is longer than any jitter buffer holds packets back.
+const FLUSH_DELAY: Duration = Duration::from_secs(3600);
+
+/// A future that reassembles the RTP packets received for a set of streams into frames.
+///
+/// Packets are received as the index of the stream they belong to along with the encoded packet.
+/// The future completes once all senders of packets have been dropped, at which point the live
+/// streams are ended as well. Packets that cannot be decoded or depacketized are dropped.
+#[must_use = "futures do nothing unless polled"]
+pub struct Ingest {
+    /// The timer used to release packets held back by the jitter buffers.
+    delay: Option<Delay>,
+
+    /// The receiving end of the packets, paired with the indices of their streams.
+    rx_packet: UnboundedReceiver<(usize, Bytes)>,
+
+    /// The reassembler of each stream, along with the sender its frames are pushed into.
+    streams: Vec<(Reassembler, LiveStreamSender)>,
+}
+
+impl Ingest {
+    /// Constructs a new ingest pushing the packets received through the given receiver into the
+    /// given streams.
+    pub fn new(
+        streams: Vec<(Reassembler, LiveStreamSender)>,
+        rx_packet: UnboundedReceiver<(usize, Bytes)>,
+    ) -> Self {
+        Ingest {
+            delay: None,
+            rx_packet,
+            streams,
+        }
+    }
+
+    /// Pushes every frame that can be produced at the given instant into its stream, returning the
+    /// earliest instant at which buffered packets need to be released.
+    fn push_frames(&mut self, now: Instant) -> Option<Instant> {
+        let mut deadline: Option<Instant> = None;
+
+        for (reassembler, sender) in &mut self.streams {
+            loop {
+                match reassembler.poll(now) {
+                    // Frames are dropped if the stream is not keeping up, as with any live stream.
+                    Ok(Some(frame)) => {
+                        let _ = sender.send(frame);
+                    }
+                    Ok(None) => break,
+                    Err(_) => continue,
+                }
+            }
+
+            if let Some(next_deadline) = reassembler.next_deadline() {
+                deadline =
+                    Some(deadline.map_or(next_deadline, |deadline| deadline.min(next_deadline)));
+            }
+        }
+
+        deadline
+    }
+}
+
+impl Future for Ingest {
+    type Item = ();
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        loop {
+            let now = Instant::now();
+
+            loop {
+                match self.rx_packet.poll()? {
+                    Async::Ready(Some((index, data))) => {
+                        let reassembler = match self.streams.get_mut(index) {
+                            Some((reassembler, _)) => reassembler,
+                            None => continue,
+                        };
+
+                        if let Ok(packet) = Packet::decode(data) {
+                            reassembler.push(packet, now);
+                        }
+                    }
+                    Async::Ready(None) => {
+                        self.push_frames(now + FLUSH_DELAY);
+                        return Ok(Async::Ready(()));
+                    }
+                    Async::NotReady => break,
+                }
+            }
+
+            self.delay = self
+                .push_frames(now)
+                .map(|deadline| match self.delay.take() {
+                    Some(mut delay) => {
+                        delay.reset(deadline);
+                        delay
+                    }
+                    None => Delay::new(deadline),
+                });
+
+            match self.delay.as_mut().map(Delay::poll) {
+                Some(Ok(Async::Ready(()))) => continue,
+                Some(Err(_)) => return Err(()),
+                _ => return Ok(Async::NotReady),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bytes::{Bytes, BytesMut};
+    use futures::{sync::mpsc, Stream};
+    use tokio::runtime::current_thread::Runtime;
+
+    use crate::media::{
+        depacketizer::{H264Depacketizer, Reassembler},
+        format::RTPMap,
+        frame::Frame,
+        ingest::Ingest,
+        jitter::Config as JitterBufferConfig,
+        live::LiveStream,
+        packetizer,
+    };
+
+    #[test]
+    fn test_ingest_flushes_frames_when_packets_end() {
+        let format = RTPMap::new(96, "H264", 90000, None);
+        let (stream, sender) = LiveStream::new(format.clone(), None);
+        let (tx_packet, rx_packet) = mpsc::unbounded();
+        let reassembler = Reassembler::new(
+            Box::new(H264Depacketizer::new()),
+            JitterBufferConfig::default(),
+        );
+        let ingest = Ingest::new(vec![(reassembler, sender)], rx_packet);
+
+        let mut packetizer =
+            packetizer::from_format(&format, None, packetizer::Config::default()).unwrap();
+        let frames = vec![
+            Frame::new(&[0, 0, 0, 1, 0x65, 0x88, 0x84][..], 0, true),
+            Frame::new(&[0, 0, 0, 1, 0x41, 0x9A, 0x02][..], 3000, false),
+        ];
+
+        // Packets for unknown streams and undecodable packets are dropped.
+        tx_packet
+            .unbounded_send((1, Bytes::from_static(&[0x80])))
+            .unwrap();
+        tx_packet
+            .unbounded_send((0, Bytes::from_static(&[0x00])))
+            .unwrap();
+
+        for frame in &frames {
+            for packet in packetizer.packetize(frame).unwrap() {
+                let mut buffer = BytesMut::new();
+                packet.encode(&mut buffer);
+                tx_packet.unbounded_send((0, buffer.freeze())).unwrap();
+            }
+        }
+
+        drop(tx_packet);
+
+        let mut runtime = Runtime::new().unwrap();
+        runtime.block_on(ingest).unwrap();
+        let received = runtime.block_on(stream.collect()).unwrap();
+
+        assert_eq!(received.len(), 2);
+        assert_eq!(received[0].data(), frames[0].data());
+        assert!(received[0].is_keyframe());
+        assert_eq!(received[1].timestamp(), 3000);
+        assert!(!received[1].is_keyframe());
+    }
+}
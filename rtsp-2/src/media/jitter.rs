@@ -0,0 +1,374 @@
+//! Jitter Buffer
+//!
+//! RTP delivered over UDP may arrive out of order, duplicated, or not at all. The [`JitterBuffer`]
+//! holds packets for up to a configurable latency so they can be released in sequence number
+//! order. Packets arriving in order are released immediately, so the latency is only paid when a
+//! gap in the sequence numbers has to be waited out.
+
+use std::{
+    collections::BTreeMap,
+    time::{Duration, Instant},
+};
+
+use crate::media::rtp::Packet;
+
+/// The default maximum number of packets held by the jitter buffer.
+pub const DEFAULT_JITTER_BUFFER_CAPACITY: usize = 512;
+
+/// The default maximum duration a packet will be held while waiting for missing packets.
+pub const DEFAULT_JITTER_BUFFER_LATENCY: Duration = Duration::from_millis(200);
+
+/// A reordering buffer for RTP packets of a single synchronization source.
+#[derive(Debug)]
+pub struct JitterBuffer {
+    /// Configuration for how the jitter buffer should operate.
+    config: Config,
+
+    /// The highest extended sequence number seen so far, used to extend new sequence numbers.
+    highest_sequence_number: Option<u64>,
+
+    /// The extended sequence number of the next packet to be released.
+    next_sequence_number: Option<u64>,
+
+    /// All buffered packets keyed by extended sequence number, along with their arrival time.
+    packets: BTreeMap<u64, (Instant, Packet)>,
+
+    /// Counters describing what has happened to packets so far.
+    statistics: Statistics,
+}
+
+impl JitterBuffer {
+    /// Returns the configuration of the jitter buffer.
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Returns whether no packets are currently buffered.
+    pub fn is_empty(&self) -> bool {
+        self.packets.is_empty()
+    }
+
+    /// Returns the number of packets currently buffered.
+    pub fn len(&self) -> usize {
+        self.packets.len()
+    }
+
+    /// Constructs a new jitter buffer using the default configuration.
+    pub fn new() -> Self {
+        JitterBuffer::with_config(Config::default())
+    }
+
+    /// Returns the instant at which [`JitterBuffer::pop`] will next release a packet by giving up
+    /// on missing ones, or [`Option::None`] if nothing is buffered.
+    ///
+    /// Callers driving the buffer from a timer should wake up at this instant.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.packets
+            .values()
+            .next()
+            .map(|(arrival, _)| *arrival + self.config.latency)
+    }
+
+    /// Releases the next packet in sequence number order, if one is available.
+    ///
+    /// A packet is available if it is the next expected packet, if the oldest buffered packet has
+    /// been waiting for longer than the configured latency, or if the buffer is over capacity. In
+    /// the last two cases, any missing packets before it are considered lost.
+    pub fn pop(&mut self, now: Instant) -> Option<Packet> {
+        let (&sequence_number, &(arrival, _)) = self.packets.iter().next()?;
+        let is_next = self.next_sequence_number == Some(sequence_number);
+        let is_expired = now.duration_since(arrival) >= self.config.latency;
+        let is_over_capacity = self.packets.len() > self.config.capacity;
+
+        if !is_next && !is_expired && !is_over_capacity {
+            return None;
+        }
+
+        if let Some(next_sequence_number) = self.next_sequence_number {
+            self.statistics.lost += sequence_number.saturating_sub(next_sequence_number);
+        }
+
+        let (_, packet) = self
+            .packets
+            .remove(&sequence_number)
+            .expect("first buffered packet should exist");
+        self.next_sequence_number = Some(sequence_number + 1);
+        self.statistics.released += 1;
+        Some(packet)
+    }
+
+    /// Inserts a packet that arrived at the given instant.
+    ///
+    /// Packets that are older than what has already been released, or that are already buffered,
+    /// are discarded.
+    pub fn push(&mut self, packet: Packet, arrival: Instant) -> Insertion {
+        let sequence_number = match self.highest_sequence_number {
+            Some(highest) => extend_sequence_number(highest, packet.sequence_number()),
+            // Offset the first packet so that packets preceding it do not underflow.
+            None => u64::from(packet.sequence_number()) + (1 << 16),
+        };
+
+        if sequence_number > self.highest_sequence_number.unwrap_or(0) {
+            self.highest_sequence_number = Some(sequence_number);
+        }
+
+        match self.next_sequence_number {
+            Some(next_sequence_number) if sequence_number < next_sequence_number => {
+                self.statistics.late += 1;
+                return Insertion::Late;
+            }
+            None => self.next_sequence_number = Some(sequence_number),
+            _ => (),
+        }
+
+        if self.packets.contains_key(&sequence_number) {
+            self.statistics.duplicate += 1;
+            return Insertion::Duplicate;
+        }
+
+        if self
+            .packets
+            .keys()
+            .next_back()
+            .map(|&last| sequence_number < last)
+            .unwrap_or(false)
+        {
+            self.statistics.reordered += 1;
+        }
+
+        self.packets.insert(sequence_number, (arrival, packet));
+        Insertion::Buffered
+    }
+
+    /// Discards all buffered packets and forgets the sequence number state, for example after a
+    /// seek.
+    pub fn reset(&mut self) {
+        self.highest_sequence_number = None;
+        self.next_sequence_number = None;
+        self.packets.clear();
+    }
+
+    /// Returns counters describing what has happened to packets so far.
+    pub fn statistics(&self) -> &Statistics {
+        &self.statistics
+    }
+
+    /// Constructs a new jitter buffer using the given configuration.
+    pub fn with_config(config: Config) -> Self {
+        JitterBuffer {
+            config,
+            highest_sequence_number: None,
+            next_sequence_number: None,
+            packets: BTreeMap::new(),
+            statistics: Statistics::default(),
+        }
+    }
+}
+
+impl Default for JitterBuffer {
+    fn default() -> Self {
+        JitterBuffer::new()
+    }
+}
+
+/// The outcome of inserting a packet into the jitter buffer.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Insertion {
+    /// The packet was buffered and will be released in order.
+    Buffered,
+
+    /// A packet with the same sequence number is already buffered, so it was discarded.
+    Duplicate,
+
+    /// The packet arrived after later packets were already released, so it was discarded.
+    Late,
+}
+
+/// Counters describing what has happened to packets passing through a jitter buffer.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct Statistics {
+    /// The number of packets discarded because they were already buffered.
+    pub duplicate: u64,
+
+    /// The number of packets discarded because they arrived too late.
+    pub late: u64,
+
+    /// The number of packets that were skipped because they never arrived in time.
+    pub lost: u64,
+
+    /// The number of packets that arrived with a lower sequence number than a buffered packet.
+    pub reordered: u64,
+
+    /// The number of packets released from the buffer.
+    pub released: u64,
+}
+
+/// A set of configuration options controlling how the jitter buffer functions.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Config {
+    /// The maximum number of packets held before packets are forcibly released.
+    capacity: usize,
+
+    /// The maximum duration a packet will be held while waiting for missing packets.
+    latency: Duration,
+}
+
+impl Config {
+    /// Constructs a builder for specifying possible options.
+    pub fn builder() -> ConfigBuilder {
+        ConfigBuilder::new()
+    }
+
+    /// Returns the maximum number of packets held before packets are forcibly released.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the maximum duration a packet will be held while waiting for missing packets.
+    pub fn latency(&self) -> Duration {
+        self.latency
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config::builder().build()
+    }
+}
+
+/// A builder type for constructing a [`Config`].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct ConfigBuilder {
+    /// The maximum number of packets held before packets are forcibly released.
+    capacity: usize,
+
+    /// The maximum duration a packet will be held while waiting for missing packets.
+    latency: Duration,
+}
+
+impl ConfigBuilder {
+    /// Converts the builder into a [`Config`].
+    pub fn build(self) -> Config {
+        Config {
+            capacity: self.capacity,
+            latency: self.latency,
+        }
+    }
+
+    /// Sets the maximum number of buffered packets.
+    pub fn capacity(&mut self, capacity: usize) -> &mut Self {
+        self.capacity = capacity;
+        self
+    }
+
+    /// Sets the maximum duration a packet will be held.
+    pub fn latency(&mut self, latency: Duration) -> &mut Self {
+        self.latency = latency;
+        self
+    }
+
+    /// Constructs a new builder with a default configuration.
+    pub fn new() -> Self {
+        ConfigBuilder {
+            capacity: DEFAULT_JITTER_BUFFER_CAPACITY,
+            latency: DEFAULT_JITTER_BUFFER_LATENCY,
+        }
+    }
+
+    /// Sets the maximum number of buffered packets.
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity(capacity);
+        self
+    }
+
+    /// Sets the maximum duration a packet will be held.
+    pub fn with_latency(mut self, latency: Duration) -> Self {
+        self.latency(latency);
+        self
+    }
+}
+
+impl Default for ConfigBuilder {
+    fn default() -> Self {
+        ConfigBuilder::new()
+    }
+}
+
+/// Extends a 16-bit sequence number into a 64-bit sequence number using the closest value to the
+/// given reference.
+pub(crate) fn extend_sequence_number(reference: u64, sequence_number: u16) -> u64 {
+    let delta = i64::from(sequence_number.wrapping_sub(reference as u16) as i16);
+    (reference as i64 + delta).max(0) as u64
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use crate::media::{
+        jitter::{Config, Insertion, JitterBuffer},
+        rtp::Packet,
+    };
+
+    fn packet(sequence_number: u16) -> Packet {
+        Packet::new(96, sequence_number, 0, 0, false, &b""[..])
+    }
+
+    fn pop_all(buffer: &mut JitterBuffer, now: Instant) -> Vec<u16> {
+        let mut sequence_numbers = vec![];
+
+        while let Some(packet) = buffer.pop(now) {
+            sequence_numbers.push(packet.sequence_number());
+        }
+
+        sequence_numbers
+    }
+
+    #[test]
+    fn test_jitter_buffer_reorders_across_wraparound() {
+        let now = Instant::now();
+        let mut buffer = JitterBuffer::new();
+
+        for &sequence_number in &[65534, 0, 65535, 1] {
+            assert_eq!(
+                buffer.push(packet(sequence_number), now),
+                Insertion::Buffered
+            );
+        }
+
+        assert_eq!(pop_all(&mut buffer, now), vec![65534, 65535, 0, 1]);
+        assert_eq!(buffer.statistics().reordered, 1);
+    }
+
+    #[test]
+    fn test_jitter_buffer_waits_for_gap() {
+        let now = Instant::now();
+        let config = Config::builder()
+            .with_latency(Duration::from_millis(100))
+            .build();
+        let mut buffer = JitterBuffer::with_config(config);
+
+        buffer.push(packet(10), now);
+        buffer.push(packet(12), now);
+        assert_eq!(pop_all(&mut buffer, now), vec![10]);
+        assert_eq!(
+            buffer.next_deadline(),
+            Some(now + Duration::from_millis(100))
+        );
+
+        let later = now + Duration::from_millis(100);
+        assert_eq!(pop_all(&mut buffer, later), vec![12]);
+        assert_eq!(buffer.statistics().lost, 1);
+        assert_eq!(buffer.push(packet(11), later), Insertion::Late);
+    }
+
+    #[test]
+    fn test_jitter_buffer_duplicate() {
+        let now = Instant::now();
+        let mut buffer = JitterBuffer::new();
+
+        buffer.push(packet(5), now);
+        buffer.push(packet(7), now);
+        assert_eq!(buffer.push(packet(7), now), Insertion::Duplicate);
+    }
+}
@@ -0,0 +1,123 @@
+//! Live Streams
+//!
+//! A [`LiveStream`] is a [`MediaStream`] that applications push frames into as they are produced,
+//! such as frames coming out of an encoder.
+
+use std::error::Error;
+
+use futures::{
+    sync::mpsc::{self, Receiver, Sender},
+    Async, Poll, Stream,
+};
+
+use crate::media::{
+    format::{FormatParameters, RTPMap},
+    frame::Frame,
+    MediaContentModification, MediaRetention, MediaSeeking, MediaStream,
+};
+
+/// The default number of frames that can be pushed into a live stream before frames are dropped.
+pub const DEFAULT_LIVE_STREAM_CAPACITY: usize = 64;
+
+/// A live media stream that frames are pushed into through a [`LiveStreamSender`].
+///
+/// The stream ends once all senders have been dropped.
+#[derive(Debug)]
+pub struct LiveStream {
+    /// The payload format of the stream.
+    format: RTPMap,
+
+    /// The format specific parameters of the stream.
+    format_parameters: Option<FormatParameters>,
+
+    /// The receiving end of the frames pushed into the stream.
+    rx_frame: Receiver<Frame>,
+}
+
+impl LiveStream {
+    /// Constructs a new live stream with the given payload format, along with the sender used to
+    /// push frames into it.
+    pub fn new(
+        format: RTPMap,
+        format_parameters: Option<FormatParameters>,
+    ) -> (Self, LiveStreamSender) {
+        LiveStream::with_capacity(format, format_parameters, DEFAULT_LIVE_STREAM_CAPACITY)
+    }
+
+    /// Constructs a new live stream that buffers up to the given number of frames.
+    pub fn with_capacity(
+        format: RTPMap,
+        format_parameters: Option<FormatParameters>,
+        capacity: usize,
+    ) -> (Self, LiveStreamSender) {
+        let (tx_frame, rx_frame) = mpsc::channel(capacity);
+        let stream = LiveStream {
+            format,
+            format_parameters,
+            rx_frame,
+        };
+
+        (stream, LiveStreamSender(tx_frame))
+    }
+}
+
+impl MediaStream for LiveStream {
+    fn content_modification(&self) -> MediaContentModification {
+        MediaContentModification::TimeProgressing
+    }
+
+    fn format(&self) -> &RTPMap {
+        &self.format
+    }
+
+    fn format_parameters(&self) -> Option<&FormatParameters> {
+        self.format_parameters.as_ref()
+    }
+
+    fn poll_frame(&mut self) -> Poll<Option<Frame>, Box<dyn Error + Send + 'static>> {
+        Ok(self
+            .rx_frame
+            .poll()
+            .expect("`LiveStream.rx_frame` should not error"))
+    }
+
+    fn retention(&self) -> MediaRetention {
+        MediaRetention::TimeDuration
+    }
+
+    fn seeking(&self) -> MediaSeeking {
+        MediaSeeking::NoSeeking
+    }
+}
+
+/// A handle used to push frames into a [`LiveStream`].
+#[derive(Clone, Debug)]
+pub struct LiveStreamSender(Sender<Frame>);
+
+impl LiveStreamSender {
+    /// Pushes a frame into the stream without waiting.
+    ///
+    /// If the stream is not keeping up, the frame is dropped rather than delaying the producer,
+    /// which is the right trade-off for live media. An error containing the frame is returned if
+    /// the frame was dropped or the stream no longer exists.
+    pub fn send(&mut self, frame: Frame) -> Result<(), Frame> {
+        self.0.try_send(frame).map_err(|error| error.into_inner())
+    }
+
+    /// Returns whether the stream has been dropped.
+    pub fn is_closed(&self) -> bool {
+        self.0.is_closed()
+    }
+}
+
+impl Stream for LiveStream {
+    type Item = Frame;
+    type Error = Box<dyn Error + Send + 'static>;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        match self.poll_frame()? {
+            Async::Ready(frame) => Ok(Async::Ready(frame)),
+            Async::NotReady => Ok(Async::NotReady),
+        }
+    }
+}
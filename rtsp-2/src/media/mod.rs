@@ -0,0 +1,256 @@
+//! Media
+//!
+//! This module contains everything needed to move media between RTP and complete frames: payload
+//! formats, packetizers and depacketizers, jitter buffering and the [`MediaStream`] trait through
+//! which applications provide media to the server.
+
+pub mod depacketizer;
+pub mod fanout;
+pub mod file;
+pub mod format;
+pub mod frame;
+pub mod ingest;
+pub mod jitter;
+pub mod live;
+pub mod packetizer;
+pub mod record;
+pub mod rtp;
+pub mod sdp;
+pub mod timeline;
+
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+    time::Duration,
+};
+
+use futures::Poll;
+
+use crate::media::{
+    format::{FormatParameters, RTPMap},
+    frame::Frame,
+    sdp::{MediaDescription, SessionDescription},
+};
+
+/// A collection of media streams that are controlled together, each identified by the control
+/// path relative to the presentation.
+pub struct Presentation {
+    // aggregate_control_uri: ControlURI,
+    // description: PresentationDescription,
+    /// The media streams of the presentation along with their control paths, such as
+    /// `"trackID=0"`.
+    media_streams: Vec<(String, Box<dyn MediaStream + Send>)>,
+
+    /// How the presentation is meant to be used.
+    usage: MediaUsage,
+}
+
+impl Presentation {
+    /// Adds a media stream with the given control path.
+    pub fn add_stream<TControl, TStream>(&mut self, control: TControl, stream: TStream) -> &mut Self
+    where
+        TControl: Into<String>,
+        TStream: MediaStream + Send + 'static,
+    {
+        self.media_streams.push((control.into(), Box::new(stream)));
+        self
+    }
+
+    /// Returns a session description of the presentation with the given name.
+    ///
+    /// The control attribute of each media description is the control path of its stream, so it
+    /// is relative to the presentation URI.
+    pub fn description<TName>(&self, name: TName) -> SessionDescription
+    where
+        TName: Into<String>,
+    {
+        let mut description = SessionDescription::new(name);
+        description.set_duration(self.duration());
+
+        for (control, stream) in &self.media_streams {
+            description.add_media(MediaDescription::new(
+                control.clone(),
+                stream.format().clone(),
+                stream.format_parameters().cloned(),
+            ));
+        }
+
+        description
+    }
+
+    /// Returns the duration of the longest media stream, if the duration of all media streams is
+    /// known.
+    pub fn duration(&self) -> Option<Duration> {
+        self.media_streams
+            .iter()
+            .map(|(_, stream)| stream.duration())
+            .fold(Some(Duration::from_secs(0)), |longest, duration| {
+                Some(longest?.max(duration?))
+            })
+    }
+
+    /// Returns the media streams along with their control paths, consuming the presentation.
+    pub fn into_streams(self) -> Vec<(String, Box<dyn MediaStream + Send>)> {
+        self.media_streams
+    }
+
+    /// Constructs a new live presentation without any media streams.
+    pub fn new() -> Self {
+        Presentation {
+            media_streams: Vec::new(),
+            usage: MediaUsage::Live,
+        }
+    }
+
+    /// Sets how the presentation is meant to be used.
+    ///
+    /// The media streams of [`MediaUsage::OnDemand`] presentations must support
+    /// [`MediaStream::try_clone`], since each session plays its own copy of the streams.
+    pub fn set_usage(&mut self, usage: MediaUsage) -> &mut Self {
+        self.usage = usage;
+        self
+    }
+
+    /// Returns the media streams along with their control paths.
+    pub fn streams(&self) -> &[(String, Box<dyn MediaStream + Send>)] {
+        &self.media_streams
+    }
+
+    /// Returns how the presentation is meant to be used.
+    pub fn usage(&self) -> MediaUsage {
+        self.usage
+    }
+
+    /// Adds a media stream with the given control path.
+    pub fn with_stream<TControl, TStream>(mut self, control: TControl, stream: TStream) -> Self
+    where
+        TControl: Into<String>,
+        TStream: MediaStream + Send + 'static,
+    {
+        self.add_stream(control, stream);
+        self
+    }
+
+    /// Sets how the presentation is meant to be used.
+    pub fn with_usage(mut self, usage: MediaUsage) -> Self {
+        self.set_usage(usage);
+        self
+    }
+}
+
+impl Default for Presentation {
+    fn default() -> Self {
+        Presentation::new()
+    }
+}
+
+pub trait PresentationDescription {
+    // fn aggregate_control_uri() -> ControlURI;
+}
+
+/// A source of frames for a single media stream.
+///
+/// Applications implement this trait to provide media to the server, which packetizes the frames
+/// according to [`MediaStream::format`] and delivers them to every session playing the stream. The
+/// timestamp of each frame must be in units of the clock rate of the format. See
+/// [`live::LiveStream`] for an implementation frames can be pushed into.
+pub trait MediaStream {
+    /// Returns how the content of the stream may change over time.
+    fn content_modification(&self) -> MediaContentModification;
+    // fn control_uri() -> ControlURI;
+
+    /// Returns the duration of the stream, if it is known.
+    fn duration(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Returns the payload format of the stream.
+    fn format(&self) -> &RTPMap;
+
+    /// Returns the format specific parameters of the stream, if any.
+    fn format_parameters(&self) -> Option<&FormatParameters>;
+
+    /// Returns the next frame of the stream, or `None` if the stream has ended.
+    fn poll_frame(&mut self) -> Poll<Option<Frame>, Box<dyn Error + Send + 'static>>;
+
+    /// Returns how long the content of the stream is retained.
+    fn retention(&self) -> MediaRetention;
+
+    /// Notifies the stream that delivery is resuming after a pause, so that streams paced in real
+    /// time continue from the next frame instead of catching up on the time spent paused.
+    fn resume(&mut self) {}
+
+    // fn scale_factors() -> ScaleFactors;
+
+    /// Moves the stream to the given position, returning the position actually moved to.
+    ///
+    /// Streams move to the nearest preceding keyframe, so the returned position may be earlier than
+    /// the one requested. The RTP timestamp of each frame of a seekable stream is its position in
+    /// units of the clock rate, so the returned position also determines the RTP timestamp of the
+    /// next frame.
+    fn seek(&mut self, position: Duration) -> Result<Duration, SeekError> {
+        let _ = position;
+        Err(SeekError::Unsupported)
+    }
+
+    /// Returns how the stream can be seeked.
+    fn seeking(&self) -> MediaSeeking;
+
+    /// Returns an independent copy of the stream starting from the beginning, if possible.
+    fn try_clone(&self) -> Option<Box<dyn MediaStream + Send>> {
+        None
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum MediaContentModification {
+    Dynamic,
+    Immutable,
+    TimeProgressing,
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum MediaRetention {
+    TimeDuration,
+    TimeLimited,
+    Unlimited,
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum MediaSeeking {
+    BeginningOnly,
+    NoSeeking,
+    RandomAccess,
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum MediaUsage {
+    DynamicOnDemand,
+    Live,
+    LiveWithRecording,
+    OnDemand,
+}
+
+/// An error type for when a media stream could not be seeked.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum SeekError {
+    /// The position is past the end of the stream.
+    OutOfRange,
+
+    /// The stream does not support seeking.
+    Unsupported,
+}
+
+impl Display for SeekError {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        use self::SeekError::*;
+
+        match self {
+            OutOfRange => write!(formatter, "seek position out of range"),
+            Unsupported => write!(formatter, "seeking unsupported"),
+        }
+    }
+}
+
+impl Error for SeekError {}
@@ -0,0 +1,184 @@
+//! AAC Packetizer
+//!
+//! Produces RTP packets using the `"mpeg4-generic"` payload format described by
+//! [RFC3640](https://tools.ietf.org/html/rfc3640) in the `"AAC-hbr"` mode. Each packet carries a
+//! single access unit, which is fragmented across several packets if it does not fit in one.
+
+use bytes::{BufMut, BytesMut};
+
+use crate::media::{
+    depacketizer::aac::AudioSpecificConfig,
+    format::{FormatError, FormatParameters},
+    frame::Frame,
+    packetizer::{Config, PacketizeError, Packetizer, Sequencer},
+    rtp::Packet,
+};
+
+/// The number of bits of the size field of each AU header in the `"AAC-hbr"` mode.
+pub const AAC_HBR_SIZE_LENGTH: u32 = 13;
+
+/// The number of bits of the index field of each AU header in the `"AAC-hbr"` mode.
+pub const AAC_HBR_INDEX_LENGTH: u32 = 3;
+
+/// The size of the AU header section of a packet carrying a single access unit.
+const AU_HEADER_SECTION_SIZE: usize = 4;
+
+/// A packetizer for AAC audio.
+#[derive(Debug)]
+pub struct AACPacketizer {
+    /// Produces the packets of the stream.
+    sequencer: Sequencer,
+}
+
+impl AACPacketizer {
+    /// Returns the configuration of the packetizer.
+    pub fn config(&self) -> &Config {
+        self.sequencer.config()
+    }
+
+    /// Returns the `"a=fmtp"` parameters describing the packets produced by this packetizer for
+    /// the given decoder configuration.
+    pub fn format_parameters(
+        payload_type: u8,
+        audio_specific_config: &AudioSpecificConfig,
+    ) -> FormatParameters {
+        let config = audio_specific_config
+            .encode()
+            .iter()
+            .map(|byte| format!("{:02X}", byte))
+            .collect::<String>();
+        let mut parameters = FormatParameters::new(payload_type);
+        parameters.insert("streamtype", "5");
+        parameters.insert("profile-level-id", "1");
+        parameters.insert("mode", "AAC-hbr");
+        parameters.insert("sizelength", AAC_HBR_SIZE_LENGTH.to_string());
+        parameters.insert("indexlength", AAC_HBR_INDEX_LENGTH.to_string());
+        parameters.insert("indexdeltalength", AAC_HBR_INDEX_LENGTH.to_string());
+        parameters.insert("config", config);
+        parameters
+    }
+
+    /// Constructs a new packetizer from the `"a=fmtp"` parameters of the stream.
+    ///
+    /// Only the `"AAC-hbr"` AU header layout is supported.
+    pub fn from_parameters(
+        parameters: &FormatParameters,
+        config: Config,
+    ) -> Result<Self, FormatError> {
+        let is_hbr = parameters
+            .get("mode")
+            .map_or(true, |mode| mode.eq_ignore_ascii_case("AAC-hbr"));
+        let size_length = parameters
+            .get_integer::<u32>("sizelength")
+            .unwrap_or(AAC_HBR_SIZE_LENGTH);
+        let index_length = parameters
+            .get_integer::<u32>("indexlength")
+            .unwrap_or(AAC_HBR_INDEX_LENGTH);
+
+        if !is_hbr || size_length != AAC_HBR_SIZE_LENGTH || index_length != AAC_HBR_INDEX_LENGTH {
+            return Err(FormatError::Unsupported);
+        }
+
+        Ok(AACPacketizer::with_config(config))
+    }
+
+    /// Constructs a new packetizer with the default configuration.
+    pub fn new() -> Self {
+        AACPacketizer::with_config(Config::default())
+    }
+
+    /// Constructs a new packetizer with the given configuration.
+    pub fn with_config(config: Config) -> Self {
+        AACPacketizer {
+            sequencer: Sequencer::new(config),
+        }
+    }
+}
+
+impl Default for AACPacketizer {
+    fn default() -> Self {
+        AACPacketizer::new()
+    }
+}
+
+impl Packetizer for AACPacketizer {
+    fn next_sequence_number(&self) -> u16 {
+        self.sequencer.next_sequence_number()
+    }
+
+    fn packetize(&mut self, frame: &Frame) -> Result<Vec<Packet>, PacketizeError> {
+        let data = frame.data();
+
+        if data.is_empty() {
+            return Err(PacketizeError::EmptyFrame);
+        }
+
+        if data.len() >= 1 << AAC_HBR_SIZE_LENGTH {
+            return Err(PacketizeError::FrameTooLarge);
+        }
+
+        // A single AU header of 16 bits: the size of the entire access unit followed by an index
+        // of zero. Fragments repeat the same header.
+        let max_fragment_size = self.sequencer.max_payload_size() - AU_HEADER_SECTION_SIZE;
+        let au_header = (data.len() as u16) << AAC_HBR_INDEX_LENGTH;
+        let chunks = data.chunks(max_fragment_size).collect::<Vec<_>>();
+        let mut packets = Vec::with_capacity(chunks.len());
+
+        for (index, chunk) in chunks.iter().enumerate() {
+            let mut payload = BytesMut::with_capacity(AU_HEADER_SECTION_SIZE + chunk.len());
+            payload.put_u16_be(16);
+            payload.put_u16_be(au_header);
+            payload.extend_from_slice(chunk);
+            packets.push(self.sequencer.packet(
+                frame.timestamp(),
+                index == chunks.len() - 1,
+                payload.freeze(),
+            ));
+        }
+
+        Ok(packets)
+    }
+
+    fn ssrc(&self) -> u32 {
+        self.sequencer.config().ssrc()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::media::{
+        depacketizer::{aac::AudioSpecificConfig, AACDepacketizer, Depacketizer},
+        frame::Frame,
+        packetizer::{aac::AACPacketizer, Config, Packetizer, MIN_PACKETIZER_MTU},
+    };
+
+    #[test]
+    fn test_aac_packetizer_round_trip() {
+        let parameters =
+            AACPacketizer::format_parameters(97, &AudioSpecificConfig::new(2, 48000, 2));
+        assert_eq!(parameters.get("config"), Some("1190"));
+
+        let config = Config::builder().with_mtu(MIN_PACKETIZER_MTU).build();
+        let mut packetizer = AACPacketizer::from_parameters(&parameters, config).unwrap();
+        let mut depacketizer = AACDepacketizer::from_parameters(48000, &parameters).unwrap();
+
+        for (timestamp, size) in [(0, 8), (1024, 40)].iter().cloned() {
+            let data = (0..size).map(|byte| byte as u8).collect::<Vec<_>>();
+            let packets = packetizer
+                .packetize(&Frame::new(data.clone(), timestamp, true))
+                .unwrap();
+            assert!(packets
+                .iter()
+                .all(|packet| packet.encoded_len() <= MIN_PACKETIZER_MTU));
+            assert!(packets.last().unwrap().marker());
+
+            for packet in packets {
+                depacketizer.push(packet).unwrap();
+            }
+
+            let frame = depacketizer.pull().unwrap();
+            assert_eq!(frame.timestamp(), timestamp);
+            assert_eq!(&frame.data()[..], &data[..]);
+        }
+    }
+}
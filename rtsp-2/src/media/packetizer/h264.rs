@@ -0,0 +1,146 @@
+//! H.264 Packetizer
+//!
+//! Produces RTP packets using the payload format described by
+//! [RFC6184](https://tools.ietf.org/html/rfc6184) in packetization mode 1. NAL units that fit in a
+//! packet are sent as single NAL unit packets, while larger ones are split into FU-A fragmentation
+//! units.
+
+use bytes::BytesMut;
+
+use crate::media::{
+    frame::Frame,
+    packetizer::{self, Config, PacketizeError, Packetizer, Sequencer},
+    rtp::Packet,
+};
+
+/// The NAL unit type of an FU-A fragmentation unit.
+const NAL_UNIT_TYPE_FU_A: u8 = 28;
+
+/// A packetizer for H.264 video.
+#[derive(Debug)]
+pub struct H264Packetizer {
+    /// Produces the packets of the stream.
+    sequencer: Sequencer,
+}
+
+impl H264Packetizer {
+    /// Returns the configuration of the packetizer.
+    pub fn config(&self) -> &Config {
+        self.sequencer.config()
+    }
+
+    /// Constructs a new packetizer with the default configuration.
+    pub fn new() -> Self {
+        H264Packetizer::with_config(Config::default())
+    }
+
+    /// Constructs a new packetizer with the given configuration.
+    pub fn with_config(config: Config) -> Self {
+        H264Packetizer {
+            sequencer: Sequencer::new(config),
+        }
+    }
+}
+
+impl Default for H264Packetizer {
+    fn default() -> Self {
+        H264Packetizer::new()
+    }
+}
+
+impl Packetizer for H264Packetizer {
+    fn next_sequence_number(&self) -> u16 {
+        self.sequencer.next_sequence_number()
+    }
+
+    fn packetize(&mut self, frame: &Frame) -> Result<Vec<Packet>, PacketizeError> {
+        let nal_units = packetizer::split_annex_b(frame.data());
+        let max_payload_size = self.sequencer.max_payload_size();
+        let timestamp = frame.timestamp();
+        let mut packets = Vec::new();
+
+        if nal_units.is_empty() {
+            return Err(PacketizeError::EmptyFrame);
+        }
+
+        for (index, nal_unit) in nal_units.iter().enumerate() {
+            let is_last_nal_unit = index == nal_units.len() - 1;
+
+            if nal_unit.len() <= max_payload_size {
+                packets.push(
+                    self.sequencer
+                        .packet(timestamp, is_last_nal_unit, nal_unit.clone()),
+                );
+                continue;
+            }
+
+            let indicator = (nal_unit[0] & 0xE0) | NAL_UNIT_TYPE_FU_A;
+            let nal_unit_type = nal_unit[0] & 0x1F;
+            let chunks = nal_unit[1..]
+                .chunks(max_payload_size - 2)
+                .collect::<Vec<_>>();
+
+            for (chunk_index, chunk) in chunks.iter().enumerate() {
+                let is_start = chunk_index == 0;
+                let is_end = chunk_index == chunks.len() - 1;
+                let header = (u8::from(is_start) << 7) | (u8::from(is_end) << 6) | nal_unit_type;
+                let mut payload = BytesMut::with_capacity(chunk.len() + 2);
+                payload.extend_from_slice(&[indicator, header]);
+                payload.extend_from_slice(chunk);
+                packets.push(self.sequencer.packet(
+                    timestamp,
+                    is_last_nal_unit && is_end,
+                    payload.freeze(),
+                ));
+            }
+        }
+
+        Ok(packets)
+    }
+
+    fn ssrc(&self) -> u32 {
+        self.sequencer.config().ssrc()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::media::{
+        depacketizer::{Depacketizer, H264Depacketizer},
+        frame::Frame,
+        packetizer::{h264::H264Packetizer, Config, Packetizer, MIN_PACKETIZER_MTU},
+    };
+
+    #[test]
+    fn test_h264_packetizer_fragmentation_round_trip() {
+        let config = Config::builder()
+            .with_mtu(MIN_PACKETIZER_MTU)
+            .with_initial_sequence_number(u16::max_value())
+            .build();
+        let mut packetizer = H264Packetizer::with_config(config);
+        let mut data = vec![0, 0, 0, 1, 0x67, 0x42, 0x00, 0x29, 0, 0, 0, 1, 0x65];
+        data.extend((0..100).map(|byte| byte as u8 | 0x80));
+        let frame = Frame::new(data.clone(), 9000, true);
+
+        let packets = packetizer.packetize(&frame).unwrap();
+        assert!(packets.len() > 2);
+        assert!(packets
+            .iter()
+            .all(|packet| packet.encoded_len() <= MIN_PACKETIZER_MTU));
+        assert!(packets.iter().all(|packet| packet.timestamp() == 9000));
+        assert_eq!(packets.iter().filter(|packet| packet.marker()).count(), 1);
+        assert!(packets.last().unwrap().marker());
+        assert_eq!(packets[1].sequence_number(), 0);
+        assert_eq!(packetizer.next_sequence_number(), packets.len() as u16 - 1);
+
+        let mut depacketizer = H264Depacketizer::new();
+
+        for packet in packets {
+            depacketizer.push(packet).unwrap();
+        }
+
+        let depacketized = depacketizer.pull().unwrap();
+        assert!(depacketized.is_keyframe());
+        assert_eq!(&depacketized.data()[..], &data[..]);
+    }
+}
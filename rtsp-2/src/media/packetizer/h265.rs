@@ -0,0 +1,143 @@
+//! H.265 Packetizer
+//!
+//! Produces RTP packets using the payload format described by
+//! [RFC7798](https://tools.ietf.org/html/rfc7798) without decoding order numbers. NAL units that
+//! fit in a packet are sent as single NAL unit packets, while larger ones are split into
+//! fragmentation units.
+
+use bytes::BytesMut;
+
+use crate::media::{
+    frame::Frame,
+    packetizer::{self, Config, PacketizeError, Packetizer, Sequencer},
+    rtp::Packet,
+};
+
+/// The NAL unit type of a fragmentation unit.
+const NAL_UNIT_TYPE_FU: u8 = 49;
+
+/// A packetizer for H.265 video.
+#[derive(Debug)]
+pub struct H265Packetizer {
+    /// Produces the packets of the stream.
+    sequencer: Sequencer,
+}
+
+impl H265Packetizer {
+    /// Returns the configuration of the packetizer.
+    pub fn config(&self) -> &Config {
+        self.sequencer.config()
+    }
+
+    /// Constructs a new packetizer with the default configuration.
+    pub fn new() -> Self {
+        H265Packetizer::with_config(Config::default())
+    }
+
+    /// Constructs a new packetizer with the given configuration.
+    pub fn with_config(config: Config) -> Self {
+        H265Packetizer {
+            sequencer: Sequencer::new(config),
+        }
+    }
+}
+
+impl Default for H265Packetizer {
+    fn default() -> Self {
+        H265Packetizer::new()
+    }
+}
+
+impl Packetizer for H265Packetizer {
+    fn next_sequence_number(&self) -> u16 {
+        self.sequencer.next_sequence_number()
+    }
+
+    fn packetize(&mut self, frame: &Frame) -> Result<Vec<Packet>, PacketizeError> {
+        let mut nal_units = packetizer::split_annex_b(frame.data());
+        let max_payload_size = self.sequencer.max_payload_size();
+        let timestamp = frame.timestamp();
+        let mut packets = Vec::new();
+
+        // Every NAL unit has a two byte header, anything shorter cannot be sent.
+        nal_units.retain(|nal_unit| nal_unit.len() >= 2);
+
+        if nal_units.is_empty() {
+            return Err(PacketizeError::EmptyFrame);
+        }
+
+        for (index, nal_unit) in nal_units.iter().enumerate() {
+            let is_last_nal_unit = index == nal_units.len() - 1;
+
+            if nal_unit.len() <= max_payload_size {
+                packets.push(
+                    self.sequencer
+                        .packet(timestamp, is_last_nal_unit, nal_unit.clone()),
+                );
+                continue;
+            }
+
+            let payload_header = [(nal_unit[0] & 0x81) | (NAL_UNIT_TYPE_FU << 1), nal_unit[1]];
+            let nal_unit_type = (nal_unit[0] >> 1) & 0x3F;
+            let chunks = nal_unit[2..]
+                .chunks(max_payload_size - 3)
+                .collect::<Vec<_>>();
+
+            for (chunk_index, chunk) in chunks.iter().enumerate() {
+                let is_start = chunk_index == 0;
+                let is_end = chunk_index == chunks.len() - 1;
+                let header = (u8::from(is_start) << 7) | (u8::from(is_end) << 6) | nal_unit_type;
+                let mut payload = BytesMut::with_capacity(chunk.len() + 3);
+                payload.extend_from_slice(&payload_header);
+                payload.extend_from_slice(&[header]);
+                payload.extend_from_slice(chunk);
+                packets.push(self.sequencer.packet(
+                    timestamp,
+                    is_last_nal_unit && is_end,
+                    payload.freeze(),
+                ));
+            }
+        }
+
+        Ok(packets)
+    }
+
+    fn ssrc(&self) -> u32 {
+        self.sequencer.config().ssrc()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::media::{
+        depacketizer::{Depacketizer, H265Depacketizer},
+        frame::Frame,
+        packetizer::{h265::H265Packetizer, Config, Packetizer, MIN_PACKETIZER_MTU},
+    };
+
+    #[test]
+    fn test_h265_packetizer_fragmentation_round_trip() {
+        let config = Config::builder().with_mtu(MIN_PACKETIZER_MTU).build();
+        let mut packetizer = H265Packetizer::with_config(config);
+        let mut data = vec![0, 0, 0, 1, 0x40, 0x01, 0x0C, 0, 0, 0, 1, 0x26, 0x01];
+        data.extend((0..100).map(|byte| byte as u8 | 0x80));
+        let frame = Frame::new(data.clone(), 3000, true);
+
+        let packets = packetizer.packetize(&frame).unwrap();
+        assert!(packets
+            .iter()
+            .all(|packet| packet.encoded_len() <= MIN_PACKETIZER_MTU));
+        assert!(packets.last().unwrap().marker());
+        assert_eq!(packets[1].payload()[0] >> 1 & 0x3F, 49);
+
+        let mut depacketizer = H265Depacketizer::new();
+
+        for packet in packets {
+            depacketizer.push(packet).unwrap();
+        }
+
+        let depacketized = depacketizer.pull().unwrap();
+        assert!(depacketized.is_keyframe());
+        assert_eq!(&depacketized.data()[..], &data[..]);
+    }
+}
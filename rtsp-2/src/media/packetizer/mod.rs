@@ -0,0 +1,384 @@
+//! Packetizers
+//!
+//! A packetizer turns complete [`Frame`]s of a single stream into RTP packets according to the
+//! payload format of the stream. Packets never exceed the configured MTU, the marker bit is set on
+//! the last packet of each frame and the RTP timestamp of each packet is the timestamp of its
+//! frame. Video frames are expected in Annex B format, while AAC frames are expected as raw access
+//! units without ADTS headers.
+
+pub mod aac;
+pub mod h264;
+pub mod h265;
+
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+};
+
+use bytes::Bytes;
+use rand::random;
+
+pub use self::{aac::AACPacketizer, h264::H264Packetizer, h265::H265Packetizer};
+use crate::media::{
+    format::{FormatError, FormatParameters, RTPMap},
+    frame::Frame,
+    rtp::{Packet, RTP_FIXED_HEADER_SIZE},
+};
+
+/// The default maximum size of an RTP packet, chosen to fit in a single Ethernet frame along with
+/// IP and UDP headers and some room for tunneling overhead.
+pub const DEFAULT_PACKETIZER_MTU: usize = 1400;
+
+/// The default payload type used for dynamically assigned payload formats.
+pub const DEFAULT_PACKETIZER_PAYLOAD_TYPE: u8 = 96;
+
+/// The smallest MTU accepted, which leaves room for the RTP header and the largest payload format
+/// header.
+pub const MIN_PACKETIZER_MTU: usize = RTP_FIXED_HEADER_SIZE + 16;
+
+/// A trait for types that split frames of a single stream into RTP packets.
+pub trait Packetizer {
+    /// Returns the sequence number the next packet will have.
+    fn next_sequence_number(&self) -> u16;
+
+    /// Splits the given frame into RTP packets.
+    fn packetize(&mut self, frame: &Frame) -> Result<Vec<Packet>, PacketizeError>;
+
+    /// Returns the synchronization source used for all packets.
+    fn ssrc(&self) -> u32;
+}
+
+/// Constructs the packetizer appropriate for the given payload format.
+///
+/// The supported encoding names are `"H264"`, `"H265"` and `"MPEG4-GENERIC"` (AAC). The payload
+/// type of the format overrides the one in the configuration.
+pub fn from_format(
+    rtpmap: &RTPMap,
+    parameters: Option<&FormatParameters>,
+    config: Config,
+) -> Result<Box<dyn Packetizer + Send>, FormatError> {
+    let config = Config {
+        payload_type: rtpmap.payload_type(),
+        ..config
+    };
+    let encoding_name = rtpmap.encoding_name();
+
+    if encoding_name.eq_ignore_ascii_case("H264") {
+        if let Some(mode) = parameters.and_then(|parameters| parameters.get("packetization-mode")) {
+            if mode != "0" && mode != "1" {
+                return Err(FormatError::Unsupported);
+            }
+        }
+
+        Ok(Box::new(H264Packetizer::with_config(config)))
+    } else if encoding_name.eq_ignore_ascii_case("H265") {
+        Ok(Box::new(H265Packetizer::with_config(config)))
+    } else if encoding_name.eq_ignore_ascii_case("MPEG4-GENERIC") {
+        let empty_parameters = FormatParameters::new(rtpmap.payload_type());
+        let parameters = parameters.unwrap_or(&empty_parameters);
+        Ok(Box::new(AACPacketizer::from_parameters(
+            parameters, config,
+        )?))
+    } else {
+        Err(FormatError::Unsupported)
+    }
+}
+
+/// A set of configuration options controlling how packets are produced.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Config {
+    /// The sequence number of the first packet.
+    initial_sequence_number: u16,
+
+    /// The maximum size of a packet including the RTP header.
+    mtu: usize,
+
+    /// The payload type of all packets.
+    payload_type: u8,
+
+    /// The synchronization source of all packets.
+    ssrc: u32,
+}
+
+impl Config {
+    /// Constructs a builder for specifying possible options.
+    pub fn builder() -> ConfigBuilder {
+        ConfigBuilder::new()
+    }
+
+    /// Returns the sequence number of the first packet.
+    pub fn initial_sequence_number(&self) -> u16 {
+        self.initial_sequence_number
+    }
+
+    /// Returns the maximum size of a packet including the RTP header.
+    pub fn mtu(&self) -> usize {
+        self.mtu
+    }
+
+    /// Returns the payload type of all packets.
+    pub fn payload_type(&self) -> u8 {
+        self.payload_type
+    }
+
+    /// Returns the synchronization source of all packets.
+    pub fn ssrc(&self) -> u32 {
+        self.ssrc
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config::builder().build()
+    }
+}
+
+/// A builder type for constructing a [`Config`].
+///
+/// The synchronization source and initial sequence number are random unless set, as recommended
+/// by [RFC3550](https://tools.ietf.org/html/rfc3550#section-5.1).
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct ConfigBuilder {
+    /// The sequence number of the first packet.
+    initial_sequence_number: u16,
+
+    /// The maximum size of a packet including the RTP header.
+    mtu: usize,
+
+    /// The payload type of all packets.
+    payload_type: u8,
+
+    /// The synchronization source of all packets.
+    ssrc: u32,
+}
+
+impl ConfigBuilder {
+    /// Converts the builder into a [`Config`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the MTU is less than [`MIN_PACKETIZER_MTU`] or the payload type is not in the
+    /// range `0..=127`.
+    pub fn build(self) -> Config {
+        assert!(self.mtu >= MIN_PACKETIZER_MTU);
+        assert!(self.payload_type < 128);
+
+        Config {
+            initial_sequence_number: self.initial_sequence_number,
+            mtu: self.mtu,
+            payload_type: self.payload_type,
+            ssrc: self.ssrc,
+        }
+    }
+
+    /// Sets the sequence number of the first packet.
+    pub fn initial_sequence_number(&mut self, sequence_number: u16) -> &mut Self {
+        self.initial_sequence_number = sequence_number;
+        self
+    }
+
+    /// Sets the maximum size of a packet including the RTP header.
+    pub fn mtu(&mut self, mtu: usize) -> &mut Self {
+        self.mtu = mtu;
+        self
+    }
+
+    /// Constructs a new builder with a default configuration.
+    pub fn new() -> Self {
+        ConfigBuilder {
+            initial_sequence_number: random(),
+            mtu: DEFAULT_PACKETIZER_MTU,
+            payload_type: DEFAULT_PACKETIZER_PAYLOAD_TYPE,
+            ssrc: random(),
+        }
+    }
+
+    /// Sets the payload type of all packets.
+    pub fn payload_type(&mut self, payload_type: u8) -> &mut Self {
+        self.payload_type = payload_type;
+        self
+    }
+
+    /// Sets the synchronization source of all packets.
+    pub fn ssrc(&mut self, ssrc: u32) -> &mut Self {
+        self.ssrc = ssrc;
+        self
+    }
+
+    /// Sets the sequence number of the first packet.
+    pub fn with_initial_sequence_number(mut self, sequence_number: u16) -> Self {
+        self.initial_sequence_number(sequence_number);
+        self
+    }
+
+    /// Sets the maximum size of a packet including the RTP header.
+    pub fn with_mtu(mut self, mtu: usize) -> Self {
+        self.mtu(mtu);
+        self
+    }
+
+    /// Sets the payload type of all packets.
+    pub fn with_payload_type(mut self, payload_type: u8) -> Self {
+        self.payload_type(payload_type);
+        self
+    }
+
+    /// Sets the synchronization source of all packets.
+    pub fn with_ssrc(mut self, ssrc: u32) -> Self {
+        self.ssrc(ssrc);
+        self
+    }
+}
+
+impl Default for ConfigBuilder {
+    fn default() -> Self {
+        ConfigBuilder::new()
+    }
+}
+
+/// An error type for when a frame could not be packetized.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum PacketizeError {
+    /// The frame did not contain any data, or any NAL units for video.
+    EmptyFrame,
+
+    /// The frame is larger than the payload format can describe.
+    FrameTooLarge,
+}
+
+impl Display for PacketizeError {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        use self::PacketizeError::*;
+
+        match self {
+            EmptyFrame => write!(formatter, "empty frame"),
+            FrameTooLarge => write!(formatter, "frame too large"),
+        }
+    }
+}
+
+impl Error for PacketizeError {}
+
+/// Keeps track of the sequence number while producing packets for a single stream.
+#[derive(Debug)]
+pub(crate) struct Sequencer {
+    /// The configuration of the packetizer.
+    config: Config,
+
+    /// The sequence number of the next packet.
+    next_sequence_number: u16,
+}
+
+impl Sequencer {
+    /// Returns the configuration of the packetizer.
+    pub(crate) fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Returns the largest payload that fits in a packet.
+    pub(crate) fn max_payload_size(&self) -> usize {
+        self.config.mtu - RTP_FIXED_HEADER_SIZE
+    }
+
+    /// Constructs a new sequencer starting at the configured initial sequence number.
+    pub(crate) fn new(config: Config) -> Self {
+        Sequencer {
+            config,
+            next_sequence_number: config.initial_sequence_number,
+        }
+    }
+
+    /// Returns the sequence number the next packet will have.
+    pub(crate) fn next_sequence_number(&self) -> u16 {
+        self.next_sequence_number
+    }
+
+    /// Constructs the next packet of the stream.
+    pub(crate) fn packet<TPayload>(
+        &mut self,
+        timestamp: u32,
+        marker: bool,
+        payload: TPayload,
+    ) -> Packet
+    where
+        TPayload: Into<Bytes>,
+    {
+        let sequence_number = self.next_sequence_number;
+        self.next_sequence_number = sequence_number.wrapping_add(1);
+        Packet::new(
+            self.config.payload_type,
+            sequence_number,
+            timestamp,
+            self.config.ssrc,
+            marker,
+            payload,
+        )
+    }
+}
+
+/// Splits an Annex B formatted buffer into its NAL units, without start codes.
+pub(crate) fn split_annex_b(data: &Bytes) -> Vec<Bytes> {
+    let mut nal_units = Vec::new();
+    let mut start = None;
+    let mut index = 0;
+
+    while index + 3 <= data.len() {
+        if data[index] == 0 && data[index + 1] == 0 && data[index + 2] == 1 {
+            if let Some(start) = start {
+                nal_units.push(trim_trailing_zeros(data.slice(start, index)));
+            }
+
+            index += 3;
+            start = Some(index);
+        } else {
+            index += 1;
+        }
+    }
+
+    match start {
+        Some(start) => nal_units.push(trim_trailing_zeros(data.slice_from(start))),
+
+        // Not in Annex B format, so treat the entire buffer as a single NAL unit.
+        None => nal_units.push(data.clone()),
+    }
+
+    nal_units.retain(|nal_unit| !nal_unit.is_empty());
+    nal_units
+}
+
+/// Removes the zero bytes preceding the next start code, which are either the leading zero of a
+/// four byte start code or trailing zero bytes that are not part of the NAL unit.
+fn trim_trailing_zeros(mut nal_unit: Bytes) -> Bytes {
+    let length = nal_unit
+        .iter()
+        .rposition(|&byte| byte != 0)
+        .map_or(0, |index| index + 1);
+    nal_unit.truncate(length);
+    nal_unit
+}
+
+#[cfg(test)]
+mod test {
+    use bytes::Bytes;
+
+    use crate::media::packetizer;
+
+    #[test]
+    fn test_split_annex_b() {
+        let data = Bytes::from(
+            &[
+                0, 0, 0, 1, 0x67, 0x42, 0, 0, 1, 0x68, 0xCE, 0, 0, 0, 1, 0x65,
+            ][..],
+        );
+        let nal_units = packetizer::split_annex_b(&data);
+        assert_eq!(
+            nal_units,
+            vec![
+                Bytes::from(&[0x67, 0x42][..]),
+                Bytes::from(&[0x68, 0xCE][..]),
+                Bytes::from(&[0x65][..]),
+            ]
+        );
+    }
+}
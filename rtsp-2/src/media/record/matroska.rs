@@ -0,0 +1,247 @@
+//! Matroska
+//!
+//! Writes recordings as [Matroska](https://www.matroska.org/technical/elements.html) files. The
+//! segment is written with an unknown size so that the file stays valid while it grows, and each
+//! fragment is a cluster of simple blocks with a known size.
+
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+};
+
+use crate::media::record::{Codec, RecordError, Sample, TrackInfo};
+
+/// The identifier of the EBML header, which starts every Matroska file.
+pub(crate) const EBML_MAGIC: [u8; 4] = [0x1A, 0x45, 0xDF, 0xA3];
+
+/// The identifier of the `Cluster` element.
+const CLUSTER_ID: u32 = 0x1F43_B675;
+
+/// The identifier of the `Segment` element.
+const SEGMENT_ID: u32 = 0x1853_8067;
+
+/// The identifier of the `Tracks` element.
+const TRACKS_ID: u32 = 0x1654_AE6B;
+
+/// The number of nanoseconds per timestamp unit, which makes timestamps milliseconds.
+const TIMESTAMP_SCALE: u64 = 1_000_000;
+
+/// The encoded size of elements whose size is unknown.
+const UNKNOWN_SIZE: [u8; 8] = [0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF];
+
+/// Writes the EBML header and the start of the segment up to and including the track entries.
+pub(crate) fn write_header(tracks: &[TrackInfo], buffer: &mut Vec<u8>) {
+    write_master(buffer, 0x1A45_DFA3, |buffer| {
+        write_uint(buffer, 0x4286, 1);
+        write_uint(buffer, 0x42F7, 1);
+        write_uint(buffer, 0x42F2, 4);
+        write_uint(buffer, 0x42F3, 8);
+        write_element(buffer, 0x4282, b"matroska");
+        write_uint(buffer, 0x4287, 4);
+        write_uint(buffer, 0x4285, 2);
+    });
+
+    write_id(buffer, SEGMENT_ID);
+    buffer.extend_from_slice(&UNKNOWN_SIZE);
+
+    write_master(buffer, 0x1549_A966, |buffer| {
+        write_uint(buffer, 0x2A_D7B1, TIMESTAMP_SCALE);
+        write_element(buffer, 0x4D80, b"rtsp-2");
+        write_element(buffer, 0x5741, b"rtsp-2");
+    });
+
+    write_master(buffer, TRACKS_ID, |buffer| {
+        for track in tracks {
+            write_master(buffer, 0xAE, |buffer| {
+                let (track_type, codec_id): (u64, &[u8]) = match track.codec {
+                    Codec::AAC => (2, b"A_AAC"),
+                    Codec::H264 => (1, b"V_MPEG4/ISO/AVC"),
+                    Codec::H265 => (1, b"V_MPEGH/ISO/HEVC"),
+                };
+
+                write_uint(buffer, 0xD7, u64::from(track.id));
+                write_uint(buffer, 0x73C5, u64::from(track.id));
+                write_uint(buffer, 0x83, track_type);
+                write_uint(buffer, 0x9C, 0);
+                write_element(buffer, 0x86, codec_id);
+                write_element(buffer, 0x63A2, &track.decoder_configuration);
+
+                if !track.codec.is_video() {
+                    write_master(buffer, 0xE1, |buffer| {
+                        write_id(buffer, 0xB5);
+                        write_size(buffer, 8);
+                        buffer.extend_from_slice(
+                            &f64::from(track.sampling_frequency).to_bits().to_be_bytes(),
+                        );
+                        write_uint(buffer, 0x9F, u64::from(track.channels));
+                    });
+                }
+            });
+        }
+    });
+}
+
+/// Writes a cluster holding the given samples of each stream, ordered by their timestamps.
+pub(crate) fn write_fragment(tracks: &[TrackInfo], fragment: &[Vec<Sample>], buffer: &mut Vec<u8>) {
+    let mut blocks = tracks
+        .iter()
+        .zip(fragment)
+        .flat_map(|(track, samples)| {
+            samples.iter().map(move |sample| {
+                let timestamp = (u128::from(sample.time) * 1_000_000_000
+                    / (u128::from(track.clock_rate) * u128::from(TIMESTAMP_SCALE)))
+                    as u64;
+                (timestamp, track.id, sample)
+            })
+        })
+        .collect::<Vec<_>>();
+    blocks.sort_by_key(|&(timestamp, _, _)| timestamp);
+
+    let cluster_timestamp = match blocks.first() {
+        Some(&(timestamp, _, _)) => timestamp,
+        None => return,
+    };
+
+    write_master(buffer, CLUSTER_ID, |buffer| {
+        write_uint(buffer, 0xE7, cluster_timestamp);
+
+        for (timestamp, track_id, sample) in blocks {
+            let relative_timestamp = (timestamp - cluster_timestamp).min(i16::MAX as u64) as i16;
+            let flags = if sample.keyframe { 0x80 } else { 0x00 };
+
+            write_id(buffer, 0xA3);
+            write_size(buffer, 4 + sample.data.len() as u64);
+            buffer.push(0x80 | track_id as u8);
+            buffer.extend_from_slice(&relative_timestamp.to_be_bytes());
+            buffer.push(flags);
+            buffer.extend_from_slice(&sample.data);
+        }
+    });
+}
+
+/// Returns the length of the given Matroska file without a partially written cluster at its end.
+///
+/// Only complete elements following the `Tracks` element are kept.
+pub(crate) fn recoverable_length(file: &mut File) -> Result<u64, RecordError> {
+    let length = file.seek(SeekFrom::End(0))?;
+
+    let (_, header_size, header_length) =
+        read_element_header(file, 0, length)?.ok_or(RecordError::InvalidData)?;
+    let segment_offset = header_length + header_size.ok_or(RecordError::InvalidData)?;
+
+    let (id, _, segment_header_length) =
+        read_element_header(file, segment_offset, length)?.ok_or(RecordError::InvalidData)?;
+
+    if id != SEGMENT_ID {
+        return Err(RecordError::InvalidData);
+    }
+
+    let mut offset = segment_offset + segment_header_length;
+    let mut recoverable_length = None;
+
+    while let Some((id, Some(size), header_length)) = read_element_header(file, offset, length)? {
+        if length - offset - header_length < size {
+            break;
+        }
+
+        offset += header_length + size;
+
+        if id == TRACKS_ID || recoverable_length.is_some() {
+            recoverable_length = Some(offset);
+        }
+    }
+
+    recoverable_length.ok_or(RecordError::InvalidData)
+}
+
+/// Reads the identifier, size and header length of the element at the given offset. The size is
+/// [`Option::None`] if it is unknown.
+fn read_element_header(
+    file: &mut File,
+    offset: u64,
+    length: u64,
+) -> Result<Option<(u32, Option<u64>, u64)>, RecordError> {
+    let mut header = [0; 12];
+    let available = (length.saturating_sub(offset)).min(12) as usize;
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(&mut header[..available])?;
+    let header = &header[..available];
+
+    let id_length = match header.first() {
+        Some(&byte) if byte != 0 => byte.leading_zeros() as usize + 1,
+        _ => return Ok(None),
+    };
+
+    if id_length > 4 || header.len() <= id_length {
+        return Ok(None);
+    }
+
+    let id = header[..id_length]
+        .iter()
+        .fold(0, |id, &byte| id << 8 | u32::from(byte));
+    let size_length = match header[id_length] {
+        0 => return Ok(None),
+        byte => byte.leading_zeros() as usize + 1,
+    };
+
+    if header.len() < id_length + size_length {
+        return Ok(None);
+    }
+
+    let size_bytes = &header[id_length..id_length + size_length];
+    let marker = 0xFFu8.checked_shr(size_length as u32).unwrap_or(0);
+    let unknown =
+        size_bytes[0] & marker == marker && size_bytes[1..].iter().all(|&byte| byte == 0xFF);
+    let size = size_bytes[1..]
+        .iter()
+        .fold(u64::from(size_bytes[0] & marker), |size, &byte| {
+            size << 8 | u64::from(byte)
+        });
+
+    Ok(Some((
+        id,
+        if unknown { None } else { Some(size) },
+        (id_length + size_length) as u64,
+    )))
+}
+
+/// Writes an element with the given identifier and binary contents.
+fn write_element(buffer: &mut Vec<u8>, id: u32, content: &[u8]) {
+    write_id(buffer, id);
+    write_size(buffer, content.len() as u64);
+    buffer.extend_from_slice(content);
+}
+
+/// Writes an element identifier, which already includes its length marker.
+fn write_id(buffer: &mut Vec<u8>, id: u32) {
+    let bytes = id.to_be_bytes();
+    let skip = (id.leading_zeros() / 8) as usize;
+    buffer.extend_from_slice(&bytes[skip.min(3)..]);
+}
+
+/// Writes a master element whose children are written by the given function.
+fn write_master<TContent>(buffer: &mut Vec<u8>, id: u32, content: TContent)
+where
+    TContent: FnOnce(&mut Vec<u8>),
+{
+    let mut children = Vec::new();
+    content(&mut children);
+    write_element(buffer, id, &children);
+}
+
+/// Writes an element size as a variable length integer of the shortest possible length.
+fn write_size(buffer: &mut Vec<u8>, size: u64) {
+    // The value with all bits set is reserved for unknown sizes.
+    let length = (1..=8)
+        .find(|&length| size < (1 << (7 * length)) - 1)
+        .unwrap_or(8);
+    let bytes = (size | 1 << (7 * length)).to_be_bytes();
+    buffer.extend_from_slice(&bytes[8 - length..]);
+}
+
+/// Writes an unsigned integer element using as few bytes as possible.
+fn write_uint(buffer: &mut Vec<u8>, id: u32, value: u64) {
+    let bytes = value.to_be_bytes();
+    let skip = ((value.leading_zeros() / 8) as usize).min(7);
+    write_element(buffer, id, &bytes[skip..]);
+}
@@ -0,0 +1,1005 @@
+//! Recording
+//!
+//! A [`Recorder`] takes the depacketized frames of the streams of a client session and muxes them
+//! into fragmented MP4 or Matroska files. Frames are written in fragments, each starting at a
+//! keyframe of the reference stream, so an interrupted recording loses at most the fragment being
+//! written, which [`recover`] trims off. Recordings can be split into segments of a given duration,
+//! each of which is a self-contained file.
+//!
+//! H.264, H.265 and AAC streams can be recorded.
+
+pub mod matroska;
+pub mod mp4;
+
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+    fs::{File, OpenOptions},
+    io::{self, Read, Write},
+    mem,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use bytes::Bytes;
+
+use crate::media::{
+    depacketizer::{AACDepacketizer, H264Depacketizer, H265Depacketizer},
+    format::{FormatError, FormatParameters, RTPMap},
+    frame::Frame,
+    packetizer,
+    timeline::Timeline,
+};
+
+/// The default minimum duration of a fragment.
+pub const DEFAULT_FRAGMENT_DURATION: Duration = Duration::from_secs(1);
+
+/// The maximum duration of a fragment. Matroska block timestamps are relative to their cluster and
+/// limited to 16 bits of milliseconds, so fragments are cut regardless of keyframes past this.
+pub const MAX_FRAGMENT_DURATION: Duration = Duration::from_secs(30);
+
+/// The codec of a recorded stream.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub(crate) enum Codec {
+    /// AAC audio.
+    AAC,
+
+    /// H.264 video.
+    H264,
+
+    /// H.265 video.
+    H265,
+}
+
+impl Codec {
+    /// Returns whether the codec is a video codec.
+    pub(crate) fn is_video(self) -> bool {
+        self != Codec::AAC
+    }
+}
+
+/// The container format recordings are written in.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ContainerFormat {
+    /// Matroska with clusters as fragments.
+    Matroska,
+
+    /// Fragmented MP4.
+    MP4,
+}
+
+impl ContainerFormat {
+    /// Returns the file extension of the container format.
+    pub fn extension(self) -> &'static str {
+        match self {
+            ContainerFormat::Matroska => "mkv",
+            ContainerFormat::MP4 => "mp4",
+        }
+    }
+}
+
+/// Muxes the frames of one or more streams into segment files.
+///
+/// Nothing is written until every stream has its decoder configuration, which is taken from the
+/// format parameters or, for video streams, from parameter sets sent in-band, and until the
+/// reference stream produces a keyframe. The reference stream is the first video stream, or the
+/// first stream if there are no video streams.
+///
+/// Frames with presentation times, such as those produced by a
+/// [`Reassembler`](crate::media::depacketizer::Reassembler) with a timeline, are placed according
+/// to them, which keeps streams synchronized. Otherwise each stream starts at its first frame,
+/// using its RTP timestamps from there on, including across wraparound.
+pub struct Recorder {
+    /// The configuration of the recorder.
+    config: Config,
+
+    /// The directory segment files are written to.
+    directory: PathBuf,
+
+    /// The file of the current segment, if one has been started.
+    file: Option<File>,
+
+    /// The sequence number of the next fragment in the current segment.
+    fragment_sequence_number: u32,
+
+    /// The prefix of the segment file names.
+    prefix: String,
+
+    /// The presentation time at which the current segment starts.
+    segment_start: Option<Duration>,
+
+    /// The paths of all segment files written so far.
+    segments: Vec<PathBuf>,
+
+    /// The recorded streams.
+    tracks: Vec<Track>,
+}
+
+impl Recorder {
+    /// Adds a stream with the given payload format, returning the index frames of the stream are
+    /// pushed with.
+    ///
+    /// Streams must be added before the first frame is pushed.
+    pub fn add_track(
+        &mut self,
+        format: &RTPMap,
+        parameters: Option<&FormatParameters>,
+    ) -> Result<usize, RecordError> {
+        if self.segment_start.is_some() {
+            return Err(RecordError::AlreadyStarted);
+        }
+
+        self.tracks.push(Track::new(format, parameters)?);
+        Ok(self.tracks.len() - 1)
+    }
+
+    /// Returns the configuration of the recorder.
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Writes all remaining frames and closes the current segment.
+    pub fn finish(&mut self) -> Result<(), RecordError> {
+        if self.segment_start.is_some() {
+            self.write_fragment(true)?;
+        }
+
+        self.file = None;
+        self.segment_start = None;
+        Ok(())
+    }
+
+    /// Constructs a new recorder writing segments named `"<prefix>-<index>.<extension>"` into the
+    /// given directory, using the default configuration.
+    pub fn new<TDirectory, TPrefix>(directory: TDirectory, prefix: TPrefix) -> Self
+    where
+        TDirectory: Into<PathBuf>,
+        TPrefix: Into<String>,
+    {
+        Recorder::with_config(directory, prefix, Config::default())
+    }
+
+    /// Records a frame of the stream with the given index.
+    ///
+    /// Frames that arrive before recording has started or that lie before the start of the current
+    /// segment are dropped.
+    pub fn push(&mut self, track: usize, frame: Frame) -> Result<(), RecordError> {
+        let reference = self.reference_track();
+        let state = self
+            .tracks
+            .get_mut(track)
+            .ok_or(RecordError::InvalidTrack)?;
+        state.update_parameter_sets(&frame);
+
+        let time = match state.presentation_time(&frame) {
+            Some(time) => time,
+            None => return Ok(()),
+        };
+        let keyframe = frame.is_keyframe() || !state.codec.is_video();
+        let boundary = track == reference && keyframe;
+
+        let segment_start = match self.segment_start {
+            Some(segment_start) => segment_start,
+            None if boundary && self.tracks.iter().all(Track::is_configured) => {
+                self.start_segment(time)?;
+                time
+            }
+            None => return Ok(()),
+        };
+
+        if time < segment_start {
+            return Ok(());
+        }
+
+        let state = &mut self.tracks[track];
+        let sample_time = duration_to_ticks(time - segment_start, state.clock_rate);
+        state.complete_pending(sample_time);
+
+        let elapsed = state.elapsed(sample_time);
+        let rotate = boundary
+            && self
+                .config
+                .segment_duration
+                .map_or(false, |duration| time - segment_start >= duration);
+
+        if rotate {
+            self.write_fragment(true)?;
+            self.start_segment(time)?;
+        } else if (boundary && elapsed >= self.config.fragment_duration)
+            || elapsed >= MAX_FRAGMENT_DURATION
+        {
+            self.write_fragment(false)?;
+        }
+
+        let segment_start = self.segment_start.unwrap_or(segment_start);
+        let state = &mut self.tracks[track];
+        state.pending = Some(Sample {
+            data: state.sample_data(frame.data()),
+            duration: 0,
+            keyframe,
+            time: duration_to_ticks(time - segment_start, state.clock_rate),
+        });
+
+        Ok(())
+    }
+
+    /// Returns the index of the stream whose keyframes start fragments and segments.
+    fn reference_track(&self) -> usize {
+        self.tracks
+            .iter()
+            .position(|track| track.codec.is_video())
+            .unwrap_or(0)
+    }
+
+    /// Returns the paths of all segment files written so far, in order.
+    pub fn segments(&self) -> &[PathBuf] {
+        &self.segments
+    }
+
+    /// Starts a new segment at the given presentation time, creating its file and writing its
+    /// header.
+    fn start_segment(&mut self, time: Duration) -> Result<(), RecordError> {
+        let path = self.directory.join(format!(
+            "{}-{:05}.{}",
+            self.prefix,
+            self.segments.len(),
+            self.config.format.extension()
+        ));
+        let mut file = File::create(&path)?;
+        let tracks = self
+            .tracks
+            .iter()
+            .enumerate()
+            .map(|(index, track)| track.info(index))
+            .collect::<Vec<_>>();
+        let mut buffer = Vec::new();
+
+        match self.config.format {
+            ContainerFormat::Matroska => matroska::write_header(&tracks, &mut buffer),
+            ContainerFormat::MP4 => mp4::write_header(&tracks, &mut buffer),
+        }
+
+        file.write_all(&buffer)?;
+        self.file = Some(file);
+        self.fragment_sequence_number = 1;
+        self.segment_start = Some(time);
+        self.segments.push(path);
+        Ok(())
+    }
+
+    /// Constructs a new recorder writing segments named `"<prefix>-<index>.<extension>"` into the
+    /// given directory.
+    pub fn with_config<TDirectory, TPrefix>(
+        directory: TDirectory,
+        prefix: TPrefix,
+        config: Config,
+    ) -> Self
+    where
+        TDirectory: Into<PathBuf>,
+        TPrefix: Into<String>,
+    {
+        Recorder {
+            config,
+            directory: directory.into(),
+            file: None,
+            fragment_sequence_number: 1,
+            prefix: prefix.into(),
+            segment_start: None,
+            segments: Vec::new(),
+            tracks: Vec::new(),
+        }
+    }
+
+    /// Writes the completed samples of all streams as a fragment of the current segment.
+    ///
+    /// When ending the segment, samples still waiting for their duration are included as well,
+    /// assuming they last as long as the sample before them.
+    fn write_fragment(&mut self, end_of_segment: bool) -> Result<(), RecordError> {
+        if end_of_segment {
+            for track in &mut self.tracks {
+                if let Some(mut sample) = track.pending.take() {
+                    sample.duration = track.last_duration;
+                    track.samples.push(sample);
+                }
+            }
+        }
+
+        let fragment = self
+            .tracks
+            .iter_mut()
+            .map(|track| mem::take(&mut track.samples))
+            .collect::<Vec<_>>();
+
+        if fragment.iter().all(Vec::is_empty) {
+            return Ok(());
+        }
+
+        let tracks = self
+            .tracks
+            .iter()
+            .enumerate()
+            .map(|(index, track)| track.info(index))
+            .collect::<Vec<_>>();
+        let mut buffer = Vec::new();
+
+        match self.config.format {
+            ContainerFormat::Matroska => matroska::write_fragment(&tracks, &fragment, &mut buffer),
+            ContainerFormat::MP4 => mp4::write_fragment(
+                &tracks,
+                &fragment,
+                self.fragment_sequence_number,
+                &mut buffer,
+            ),
+        }
+
+        self.fragment_sequence_number += 1;
+
+        // Each fragment is written at once, so an interrupted write leaves at most one partial
+        // fragment at the end of the file.
+        let file = self
+            .file
+            .as_mut()
+            .expect("a segment should have been started");
+        file.write_all(&buffer)?;
+        file.flush()?;
+        Ok(())
+    }
+}
+
+/// The configuration of a [`Recorder`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Config {
+    /// The container format of the segment files.
+    format: ContainerFormat,
+
+    /// The minimum duration of a fragment.
+    fragment_duration: Duration,
+
+    /// The duration after which a new segment is started, if recordings are segmented.
+    segment_duration: Option<Duration>,
+}
+
+impl Config {
+    /// Returns a new builder for the configuration.
+    pub fn builder() -> ConfigBuilder {
+        ConfigBuilder::new()
+    }
+
+    /// Returns the container format of the segment files.
+    pub fn format(&self) -> ContainerFormat {
+        self.format
+    }
+
+    /// Returns the minimum duration of a fragment. Fragments are cut at the first keyframe of the
+    /// reference stream after this duration.
+    pub fn fragment_duration(&self) -> Duration {
+        self.fragment_duration
+    }
+
+    /// Returns the duration after which a new segment is started at the next keyframe of the
+    /// reference stream, if recordings are segmented.
+    pub fn segment_duration(&self) -> Option<Duration> {
+        self.segment_duration
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config::builder().build()
+    }
+}
+
+/// A builder for [`Config`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ConfigBuilder {
+    /// The container format of the segment files.
+    format: ContainerFormat,
+
+    /// The minimum duration of a fragment.
+    fragment_duration: Duration,
+
+    /// The duration after which a new segment is started, if recordings are segmented.
+    segment_duration: Option<Duration>,
+}
+
+impl ConfigBuilder {
+    /// Builds the configuration.
+    pub fn build(self) -> Config {
+        Config {
+            format: self.format,
+            fragment_duration: self.fragment_duration.min(MAX_FRAGMENT_DURATION),
+            segment_duration: self.segment_duration,
+        }
+    }
+
+    /// Sets the container format of the segment files.
+    pub fn format(&mut self, format: ContainerFormat) -> &mut Self {
+        self.format = format;
+        self
+    }
+
+    /// Sets the minimum duration of a fragment, which is capped at [`MAX_FRAGMENT_DURATION`].
+    pub fn fragment_duration(&mut self, duration: Duration) -> &mut Self {
+        self.fragment_duration = duration;
+        self
+    }
+
+    /// Constructs a new builder with the default values, which record fragmented MP4 without
+    /// segmenting.
+    pub fn new() -> Self {
+        ConfigBuilder {
+            format: ContainerFormat::MP4,
+            fragment_duration: DEFAULT_FRAGMENT_DURATION,
+            segment_duration: None,
+        }
+    }
+
+    /// Sets the duration after which a new segment is started, if recordings are segmented.
+    pub fn segment_duration(&mut self, duration: Option<Duration>) -> &mut Self {
+        self.segment_duration = duration;
+        self
+    }
+
+    /// Sets the container format of the segment files.
+    pub fn with_format(mut self, format: ContainerFormat) -> Self {
+        self.format(format);
+        self
+    }
+
+    /// Sets the minimum duration of a fragment, which is capped at [`MAX_FRAGMENT_DURATION`].
+    pub fn with_fragment_duration(mut self, duration: Duration) -> Self {
+        self.fragment_duration(duration);
+        self
+    }
+
+    /// Sets the duration after which a new segment is started, if recordings are segmented.
+    pub fn with_segment_duration(mut self, duration: Option<Duration>) -> Self {
+        self.segment_duration(duration);
+        self
+    }
+}
+
+impl Default for ConfigBuilder {
+    fn default() -> Self {
+        ConfigBuilder::new()
+    }
+}
+
+/// Trims a partially written fragment off the end of an interrupted recording, returning the
+/// length of the recovered file.
+///
+/// The container format is detected from the contents of the file. An error is returned if not
+/// even the header of the file is complete.
+pub fn recover<TPath>(path: TPath) -> Result<u64, RecordError>
+where
+    TPath: AsRef<Path>,
+{
+    let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+    let mut magic = [0; 4];
+    file.read_exact(&mut magic)
+        .map_err(|_| RecordError::InvalidData)?;
+
+    let length = if magic == matroska::EBML_MAGIC {
+        matroska::recoverable_length(&mut file)?
+    } else {
+        mp4::recoverable_length(&mut file)?
+    };
+
+    file.set_len(length)?;
+    Ok(length)
+}
+
+/// A possible error value when recording.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum RecordError {
+    /// A stream was added after recording started.
+    AlreadyStarted,
+
+    /// The payload format of a stream is unsupported or its format parameters are invalid.
+    Format(FormatError),
+
+    /// A recording to be recovered does not have a complete header.
+    InvalidData,
+
+    /// A frame was pushed for a stream that does not exist.
+    InvalidTrack,
+
+    /// A segment file could not be written.
+    IO(io::ErrorKind),
+}
+
+impl Display for RecordError {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        use self::RecordError::*;
+
+        match self {
+            AlreadyStarted => write!(formatter, "recording already started"),
+            Format(error) => write!(formatter, "{}", error),
+            InvalidData => write!(formatter, "invalid recording data"),
+            InvalidTrack => write!(formatter, "invalid recording track"),
+            IO(kind) => write!(formatter, "recording IO error: {:?}", kind),
+        }
+    }
+}
+
+impl Error for RecordError {}
+
+impl From<FormatError> for RecordError {
+    fn from(value: FormatError) -> Self {
+        RecordError::Format(value)
+    }
+}
+
+impl From<io::Error> for RecordError {
+    fn from(value: io::Error) -> Self {
+        RecordError::IO(value.kind())
+    }
+}
+
+/// A frame as stored in a fragment.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct Sample {
+    /// The sample data, with video NAL units prefixed by their four byte length.
+    pub(crate) data: Bytes,
+
+    /// The duration of the sample in ticks of the stream clock.
+    pub(crate) duration: u32,
+
+    /// Whether the sample is a keyframe.
+    pub(crate) keyframe: bool,
+
+    /// The time of the sample relative to the start of the segment in ticks of the stream clock.
+    pub(crate) time: u64,
+}
+
+/// The description of a stream written into segment headers.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct TrackInfo {
+    /// The number of audio channels.
+    pub(crate) channels: u16,
+
+    /// The clock rate of the stream, used as its timescale.
+    pub(crate) clock_rate: u32,
+
+    /// The codec of the stream.
+    pub(crate) codec: Codec,
+
+    /// The decoder configuration record: `avcC` or `hvcC` contents for video and the
+    /// `AudioSpecificConfig` for audio.
+    pub(crate) decoder_configuration: Bytes,
+
+    /// The identifier of the stream, starting at one.
+    pub(crate) id: u32,
+
+    /// The audio sampling frequency.
+    pub(crate) sampling_frequency: u32,
+}
+
+/// The state of a recorded stream.
+struct Track {
+    /// The decoder configuration of audio streams.
+    audio_configuration: Option<Bytes>,
+
+    /// The number of audio channels.
+    channels: u16,
+
+    /// The clock rate of the stream.
+    clock_rate: u32,
+
+    /// The codec of the stream.
+    codec: Codec,
+
+    /// The duration of the last completed sample, used for samples whose duration is unknown.
+    last_duration: u32,
+
+    /// The video parameter sets, in the order VPS, SPS and PPS, where VPS is only used by H.265.
+    parameter_sets: [Option<Bytes>; 3],
+
+    /// The last sample, waiting for the next one to determine its duration.
+    pending: Option<Sample>,
+
+    /// The audio sampling frequency.
+    sampling_frequency: u32,
+
+    /// The completed samples of the current fragment.
+    samples: Vec<Sample>,
+
+    /// The timeline used for frames without presentation times, anchored at the first frame.
+    timeline: Option<Timeline>,
+}
+
+impl Track {
+    /// Completes the pending sample now that the next one is known to start at the given time.
+    fn complete_pending(&mut self, time: u64) {
+        if let Some(mut sample) = self.pending.take() {
+            sample.duration = time.saturating_sub(sample.time).min(u64::from(u32::MAX)) as u32;
+
+            if sample.duration > 0 {
+                self.last_duration = sample.duration;
+            }
+
+            self.samples.push(sample);
+        }
+    }
+
+    /// Returns the duration of the current fragment if a sample at the given time was added.
+    fn elapsed(&self, time: u64) -> Duration {
+        match self.samples.first() {
+            Some(sample) => ticks_to_duration(time.saturating_sub(sample.time), self.clock_rate),
+            None => Duration::from_secs(0),
+        }
+    }
+
+    /// Returns the description of the stream written into segment headers.
+    fn info(&self, index: usize) -> TrackInfo {
+        let decoder_configuration = match self.codec {
+            Codec::AAC => self.audio_configuration.clone().unwrap_or_default(),
+            Codec::H264 => avc_decoder_configuration(&self.parameter_sets),
+            Codec::H265 => hevc_decoder_configuration(&self.parameter_sets),
+        };
+
+        TrackInfo {
+            channels: self.channels,
+            clock_rate: self.clock_rate,
+            codec: self.codec,
+            decoder_configuration,
+            id: index as u32 + 1,
+            sampling_frequency: self.sampling_frequency,
+        }
+    }
+
+    /// Returns whether the decoder configuration of the stream is known.
+    fn is_configured(&self) -> bool {
+        match self.codec {
+            Codec::AAC => self.audio_configuration.is_some(),
+            Codec::H264 => self.parameter_sets[1..].iter().all(Option::is_some),
+            Codec::H265 => self.parameter_sets.iter().all(Option::is_some),
+        }
+    }
+
+    /// Constructs the state of a stream with the given payload format.
+    fn new(format: &RTPMap, parameters: Option<&FormatParameters>) -> Result<Self, RecordError> {
+        let empty_parameters = FormatParameters::new(format.payload_type());
+        let parameters = parameters.unwrap_or(&empty_parameters);
+        let encoding_name = format.encoding_name();
+        let mut track = Track {
+            audio_configuration: None,
+            channels: format.channels().unwrap_or(1),
+            clock_rate: format.clock_rate(),
+            codec: Codec::AAC,
+            last_duration: 0,
+            parameter_sets: [None, None, None],
+            pending: None,
+            sampling_frequency: format.clock_rate(),
+            samples: Vec::new(),
+            timeline: None,
+        };
+
+        if encoding_name.eq_ignore_ascii_case("H264") {
+            track.codec = Codec::H264;
+
+            for parameter_set in H264Depacketizer::from_parameters(parameters)?.parameter_sets() {
+                track.add_parameter_set(parameter_set);
+            }
+        } else if encoding_name.eq_ignore_ascii_case("H265") {
+            track.codec = Codec::H265;
+
+            for parameter_set in H265Depacketizer::from_parameters(parameters)?.parameter_sets() {
+                track.add_parameter_set(parameter_set);
+            }
+        } else if encoding_name.eq_ignore_ascii_case("MPEG4-GENERIC") {
+            let depacketizer = AACDepacketizer::from_parameters(format.clock_rate(), parameters)?;
+
+            if let Some(config) = depacketizer.config() {
+                track.audio_configuration = Some(Bytes::from(config.encode()));
+                track.channels = u16::from(config.channel_configuration());
+                track.sampling_frequency = config.sampling_frequency();
+            }
+        } else {
+            return Err(RecordError::Format(FormatError::Unsupported));
+        }
+
+        Ok(track)
+    }
+
+    /// Stores the given NAL unit if it is a parameter set.
+    fn add_parameter_set(&mut self, nal_unit: &Bytes) {
+        let index = match (self.codec, nal_unit.first()) {
+            (Codec::H264, Some(header)) => match header & 0x1F {
+                7 => 1,
+                8 => 2,
+                _ => return,
+            },
+            (Codec::H265, Some(header)) => match (header >> 1) & 0x3F {
+                32 => 0,
+                33 => 1,
+                34 => 2,
+                _ => return,
+            },
+            _ => return,
+        };
+
+        self.parameter_sets[index] = Some(nal_unit.clone());
+    }
+
+    /// Returns the presentation time of the given frame, using the RTP timestamp if the frame
+    /// does not have one.
+    fn presentation_time(&mut self, frame: &Frame) -> Option<Duration> {
+        if let Some(time) = frame.presentation_time() {
+            return Some(time);
+        }
+
+        let clock_rate = self.clock_rate;
+        self.timeline
+            .get_or_insert_with(|| {
+                Timeline::new(clock_rate, frame.timestamp(), Duration::from_secs(0))
+            })
+            .presentation_time(frame.timestamp())
+    }
+
+    /// Converts the data of a frame to how it is stored in samples.
+    fn sample_data(&self, data: &Bytes) -> Bytes {
+        if !self.codec.is_video() {
+            return data.clone();
+        }
+
+        let mut buffer = Vec::with_capacity(data.len());
+
+        for nal_unit in packetizer::split_annex_b(data) {
+            buffer.extend_from_slice(&(nal_unit.len() as u32).to_be_bytes());
+            buffer.extend_from_slice(&nal_unit);
+        }
+
+        Bytes::from(buffer)
+    }
+
+    /// Picks up parameter sets sent in-band by keyframes while the configuration is incomplete.
+    fn update_parameter_sets(&mut self, frame: &Frame) {
+        if self.codec.is_video() && frame.is_keyframe() && !self.is_configured() {
+            for nal_unit in packetizer::split_annex_b(frame.data()) {
+                self.add_parameter_set(&nal_unit);
+            }
+        }
+    }
+}
+
+/// Builds an `AVCDecoderConfigurationRecord` as described by ISO/IEC 14496-15, Section 5.3.3.1,
+/// from the SPS and PPS.
+fn avc_decoder_configuration(parameter_sets: &[Option<Bytes>; 3]) -> Bytes {
+    let (sps, pps) = match (&parameter_sets[1], &parameter_sets[2]) {
+        (Some(sps), Some(pps)) if sps.len() >= 4 => (sps, pps),
+        _ => return Bytes::new(),
+    };
+    let mut buffer = vec![1, sps[1], sps[2], sps[3], 0xFF, 0xE1];
+    buffer.extend_from_slice(&(sps.len() as u16).to_be_bytes());
+    buffer.extend_from_slice(sps);
+    buffer.push(1);
+    buffer.extend_from_slice(&(pps.len() as u16).to_be_bytes());
+    buffer.extend_from_slice(pps);
+    Bytes::from(buffer)
+}
+
+/// Builds an `HEVCDecoderConfigurationRecord` as described by ISO/IEC 14496-15, Section 8.3.3.1,
+/// from the VPS, SPS and PPS.
+///
+/// The general profile, tier and level are taken from the SPS, while the remaining fields assume
+/// 8-bit 4:2:0 video, which is what cameras produce.
+fn hevc_decoder_configuration(parameter_sets: &[Option<Bytes>; 3]) -> Bytes {
+    let sps = match &parameter_sets[1] {
+        Some(sps) => remove_emulation_prevention(sps),
+        None => return Bytes::new(),
+    };
+
+    // The profile, tier and level follow the two byte NAL unit header and a byte of SPS fields.
+    let profile_tier_level = match sps.get(3..15) {
+        Some(profile_tier_level) => profile_tier_level,
+        None => return Bytes::new(),
+    };
+
+    let mut buffer = vec![1];
+    buffer.extend_from_slice(profile_tier_level);
+    buffer.extend_from_slice(&[0xF0, 0x00, 0xFC, 0xFD, 0xF8, 0xF8, 0x00, 0x00, 0x0F, 3]);
+
+    for (index, parameter_set) in parameter_sets.iter().enumerate() {
+        let parameter_set = parameter_set.as_ref().map(|set| &set[..]).unwrap_or(&[]);
+        buffer.push(0x80 | (32 + index as u8));
+        buffer.extend_from_slice(&1u16.to_be_bytes());
+        buffer.extend_from_slice(&(parameter_set.len() as u16).to_be_bytes());
+        buffer.extend_from_slice(parameter_set);
+    }
+
+    Bytes::from(buffer)
+}
+
+/// Converts a duration to a number of ticks of the given clock rate, rounding up so that
+/// converting ticks to a duration and back is lossless.
+fn duration_to_ticks(duration: Duration, clock_rate: u32) -> u64 {
+    ((duration.as_nanos() * u128::from(clock_rate) + 999_999_999) / 1_000_000_000) as u64
+}
+
+/// Removes the emulation prevention bytes from a NAL unit.
+fn remove_emulation_prevention(nal_unit: &[u8]) -> Vec<u8> {
+    let mut buffer = Vec::with_capacity(nal_unit.len());
+    let mut zeros = 0;
+
+    for &byte in nal_unit {
+        if zeros >= 2 && byte == 3 {
+            zeros = 0;
+            continue;
+        }
+
+        zeros = if byte == 0 { zeros + 1 } else { 0 };
+        buffer.push(byte);
+    }
+
+    buffer
+}
+
+/// Converts a number of ticks of the given clock rate to a duration.
+fn ticks_to_duration(ticks: u64, clock_rate: u32) -> Duration {
+    Duration::from_nanos((u128::from(ticks) * 1_000_000_000 / u128::from(clock_rate)) as u64)
+}
+
+#[cfg(test)]
+mod test {
+    use std::{fs, path::PathBuf, time::Duration};
+
+    use bytes::Bytes;
+
+    use crate::media::{
+        format::{FormatParameters, RTPMap},
+        frame::Frame,
+        record::{self, Config, ContainerFormat, Recorder},
+    };
+
+    /// Returns an empty directory for a test to record into.
+    fn test_directory(name: &str) -> PathBuf {
+        let directory =
+            std::env::temp_dir().join(format!("rtsp-record-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    /// Returns a recorder with an H.264 stream whose parameter sets are sent in-band.
+    fn h264_recorder(directory: &PathBuf, config: Config) -> Recorder {
+        let mut recorder = Recorder::with_config(directory.clone(), "camera", config);
+        let mut parameters = FormatParameters::new(96);
+        parameters.insert("packetization-mode", "1");
+        recorder
+            .add_track(&RTPMap::new(96, "H264", 90000, None), Some(&parameters))
+            .unwrap();
+        recorder
+    }
+
+    /// Returns an H.264 frame, carrying parameter sets if it is a keyframe.
+    fn h264_frame(timestamp: u32, keyframe: bool) -> Frame {
+        let mut data = Vec::new();
+
+        if keyframe {
+            data.extend_from_slice(&[0, 0, 0, 1, 0x67, 0x42, 0x00, 0x1E, 0xAB]);
+            data.extend_from_slice(&[0, 0, 0, 1, 0x68, 0xCE, 0x3C, 0x80]);
+            data.extend_from_slice(&[0, 0, 0, 1, 0x65, 0x88, 0x84]);
+        } else {
+            data.extend_from_slice(&[0, 0, 0, 1, 0x41, 0x9A, 0x02]);
+        }
+
+        Frame::new(Bytes::from(data), timestamp, keyframe)
+    }
+
+    /// Returns the times and durations of the samples of a fragmented MP4 file.
+    fn sample_times(data: &[u8]) -> (Vec<u64>, Vec<u32>) {
+        let read_u32 = |offset: usize| {
+            u32::from_be_bytes([
+                data[offset],
+                data[offset + 1],
+                data[offset + 2],
+                data[offset + 3],
+            ])
+        };
+        let mut times = Vec::new();
+        let mut durations = Vec::new();
+        let mut offset = 0;
+
+        while offset < data.len() {
+            let size = read_u32(offset) as usize;
+
+            if &data[offset + 4..offset + 8] == b"moof" {
+                // The fragments written hold a single track, so the `"traf"` box follows the
+                // `"mfhd"` box and contains `"tfhd"`, `"tfdt"` and `"trun"` in that order.
+                let traf = offset + 8 + 16;
+                let tfdt = traf + 8 + 16;
+                let mut time =
+                    u64::from(read_u32(tfdt + 12)) << 32 | u64::from(read_u32(tfdt + 16));
+                let trun = tfdt + 20;
+                let count = read_u32(trun + 12) as usize;
+
+                for index in 0..count {
+                    let duration = read_u32(trun + 20 + index * 12);
+                    times.push(time);
+                    durations.push(duration);
+                    time += u64::from(duration);
+                }
+            }
+
+            offset += size;
+        }
+
+        (times, durations)
+    }
+
+    #[test]
+    fn test_recorder_segments_across_wraparound() {
+        let directory = test_directory("segments");
+        let config = Config::builder()
+            .with_format(ContainerFormat::MP4)
+            .with_segment_duration(Some(Duration::from_secs(2)))
+            .build();
+        let mut recorder = h264_recorder(&directory, config);
+
+        // One second GOPs of 10 frames, starting just before the RTP timestamp wraps around.
+        let start = u32::MAX - 45000;
+
+        for index in 0..50u32 {
+            let timestamp = start.wrapping_add(index * 9000);
+            recorder
+                .push(0, h264_frame(timestamp, index % 10 == 0))
+                .unwrap();
+        }
+
+        recorder.finish().unwrap();
+
+        let segments = recorder.segments().to_vec();
+        assert_eq!(segments.len(), 3);
+        assert!(segments[0].ends_with("camera-00000.mp4"));
+
+        // Every segment starts at zero and holds 20 frames of 9000 ticks, except the last.
+        for (index, segment) in segments.iter().enumerate() {
+            let data = fs::read(segment).unwrap();
+            let (times, durations) = sample_times(&data);
+            let frames = if index == 2 { 10 } else { 20 };
+            assert_eq!(
+                times,
+                (0..frames).map(|index| index * 9000).collect::<Vec<_>>()
+            );
+            assert!(durations.iter().all(|&duration| duration == 9000));
+        }
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_recover() {
+        for &format in &[ContainerFormat::Matroska, ContainerFormat::MP4] {
+            let directory = test_directory(format.extension());
+            let config = Config::builder().with_format(format).build();
+            let mut recorder = h264_recorder(&directory, config);
+
+            for index in 0..30u32 {
+                recorder
+                    .push(0, h264_frame(index * 9000, index % 10 == 0))
+                    .unwrap();
+            }
+
+            recorder.finish().unwrap();
+
+            let path = recorder.segments()[0].clone();
+            let complete = fs::read(&path).unwrap();
+
+            // A complete recording is left untouched.
+            assert_eq!(record::recover(&path).unwrap(), complete.len() as u64);
+
+            // Cutting the last fragment short makes recovery drop it entirely.
+            fs::write(&path, &complete[..complete.len() - 5]).unwrap();
+            let length = record::recover(&path).unwrap() as usize;
+            assert!(length < complete.len() - 5);
+            assert_eq!(fs::read(&path).unwrap(), &complete[..length]);
+
+            // Recovery is stable.
+            assert_eq!(record::recover(&path).unwrap() as usize, length);
+
+            fs::remove_dir_all(&directory).unwrap();
+        }
+    }
+}
@@ -0,0 +1,369 @@
+//! Fragmented MP4
+//!
+//! Writes recordings as fragmented MP4 files as described by ISO/IEC 14496-12. The header consists
+//! of the `"ftyp"` and `"moov"` boxes, with sample tables left empty, and each fragment is a
+//! `"moof"` box followed by the `"mdat"` box holding its samples.
+//!
+//! Video is written with the `"avc3"` and `"hev1"` sample entries, which allow parameter sets to
+//! change in-band as some cameras do.
+
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+};
+
+use crate::media::record::{Codec, RecordError, Sample, TrackInfo};
+
+/// The sample flags of keyframes, which do not depend on other samples.
+const KEYFRAME_SAMPLE_FLAGS: u32 = 0x0200_0000;
+
+/// The sample flags of other frames, which depend on other samples and are not sync samples.
+const NON_KEYFRAME_SAMPLE_FLAGS: u32 = 0x0101_0000;
+
+/// The identity transformation matrix used by the movie and track headers.
+const UNITY_MATRIX: [u32; 9] = [0x0001_0000, 0, 0, 0, 0x0001_0000, 0, 0, 0, 0x4000_0000];
+
+/// Writes the `"ftyp"` and `"moov"` boxes describing the given streams.
+pub(crate) fn write_header(tracks: &[TrackInfo], buffer: &mut Vec<u8>) {
+    write_box(buffer, b"ftyp", |buffer| {
+        buffer.extend_from_slice(b"iso5");
+        buffer.extend_from_slice(&512u32.to_be_bytes());
+        buffer.extend_from_slice(b"iso5iso6mp41");
+    });
+
+    write_box(buffer, b"moov", |buffer| {
+        write_full_box(buffer, b"mvhd", 0, 0, |buffer| {
+            // The creation time, modification time, timescale and duration.
+            write_u32s(buffer, &[0, 0, 1000, 0]);
+
+            // The preferred rate and volume, followed by reserved fields.
+            write_u32s(buffer, &[0x0001_0000]);
+            buffer.extend_from_slice(&0x0100u16.to_be_bytes());
+            buffer.extend_from_slice(&[0; 10]);
+            write_u32s(buffer, &UNITY_MATRIX);
+            write_u32s(buffer, &[0; 6]);
+            write_u32s(buffer, &[tracks.len() as u32 + 1]);
+        });
+
+        for track in tracks {
+            write_track(buffer, track);
+        }
+
+        write_box(buffer, b"mvex", |buffer| {
+            for track in tracks {
+                write_full_box(buffer, b"trex", 0, 0, |buffer| {
+                    write_u32s(buffer, &[track.id, 1, 0, 0, 0]);
+                });
+            }
+        });
+    });
+}
+
+/// Writes a `"moof"` box and its `"mdat"` box holding the given samples of each stream.
+pub(crate) fn write_fragment(
+    tracks: &[TrackInfo],
+    fragment: &[Vec<Sample>],
+    sequence_number: u32,
+    buffer: &mut Vec<u8>,
+) {
+    let start = buffer.len();
+    let mut data_offset_positions = Vec::new();
+
+    write_box(buffer, b"moof", |buffer| {
+        write_full_box(buffer, b"mfhd", 0, 0, |buffer| {
+            write_u32s(buffer, &[sequence_number]);
+        });
+
+        for (track, samples) in tracks.iter().zip(fragment) {
+            let first = match samples.first() {
+                Some(first) => first,
+                None => continue,
+            };
+
+            write_box(buffer, b"traf", |buffer| {
+                // Data offsets are relative to the start of the `"moof"` box.
+                write_full_box(buffer, b"tfhd", 0, 0x02_0000, |buffer| {
+                    write_u32s(buffer, &[track.id]);
+                });
+                write_full_box(buffer, b"tfdt", 1, 0, |buffer| {
+                    buffer.extend_from_slice(&first.time.to_be_bytes());
+                });
+
+                // The data offset, sample duration, sample size and sample flags are present.
+                write_full_box(buffer, b"trun", 0, 0x0701, |buffer| {
+                    write_u32s(buffer, &[samples.len() as u32]);
+                    data_offset_positions.push(buffer.len());
+                    write_u32s(buffer, &[0]);
+
+                    for sample in samples {
+                        let flags = if sample.keyframe {
+                            KEYFRAME_SAMPLE_FLAGS
+                        } else {
+                            NON_KEYFRAME_SAMPLE_FLAGS
+                        };
+                        write_u32s(buffer, &[sample.duration, sample.data.len() as u32, flags]);
+                    }
+                });
+            });
+        }
+    });
+
+    // The data of each stream follows the `"mdat"` box header.
+    let mut data_offset = (buffer.len() - start + 8) as u32;
+
+    for (position, samples) in data_offset_positions
+        .into_iter()
+        .zip(fragment.iter().filter(|samples| !samples.is_empty()))
+    {
+        buffer[position..position + 4].copy_from_slice(&data_offset.to_be_bytes());
+        data_offset += samples
+            .iter()
+            .map(|sample| sample.data.len() as u32)
+            .sum::<u32>();
+    }
+
+    write_box(buffer, b"mdat", |buffer| {
+        for sample in fragment.iter().flatten() {
+            buffer.extend_from_slice(&sample.data);
+        }
+    });
+}
+
+/// Returns the length of the given fragmented MP4 file without a partially written fragment at its
+/// end.
+///
+/// Only complete `"moof"` and `"mdat"` pairs following the `"moov"` box are kept.
+pub(crate) fn recoverable_length(file: &mut File) -> Result<u64, RecordError> {
+    let length = file.seek(SeekFrom::End(0))?;
+    let mut offset = 0;
+    let mut recoverable_length = None;
+    let mut pending_moof = false;
+
+    while let Some((kind, size)) = read_box_header(file, offset, length)? {
+        offset += size;
+
+        match &kind {
+            b"moov" => recoverable_length = Some(offset),
+            b"moof" => pending_moof = true,
+            b"mdat" if pending_moof => {
+                pending_moof = false;
+                recoverable_length = recoverable_length.map(|_| offset);
+            }
+            _ if !pending_moof => {
+                recoverable_length = recoverable_length.map(|_| offset);
+            }
+            _ => {}
+        }
+    }
+
+    recoverable_length.ok_or(RecordError::InvalidData)
+}
+
+/// Reads the type and total size of the box at the given offset, if the whole box lies within the
+/// file.
+fn read_box_header(
+    file: &mut File,
+    offset: u64,
+    length: u64,
+) -> Result<Option<([u8; 4], u64)>, RecordError> {
+    if length < offset + 8 {
+        return Ok(None);
+    }
+
+    let mut header = [0; 16];
+    let header_length = if length >= offset + 16 { 16 } else { 8 };
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(&mut header[..header_length])?;
+
+    let mut kind = [0; 4];
+    kind.copy_from_slice(&header[4..8]);
+    let size = match u32::from_be_bytes([header[0], header[1], header[2], header[3]]) {
+        // A size of zero extends the box to the end of the file, which is never done for complete
+        // boxes here.
+        0 => return Ok(None),
+        1 if header_length == 16 => {
+            let mut size = [0; 8];
+            size.copy_from_slice(&header[8..16]);
+            u64::from_be_bytes(size)
+        }
+        size if size >= 8 => u64::from(size),
+        _ => return Ok(None),
+    };
+
+    if length - offset < size {
+        Ok(None)
+    } else {
+        Ok(Some((kind, size)))
+    }
+}
+
+/// Writes a box of the given type whose contents are written by the given function.
+fn write_box<TContent>(buffer: &mut Vec<u8>, kind: &[u8; 4], content: TContent)
+where
+    TContent: FnOnce(&mut Vec<u8>),
+{
+    let start = buffer.len();
+    buffer.extend_from_slice(&[0; 4]);
+    buffer.extend_from_slice(kind);
+    content(buffer);
+
+    let size = (buffer.len() - start) as u32;
+    buffer[start..start + 4].copy_from_slice(&size.to_be_bytes());
+}
+
+/// Writes a full box of the given type, version and flags whose contents are written by the given
+/// function.
+fn write_full_box<TContent>(
+    buffer: &mut Vec<u8>,
+    kind: &[u8; 4],
+    version: u8,
+    flags: u32,
+    content: TContent,
+) where
+    TContent: FnOnce(&mut Vec<u8>),
+{
+    write_box(buffer, kind, |buffer| {
+        write_u32s(buffer, &[u32::from(version) << 24 | flags]);
+        content(buffer);
+    });
+}
+
+/// Writes the `"stsd"` box holding the sample entry of the given stream.
+fn write_sample_description(buffer: &mut Vec<u8>, track: &TrackInfo) {
+    write_full_box(buffer, b"stsd", 0, 0, |buffer| {
+        write_u32s(buffer, &[1]);
+
+        let kind = match track.codec {
+            Codec::AAC => b"mp4a",
+            Codec::H264 => b"avc3",
+            Codec::H265 => b"hev1",
+        };
+
+        write_box(buffer, kind, |buffer| {
+            // Reserved fields followed by the data reference index.
+            buffer.extend_from_slice(&[0; 6]);
+            buffer.extend_from_slice(&1u16.to_be_bytes());
+
+            if track.codec.is_video() {
+                // The dimensions are left unknown, decoders take them from the parameter sets.
+                buffer.extend_from_slice(&[0; 16]);
+                buffer.extend_from_slice(&[0; 4]);
+                write_u32s(buffer, &[0x0048_0000, 0x0048_0000, 0]);
+                buffer.extend_from_slice(&1u16.to_be_bytes());
+                buffer.extend_from_slice(&[0; 32]);
+                buffer.extend_from_slice(&[0x00, 0x18, 0xFF, 0xFF]);
+
+                let kind = if track.codec == Codec::H264 {
+                    b"avcC"
+                } else {
+                    b"hvcC"
+                };
+                write_box(buffer, kind, |buffer| {
+                    buffer.extend_from_slice(&track.decoder_configuration);
+                });
+            } else {
+                buffer.extend_from_slice(&[0; 8]);
+                buffer.extend_from_slice(&track.channels.to_be_bytes());
+                buffer.extend_from_slice(&16u16.to_be_bytes());
+                buffer.extend_from_slice(&[0; 4]);
+                write_u32s(buffer, &[track.sampling_frequency.min(0xFFFF) << 16]);
+                write_elementary_stream_descriptor(buffer, track);
+            }
+        });
+    });
+}
+
+/// Writes the `"esds"` box of an AAC stream as described by ISO/IEC 14496-1, Section 7.2.6.5.
+fn write_elementary_stream_descriptor(buffer: &mut Vec<u8>, track: &TrackInfo) {
+    let configuration = &track.decoder_configuration;
+    let decoder_specific_length = configuration.len() as u8;
+    let decoder_config_length = 13 + 2 + decoder_specific_length;
+    let elementary_stream_length = 3 + 2 + decoder_config_length + 3;
+
+    write_full_box(buffer, b"esds", 0, 0, |buffer| {
+        // The ES descriptor with an ES identifier of zero and no flags.
+        buffer.extend_from_slice(&[0x03, elementary_stream_length, 0, 0, 0]);
+
+        // The decoder configuration descriptor for MPEG-4 audio in an audio stream.
+        buffer.extend_from_slice(&[0x04, decoder_config_length, 0x40, 0x15, 0, 0, 0]);
+        write_u32s(buffer, &[0, 0]);
+        buffer.extend_from_slice(&[0x05, decoder_specific_length]);
+        buffer.extend_from_slice(configuration);
+
+        // The SL configuration descriptor predefined for MP4 files.
+        buffer.extend_from_slice(&[0x06, 0x01, 0x02]);
+    });
+}
+
+/// Writes the `"trak"` box describing the given stream.
+fn write_track(buffer: &mut Vec<u8>, track: &TrackInfo) {
+    let video = track.codec.is_video();
+
+    write_box(buffer, b"trak", |buffer| {
+        // The track is enabled and used in the presentation.
+        write_full_box(buffer, b"tkhd", 0, 3, |buffer| {
+            write_u32s(buffer, &[0, 0, track.id, 0, 0, 0, 0, 0]);
+            let volume: u16 = if video { 0 } else { 0x0100 };
+            buffer.extend_from_slice(&volume.to_be_bytes());
+            buffer.extend_from_slice(&[0; 2]);
+            write_u32s(buffer, &UNITY_MATRIX);
+            write_u32s(buffer, &[0, 0]);
+        });
+
+        write_box(buffer, b"mdia", |buffer| {
+            write_full_box(buffer, b"mdhd", 0, 0, |buffer| {
+                write_u32s(buffer, &[0, 0, track.clock_rate, 0]);
+
+                // The undetermined language code.
+                buffer.extend_from_slice(&[0x55, 0xC4, 0, 0]);
+            });
+
+            write_full_box(buffer, b"hdlr", 0, 0, |buffer| {
+                write_u32s(buffer, &[0]);
+                buffer.extend_from_slice(if video { b"vide" } else { b"soun" });
+                write_u32s(buffer, &[0, 0, 0]);
+                buffer.extend_from_slice(if video {
+                    b"VideoHandler\0"
+                } else {
+                    b"SoundHandler\0"
+                });
+            });
+
+            write_box(buffer, b"minf", |buffer| {
+                if video {
+                    write_full_box(buffer, b"vmhd", 0, 1, |buffer| {
+                        buffer.extend_from_slice(&[0; 8]);
+                    });
+                } else {
+                    write_full_box(buffer, b"smhd", 0, 0, |buffer| {
+                        buffer.extend_from_slice(&[0; 4]);
+                    });
+                }
+
+                write_box(buffer, b"dinf", |buffer| {
+                    write_full_box(buffer, b"dref", 0, 0, |buffer| {
+                        write_u32s(buffer, &[1]);
+
+                        // The media data is in the same file.
+                        write_full_box(buffer, b"url ", 0, 1, |_| {});
+                    });
+                });
+
+                write_box(buffer, b"stbl", |buffer| {
+                    write_sample_description(buffer, track);
+                    write_full_box(buffer, b"stts", 0, 0, |buffer| write_u32s(buffer, &[0]));
+                    write_full_box(buffer, b"stsc", 0, 0, |buffer| write_u32s(buffer, &[0]));
+                    write_full_box(buffer, b"stsz", 0, 0, |buffer| write_u32s(buffer, &[0, 0]));
+                    write_full_box(buffer, b"stco", 0, 0, |buffer| write_u32s(buffer, &[0]));
+                });
+            });
+        });
+    });
+}
+
+/// Writes the given values in big-endian order.
+fn write_u32s(buffer: &mut Vec<u8>, values: &[u32]) {
+    for value in values {
+        buffer.extend_from_slice(&value.to_be_bytes());
+    }
+}
@@ -0,0 +1,360 @@
+//! RTP Packets
+//!
+//! This module contains a minimal representation of RTP packets as described by
+//! [RFC3550](https://tools.ietf.org/html/rfc3550#section-5.1), sufficient for reordering and
+//! (de)packetizing media. Header extensions are preserved but not interpreted.
+
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+};
+
+use bytes::{BufMut, Bytes, BytesMut};
+
+/// The size of the fixed part of the RTP header.
+pub const RTP_FIXED_HEADER_SIZE: usize = 12;
+
+/// The only RTP version supported.
+pub const RTP_VERSION: u8 = 2;
+
+/// An RTP packet.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Packet {
+    /// The contributing sources of the packet.
+    csrcs: Vec<u32>,
+
+    /// The header extension of the packet as the profile-defined identifier and the raw extension
+    /// data.
+    extension: Option<(u16, Bytes)>,
+
+    /// Whether the marker bit is set. For video, this marks the last packet of an access unit.
+    marker: bool,
+
+    /// The payload of the packet with any padding removed.
+    payload: Bytes,
+
+    /// The payload type of the packet.
+    payload_type: u8,
+
+    /// The sequence number of the packet.
+    sequence_number: u16,
+
+    /// The synchronization source of the packet.
+    ssrc: u32,
+
+    /// The RTP timestamp of the packet.
+    timestamp: u32,
+}
+
+impl Packet {
+    /// Returns the contributing sources of the packet.
+    pub fn csrcs(&self) -> &[u32] {
+        &self.csrcs
+    }
+
+    /// Returns a mutable reference to the contributing sources of the packet.
+    pub fn csrcs_mut(&mut self) -> &mut Vec<u32> {
+        &mut self.csrcs
+    }
+
+    /// Decodes a packet from the given buffer. The buffer must contain exactly one packet.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rtsp::media::rtp::Packet;
+    ///
+    /// let buffer = [0x80, 0xE0, 0x00, 0x01, 0x00, 0x00, 0x00, 0x10, 0xDE, 0xAD, 0xBE, 0xEF, 0x01];
+    /// let packet = Packet::decode(&buffer[..]).unwrap();
+    /// assert!(packet.marker());
+    /// assert_eq!(packet.payload_type(), 96);
+    /// assert_eq!(packet.sequence_number(), 1);
+    /// assert_eq!(packet.timestamp(), 16);
+    /// assert_eq!(packet.ssrc(), 0xDEAD_BEEF);
+    /// assert_eq!(&packet.payload()[..], &[0x01]);
+    /// ```
+    pub fn decode<TBuffer>(buffer: TBuffer) -> Result<Self, PacketError>
+    where
+        TBuffer: Into<Bytes>,
+    {
+        let buffer = buffer.into();
+
+        if buffer.len() < RTP_FIXED_HEADER_SIZE {
+            return Err(PacketError::Truncated);
+        }
+
+        if buffer[0] >> 6 != RTP_VERSION {
+            return Err(PacketError::UnsupportedVersion);
+        }
+
+        let has_padding = buffer[0] & 0x20 != 0;
+        let has_extension = buffer[0] & 0x10 != 0;
+        let csrc_count = (buffer[0] & 0x0F) as usize;
+        let mut offset = RTP_FIXED_HEADER_SIZE + csrc_count * 4;
+
+        if buffer.len() < offset {
+            return Err(PacketError::Truncated);
+        }
+
+        let csrcs = (0..csrc_count)
+            .map(|index| read_u32(&buffer[RTP_FIXED_HEADER_SIZE + index * 4..]))
+            .collect();
+
+        let extension = if has_extension {
+            if buffer.len() < offset + 4 {
+                return Err(PacketError::Truncated);
+            }
+
+            let profile = read_u16(&buffer[offset..]);
+            let length = read_u16(&buffer[offset + 2..]) as usize * 4;
+            offset += 4;
+
+            if buffer.len() < offset + length {
+                return Err(PacketError::Truncated);
+            }
+
+            let data = buffer.slice(offset, offset + length);
+            offset += length;
+            Some((profile, data))
+        } else {
+            None
+        };
+
+        let mut end = buffer.len();
+
+        if has_padding {
+            let padding = buffer[end - 1] as usize;
+
+            if padding == 0 || end - offset < padding {
+                return Err(PacketError::InvalidPadding);
+            }
+
+            end -= padding;
+        }
+
+        Ok(Packet {
+            csrcs,
+            extension,
+            marker: buffer[1] & 0x80 != 0,
+            payload: buffer.slice(offset, end),
+            payload_type: buffer[1] & 0x7F,
+            sequence_number: read_u16(&buffer[2..]),
+            ssrc: read_u32(&buffer[8..]),
+            timestamp: read_u32(&buffer[4..]),
+        })
+    }
+
+    /// Encodes the packet into the given buffer. Padding is never added.
+    pub fn encode(&self, buffer: &mut BytesMut) {
+        buffer.reserve(self.encoded_len());
+
+        let mut first_byte = (RTP_VERSION << 6) | (self.csrcs.len() as u8 & 0x0F);
+
+        if self.extension.is_some() {
+            first_byte |= 0x10;
+        }
+
+        buffer.put_u8(first_byte);
+        buffer.put_u8(((self.marker as u8) << 7) | (self.payload_type & 0x7F));
+        buffer.put_u16_be(self.sequence_number);
+        buffer.put_u32_be(self.timestamp);
+        buffer.put_u32_be(self.ssrc);
+
+        for &csrc in self.csrcs.iter().take(15) {
+            buffer.put_u32_be(csrc);
+        }
+
+        if let Some((profile, data)) = self.extension.as_ref() {
+            buffer.put_u16_be(*profile);
+            buffer.put_u16_be(((data.len() + 3) / 4) as u16);
+            buffer.put_slice(data);
+
+            for _ in data.len()..(data.len() + 3) / 4 * 4 {
+                buffer.put_u8(0);
+            }
+        }
+
+        buffer.put_slice(&self.payload);
+    }
+
+    /// Returns the number of bytes the packet occupies when encoded.
+    pub fn encoded_len(&self) -> usize {
+        let extension_len = self
+            .extension
+            .as_ref()
+            .map(|(_, data)| 4 + (data.len() + 3) / 4 * 4)
+            .unwrap_or(0);
+
+        RTP_FIXED_HEADER_SIZE + self.csrcs.len().min(15) * 4 + extension_len + self.payload.len()
+    }
+
+    /// Returns the header extension of the packet.
+    pub fn extension(&self) -> Option<&(u16, Bytes)> {
+        self.extension.as_ref()
+    }
+
+    /// Returns a mutable reference to the header extension of the packet.
+    pub fn extension_mut(&mut self) -> &mut Option<(u16, Bytes)> {
+        &mut self.extension
+    }
+
+    /// Returns whether the marker bit is set.
+    pub fn marker(&self) -> bool {
+        self.marker
+    }
+
+    /// Returns a mutable reference to the marker bit.
+    pub fn marker_mut(&mut self) -> &mut bool {
+        &mut self.marker
+    }
+
+    /// Constructs a new packet without contributing sources or a header extension.
+    pub fn new<TPayload>(
+        payload_type: u8,
+        sequence_number: u16,
+        timestamp: u32,
+        ssrc: u32,
+        marker: bool,
+        payload: TPayload,
+    ) -> Self
+    where
+        TPayload: Into<Bytes>,
+    {
+        Packet {
+            csrcs: Vec::new(),
+            extension: None,
+            marker,
+            payload: payload.into(),
+            payload_type: payload_type & 0x7F,
+            sequence_number,
+            ssrc,
+            timestamp,
+        }
+    }
+
+    /// Returns the payload of the packet.
+    pub fn payload(&self) -> &Bytes {
+        &self.payload
+    }
+
+    /// Returns a mutable reference to the payload of the packet.
+    pub fn payload_mut(&mut self) -> &mut Bytes {
+        &mut self.payload
+    }
+
+    /// Returns the payload type of the packet.
+    pub fn payload_type(&self) -> u8 {
+        self.payload_type
+    }
+
+    /// Returns the sequence number of the packet.
+    pub fn sequence_number(&self) -> u16 {
+        self.sequence_number
+    }
+
+    /// Returns a mutable reference to the sequence number of the packet.
+    pub fn sequence_number_mut(&mut self) -> &mut u16 {
+        &mut self.sequence_number
+    }
+
+    /// Returns the synchronization source of the packet.
+    pub fn ssrc(&self) -> u32 {
+        self.ssrc
+    }
+
+    /// Returns a mutable reference to the synchronization source of the packet.
+    pub fn ssrc_mut(&mut self) -> &mut u32 {
+        &mut self.ssrc
+    }
+
+    /// Returns the RTP timestamp of the packet.
+    pub fn timestamp(&self) -> u32 {
+        self.timestamp
+    }
+
+    /// Returns a mutable reference to the RTP timestamp of the packet.
+    pub fn timestamp_mut(&mut self) -> &mut u32 {
+        &mut self.timestamp
+    }
+}
+
+/// A possible error value when decoding an RTP packet.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum PacketError {
+    /// The padding length was zero or exceeded the payload.
+    InvalidPadding,
+
+    /// The buffer was shorter than what the header requires.
+    Truncated,
+
+    /// The version field was not `2`.
+    UnsupportedVersion,
+}
+
+impl Display for PacketError {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        use self::PacketError::*;
+
+        match self {
+            InvalidPadding => write!(formatter, "invalid RTP padding"),
+            Truncated => write!(formatter, "truncated RTP packet"),
+            UnsupportedVersion => write!(formatter, "unsupported RTP version"),
+        }
+    }
+}
+
+impl Error for PacketError {}
+
+/// Reads a big-endian `u16` from the start of the buffer.
+pub(crate) fn read_u16(buffer: &[u8]) -> u16 {
+    (u16::from(buffer[0]) << 8) | u16::from(buffer[1])
+}
+
+/// Reads a big-endian `u32` from the start of the buffer.
+pub(crate) fn read_u32(buffer: &[u8]) -> u32 {
+    (u32::from(read_u16(buffer)) << 16) | u32::from(read_u16(&buffer[2..]))
+}
+
+#[cfg(test)]
+mod test {
+    use bytes::BytesMut;
+
+    use crate::media::rtp::{Packet, PacketError};
+
+    #[test]
+    fn test_packet_round_trip() {
+        let mut packet = Packet::new(97, 65535, 0xFFFF_FFFF, 7, false, &b"payload"[..]);
+        packet.csrcs_mut().push(42);
+        *packet.extension_mut() = Some((0xBEDE, (&b"ext"[..]).into()));
+
+        let mut buffer = BytesMut::new();
+        packet.encode(&mut buffer);
+        assert_eq!(buffer.len(), packet.encoded_len());
+
+        let mut decoded = Packet::decode(buffer.freeze()).unwrap();
+        let (profile, data) = decoded.extension_mut().take().unwrap();
+        assert_eq!(profile, 0xBEDE);
+        assert_eq!(&data[..], b"ext\0");
+
+        *packet.extension_mut() = None;
+        assert_eq!(decoded, packet);
+    }
+
+    #[test]
+    fn test_packet_decode_padding() {
+        let buffer = [
+            0xA0, 0x60, 0x00, 0x01, 0x00, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x01, 0xAA, 0x00,
+            0x02,
+        ];
+        assert_eq!(&Packet::decode(&buffer[..]).unwrap().payload()[..], &[0xAA]);
+
+        let buffer = [
+            0xA0, 0x60, 0x00, 0x01, 0x00, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x01, 0x09,
+        ];
+        assert_eq!(
+            Packet::decode(&buffer[..]),
+            Err(PacketError::InvalidPadding)
+        );
+    }
+}
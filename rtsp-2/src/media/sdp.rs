@@ -0,0 +1,449 @@
+//! Session Descriptions
+//!
+//! Generates the [SDP](https://tools.ietf.org/html/rfc4566) session descriptions returned in
+//! response to DESCRIBE requests, following the conventions of
+//! [RFC7826](https://tools.ietf.org/html/rfc7826#appendix-D).
+
+use std::{
+    convert::TryFrom,
+    error::Error,
+    fmt::{self, Display, Formatter},
+    time::Duration,
+};
+
+use rand::random;
+
+use crate::{
+    header::types::range::{NPTTime, Range},
+    media::format::{FormatParameters, RTPMap},
+};
+
+/// The MIME type of session descriptions.
+pub const SDP_CONTENT_TYPE: &str = "application/sdp";
+
+/// A description of a presentation and its media streams.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SessionDescription {
+    /// The duration of the presentation, or [`Option::None`] for live presentations.
+    duration: Option<Duration>,
+
+    /// The descriptions of the media streams.
+    media: Vec<MediaDescription>,
+
+    /// The name of the session.
+    name: String,
+
+    /// The session identifier used in the origin line.
+    session_id: u32,
+}
+
+impl SessionDescription {
+    /// Adds the description of a media stream.
+    pub fn add_media(&mut self, media: MediaDescription) -> &mut Self {
+        self.media.push(media);
+        self
+    }
+
+    /// Returns the duration of the presentation, or [`Option::None`] for live presentations.
+    pub fn duration(&self) -> Option<Duration> {
+        self.duration
+    }
+
+    /// Returns the descriptions of the media streams.
+    pub fn media(&self) -> &[MediaDescription] {
+        &self.media
+    }
+
+    /// Returns the name of the session.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Constructs a new description of a live session with the given name and no media streams.
+    pub fn new<TName>(name: TName) -> Self
+    where
+        TName: Into<String>,
+    {
+        SessionDescription {
+            duration: None,
+            media: Vec::new(),
+            name: name.into(),
+            session_id: random(),
+        }
+    }
+
+    /// Returns the range of the presentation as given by the `"a=range"` attribute.
+    pub fn range(&self) -> Range {
+        match self.duration {
+            Some(duration) => Range::new(
+                NPTTime::Time(Duration::from_secs(0)),
+                Some(NPTTime::Time(duration)),
+            ),
+            None => Range::new(NPTTime::Now, None),
+        }
+    }
+
+    /// Sets the duration of the presentation, or [`Option::None`] for live presentations.
+    pub fn set_duration(&mut self, duration: Option<Duration>) -> &mut Self {
+        self.duration = duration;
+        self
+    }
+
+    /// Adds the description of a media stream.
+    pub fn with_media(mut self, media: MediaDescription) -> Self {
+        self.add_media(media);
+        self
+    }
+}
+
+impl Display for SessionDescription {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        let name = if self.name.is_empty() {
+            "-"
+        } else {
+            &self.name
+        };
+
+        write!(formatter, "v=0\r\n")?;
+        write!(formatter, "o=- {} 1 IN IP4 0.0.0.0\r\n", self.session_id)?;
+        write!(formatter, "s={}\r\n", name)?;
+        write!(formatter, "t=0 0\r\n")?;
+        write!(formatter, "a=control:*\r\n")?;
+        write!(formatter, "a=range:{}\r\n", self.range())?;
+
+        for media in &self.media {
+            media.fmt(formatter)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<'description> TryFrom<&'description str> for SessionDescription {
+    type Error = SessionDescriptionError;
+
+    /// Parses a session description, such as the body of a DESCRIBE response.
+    ///
+    /// Only what is needed to set up and receive the media streams is kept: the session name and
+    /// range, along with the format and control path of each media description. Media
+    /// descriptions use their first payload format, which must either be described by an
+    /// `"a=rtpmap"` attribute or be one of the common static payload types.
+    fn try_from(value: &'description str) -> Result<Self, Self::Error> {
+        let mut description = SessionDescription::new("");
+        let mut media: Option<PartialMediaDescription> = None;
+
+        for line in value.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            let mut parts = line.splitn(2, '=');
+            let (kind, value) = match (parts.next(), parts.next()) {
+                (Some(kind), Some(value)) if kind.len() == 1 => (kind, value.trim()),
+                _ => return Err(SessionDescriptionError::InvalidLine),
+            };
+
+            if kind == "m" {
+                if let Some(media) = media.take() {
+                    description.add_media(media.finish()?);
+                }
+
+                let payload_type = value
+                    .split_whitespace()
+                    .nth(3)
+                    .and_then(|payload_type| payload_type.parse::<u8>().ok())
+                    .filter(|&payload_type| payload_type <= 127)
+                    .ok_or(SessionDescriptionError::InvalidMedia)?;
+                media = Some(PartialMediaDescription {
+                    control: None,
+                    format: None,
+                    format_parameters: None,
+                    payload_type,
+                });
+                continue;
+            }
+
+            match (kind, media.as_mut()) {
+                ("s", None) if value != "-" => description.name = value.to_string(),
+                ("a", None) => {
+                    if let Some(range) = value.strip_prefix("range:") {
+                        description.duration = match Range::try_from(range) {
+                            Ok(range) => match (range.start(), range.end()) {
+                                (Some(NPTTime::Time(start)), Some(NPTTime::Time(end))) => {
+                                    end.checked_sub(start)
+                                }
+                                _ => None,
+                            },
+                            Err(_) => None,
+                        };
+                    }
+                }
+                ("a", Some(media)) => media.parse_attribute(value)?,
+                _ => (),
+            }
+        }
+
+        if let Some(media) = media.take() {
+            description.add_media(media.finish()?);
+        }
+
+        Ok(description)
+    }
+}
+
+/// A description of a single media stream.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MediaDescription {
+    /// The control path of the stream relative to the presentation.
+    control: String,
+
+    /// The payload format of the stream.
+    format: RTPMap,
+
+    /// The format specific parameters of the stream.
+    format_parameters: Option<FormatParameters>,
+}
+
+impl MediaDescription {
+    /// Returns the control path of the stream relative to the presentation.
+    pub fn control(&self) -> &str {
+        &self.control
+    }
+
+    /// Returns the payload format of the stream.
+    pub fn format(&self) -> &RTPMap {
+        &self.format
+    }
+
+    /// Returns the format specific parameters of the stream.
+    pub fn format_parameters(&self) -> Option<&FormatParameters> {
+        self.format_parameters.as_ref()
+    }
+
+    /// Returns the media type of the stream, which is `"video"` for formats using the 90 kHz
+    /// video clock and `"audio"` otherwise.
+    pub fn media_type(&self) -> &'static str {
+        if self.format.channels().is_none() && self.format.clock_rate() == 90000 {
+            "video"
+        } else {
+            "audio"
+        }
+    }
+
+    /// Constructs a new description of a media stream.
+    pub fn new<TControl>(
+        control: TControl,
+        format: RTPMap,
+        format_parameters: Option<FormatParameters>,
+    ) -> Self
+    where
+        TControl: Into<String>,
+    {
+        MediaDescription {
+            control: control.into(),
+            format,
+            format_parameters,
+        }
+    }
+}
+
+impl Display for MediaDescription {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "m={} 0 RTP/AVP {}\r\n",
+            self.media_type(),
+            self.format.payload_type()
+        )?;
+        write!(formatter, "a=rtpmap:{}\r\n", self.format)?;
+
+        if let Some(format_parameters) = self.format_parameters.as_ref() {
+            write!(formatter, "a=fmtp:{}\r\n", format_parameters)?;
+        }
+
+        write!(formatter, "a=control:{}\r\n", self.control)
+    }
+}
+
+/// A possible error value when parsing a session description.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum SessionDescriptionError {
+    /// An `"a=rtpmap"` or `"a=fmtp"` attribute of a media description was invalid.
+    InvalidFormat,
+
+    /// A line was not of the form `<type>=<value>`.
+    InvalidLine,
+
+    /// A media description did not have a valid payload format.
+    InvalidMedia,
+
+    /// The payload format of a media description was neither described nor a known static
+    /// payload type.
+    UnknownFormat,
+}
+
+impl Display for SessionDescriptionError {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        use self::SessionDescriptionError::*;
+
+        match self {
+            InvalidFormat => write!(formatter, "invalid session description format"),
+            InvalidLine => write!(formatter, "invalid session description line"),
+            InvalidMedia => write!(formatter, "invalid session description media"),
+            UnknownFormat => write!(formatter, "unknown session description format"),
+        }
+    }
+}
+
+impl Error for SessionDescriptionError {}
+
+/// A media description being parsed.
+struct PartialMediaDescription {
+    /// The control path of the stream, if one was given.
+    control: Option<String>,
+
+    /// The payload format given by the `"a=rtpmap"` attribute.
+    format: Option<RTPMap>,
+
+    /// The format specific parameters given by the `"a=fmtp"` attribute.
+    format_parameters: Option<FormatParameters>,
+
+    /// The first payload type of the media description.
+    payload_type: u8,
+}
+
+impl PartialMediaDescription {
+    /// Completes the media description, falling back to the static payload type if no
+    /// `"a=rtpmap"` attribute was given.
+    fn finish(self) -> Result<MediaDescription, SessionDescriptionError> {
+        let format = match self.format {
+            Some(format) => format,
+            None => {
+                static_format(self.payload_type).ok_or(SessionDescriptionError::UnknownFormat)?
+            }
+        };
+
+        Ok(MediaDescription::new(
+            self.control.unwrap_or_default(),
+            format,
+            self.format_parameters,
+        ))
+    }
+
+    /// Parses an attribute of the media description. Formats of other payload types than the
+    /// first are ignored.
+    fn parse_attribute(&mut self, value: &str) -> Result<(), SessionDescriptionError> {
+        if let Some(control) = value.strip_prefix("control:") {
+            self.control = Some(control.trim().to_string());
+        } else if let Some(format) = value.strip_prefix("rtpmap:") {
+            let format =
+                RTPMap::try_from(format).map_err(|_| SessionDescriptionError::InvalidFormat)?;
+
+            if format.payload_type() == self.payload_type {
+                self.format = Some(format);
+            }
+        } else if let Some(parameters) = value.strip_prefix("fmtp:") {
+            let parameters = FormatParameters::try_from(parameters)
+                .map_err(|_| SessionDescriptionError::InvalidFormat)?;
+
+            if parameters.payload_type() == self.payload_type {
+                self.format_parameters = Some(parameters);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the format of the static payload types commonly used by cameras, as assigned by
+/// [RFC3551](https://tools.ietf.org/html/rfc3551#section-6).
+fn static_format(payload_type: u8) -> Option<RTPMap> {
+    match payload_type {
+        0 => Some(RTPMap::new(0, "PCMU", 8000, Some(1))),
+        8 => Some(RTPMap::new(8, "PCMA", 8000, Some(1))),
+        26 => Some(RTPMap::new(26, "JPEG", 90000, None)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{convert::TryFrom, time::Duration};
+
+    use crate::media::{
+        format::{FormatParameters, RTPMap},
+        sdp::{MediaDescription, SessionDescription},
+    };
+
+    #[test]
+    fn test_session_description() {
+        let mut format_parameters = FormatParameters::new(96);
+        format_parameters.insert("packetization-mode", "1");
+        let mut description = SessionDescription::new("clip.h264")
+            .with_media(MediaDescription::new(
+                "trackID=0",
+                RTPMap::new(96, "H264", 90000, None),
+                Some(format_parameters),
+            ))
+            .with_media(MediaDescription::new(
+                "trackID=1",
+                RTPMap::new(97, "MPEG4-GENERIC", 48000, Some(2)),
+                None,
+            ));
+        description.set_duration(Some(Duration::from_millis(12500)));
+
+        let text = description.to_string();
+        let lines = text.split("\r\n").collect::<Vec<_>>();
+        assert_eq!(lines[0], "v=0");
+        assert!(lines[1].starts_with("o=- "));
+        assert_eq!(
+            &lines[2..],
+            &[
+                "s=clip.h264",
+                "t=0 0",
+                "a=control:*",
+                "a=range:npt=0-12.5",
+                "m=video 0 RTP/AVP 96",
+                "a=rtpmap:96 H264/90000",
+                "a=fmtp:96 packetization-mode=1",
+                "a=control:trackID=0",
+                "m=audio 0 RTP/AVP 97",
+                "a=rtpmap:97 MPEG4-GENERIC/48000/2",
+                "a=control:trackID=1",
+                "",
+            ]
+        );
+
+        let parsed = SessionDescription::try_from(text.as_str()).unwrap();
+        assert_eq!(parsed.name(), "clip.h264");
+        assert_eq!(parsed.duration(), Some(Duration::from_millis(12500)));
+        assert_eq!(parsed.media(), description.media());
+    }
+
+    #[test]
+    fn test_session_description_parse_camera() {
+        let text = "v=0\r\n\
+                    o=- 1 1 IN IP4 192.168.1.64\r\n\
+                    s=Session streamed by camera\r\n\
+                    t=0 0\r\n\
+                    a=control:*\r\n\
+                    a=range:npt=now-\r\n\
+                    m=video 0 RTP/AVP 96\r\n\
+                    a=rtpmap:96 H264/90000\r\n\
+                    a=fmtp:96 packetization-mode=1\r\n\
+                    a=control:rtsp://192.168.1.64/Streaming/Channels/101/trackID=1\r\n\
+                    m=audio 0 RTP/AVP 0\r\n\
+                    a=control:trackID=2\r\n";
+        let description = SessionDescription::try_from(text).unwrap();
+        assert_eq!(description.duration(), None);
+        assert_eq!(description.media().len(), 2);
+        assert_eq!(
+            description.media()[0].control(),
+            "rtsp://192.168.1.64/Streaming/Channels/101/trackID=1"
+        );
+        assert_eq!(
+            description.media()[0]
+                .format_parameters()
+                .and_then(|parameters| parameters.get("packetization-mode")),
+            Some("1")
+        );
+        assert_eq!(description.media()[1].format().encoding_name(), "PCMU");
+    }
+}
@@ -0,0 +1,106 @@
+//! Presentation Timeline
+//!
+//! RTP timestamps start at a random offset and wrap around every 2^32 ticks. The [`Timeline`]
+//! anchors them onto the presentation timeline using the `"rtptime"` given in the `"RTP-Info"`
+//! header of a PLAY response together with the start of the played range.
+
+use std::time::Duration;
+
+use crate::header::types::rtp_info::SSRCInfo;
+
+/// Maps the RTP timestamps of a single stream to presentation times.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Timeline {
+    /// The clock rate of the RTP timestamps in Hz.
+    clock_rate: u32,
+
+    /// The extended RTP timestamp of the most recently mapped timestamp. This is used to detect
+    /// wraparound.
+    last_timestamp: i64,
+
+    /// The presentation time corresponding to the anchor timestamp.
+    start_time: Duration,
+
+    /// The extended RTP timestamp that corresponds to the start time.
+    start_timestamp: i64,
+}
+
+impl Timeline {
+    /// Constructs a new timeline anchoring the given RTP timestamp to the given presentation time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the clock rate is zero.
+    pub fn new(clock_rate: u32, rtp_timestamp: u32, start_time: Duration) -> Self {
+        assert!(clock_rate > 0, "clock rate must be non-zero");
+
+        Timeline {
+            clock_rate,
+            last_timestamp: i64::from(rtp_timestamp),
+            start_time,
+            start_timestamp: i64::from(rtp_timestamp),
+        }
+    }
+
+    /// Returns the clock rate of the RTP timestamps in Hz.
+    pub fn clock_rate(&self) -> u32 {
+        self.clock_rate
+    }
+
+    /// Constructs a new timeline from the `"RTP-Info"` information of a stream and the start of the
+    /// played range. [`Option::None`] is returned if the `"rtptime"` parameter was not given.
+    pub fn from_rtp_info(clock_rate: u32, info: &SSRCInfo, start_time: Duration) -> Option<Self> {
+        info.rtp_timestamp()
+            .map(|rtp_timestamp| Timeline::new(clock_rate, rtp_timestamp, start_time))
+    }
+
+    /// Returns the presentation time for the given RTP timestamp.
+    ///
+    /// Timestamps are assumed to be close to the previously mapped timestamp, so wraparound in
+    /// either direction is handled. Timestamps before the start timestamp map to times before the
+    /// start time, and [`Option::None`] is returned if such a time would be negative.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    ///
+    /// use rtsp::media::timeline::Timeline;
+    ///
+    /// let mut timeline = Timeline::new(90000, 4_294_960_000, Duration::from_secs(10));
+    /// assert_eq!(timeline.presentation_time(4_294_960_000), Some(Duration::from_secs(10)));
+    /// assert_eq!(timeline.presentation_time(82_704), Some(Duration::from_secs(11)));
+    /// ```
+    pub fn presentation_time(&mut self, rtp_timestamp: u32) -> Option<Duration> {
+        let delta = i64::from(rtp_timestamp.wrapping_sub(self.last_timestamp as u32) as i32);
+        self.last_timestamp += delta;
+
+        let ticks = self.last_timestamp - self.start_timestamp;
+        let offset = Duration::from_nanos(
+            (ticks.abs() as u128 * 1_000_000_000 / u128::from(self.clock_rate)) as u64,
+        );
+
+        if ticks >= 0 {
+            self.start_time.checked_add(offset)
+        } else {
+            self.start_time.checked_sub(offset)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use crate::media::timeline::Timeline;
+
+    #[test]
+    fn test_timeline_before_start() {
+        let mut timeline = Timeline::new(8000, 16000, Duration::from_secs(1));
+        assert_eq!(
+            timeline.presentation_time(12000),
+            Some(Duration::from_millis(500))
+        );
+        assert_eq!(timeline.presentation_time(0), None);
+    }
+}
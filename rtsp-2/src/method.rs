@@ -22,6 +22,8 @@ use std::{
     str,
 };
 
+use rtsp_1::method::Method as Rtsp1Method;
+
 use crate::syntax;
 
 /// An RTSP request method (as defined in
@@ -192,6 +194,13 @@ impl Display for Method {
     }
 }
 
+/// Bridges the RTSP/1.0 methods that RTSP/2.0 dropped, which are represented as extension methods.
+impl From<Rtsp1Method> for Method {
+    fn from(value: Rtsp1Method) -> Self {
+        Method::Extension(ExtensionMethod(value.as_str().to_string()))
+    }
+}
+
 impl From<Method> for String {
     fn from(value: Method) -> Self {
         value.to_string()
@@ -246,6 +255,18 @@ impl<'method> PartialEq<Method> for &'method str {
     }
 }
 
+impl PartialEq<Rtsp1Method> for Method {
+    fn eq(&self, other: &Rtsp1Method) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl PartialEq<Method> for Rtsp1Method {
+    fn eq(&self, other: &Method) -> bool {
+        other == self
+    }
+}
+
 impl<'method> TryFrom<&'method [u8]> for Method {
     type Error = MethodError;
 
@@ -0,0 +1,79 @@
+//! Metrics
+//!
+//! This module contains the [`Metrics`] trait that connections and servers report into, such as
+//! when a connection is opened or a request is serviced. Applications implement it to export the
+//! measurements to their monitoring system of choice, or use [`PrometheusMetrics`] to expose them
+//! in the Prometheus text format on an HTTP endpoint of their own:
+//!
+//! ```
+//! use std::sync::Arc;
+//!
+//! use rtsp::metrics::PrometheusMetrics;
+//! use rtsp::server::Server;
+//!
+//! let metrics = Arc::new(PrometheusMetrics::new());
+//! let server = Server::new().with_metrics(metrics.clone());
+//!
+//! // Serve the server, and answer scrapes of the HTTP endpoint with `metrics.encode()`.
+//! ```
+//!
+//! All methods of the trait do nothing by default, so implementations only need to implement the
+//! measurements they are interested in.
+
+pub mod prometheus;
+
+use std::fmt::Debug;
+
+pub use self::prometheus::PrometheusMetrics;
+use crate::{
+    method::Method,
+    protocol::codec::{decoder::Deviation, DecodeError},
+    status::StatusCode,
+};
+
+/// A sink for measurements of connections and servers.
+///
+/// Methods are called from the tasks driving connections, so they should return quickly.
+pub trait Metrics: Debug + Send + Sync {
+    /// Called when the given number of bytes were read from the transport of a connection.
+    fn bytes_received(&self, _count: usize) {}
+
+    /// Called when the given number of bytes were written to the transport of a connection.
+    fn bytes_sent(&self, _count: usize) {}
+
+    /// Called when a connection is closed. This is called exactly once for every opened connection.
+    fn connection_closed(&self) {}
+
+    /// Called when a connection is opened.
+    fn connection_opened(&self) {}
+
+    /// Called when a message received on a connection could not be decoded.
+    fn decode_error(&self, _error: DecodeError) {}
+
+    /// Called when a message received on a connection deviated from the syntax in a way that was
+    /// tolerated. This is called once per deviation for every message, so it can be used to find
+    /// noncompliant agents.
+    fn deviation_tolerated(&self, _deviation: Deviation) {}
+
+    /// Called when a request received on a connection was forwarded to its request handler, leaving
+    /// the queue of requests awaiting forwarding. This is also called for every queued request when
+    /// the connection is closed.
+    fn request_dequeued(&self) {}
+
+    /// Called when a request received on a connection entered the queue of requests awaiting
+    /// forwarding to its request handler.
+    fn request_queued(&self) {}
+
+    /// Called when the queue of requests awaiting forwarding on a connection became full. No
+    /// further messages are read from the connection until a request is forwarded.
+    fn request_queue_full(&self) {}
+
+    /// Called when a request received on a connection was responded to with the given status code.
+    fn request_serviced(&self, _method: &Method, _status_code: StatusCode) {}
+
+    /// Called when a server ended a session.
+    fn session_closed(&self) {}
+
+    /// Called when a server started a session.
+    fn session_opened(&self) {}
+}
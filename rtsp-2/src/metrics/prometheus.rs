@@ -0,0 +1,385 @@
+//! Prometheus
+//!
+//! This module contains [`PrometheusMetrics`], which keeps the measurements reported to it and
+//! encodes them in the [Prometheus text exposition format](https://prometheus.io/docs/instrumenting/exposition_formats/).
+
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Write},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+use crate::{
+    method::Method,
+    metrics::Metrics,
+    protocol::codec::{
+        decoder::{
+            request::DecodeError as RequestDecodeError,
+            response::DecodeError as ResponseDecodeError, Deviation,
+        },
+        DecodeError,
+    },
+    status::StatusCode,
+};
+
+/// The content type of the encoded metrics, to be used when responding to scrapes.
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+/// Metrics kept in memory and encoded in the Prometheus text exposition format.
+///
+/// The following metrics are exposed:
+///
+/// - `rtsp_connections_open`: the number of open connections.
+/// - `rtsp_connections_total`: the number of connections opened.
+/// - `rtsp_decode_errors_total`: the number of messages that could not be decoded, labeled with
+///   whether the message was a request or a response and the kind of error.
+/// - `rtsp_received_bytes_total`: the number of bytes read from connections.
+/// - `rtsp_request_queue_depth`: the number of requests awaiting forwarding to request handlers.
+/// - `rtsp_request_queue_full_total`: the number of times the request queue of a connection became
+///   full.
+/// - `rtsp_requests_total`: the number of requests serviced, labeled with their method and the
+///   status code of their response. Extension methods are all labeled `other`, since clients can
+///   make up any number of them.
+/// - `rtsp_sent_bytes_total`: the number of bytes written to connections.
+/// - `rtsp_sessions_open`: the number of sessions of servers.
+/// - `rtsp_tolerated_deviations_total`: the number of tolerated deviations from the syntax in
+///   received messages, labeled with the kind of deviation.
+#[derive(Debug, Default)]
+pub struct PrometheusMetrics {
+    bytes_received: AtomicU64,
+    bytes_sent: AtomicU64,
+    connections_open: AtomicU64,
+    connections_total: AtomicU64,
+    decode_errors: Mutex<BTreeMap<(&'static str, &'static str), u64>>,
+    deviations: Mutex<BTreeMap<Deviation, u64>>,
+    request_queue_depth: AtomicU64,
+    request_queue_full: AtomicU64,
+    requests: Mutex<BTreeMap<(String, u16), u64>>,
+    sessions_open: AtomicU64,
+}
+
+impl PrometheusMetrics {
+    /// Encodes the current value of all metrics in the Prometheus text exposition format.
+    pub fn encode(&self) -> String {
+        let mut output = String::new();
+        self.write(&mut output)
+            .expect("writing to a string should not fail");
+        output
+    }
+
+    /// Constructs new metrics with all values set to zero.
+    pub fn new() -> Self {
+        PrometheusMetrics::default()
+    }
+
+    /// Writes the current value of all metrics to the given writer.
+    fn write(&self, writer: &mut impl Write) -> fmt::Result {
+        write_metric(
+            writer,
+            "rtsp_connections_open",
+            "gauge",
+            "Number of open connections.",
+            &self.connections_open,
+        )?;
+        write_metric(
+            writer,
+            "rtsp_connections_total",
+            "counter",
+            "Number of connections opened.",
+            &self.connections_total,
+        )?;
+
+        write_header(
+            writer,
+            "rtsp_decode_errors_total",
+            "counter",
+            "Number of messages that could not be decoded.",
+        )?;
+
+        for ((message, kind), count) in self.decode_errors.lock().unwrap().iter() {
+            writeln!(
+                writer,
+                "rtsp_decode_errors_total{{message=\"{}\",kind=\"{}\"}} {}",
+                message, kind, count
+            )?;
+        }
+
+        write_metric(
+            writer,
+            "rtsp_received_bytes_total",
+            "counter",
+            "Number of bytes read from connections.",
+            &self.bytes_received,
+        )?;
+        write_metric(
+            writer,
+            "rtsp_request_queue_depth",
+            "gauge",
+            "Number of requests awaiting forwarding to request handlers.",
+            &self.request_queue_depth,
+        )?;
+        write_metric(
+            writer,
+            "rtsp_request_queue_full_total",
+            "counter",
+            "Number of times the request queue of a connection became full.",
+            &self.request_queue_full,
+        )?;
+
+        write_header(
+            writer,
+            "rtsp_requests_total",
+            "counter",
+            "Number of requests serviced.",
+        )?;
+
+        for ((method, status_code), count) in self.requests.lock().unwrap().iter() {
+            writeln!(
+                writer,
+                "rtsp_requests_total{{method=\"{}\",status=\"{}\"}} {}",
+                method, status_code, count
+            )?;
+        }
+
+        write_metric(
+            writer,
+            "rtsp_sent_bytes_total",
+            "counter",
+            "Number of bytes written to connections.",
+            &self.bytes_sent,
+        )?;
+        write_metric(
+            writer,
+            "rtsp_sessions_open",
+            "gauge",
+            "Number of sessions of servers.",
+            &self.sessions_open,
+        )?;
+
+        write_header(
+            writer,
+            "rtsp_tolerated_deviations_total",
+            "counter",
+            "Number of tolerated deviations from the syntax in received messages.",
+        )?;
+
+        for (deviation, count) in self.deviations.lock().unwrap().iter() {
+            writeln!(
+                writer,
+                "rtsp_tolerated_deviations_total{{deviation=\"{:?}\"}} {}",
+                deviation, count
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Metrics for PrometheusMetrics {
+    fn bytes_received(&self, count: usize) {
+        self.bytes_received
+            .fetch_add(count as u64, Ordering::Relaxed);
+    }
+
+    fn bytes_sent(&self, count: usize) {
+        self.bytes_sent.fetch_add(count as u64, Ordering::Relaxed);
+    }
+
+    fn connection_closed(&self) {
+        self.connections_open.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    fn connection_opened(&self) {
+        self.connections_open.fetch_add(1, Ordering::Relaxed);
+        self.connections_total.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn decode_error(&self, error: DecodeError) {
+        let label = match error {
+            DecodeError::Request(error) => ("request", request_decode_error_kind(error)),
+            DecodeError::Response(error) => ("response", response_decode_error_kind(error)),
+        };
+
+        *self.decode_errors.lock().unwrap().entry(label).or_insert(0) += 1;
+    }
+
+    fn deviation_tolerated(&self, deviation: Deviation) {
+        *self
+            .deviations
+            .lock()
+            .unwrap()
+            .entry(deviation)
+            .or_insert(0) += 1;
+    }
+
+    fn request_dequeued(&self) {
+        self.request_queue_depth.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    fn request_queued(&self) {
+        self.request_queue_depth.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn request_queue_full(&self) {
+        self.request_queue_full.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn request_serviced(&self, method: &Method, status_code: StatusCode) {
+        *self
+            .requests
+            .lock()
+            .unwrap()
+            .entry((method_label(method), u16::from(status_code)))
+            .or_insert(0) += 1;
+    }
+
+    fn session_closed(&self) {
+        self.sessions_open.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    fn session_opened(&self) {
+        self.sessions_open.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Returns the label of the given method, which is `"other"` for all extension methods so that
+/// clients cannot add any number of series.
+fn method_label(method: &Method) -> String {
+    match method {
+        Method::Extension(_) => "other".to_string(),
+        method => method.to_string(),
+    }
+}
+
+/// Returns the label of the kind of the given request decoding error.
+fn request_decode_error_kind(error: RequestDecodeError) -> &'static str {
+    use self::RequestDecodeError::*;
+
+    match error {
+        BodyTooLong => "BodyTooLong",
+        Deviation(_) => "Deviation",
+        HeaderName(_) => "HeaderName",
+        HeaderNameTooLong => "HeaderNameTooLong",
+        HeaderValue(_) => "HeaderValue",
+        HeaderValueTooLong => "HeaderValueTooLong",
+        InvalidContentLength => "InvalidContentLength",
+        Method(_) => "Method",
+        MethodTooLong => "MethodTooLong",
+        TooManyHeaders => "TooManyHeaders",
+        URI(_) => "URI",
+        URITooLong => "URITooLong",
+        UnsupportedVersion => "UnsupportedVersion",
+        Version(_) => "Version",
+    }
+}
+
+/// Returns the label of the kind of the given response decoding error.
+fn response_decode_error_kind(error: ResponseDecodeError) -> &'static str {
+    use self::ResponseDecodeError::*;
+
+    match error {
+        BodyTooLong => "BodyTooLong",
+        Deviation(_) => "Deviation",
+        HeaderName(_) => "HeaderName",
+        HeaderNameTooLong => "HeaderNameTooLong",
+        HeaderValue(_) => "HeaderValue",
+        HeaderValueTooLong => "HeaderValueTooLong",
+        InvalidContentLength => "InvalidContentLength",
+        ReasonPhrase(_) => "ReasonPhrase",
+        ReasonPhraseTooLong => "ReasonPhraseTooLong",
+        StatusCode(_) => "StatusCode",
+        TooManyHeaders => "TooManyHeaders",
+        UnsupportedVersion => "UnsupportedVersion",
+        Version(_) => "Version",
+    }
+}
+
+/// Writes the `"HELP"` and `"TYPE"` lines of a metric.
+fn write_header(writer: &mut impl Write, name: &str, kind: &str, help: &str) -> fmt::Result {
+    writeln!(writer, "# HELP {} {}", name, help)?;
+    writeln!(writer, "# TYPE {} {}", name, kind)
+}
+
+/// Writes a metric without labels.
+fn write_metric(
+    writer: &mut impl Write,
+    name: &str,
+    kind: &str,
+    help: &str,
+    value: &AtomicU64,
+) -> fmt::Result {
+    write_header(writer, name, kind, help)?;
+    writeln!(writer, "{} {}", name, value.load(Ordering::Relaxed))
+}
+
+#[cfg(test)]
+mod test {
+    use std::convert::TryFrom;
+
+    use crate::{
+        method::Method,
+        metrics::{Metrics, PrometheusMetrics},
+        protocol::codec::{
+            decoder::{
+                request::DecodeError as RequestDecodeError,
+                response::DecodeError as ResponseDecodeError, Deviation,
+            },
+            DecodeError,
+        },
+        status::StatusCode,
+        version::DecodeError as VersionDecodeError,
+    };
+
+    #[test]
+    fn test_prometheus_metrics_encode() {
+        let metrics = PrometheusMetrics::new();
+        metrics.connection_opened();
+        metrics.connection_opened();
+        metrics.connection_closed();
+        metrics.bytes_received(100);
+        metrics.bytes_sent(40);
+        metrics.decode_error(DecodeError::Request(RequestDecodeError::BodyTooLong));
+        metrics.decode_error(DecodeError::Response(ResponseDecodeError::Version(
+            VersionDecodeError::Invalid,
+        )));
+        metrics.deviation_tolerated(Deviation::BareLineFeed);
+        metrics.deviation_tolerated(Deviation::BareLineFeed);
+        metrics.request_queued();
+        metrics.request_queue_full();
+        metrics.request_serviced(&Method::Options, StatusCode::OK);
+        metrics.request_serviced(&Method::Options, StatusCode::OK);
+        metrics.request_serviced(&Method::Describe, StatusCode::NotFound);
+        metrics.request_serviced(&Method::try_from("ANNOUNCE").unwrap(), StatusCode::OK);
+        metrics.request_serviced(&Method::try_from("X-RANDOM").unwrap(), StatusCode::OK);
+        metrics.session_opened();
+
+        let output = metrics.encode();
+        let lines = output
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            lines,
+            vec![
+                "rtsp_connections_open 1",
+                "rtsp_connections_total 2",
+                "rtsp_decode_errors_total{message=\"request\",kind=\"BodyTooLong\"} 1",
+                "rtsp_decode_errors_total{message=\"response\",kind=\"Version\"} 1",
+                "rtsp_received_bytes_total 100",
+                "rtsp_request_queue_depth 1",
+                "rtsp_request_queue_full_total 1",
+                "rtsp_requests_total{method=\"DESCRIBE\",status=\"404\"} 1",
+                "rtsp_requests_total{method=\"OPTIONS\",status=\"200\"} 2",
+                "rtsp_requests_total{method=\"other\",status=\"200\"} 2",
+                "rtsp_sent_bytes_total 40",
+                "rtsp_sessions_open 1",
+                "rtsp_tolerated_deviations_total{deviation=\"BareLineFeed\"} 2",
+            ]
+        );
+        assert!(output.contains("# TYPE rtsp_requests_total counter\n"));
+    }
+}
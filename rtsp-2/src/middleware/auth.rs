@@ -0,0 +1,680 @@
+//! Authentication
+//!
+//! An [`AuthenticationLayer`] answers requests that do not carry valid credentials of a known user
+//! with a 401 (Unauthorized) response challenging the client to authenticate, as described by
+//! [RFC7826, Section 19](https://tools.ietf.org/html/rfc7826#section-19).
+//!
+//! Digest authentication, as defined by [RFC2617](https://tools.ietf.org/html/rfc2617), is always
+//! offered. Basic authentication sends passwords in the clear, so it is only offered if allowed,
+//! such as when connections are encrypted.
+
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    mem, str,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use futures::{Async, Future, Poll};
+use rand::random;
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::{
+    header::{name::HeaderName, value::HeaderValue},
+    middleware::status_response,
+    request::Request,
+    response::Response,
+    status::StatusCode,
+    uri::request::URI,
+};
+
+/// How long a nonce can be used for after it was issued, after which clients are asked to
+/// authenticate again with a new one.
+pub const NONCE_LIFETIME: Duration = Duration::from_secs(300);
+
+/// A layer wrapping services with an [`AuthenticationService`].
+#[derive(Clone, Debug)]
+pub struct AuthenticationLayer {
+    /// Whether Basic authentication is allowed.
+    basic_allowed: bool,
+
+    /// The highest nonce count used with each nonce, so that credentials cannot be replayed.
+    nonce_counts: Arc<Mutex<HashMap<String, u32>>>,
+
+    /// The protection space the credentials apply to, shown to users by clients.
+    realm: String,
+
+    /// The key nonces are signed with, so that they can be verified without being stored.
+    secret: Arc<String>,
+
+    /// The passwords of the known users, keyed by their names.
+    users: Arc<HashMap<String, String>>,
+}
+
+impl AuthenticationLayer {
+    /// Adds a user allowed to make requests.
+    pub fn add_user<TName, TPassword>(&mut self, name: TName, password: TPassword) -> &mut Self
+    where
+        TName: Into<String>,
+        TPassword: Into<String>,
+    {
+        Arc::make_mut(&mut self.users).insert(name.into(), password.into());
+        self
+    }
+
+    /// Returns whether Basic authentication is allowed.
+    pub fn is_basic_allowed(&self) -> bool {
+        self.basic_allowed
+    }
+
+    /// Constructs a new layer without any users for the given realm, such as `"camera"`.
+    ///
+    /// Quotes and backslashes cannot be part of the realm, so they are removed.
+    pub fn new<TRealm>(realm: TRealm) -> Self
+    where
+        TRealm: Into<String>,
+    {
+        let realm = realm.into().replace(['"', '\\'], "");
+
+        AuthenticationLayer {
+            basic_allowed: false,
+            nonce_counts: Arc::new(Mutex::new(HashMap::new())),
+            realm,
+            secret: Arc::new(format!("{:032x}", random::<u128>())),
+            users: Arc::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the realm the credentials apply to.
+    pub fn realm(&self) -> &str {
+        &self.realm
+    }
+
+    /// Sets whether Basic authentication is allowed. Since it sends passwords in the clear, it
+    /// should only be allowed for encrypted connections.
+    pub fn set_basic_allowed(&mut self, allowed: bool) -> &mut Self {
+        self.basic_allowed = allowed;
+        self
+    }
+
+    /// Sets whether Basic authentication is allowed.
+    pub fn with_basic_allowed(mut self, allowed: bool) -> Self {
+        self.set_basic_allowed(allowed);
+        self
+    }
+
+    /// Adds a user allowed to make requests.
+    pub fn with_user<TName, TPassword>(mut self, name: TName, password: TPassword) -> Self
+    where
+        TName: Into<String>,
+        TPassword: Into<String>,
+    {
+        self.add_user(name, password);
+        self
+    }
+}
+
+impl<TService> Layer<TService> for AuthenticationLayer {
+    type Service = AuthenticationService<TService>;
+
+    fn layer(&self, inner: TService) -> Self::Service {
+        AuthenticationService {
+            inner,
+            layer: self.clone(),
+        }
+    }
+}
+
+/// A service answering requests without valid credentials with a 401 (Unauthorized) response.
+#[derive(Clone, Debug)]
+pub struct AuthenticationService<TService> {
+    inner: TService,
+    layer: AuthenticationLayer,
+}
+
+impl<TService> AuthenticationService<TService> {
+    /// Checks the credentials of the given request, returning whether the nonce was stale if they
+    /// are not valid.
+    fn authenticate<TBody>(&self, request: &Request<TBody>) -> Result<(), bool> {
+        let authorization = request
+            .headers()
+            .get(&HeaderName::Authorization)
+            .ok_or(false)?
+            .as_str();
+        let (scheme, credentials) = match authorization.find(' ') {
+            Some(index) => (&authorization[..index], authorization[index..].trim()),
+            None => return Err(false),
+        };
+
+        if scheme.eq_ignore_ascii_case("Basic") && self.layer.basic_allowed {
+            let credentials = base64::decode(credentials).map_err(|_| false)?;
+            let credentials = str::from_utf8(&credentials).map_err(|_| false)?;
+            let (name, password) = credentials.split_once(':').ok_or(false)?;
+
+            let (expected, known) = self.user_password(name);
+
+            return if constant_time_eq(expected.as_bytes(), password.as_bytes()) && known {
+                Ok(())
+            } else {
+                Err(false)
+            };
+        }
+
+        if !scheme.eq_ignore_ascii_case("Digest") {
+            return Err(false);
+        }
+
+        let parameters = digest_parameters(credentials);
+        let parameter = |name| parameters.get(name).map(String::as_str).ok_or(false);
+        let name = parameter("username")?;
+        let nonce = parameter("nonce")?;
+        let uri = parameter("uri")?;
+        let (password, known) = self.user_password(name);
+
+        if parameter("realm")? != self.layer.realm || !digest_uri_matches(uri, request.uri()) {
+            return Err(false);
+        }
+
+        let secret = md5_hex(&format!("{}:{}:{}", name, self.layer.realm, password));
+        let method = md5_hex(&format!("{}:{}", request.method().as_str(), uri));
+        let (expected, count) = match parameters.get("qop").map(String::as_str) {
+            Some(qop) if qop.eq_ignore_ascii_case("auth") => {
+                let count = parameter("nc")?;
+                let expected = md5_hex(&format!(
+                    "{}:{}:{}:{}:{}:{}",
+                    secret,
+                    nonce,
+                    count,
+                    parameter("cnonce")?,
+                    qop,
+                    method
+                ));
+                let count = u32::from_str_radix(count, 16).map_err(|_| false)?;
+                (expected, Some(count))
+            }
+            Some(_) => return Err(false),
+            None => (md5_hex(&format!("{}:{}:{}", secret, nonce, method)), None),
+        };
+
+        let response = parameter("response")?.to_ascii_lowercase();
+
+        if !constant_time_eq(response.as_bytes(), expected.as_bytes()) || !known {
+            return Err(false);
+        }
+
+        // The credentials are correct, so an expired nonce only needs to be renewed without asking
+        // the user again.
+        match nonce_age(nonce, &self.layer.secret) {
+            Some(age) if age <= NONCE_LIFETIME => (),
+            Some(_) => return Err(true),
+            None => return Err(false),
+        }
+
+        match count {
+            Some(count) => self.record_nonce_count(nonce, count),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns a 401 (Unauthorized) response with the challenges of the allowed schemes.
+    fn challenge<TRequestBody, TResponseBody>(
+        &self,
+        request: &Request<TRequestBody>,
+        stale: bool,
+    ) -> Response<TResponseBody>
+    where
+        TResponseBody: Default,
+    {
+        let mut response = status_response(StatusCode::Unauthorized, request.version());
+        let mut digest = format!(
+            "Digest realm=\"{}\", nonce=\"{}\", algorithm=MD5, qop=\"auth\"",
+            self.layer.realm,
+            nonce(&self.layer.secret)
+        );
+
+        if stale {
+            digest.push_str(", stale=TRUE");
+        }
+
+        response.headers_mut().append(
+            HeaderName::WWWAuthenticate,
+            HeaderValue::try_from(digest.as_str()).expect("challenge should be a valid value"),
+        );
+
+        if self.layer.basic_allowed {
+            let basic = format!("Basic realm=\"{}\"", self.layer.realm);
+            response.headers_mut().append(
+                HeaderName::WWWAuthenticate,
+                HeaderValue::try_from(basic.as_str()).expect("challenge should be a valid value"),
+            );
+        }
+
+        response
+    }
+
+    /// Records the nonce count the given nonce was used with, failing if it was not higher than
+    /// the counts it was used with before, as the request is then a replay.
+    ///
+    /// Counts of nonces that expired are forgotten.
+    fn record_nonce_count(&self, nonce: &str, count: u32) -> Result<(), bool> {
+        let mut nonce_counts = self
+            .layer
+            .nonce_counts
+            .lock()
+            .expect("`AuthenticationLayer.nonce_counts` should not be poisoned");
+
+        match nonce_counts.get(nonce) {
+            Some(&highest) if count <= highest => return Err(false),
+            Some(_) => (),
+            None => nonce_counts.retain(|nonce, _| {
+                matches!(nonce_age(nonce, &self.layer.secret), Some(age) if age <= NONCE_LIFETIME)
+            }),
+        }
+
+        nonce_counts.insert(nonce.to_string(), count);
+        Ok(())
+    }
+
+    /// Returns the password of the user with the given name, and whether the user is known.
+    ///
+    /// The secret of the layer stands in for the passwords of unknown users, so that their
+    /// credentials take as long to check as those of known users.
+    fn user_password(&self, name: &str) -> (&str, bool) {
+        match self.layer.users.get(name) {
+            Some(password) => (password, true),
+            None => (&self.layer.secret, false),
+        }
+    }
+}
+
+impl<TService, TRequestBody, TResponseBody> Service<Request<TRequestBody>>
+    for AuthenticationService<TService>
+where
+    TService: Service<Request<TRequestBody>, Response = Response<TResponseBody>>,
+    TResponseBody: Default,
+{
+    type Response = Response<TResponseBody>;
+    type Error = TService::Error;
+    type Future = AuthenticationFuture<TService::Future, TResponseBody>;
+
+    fn call(&mut self, request: Request<TRequestBody>) -> Self::Future {
+        let state = match self.authenticate(&request) {
+            Ok(()) => AuthenticationState::Handling(self.inner.call(request)),
+            Err(stale) => AuthenticationState::Rejected(self.challenge(&request, stale)),
+        };
+
+        AuthenticationFuture { state }
+    }
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        self.inner.poll_ready()
+    }
+}
+
+/// The future returned by an [`AuthenticationService`].
+#[must_use = "futures do nothing unless polled"]
+pub struct AuthenticationFuture<TFuture, TBody> {
+    state: AuthenticationState<TFuture, TBody>,
+}
+
+impl<TFuture, TBody> Future for AuthenticationFuture<TFuture, TBody>
+where
+    TFuture: Future<Item = Response<TBody>>,
+{
+    type Item = Response<TBody>;
+    type Error = TFuture::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        use self::AuthenticationState::*;
+
+        match &mut self.state {
+            Handling(inner) => inner.poll(),
+            Rejected(_) => match mem::replace(&mut self.state, Done) {
+                Rejected(response) => Ok(Async::Ready(response)),
+                _ => unreachable!(),
+            },
+            Done => panic!("`AuthenticationFuture` polled after completion"),
+        }
+    }
+}
+
+/// The state of an [`AuthenticationFuture`].
+#[allow(clippy::large_enum_variant)]
+enum AuthenticationState<TFuture, TBody> {
+    /// The future already resolved to its response.
+    Done,
+
+    /// The request is being handled by the wrapped service.
+    Handling(TFuture),
+
+    /// The request was rejected since it did not carry valid credentials.
+    Rejected(Response<TBody>),
+}
+
+/// Compares the given secrets in time that only depends on their lengths, so that the time taken
+/// to reject credentials does not reveal how much of them was correct.
+fn constant_time_eq(left: &[u8], right: &[u8]) -> bool {
+    left.len() == right.len()
+        && left
+            .iter()
+            .zip(right)
+            .fold(0, |difference, (left, right)| difference | (left ^ right))
+            == 0
+}
+
+/// Returns whether the URI of Digest credentials identifies the given request URI, either in
+/// absolute form or as its path and query.
+fn digest_uri_matches(digest_uri: &str, request_uri: &URI) -> bool {
+    if let Ok(mut digest_uri) = URI::try_from(digest_uri) {
+        let mut request_uri = request_uri.clone();
+        digest_uri.normalize();
+        request_uri.normalize();
+        return digest_uri == request_uri;
+    }
+
+    if request_uri.is_asterisk() {
+        return false;
+    }
+
+    let path = request_uri.path().to_string();
+
+    match (digest_uri.split_once('?'), request_uri.query()) {
+        (Some((digest_path, digest_query)), Some(query)) => {
+            digest_path == path && digest_query == query.as_str()
+        }
+        (None, None) => digest_uri == path,
+        _ => false,
+    }
+}
+
+/// Parses the comma separated parameters of Digest credentials, removing the quotes of quoted
+/// values. Names are converted to lowercase as they are case insensitive.
+fn digest_parameters(credentials: &str) -> HashMap<String, String> {
+    let mut parameters = HashMap::new();
+    let mut remaining = credentials.trim();
+
+    while let Some(index) = remaining.find('=') {
+        let name = remaining[..index].trim().to_ascii_lowercase();
+        let rest = remaining[index + 1..].trim_start();
+
+        let (value, rest) = if let Some(quoted) = rest.strip_prefix('"') {
+            let mut value = String::new();
+            let mut characters = quoted.char_indices();
+            let mut end = quoted.len();
+
+            while let Some((index, character)) = characters.next() {
+                match character {
+                    '\\' => value.extend(characters.next().map(|(_, character)| character)),
+                    '"' => {
+                        end = index + 1;
+                        break;
+                    }
+                    character => value.push(character),
+                }
+            }
+
+            (value, &quoted[end..])
+        } else {
+            let end = rest.find(',').unwrap_or(rest.len());
+            (rest[..end].trim().to_string(), &rest[end..])
+        };
+
+        parameters.insert(name, value);
+        remaining = rest.trim_start().trim_start_matches(',');
+    }
+
+    parameters
+}
+
+/// Returns the MD5 digest of the given value as lowercase hexadecimal.
+fn md5_hex(value: &str) -> String {
+    format!("{:x}", md5::compute(value))
+}
+
+/// Returns a new nonce, made up of the time it was issued and a signature of that time so that
+/// clients cannot forge nonces.
+fn nonce(secret: &str) -> String {
+    let issued = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    format!(
+        "{:016x}{}",
+        issued,
+        md5_hex(&format!("{}:{}", issued, secret))
+    )
+}
+
+/// Returns how long ago the given nonce was issued, if it was issued by this layer.
+fn nonce_age(nonce: &str, secret: &str) -> Option<Duration> {
+    if nonce.len() != 48 || !nonce.is_ascii() {
+        return None;
+    }
+
+    let issued = u64::from_str_radix(&nonce[..16], 16).ok()?;
+
+    let signature = md5_hex(&format!("{}:{}", issued, secret));
+
+    if !constant_time_eq(&nonce.as_bytes()[16..], signature.as_bytes()) {
+        return None;
+    }
+
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs();
+    Some(Duration::from_secs(now.saturating_sub(issued)))
+}
+
+#[cfg(test)]
+mod test {
+    use std::convert::TryFrom;
+
+    use futures::Future;
+    use tower_layer::Layer;
+    use tower_service::Service;
+
+    use crate::{
+        header::{name::HeaderName, value::HeaderValue},
+        middleware::{
+            auth::{
+                constant_time_eq, digest_parameters, md5_hex, nonce, AuthenticationLayer,
+                AuthenticationService,
+            },
+            test::{request, response, FnService},
+        },
+        status::StatusCode,
+    };
+
+    fn authorized_request(authorization: &str) -> crate::request::Request<bytes::BytesMut> {
+        let mut request = request("rtsp://example.com/stream");
+        request.headers_mut().insert(
+            HeaderName::Authorization,
+            HeaderValue::try_from(authorization).unwrap(),
+        );
+        request
+    }
+
+    #[test]
+    fn test_basic_authentication() {
+        let layer = AuthenticationLayer::new("camera").with_user("admin", "secret");
+        let mut service = layer.layer(FnService(|_| Ok::<_, ()>(response())));
+        let credentials = format!("Basic {}", base64::encode("admin:secret"));
+
+        // Basic credentials are not accepted unless allowed.
+        let received = service
+            .call(authorized_request(&credentials))
+            .wait()
+            .unwrap();
+        assert_eq!(received.status_code(), StatusCode::Unauthorized);
+
+        let mut service = layer
+            .with_basic_allowed(true)
+            .layer(FnService(|_| Ok::<_, ()>(response())));
+        let received = service
+            .call(authorized_request(&credentials))
+            .wait()
+            .unwrap();
+        assert_eq!(received.status_code(), StatusCode::OK);
+
+        let credentials = format!("Basic {}", base64::encode("admin:wrong"));
+        let received = service
+            .call(authorized_request(&credentials))
+            .wait()
+            .unwrap();
+        assert_eq!(received.status_code(), StatusCode::Unauthorized);
+        assert_eq!(
+            received
+                .headers()
+                .get_all(&HeaderName::WWWAuthenticate)
+                .count(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"", b""));
+        assert!(constant_time_eq(b"password", b"password"));
+        assert!(!constant_time_eq(b"password", b"passwore"));
+        assert!(!constant_time_eq(b"password", b"Password"));
+        assert!(!constant_time_eq(b"password", b"passwor"));
+    }
+
+    #[test]
+    fn test_digest_authentication() {
+        let layer = AuthenticationLayer::new("camera").with_user("admin", "secret");
+        let mut service = layer.layer(FnService(|_| Ok::<_, ()>(response())));
+
+        let received = service
+            .call(request("rtsp://example.com/stream"))
+            .wait()
+            .unwrap();
+        assert_eq!(received.status_code(), StatusCode::Unauthorized);
+
+        let challenge = received
+            .headers()
+            .get(&HeaderName::WWWAuthenticate)
+            .unwrap()
+            .as_str()
+            .to_string();
+        let parameters = digest_parameters(challenge.trim_start_matches("Digest"));
+        assert_eq!(parameters["realm"], "camera");
+        assert!(!parameters.contains_key("stale"));
+
+        let secret = md5_hex("admin:camera:secret");
+        let method = md5_hex("OPTIONS:rtsp://example.com/stream");
+        let digest = |nonce: &str| md5_hex(&format!("{}:{}:{}", secret, nonce, method));
+        let credentials = |nonce: &str, response: &str| {
+            format!(
+                "Digest username=\"admin\", realm=\"camera\", nonce=\"{}\", \
+                 uri=\"rtsp://example.com/stream\", response=\"{}\"",
+                nonce, response
+            )
+        };
+
+        let issued = parameters["nonce"].as_str();
+        let received = service
+            .call(authorized_request(&credentials(issued, &digest(issued))))
+            .wait()
+            .unwrap();
+        assert_eq!(received.status_code(), StatusCode::OK);
+
+        let received = service
+            .call(authorized_request(&credentials(issued, &md5_hex("wrong"))))
+            .wait()
+            .unwrap();
+        assert_eq!(received.status_code(), StatusCode::Unauthorized);
+
+        // Correct credentials with a nonce issued long ago are only stale.
+        let expired = format!("{:016x}{}", 0, md5_hex(&format!("0:{}", layer.secret)));
+        let received = service
+            .call(authorized_request(&credentials(
+                &expired,
+                &digest(&expired),
+            )))
+            .wait()
+            .unwrap();
+        let challenge = received
+            .headers()
+            .get(&HeaderName::WWWAuthenticate)
+            .unwrap();
+        assert!(challenge.as_str().ends_with("stale=TRUE"));
+
+        // Nonces not issued by the layer are rejected outright.
+        let forged = nonce("other");
+        let received = service
+            .call(authorized_request(&credentials(&forged, &digest(&forged))))
+            .wait()
+            .unwrap();
+        let challenge = received
+            .headers()
+            .get(&HeaderName::WWWAuthenticate)
+            .unwrap();
+        assert!(!challenge.as_str().contains("stale"));
+    }
+
+    #[test]
+    fn test_digest_credentials_cannot_be_replayed() {
+        let layer = AuthenticationLayer::new("camera").with_user("admin", "secret");
+        let mut service = layer.layer(FnService(|_| Ok::<_, ()>(response())));
+        let issued = nonce(&layer.secret);
+        let secret = md5_hex("admin:camera:secret");
+        let credentials = |name: &str, uri: &str, count: &str| {
+            let method = md5_hex(&format!("OPTIONS:{}", uri));
+            let response = md5_hex(&format!(
+                "{}:{}:{}:abc:auth:{}",
+                secret, issued, count, method
+            ));
+            format!(
+                "Digest username=\"{}\", realm=\"camera\", nonce=\"{}\", uri=\"{}\", \
+                 qop=auth, nc={}, cnonce=\"abc\", response=\"{}\"",
+                name, issued, uri, count, response
+            )
+        };
+        let status = |service: &mut AuthenticationService<_>, credentials: &str| {
+            service
+                .call(authorized_request(credentials))
+                .wait()
+                .unwrap()
+                .status_code()
+        };
+
+        // Credentials only apply to the URI they were computed for, in either form.
+        let other = credentials("admin", "rtsp://example.com/other", "00000001");
+        assert_eq!(status(&mut service, &other), StatusCode::Unauthorized);
+        let other = credentials("admin", "/other", "00000001");
+        assert_eq!(status(&mut service, &other), StatusCode::Unauthorized);
+
+        let first = credentials("admin", "rtsp://example.com/stream", "00000001");
+        assert_eq!(status(&mut service, &first), StatusCode::OK);
+        assert_eq!(status(&mut service, &first), StatusCode::Unauthorized);
+
+        let second = credentials("admin", "/stream", "00000002");
+        assert_eq!(status(&mut service, &second), StatusCode::OK);
+        let first = credentials("admin", "/stream", "00000001");
+        assert_eq!(status(&mut service, &first), StatusCode::Unauthorized);
+
+        // Unknown users are rejected even if their response matches the secret of the layer.
+        let unknown = md5_hex(&format!("nobody:camera:{}", layer.secret));
+        let method = md5_hex("OPTIONS:/stream");
+        let response = md5_hex(&format!("{}:{}:{}", unknown, issued, method));
+        let unknown = format!(
+            "Digest username=\"nobody\", realm=\"camera\", nonce=\"{}\", uri=\"/stream\", \
+             response=\"{}\"",
+            issued, response
+        );
+        assert_eq!(status(&mut service, &unknown), StatusCode::Unauthorized);
+    }
+
+    #[test]
+    fn test_digest_parameters() {
+        let parameters = digest_parameters(
+            "username=\"ad\\\"min\", realm=\"a, b\",nc=00000001 , qop=auth, uri=\"/\"",
+        );
+        assert_eq!(parameters["username"], "ad\"min");
+        assert_eq!(parameters["realm"], "a, b");
+        assert_eq!(parameters["nc"], "00000001");
+        assert_eq!(parameters["qop"], "auth");
+        assert_eq!(parameters["uri"], "/");
+    }
+}
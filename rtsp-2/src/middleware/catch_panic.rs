@@ -0,0 +1,155 @@
+//! Catch Panic
+//!
+//! A [`CatchPanicLayer`] answers requests the wrapped service panics while handling with a 500
+//! (Internal Server Error) response, so that a bug in handling one request does not take down the
+//! connection or the task it runs on.
+
+use std::{
+    mem,
+    panic::{self, AssertUnwindSafe},
+};
+
+use futures::{Async, Future, Poll};
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::{
+    middleware::status_response, request::Request, response::Response, status::StatusCode,
+    version::Version,
+};
+
+/// A layer wrapping services with a [`CatchPanicService`].
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct CatchPanicLayer;
+
+impl CatchPanicLayer {
+    /// Constructs a new layer.
+    pub fn new() -> Self {
+        CatchPanicLayer
+    }
+}
+
+impl<TService> Layer<TService> for CatchPanicLayer {
+    type Service = CatchPanicService<TService>;
+
+    fn layer(&self, inner: TService) -> Self::Service {
+        CatchPanicService { inner }
+    }
+}
+
+/// A service converting panics of the wrapped service while handling a request into 500 (Internal
+/// Server Error) responses.
+///
+/// Panics are still reported by the panic hook. The wrapped service keeps being used after a
+/// panic, so it should not be left in an inconsistent state by one, such as with a poisoned lock.
+#[derive(Clone, Debug)]
+pub struct CatchPanicService<TService> {
+    inner: TService,
+}
+
+impl<TService, TRequestBody, TResponseBody> Service<Request<TRequestBody>>
+    for CatchPanicService<TService>
+where
+    TService: Service<Request<TRequestBody>, Response = Response<TResponseBody>>,
+    TResponseBody: Default,
+{
+    type Response = Response<TResponseBody>;
+    type Error = TService::Error;
+    type Future = CatchPanicFuture<TService::Future>;
+
+    fn call(&mut self, request: Request<TRequestBody>) -> Self::Future {
+        let version = request.version();
+        let inner = &mut self.inner;
+        let state = match panic::catch_unwind(AssertUnwindSafe(|| inner.call(request))) {
+            Ok(future) => CatchPanicState::Handling(future),
+            Err(_) => CatchPanicState::Panicked,
+        };
+
+        CatchPanicFuture { state, version }
+    }
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        self.inner.poll_ready()
+    }
+}
+
+/// The future returned by a [`CatchPanicService`].
+#[must_use = "futures do nothing unless polled"]
+pub struct CatchPanicFuture<TFuture> {
+    state: CatchPanicState<TFuture>,
+    version: Version,
+}
+
+impl<TFuture, TBody> Future for CatchPanicFuture<TFuture>
+where
+    TFuture: Future<Item = Response<TBody>>,
+    TBody: Default,
+{
+    type Item = Response<TBody>;
+    type Error = TFuture::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        use self::CatchPanicState::*;
+
+        if let Handling(future) = &mut self.state {
+            match panic::catch_unwind(AssertUnwindSafe(|| future.poll())) {
+                Ok(result) => return result,
+
+                // The future must not be polled again after it panicked.
+                Err(_) => self.state = Panicked,
+            }
+        }
+
+        match mem::replace(&mut self.state, Done) {
+            Panicked => Ok(Async::Ready(status_response(
+                StatusCode::InternalServerError,
+                self.version,
+            ))),
+            _ => panic!("`CatchPanicFuture` polled after completion"),
+        }
+    }
+}
+
+/// The state of a [`CatchPanicFuture`].
+enum CatchPanicState<TFuture> {
+    /// The future already resolved to its response.
+    Done,
+
+    /// The request is being handled by the wrapped service.
+    Handling(TFuture),
+
+    /// The wrapped service panicked while handling the request.
+    Panicked,
+}
+
+#[cfg(test)]
+mod test {
+    use bytes::BytesMut;
+    use futures::{future, Future};
+    use tower_layer::Layer;
+    use tower_service::Service;
+
+    use crate::{
+        middleware::{
+            catch_panic::CatchPanicLayer,
+            test::{request, FnService},
+        },
+        response::Response,
+        status::StatusCode,
+    };
+
+    #[test]
+    fn test_panics_become_internal_server_errors() {
+        let mut service = CatchPanicLayer::new().layer(FnService(|_| -> future::Empty<_, ()> {
+            panic!("handling request")
+        }));
+        let response = service.call(request("rtsp://example.com/")).wait().unwrap();
+        assert_eq!(response.status_code(), StatusCode::InternalServerError);
+
+        let mut service = CatchPanicLayer::new().layer(FnService(|_| {
+            future::lazy(|| -> Result<Response<BytesMut>, ()> { panic!("polling response") })
+        }));
+        let response = service.call(request("rtsp://example.com/")).wait().unwrap();
+        assert_eq!(response.status_code(), StatusCode::InternalServerError);
+    }
+}
@@ -0,0 +1,200 @@
+//! Concurrency
+//!
+//! A [`ConcurrencyLimitLayer`] limits how many requests the services it wraps handle at once,
+//! answering requests beyond the limit with a 503 (Service Unavailable) response.
+//!
+//! Connections only handle one request at a time and do not wait for their service to be ready,
+//! so requests beyond the limit are shed instead of being queued.
+
+use std::{
+    mem,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use futures::{Async, Future, Poll};
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::{
+    middleware::status_response, request::Request, response::Response, status::StatusCode,
+};
+
+/// A layer wrapping services with a [`ConcurrencyLimitService`].
+///
+/// The limit is shared by every service the layer wraps, such as the services of all connections
+/// of a server.
+#[derive(Clone, Debug)]
+pub struct ConcurrencyLimitLayer {
+    /// The number of requests being handled by the wrapped services.
+    in_flight: Arc<AtomicUsize>,
+
+    /// The maximum number of requests handled at once.
+    limit: usize,
+}
+
+impl ConcurrencyLimitLayer {
+    /// Returns the number of requests currently being handled by the wrapped services.
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::SeqCst)
+    }
+
+    /// Returns the maximum number of requests handled at once.
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Constructs a new layer allowing the wrapped services to handle the given number of requests
+    /// at once.
+    pub fn new(limit: usize) -> Self {
+        ConcurrencyLimitLayer {
+            in_flight: Arc::new(AtomicUsize::new(0)),
+            limit,
+        }
+    }
+}
+
+impl<TService> Layer<TService> for ConcurrencyLimitLayer {
+    type Service = ConcurrencyLimitService<TService>;
+
+    fn layer(&self, inner: TService) -> Self::Service {
+        ConcurrencyLimitService {
+            in_flight: self.in_flight.clone(),
+            inner,
+            limit: self.limit,
+        }
+    }
+}
+
+/// A service answering requests with a 503 (Service Unavailable) response while too many
+/// requests are being handled.
+#[derive(Clone, Debug)]
+pub struct ConcurrencyLimitService<TService> {
+    in_flight: Arc<AtomicUsize>,
+    inner: TService,
+    limit: usize,
+}
+
+impl<TService, TRequestBody, TResponseBody> Service<Request<TRequestBody>>
+    for ConcurrencyLimitService<TService>
+where
+    TService: Service<Request<TRequestBody>, Response = Response<TResponseBody>>,
+    TResponseBody: Default,
+{
+    type Response = Response<TResponseBody>;
+    type Error = TService::Error;
+    type Future = ConcurrencyLimitFuture<TService::Future, TResponseBody>;
+
+    fn call(&mut self, request: Request<TRequestBody>) -> Self::Future {
+        if self.in_flight.fetch_add(1, Ordering::SeqCst) >= self.limit {
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+            let response = status_response(StatusCode::ServiceUnavailable, request.version());
+
+            return ConcurrencyLimitFuture {
+                state: ConcurrencyLimitState::Rejected(response),
+            };
+        }
+
+        let permit = Permit(self.in_flight.clone());
+
+        ConcurrencyLimitFuture {
+            state: ConcurrencyLimitState::Handling {
+                inner: self.inner.call(request),
+                _permit: permit,
+            },
+        }
+    }
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        self.inner.poll_ready()
+    }
+}
+
+/// The future returned by a [`ConcurrencyLimitService`].
+#[must_use = "futures do nothing unless polled"]
+pub struct ConcurrencyLimitFuture<TFuture, TBody> {
+    state: ConcurrencyLimitState<TFuture, TBody>,
+}
+
+impl<TFuture, TBody> Future for ConcurrencyLimitFuture<TFuture, TBody>
+where
+    TFuture: Future<Item = Response<TBody>>,
+{
+    type Item = Response<TBody>;
+    type Error = TFuture::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        use self::ConcurrencyLimitState::*;
+
+        match &mut self.state {
+            Handling { inner, .. } => inner.poll(),
+            Rejected(_) => match mem::replace(&mut self.state, Done) {
+                Rejected(response) => Ok(Async::Ready(response)),
+                _ => unreachable!(),
+            },
+            Done => panic!("`ConcurrencyLimitFuture` polled after completion"),
+        }
+    }
+}
+
+/// The state of a [`ConcurrencyLimitFuture`].
+enum ConcurrencyLimitState<TFuture, TBody> {
+    /// The future already resolved to its response.
+    Done,
+
+    /// The request is being handled by the wrapped service.
+    Handling {
+        inner: TFuture,
+
+        /// Counts the request as in flight until the future is dropped.
+        _permit: Permit,
+    },
+
+    /// The request was rejected since too many requests were being handled.
+    Rejected(Response<TBody>),
+}
+
+/// Counts a request as in flight for as long as it exists.
+struct Permit(Arc<AtomicUsize>);
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bytes::BytesMut;
+    use futures::{future, Future};
+    use tower_layer::Layer;
+    use tower_service::Service;
+
+    use crate::{
+        middleware::{
+            concurrency::ConcurrencyLimitLayer,
+            test::{request, FnService},
+        },
+        response::Response,
+        status::StatusCode,
+    };
+
+    #[test]
+    fn test_concurrency_limit_shared_between_services() {
+        let layer = ConcurrencyLimitLayer::new(1);
+        let mut first = layer.layer(FnService(|_| future::empty::<Response<BytesMut>, ()>()));
+        let mut second = layer.layer(FnService(|_| future::empty::<Response<BytesMut>, ()>()));
+
+        let handling = first.call(request("rtsp://example.com/"));
+        assert_eq!(layer.in_flight(), 1);
+
+        let response = second.call(request("rtsp://example.com/")).wait().unwrap();
+        assert_eq!(response.status_code(), StatusCode::ServiceUnavailable);
+        assert_eq!(layer.in_flight(), 1);
+
+        drop(handling);
+        assert_eq!(layer.in_flight(), 0);
+    }
+}
@@ -0,0 +1,126 @@
+//! CSeq
+//!
+//! A [`CSeqLayer`] echoes the `"CSeq"` header of each request in its response, as RTSP requires of
+//! every response.
+
+use futures::{try_ready, Async, Future, Poll};
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::{
+    header::{map::HeaderMapExtension, types::CSeq},
+    request::Request,
+    response::Response,
+};
+
+/// A layer wrapping services with a [`CSeqService`].
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct CSeqLayer;
+
+impl CSeqLayer {
+    /// Constructs a new layer.
+    pub fn new() -> Self {
+        CSeqLayer
+    }
+}
+
+impl<TService> Layer<TService> for CSeqLayer {
+    type Service = CSeqService<TService>;
+
+    fn layer(&self, inner: TService) -> Self::Service {
+        CSeqService { inner }
+    }
+}
+
+/// A service setting the `"CSeq"` header of each response to the one of its request, replacing
+/// whatever the wrapped service set.
+#[derive(Clone, Debug)]
+pub struct CSeqService<TService> {
+    inner: TService,
+}
+
+impl<TService, TRequestBody, TResponseBody> Service<Request<TRequestBody>> for CSeqService<TService>
+where
+    TService: Service<Request<TRequestBody>, Response = Response<TResponseBody>>,
+{
+    type Response = Response<TResponseBody>;
+    type Error = TService::Error;
+    type Future = CSeqFuture<TService::Future>;
+
+    fn call(&mut self, request: Request<TRequestBody>) -> Self::Future {
+        CSeqFuture {
+            cseq: request.headers().typed_get(),
+            inner: self.inner.call(request),
+        }
+    }
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        self.inner.poll_ready()
+    }
+}
+
+/// The future returned by a [`CSeqService`].
+#[must_use = "futures do nothing unless polled"]
+pub struct CSeqFuture<TFuture> {
+    /// The `"CSeq"` of the request, if it had a valid one.
+    cseq: Option<CSeq>,
+
+    /// The future of the wrapped service.
+    inner: TFuture,
+}
+
+impl<TFuture, TBody> Future for CSeqFuture<TFuture>
+where
+    TFuture: Future<Item = Response<TBody>>,
+{
+    type Item = Response<TBody>;
+    type Error = TFuture::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        let mut response = try_ready!(self.inner.poll());
+
+        if let Some(cseq) = self.cseq {
+            response.headers_mut().typed_insert(cseq);
+        }
+
+        Ok(Async::Ready(response))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::convert::TryFrom;
+
+    use futures::Future;
+    use tower_layer::Layer;
+    use tower_service::Service;
+
+    use crate::{
+        header::{map::HeaderMapExtension, types::CSeq},
+        middleware::{
+            cseq::CSeqLayer,
+            test::{request, response, FnService},
+        },
+    };
+
+    #[test]
+    fn test_cseq_echoed() {
+        let mut service = CSeqLayer::new().layer(FnService(|_| {
+            let mut response = response();
+            response
+                .headers_mut()
+                .typed_insert(CSeq::try_from(1).unwrap());
+            Ok::<_, ()>(response)
+        }));
+
+        let mut request = request("rtsp://example.com/");
+        request
+            .headers_mut()
+            .typed_insert(CSeq::try_from(835).unwrap());
+        let response = service.call(request).wait().unwrap();
+        assert_eq!(
+            response.headers().typed_get::<CSeq>(),
+            Some(CSeq::try_from(835).unwrap())
+        );
+    }
+}
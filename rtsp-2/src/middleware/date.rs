@@ -0,0 +1,82 @@
+//! Date
+//!
+//! A [`DateLayer`] adds a `"Date"` header with the current time to responses that do not have one.
+
+use futures::{try_ready, Async, Future, Poll};
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::{
+    header::{map::HeaderMapExtension, name::HeaderName, types::Date},
+    request::Request,
+    response::Response,
+};
+
+/// A layer wrapping services with a [`DateService`].
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct DateLayer;
+
+impl DateLayer {
+    /// Constructs a new layer.
+    pub fn new() -> Self {
+        DateLayer
+    }
+}
+
+impl<TService> Layer<TService> for DateLayer {
+    type Service = DateService<TService>;
+
+    fn layer(&self, inner: TService) -> Self::Service {
+        DateService { inner }
+    }
+}
+
+/// A service adding a `"Date"` header to the responses of the wrapped service that do not have
+/// one, dated when the response is ready.
+#[derive(Clone, Debug)]
+pub struct DateService<TService> {
+    inner: TService,
+}
+
+impl<TService, TRequestBody, TResponseBody> Service<Request<TRequestBody>> for DateService<TService>
+where
+    TService: Service<Request<TRequestBody>, Response = Response<TResponseBody>>,
+{
+    type Response = Response<TResponseBody>;
+    type Error = TService::Error;
+    type Future = DateFuture<TService::Future>;
+
+    fn call(&mut self, request: Request<TRequestBody>) -> Self::Future {
+        DateFuture {
+            inner: self.inner.call(request),
+        }
+    }
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        self.inner.poll_ready()
+    }
+}
+
+/// The future returned by a [`DateService`].
+#[must_use = "futures do nothing unless polled"]
+pub struct DateFuture<TFuture> {
+    inner: TFuture,
+}
+
+impl<TFuture, TBody> Future for DateFuture<TFuture>
+where
+    TFuture: Future<Item = Response<TBody>>,
+{
+    type Item = Response<TBody>;
+    type Error = TFuture::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        let mut response = try_ready!(self.inner.poll());
+
+        if !response.headers().contains_key(&HeaderName::Date) {
+            response.headers_mut().typed_insert(Date::new());
+        }
+
+        Ok(Async::Ready(response))
+    }
+}
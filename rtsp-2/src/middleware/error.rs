@@ -0,0 +1,113 @@
+//! Error
+//!
+//! An [`ErrorResponseLayer`] answers requests the wrapped service fails to handle with a 500
+//! (Internal Server Error) response, so that the client is never left without a response.
+
+use futures::{Async, Future, Poll};
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::{
+    middleware::status_response, request::Request, response::Response, status::StatusCode,
+    version::Version,
+};
+
+/// A layer wrapping services with an [`ErrorResponseService`].
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct ErrorResponseLayer;
+
+impl ErrorResponseLayer {
+    /// Constructs a new layer.
+    pub fn new() -> Self {
+        ErrorResponseLayer
+    }
+}
+
+impl<TService> Layer<TService> for ErrorResponseLayer {
+    type Service = ErrorResponseService<TService>;
+
+    fn layer(&self, inner: TService) -> Self::Service {
+        ErrorResponseService { inner }
+    }
+}
+
+/// A service converting the errors of the wrapped service into 500 (Internal Server Error)
+/// responses.
+///
+/// The error type is kept so that the service can be used wherever the wrapped service was, but
+/// handling requests never fails. Errors of [`Service::poll_ready`] are passed through.
+#[derive(Clone, Debug)]
+pub struct ErrorResponseService<TService> {
+    inner: TService,
+}
+
+impl<TService, TRequestBody, TResponseBody> Service<Request<TRequestBody>>
+    for ErrorResponseService<TService>
+where
+    TService: Service<Request<TRequestBody>, Response = Response<TResponseBody>>,
+    TResponseBody: Default,
+{
+    type Response = Response<TResponseBody>;
+    type Error = TService::Error;
+    type Future = ErrorResponseFuture<TService::Future>;
+
+    fn call(&mut self, request: Request<TRequestBody>) -> Self::Future {
+        ErrorResponseFuture {
+            version: request.version(),
+            inner: self.inner.call(request),
+        }
+    }
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        self.inner.poll_ready()
+    }
+}
+
+/// The future returned by an [`ErrorResponseService`].
+#[must_use = "futures do nothing unless polled"]
+pub struct ErrorResponseFuture<TFuture> {
+    inner: TFuture,
+    version: Version,
+}
+
+impl<TFuture, TBody> Future for ErrorResponseFuture<TFuture>
+where
+    TFuture: Future<Item = Response<TBody>>,
+    TBody: Default,
+{
+    type Item = Response<TBody>;
+    type Error = TFuture::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        match self.inner.poll() {
+            Err(_) => Ok(Async::Ready(status_response(
+                StatusCode::InternalServerError,
+                self.version,
+            ))),
+            result => result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use futures::Future;
+    use tower_layer::Layer;
+    use tower_service::Service;
+
+    use crate::{
+        middleware::{
+            error::ErrorResponseLayer,
+            test::{request, FnService},
+        },
+        response::Response,
+        status::StatusCode,
+    };
+
+    #[test]
+    fn test_errors_become_internal_server_errors() {
+        let mut service = ErrorResponseLayer::new().layer(FnService(|_| Err::<Response<_>, _>(())));
+        let response = service.call(request("rtsp://example.com/")).wait().unwrap();
+        assert_eq!(response.status_code(), StatusCode::InternalServerError);
+    }
+}
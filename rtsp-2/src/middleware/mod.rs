@@ -0,0 +1,103 @@
+//! Middleware
+//!
+//! This module contains [`tower_layer::Layer`]s that wrap RTSP services, such as the ones a
+//! [`Connection`] serves requests with, to handle concerns shared by many services. Each layer
+//! handles a single concern, so servers can compose only the ones they need:
+//!
+//! ```
+//! use rtsp::middleware::{
+//!     catch_panic::CatchPanicLayer, cseq::CSeqLayer, error::ErrorResponseLayer,
+//!     normalize::NormalizeURILayer,
+//! };
+//! use rtsp::protocol::service::EmptyService;
+//! use tower_layer::Layer;
+//!
+//! let service = CatchPanicLayer::new().layer(
+//!     CSeqLayer::new().layer(
+//!         ErrorResponseLayer::new().layer(NormalizeURILayer::new().layer(EmptyService)),
+//!     ),
+//! );
+//! ```
+//!
+//! Layers that answer requests themselves, such as when a request times out, respond with the
+//! protocol version of the request.
+//!
+//! [`Connection`]: crate::protocol::connection::Connection
+
+pub mod auth;
+pub mod catch_panic;
+pub mod concurrency;
+pub mod cseq;
+pub mod date;
+pub mod error;
+pub mod normalize;
+pub mod server_header;
+pub mod timeout;
+pub mod via;
+
+use crate::{response::Response, status::StatusCode, version::Version};
+
+/// Returns a response with the given status code, protocol version and an empty body.
+pub(crate) fn status_response<TBody>(status_code: StatusCode, version: Version) -> Response<TBody>
+where
+    TBody: Default,
+{
+    Response::<()>::builder()
+        .with_status_code(status_code)
+        .with_version(version)
+        .with_body(TBody::default())
+        .build()
+        .expect("status response should be valid")
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use bytes::BytesMut;
+    use futures::{Async, IntoFuture, Poll};
+    use tower_service::Service;
+
+    use crate::{request::Request, response::Response};
+
+    /// A service responding to requests through the given function.
+    pub(crate) struct FnService<TFunction>(pub(crate) TFunction);
+
+    impl<TFunction, TFuture> Service<Request<BytesMut>> for FnService<TFunction>
+    where
+        TFunction: FnMut(Request<BytesMut>) -> TFuture,
+        TFuture: IntoFuture<Item = Response<BytesMut>>,
+    {
+        type Response = Response<BytesMut>;
+        type Error = TFuture::Error;
+        type Future = TFuture::Future;
+
+        fn call(&mut self, request: Request<BytesMut>) -> Self::Future {
+            (self.0)(request).into_future()
+        }
+
+        fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+            Ok(Async::Ready(()))
+        }
+    }
+
+    /// Returns an empty request for the given URI.
+    pub(crate) fn request(uri: &str) -> Request<BytesMut> {
+        use std::convert::TryFrom;
+
+        use crate::{method::Method, uri::request::URI};
+
+        Request::<()>::builder()
+            .with_method(Method::Options)
+            .with_uri(URI::try_from(uri).unwrap())
+            .with_body(BytesMut::new())
+            .build()
+            .unwrap()
+    }
+
+    /// Returns an empty 200 (OK) response.
+    pub(crate) fn response() -> Response<BytesMut> {
+        Response::<()>::builder()
+            .with_body(BytesMut::new())
+            .build()
+            .unwrap()
+    }
+}
@@ -0,0 +1,87 @@
+//! Normalize
+//!
+//! A [`NormalizeURILayer`] normalizes the URI of each request before it reaches the wrapped
+//! service, so that equivalent URIs such as `"rtsp://EXAMPLE.com/a/../b"` and
+//! `"rtsp://example.com/b"` are handled alike.
+
+use futures::Poll;
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::request::Request;
+
+/// A layer wrapping services with a [`NormalizeURIService`].
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct NormalizeURILayer;
+
+impl NormalizeURILayer {
+    /// Constructs a new layer.
+    pub fn new() -> Self {
+        NormalizeURILayer
+    }
+}
+
+impl<TService> Layer<TService> for NormalizeURILayer {
+    type Service = NormalizeURIService<TService>;
+
+    fn layer(&self, inner: TService) -> Self::Service {
+        NormalizeURIService { inner }
+    }
+}
+
+/// A service normalizing the URI of each request before passing it to the wrapped service.
+#[derive(Clone, Debug)]
+pub struct NormalizeURIService<TService> {
+    inner: TService,
+}
+
+impl<TService, TBody> Service<Request<TBody>> for NormalizeURIService<TService>
+where
+    TService: Service<Request<TBody>>,
+{
+    type Response = TService::Response;
+    type Error = TService::Error;
+    type Future = TService::Future;
+
+    fn call(&mut self, mut request: Request<TBody>) -> Self::Future {
+        request.uri_mut().normalize();
+        self.inner.call(request)
+    }
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        self.inner.poll_ready()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bytes::BytesMut;
+    use futures::Future;
+    use tower_layer::Layer;
+    use tower_service::Service;
+
+    use crate::{
+        middleware::{
+            normalize::NormalizeURILayer,
+            test::{request, response, FnService},
+        },
+        request::Request,
+    };
+
+    #[test]
+    fn test_uri_normalized() {
+        let mut service =
+            NormalizeURILayer::new().layer(FnService(|request: Request<BytesMut>| {
+                assert_eq!(
+                    request.uri().to_string(),
+                    "rtsp://example.com/live/trackID=0"
+                );
+                Ok::<_, ()>(response())
+            }));
+
+        service
+            .call(request("rtsp://EXAMPLE.com/live/../live/./trackID=0"))
+            .wait()
+            .unwrap();
+    }
+}
@@ -0,0 +1,157 @@
+//! Server Header
+//!
+//! A [`ServerHeaderLayer`] adds a `"Server"` header identifying the server software to responses
+//! that do not have one.
+
+use std::convert::TryFrom;
+
+use futures::{try_ready, Async, Future, Poll};
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::{
+    header::{name::HeaderName, value::HeaderValue},
+    request::Request,
+    response::Response,
+};
+
+/// A layer wrapping services with a [`ServerHeaderService`].
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct ServerHeaderLayer {
+    /// The value of the `"Server"` header.
+    value: HeaderValue,
+}
+
+impl ServerHeaderLayer {
+    /// Constructs a new layer adding the given `"Server"` header value, such as
+    /// `"camera-proxy/1.2"`.
+    pub fn new(value: HeaderValue) -> Self {
+        ServerHeaderLayer { value }
+    }
+
+    /// Returns the value of the `"Server"` header.
+    pub fn value(&self) -> &HeaderValue {
+        &self.value
+    }
+}
+
+impl Default for ServerHeaderLayer {
+    /// Constructs a new layer identifying this library, such as `"rtsp-2/0.1.0"`.
+    fn default() -> Self {
+        let value = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+        ServerHeaderLayer::new(HeaderValue::try_from(value).unwrap())
+    }
+}
+
+impl<TService> Layer<TService> for ServerHeaderLayer {
+    type Service = ServerHeaderService<TService>;
+
+    fn layer(&self, inner: TService) -> Self::Service {
+        ServerHeaderService {
+            inner,
+            value: self.value.clone(),
+        }
+    }
+}
+
+/// A service adding a `"Server"` header to the responses of the wrapped service that do not have
+/// one.
+#[derive(Clone, Debug)]
+pub struct ServerHeaderService<TService> {
+    inner: TService,
+    value: HeaderValue,
+}
+
+impl<TService, TRequestBody, TResponseBody> Service<Request<TRequestBody>>
+    for ServerHeaderService<TService>
+where
+    TService: Service<Request<TRequestBody>, Response = Response<TResponseBody>>,
+{
+    type Response = Response<TResponseBody>;
+    type Error = TService::Error;
+    type Future = ServerHeaderFuture<TService::Future>;
+
+    fn call(&mut self, request: Request<TRequestBody>) -> Self::Future {
+        ServerHeaderFuture {
+            inner: self.inner.call(request),
+            value: Some(self.value.clone()),
+        }
+    }
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        self.inner.poll_ready()
+    }
+}
+
+/// The future returned by a [`ServerHeaderService`].
+#[must_use = "futures do nothing unless polled"]
+pub struct ServerHeaderFuture<TFuture> {
+    inner: TFuture,
+    value: Option<HeaderValue>,
+}
+
+impl<TFuture, TBody> Future for ServerHeaderFuture<TFuture>
+where
+    TFuture: Future<Item = Response<TBody>>,
+{
+    type Item = Response<TBody>;
+    type Error = TFuture::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        let mut response = try_ready!(self.inner.poll());
+        let value = self
+            .value
+            .take()
+            .expect("`ServerHeaderFuture` polled after completion");
+
+        if !response.headers().contains_key(&HeaderName::Server) {
+            response.headers_mut().insert(HeaderName::Server, value);
+        }
+
+        Ok(Async::Ready(response))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::convert::TryFrom;
+
+    use futures::Future;
+    use tower_layer::Layer;
+    use tower_service::Service;
+
+    use crate::{
+        header::{map::HeaderMapExtension, name::HeaderName, types::Date, value::HeaderValue},
+        middleware::{
+            date::DateLayer,
+            server_header::ServerHeaderLayer,
+            test::{request, response, FnService},
+        },
+    };
+
+    #[test]
+    fn test_headers_added_unless_present() {
+        let mut service = ServerHeaderLayer::default()
+            .layer(DateLayer::new().layer(FnService(|_| Ok::<_, ()>(response()))));
+        let received = service.call(request("rtsp://example.com/")).wait().unwrap();
+        assert_eq!(
+            received.headers().get(&HeaderName::Server),
+            Some(&HeaderValue::try_from("rtsp-2/0.1.0").unwrap())
+        );
+        assert!(received.headers().typed_get::<Date>().is_some());
+
+        let layer = ServerHeaderLayer::new(HeaderValue::try_from("outer").unwrap());
+        let mut service = layer.layer(FnService(|_| {
+            let mut response = response();
+            response
+                .headers_mut()
+                .insert(HeaderName::Server, HeaderValue::try_from("inner").unwrap());
+            Ok::<_, ()>(response)
+        }));
+        let received = service.call(request("rtsp://example.com/")).wait().unwrap();
+        assert_eq!(
+            received.headers().get(&HeaderName::Server),
+            Some(&HeaderValue::try_from("inner").unwrap())
+        );
+    }
+}
@@ -0,0 +1,153 @@
+//! Timeout
+//!
+//! A [`TimeoutLayer`] limits how long the wrapped service may take to handle a request, answering
+//! requests that take longer with a 503 (Service Unavailable) response.
+
+use std::time::{Duration, Instant};
+
+use futures::{Async, Future, Poll};
+use tokio_timer::Delay;
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::{
+    middleware::status_response, request::Request, response::Response, status::StatusCode,
+    version::Version,
+};
+
+/// A layer wrapping services with a [`TimeoutService`].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct TimeoutLayer {
+    /// How long the wrapped service may take to handle a request.
+    duration: Duration,
+}
+
+impl TimeoutLayer {
+    /// Returns how long the wrapped service may take to handle a request.
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    /// Constructs a new layer allowing the wrapped service the given duration to handle each
+    /// request.
+    pub fn new(duration: Duration) -> Self {
+        TimeoutLayer { duration }
+    }
+}
+
+impl<TService> Layer<TService> for TimeoutLayer {
+    type Service = TimeoutService<TService>;
+
+    fn layer(&self, inner: TService) -> Self::Service {
+        TimeoutService {
+            duration: self.duration,
+            inner,
+        }
+    }
+}
+
+/// A service answering requests the wrapped service does not handle in time with a 503 (Service
+/// Unavailable) response.
+///
+/// The future handling a request that timed out is dropped, so the wrapped service must not rely
+/// on it running to completion.
+#[derive(Clone, Debug)]
+pub struct TimeoutService<TService> {
+    duration: Duration,
+    inner: TService,
+}
+
+impl<TService, TRequestBody, TResponseBody> Service<Request<TRequestBody>>
+    for TimeoutService<TService>
+where
+    TService: Service<Request<TRequestBody>, Response = Response<TResponseBody>>,
+    TResponseBody: Default,
+{
+    type Response = Response<TResponseBody>;
+    type Error = TService::Error;
+    type Future = TimeoutFuture<TService::Future>;
+
+    fn call(&mut self, request: Request<TRequestBody>) -> Self::Future {
+        TimeoutFuture {
+            delay: Delay::new(Instant::now() + self.duration),
+            version: request.version(),
+            inner: self.inner.call(request),
+        }
+    }
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        self.inner.poll_ready()
+    }
+}
+
+/// The future returned by a [`TimeoutService`].
+#[must_use = "futures do nothing unless polled"]
+pub struct TimeoutFuture<TFuture> {
+    delay: Delay,
+    inner: TFuture,
+    version: Version,
+}
+
+impl<TFuture, TBody> Future for TimeoutFuture<TFuture>
+where
+    TFuture: Future<Item = Response<TBody>>,
+    TBody: Default,
+{
+    type Item = Response<TBody>;
+    type Error = TFuture::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        if let Async::Ready(response) = self.inner.poll()? {
+            return Ok(Async::Ready(response));
+        }
+
+        match self.delay.poll() {
+            Ok(Async::NotReady) => Ok(Async::NotReady),
+
+            // Timer errors only occur when the timer is shut down or at capacity, in which case the
+            // request is given up on as well.
+            _ => Ok(Async::Ready(status_response(
+                StatusCode::ServiceUnavailable,
+                self.version,
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use bytes::BytesMut;
+    use futures::future;
+    use tokio::runtime::current_thread::Runtime;
+    use tower_layer::Layer;
+    use tower_service::Service;
+
+    use crate::{
+        middleware::{
+            test::{request, response, FnService},
+            timeout::TimeoutLayer,
+        },
+        response::Response,
+        status::StatusCode,
+    };
+
+    #[test]
+    fn test_timeout() {
+        let mut runtime = Runtime::new().unwrap();
+        let layer = TimeoutLayer::new(Duration::from_millis(10));
+
+        let mut service = layer.layer(FnService(|_| future::empty::<Response<BytesMut>, ()>()));
+        let received = runtime
+            .block_on(service.call(request("rtsp://example.com/")))
+            .unwrap();
+        assert_eq!(received.status_code(), StatusCode::ServiceUnavailable);
+
+        let mut service = layer.layer(FnService(|_| future::ok::<_, ()>(response())));
+        let received = runtime
+            .block_on(service.call(request("rtsp://example.com/")))
+            .unwrap();
+        assert_eq!(received.status_code(), StatusCode::OK);
+    }
+}
@@ -0,0 +1,385 @@
+//! Via
+//!
+//! A [`ViaLayer`] records an intermediary in the `"Via"` header of the requests and responses that
+//! pass through the service it wraps, such as a service forwarding requests to another server. It
+//! also refuses requests that already passed through the intermediary, since forwarding them again
+//! would create a loop.
+
+use std::{convert::TryFrom, mem};
+
+use futures::{try_ready, Async, Future, Poll};
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::{
+    header::{
+        map::{HeaderMap, HeaderMapExtension},
+        name::HeaderName,
+        types::{via::ViaEntry, Via},
+        value::HeaderValue,
+    },
+    reason::ReasonPhrase,
+    request::Request,
+    response::Response,
+    status::StatusCode,
+    version::Version,
+};
+
+/// The status code requests that would loop are answered with. RTSP does not define one, so this
+/// is the `"Loop Detected"` status code of HTTP.
+const LOOP_DETECTED: u16 = 508;
+
+/// What happens to the `"Via"` header of messages passing through a [`ViaService`].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum ViaAction {
+    /// The entry of the intermediary is appended to the header, creating it if needed.
+    Append,
+
+    /// The header is left as it is.
+    Keep,
+
+    /// Every entry received by the intermediary is removed from the header, removing the header if
+    /// no entries are left.
+    Strip,
+}
+
+impl ViaAction {
+    /// Applies the action for the given entry to the given headers.
+    ///
+    /// Headers that cannot be decoded are only appended to, so that entries of other
+    /// intermediaries are never lost.
+    fn apply(self, headers: &mut HeaderMap, entry: &ViaEntry) {
+        use self::ViaAction::*;
+
+        match (self, headers.typed_try_get::<Via>()) {
+            (Append, Ok(via)) => {
+                let mut via = via.unwrap_or_default();
+                via.push(entry.clone());
+                headers.typed_insert(via);
+            }
+            (Append, Err(_)) => {
+                let value = HeaderValue::try_from(entry.to_string().as_str())
+                    .expect("`\"Via\"` entry should be a valid header value");
+                headers.append(HeaderName::Via, value);
+            }
+            (Strip, Ok(Some(mut via))) => {
+                if !via.strip(entry) {
+                    return;
+                }
+
+                if via.is_empty() {
+                    headers.remove(&HeaderName::Via);
+                } else {
+                    headers.typed_insert(via);
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+/// A layer wrapping services with a [`ViaService`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ViaLayer {
+    /// The entry identifying the intermediary.
+    entry: ViaEntry,
+
+    /// What happens to the `"Via"` header of requests.
+    request_action: ViaAction,
+
+    /// What happens to the `"Via"` header of responses.
+    response_action: ViaAction,
+}
+
+impl ViaLayer {
+    /// Returns the entry identifying the intermediary.
+    pub fn entry(&self) -> &ViaEntry {
+        &self.entry
+    }
+
+    /// Constructs a new layer for the intermediary identified by the given entry, which is
+    /// appended to both requests and responses by default.
+    pub fn new(entry: ViaEntry) -> Self {
+        ViaLayer {
+            entry,
+            request_action: ViaAction::Append,
+            response_action: ViaAction::Append,
+        }
+    }
+
+    /// Returns what happens to the `"Via"` header of requests.
+    pub fn request_action(&self) -> ViaAction {
+        self.request_action
+    }
+
+    /// Returns what happens to the `"Via"` header of responses.
+    pub fn response_action(&self) -> ViaAction {
+        self.response_action
+    }
+
+    /// Sets what happens to the `"Via"` header of requests.
+    pub fn set_request_action(&mut self, action: ViaAction) -> &mut Self {
+        self.request_action = action;
+        self
+    }
+
+    /// Sets what happens to the `"Via"` header of responses.
+    pub fn set_response_action(&mut self, action: ViaAction) -> &mut Self {
+        self.response_action = action;
+        self
+    }
+
+    /// Sets what happens to the `"Via"` header of requests.
+    pub fn with_request_action(mut self, action: ViaAction) -> Self {
+        self.set_request_action(action);
+        self
+    }
+
+    /// Sets what happens to the `"Via"` header of responses.
+    pub fn with_response_action(mut self, action: ViaAction) -> Self {
+        self.set_response_action(action);
+        self
+    }
+}
+
+impl<TService> Layer<TService> for ViaLayer {
+    type Service = ViaService<TService>;
+
+    fn layer(&self, inner: TService) -> Self::Service {
+        ViaService {
+            inner,
+            layer: self.clone(),
+        }
+    }
+}
+
+/// A service recording an intermediary in the `"Via"` header of the messages passing through the
+/// service it wraps.
+///
+/// When entries are appended to requests, requests whose `"Via"` header already contains an entry
+/// received by the intermediary are answered with `508 Loop Detected` instead of being passed on.
+#[derive(Clone, Debug)]
+pub struct ViaService<TService> {
+    inner: TService,
+    layer: ViaLayer,
+}
+
+impl<TService> ViaService<TService> {
+    /// Returns a reference to the wrapped service.
+    pub fn get_ref(&self) -> &TService {
+        &self.inner
+    }
+
+    /// Returns the wrapped service, consuming this service.
+    pub fn into_inner(self) -> TService {
+        self.inner
+    }
+}
+
+impl<TService, TRequestBody, TResponseBody> Service<Request<TRequestBody>> for ViaService<TService>
+where
+    TService: Service<Request<TRequestBody>, Response = Response<TResponseBody>>,
+    TResponseBody: Default,
+{
+    type Response = Response<TResponseBody>;
+    type Error = TService::Error;
+    type Future = ViaFuture<TService::Future, TResponseBody>;
+
+    fn call(&mut self, mut request: Request<TRequestBody>) -> Self::Future {
+        let entry = &self.layer.entry;
+
+        if self.layer.request_action == ViaAction::Append
+            && request
+                .headers()
+                .typed_get::<Via>()
+                .map_or(false, |via| via.detects_loop(entry))
+        {
+            let response = loop_detected_response(request.version(), TResponseBody::default());
+
+            return ViaFuture {
+                state: ViaFutureState::Loop(response),
+            };
+        }
+
+        self.layer
+            .request_action
+            .apply(request.headers_mut(), entry);
+
+        ViaFuture {
+            state: ViaFutureState::Inner {
+                action: self.layer.response_action,
+                entry: entry.clone(),
+                future: self.inner.call(request),
+            },
+        }
+    }
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        self.inner.poll_ready()
+    }
+}
+
+/// The future returned by a [`ViaService`].
+#[must_use = "futures do nothing unless polled"]
+pub struct ViaFuture<TFuture, TBody> {
+    state: ViaFutureState<TFuture, TBody>,
+}
+
+impl<TFuture, TBody> Future for ViaFuture<TFuture, TBody>
+where
+    TFuture: Future<Item = Response<TBody>>,
+{
+    type Item = Response<TBody>;
+    type Error = TFuture::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        use self::ViaFutureState::*;
+
+        match &mut self.state {
+            Inner {
+                action,
+                entry,
+                future,
+            } => {
+                let mut response = try_ready!(future.poll());
+                action.apply(response.headers_mut(), entry);
+                Ok(Async::Ready(response))
+            }
+            Loop(_) => match mem::replace(&mut self.state, Done) {
+                Loop(response) => Ok(Async::Ready(response)),
+                _ => unreachable!(),
+            },
+            Done => panic!("`ViaFuture` polled after completion"),
+        }
+    }
+}
+
+/// Returns the response to a request that would loop if it were passed on.
+pub(crate) fn loop_detected_response<TBody>(version: Version, body: TBody) -> Response<TBody> {
+    Response::<()>::builder()
+        .with_status_code(StatusCode::try_from(LOOP_DETECTED).unwrap())
+        .with_reason_phrase(Some(ReasonPhrase::try_from("Loop Detected").unwrap()))
+        .with_version(version)
+        .with_body(body)
+        .build()
+        .expect("loop detected response should be valid")
+}
+
+/// The state of a [`ViaFuture`].
+enum ViaFutureState<TFuture, TBody> {
+    /// The future already resolved to its response.
+    Done,
+
+    /// The request was passed on and the response will be recorded in.
+    Inner {
+        action: ViaAction,
+        entry: ViaEntry,
+        future: TFuture,
+    },
+
+    /// The request would have looped, so it is answered without being passed on.
+    Loop(Response<TBody>),
+}
+
+#[cfg(test)]
+mod test {
+    use std::convert::TryFrom;
+
+    use bytes::BytesMut;
+    use futures::{future, Future};
+    use tower_layer::Layer;
+    use tower_service::Service;
+
+    use crate::{
+        header::{
+            map::HeaderMapExtension,
+            name::HeaderName,
+            types::{via::ViaEntry, Via},
+            value::HeaderValue,
+        },
+        method::Method,
+        middleware::via::{ViaAction, ViaLayer},
+        protocol::service::EmptyService,
+        request::Request,
+        response::Response,
+        status::StatusCode,
+        uri::request::URI,
+    };
+
+    /// A service that echoes the `"Via"` header of the request in its response.
+    struct Echo;
+
+    impl Service<Request<BytesMut>> for Echo {
+        type Response = Response<BytesMut>;
+        type Error = ();
+        type Future = future::FutureResult<Self::Response, Self::Error>;
+
+        fn call(&mut self, request: Request<BytesMut>) -> Self::Future {
+            let mut builder = Response::<()>::builder();
+
+            for value in request.headers().get_all(&HeaderName::Via) {
+                builder.header(HeaderName::Via, value.clone());
+            }
+
+            future::ok(builder.with_body(BytesMut::new()).build().unwrap())
+        }
+
+        fn poll_ready(&mut self) -> futures::Poll<(), Self::Error> {
+            Ok(futures::Async::Ready(()))
+        }
+    }
+
+    fn request(via: Option<&str>) -> Request<BytesMut> {
+        let mut builder = Request::<()>::builder()
+            .with_method(Method::Options)
+            .with_uri(URI::try_from("rtsp://example.com/").unwrap());
+
+        if let Some(via) = via {
+            builder.header(HeaderName::Via, HeaderValue::try_from(via).unwrap());
+        }
+
+        builder.with_body(BytesMut::new()).build().unwrap()
+    }
+
+    #[test]
+    fn test_via_layer_appends_and_strips() {
+        let entry = ViaEntry::try_from("RTSP/2.0 proxy:8554").unwrap();
+        let mut service = ViaLayer::new(entry.clone()).layer(Echo);
+        let response = service
+            .call(request(Some("RTSP/2.0 client-proxy")))
+            .wait()
+            .unwrap();
+        let via = response.headers().typed_get::<Via>().unwrap();
+        assert_eq!(via.len(), 3);
+        assert!(via[1].is_received_by(&entry));
+        assert!(via[2].is_received_by(&entry));
+
+        let mut service = ViaLayer::new(entry.clone())
+            .with_response_action(ViaAction::Strip)
+            .layer(Echo);
+        let response = service.call(request(None)).wait().unwrap();
+        assert!(response.headers().get(&HeaderName::Via).is_none());
+
+        // Undecodable headers are appended to instead of being replaced.
+        let mut service = ViaLayer::new(entry)
+            .with_response_action(ViaAction::Keep)
+            .layer(Echo);
+        let response = service
+            .call(request(Some("HTTP/1.1 proxy")))
+            .wait()
+            .unwrap();
+        assert_eq!(response.headers().get_all(&HeaderName::Via).count(), 2);
+    }
+
+    #[test]
+    fn test_via_layer_detects_loops() {
+        let entry = ViaEntry::try_from("RTSP/2.0 proxy").unwrap();
+        let mut service = ViaLayer::new(entry).layer(EmptyService);
+        let response = service
+            .call(request(Some("RTSP/2.0 camera, RTSP/2.0 proxy:554")))
+            .wait()
+            .unwrap();
+        assert_eq!(response.status_code(), StatusCode::try_from(508).unwrap());
+    }
+}
@@ -15,6 +15,10 @@ macro_rules! try_complete {
 pub mod request;
 pub mod response;
 
+use std::fmt::{self, Display, Formatter};
+
+use crate::header::{map::HeaderMap, name::HeaderName, value::HeaderValue};
+
 /// The default maximum length a request or response body can be.
 const BODY_DEFAULT_MAX_LENGTH: usize = 65536;
 
@@ -237,6 +241,175 @@ impl<TResult, TError> From<Result<TResult, TError>> for DecodeResult<TResult, TE
     }
 }
 
+/// A way in which real agents commonly deviate from the message syntax, which decoders can be
+/// configured to tolerate using a [`Strictness`] policy.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[non_exhaustive]
+pub enum Deviation {
+    /// A line ended with a bare `"\n"` instead of `"\r\n"`.
+    BareLineFeed,
+
+    /// The `"Content-Length"` header was given more than once, always with the same value. The
+    /// duplicates are removed from the decoded message.
+    DuplicateContentLength,
+
+    /// The status line of a response ended right after the status code, without a reason phrase.
+    /// The canonical reason phrase of the status code is used instead, so this is not tolerated
+    /// for extension status codes.
+    MissingReasonPhrase,
+
+    /// A header value was continued on the next line by starting it with a space or a tab. This is
+    /// allowed by the syntax of RFC 7826, but not by the syntax of later HTTP specifications, and
+    /// is tolerated by default.
+    ObsoleteLineFolding,
+
+    /// A header name was followed by spaces or tabs before the colon. This is allowed by the syntax
+    /// of RFC 7826, but not by the syntax of later HTTP specifications, and is tolerated by
+    /// default.
+    SpaceBeforeColon,
+}
+
+impl Deviation {
+    /// Returns the bit representing this deviation in a [`Strictness`] policy.
+    fn bit(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+impl Display for Deviation {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        use self::Deviation::*;
+
+        match self {
+            BareLineFeed => write!(formatter, "bare line feed"),
+            DuplicateContentLength => write!(formatter, "duplicate content length"),
+            MissingReasonPhrase => write!(formatter, "missing reason phrase"),
+            ObsoleteLineFolding => write!(formatter, "obsolete line folding"),
+            SpaceBeforeColon => write!(formatter, "space before colon"),
+        }
+    }
+}
+
+/// A policy of which [`Deviation`]s a decoder tolerates. Messages with deviations that are not
+/// tolerated fail to decode.
+///
+/// By default, only the deviations allowed by the syntax of RFC 7826 are tolerated.
+///
+/// # Examples
+///
+/// ```
+/// use rtsp::protocol::codec::decoder::{Deviation, Strictness};
+///
+/// let strictness = Strictness::strict().with_tolerated(Deviation::BareLineFeed, true);
+/// assert!(strictness.tolerates(Deviation::BareLineFeed));
+/// assert!(!strictness.tolerates(Deviation::MissingReasonPhrase));
+/// ```
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Strictness {
+    /// The bits of the tolerated deviations.
+    tolerated: u8,
+}
+
+impl Strictness {
+    /// Constructs a policy tolerating every deviation.
+    pub fn lenient() -> Self {
+        use self::Deviation::*;
+
+        Strictness::strict()
+            .with_tolerated(BareLineFeed, true)
+            .with_tolerated(DuplicateContentLength, true)
+            .with_tolerated(MissingReasonPhrase, true)
+            .with_tolerated(ObsoleteLineFolding, true)
+            .with_tolerated(SpaceBeforeColon, true)
+    }
+
+    /// Sets whether the given deviation is tolerated.
+    pub fn set_tolerated(&mut self, deviation: Deviation, tolerated: bool) -> &mut Self {
+        if tolerated {
+            self.tolerated |= deviation.bit();
+        } else {
+            self.tolerated &= !deviation.bit();
+        }
+
+        self
+    }
+
+    /// Constructs a policy tolerating no deviations.
+    pub fn strict() -> Self {
+        Strictness { tolerated: 0 }
+    }
+
+    /// Returns whether the given deviation is tolerated.
+    pub fn tolerates(&self, deviation: Deviation) -> bool {
+        self.tolerated & deviation.bit() != 0
+    }
+
+    /// Sets whether the given deviation is tolerated.
+    pub fn with_tolerated(mut self, deviation: Deviation, tolerated: bool) -> Self {
+        self.set_tolerated(deviation, tolerated);
+        self
+    }
+}
+
+impl Default for Strictness {
+    fn default() -> Self {
+        Strictness::strict()
+            .with_tolerated(Deviation::ObsoleteLineFolding, true)
+            .with_tolerated(Deviation::SpaceBeforeColon, true)
+    }
+}
+
+/// Returns the value of the `"Content-Length"` header if it was given more than once, always with
+/// the same value.
+fn duplicate_content_length(headers: &HeaderMap) -> Option<HeaderValue> {
+    let mut values = headers.get_all(&HeaderName::ContentLength);
+    let value = values.next()?;
+    let mut duplicated = false;
+
+    for other_value in values {
+        if other_value != value {
+            return None;
+        }
+
+        duplicated = true;
+    }
+
+    if duplicated {
+        Some(value.clone())
+    } else {
+        None
+    }
+}
+
+/// Finds the first line ending in the given buffer, looking at no more than `max_length` bytes
+/// before it. The index and length of the line ending are returned, where the length is one for a
+/// bare `"\n"`.
+fn find_line_ending(buffer: &[u8], max_length: usize) -> Option<(usize, usize)> {
+    buffer
+        .iter()
+        .take(max_length + 1)
+        .position(|&byte| byte == b'\n')
+        .map(|index| match index.checked_sub(1) {
+            Some(index) if buffer[index] == b'\r' => (index, 2),
+            _ => (index, 1),
+        })
+}
+
+/// Replaces bare `"\n"` line endings in the given header value with `"\r\n"`.
+fn normalize_line_endings(value: &[u8]) -> Vec<u8> {
+    let mut normalized = Vec::with_capacity(value.len() + 1);
+
+    for (index, &byte) in value.iter().enumerate() {
+        if byte == b'\n' && (index == 0 || value[index - 1] != b'\r') {
+            normalized.push(b'\r');
+        }
+
+        normalized.push(byte);
+    }
+
+    normalized
+}
+
 /// Trims the given header name of any trailing spaces or tabs.
 fn trim_header_name(name: &[u8]) -> &[u8] {
     let mut index = name.len();
@@ -278,7 +451,38 @@ fn trim_header_value(value: &[u8]) -> &[u8] {
 
 #[cfg(test)]
 mod test {
-    use crate::protocol::codec::decoder;
+    use crate::protocol::codec::decoder::{self, Deviation, Strictness};
+
+    #[test]
+    fn test_find_line_ending() {
+        assert_eq!(decoder::find_line_ending(b"test\r\n", 10), Some((4, 2)));
+        assert_eq!(decoder::find_line_ending(b"test\n", 10), Some((4, 1)));
+        assert_eq!(decoder::find_line_ending(b"\n", 10), Some((0, 1)));
+        assert_eq!(decoder::find_line_ending(b"test\r\n", 3), None);
+        assert_eq!(decoder::find_line_ending(b"test", 10), None);
+    }
+
+    #[test]
+    fn test_normalize_line_endings() {
+        assert_eq!(
+            decoder::normalize_line_endings(b"a\n b\r\n c"),
+            b"a\r\n b\r\n c"
+        );
+        assert_eq!(decoder::normalize_line_endings(b"\n b"), b"\r\n b");
+    }
+
+    #[test]
+    fn test_strictness() {
+        let strictness = Strictness::default();
+        assert!(strictness.tolerates(Deviation::ObsoleteLineFolding));
+        assert!(strictness.tolerates(Deviation::SpaceBeforeColon));
+        assert!(!strictness.tolerates(Deviation::BareLineFeed));
+
+        let strictness = strictness.with_tolerated(Deviation::SpaceBeforeColon, false);
+        assert!(!strictness.tolerates(Deviation::SpaceBeforeColon));
+        assert!(Strictness::lenient().tolerates(Deviation::DuplicateContentLength));
+        assert!(!Strictness::strict().tolerates(Deviation::ObsoleteLineFolding));
+    }
 
     #[test]
     fn test_trim_header_name() {
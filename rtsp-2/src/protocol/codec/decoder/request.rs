@@ -56,7 +56,7 @@ use crate::{
     },
     method::{Method, MethodError},
     protocol::codec::decoder::{
-        self, DecodeResult as GenericDecodeResult, BODY_DEFAULT_MAX_LENGTH,
+        self, DecodeResult as GenericDecodeResult, Deviation, Strictness, BODY_DEFAULT_MAX_LENGTH,
         HEADER_DEFAULT_MAX_COUNT, HEADER_NAME_DEFAULT_MAX_LENGTH, HEADER_VALUE_DEFAULT_MAX_LENGTH,
         METHOD_DEFAULT_MAX_LENGTH, URI_DEFAULT_MAX_LENGTH,
     },
@@ -112,6 +112,12 @@ pub struct Config {
     /// The maximum length a method can be.
     method_max_length: usize,
 
+    /// Whether RTSP/1.0 requests are accepted in addition to RTSP/2.0 requests.
+    rtsp_1_0_allowed: bool,
+
+    /// The policy deciding which deviations from the syntax are tolerated.
+    strictness: Strictness,
+
     /// The maximum length a URI can be.
     uri_max_length: usize,
 }
@@ -147,6 +153,16 @@ impl Config {
         self.method_max_length
     }
 
+    /// Returns whether RTSP/1.0 requests are accepted in addition to RTSP/2.0 requests.
+    pub fn rtsp_1_0_allowed(&self) -> bool {
+        self.rtsp_1_0_allowed
+    }
+
+    /// Returns the policy deciding which deviations from the syntax are tolerated.
+    pub fn strictness(&self) -> Strictness {
+        self.strictness
+    }
+
     /// Returns the maximum length a URI can be.
     pub fn uri_max_length(&self) -> usize {
         self.uri_max_length
@@ -177,6 +193,12 @@ pub struct ConfigBuilder {
     /// The maximum length a method can be.
     method_max_length: usize,
 
+    /// Whether RTSP/1.0 requests are accepted in addition to RTSP/2.0 requests.
+    rtsp_1_0_allowed: bool,
+
+    /// The policy deciding which deviations from the syntax are tolerated.
+    strictness: Strictness,
+
     /// The maximum length a request URI can be.
     uri_max_length: usize,
 }
@@ -190,6 +212,8 @@ impl ConfigBuilder {
             header_name_max_length: self.header_name_max_length,
             header_value_max_length: self.header_value_max_length,
             method_max_length: self.method_max_length,
+            rtsp_1_0_allowed: self.rtsp_1_0_allowed,
+            strictness: self.strictness,
             uri_max_length: self.uri_max_length,
         }
     }
@@ -232,10 +256,25 @@ impl ConfigBuilder {
             header_name_max_length: HEADER_NAME_DEFAULT_MAX_LENGTH,
             header_value_max_length: HEADER_VALUE_DEFAULT_MAX_LENGTH,
             method_max_length: METHOD_DEFAULT_MAX_LENGTH,
+            rtsp_1_0_allowed: false,
+            strictness: Strictness::default(),
             uri_max_length: URI_DEFAULT_MAX_LENGTH,
         }
     }
 
+    /// Sets whether RTSP/1.0 requests are accepted in addition to RTSP/2.0 requests, which is
+    /// needed for agents that only speak RTSP/1.0, such as most publishing encoders.
+    pub fn rtsp_1_0_allowed(&mut self, allowed: bool) -> &mut Self {
+        self.rtsp_1_0_allowed = allowed;
+        self
+    }
+
+    /// Sets the policy deciding which deviations from the syntax are tolerated.
+    pub fn strictness(&mut self, strictness: Strictness) -> &mut Self {
+        self.strictness = strictness;
+        self
+    }
+
     /// Sets the maximum possible URI length.
     pub fn uri_max_length(&mut self, length: usize) -> &mut Self {
         self.uri_max_length = length;
@@ -272,6 +311,18 @@ impl ConfigBuilder {
         self
     }
 
+    /// Sets whether RTSP/1.0 requests are accepted in addition to RTSP/2.0 requests.
+    pub fn with_rtsp_1_0_allowed(mut self, allowed: bool) -> Self {
+        self.rtsp_1_0_allowed(allowed);
+        self
+    }
+
+    /// Sets the policy deciding which deviations from the syntax are tolerated.
+    pub fn with_strictness(mut self, strictness: Strictness) -> Self {
+        self.strictness(strictness);
+        self
+    }
+
     /// Sets the maximum possible URI length.
     pub fn with_uri_max_length(mut self, length: usize) -> Self {
         self.uri_max_length(length);
@@ -339,6 +390,9 @@ pub struct Decoder {
     /// current state is [`DecodeState::Body`].
     content_length: ContentLength,
 
+    /// The deviations tolerated while decoding the current request.
+    deviations: Vec<Deviation>,
+
     /// The current decode state of the request decoding.
     state: DecodeState<DecodeError>,
 }
@@ -515,13 +569,30 @@ impl Decoder {
                 }
 
                 let rest = &buffer[header_name.len() + 1..];
-                let header_value = try_complete!(self.decode_header_value(&rest));
-                let header_end_index = header_value.len() + 2;
+                let (header_value, line_ending_length) =
+                    try_complete!(self.decode_header_value(rest));
+                let header_end_index = header_value.len() + line_ending_length;
 
-                let header_name = decoder::trim_header_name(header_name);
+                let trimmed_header_name = decoder::trim_header_name(header_name);
+
+                if trimmed_header_name.len() != header_name.len() {
+                    try_complete!(self.tolerate(Deviation::SpaceBeforeColon));
+                }
+
+                // Continuation lines ending with a bare line feed are restored to the syntax header
+                // values expect.
+                let normalized_header_value;
+                let header_value = if self.deviations.contains(&Deviation::BareLineFeed)
+                    && header_value.contains(&b'\n')
+                {
+                    normalized_header_value = decoder::normalize_line_endings(header_value);
+                    &normalized_header_value[..]
+                } else {
+                    header_value
+                };
                 let header_value = decoder::trim_header_value(header_value);
 
-                match HeaderName::try_from(header_name) {
+                match HeaderName::try_from(trimmed_header_name) {
                     Ok(header_name) => match HeaderValue::try_from(header_value) {
                         Ok(header_value) => {
                             self.builder.header(header_name, header_value);
@@ -534,7 +605,26 @@ impl Decoder {
                 }
             }
             None => {
-                *buffer = &buffer[2..];
+                let line_ending_length = if buffer.starts_with(b"\r\n") { 2 } else { 1 };
+                *buffer = &buffer[line_ending_length..];
+
+                if let Some(content_length) =
+                    decoder::duplicate_content_length(&self.builder.headers)
+                {
+                    if !self
+                        .config
+                        .strictness()
+                        .tolerates(Deviation::DuplicateContentLength)
+                    {
+                        return Error(DecodeError::InvalidContentLength);
+                    }
+
+                    try_complete!(self.tolerate(Deviation::DuplicateContentLength));
+                    self.builder
+                        .headers
+                        .insert(HeaderName::ContentLength, content_length);
+                }
+
                 self.state = DecodeState::Body;
                 self.content_length = match self.builder.headers.typed_try_get::<ContentLength>() {
                     Ok(Some(content_length)) if *content_length > self.config.body_max_length() => {
@@ -562,6 +652,11 @@ impl Decoder {
             return Complete(None);
         }
 
+        if buffer.starts_with(b"\n") {
+            try_complete!(self.tolerate(Deviation::BareLineFeed));
+            return Complete(None);
+        }
+
         let max_length = self.config.header_name_max_length() + 1;
         let mut iter = buffer.iter().take(max_length);
 
@@ -572,33 +667,41 @@ impl Decoder {
         }
     }
 
-    /// Decodes a header value from the buffer.
+    /// Decodes a header value from the buffer, returning it along with the length of the line
+    /// ending following it.
     fn decode_header_value<'buffer>(
         &mut self,
         buffer: &'buffer [u8],
-    ) -> DecodeResult<&'buffer [u8]> {
+    ) -> DecodeResult<(&'buffer [u8], usize)> {
         use self::GenericDecodeResult::*;
 
         let max_length = self.config.header_value_max_length() + 1;
-        let iter = buffer
-            .windows(2)
-            .take(max_length)
-            .enumerate()
-            .filter(|&(_, bytes)| bytes == b"\r\n")
-            .map(|(i, _)| i);
-
-        for index in iter {
-            match buffer.get(index + 2) {
-                Some(&byte) if byte == b' ' || byte == b'\t' => continue,
-                Some(_) => return Complete(&buffer[0..index]),
+        let mut offset = 0;
+
+        loop {
+            let (index, length) = match decoder::find_line_ending(
+                &buffer[offset..],
+                max_length.saturating_sub(offset + 1),
+            ) {
+                Some((index, length)) => (offset + index, length),
+                None if buffer.len() >= max_length => {
+                    return Error(DecodeError::HeaderValueTooLong)
+                }
                 None => return Incomplete,
+            };
+
+            if length == 1 {
+                try_complete!(self.tolerate(Deviation::BareLineFeed));
             }
-        }
 
-        if buffer.len() >= max_length {
-            Error(DecodeError::HeaderValueTooLong)
-        } else {
-            Incomplete
+            match buffer.get(index + length) {
+                Some(&byte) if byte == b' ' || byte == b'\t' => {
+                    try_complete!(self.tolerate(Deviation::ObsoleteLineFolding));
+                    offset = index + length;
+                }
+                Some(_) => return Complete((&buffer[0..index], length)),
+                None => return Incomplete,
+            }
         }
     }
 
@@ -613,6 +716,7 @@ impl Decoder {
             Some(index) => match Method::try_from(&buffer[0..index]) {
                 Ok(method) => {
                     self.builder.method(method);
+                    self.deviations.clear();
                     self.state = DecodeState::URI;
                     *buffer = &buffer[index + 1..];
                     Complete(())
@@ -650,19 +754,28 @@ impl Decoder {
     fn decode_version<'buffer>(&mut self, buffer: &mut &'buffer [u8]) -> DecodeResult<()> {
         use self::GenericDecodeResult::*;
 
-        if buffer.len() < 10 {
+        if buffer.len() < 9 {
             return Incomplete;
         }
 
-        if &buffer[8..10] != b"\r\n" {
-            return Error(DecodeError::Version(VersionDecodeError::Invalid));
-        }
+        let length = match &buffer[8..] {
+            [b'\n', ..] => {
+                try_complete!(self.tolerate(Deviation::BareLineFeed));
+                9
+            }
+            [b'\r'] => return Incomplete,
+            [b'\r', b'\n', ..] => 10,
+            _ => return Error(DecodeError::Version(VersionDecodeError::Invalid)),
+        };
 
         match Version::try_from(&buffer[0..8]) {
-            Ok(version) if version == Version::Rtsp2_0 => {
+            Ok(version)
+                if version == Version::Rtsp2_0
+                    || (version == Version::Rtsp1_0 && self.config.rtsp_1_0_allowed()) =>
+            {
                 self.builder.version(version);
                 self.state = DecodeState::Header;
-                *buffer = &buffer[10..];
+                *buffer = &buffer[length..];
                 Complete(())
             }
             Ok(_) => Error(DecodeError::UnsupportedVersion),
@@ -670,6 +783,12 @@ impl Decoder {
         }
     }
 
+    /// Returns the deviations tolerated while decoding the current request, or the last decoded
+    /// request if decoding of another one has not started yet.
+    pub fn deviations(&self) -> &[Deviation] {
+        &self.deviations
+    }
+
     /// Constructs a new request decoder.
     pub fn new() -> Self {
         Decoder::with_config(Config::default())
@@ -711,6 +830,7 @@ impl Decoder {
     pub fn reset(&mut self) {
         self.builder = RequestBuilder::new();
         self.content_length = ContentLength::default();
+        self.deviations.clear();
         self.state = DecodeState::Method;
     }
 
@@ -757,12 +877,28 @@ impl Decoder {
         self.state.clone()
     }
 
+    /// Records the given deviation if it is tolerated, failing otherwise.
+    fn tolerate(&mut self, deviation: Deviation) -> DecodeResult<()> {
+        use self::GenericDecodeResult::*;
+
+        if !self.config.strictness().tolerates(deviation) {
+            return Error(DecodeError::Deviation(deviation));
+        }
+
+        if !self.deviations.contains(&deviation) {
+            self.deviations.push(deviation);
+        }
+
+        Complete(())
+    }
+
     /// Constructs a new request decoder with the given configuration.
     pub fn with_config(config: Config) -> Self {
         Decoder {
             builder: RequestBuilder::new(),
             config,
             content_length: ContentLength::default(),
+            deviations: Vec::new(),
             state: DecodeState::Method,
         }
     }
@@ -781,6 +917,9 @@ pub enum DecodeError {
     /// The body, as determined by the content length header, was too long.
     BodyTooLong,
 
+    /// The request deviated from the syntax in a way that is not tolerated.
+    Deviation(Deviation),
+
     /// There was an error decoding a header name.
     HeaderName(HeaderNameError),
 
@@ -824,6 +963,7 @@ impl Display for DecodeError {
 
         match self {
             BodyTooLong => write!(formatter, "body too long"),
+            Deviation(deviation) => write!(formatter, "{} not tolerated", deviation),
             HeaderName(error) => error.fmt(formatter),
             HeaderNameTooLong => write!(formatter, "header name too long"),
             HeaderValue(error) => error.fmt(formatter),
@@ -881,13 +1021,44 @@ impl From<VersionDecodeError> for DecodeError {
 #[cfg(test)]
 mod test {
     use crate::{
-        header::name::HeaderNameError,
+        header::name::{HeaderName, HeaderNameError},
         method::MethodError,
-        protocol::codec::decoder::request::{ConfigBuilder, DecodeError, DecodeResult, Decoder},
+        protocol::codec::decoder::{
+            request::{Config, ConfigBuilder, DecodeError, DecodeResult, Decoder},
+            Deviation, Strictness,
+        },
         uri::request::URIError,
-        version::DecodeError as VersionDecodeError,
+        version::{DecodeError as VersionDecodeError, Version},
     };
 
+    #[test]
+    fn test_decoder_decode_bare_line_feed() {
+        let buffer = "OPTIONS * RTSP/2.0\n\
+                      CSeq: 1\n\
+                      \n";
+        let mut decoder = Decoder::new();
+        let (result, _) = decoder.decode(buffer);
+        assert_eq!(
+            result,
+            DecodeResult::Error(DecodeError::Deviation(Deviation::BareLineFeed))
+        );
+
+        let config = Config::builder()
+            .with_strictness(Strictness::lenient())
+            .build();
+        let mut decoder = Decoder::with_config(config);
+        let (result, bytes_decoded) = decoder.decode(buffer);
+        assert_eq!(bytes_decoded, buffer.len());
+        assert_eq!(decoder.deviations(), &[Deviation::BareLineFeed]);
+
+        match result {
+            DecodeResult::Complete(request) => {
+                assert_eq!(request.headers().get(&HeaderName::CSeq).unwrap(), "1")
+            }
+            result => panic!("unexpected decode result: {:?}", result),
+        }
+    }
+
     #[test]
     fn test_decoder_decode_body_invalid_content_length() {
         let buffer = "SETUP * RTSP/2.0\r\n\
@@ -916,6 +1087,34 @@ mod test {
         assert_eq!(result, DecodeResult::Error(DecodeError::BodyTooLong));
     }
 
+    #[test]
+    fn test_decoder_decode_duplicate_content_length() {
+        let buffer = "SET_PARAMETER * RTSP/2.0\r\n\
+                      Content-Length: 4\r\n\
+                      Content-Length: 4\r\n\
+                      \r\n\
+                      body";
+        let mut decoder = Decoder::new();
+        let (result, _) = decoder.decode(buffer);
+        assert_eq!(
+            result,
+            DecodeResult::Error(DecodeError::InvalidContentLength)
+        );
+
+        let config = Config::builder()
+            .with_strictness(Strictness::lenient())
+            .build();
+        let mut decoder = Decoder::with_config(config);
+        let (result, bytes_decoded) = decoder.decode(buffer);
+        assert_eq!(bytes_decoded, buffer.len());
+        assert_eq!(decoder.deviations(), &[Deviation::DuplicateContentLength]);
+
+        match result {
+            DecodeResult::Complete(request) => assert_eq!(request.body(), "body"),
+            result => panic!("unexpected decode result: {:?}", result),
+        }
+    }
+
     #[test]
     fn test_decoder_decode_header_name_empty() {
         let buffer = "SETUP * RTSP/2.0\r\n\
@@ -1046,6 +1245,21 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_decoder_decode_uri_schemeless_colon_segment() {
+        let buffer = "SETUP ://;192* RTSP/2.0\r\n\
+                      \r\n";
+        let mut decoder = Decoder::new();
+        let (result, bytes_decoded) = decoder.decode(buffer);
+        assert_ne!(bytes_decoded, buffer.len());
+        assert_eq!(
+            result,
+            DecodeResult::Error(DecodeError::URI(
+                URIError::SchemelessPathStartsWithColonSegment
+            ))
+        );
+    }
+
     #[test]
     fn test_decoder_decode_uri_too_long() {
         let buffer = "GET rtsp://example.com/my/long/uri/path RTSP/2.0\r\n\
@@ -1092,4 +1306,23 @@ mod test {
         assert_ne!(bytes_decoded, buffer.len());
         assert_eq!(result, DecodeResult::Error(DecodeError::UnsupportedVersion));
     }
+
+    #[test]
+    fn test_decoder_decode_version_rtsp_1_0_allowed() {
+        let buffer = "ANNOUNCE rtsp://example.com/live RTSP/1.0\r\n\
+                      CSeq: 1\r\n\
+                      \r\n";
+        let config = Config::builder().with_rtsp_1_0_allowed(true).build();
+        let mut decoder = Decoder::with_config(config);
+        let (result, bytes_decoded) = decoder.decode(buffer);
+        assert_eq!(bytes_decoded, buffer.len());
+
+        match result {
+            DecodeResult::Complete(request) => {
+                assert_eq!(request.method(), &rtsp_1::method::Method::Announce);
+                assert_eq!(request.version(), Version::Rtsp1_0);
+            }
+            result => panic!("unexpected decode result: {:?}", result),
+        }
+    }
 }
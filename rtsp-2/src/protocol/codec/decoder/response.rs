@@ -51,7 +51,7 @@ use crate::{
         value::{HeaderValue, HeaderValueError},
     },
     protocol::codec::decoder::{
-        self, DecodeResult as GenericDecodeResult, BODY_DEFAULT_MAX_LENGTH,
+        self, DecodeResult as GenericDecodeResult, Deviation, Strictness, BODY_DEFAULT_MAX_LENGTH,
         HEADER_DEFAULT_MAX_COUNT, HEADER_NAME_DEFAULT_MAX_LENGTH, HEADER_VALUE_DEFAULT_MAX_LENGTH,
         REASON_PHRASE_DEFAULT_MAX_LENGTH,
     },
@@ -107,6 +107,12 @@ pub struct Config {
 
     /// The maximum length a reason phrase can be.
     reason_phrase_max_length: usize,
+
+    /// Whether RTSP/1.0 responses are accepted in addition to RTSP/2.0 responses.
+    rtsp_1_0_allowed: bool,
+
+    /// The policy deciding which deviations from the syntax are tolerated.
+    strictness: Strictness,
 }
 
 impl Config {
@@ -139,6 +145,16 @@ impl Config {
     pub fn reason_phrase_max_length(&self) -> usize {
         self.reason_phrase_max_length
     }
+
+    /// Returns whether RTSP/1.0 responses are accepted in addition to RTSP/2.0 responses.
+    pub fn rtsp_1_0_allowed(&self) -> bool {
+        self.rtsp_1_0_allowed
+    }
+
+    /// Returns the policy deciding which deviations from the syntax are tolerated.
+    pub fn strictness(&self) -> Strictness {
+        self.strictness
+    }
 }
 
 impl Default for Config {
@@ -164,6 +180,12 @@ pub struct ConfigBuilder {
 
     /// The maximum length a reason phrase can be.
     reason_phrase_max_length: usize,
+
+    /// Whether RTSP/1.0 responses are accepted in addition to RTSP/2.0 responses.
+    rtsp_1_0_allowed: bool,
+
+    /// The policy deciding which deviations from the syntax are tolerated.
+    strictness: Strictness,
 }
 
 impl ConfigBuilder {
@@ -175,6 +197,8 @@ impl ConfigBuilder {
             header_name_max_length: self.header_name_max_length,
             header_value_max_length: self.header_value_max_length,
             reason_phrase_max_length: self.reason_phrase_max_length,
+            rtsp_1_0_allowed: self.rtsp_1_0_allowed,
+            strictness: self.strictness,
         }
     }
 
@@ -210,6 +234,8 @@ impl ConfigBuilder {
             header_name_max_length: HEADER_NAME_DEFAULT_MAX_LENGTH,
             header_value_max_length: HEADER_VALUE_DEFAULT_MAX_LENGTH,
             reason_phrase_max_length: REASON_PHRASE_DEFAULT_MAX_LENGTH,
+            rtsp_1_0_allowed: false,
+            strictness: Strictness::default(),
         }
     }
 
@@ -219,6 +245,19 @@ impl ConfigBuilder {
         self
     }
 
+    /// Sets whether RTSP/1.0 responses are accepted in addition to RTSP/2.0 responses, which is
+    /// needed for agents that only speak RTSP/1.0, such as most cameras.
+    pub fn rtsp_1_0_allowed(&mut self, allowed: bool) -> &mut Self {
+        self.rtsp_1_0_allowed = allowed;
+        self
+    }
+
+    /// Sets the policy deciding which deviations from the syntax are tolerated.
+    pub fn strictness(&mut self, strictness: Strictness) -> &mut Self {
+        self.strictness = strictness;
+        self
+    }
+
     /// Sets the maximum possible body length.
     pub fn with_body_max_length(mut self, length: usize) -> Self {
         self.body_max_length(length);
@@ -248,6 +287,18 @@ impl ConfigBuilder {
         self.reason_phrase_max_length(length);
         self
     }
+
+    /// Sets whether RTSP/1.0 responses are accepted in addition to RTSP/2.0 responses.
+    pub fn with_rtsp_1_0_allowed(mut self, allowed: bool) -> Self {
+        self.rtsp_1_0_allowed(allowed);
+        self
+    }
+
+    /// Sets the policy deciding which deviations from the syntax are tolerated.
+    pub fn with_strictness(mut self, strictness: Strictness) -> Self {
+        self.strictness(strictness);
+        self
+    }
 }
 
 impl Default for ConfigBuilder {
@@ -306,6 +357,9 @@ pub struct Decoder {
     /// current state is [`DecodeState::Body`].
     content_length: ContentLength,
 
+    /// The deviations tolerated while decoding the current response.
+    deviations: Vec<Deviation>,
+
     /// The current decode state of the response decoding.
     state: DecodeState<DecodeError>,
 }
@@ -471,13 +525,30 @@ impl Decoder {
                 }
 
                 let rest = &buffer[header_name.len() + 1..];
-                let header_value = try_complete!(self.decode_header_value(&rest));
-                let header_end_index = header_value.len() + 2;
+                let (header_value, line_ending_length) =
+                    try_complete!(self.decode_header_value(rest));
+                let header_end_index = header_value.len() + line_ending_length;
 
-                let header_name = decoder::trim_header_name(header_name);
+                let trimmed_header_name = decoder::trim_header_name(header_name);
+
+                if trimmed_header_name.len() != header_name.len() {
+                    try_complete!(self.tolerate(Deviation::SpaceBeforeColon));
+                }
+
+                // Continuation lines ending with a bare line feed are restored to the syntax header
+                // values expect.
+                let normalized_header_value;
+                let header_value = if self.deviations.contains(&Deviation::BareLineFeed)
+                    && header_value.contains(&b'\n')
+                {
+                    normalized_header_value = decoder::normalize_line_endings(header_value);
+                    &normalized_header_value[..]
+                } else {
+                    header_value
+                };
                 let header_value = decoder::trim_header_value(header_value);
 
-                match HeaderName::try_from(header_name) {
+                match HeaderName::try_from(trimmed_header_name) {
                     Ok(header_name) => match HeaderValue::try_from(header_value) {
                         Ok(header_value) => {
                             self.builder.header(header_name, header_value);
@@ -490,7 +561,26 @@ impl Decoder {
                 }
             }
             None => {
-                *buffer = &buffer[2..];
+                let line_ending_length = if buffer.starts_with(b"\r\n") { 2 } else { 1 };
+                *buffer = &buffer[line_ending_length..];
+
+                if let Some(content_length) =
+                    decoder::duplicate_content_length(&self.builder.headers)
+                {
+                    if !self
+                        .config
+                        .strictness()
+                        .tolerates(Deviation::DuplicateContentLength)
+                    {
+                        return Error(DecodeError::InvalidContentLength);
+                    }
+
+                    try_complete!(self.tolerate(Deviation::DuplicateContentLength));
+                    self.builder
+                        .headers
+                        .insert(HeaderName::ContentLength, content_length);
+                }
+
                 self.state = DecodeState::Body;
                 self.content_length = match self.builder.headers.typed_try_get::<ContentLength>() {
                     Ok(Some(content_length)) if *content_length > self.config.body_max_length() => {
@@ -518,6 +608,11 @@ impl Decoder {
             return Complete(None);
         }
 
+        if buffer.starts_with(b"\n") {
+            try_complete!(self.tolerate(Deviation::BareLineFeed));
+            return Complete(None);
+        }
+
         let max_length = self.config.header_name_max_length() + 1;
         let mut iter = buffer.iter().take(max_length);
 
@@ -528,33 +623,41 @@ impl Decoder {
         }
     }
 
-    /// Decodes a header value from the buffer.
+    /// Decodes a header value from the buffer, returning it along with the length of the line
+    /// ending following it.
     fn decode_header_value<'buffer>(
         &mut self,
         buffer: &'buffer [u8],
-    ) -> DecodeResult<&'buffer [u8]> {
+    ) -> DecodeResult<(&'buffer [u8], usize)> {
         use self::GenericDecodeResult::*;
 
         let max_length = self.config.header_value_max_length() + 1;
-        let iter = buffer
-            .windows(2)
-            .take(max_length)
-            .enumerate()
-            .filter(|&(_, b)| b == b"\r\n")
-            .map(|(i, _)| i);
-
-        for index in iter {
-            match buffer.get(index + 2) {
-                Some(&byte) if byte == b' ' || byte == b'\t' => continue,
-                Some(_) => return Complete(&buffer[0..index]),
+        let mut offset = 0;
+
+        loop {
+            let (index, length) = match decoder::find_line_ending(
+                &buffer[offset..],
+                max_length.saturating_sub(offset + 1),
+            ) {
+                Some((index, length)) => (offset + index, length),
+                None if buffer.len() >= max_length => {
+                    return Error(DecodeError::HeaderValueTooLong)
+                }
                 None => return Incomplete,
+            };
+
+            if length == 1 {
+                try_complete!(self.tolerate(Deviation::BareLineFeed));
             }
-        }
 
-        if buffer.len() >= max_length {
-            Error(DecodeError::HeaderValueTooLong)
-        } else {
-            Incomplete
+            match buffer.get(index + length) {
+                Some(&byte) if byte == b' ' || byte == b'\t' => {
+                    try_complete!(self.tolerate(Deviation::ObsoleteLineFolding));
+                    offset = index + length;
+                }
+                Some(_) => return Complete((&buffer[0..index], length)),
+                None => return Incomplete,
+            }
         }
     }
 
@@ -564,22 +667,38 @@ impl Decoder {
 
         let max_length = self.config.reason_phrase_max_length() + 2;
 
-        match buffer
-            .windows(2)
-            .take(max_length)
-            .position(|bytes| bytes == b"\r\n")
+        let (index, length) = match decoder::find_line_ending(buffer, max_length) {
+            Some(line_ending) => line_ending,
+            None if buffer.len() >= max_length => return Error(DecodeError::ReasonPhraseTooLong),
+            None => return Incomplete,
+        };
+
+        if length == 1 {
+            try_complete!(self.tolerate(Deviation::BareLineFeed));
+        }
+
+        if index == 0
+            && !matches!(self.builder.status_code, StatusCode::Extension(_))
+            && self
+                .config
+                .strictness()
+                .tolerates(Deviation::MissingReasonPhrase)
         {
-            Some(index) => match ReasonPhrase::try_from(&buffer[0..index]) {
-                Ok(reason_phrase) => {
-                    self.builder.reason_phrase(Some(reason_phrase));
-                    self.state = DecodeState::Header;
-                    *buffer = &buffer[index + 2..];
-                    Complete(())
-                }
-                Err(error) => Error(error.into()),
-            },
-            None if buffer.len() >= max_length => Error(DecodeError::ReasonPhraseTooLong),
-            None => Incomplete,
+            try_complete!(self.tolerate(Deviation::MissingReasonPhrase));
+            self.builder.reason_phrase(None);
+            self.state = DecodeState::Header;
+            *buffer = &buffer[length..];
+            return Complete(());
+        }
+
+        match ReasonPhrase::try_from(&buffer[0..index]) {
+            Ok(reason_phrase) => {
+                self.builder.reason_phrase(Some(reason_phrase));
+                self.state = DecodeState::Header;
+                *buffer = &buffer[index + length..];
+                Complete(())
+            }
+            Err(error) => Error(error.into()),
         }
     }
 
@@ -591,15 +710,26 @@ impl Decoder {
             return Incomplete;
         }
 
-        if buffer[3] != b' ' {
-            return Error(DecodeError::StatusCode(StatusCodeError::Invalid));
-        }
+        // Without a reason phrase, some agents also leave out the space following the status code.
+        // The line ending is then left for decoding the reason phrase.
+        let length = match buffer[3] {
+            b' ' => 4,
+            b'\r' | b'\n'
+                if self
+                    .config
+                    .strictness()
+                    .tolerates(Deviation::MissingReasonPhrase) =>
+            {
+                3
+            }
+            _ => return Error(DecodeError::StatusCode(StatusCodeError::Invalid)),
+        };
 
         match StatusCode::try_from(&buffer[0..3]) {
             Ok(status_code) => {
                 self.builder.status_code(status_code);
                 self.state = DecodeState::ReasonPhrase;
-                *buffer = &buffer[4..];
+                *buffer = &buffer[length..];
                 Complete(())
             }
             Err(error) => Error(error.into()),
@@ -619,8 +749,12 @@ impl Decoder {
         }
 
         match Version::try_from(&buffer[0..8]) {
-            Ok(version) if version == Version::Rtsp2_0 => {
+            Ok(version)
+                if version == Version::Rtsp2_0
+                    || (version == Version::Rtsp1_0 && self.config.rtsp_1_0_allowed()) =>
+            {
                 self.builder.version(version);
+                self.deviations.clear();
                 self.state = DecodeState::StatusCode;
                 *buffer = &buffer[9..];
                 Complete(())
@@ -630,6 +764,12 @@ impl Decoder {
         }
     }
 
+    /// Returns the deviations tolerated while decoding the current response, or the last decoded
+    /// response if decoding of another one has not started yet.
+    pub fn deviations(&self) -> &[Deviation] {
+        &self.deviations
+    }
+
     /// Constructs a new response decoder.
     pub fn new() -> Self {
         Decoder::with_config(Config::default())
@@ -665,6 +805,7 @@ impl Decoder {
     pub fn reset(&mut self) {
         self.builder = ResponseBuilder::new();
         self.content_length = ContentLength::default();
+        self.deviations.clear();
         self.state = DecodeState::Version;
     }
 
@@ -707,12 +848,28 @@ impl Decoder {
         self.state.clone()
     }
 
+    /// Records the given deviation if it is tolerated, failing otherwise.
+    fn tolerate(&mut self, deviation: Deviation) -> DecodeResult<()> {
+        use self::GenericDecodeResult::*;
+
+        if !self.config.strictness().tolerates(deviation) {
+            return Error(DecodeError::Deviation(deviation));
+        }
+
+        if !self.deviations.contains(&deviation) {
+            self.deviations.push(deviation);
+        }
+
+        Complete(())
+    }
+
     /// Constructs a new response decoder using the given configuration.
     pub fn with_config(config: Config) -> Self {
         Decoder {
             builder: ResponseBuilder::new(),
             config,
             content_length: ContentLength::default(),
+            deviations: Vec::new(),
             state: DecodeState::Version,
         }
     }
@@ -731,6 +888,9 @@ pub enum DecodeError {
     /// The body, as determined by the content length header, was too long.
     BodyTooLong,
 
+    /// The response deviated from the syntax in a way that is not tolerated.
+    Deviation(Deviation),
+
     /// There was an error decoding a header name.
     HeaderName(HeaderNameError),
 
@@ -773,6 +933,7 @@ impl Display for DecodeError {
 
         match self {
             BodyTooLong => write!(formatter, "body too long"),
+            Deviation(deviation) => write!(formatter, "{} not tolerated", deviation),
             HeaderName(error) => error.fmt(formatter),
             HeaderNameTooLong => write!(formatter, "header name too long"),
             HeaderValue(error) => error.fmt(formatter),
@@ -827,13 +988,47 @@ impl From<VersionDecodeError> for DecodeError {
 #[cfg(test)]
 mod test {
     use crate::{
-        header::name::HeaderNameError,
-        protocol::codec::decoder::response::{ConfigBuilder, DecodeError, DecodeResult, Decoder},
+        header::name::{HeaderName, HeaderNameError},
+        protocol::codec::decoder::{
+            response::{Config, ConfigBuilder, DecodeError, DecodeResult, Decoder},
+            Deviation, Strictness,
+        },
         reason::ReasonPhraseError,
-        status::StatusCodeError,
-        version::DecodeError as VersionDecodeError,
+        status::{StatusCode, StatusCodeError},
+        version::{DecodeError as VersionDecodeError, Version},
     };
 
+    #[test]
+    fn test_decoder_decode_bare_line_feed() {
+        let buffer = "RTSP/2.0 200 OK\n\
+                      CSeq: 1\n\
+                      Content-Length: 4\n\
+                      \n\
+                      body";
+        let mut decoder = Decoder::new();
+        let (result, _) = decoder.decode(buffer);
+        assert_eq!(
+            result,
+            DecodeResult::Error(DecodeError::Deviation(Deviation::BareLineFeed))
+        );
+
+        let config = Config::builder()
+            .with_strictness(Strictness::lenient())
+            .build();
+        let mut decoder = Decoder::with_config(config);
+        let (result, bytes_decoded) = decoder.decode(buffer);
+        assert_eq!(bytes_decoded, buffer.len());
+        assert_eq!(decoder.deviations(), &[Deviation::BareLineFeed]);
+
+        match result {
+            DecodeResult::Complete(response) => {
+                assert_eq!(response.headers().get(&HeaderName::CSeq).unwrap(), "1");
+                assert_eq!(response.body(), "body");
+            }
+            result => panic!("unexpected decode result: {:?}", result),
+        }
+    }
+
     #[test]
     fn test_decoder_decode_body_invalid_content_length() {
         let buffer = "RTSP/2.0 200 OK\r\n\
@@ -862,6 +1057,46 @@ mod test {
         assert_eq!(result, DecodeResult::Error(DecodeError::BodyTooLong));
     }
 
+    #[test]
+    fn test_decoder_decode_duplicate_content_length() {
+        let buffer = "RTSP/2.0 200 OK\r\n\
+                      Content-Length: 4\r\n\
+                      Content-Length: 4\r\n\
+                      \r\n\
+                      body";
+        let mut decoder = Decoder::new();
+        let (result, _) = decoder.decode(buffer);
+        assert_eq!(
+            result,
+            DecodeResult::Error(DecodeError::InvalidContentLength)
+        );
+
+        let config = Config::builder()
+            .with_strictness(Strictness::lenient())
+            .build();
+        let mut decoder = Decoder::with_config(config);
+        let (result, bytes_decoded) = decoder.decode(buffer);
+        assert_eq!(bytes_decoded, buffer.len());
+        assert_eq!(decoder.deviations(), &[Deviation::DuplicateContentLength]);
+
+        match result {
+            DecodeResult::Complete(response) => assert_eq!(response.body(), "body"),
+            result => panic!("unexpected decode result: {:?}", result),
+        }
+
+        let buffer = "RTSP/2.0 200 OK\r\n\
+                      Content-Length: 4\r\n\
+                      Content-Length: 5\r\n\
+                      \r\n\
+                      body";
+        let mut decoder = Decoder::with_config(config);
+        let (result, _) = decoder.decode(buffer);
+        assert_eq!(
+            result,
+            DecodeResult::Error(DecodeError::InvalidContentLength)
+        );
+    }
+
     #[test]
     fn test_decoder_decode_header_name_empty() {
         let buffer = "RTSP/2.0 200 OK\r\n\
@@ -902,6 +1137,64 @@ mod test {
         assert_eq!(result, DecodeResult::Error(DecodeError::HeaderNameTooLong));
     }
 
+    #[test]
+    fn test_decoder_decode_header_obsolete_line_folding() {
+        let buffer = "RTSP/2.0 200 OK\r\n\
+                      Public: OPTIONS,\r\n \
+                      DESCRIBE\r\n\
+                      \r\n";
+        let mut decoder = Decoder::new();
+        let (result, bytes_decoded) = decoder.decode(buffer);
+        assert_eq!(bytes_decoded, buffer.len());
+        assert_eq!(decoder.deviations(), &[Deviation::ObsoleteLineFolding]);
+
+        match result {
+            DecodeResult::Complete(response) => assert_eq!(
+                response.headers().get(&HeaderName::Public).unwrap(),
+                "OPTIONS,\r\n DESCRIBE"
+            ),
+            result => panic!("unexpected decode result: {:?}", result),
+        }
+
+        let config = Config::builder()
+            .with_strictness(Strictness::strict())
+            .build();
+        let mut decoder = Decoder::with_config(config);
+        let (result, _) = decoder.decode(buffer);
+        assert_eq!(
+            result,
+            DecodeResult::Error(DecodeError::Deviation(Deviation::ObsoleteLineFolding))
+        );
+    }
+
+    #[test]
+    fn test_decoder_decode_header_space_before_colon() {
+        let buffer = "RTSP/2.0 200 OK\r\n\
+                      CSeq : 1\r\n\
+                      \r\n";
+        let mut decoder = Decoder::new();
+        let (result, bytes_decoded) = decoder.decode(buffer);
+        assert_eq!(bytes_decoded, buffer.len());
+        assert_eq!(decoder.deviations(), &[Deviation::SpaceBeforeColon]);
+
+        match result {
+            DecodeResult::Complete(response) => {
+                assert_eq!(response.headers().get(&HeaderName::CSeq).unwrap(), "1")
+            }
+            result => panic!("unexpected decode result: {:?}", result),
+        }
+
+        let config = Config::builder()
+            .with_strictness(Strictness::strict())
+            .build();
+        let mut decoder = Decoder::with_config(config);
+        let (result, _) = decoder.decode(buffer);
+        assert_eq!(
+            result,
+            DecodeResult::Error(DecodeError::Deviation(Deviation::SpaceBeforeColon))
+        );
+    }
+
     #[test]
     fn test_decoder_decode_header_too_many() {
         let buffer = "RTSP/2.0 200 OK\r\n\
@@ -942,6 +1235,37 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_decoder_decode_reason_phrase_missing() {
+        let config = Config::builder()
+            .with_strictness(
+                Strictness::default().with_tolerated(Deviation::MissingReasonPhrase, true),
+            )
+            .build();
+
+        for buffer in &["RTSP/2.0 200\r\n\r\n", "RTSP/2.0 200 \r\n\r\n"] {
+            let mut decoder = Decoder::with_config(config);
+            let (result, bytes_decoded) = decoder.decode(buffer);
+            assert_eq!(bytes_decoded, buffer.len());
+            assert_eq!(decoder.deviations(), &[Deviation::MissingReasonPhrase]);
+
+            match result {
+                DecodeResult::Complete(response) => {
+                    assert_eq!(response.status_code(), StatusCode::OK);
+                    assert_eq!(response.reason_phrase(), "OK");
+                }
+                result => panic!("unexpected decode result: {:?}", result),
+            }
+        }
+
+        let mut decoder = Decoder::new();
+        let (result, _) = decoder.decode("RTSP/2.0 200\r\n\r\n");
+        assert_eq!(
+            result,
+            DecodeResult::Error(DecodeError::StatusCode(StatusCodeError::Invalid))
+        );
+    }
+
     #[test]
     fn test_decoder_decode_reason_phrase_too_long() {
         let buffer = "RTSP/2.0 200 OKOKOKOKOKOK\r\n\
@@ -1006,4 +1330,23 @@ mod test {
         assert_ne!(bytes_decoded, buffer.len());
         assert_eq!(result, DecodeResult::Error(DecodeError::UnsupportedVersion));
     }
+
+    #[test]
+    fn test_decoder_decode_version_rtsp_1_0_allowed() {
+        let buffer = "RTSP/1.0 200 OK\r\n\
+                      CSeq: 1\r\n\
+                      \r\n";
+        let config = Config::builder().with_rtsp_1_0_allowed(true).build();
+        let mut decoder = Decoder::with_config(config);
+        let (result, bytes_decoded) = decoder.decode(buffer);
+        assert_eq!(bytes_decoded, buffer.len());
+
+        match result {
+            DecodeResult::Complete(response) => {
+                assert_eq!(response.status_code(), StatusCode::OK);
+                assert_eq!(response.version(), Version::Rtsp1_0);
+            }
+            result => panic!("unexpected decode result: {:?}", result),
+        }
+    }
 }
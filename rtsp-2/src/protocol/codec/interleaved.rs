@@ -0,0 +1,90 @@
+//! Interleaved Data
+//!
+//! When media is interleaved with the control connection as described by
+//! [[RFC7826, Section 14]](https://tools.ietf.org/html/rfc7826#section-14), each packet is sent as
+//! a `"$"` followed by a one byte channel identifier, a two byte big-endian length and the packet
+//! itself.
+
+use bytes::{BufMut, Bytes, BytesMut};
+
+/// The byte that starts every interleaved data frame.
+pub const INTERLEAVED_DATA_MARKER: u8 = b'$';
+
+/// The size of the header preceding the payload of an interleaved data frame.
+pub const INTERLEAVED_DATA_HEADER_SIZE: usize = 4;
+
+/// The largest payload that fits in a single interleaved data frame.
+pub const MAX_INTERLEAVED_DATA_SIZE: usize = u16::max_value() as usize;
+
+/// A single frame of data interleaved with the control connection.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct InterleavedData {
+    /// The channel identifier as negotiated by the `"interleaved"` transport parameter.
+    channel: u8,
+
+    /// The data, usually a single RTP or RTCP packet.
+    payload: Bytes,
+}
+
+impl InterleavedData {
+    /// Returns the channel identifier.
+    pub fn channel(&self) -> u8 {
+        self.channel
+    }
+
+    /// Constructs new interleaved data for the given channel.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the payload is larger than [`MAX_INTERLEAVED_DATA_SIZE`].
+    pub fn new<TPayload>(channel: u8, payload: TPayload) -> Self
+    where
+        TPayload: Into<Bytes>,
+    {
+        let payload = payload.into();
+        assert!(payload.len() <= MAX_INTERLEAVED_DATA_SIZE);
+        InterleavedData { channel, payload }
+    }
+
+    /// Returns the data.
+    pub fn payload(&self) -> &Bytes {
+        &self.payload
+    }
+
+    /// Returns the data, consuming the frame.
+    pub fn into_payload(self) -> Bytes {
+        self.payload
+    }
+}
+
+/// Decodes an interleaved data frame from the start of the buffer, which must start with
+/// [`INTERLEAVED_DATA_MARKER`].
+///
+/// Returns `None` if the buffer does not yet contain the entire frame.
+pub fn decode(buffer: &mut BytesMut) -> Option<InterleavedData> {
+    debug_assert_eq!(buffer.first(), Some(&INTERLEAVED_DATA_MARKER));
+
+    if buffer.len() < INTERLEAVED_DATA_HEADER_SIZE {
+        return None;
+    }
+
+    let length = (usize::from(buffer[2]) << 8) | usize::from(buffer[3]);
+
+    if buffer.len() < INTERLEAVED_DATA_HEADER_SIZE + length {
+        return None;
+    }
+
+    let channel = buffer[1];
+    buffer.advance(INTERLEAVED_DATA_HEADER_SIZE);
+    let payload = buffer.split_to(length).freeze();
+    Some(InterleavedData { channel, payload })
+}
+
+/// Encodes the interleaved data frame into the buffer.
+pub fn encode(data: &InterleavedData, buffer: &mut BytesMut) {
+    buffer.reserve(INTERLEAVED_DATA_HEADER_SIZE + data.payload.len());
+    buffer.put_u8(INTERLEAVED_DATA_MARKER);
+    buffer.put_u8(data.channel);
+    buffer.put_u16_be(data.payload.len() as u16);
+    buffer.extend_from_slice(&data.payload);
+}
@@ -1,6 +1,7 @@
 #[macro_use]
 pub mod decoder;
 pub mod encoder;
+pub mod interleaved;
 
 use std::{
     convert::Infallible,
@@ -18,16 +19,17 @@ use crate::{
     protocol::codec::{
         decoder::{
             request::{
-                DecodeError as RequestDecodeError, DecodeState as RequestDecodeState,
-                Decoder as RequestDecoder,
+                Config as RequestDecoderConfig, DecodeError as RequestDecodeError,
+                DecodeState as RequestDecodeState, Decoder as RequestDecoder,
             },
             response::{
-                DecodeError as ResponseDecodeError, DecodeState as ResponseDecodeState,
-                Decoder as ResponseDecoder,
+                Config as ResponseDecoderConfig, DecodeError as ResponseDecodeError,
+                DecodeState as ResponseDecodeState, Decoder as ResponseDecoder,
             },
-            DecodeResult,
+            DecodeResult, Deviation,
         },
         encoder::{request, response},
+        interleaved::{InterleavedData, INTERLEAVED_DATA_MARKER},
     },
     request::Request,
     response::Response,
@@ -42,6 +44,10 @@ const MINIMUM_INFO_LINE_SIZE: usize = 5;
 /// two (as well as proxies).
 #[derive(Debug)]
 pub struct Codec {
+    /// Whether a partial interleaved data frame is buffered, for which a
+    /// [`CodecEvent::DecodingStarted`] event has been sent.
+    decoding_interleaved_data: bool,
+
     /// The request decoder that maintains partial parsing state.
     request_decoder: RequestDecoder,
 
@@ -54,11 +60,39 @@ pub struct Codec {
 }
 
 impl Codec {
+    /// Decodes an interleaved data frame.
+    ///
+    /// Frames usually arrive in their entirety, for which no events are sent. But if only part of
+    /// a frame is buffered, a [`CodecEvent::DecodingStarted`] event is sent so that the decoding
+    /// timeout applies to the rest of it, and a [`CodecEvent::DecodingEnded`] event is sent once
+    /// it is complete.
+    fn decode_interleaved_data(&mut self, buffer: &mut BytesMut) -> Option<Message> {
+        match interleaved::decode(buffer) {
+            Some(data) => {
+                if self.decoding_interleaved_data {
+                    self.decoding_interleaved_data = false;
+                    self.send_codec_event(CodecEvent::DecodingEnded);
+                }
+
+                Some(Message::Data(data))
+            }
+            None => {
+                if !self.decoding_interleaved_data {
+                    self.decoding_interleaved_data = true;
+                    self.send_codec_event(CodecEvent::DecodingStarted);
+                }
+
+                None
+            }
+        }
+    }
+
     /// Decodes a request.
     ///
     /// Using the internal request decoder, this function will attempt to make progress on decoding
     /// a request using the buffer. If a request is successfully decoded or an error occurs, this
-    /// function will send a [`CodecEvent::DecodingEnded`] event.
+    /// function will send a [`CodecEvent::DecodingEnded`] event, preceded by a
+    /// [`CodecEvent::DeviationTolerated`] event for every deviation tolerated while decoding it.
     ///
     /// The return value of this function can be divided into four parts:
     ///
@@ -77,6 +111,11 @@ impl Codec {
 
         match result {
             DecodeResult::Complete(request) => {
+                for index in 0..self.request_decoder.deviations().len() {
+                    let deviation = self.request_decoder.deviations()[index];
+                    self.send_codec_event(CodecEvent::DeviationTolerated(deviation));
+                }
+
                 self.send_codec_event(CodecEvent::DecodingEnded);
                 Ok(Some(Message::Request(request)))
             }
@@ -92,7 +131,8 @@ impl Codec {
     ///
     /// Using the internal response decoder, this function will attempt to make progress on decoding
     /// a response using the buffer. If a response is successfully decoded or an error occurs, this
-    /// function will send a [`CodecEvent::DecodingEnded`] event.
+    /// function will send a [`CodecEvent::DecodingEnded`] event, preceded by a
+    /// [`CodecEvent::DeviationTolerated`] event for every deviation tolerated while decoding it.
     ///
     /// The return value of this function can be divided into four parts:
     ///
@@ -111,6 +151,11 @@ impl Codec {
 
         match result {
             DecodeResult::Complete(response) => {
+                for index in 0..self.response_decoder.deviations().len() {
+                    let deviation = self.response_decoder.deviations()[index];
+                    self.send_codec_event(CodecEvent::DeviationTolerated(deviation));
+                }
+
                 self.send_codec_event(CodecEvent::DecodingEnded);
                 Ok(Some(Message::Response(response)))
             }
@@ -124,11 +169,11 @@ impl Codec {
 
     /// Constructs a new codec without an event sink.
     pub fn new() -> Self {
-        Codec {
-            request_decoder: RequestDecoder::new(),
-            response_decoder: ResponseDecoder::new(),
-            tx_event: None,
-        }
+        Codec::with_config(
+            RequestDecoderConfig::default(),
+            ResponseDecoderConfig::default(),
+            None,
+        )
     }
 
     /// Sends a [`CodecEvent`] through the internal event sink.
@@ -143,14 +188,29 @@ impl Codec {
         }
     }
 
-    /// Constructs a new codec with an event sink.
-    pub fn with_events(tx_event: UnboundedSender<CodecEvent>) -> Self {
+    /// Constructs a new codec that decodes requests and responses using the given configurations,
+    /// with an optional event sink.
+    pub fn with_config(
+        request_decoder_config: RequestDecoderConfig,
+        response_decoder_config: ResponseDecoderConfig,
+        tx_event: Option<UnboundedSender<CodecEvent>>,
+    ) -> Self {
         Codec {
-            request_decoder: RequestDecoder::new(),
-            response_decoder: ResponseDecoder::new(),
-            tx_event: Some(tx_event),
+            decoding_interleaved_data: false,
+            request_decoder: RequestDecoder::with_config(request_decoder_config),
+            response_decoder: ResponseDecoder::with_config(response_decoder_config),
+            tx_event,
         }
     }
+
+    /// Constructs a new codec with an event sink.
+    pub fn with_events(tx_event: UnboundedSender<CodecEvent>) -> Self {
+        Codec::with_config(
+            RequestDecoderConfig::default(),
+            ResponseDecoderConfig::default(),
+            Some(tx_event),
+        )
+    }
 }
 
 impl Decoder for Codec {
@@ -162,7 +222,8 @@ impl Decoder for Codec {
     /// Using the internal decoders, this function will attempt to make progress on decoding either
     /// a request or response using the buffer. If neither of the decoders are active, this
     /// function will send a [`CodecEvent::DecodingStarted`] event if the buffer is non-empty after
-    /// removing all preceding newlines.
+    /// removing all preceding newlines. Interleaved data frames only send events if they are not
+    /// buffered in their entirety, see [`Codec::decode_interleaved_data`].
     ///
     /// The return value of this function can be divided into four parts:
     ///
@@ -187,6 +248,10 @@ impl Decoder for Codec {
                 buffer.split_to(2);
             }
 
+            if buffer.first() == Some(&INTERLEAVED_DATA_MARKER) {
+                return Ok(self.decode_interleaved_data(buffer));
+            }
+
             if !buffer.is_empty() {
                 self.send_codec_event(CodecEvent::DecodingStarted);
             }
@@ -232,21 +297,28 @@ impl Encoder for Codec {
 
     /// Encodes a message.
     ///
-    /// This function will encode the given message into the given buffer. Before encoding the
-    /// message, a [`CodecEvent::EncodingStarted`] event will be sent. And after encoding has
-    /// finished, an [`CodecEvent::EncodingEnded`] event will be sent.
+    /// This function will encode the given message into the given buffer. Before encoding a
+    /// request or response, a [`CodecEvent::EncodingStarted`] event will be sent. And after encoding
+    /// has finished, an [`CodecEvent::EncodingEnded`] event will be sent. No events are sent for
+    /// interleaved data.
     ///
     /// Although a [`Result`] is returned, this function will never return an error as the actual
     /// message encoding cannot fail. As a result, `Ok(())` will always be returned.
     fn encode(&mut self, message: Self::Item, buffer: &mut BytesMut) -> Result<(), Self::Error> {
-        self.send_codec_event(CodecEvent::EncodingStarted);
-
         match message {
-            Message::Request(request) => request::encode(&request, buffer),
-            Message::Response(response) => response::encode(&response, buffer),
+            Message::Data(data) => interleaved::encode(&data, buffer),
+            Message::Request(request) => {
+                self.send_codec_event(CodecEvent::EncodingStarted);
+                request::encode(&request, buffer);
+                self.send_codec_event(CodecEvent::EncodingEnded);
+            }
+            Message::Response(response) => {
+                self.send_codec_event(CodecEvent::EncodingStarted);
+                response::encode(&response, buffer);
+                self.send_codec_event(CodecEvent::EncodingEnded);
+            }
         }
 
-        self.send_codec_event(CodecEvent::EncodingEnded);
         Ok(())
     }
 }
@@ -263,6 +335,10 @@ pub enum CodecEvent {
     /// The decoding of a message has started.
     DecodingStarted,
 
+    /// The message being decoded deviated from the syntax in a way that was tolerated. This will be
+    /// sent before the corresponding [`CodecEvent::DecodingEnded`] event.
+    DeviationTolerated(Deviation),
+
     /// The encoding of a message has ended.
     EncodingEnded,
 
@@ -270,10 +346,13 @@ pub enum CodecEvent {
     EncodingStarted,
 }
 
-/// An abstract message type that is either a request or response.
+/// An abstract message type that is either a request, a response or interleaved data.
 #[derive(Clone, Debug, Eq, PartialEq)]
 #[allow(clippy::large_enum_variant)]
 pub enum Message {
+    /// This message is interleaved data, such as an RTP packet.
+    Data(InterleavedData),
+
     /// This message is a request.
     Request(Request<BytesMut>),
 
@@ -382,7 +461,7 @@ mod test {
     use crate::{
         header::{name::HeaderName, types::ContentLength, value::HeaderValue},
         method::Method,
-        protocol::codec::{Codec, CodecEvent, Message},
+        protocol::codec::{interleaved::InterleavedData, Codec, CodecEvent, Message},
         request::Request,
         response::Response,
         uri::request::URI,
@@ -449,6 +528,29 @@ mod test {
         assert_eq!(buffer, expected_buffer);
     }
 
+    #[test]
+    fn test_codec_interleaved_data() {
+        let mut codec = Codec::new();
+        let mut buffer = BytesMut::new();
+        let data = InterleavedData::new(1, &b"RTP"[..]);
+        codec
+            .encode(Message::Data(data.clone()), &mut buffer)
+            .unwrap();
+        assert_eq!(&buffer[..], b"$\x01\x00\x03RTP");
+
+        buffer.extend_from_slice(b"OPTIONS * RTSP/2.0\r\n\r\n$\x02\x00");
+        assert_eq!(
+            codec.decode(&mut buffer).unwrap(),
+            Some(Message::Data(data))
+        );
+        assert!(match codec.decode(&mut buffer).unwrap() {
+            Some(Message::Request(request)) => *request.method() == Method::Options,
+            _ => false,
+        });
+        assert_eq!(codec.decode(&mut buffer).unwrap(), None);
+        assert_eq!(&buffer[..], b"$\x02\x00");
+    }
+
     #[test]
     fn test_codec_events() {
         let (tx_event, rx_event) = unbounded();
@@ -493,4 +595,32 @@ mod test {
             ]
         );
     }
+
+    #[test]
+    fn test_codec_interleaved_data_events() {
+        let (tx_event, rx_event) = unbounded();
+
+        {
+            let mut codec = Codec::with_events(tx_event);
+            let mut buffer = BytesMut::from(&b"$\x00\x00\x03RTP$\x00\xff"[..]);
+            assert!(codec.decode(&mut buffer).unwrap().is_some());
+            assert_eq!(codec.decode(&mut buffer).unwrap(), None);
+
+            buffer.extend_from_slice(b"\xff");
+            assert_eq!(codec.decode(&mut buffer).unwrap(), None);
+
+            buffer.extend_from_slice(&[0; 0xffff]);
+            assert!(codec.decode(&mut buffer).unwrap().is_some());
+            assert!(buffer.is_empty());
+        }
+
+        let mut runtime = Runtime::new().unwrap();
+        let events = runtime.block_on(rx_event.collect()).unwrap();
+
+        // Only the frame that was not buffered in its entirety is subject to the decoding timeout.
+        assert_eq!(
+            events,
+            vec![CodecEvent::DecodingStarted, CodecEvent::DecodingEnded]
+        );
+    }
 }
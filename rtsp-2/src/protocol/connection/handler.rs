@@ -2,7 +2,10 @@
 //!
 //! This module contains the logic for servicing incoming requests and mapping them to responses.
 
-use std::time::{Duration, Instant};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use bytes::BytesMut;
 use futures::{
@@ -19,12 +22,15 @@ use crate::{
         name::HeaderName,
         types::{CSeq, ContentLength},
     },
+    method::Method,
+    metrics::Metrics,
     protocol::{codec::Message, connection::sender::SenderHandle},
     request::Request,
     response::{
         Response, BAD_REQUEST_RESPONSE, CONTINUE_RESPONSE, INTERNAL_SERVER_ERROR_RESPONSE,
         NOT_IMPLEMENTED_RESPONSE,
     },
+    status::StatusCode,
     uri::Scheme,
 };
 
@@ -45,10 +51,18 @@ where
     /// responses.
     continue_wait_duration: Option<Duration>,
 
+    /// The metrics serviced requests are reported into, if any.
+    metrics: Option<Arc<dyn Metrics>>,
+
     /// The stream of incoming requests to be serviced. It is assumed that the requests are coming
     /// in order by their `"CSeq"` headers.
     rx_incoming_request: Fuse<Receiver<(CSeq, Request<BytesMut>)>>,
 
+    /// The span of the request currently being serviced, along with when it started being
+    /// serviced. The status code and latency are recorded in it once the request is responded to.
+    #[cfg(feature = "tracing")]
+    request_span: Option<(tracing::Span, Instant)>,
+
     /// A handle to the sender instance used for sending responses returned by the service.
     sender_handle: Option<SenderHandle>,
 
@@ -58,6 +72,14 @@ where
     /// The [`Future`] that will finish with the response for the current request being serviced.
     serviced_request: Option<(CSeq, TService::Future)>,
 
+    /// The method of the current request being serviced, kept until it is responded to when
+    /// metrics are reported.
+    serviced_request_method: Option<Method>,
+
+    /// The span the handler is recorded within, which is the span current at construction.
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
+
     /// A sender that notifies the receiver when the request handler is shutdown. Shutdown occurs
     /// when all incoming requests have finished being serviced.
     tx_shutdown_event: Option<oneshot::Sender<()>>,
@@ -76,14 +98,21 @@ where
         sender_handle: SenderHandle,
         tx_shutdown_event: oneshot::Sender<()>,
         continue_wait_duration: Option<Duration>,
+        metrics: Option<Arc<dyn Metrics>>,
     ) -> Self {
         RequestHandler {
             continue_timer: None,
             continue_wait_duration,
+            metrics,
+            #[cfg(feature = "tracing")]
+            request_span: None,
             rx_incoming_request: rx_incoming_request.fuse(),
             sender_handle: Some(sender_handle),
             service,
             serviced_request: None,
+            serviced_request_method: None,
+            #[cfg(feature = "tracing")]
+            span: tracing::Span::current(),
             tx_shutdown_event: Some(tx_shutdown_event),
         }
     }
@@ -106,6 +135,7 @@ where
         while let Some(continue_timer) = self.continue_timer.as_mut() {
             match continue_timer.poll() {
                 Ok(Async::Ready(_)) => {
+                    event!(DEBUG, "request still being serviced");
                     self.send_response(cseq, CONTINUE_RESPONSE.clone());
                     self.reset_continue_timer();
                 }
@@ -115,6 +145,10 @@ where
                     // 100 (Continue) responses for this request. The client may stop waiting for
                     // the request, but it will still be handled even if the corresponding response
                     // does not make it to the client before the request expires.
+                    event!(
+                        WARN,
+                        "too many timers, no longer sending continue responses"
+                    );
                     self.continue_timer = None;
                 }
                 _ => panic!("continue timer should not be shutdown"),
@@ -140,6 +174,11 @@ where
             Some((cseq, serviced_request)) => {
                 let cseq = *cseq;
 
+                #[cfg(feature = "tracing")]
+                let span = self.request_span.as_ref().map(|(span, _)| span.clone());
+                #[cfg(feature = "tracing")]
+                let _entered = span.as_ref().map(tracing::Span::enter);
+
                 match serviced_request.poll() {
                     Ok(Async::Ready(response)) => {
                         self.send_response(cseq, response.into());
@@ -152,6 +191,7 @@ where
                         Ok(Async::NotReady)
                     }
                     Err(_) => {
+                        event!(WARN, "service failed to handle request");
                         self.send_response(cseq, INTERNAL_SERVER_ERROR_RESPONSE.clone());
                         self.continue_timer = None;
                         self.serviced_request = None;
@@ -170,7 +210,28 @@ where
     /// sent back with the request never being forwarded to the service. Otherwise, the request is
     /// forwarded to the service and the continue timer is set.
     fn process_request(&mut self, cseq: CSeq, request: Request<BytesMut>) {
+        #[cfg(feature = "tracing")]
+        let span = tracing::info_span!(
+            "request",
+            cseq = *cseq,
+            method = %request.method(),
+            uri = %request.uri(),
+            status = tracing::field::Empty,
+            latency_ms = tracing::field::Empty,
+        );
+        #[cfg(feature = "tracing")]
+        let _entered = span.enter();
+        #[cfg(feature = "tracing")]
+        {
+            self.request_span = Some((span.clone(), Instant::now()));
+        }
+
+        if self.metrics.is_some() {
+            self.serviced_request_method = Some(request.method().clone());
+        }
+
         if request.uri().scheme() == Some(Scheme::RTSPU) {
+            event!(DEBUG, "rejected request using unsupported scheme");
             self.send_response(cseq, NOT_IMPLEMENTED_RESPONSE.clone());
             return;
         }
@@ -180,6 +241,7 @@ where
                 if *content_length > 0
                     && !request.headers().contains_key(&HeaderName::ContentType) =>
             {
+                event!(DEBUG, "rejected request with body but without content type");
                 self.send_response(cseq, BAD_REQUEST_RESPONSE.clone());
             }
             _ => {
@@ -205,6 +267,24 @@ where
     fn send_response(&mut self, cseq: CSeq, mut response: Response<BytesMut>) {
         response.headers_mut().typed_insert(cseq);
 
+        #[cfg(feature = "tracing")]
+        if response.status_code() != StatusCode::Continue {
+            if let Some((span, start_time)) = self.request_span.take() {
+                let latency = start_time.elapsed();
+                span.record("status", u16::from(response.status_code()));
+                span.record("latency_ms", latency.as_millis() as u64);
+                span.in_scope(|| event!(DEBUG, ?latency, "request serviced"));
+            }
+        }
+
+        if response.status_code() != StatusCode::Continue {
+            if let (Some(metrics), Some(method)) =
+                (self.metrics.as_ref(), self.serviced_request_method.take())
+            {
+                metrics.request_serviced(&method, response.status_code());
+            }
+        }
+
         if let Some(sender_handle) = self.sender_handle.as_mut() {
             if sender_handle
                 .try_send_message(Message::Response(response))
@@ -224,6 +304,10 @@ where
     /// and definitely should not happen while we are in the middle of servicing a request (as this
     /// can lead to undefined application state).
     fn shutdown(&mut self) {
+        if self.tx_shutdown_event.is_some() {
+            event!(DEBUG, "request handler shut down");
+        }
+
         self.continue_timer = None;
         self.sender_handle = None;
         self.serviced_request = None;
@@ -269,6 +353,11 @@ where
     ///
     /// The error `Err(())` will never be returned.
     fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        #[cfg(feature = "tracing")]
+        let span = self.span.clone();
+        #[cfg(feature = "tracing")]
+        let _entered = span.enter();
+
         loop {
             try_ready!(self.poll_serviced_request());
 
@@ -374,6 +463,7 @@ mod test {
             sender_handle,
             tx_shutdown_event,
             Some(Duration::from_millis(100)),
+            None,
         );
 
         tx_incoming_request
@@ -435,6 +525,7 @@ mod test {
             sender_handle,
             tx_shutdown_event,
             None,
+            None,
         );
 
         tx_incoming_request
@@ -478,6 +569,7 @@ mod test {
             sender_handle,
             tx_shutdown_event,
             None,
+            None,
         );
 
         tx_incoming_request
@@ -523,6 +615,7 @@ mod test {
             sender_handle,
             tx_shutdown_event,
             None,
+            None,
         );
 
         tx_incoming_request
@@ -567,6 +660,7 @@ mod test {
             sender_handle,
             tx_shutdown_event,
             None,
+            None,
         );
 
         mem::drop(tx_incoming_request);
@@ -0,0 +1,82 @@
+//! Metered Transport
+//!
+//! This module contains a transport wrapper reporting the number of bytes read and written through
+//! it into [`Metrics`].
+
+use std::{
+    io::{self, Read, Write},
+    sync::Arc,
+};
+
+use futures::Poll;
+use tokio_io::{AsyncRead, AsyncWrite};
+
+use crate::metrics::Metrics;
+
+/// A transport reporting the number of bytes read from and written to the transport it wraps.
+pub struct MeteredTransport<TTransport> {
+    /// The metrics the number of bytes are reported into, if any.
+    metrics: Option<Arc<dyn Metrics>>,
+
+    /// The wrapped transport.
+    transport: TTransport,
+}
+
+impl<TTransport> MeteredTransport<TTransport> {
+    /// Constructs a new transport reporting into the given metrics.
+    pub fn new(transport: TTransport, metrics: Option<Arc<dyn Metrics>>) -> Self {
+        MeteredTransport { metrics, transport }
+    }
+}
+
+impl<TTransport> AsyncRead for MeteredTransport<TTransport>
+where
+    TTransport: AsyncRead,
+{
+    unsafe fn prepare_uninitialized_buffer(&self, buffer: &mut [u8]) -> bool {
+        self.transport.prepare_uninitialized_buffer(buffer)
+    }
+}
+
+impl<TTransport> AsyncWrite for MeteredTransport<TTransport>
+where
+    TTransport: AsyncWrite,
+{
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        self.transport.shutdown()
+    }
+}
+
+impl<TTransport> Read for MeteredTransport<TTransport>
+where
+    TTransport: Read,
+{
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        let count = self.transport.read(buffer)?;
+
+        if let Some(metrics) = self.metrics.as_ref() {
+            metrics.bytes_received(count);
+        }
+
+        Ok(count)
+    }
+}
+
+impl<TTransport> Write for MeteredTransport<TTransport>
+where
+    TTransport: Write,
+{
+    fn flush(&mut self) -> io::Result<()> {
+        self.transport.flush()
+    }
+
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        let count = self.transport.write(buffer)?;
+
+        if let Some(metrics) = self.metrics.as_ref() {
+            metrics.bytes_sent(count);
+        }
+
+        Ok(count)
+    }
+}
@@ -1,4 +1,5 @@
 mod handler;
+mod metered;
 mod pending;
 mod receiver;
 mod sender;
@@ -8,6 +9,7 @@ use std::{
     error::Error,
     fmt::{self, Display, Formatter},
     mem,
+    net::SocketAddr,
     sync::{
         atomic::{AtomicBool, Ordering},
         Arc, Mutex,
@@ -21,7 +23,7 @@ use futures::{
     future::{Either, Shared},
     stream::{SplitSink, SplitStream},
     sync::{
-        mpsc::{self, UnboundedSender},
+        mpsc::{self, UnboundedReceiver, UnboundedSender},
         oneshot,
     },
     Async, Future, Poll, Stream,
@@ -41,11 +43,20 @@ pub use self::{
 };
 use crate::{
     header::{map::HeaderMapExtension, types::CSeq},
+    metrics::Metrics,
     protocol::{
-        codec::{Codec, Message},
+        codec::{
+            decoder::{
+                request::Config as RequestDecoderConfig, response::Config as ResponseDecoderConfig,
+                Strictness,
+            },
+            interleaved::InterleavedData,
+            Codec, Message,
+        },
         connection::{
+            metered::MeteredTransport,
             pending::PendingRequestUpdate,
-            receiver::Receiver,
+            receiver::{InterleavedDataSender, Receiver},
             sender::Sender,
             shutdown::{ShutdownHandler, ShutdownState},
         },
@@ -59,6 +70,12 @@ pub const DEFAULT_DECODE_TIMEOUT_DURATION: Duration = Duration::from_secs(10);
 pub const DEFAULT_GRACEFUL_SHUTDOWN_TIMEOUT_DURATION: Duration = Duration::from_secs(10);
 pub const DEFAULT_REQUEST_BUFFER_SIZE: usize = 10;
 
+/// The sink half of the framed transport of a connection.
+type TransportSink<TTransport> = SplitSink<Framed<MeteredTransport<TTransport>, Codec>>;
+
+/// The stream half of the framed transport of a connection.
+type TransportStream<TTransport> = SplitStream<Framed<MeteredTransport<TTransport>, Codec>>;
+
 /// Represents an RTSP connection between two RTSP agents.
 ///
 /// RTSP servers and clients are both capable of sending and receiving requests and responses. As a
@@ -72,17 +89,24 @@ where
     /// connection.
     allow_requests: Arc<AtomicBool>,
 
+    /// The metrics the connection reports into, if any.
+    metrics: Option<Arc<dyn Metrics>>,
+
     /// The internal receiver responsible for processing all incoming messages.
-    receiver: Option<Receiver<SplitStream<Framed<TTransport, Codec>>>>,
+    receiver: Option<Receiver<TransportStream<TTransport>>>,
 
     /// A shutdown event receiver for when the request handler has finished processing all requests.
     rx_handler_shutdown_event: Option<Shared<oneshot::Receiver<()>>>,
 
     /// The internal sender responsible for sending all outgoing messages through the connection.
-    sender: Option<Sender<SplitSink<Framed<TTransport, Codec>>>>,
+    sender: Option<Sender<TransportSink<TTransport>>>,
 
     /// The shutdown handler that keeps watch for a shutdown signal.
     shutdown: ShutdownHandler,
+
+    /// The span everything happening on the connection is recorded within.
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
 }
 
 impl<TTransport> Connection<TTransport>
@@ -124,6 +148,7 @@ where
         if let Some(receiver) = self.receiver.as_mut() {
             match receiver.poll() {
                 Ok(Async::Ready(_)) | Err(_) => {
+                    event!(DEBUG, "receiving finished");
                     self.shutdown_receiver();
                 }
                 _ => (),
@@ -135,16 +160,18 @@ where
     ///
     /// This is a no-op if the receiver is not shutdown. Otherwise, if the request handler is also
     /// shutdown, this means the sender needs to be shutdown as well, so the connection can be
-    /// closed.
+    /// closed. Without a request handler, such as on client connections, nothing is left to send
+    /// responses for, so the sender is shutdown right away.
     fn poll_request_handler_shutdown(&mut self) {
         if self.is_receiver_shutdown() {
-            if let Some(rx_handler_shutdown_event) = self.rx_handler_shutdown_event.as_mut() {
-                match rx_handler_shutdown_event.poll() {
+            match self.rx_handler_shutdown_event.as_mut() {
+                Some(rx_handler_shutdown_event) => match rx_handler_shutdown_event.poll() {
                     Ok(Async::Ready(_)) | Err(_) => {
                         self.shutdown_sender();
                     }
                     Ok(Async::NotReady) => (),
-                }
+                },
+                None => self.shutdown_sender(),
             }
         }
     }
@@ -156,7 +183,13 @@ where
     fn poll_sender(&mut self) {
         if let Some(sender) = self.sender.as_mut() {
             match sender.poll() {
-                Ok(Async::Ready(_)) | Err(_) => {
+                Ok(Async::Ready(_)) => {
+                    event!(DEBUG, "sending finished");
+                    self.shutdown_request_receiver();
+                    self.shutdown_sender();
+                }
+                Err(_error) => {
+                    event!(WARN, error = %_error, "sending failed");
                     self.shutdown_request_receiver();
                     self.shutdown_sender();
                 }
@@ -211,6 +244,17 @@ where
         TService::Future: Send + 'static,
         TService::Response: Into<Response<BytesMut>>,
     {
+        // Everything happening on the connection, including the servicing of requests, is recorded
+        // within its span.
+        #[cfg(feature = "tracing")]
+        let span = match config.peer_address() {
+            Some(peer_address) => tracing::info_span!("connection", %peer_address),
+            None => tracing::info_span!("connection"),
+        };
+        #[cfg(feature = "tracing")]
+        let _entered = span.clone().entered();
+        event!(DEBUG, "connection opened");
+
         // Create all channels that the connection components will use to communicate with each
         // other.
 
@@ -221,7 +265,22 @@ where
         let (tx_initiate_shutdown, rx_initiate_shutdown) = oneshot::channel();
         let (tx_connection_shutdown_event, rx_connection_shutdown_event) = oneshot::channel();
         let (tx_handler_shutdown_event, rx_handler_shutdown_event) = oneshot::channel();
-        let codec = Codec::with_events(tx_codec_event);
+        let tx_interleaved_data = Arc::new(Mutex::new(InterleavedDataSender::default()));
+        let request_decoder_config = RequestDecoderConfig::builder()
+            .with_rtsp_1_0_allowed(config.rtsp_1_0_allowed())
+            .with_strictness(config.strictness())
+            .build();
+        let response_decoder_config = ResponseDecoderConfig::builder()
+            .with_rtsp_1_0_allowed(config.rtsp_1_0_allowed())
+            .with_strictness(config.strictness())
+            .build();
+        let codec = Codec::with_config(
+            request_decoder_config,
+            response_decoder_config,
+            Some(tx_codec_event),
+        );
+        let metrics = config.metrics().cloned();
+        let transport = MeteredTransport::new(transport, metrics.clone());
         let (sink, stream) = codec.framed(transport).split();
 
         // Create individual components. A request handler is only created if a service was given.
@@ -235,6 +294,8 @@ where
             sender_handle.clone(),
             config.decode_timeout_duration(),
             config.request_buffer_size(),
+            tx_interleaved_data.clone(),
+            metrics.clone(),
         );
         let handler = if let Some(service) = service {
             Some(RequestHandler::new(
@@ -243,6 +304,7 @@ where
                 sender_handle.clone(),
                 tx_handler_shutdown_event,
                 config.continue_wait_duration(),
+                metrics.clone(),
             ))
         } else {
             None
@@ -255,18 +317,26 @@ where
 
         // Create the connection and the connection handle.
 
+        if let Some(metrics) = metrics.as_ref() {
+            metrics.connection_opened();
+        }
+
         let connection = Connection {
             allow_requests: Arc::new(AtomicBool::new(true)),
+            metrics,
             receiver: Some(receiver),
             rx_handler_shutdown_event: rx_handler_shutdown_event.clone(),
             sender: Some(sender),
             shutdown: ShutdownHandler::new(rx_initiate_shutdown, tx_connection_shutdown_event),
+            #[cfg(feature = "tracing")]
+            span,
         };
         let connection_handle = ConnectionHandle::new(
             connection.allow_requests.clone(),
             rx_connection_shutdown_event.shared(),
             rx_handler_shutdown_event,
             sender_handle,
+            tx_interleaved_data,
             tx_pending_request,
             tx_initiate_shutdown,
             config.graceful_shutdown_timeout_default_duration(),
@@ -278,6 +348,17 @@ where
     }
 }
 
+impl<TTransport> Drop for Connection<TTransport>
+where
+    TTransport: AsyncRead + AsyncWrite + Send + 'static,
+{
+    fn drop(&mut self) {
+        if let Some(metrics) = self.metrics.as_ref() {
+            metrics.connection_closed();
+        }
+    }
+}
+
 impl<TTransport> Future for Connection<TTransport>
 where
     TTransport: AsyncRead + AsyncWrite + Send + 'static,
@@ -297,6 +378,11 @@ where
     ///
     /// The error `Err(())` will never be returned.
     fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        #[cfg(feature = "tracing")]
+        let span = self.span.clone();
+        #[cfg(feature = "tracing")]
+        let _entered = span.enter();
+
         self.poll_receiver();
         self.poll_sender();
 
@@ -338,6 +424,7 @@ where
                 self.shutdown_receiver();
                 self.shutdown_sender();
                 self.allow_requests.store(false, Ordering::SeqCst);
+                event!(DEBUG, "connection closed");
                 return Ok(Async::Ready(()));
             }
         }
@@ -345,6 +432,7 @@ where
         // We may have went from a running state to a shutdown state above, so check again.
         if self.is_shutdown() {
             self.shutdown.force_shutdown();
+            event!(DEBUG, "connection closed");
             Ok(Async::Ready(()))
         } else {
             Ok(Async::NotReady)
@@ -381,6 +469,10 @@ pub struct ConnectionHandle {
     /// A shared sender which allows us to shutdown the connection.
     shutdown_sender: Arc<Mutex<ConnectionShutdownSender>>,
 
+    /// The sender through which the receiver forwards incoming interleaved data, if anyone is
+    /// listening for it.
+    tx_interleaved_data: Arc<Mutex<InterleavedDataSender>>,
+
     /// A sender used to notify the response receiver that we want to add a new pending request.
     tx_pending_request: UnboundedSender<PendingRequestUpdate>,
 }
@@ -393,6 +485,7 @@ impl ConnectionHandle {
         rx_connection_shutdown_event: Shared<oneshot::Receiver<()>>,
         rx_handler_shutdown_event: Option<Shared<oneshot::Receiver<()>>>,
         sender_handle: SenderHandle,
+        tx_interleaved_data: Arc<Mutex<InterleavedDataSender>>,
         tx_pending_request: UnboundedSender<PendingRequestUpdate>,
         tx_initiate_shutdown: oneshot::Sender<ShutdownType>,
         graceful_shutdown_timeout_default_duration: Duration,
@@ -416,10 +509,33 @@ impl ConnectionHandle {
             sequence_number: Arc::new(Mutex::new(CSeq::random())),
             shutdown_receiver,
             shutdown_sender: Arc::new(Mutex::new(shutdown_sender)),
+            tx_interleaved_data,
             tx_pending_request,
         }
     }
 
+    /// Returns a stream of the interleaved data received on this connection.
+    ///
+    /// Only one such stream exists at a time, so calling this again ends the stream previously
+    /// returned. Interleaved data that arrives while no one is listening is dropped. The stream
+    /// ends once the connection stops receiving.
+    pub fn interleaved_data(&mut self) -> UnboundedReceiver<InterleavedData> {
+        self.tx_interleaved_data
+            .lock()
+            .expect("`ConnectionHandle.tx_interleaved_data` should not be poisoned")
+            .listen()
+    }
+
+    /// Sends the given interleaved data through the connection.
+    ///
+    /// Unlike requests, interleaved data can still be sent while the connection is gracefully
+    /// shutting down, as long as the sender is running.
+    pub fn send_interleaved_data(&self, data: InterleavedData) -> Result<(), OperationError> {
+        self.sender_handle
+            .try_send_message(Message::Data(data))
+            .map_err(|_| OperationError::Closed)
+    }
+
     /// Sends the given request with default options.
     ///
     /// See [`ConnectionHandle::send_request_with_options`] for more information.
@@ -625,13 +741,18 @@ impl Drop for ConnectionShutdownSender {
 }
 
 /// A configuration option for controlling the behavior of an RTSP connection.
+#[derive(Clone)]
 pub struct Config {
     continue_wait_duration: Option<Duration>,
     decode_timeout_duration: Duration,
     graceful_shutdown_timeout_default_duration: Duration,
+    metrics: Option<Arc<dyn Metrics>>,
+    peer_address: Option<SocketAddr>,
     request_buffer_size: usize,
     request_max_timeout_default_duration: Option<Duration>,
     request_timeout_default_duration: Option<Duration>,
+    rtsp_1_0_allowed: bool,
+    strictness: Strictness,
 }
 
 impl Config {
@@ -660,6 +781,35 @@ impl Config {
         self.graceful_shutdown_timeout_default_duration
     }
 
+    /// Converts the config into a builder with the same options, so that some of them can be
+    /// changed.
+    pub fn into_builder(self) -> ConfigBuilder {
+        ConfigBuilder {
+            continue_wait_duration: self.continue_wait_duration,
+            decode_timeout_duration: self.decode_timeout_duration,
+            graceful_shutdown_timeout_default_duration: self
+                .graceful_shutdown_timeout_default_duration,
+            metrics: self.metrics,
+            peer_address: self.peer_address,
+            request_buffer_size: self.request_buffer_size,
+            request_max_timeout_default_duration: self.request_max_timeout_default_duration,
+            request_timeout_default_duration: self.request_timeout_default_duration,
+            rtsp_1_0_allowed: self.rtsp_1_0_allowed,
+            strictness: self.strictness,
+        }
+    }
+
+    /// Returns the metrics the connection reports into, if any.
+    pub fn metrics(&self) -> Option<&Arc<dyn Metrics>> {
+        self.metrics.as_ref()
+    }
+
+    /// Returns the address of the other end of the connection, if known. This is only used to
+    /// identify the connection in diagnostics.
+    pub fn peer_address(&self) -> Option<SocketAddr> {
+        self.peer_address
+    }
+
     /// Returns how many requests are allow to be buffered on the connection.
     pub fn request_buffer_size(&self) -> usize {
         self.request_buffer_size
@@ -676,6 +826,17 @@ impl Config {
     pub fn request_timeout_default_duration(&self) -> Option<Duration> {
         self.request_timeout_default_duration
     }
+
+    /// Returns whether RTSP/1.0 requests and responses are accepted in addition to RTSP/2.0 ones.
+    pub fn rtsp_1_0_allowed(&self) -> bool {
+        self.rtsp_1_0_allowed
+    }
+
+    /// Returns the policy deciding which deviations from the syntax are tolerated in received
+    /// requests and responses.
+    pub fn strictness(&self) -> Strictness {
+        self.strictness
+    }
 }
 
 impl Default for Config {
@@ -689,9 +850,13 @@ pub struct ConfigBuilder {
     continue_wait_duration: Option<Duration>,
     decode_timeout_duration: Duration,
     graceful_shutdown_timeout_default_duration: Duration,
+    metrics: Option<Arc<dyn Metrics>>,
+    peer_address: Option<SocketAddr>,
     request_buffer_size: usize,
     request_max_timeout_default_duration: Option<Duration>,
     request_timeout_default_duration: Option<Duration>,
+    rtsp_1_0_allowed: bool,
+    strictness: Strictness,
 }
 
 impl ConfigBuilder {
@@ -702,9 +867,13 @@ impl ConfigBuilder {
             decode_timeout_duration: self.decode_timeout_duration,
             graceful_shutdown_timeout_default_duration: self
                 .graceful_shutdown_timeout_default_duration,
+            metrics: self.metrics,
+            peer_address: self.peer_address,
             request_buffer_size: self.request_buffer_size,
             request_max_timeout_default_duration: self.request_max_timeout_default_duration,
             request_timeout_default_duration: self.request_timeout_default_duration,
+            rtsp_1_0_allowed: self.rtsp_1_0_allowed,
+            strictness: self.strictness,
         }
     }
 
@@ -728,18 +897,35 @@ impl ConfigBuilder {
         self
     }
 
+    /// Sets the metrics the connection reports into.
+    pub fn metrics(&mut self, metrics: Option<Arc<dyn Metrics>>) -> &mut Self {
+        self.metrics = metrics;
+        self
+    }
+
     /// Constructs a new config builder.
     pub fn new() -> Self {
         ConfigBuilder {
             continue_wait_duration: Some(DEFAULT_CONTINUE_WAIT_DURATION),
             decode_timeout_duration: DEFAULT_DECODE_TIMEOUT_DURATION,
             graceful_shutdown_timeout_default_duration: DEFAULT_GRACEFUL_SHUTDOWN_TIMEOUT_DURATION,
+            metrics: None,
+            peer_address: None,
             request_buffer_size: DEFAULT_REQUEST_BUFFER_SIZE,
             request_max_timeout_default_duration: Some(REQUEST_MAX_TIMEOUT_DEFAULT_DURATION),
             request_timeout_default_duration: Some(REQUEST_TIMEOUT_DEFAULT_DURATION),
+            rtsp_1_0_allowed: false,
+            strictness: Strictness::default(),
         }
     }
 
+    /// Sets the address of the other end of the connection. This is only used to identify the
+    /// connection in diagnostics.
+    pub fn peer_address(&mut self, address: Option<SocketAddr>) -> &mut Self {
+        self.peer_address = address;
+        self
+    }
+
     /// Sets how many requests are allow to be buffered on the connection.
     pub fn request_buffer_size(&mut self, size: usize) -> &mut Self {
         self.request_buffer_size = size;
@@ -763,6 +949,19 @@ impl ConfigBuilder {
         self
     }
 
+    /// Sets whether RTSP/1.0 requests and responses are accepted in addition to RTSP/2.0 ones.
+    pub fn rtsp_1_0_allowed(&mut self, allowed: bool) -> &mut Self {
+        self.rtsp_1_0_allowed = allowed;
+        self
+    }
+
+    /// Sets the policy deciding which deviations from the syntax are tolerated in received requests
+    /// and responses.
+    pub fn strictness(&mut self, strictness: Strictness) -> &mut Self {
+        self.strictness = strictness;
+        self
+    }
+
     /// Consumes the builder and sets how long the server should wait to send Continue (100)
     /// responses, while a request is being processed.
     pub fn with_continue_wait_duration(mut self, duration: Option<Duration>) -> Self {
@@ -784,6 +983,19 @@ impl ConfigBuilder {
         self
     }
 
+    /// Consumes the builder and sets the metrics the connection reports into.
+    pub fn with_metrics(mut self, metrics: Option<Arc<dyn Metrics>>) -> Self {
+        self.metrics(metrics);
+        self
+    }
+
+    /// Consumes the builder and sets the address of the other end of the connection. This is only
+    /// used to identify the connection in diagnostics.
+    pub fn with_peer_address(mut self, address: Option<SocketAddr>) -> Self {
+        self.peer_address(address);
+        self
+    }
+
     /// Consumes the builder and sets how many requests are allow to be buffered on the connection.
     pub fn with_request_buffer_size(mut self, size: usize) -> Self {
         self.request_buffer_size(size);
@@ -804,6 +1016,20 @@ impl ConfigBuilder {
         self.request_timeout_default_duration(duration);
         self
     }
+
+    /// Consumes the builder and sets whether RTSP/1.0 requests and responses are accepted in
+    /// addition to RTSP/2.0 ones.
+    pub fn with_rtsp_1_0_allowed(mut self, allowed: bool) -> Self {
+        self.rtsp_1_0_allowed(allowed);
+        self
+    }
+
+    /// Consumes the builder and sets the policy deciding which deviations from the syntax are
+    /// tolerated in received requests and responses.
+    pub fn with_strictness(mut self, strictness: Strictness) -> Self {
+        self.strictness(strictness);
+        self
+    }
 }
 
 impl Default for ConfigBuilder {
@@ -867,18 +1093,21 @@ pub enum RequestTimeoutType {
 
 #[cfg(test)]
 mod test {
-    use futures::stream::{SplitSink, SplitStream};
-    use tokio_codec::Framed;
+    use std::{io::Write, net::TcpListener, time::Duration};
+
+    use futures::Future;
+    use tokio::runtime::Runtime;
     use tokio_tcp::TcpStream;
+    use tokio_timer::Timeout;
 
     use crate::protocol::{
-        codec::Codec,
         connection::{
             handler::RequestHandler,
             pending::SendRequest,
             receiver::Receiver,
             sender::{Sender, SenderHandle},
-            Connection, ConnectionHandle, ConnectionShutdownReceiver,
+            Config, Connection, ConnectionHandle, ConnectionShutdownReceiver, TransportSink,
+            TransportStream,
         },
         service::EmptyService,
     };
@@ -889,13 +1118,58 @@ mod test {
         fn check_send_and_sync<Type: Send + Sync>() {}
 
         check_send::<Connection<TcpStream>>();
-        check_send::<Receiver<SplitStream<Framed<TcpStream, Codec>>>>();
+        check_send::<Receiver<TransportStream<TcpStream>>>();
         check_send::<RequestHandler<EmptyService>>();
         check_send::<SendRequest>();
-        check_send::<Sender<SplitSink<Framed<TcpStream, Codec>>>>();
+        check_send::<Sender<TransportSink<TcpStream>>>();
 
         check_send_and_sync::<ConnectionHandle>();
         check_send_and_sync::<ConnectionShutdownReceiver>();
         check_send_and_sync::<SenderHandle>();
     }
+
+    #[test]
+    fn test_connection_closes_on_stalled_interleaved_data() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let mut runtime = Runtime::new().unwrap();
+        let transport = runtime.block_on(TcpStream::connect(&address)).unwrap();
+        let config = Config::builder()
+            .with_decode_timeout_duration(Duration::from_millis(100))
+            .build();
+        let (connection, _, mut handle) =
+            Connection::with_config(transport, None::<EmptyService>, config);
+        runtime.spawn(connection);
+
+        // The peer starts an interleaved data frame but never finishes it.
+        let (mut peer, _) = listener.accept().unwrap();
+        peer.write_all(b"$\x00\xff\xff").unwrap();
+        let closed = Timeout::new(handle.shutdown_receiver(), Duration::from_secs(5));
+        assert!(runtime.block_on(closed).is_ok());
+
+        drop(peer);
+        drop(handle);
+        runtime.shutdown_now().wait().unwrap();
+    }
+
+    #[test]
+    fn test_connection_without_handler_closes_with_peer() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let mut runtime = Runtime::new().unwrap();
+        let transport = runtime.block_on(TcpStream::connect(&address)).unwrap();
+        let (connection, handler, mut handle) =
+            Connection::with_config(transport, None::<EmptyService>, Config::default());
+        assert!(handler.is_none());
+        runtime.spawn(connection);
+
+        // Once the peer closes the connection, nothing is left to be sent on a connection without
+        // a request handler, so it is closed as well.
+        drop(listener.accept().unwrap());
+        let closed = Timeout::new(handle.shutdown_receiver(), Duration::from_secs(5));
+        assert!(runtime.block_on(closed).is_ok());
+
+        drop(handle);
+        runtime.shutdown_now().wait().unwrap();
+    }
 }
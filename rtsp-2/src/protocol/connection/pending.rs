@@ -130,6 +130,11 @@ impl SendRequest {
         if let Some(timer) = self.max_timer.as_mut() {
             match timer.poll() {
                 Ok(Async::Ready(_)) => {
+                    event!(
+                        WARN,
+                        cseq = *self.sequence_number,
+                        "request exceeded its maximum timeout"
+                    );
                     self.cancel_request();
                     return Err(OperationError::RequestTimedOut(RequestTimeoutType::Long));
                 }
@@ -151,6 +156,11 @@ impl SendRequest {
         if let Some(timer) = self.timer.as_mut() {
             match timer.poll() {
                 Ok(Async::Ready(_)) => {
+                    event!(
+                        WARN,
+                        cseq = *self.sequence_number,
+                        "request timed out waiting for a response"
+                    );
                     self.cancel_request();
                     return Err(OperationError::RequestTimedOut(RequestTimeoutType::Short));
                 }
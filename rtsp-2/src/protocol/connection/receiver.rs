@@ -3,6 +3,7 @@ use std::{
     error::Error,
     fmt::{self, Display, Formatter},
     mem,
+    sync::{Arc, Mutex},
     time::{Duration, Instant},
 };
 
@@ -11,7 +12,7 @@ use fnv::FnvBuildHasher;
 use futures::{
     stream::Fuse,
     sync::{
-        mpsc::{Sender, UnboundedReceiver},
+        mpsc::{self, Sender, UnboundedReceiver, UnboundedSender},
         oneshot,
     },
     Async, AsyncSink, Future, Poll, Sink, Stream,
@@ -20,10 +21,11 @@ use tokio_timer::Delay;
 
 use crate::{
     header::{map::HeaderMapExtension, types::CSeq},
+    metrics::Metrics,
     protocol::{
         codec::{
-            decoder::request::DecodeError as RequestDecodeError, CodecEvent, DecodeError, Message,
-            ProtocolError,
+            decoder::request::DecodeError as RequestDecodeError, interleaved::InterleavedData,
+            CodecEvent, DecodeError, Message, ProtocolError,
         },
         connection::{
             pending::{PendingRequestResponse, PendingRequestUpdate},
@@ -56,6 +58,9 @@ where
     /// order of their `"CSeq"`s.
     forwarding_receiver: Option<ForwardingReceiver>,
 
+    /// The metrics the receiver reports into, if any.
+    metrics: Option<Arc<dyn Metrics>>,
+
     /// Are requests allowed to be accepted.
     requests_allowed: bool,
 
@@ -71,6 +76,10 @@ where
     /// The underlying connection stream from which messages are read and decoded from. This stream
     /// must represent an ordered, reliable protocol (e.g. TCP).
     stream: Option<TStream>,
+
+    /// A sender shared with connection handles through which incoming interleaved data is
+    /// forwarded. Interleaved data is dropped if no one is listening for it.
+    tx_interleaved_data: Arc<Mutex<InterleavedDataSender>>,
 }
 
 impl<TStream> Receiver<TStream>
@@ -80,10 +89,13 @@ where
     /// Processes the given codec event.
     ///
     /// Encoding events are ignored, but decoding events are used to create decoding timers such
-    /// that unlively connections are not kept open.
+    /// that unlively connections are not kept open. Tolerated deviations are reported to the
+    /// metrics, if any.
     fn handle_codec_event(&mut self, event: CodecEvent) {
         use self::CodecEvent::*;
 
+        event!(TRACE, codec_event = ?event);
+
         match event {
             DecodingStarted => {
                 let expire_time = Instant::now() + self.decode_timeout_duration;
@@ -92,6 +104,13 @@ where
             DecodingEnded => {
                 self.decoding_timer = None;
             }
+            DeviationTolerated(deviation) => {
+                event!(DEBUG, %deviation, "tolerated deviation");
+
+                if let Some(metrics) = self.metrics.as_ref() {
+                    metrics.deviation_tolerated(deviation);
+                }
+            }
             _ => {}
         }
     }
@@ -103,8 +122,16 @@ where
     ///
     /// If it is a response, it will be matched against a pending request with the same `"CSeq"` (if
     /// it exists).
+    ///
+    /// If it is interleaved data, it will be forwarded to whoever is listening for it.
     fn handle_message(&mut self, message: Message) -> Result<(), RequestReceiverError> {
         match message {
+            Message::Data(data) => {
+                self.tx_interleaved_data
+                    .lock()
+                    .expect("`Receiver.tx_interleaved_data` should not be poisoned")
+                    .send(data);
+            }
             Message::Request(request) => {
                 if self.requests_allowed {
                     self.forwarding_receiver
@@ -168,6 +195,8 @@ where
             .as_ref()
             .expect("request receiver error should imply message sending is active");
 
+        event!(WARN, %error, "rejected request");
+
         match error {
             RequestReceiverError::BadRequest => {
                 let message = Message::Response(BAD_REQUEST_RESPONSE.clone());
@@ -219,6 +248,8 @@ where
         sender_handle: SenderHandle,
         decode_timeout_duration: Duration,
         request_buffer_size: usize,
+        tx_interleaved_data: Arc<Mutex<InterleavedDataSender>>,
+        metrics: Option<Arc<dyn Metrics>>,
     ) -> Self {
         Receiver {
             decode_timeout_duration,
@@ -226,12 +257,15 @@ where
             forwarding_receiver: Some(ForwardingReceiver::new(
                 tx_incoming_request,
                 request_buffer_size,
+                metrics.clone(),
             )),
+            metrics,
             requests_allowed: true,
             response_receiver: Some(ResponseReceiver::new(rx_pending_request)),
             rx_codec_event: rx_codec_event.fuse(),
             sender_handle: Some(sender_handle),
             stream: Some(stream),
+            tx_interleaved_data,
         }
     }
 
@@ -310,15 +344,16 @@ where
         let _ = self.poll_codec_events();
 
         match self.poll_decoding_timer() {
-            Ok(Async::Ready(_)) | Err(_) => {
-                // Either the decoding timer expired or there were too many timers.
-                //
-                // In the former, the other agent took too long to send anymore data, so we close
-                // receiving in order to avoid locking up resources for no reason.
-                //
-                // In the latter, we no longer have a decoder timer. Shutting down receiving serves
-                // two purposes here. One, it helps to prevent DoS attacks and two, it helps to shed
-                // load.
+            Ok(Async::Ready(_)) => {
+                // The other agent took too long to send anymore data, so we close receiving in
+                // order to avoid locking up resources for no reason.
+                event!(WARN, timeout = ?self.decode_timeout_duration, "decoding timed out");
+                return Ok(Async::Ready(()));
+            }
+            Err(_) => {
+                // We no longer have a decoder timer. Shutting down receiving serves two purposes
+                // here. One, it helps to prevent DoS attacks and two, it helps to shed load.
+                event!(WARN, "too many timers, no decoding timer available");
                 return Ok(Async::Ready(()));
             }
             _ => (),
@@ -360,9 +395,22 @@ where
                         self.stream = Some(stream);
                         return Ok(Async::NotReady);
                     }
-                    Ok(Async::Ready(None)) => return Ok(Async::Ready(())),
+                    Ok(Async::Ready(None)) => {
+                        event!(DEBUG, "stream ended");
+                        self.shutdown_stream();
+                        return Ok(Async::Ready(()));
+                    }
                     Err(error) => {
+                        event!(WARN, %error, "failed to decode message");
+
+                        if let (Some(metrics), ProtocolError::DecodeError(decode_error)) =
+                            (self.metrics.as_ref(), &error)
+                        {
+                            metrics.decode_error(*decode_error);
+                        }
+
                         self.handle_protocol_error(&error);
+                        self.shutdown_stream();
                         return Err(error);
                     }
                 }
@@ -403,8 +451,7 @@ where
         self.requests_allowed = false;
 
         if self.is_response_receiver_shutdown() {
-            self.sender_handle = None;
-            self.stream = None;
+            self.shutdown_stream();
         }
 
         if let Some(forwarding_receiver) = self.forwarding_receiver.as_mut() {
@@ -423,12 +470,33 @@ where
         self.response_receiver = None;
 
         if self.is_request_receiver_shutdown() {
-            self.sender_handle = None;
-            self.stream = None;
+            self.shutdown_stream();
         }
 
         self.is_shutdown()
     }
+
+    /// Shuts down the underlying connection stream.
+    ///
+    /// Since no more interleaved data can be received afterwards, whoever is listening for it is
+    /// notified that it has ended.
+    fn shutdown_stream(&mut self) {
+        self.sender_handle = None;
+        self.stream = None;
+        self.tx_interleaved_data
+            .lock()
+            .expect("`Receiver.tx_interleaved_data` should not be poisoned")
+            .close();
+    }
+}
+
+impl<TStream> Drop for Receiver<TStream>
+where
+    TStream: Stream<Item = Message, Error = ProtocolError> + Send + 'static,
+{
+    fn drop(&mut self) {
+        self.shutdown_stream();
+    }
 }
 
 impl<TStream> Future for Receiver<TStream>
@@ -474,6 +542,49 @@ where
     }
 }
 
+/// The sender through which incoming interleaved data is forwarded to whoever is listening for it.
+///
+/// It is shared between the receiver and the connection handles. Once the receiver closes it, no
+/// more interleaved data can arrive, so any stream listening afterwards ends right away.
+#[derive(Debug, Default)]
+pub struct InterleavedDataSender {
+    /// Whether receiving has ended.
+    closed: bool,
+
+    /// The sender of the stream currently listening for interleaved data, if any.
+    tx_interleaved_data: Option<UnboundedSender<InterleavedData>>,
+}
+
+impl InterleavedDataSender {
+    /// Ends the stream currently listening for interleaved data, if any, as well as any that
+    /// listen afterwards.
+    pub fn close(&mut self) {
+        self.closed = true;
+        self.tx_interleaved_data = None;
+    }
+
+    /// Returns a stream of the interleaved data received from now on, ending the stream previously
+    /// returned.
+    pub fn listen(&mut self) -> UnboundedReceiver<InterleavedData> {
+        let (tx_interleaved_data, rx_interleaved_data) = mpsc::unbounded();
+
+        if !self.closed {
+            self.tx_interleaved_data = Some(tx_interleaved_data);
+        }
+
+        rx_interleaved_data
+    }
+
+    /// Forwards the given interleaved data to the stream listening for it, if any.
+    pub fn send(&mut self, data: InterleavedData) {
+        if let Some(tx_interleaved_data) = self.tx_interleaved_data.as_ref() {
+            if tx_interleaved_data.unbounded_send(data).is_err() {
+                self.tx_interleaved_data = None;
+            }
+        }
+    }
+}
+
 /// Receiver responsible for forwarding incoming requests to the request handler in the order of
 /// their `"CSeq"`s.
 ///
@@ -490,6 +601,9 @@ struct ForwardingReceiver {
     /// initial `"CSeq"`.
     incoming_sequence_number: Option<CSeq>,
 
+    /// The metrics the forwarding receiver reports the buffering of requests into, if any.
+    metrics: Option<Arc<dyn Metrics>>,
+
     /// The capacity of the buffer map.
     request_buffer_size: usize,
 
@@ -525,6 +639,15 @@ impl ForwardingReceiver {
                         Entry::Occupied(_) => Err(RequestReceiverError::BadRequest),
                         Entry::Vacant(entry) => {
                             entry.insert(request);
+
+                            if let Some(metrics) = self.metrics.as_ref() {
+                                metrics.request_queued();
+
+                                if self.is_full() {
+                                    metrics.request_queue_full();
+                                }
+                            }
+
                             Ok(())
                         }
                     }
@@ -574,6 +697,7 @@ impl ForwardingReceiver {
     pub fn new(
         tx_incoming_request: Sender<(CSeq, Request<BytesMut>)>,
         request_buffer_size: usize,
+        metrics: Option<Arc<dyn Metrics>>,
     ) -> Self {
         ForwardingReceiver {
             buffered_requests: HashMap::with_capacity_and_hasher(
@@ -581,12 +705,23 @@ impl ForwardingReceiver {
                 FnvBuildHasher::default(),
             ),
             incoming_sequence_number: None,
+            metrics,
             request_buffer_size,
             tx_incoming_request,
         }
     }
 }
 
+impl Drop for ForwardingReceiver {
+    fn drop(&mut self) {
+        if let Some(metrics) = self.metrics.as_ref() {
+            self.buffered_requests
+                .iter()
+                .for_each(|_| metrics.request_dequeued());
+        }
+    }
+}
+
 impl Future for ForwardingReceiver {
     type Item = ();
     type Error = ();
@@ -610,6 +745,10 @@ impl Future for ForwardingReceiver {
                     .map_err(|_| ())?
                 {
                     AsyncSink::Ready => {
+                        if let Some(metrics) = self.metrics.as_ref() {
+                            metrics.request_dequeued();
+                        }
+
                         incoming_sequence_number = incoming_sequence_number.wrapping_increment()
                     }
                     AsyncSink::NotReady((_, request)) => {
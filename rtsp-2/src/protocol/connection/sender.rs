@@ -55,7 +55,8 @@ where
 
     /// Reads outgoing messages to be sent outwards and submits them to the internal sink.
     ///
-    /// All outgoing messages automatically have a `"Date"` header appended with the current time.
+    /// All outgoing requests and responses automatically have a `"Date"` header appended with the
+    /// current time.
     ///
     /// If `Ok(Async::Ready(()))` is returned, then the outgoing message stream has ended, so there
     /// is no longer any new messages to be sent. There may still be messages that have yet to have
@@ -76,10 +77,18 @@ where
             {
                 Async::Ready(Some(mut message)) => {
                     match message {
+                        Message::Data(_) => (),
                         Message::Request(ref mut request) => {
+                            event!(
+                                TRACE,
+                                method = %request.method(),
+                                uri = %request.uri(),
+                                "sending request"
+                            );
                             request.headers_mut().typed_insert(Date::new());
                         }
                         Message::Response(ref mut response) => {
+                            event!(TRACE, status = %response.status_code(), "sending response");
                             response.headers_mut().typed_insert(Date::new());
                         }
                     }
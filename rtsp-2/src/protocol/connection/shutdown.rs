@@ -58,6 +58,7 @@ impl ShutdownHandler {
         match shutdown_type {
             ShutdownType::Graceful(duration) => {
                 debug_assert!(self.state() != ShutdownState::Shutdown);
+                event!(DEBUG, timeout = ?duration, "graceful shutdown started");
 
                 let expire_time = Instant::now() + duration;
                 self.rx_initiate_shutdown = None;
@@ -68,6 +69,7 @@ impl ShutdownHandler {
                 self.timer = None;
 
                 if let Some(tx_shutdown_event) = self.tx_shutdown_event.take() {
+                    event!(DEBUG, "immediate shutdown");
                     let _ = tx_shutdown_event.send(());
                 }
             }
@@ -134,11 +136,13 @@ impl ShutdownHandler {
             .poll()
         {
             Ok(Async::Ready(_)) => {
+                event!(DEBUG, "graceful shutdown timed out");
                 self.handle_shutdown(ShutdownType::Immediate);
                 Ok(Async::Ready(()))
             }
             Ok(Async::NotReady) => Ok(Async::NotReady),
             Err(ref error) if error.is_at_capacity() => {
+                event!(WARN, "too many timers, ending graceful shutdown");
                 self.handle_shutdown(ShutdownType::Immediate);
                 Ok(Async::Ready(()))
             }
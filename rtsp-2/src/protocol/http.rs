@@ -0,0 +1,269 @@
+//! HTTP
+//!
+//! This module contains what the transports carrying RTSP over HTTP connections share: reading the
+//! header of HTTP requests and responses, and replaying whatever was read past it.
+//!
+//! Servers accepting such transports alongside RTSP on the same address tell them apart by the
+//! method starting the first line sent on a connection.
+
+use std::{
+    cmp,
+    error::Error,
+    io::{self, Read, Write},
+    str,
+};
+
+use bytes::BytesMut;
+use futures::{try_ready, Async, Future, Poll};
+use tokio_io::{io::write_all, AsyncRead, AsyncWrite};
+
+/// The maximum length of the header of an HTTP request or response.
+pub const MAX_HEADER_LENGTH: usize = 8192;
+
+/// The response refusing an HTTP request that does not carry RTSP.
+const BAD_REQUEST_RESPONSE: &str =
+    "HTTP/1.1 400 Bad Request\r\nConnection: close\r\nContent-Length: 0\r\n\r\n";
+
+/// The methods of the HTTP requests carrying RTSP, as they start the request line.
+const METHODS: [&[u8]; 2] = [b"GET ", b"POST "];
+
+/// A transport replaying bytes already read from it before reading any more.
+#[derive(Debug)]
+pub struct Rewind<TTransport> {
+    /// The bytes read from the transport, but not yet replayed.
+    prefix: BytesMut,
+
+    /// The transport being wrapped.
+    transport: TTransport,
+}
+
+impl<TTransport> Rewind<TTransport> {
+    /// Returns a reference to the wrapped transport.
+    pub fn get_ref(&self) -> &TTransport {
+        &self.transport
+    }
+
+    /// Constructs a new transport replaying the given bytes before reading from the given
+    /// transport.
+    pub fn new(prefix: BytesMut, transport: TTransport) -> Self {
+        Rewind { prefix, transport }
+    }
+}
+
+impl<TTransport> AsyncRead for Rewind<TTransport> where TTransport: AsyncRead {}
+
+impl<TTransport> AsyncWrite for Rewind<TTransport>
+where
+    TTransport: AsyncWrite,
+{
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        self.transport.shutdown()
+    }
+}
+
+impl<TTransport> Read for Rewind<TTransport>
+where
+    TTransport: Read,
+{
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        if self.prefix.is_empty() {
+            return self.transport.read(buffer);
+        }
+
+        let count = cmp::min(buffer.len(), self.prefix.len());
+        buffer[..count].copy_from_slice(&self.prefix.split_to(count));
+        Ok(count)
+    }
+}
+
+impl<TTransport> Write for Rewind<TTransport>
+where
+    TTransport: Write,
+{
+    fn flush(&mut self) -> io::Result<()> {
+        self.transport.flush()
+    }
+
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        self.transport.write(buffer)
+    }
+}
+
+/// The header of an HTTP request or response.
+pub(crate) struct Header {
+    /// The header fields, in order.
+    fields: Vec<(String, String)>,
+
+    /// The request line or the status line.
+    start_line: String,
+}
+
+impl Header {
+    /// Returns the value of the first header field with the given name, ignoring case.
+    pub(crate) fn field(&self, name: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(field_name, _)| field_name.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Returns whether the header field with the given name contains the given comma-separated
+    /// token, ignoring case.
+    pub(crate) fn has_token(&self, name: &str, token: &str) -> bool {
+        self.field(name).is_some_and(|value| {
+            value
+                .split(',')
+                .any(|value_token| value_token.trim().eq_ignore_ascii_case(token))
+        })
+    }
+
+    /// Returns the method of the request, or the version of the response.
+    pub(crate) fn method(&self) -> &str {
+        self.start_line.split(' ').next().unwrap_or_default()
+    }
+
+    /// Parses the header from the given bytes, including the empty line ending it.
+    fn parse(bytes: &[u8]) -> io::Result<Self> {
+        let text = str::from_utf8(bytes).map_err(invalid_data)?;
+        let mut lines = text.split("\r\n").filter(|line| !line.is_empty());
+        let start_line = lines
+            .next()
+            .ok_or_else(|| invalid_data("the HTTP header is empty"))?
+            .to_string();
+        let fields = lines
+            .map(|line| {
+                let (name, value) = line
+                    .split_once(':')
+                    .ok_or_else(|| invalid_data("an HTTP header field is missing a colon"))?;
+                Ok((name.trim().to_string(), value.trim().to_string()))
+            })
+            .collect::<io::Result<_>>()?;
+
+        Ok(Header { fields, start_line })
+    }
+
+    /// Returns the line starting the header.
+    pub(crate) fn start_line(&self) -> &str {
+        &self.start_line
+    }
+
+    /// Returns the status code of the response, or the target of the request.
+    pub(crate) fn status_code(&self) -> &str {
+        self.start_line.split(' ').nth(1).unwrap_or_default()
+    }
+}
+
+/// A future reading the header of an HTTP request or response from a transport.
+///
+/// It resolves with the header, if any, and the transport replaying whatever was read past it.
+pub(crate) struct ReadHeader<TTransport> {
+    /// The bytes read so far.
+    buffer: BytesMut,
+
+    /// Whether to stop reading as soon as the bytes read cannot start an HTTP request carrying
+    /// RTSP, in which case there is no header and everything read is replayed.
+    sniff: bool,
+
+    /// The transport being read from, until the future resolves.
+    transport: Option<TTransport>,
+}
+
+impl<TTransport> ReadHeader<TTransport> {
+    /// Constructs a new future reading the header from the given transport.
+    ///
+    /// If sniffing, connections not starting with the method of an HTTP request carrying RTSP are
+    /// left unread past their first bytes.
+    pub(crate) fn new(transport: TTransport, sniff: bool) -> Self {
+        ReadHeader {
+            buffer: BytesMut::new(),
+            sniff,
+            transport: Some(transport),
+        }
+    }
+
+    /// Resolves the future, replaying the bytes read past the header.
+    fn resolve(&mut self, header: Option<Header>) -> (Option<Header>, Rewind<TTransport>) {
+        let transport = self
+            .transport
+            .take()
+            .expect("polled `ReadHeader` after completion");
+        (header, Rewind::new(self.buffer.take(), transport))
+    }
+}
+
+impl<TTransport> Future for ReadHeader<TTransport>
+where
+    TTransport: AsyncRead,
+{
+    type Item = (Option<Header>, Rewind<TTransport>);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        loop {
+            let may_be_http = METHODS.iter().any(|method| {
+                if self.buffer.len() < method.len() {
+                    method.starts_with(&self.buffer)
+                } else {
+                    self.buffer.starts_with(method)
+                }
+            });
+
+            if self.sniff && !may_be_http {
+                return Ok(Async::Ready(self.resolve(None)));
+            }
+
+            if let Some(index) = self
+                .buffer
+                .windows(4)
+                .position(|window| window == b"\r\n\r\n")
+            {
+                let header = Header::parse(&self.buffer.split_to(index + 4))?;
+                return Ok(Async::Ready(self.resolve(Some(header))));
+            }
+
+            if self.buffer.len() >= MAX_HEADER_LENGTH {
+                return Err(invalid_data("the HTTP header is too long"));
+            }
+
+            self.buffer.reserve(1024);
+
+            let transport = self
+                .transport
+                .as_mut()
+                .expect("polled `ReadHeader` after completion");
+
+            if try_ready!(AsyncRead::read_buf(transport, &mut self.buffer)) == 0 {
+                let has_method = METHODS.iter().any(|method| self.buffer.starts_with(method));
+
+                // Connections closed before sending a whole method are left for the RTSP
+                // connection to notice.
+                if self.sniff && !has_method {
+                    return Ok(Async::Ready(self.resolve(None)));
+                }
+
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+        }
+    }
+}
+
+/// Constructs an error for invalid data received on an HTTP connection.
+pub(crate) fn invalid_data<TError>(error: TError) -> io::Error
+where
+    TError: Into<Box<dyn Error + Send + Sync>>,
+{
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+/// Refuses the HTTP request received on the given transport, failing with the given reason once
+/// the response has been written.
+pub(crate) fn refuse<TTransport, TItem>(
+    transport: TTransport,
+    reason: &'static str,
+) -> impl Future<Item = TItem, Error = io::Error> + Send
+where
+    TTransport: AsyncWrite + Send,
+    TItem: Send,
+{
+    write_all(transport, BAD_REQUEST_RESPONSE).and_then(move |_| Err(invalid_data(reason)))
+}
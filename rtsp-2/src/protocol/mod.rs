@@ -1,3 +1,6 @@
 pub mod codec;
 pub mod connection;
+pub mod http;
 pub mod service;
+pub mod tunnel;
+pub mod websocket;
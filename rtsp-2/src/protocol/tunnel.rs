@@ -0,0 +1,656 @@
+//! HTTP Tunneling
+//!
+//! This module contains the tunneling of RTSP over a pair of HTTP connections, as introduced by
+//! QuickTime for clients behind proxies and firewalls only letting HTTP through.
+//!
+//! The client sends a `GET` request on one connection, over whose response the server sends its
+//! RTSP messages, and a `POST` request on another, whose body carries the RTSP messages of the
+//! client encoded as base64. Both requests carry the same `x-sessioncookie` header, so that the
+//! server can tell which connections belong together. Once both are connected, a [`Tunnel`] fuses
+//! them into a single transport a [`Connection`] can run over.
+//!
+//! [`Connection`]: crate::protocol::connection::Connection
+
+use std::{
+    cmp,
+    collections::HashMap,
+    io::{self, Read, Write},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use bytes::BytesMut;
+use futures::{
+    future::{self, Either},
+    try_ready, Async, Future, Poll,
+};
+use rand::random;
+use tokio_executor::{DefaultExecutor, Executor};
+use tokio_io::{io::write_all, AsyncRead, AsyncWrite};
+use tokio_timer::Delay;
+
+use crate::protocol::http::{self, invalid_data, Header, ReadHeader, Rewind};
+
+/// The content type of the tunneling requests and of the response to the `GET` request.
+pub const CONTENT_TYPE: &str = "application/x-rtsp-tunnelled";
+
+/// How many connections of tunnels may wait for the other one to connect at once, beyond which the
+/// connection waiting the longest is dropped.
+pub const MAX_PENDING: usize = 1024;
+
+/// How long a connection of a tunnel waits for the other one to connect before it is dropped.
+pub const PENDING_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The name of the header field correlating the connections of a tunnel.
+pub const SESSION_COOKIE: &str = "x-sessioncookie";
+
+/// Tunnels RTSP through the given connections to the server with the given host, such as
+/// `"example.com:554"`, requesting the given path, such as `"/live"`.
+///
+/// The future resolves once the server has accepted the `GET` request and the header of the `POST`
+/// request has been written.
+pub fn connect<TTransport>(
+    get: TTransport,
+    post: TTransport,
+    host: &str,
+    path: &str,
+) -> impl Future<Item = Tunnel<Rewind<TTransport>>, Error = io::Error>
+where
+    TTransport: AsyncRead + AsyncWrite,
+{
+    let cookie = format!("{:016x}", random::<u64>());
+    let get_request = format!(
+        "GET {} HTTP/1.0\r\n\
+         Host: {}\r\n\
+         {}: {}\r\n\
+         Accept: {}\r\n\
+         Pragma: no-cache\r\n\
+         Cache-Control: no-cache\r\n\r\n",
+        path, host, SESSION_COOKIE, cookie, CONTENT_TYPE
+    );
+
+    // The length is never reached, it only keeps proxies from waiting for the whole body.
+    let post_request = format!(
+        "POST {} HTTP/1.0\r\n\
+         Host: {}\r\n\
+         {}: {}\r\n\
+         Content-Type: {}\r\n\
+         Pragma: no-cache\r\n\
+         Cache-Control: no-cache\r\n\
+         Content-Length: 32767\r\n\
+         Expires: Sun, 9 Jan 1972 00:00:00 GMT\r\n\r\n",
+        path, host, SESSION_COOKIE, cookie, CONTENT_TYPE
+    );
+
+    write_all(get, get_request)
+        .and_then(|(get, _)| ReadHeader::new(get, false))
+        .and_then(|(header, get)| match header {
+            Some(ref header) if header.status_code() == "200" => Ok(get),
+            header => Err(io::Error::new(
+                io::ErrorKind::ConnectionRefused,
+                format!(
+                    "the tunnel was refused: {}",
+                    header.as_ref().map_or("", Header::start_line)
+                ),
+            )),
+        })
+        .and_then(move |get| {
+            write_all(post, post_request)
+                .map(|(post, _)| Tunnel::client(get, Rewind::new(BytesMut::new(), post)))
+        })
+}
+
+/// A connection accepted by a [`TunnelAcceptor`].
+pub enum Accepted<TTransport> {
+    /// A connection carrying RTSP directly.
+    Direct(Rewind<TTransport>),
+
+    /// A connection of a tunnel, waiting for the other one to connect.
+    Pending,
+
+    /// A tunnel both connections of which have connected.
+    Tunnel(Tunnel<Rewind<TTransport>>),
+}
+
+/// A transport fusing the two connections of a tunnel.
+///
+/// On the side of the client, messages are read from the `GET` connection and written to the
+/// `POST` connection encoded as base64. On the side of the server, it is the other way around.
+#[derive(Debug)]
+pub struct Tunnel<TTransport> {
+    /// The bytes decoded from the `POST` connection, but not yet read.
+    decoded: BytesMut,
+
+    /// The base64 characters read from the `POST` connection, but not yet decoded, since they do
+    /// not form a complete quantum.
+    encoded: Vec<u8>,
+
+    /// The connection of the `GET` request.
+    get: TTransport,
+
+    /// The connection of the `POST` request.
+    post: TTransport,
+
+    /// The side of the tunnel this end is on.
+    side: Side,
+
+    /// The base64 characters encoded for the `POST` connection, but not yet written.
+    unwritten: Vec<u8>,
+}
+
+impl<TTransport> Tunnel<TTransport> {
+    /// Fuses the connections of a tunnel on the side of the client, once the response to the `GET`
+    /// request has been read and the header of the `POST` request has been written.
+    pub fn client(get: TTransport, post: TTransport) -> Self {
+        Tunnel::new(get, post, Side::Client)
+    }
+
+    /// Constructs a new tunnel on the given side.
+    fn new(get: TTransport, post: TTransport, side: Side) -> Self {
+        Tunnel {
+            decoded: BytesMut::new(),
+            encoded: Vec::new(),
+            get,
+            post,
+            side,
+            unwritten: Vec::new(),
+        }
+    }
+
+    /// Fuses the connections of a tunnel on the side of the server, once the headers of both
+    /// requests have been read and the response to the `GET` request has been written.
+    pub fn server(get: TTransport, post: TTransport) -> Self {
+        Tunnel::new(get, post, Side::Server)
+    }
+}
+
+impl<TTransport> Tunnel<TTransport>
+where
+    TTransport: Read,
+{
+    /// Reads the bytes decoded from the base64 characters sent on the `POST` connection.
+    ///
+    /// Each quantum of four characters is decoded on its own, since clients pad the encoding of
+    /// each message they send.
+    fn read_decoded(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        let mut chunk = [0; 4096];
+
+        while self.decoded.is_empty() {
+            let count = self.post.read(&mut chunk)?;
+
+            if count == 0 {
+                return Ok(0);
+            }
+
+            self.encoded.extend(
+                chunk[..count]
+                    .iter()
+                    .filter(|byte| !byte.is_ascii_whitespace()),
+            );
+
+            let length = self.encoded.len() / 4 * 4;
+            let mut decoded = Vec::with_capacity(length / 4 * 3);
+
+            for quantum in self.encoded[..length].chunks(4) {
+                base64::decode_config_buf(quantum, base64::STANDARD, &mut decoded)
+                    .map_err(invalid_data)?;
+            }
+
+            self.encoded.drain(..length);
+            self.decoded.extend_from_slice(&decoded);
+        }
+
+        let count = cmp::min(buffer.len(), self.decoded.len());
+        buffer[..count].copy_from_slice(&self.decoded.split_to(count));
+        Ok(count)
+    }
+}
+
+impl<TTransport> Tunnel<TTransport>
+where
+    TTransport: Write,
+{
+    /// Writes the base64 characters not yet written to the `POST` connection.
+    fn write_unwritten(&mut self) -> io::Result<()> {
+        while !self.unwritten.is_empty() {
+            let count = self.post.write(&self.unwritten)?;
+
+            if count == 0 {
+                return Err(io::ErrorKind::WriteZero.into());
+            }
+
+            self.unwritten.drain(..count);
+        }
+
+        Ok(())
+    }
+
+    /// Writes the given bytes to the `POST` connection encoded as base64.
+    ///
+    /// The bytes are accepted once everything encoded before has been written, so that at most one
+    /// write is buffered.
+    fn write_encoded(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        self.write_unwritten()?;
+        self.unwritten
+            .extend_from_slice(base64::encode(buffer).as_bytes());
+
+        match self.write_unwritten() {
+            Err(ref error) if error.kind() == io::ErrorKind::WouldBlock => Ok(buffer.len()),
+            Err(error) => Err(error),
+            Ok(()) => Ok(buffer.len()),
+        }
+    }
+}
+
+impl<TTransport> AsyncRead for Tunnel<TTransport> where TTransport: AsyncRead {}
+
+impl<TTransport> AsyncWrite for Tunnel<TTransport>
+where
+    TTransport: AsyncWrite,
+{
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        match self.write_unwritten() {
+            Err(ref error) if error.kind() == io::ErrorKind::WouldBlock => {
+                return Ok(Async::NotReady)
+            }
+            result => result?,
+        }
+
+        try_ready!(self.get.shutdown());
+        self.post.shutdown()
+    }
+}
+
+impl<TTransport> Read for Tunnel<TTransport>
+where
+    TTransport: Read,
+{
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        match self.side {
+            Side::Client => self.get.read(buffer),
+            Side::Server => self.read_decoded(buffer),
+        }
+    }
+}
+
+impl<TTransport> Write for Tunnel<TTransport>
+where
+    TTransport: Write,
+{
+    fn flush(&mut self) -> io::Result<()> {
+        match self.side {
+            Side::Client => {
+                self.write_unwritten()?;
+                self.post.flush()
+            }
+            Side::Server => self.get.flush(),
+        }
+    }
+
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        match self.side {
+            Side::Client => self.write_encoded(buffer),
+            Side::Server => self.get.write(buffer),
+        }
+    }
+}
+
+/// Pairs the connections of tunnels accepted by a server, while passing other connections through
+/// unchanged.
+///
+/// A connection of a tunnel waiting for the other one is dropped once it has waited for
+/// [`PENDING_TIMEOUT`], or once it has waited the longest when more than [`MAX_PENDING`] are
+/// waiting.
+pub struct TunnelAcceptor<TTransport> {
+    /// How many connections may wait for the other connection of their tunnel at once.
+    max_pending: usize,
+
+    /// The connections waiting for the other connection of their tunnel, by session cookie.
+    pending: Arc<Mutex<HashMap<String, PendingConnection<TTransport>>>>,
+
+    /// How long a connection waits for the other connection of its tunnel before it is dropped.
+    pending_timeout: Duration,
+}
+
+impl<TTransport> TunnelAcceptor<TTransport>
+where
+    TTransport: AsyncRead + AsyncWrite + Send + 'static,
+{
+    /// Reads the start of the given connection to determine whether it is a connection of a
+    /// tunnel, pairing it with the other connection of the tunnel if it is.
+    ///
+    /// Connections sending an HTTP request that is not tunneling RTSP are refused.
+    pub fn accept(
+        &self,
+        transport: TTransport,
+    ) -> impl Future<Item = Accepted<TTransport>, Error = io::Error> + Send {
+        let acceptor = self.clone();
+
+        ReadHeader::new(transport, true).and_then(move |(header, transport)| match header {
+            Some(header) => Either::A(acceptor.accept_request(header, transport)),
+            None => Either::B(future::ok(Accepted::Direct(transport))),
+        })
+    }
+
+    /// Accepts the connection the given HTTP request was received on, pairing it with the other
+    /// connection of its tunnel.
+    pub(crate) fn accept_request(
+        &self,
+        header: Header,
+        transport: Rewind<TTransport>,
+    ) -> impl Future<Item = Accepted<TTransport>, Error = io::Error> + Send {
+        let acceptor = self.clone();
+
+        match (header.method(), header.field(SESSION_COOKIE)) {
+            ("GET", Some(cookie)) => {
+                let cookie = cookie.to_string();
+                let response = format!(
+                    "HTTP/1.0 200 OK\r\n\
+                     Connection: close\r\n\
+                     Cache-Control: no-store\r\n\
+                     Pragma: no-cache\r\n\
+                     Content-Type: {}\r\n\r\n",
+                    CONTENT_TYPE
+                );
+
+                Either::A(Either::A(write_all(transport, response).map(
+                    move |(transport, _)| acceptor.pair(cookie, Direction::ToClient, transport),
+                )))
+            }
+            ("POST", Some(cookie)) => Either::B(future::ok(acceptor.pair(
+                cookie.to_string(),
+                Direction::ToServer,
+                transport,
+            ))),
+            _ => Either::A(Either::B(http::refuse(
+                transport,
+                "the request is not tunneling RTSP",
+            ))),
+        }
+    }
+
+    /// Drops the pending connection with the given session cookie once it has waited for the
+    /// timeout, unless it has been paired or replaced by then.
+    fn expire(&self, cookie: String, accepted_at: Instant) {
+        let pending = Arc::downgrade(&self.pending);
+        let expiry = Delay::new(accepted_at + self.pending_timeout)
+            .map_err(|_| ())
+            .map(move |()| {
+                if let Some(pending) = pending.upgrade() {
+                    let mut pending = pending.lock().unwrap();
+
+                    if pending
+                        .get(&cookie)
+                        .is_some_and(|connection| connection.accepted_at == accepted_at)
+                    {
+                        pending.remove(&cookie);
+                    }
+                }
+            });
+
+        // Without an executor, expired connections are dropped once another one is accepted.
+        let _ = DefaultExecutor::current().spawn(Box::new(expiry));
+    }
+
+    /// Constructs a new acceptor without any pending connections.
+    pub fn new() -> Self {
+        TunnelAcceptor::default()
+    }
+
+    /// Pairs the given connection with the pending connection with the same session cookie if
+    /// there is one, and leaves it pending otherwise.
+    fn pair(
+        &self,
+        cookie: String,
+        direction: Direction,
+        transport: Rewind<TTransport>,
+    ) -> Accepted<TTransport> {
+        let mut pending = self.pending.lock().unwrap();
+        let now = Instant::now();
+        pending.retain(|_, connection| now - connection.accepted_at < self.pending_timeout);
+
+        match pending.remove(&cookie) {
+            Some(other) if other.direction != direction => {
+                let (get, post) = match direction {
+                    Direction::ToClient => (transport, other.transport),
+                    Direction::ToServer => (other.transport, transport),
+                };
+
+                Accepted::Tunnel(Tunnel::server(get, post))
+            }
+
+            // A connection reusing the cookie of a pending connection in the same direction
+            // replaces it.
+            _ => {
+                if pending.len() >= self.max_pending {
+                    let oldest = pending
+                        .iter()
+                        .min_by_key(|(_, connection)| connection.accepted_at)
+                        .map(|(cookie, _)| cookie.clone());
+
+                    if let Some(oldest) = oldest {
+                        pending.remove(&oldest);
+                    }
+                }
+
+                pending.insert(
+                    cookie.clone(),
+                    PendingConnection {
+                        accepted_at: now,
+                        direction,
+                        transport,
+                    },
+                );
+                self.expire(cookie, now);
+
+                Accepted::Pending
+            }
+        }
+    }
+}
+
+impl<TTransport> Clone for TunnelAcceptor<TTransport> {
+    fn clone(&self) -> Self {
+        TunnelAcceptor {
+            max_pending: self.max_pending,
+            pending: self.pending.clone(),
+            pending_timeout: self.pending_timeout,
+        }
+    }
+}
+
+impl<TTransport> Default for TunnelAcceptor<TTransport> {
+    fn default() -> Self {
+        TunnelAcceptor {
+            max_pending: MAX_PENDING,
+            pending: Arc::new(Mutex::new(HashMap::new())),
+            pending_timeout: PENDING_TIMEOUT,
+        }
+    }
+}
+
+/// The direction RTSP messages flow in over a connection of a tunnel.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Direction {
+    /// From the server to the client, over the response to the `GET` request.
+    ToClient,
+
+    /// From the client to the server, over the body of the `POST` request.
+    ToServer,
+}
+
+/// A connection of a tunnel waiting for the other one to connect.
+struct PendingConnection<TTransport> {
+    /// When the connection was accepted.
+    accepted_at: Instant,
+
+    /// The direction of the connection.
+    direction: Direction,
+
+    /// The connection itself.
+    transport: Rewind<TTransport>,
+}
+
+/// The side of a tunnel.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Side {
+    Client,
+    Server,
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        io::{Read, Write},
+        thread,
+        time::Duration,
+    };
+
+    use futures::{future, Future};
+    use tokio::runtime::Runtime;
+    use tokio_io::io::read_exact;
+
+    use crate::{
+        protocol::tunnel::{connect, Accepted, Tunnel, TunnelAcceptor},
+        testing::duplex::{duplex, DuplexStream},
+    };
+
+    /// Accepts a `POST` request with the given session cookie, returning the client side of the
+    /// connection so that it stays open.
+    fn accept_post(
+        runtime: &mut Runtime,
+        acceptor: &TunnelAcceptor<DuplexStream>,
+        cookie: &str,
+    ) -> DuplexStream {
+        let (mut client, server) = duplex();
+        write!(
+            client,
+            "POST /live HTTP/1.0\r\nx-sessioncookie: {}\r\n\r\n",
+            cookie
+        )
+        .unwrap();
+
+        match runtime.block_on(acceptor.accept(server)).unwrap() {
+            Accepted::Pending => client,
+            _ => panic!("expected a pending connection"),
+        }
+    }
+
+    #[test]
+    fn test_tunnel() {
+        let (client_get, server_get) = duplex();
+        let (client_post, server_post) = duplex();
+        let mut client = Tunnel::client(client_get, client_post);
+        let server = Tunnel::server(server_get, server_post);
+
+        client.write_all(b"OPTIONS * RTSP/2.0\r\n\r\n").unwrap();
+        client.write_all(b"GET_PARAMETER").unwrap();
+        let (mut server, received) = read_exact(server, [0; 35]).wait().unwrap();
+        assert_eq!(
+            &received[..],
+            &b"OPTIONS * RTSP/2.0\r\n\r\nGET_PARAMETER"[..]
+        );
+
+        server.write_all(b"RTSP/2.0 200 OK\r\n\r\n").unwrap();
+        let mut buffer = [0; 19];
+        future::lazy(|| client.read(&mut buffer)).wait().unwrap();
+        assert_eq!(&buffer, b"RTSP/2.0 200 OK\r\n\r\n");
+
+        // Whitespace between the encoded messages is ignored, but not invalid characters.
+        let (_client_get, server_get) = duplex();
+        let (mut client_post, server_post) = duplex();
+        client_post.write_all(b"T1BU\r\nSU9O\r\nUw==").unwrap();
+        let server = Tunnel::server(server_get, server_post);
+        let (mut server, received) = read_exact(server, [0; 7]).wait().unwrap();
+        assert_eq!(&received, b"OPTIONS");
+
+        client_post.write_all(b"!!!!").unwrap();
+        assert!(future::lazy(|| server.read(&mut buffer)).wait().is_err());
+    }
+
+    #[test]
+    fn test_tunnel_acceptor() {
+        let acceptor = TunnelAcceptor::new();
+
+        let (mut client, server) = duplex();
+        client
+            .write_all(b"GET_PARAMETER * RTSP/2.0\r\n\r\n")
+            .unwrap();
+        match acceptor.accept(server).wait().unwrap() {
+            Accepted::Direct(transport) => {
+                let (_, received) = read_exact(transport, [0; 13]).wait().unwrap();
+                assert_eq!(&received, b"GET_PARAMETER");
+            }
+            _ => panic!("expected a direct connection"),
+        }
+
+        let (mut client, server) = duplex();
+        client.write_all(b"POST / HTTP/1.0\r\n\r\n").unwrap();
+        assert!(acceptor.accept(server).wait().is_err());
+
+        let (client_get, server_get) = duplex();
+        let (client_post, server_post) = duplex();
+        let accepting = acceptor
+            .accept(server_get)
+            .join(acceptor.accept(server_post));
+        let (get, post) = connect(client_get, client_post, "127.0.0.1", "/live")
+            .join(accepting)
+            .map(|(mut client, accepted)| {
+                client.write_all(b"OPTIONS").unwrap();
+                (client, accepted)
+            })
+            .wait()
+            .unwrap()
+            .1;
+
+        let server = match (get, post) {
+            (Accepted::Pending, Accepted::Tunnel(server)) => server,
+            _ => panic!("expected the connections to be paired"),
+        };
+        let (_, received) = read_exact(server, [0; 7]).wait().unwrap();
+        assert_eq!(&received, b"OPTIONS");
+    }
+
+    #[test]
+    fn test_tunnel_acceptor_drops_expired_connections() {
+        let mut runtime = Runtime::new().unwrap();
+        let mut acceptor = TunnelAcceptor::new();
+        acceptor.pending_timeout = Duration::from_millis(100);
+
+        let _client = accept_post(&mut runtime, &acceptor, "a");
+        assert_eq!(acceptor.pending.lock().unwrap().len(), 1);
+
+        // The connection is dropped without waiting for another one to be accepted.
+        thread::sleep(Duration::from_millis(500));
+        assert!(acceptor.pending.lock().unwrap().is_empty());
+
+        drop(acceptor);
+        runtime.shutdown_on_idle().wait().unwrap();
+    }
+
+    #[test]
+    fn test_tunnel_acceptor_limits_pending_connections() {
+        let mut runtime = Runtime::new().unwrap();
+        let mut acceptor = TunnelAcceptor::new();
+        acceptor.max_pending = 2;
+
+        let _clients = ["a", "b", "c"]
+            .iter()
+            .map(|cookie| {
+                let client = accept_post(&mut runtime, &acceptor, cookie);
+                thread::sleep(Duration::from_millis(10));
+                client
+            })
+            .collect::<Vec<_>>();
+
+        // The connection waiting the longest makes room for the latest one.
+        let pending = acceptor.pending.lock().unwrap();
+        assert_eq!(pending.len(), 2);
+        assert!(!pending.contains_key("a"));
+        assert!(pending.contains_key("b") && pending.contains_key("c"));
+        drop(pending);
+
+        drop(acceptor);
+        runtime.shutdown_now().wait().unwrap();
+    }
+}
@@ -0,0 +1,601 @@
+//! WebSocket Transport
+//!
+//! This module contains the transport of RTSP over WebSocket connections, for clients such as web
+//! players that cannot open TCP connections of their own.
+//!
+//! Following common practice, each RTSP message and each interleaved data frame is sent in a binary
+//! message of its own. Messages received, whether binary or text, are read as a continuous stream,
+//! so that peers may also split RTSP messages across several of them. The `rtsp` subprotocol is
+//! preferred, but clients offering `binary`, as websockify based proxies do, or no subprotocol at
+//! all are accepted as well.
+
+use std::{
+    cmp,
+    io::{self, Read, Write},
+    str,
+};
+
+use bytes::{BufMut, BytesMut};
+use futures::{
+    future::{self, Either},
+    Async, Future, Poll,
+};
+use rand::random;
+use sha1::{Digest, Sha1};
+use tokio_io::{io::write_all, AsyncRead, AsyncWrite};
+
+use crate::protocol::http::{self, invalid_data, Header, ReadHeader, Rewind};
+
+/// The maximum length of the payload of a frame received.
+pub const MAX_FRAME_LENGTH: u64 = 1 << 24;
+
+/// The subprotocols accepted by servers, in order of preference.
+pub const SUBPROTOCOLS: [&str; 2] = ["rtsp", "binary"];
+
+/// The GUID appended to the key of the opening handshake before hashing it.
+const ACCEPT_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+const OPCODE_BINARY: u8 = 0x2;
+const OPCODE_CLOSE: u8 = 0x8;
+const OPCODE_CONTINUATION: u8 = 0x0;
+const OPCODE_PING: u8 = 0x9;
+const OPCODE_PONG: u8 = 0xA;
+const OPCODE_TEXT: u8 = 0x1;
+
+/// Reads the opening handshake from the given connection, accepting it if it upgrades to
+/// WebSocket.
+pub fn accept<TTransport>(
+    transport: TTransport,
+) -> impl Future<Item = WebSocket<Rewind<TTransport>>, Error = io::Error> + Send
+where
+    TTransport: AsyncRead + AsyncWrite + Send,
+{
+    ReadHeader::new(transport, false).and_then(|(header, transport)| match header {
+        Some(header) => Either::A(accept_request(header, transport)),
+        None => Either::B(future::err(invalid_data("the request is empty"))),
+    })
+}
+
+/// Accepts the opening handshake with the given HTTP request received on the given connection.
+///
+/// Requests that do not upgrade to WebSocket are refused.
+pub(crate) fn accept_request<TTransport>(
+    header: Header,
+    transport: Rewind<TTransport>,
+) -> impl Future<Item = WebSocket<Rewind<TTransport>>, Error = io::Error> + Send
+where
+    TTransport: AsyncWrite + Send,
+{
+    let key = match header.field("Sec-WebSocket-Key") {
+        Some(key)
+            if header.method() == "GET"
+                && is_upgrade(&header)
+                && header.has_token("Connection", "upgrade")
+                && header.field("Sec-WebSocket-Version") == Some("13") =>
+        {
+            key
+        }
+        _ => {
+            return Either::B(http::refuse(
+                transport,
+                "the request is not a WebSocket upgrade",
+            ))
+        }
+    };
+    let subprotocol = SUBPROTOCOLS
+        .iter()
+        .find(|&&subprotocol| header.has_token("Sec-WebSocket-Protocol", subprotocol))
+        .map(|subprotocol| format!("Sec-WebSocket-Protocol: {}\r\n", subprotocol))
+        .unwrap_or_default();
+    let response = format!(
+        "HTTP/1.1 101 Switching Protocols\r\n\
+         Upgrade: websocket\r\n\
+         Connection: Upgrade\r\n\
+         Sec-WebSocket-Accept: {}\r\n\
+         {}\r\n",
+        accept_key(key),
+        subprotocol
+    );
+
+    Either::A(write_all(transport, response).map(|(transport, _)| WebSocket::server(transport)))
+}
+
+/// Upgrades the given connection to the server with the given host, such as `"example.com:554"`,
+/// to WebSocket, requesting the given path, such as `"/live"`.
+///
+/// The `rtsp` subprotocol is offered.
+pub fn connect<TTransport>(
+    transport: TTransport,
+    host: &str,
+    path: &str,
+) -> impl Future<Item = WebSocket<Rewind<TTransport>>, Error = io::Error>
+where
+    TTransport: AsyncRead + AsyncWrite,
+{
+    let key = base64::encode(&random::<[u8; 16]>());
+    let expected_key = accept_key(&key);
+    let request = format!(
+        "GET {} HTTP/1.1\r\n\
+         Host: {}\r\n\
+         Upgrade: websocket\r\n\
+         Connection: Upgrade\r\n\
+         Sec-WebSocket-Key: {}\r\n\
+         Sec-WebSocket-Version: 13\r\n\
+         Sec-WebSocket-Protocol: {}\r\n\r\n",
+        path, host, key, SUBPROTOCOLS[0]
+    );
+
+    write_all(transport, request)
+        .and_then(|(transport, _)| ReadHeader::new(transport, false))
+        .and_then(move |(header, transport)| match header {
+            Some(ref header)
+                if header.status_code() == "101"
+                    && header.field("Sec-WebSocket-Accept") == Some(expected_key.as_str()) =>
+            {
+                Ok(WebSocket::client(transport))
+            }
+            header => Err(io::Error::new(
+                io::ErrorKind::ConnectionRefused,
+                format!(
+                    "the WebSocket upgrade was refused: {}",
+                    header.as_ref().map_or("", Header::start_line)
+                ),
+            )),
+        })
+}
+
+/// Returns whether the given HTTP request asks to upgrade to WebSocket.
+pub(crate) fn is_upgrade(header: &Header) -> bool {
+    header.has_token("Upgrade", "websocket")
+}
+
+/// A transport carrying RTSP over a WebSocket connection.
+#[derive(Debug)]
+pub struct WebSocket<TTransport> {
+    /// Whether the peer has closed the connection, in which case nothing more is read.
+    closed: bool,
+
+    /// Whether a close frame has been sent, in which case nothing more is written.
+    closing: bool,
+
+    /// The payload of the data frames received, but not yet read.
+    payload: BytesMut,
+
+    /// The bytes received, but not yet parsed into frames.
+    received: BytesMut,
+
+    /// The side of the connection this end is on.
+    side: Side,
+
+    /// The WebSocket connection.
+    transport: TTransport,
+
+    /// The bytes written that do not yet form a whole RTSP message or interleaved data frame.
+    ///
+    /// Messages may be written in pieces, such as their header and then their body, and they are
+    /// held here until whole so that each one is sent in a binary message of its own.
+    unframed: BytesMut,
+
+    /// The frames not yet written to the connection.
+    unwritten: BytesMut,
+}
+
+impl<TTransport> WebSocket<TTransport> {
+    /// Wraps a connection on the side of the client, once the opening handshake has completed.
+    pub fn client(transport: TTransport) -> Self {
+        WebSocket::new(transport, Side::Client)
+    }
+
+    /// Constructs a new transport on the given side.
+    fn new(transport: TTransport, side: Side) -> Self {
+        WebSocket {
+            closed: false,
+            closing: false,
+            payload: BytesMut::new(),
+            received: BytesMut::new(),
+            side,
+            transport,
+            unframed: BytesMut::new(),
+            unwritten: BytesMut::new(),
+        }
+    }
+
+    /// Parses the first frame of the bytes received, if it has been received whole, returning its
+    /// opcode and its unmasked payload.
+    fn parse_frame(&mut self) -> io::Result<Option<(u8, BytesMut)>> {
+        let received = &self.received;
+
+        if received.len() < 2 {
+            return Ok(None);
+        }
+
+        // No extensions are negotiated, so the reserved bits must not be set.
+        if received[0] & 0x70 != 0 {
+            return Err(invalid_data("a WebSocket frame has reserved bits set"));
+        }
+
+        // Clients mask the frames they send, servers do not.
+        let is_masked = received[1] & 0x80 != 0;
+
+        if is_masked != (self.side == Side::Server) {
+            return Err(invalid_data("a WebSocket frame is masked incorrectly"));
+        }
+
+        let (length, mut offset) = match received[1] & 0x7F {
+            126 if received.len() >= 4 => {
+                (u64::from(u16::from_be_bytes([received[2], received[3]])), 4)
+            }
+            127 if received.len() >= 10 => {
+                let mut length = [0; 8];
+                length.copy_from_slice(&received[2..10]);
+                (u64::from_be_bytes(length), 10)
+            }
+            126 | 127 => return Ok(None),
+            length => (u64::from(length), 2),
+        };
+
+        if length > MAX_FRAME_LENGTH {
+            return Err(invalid_data("a WebSocket frame is too long"));
+        }
+
+        // Control frames must not be fragmented, and their payload must be at most 125 bytes long.
+        if received[0] & 0x08 != 0 && (received[0] & 0x80 == 0 || length > 125) {
+            return Err(invalid_data(
+                "a WebSocket control frame is too long or fragmented",
+            ));
+        }
+
+        let mut mask = [0; 4];
+
+        if is_masked {
+            if received.len() < offset + 4 {
+                return Ok(None);
+            }
+
+            mask.copy_from_slice(&received[offset..offset + 4]);
+            offset += 4;
+        }
+
+        let length = length as usize;
+
+        if received.len() < offset + length {
+            return Ok(None);
+        }
+
+        let opcode = received[0] & 0x0F;
+        self.received.advance(offset);
+        let mut payload = self.received.split_to(length);
+
+        for (index, byte) in payload.iter_mut().enumerate() {
+            *byte ^= mask[index % 4];
+        }
+
+        Ok(Some((opcode, payload)))
+    }
+
+    /// Appends a frame with the given opcode and payload to the frames not yet written.
+    fn push_frame(&mut self, opcode: u8, payload: &[u8]) {
+        let mask_bit = if self.side == Side::Client { 0x80 } else { 0 };
+        self.unwritten.reserve(payload.len() + 14);
+        self.unwritten.put_u8(0x80 | opcode);
+
+        if payload.len() < 126 {
+            self.unwritten.put_u8(mask_bit | payload.len() as u8);
+        } else if payload.len() <= usize::from(u16::MAX) {
+            self.unwritten.put_u8(mask_bit | 126);
+            self.unwritten.put_u16_be(payload.len() as u16);
+        } else {
+            self.unwritten.put_u8(mask_bit | 127);
+            self.unwritten.put_u64_be(payload.len() as u64);
+        }
+
+        if self.side == Side::Client {
+            let mask = random::<[u8; 4]>();
+            self.unwritten.put_slice(&mask);
+            self.unwritten.extend(
+                payload
+                    .iter()
+                    .enumerate()
+                    .map(|(index, byte)| byte ^ mask[index % 4]),
+            );
+        } else {
+            self.unwritten.put_slice(payload);
+        }
+    }
+
+    /// Wraps a connection on the side of the server, once the opening handshake has completed.
+    pub fn server(transport: TTransport) -> Self {
+        WebSocket::new(transport, Side::Server)
+    }
+}
+
+impl<TTransport> WebSocket<TTransport>
+where
+    TTransport: Write,
+{
+    /// Writes the frames not yet written to the connection.
+    fn write_unwritten(&mut self) -> io::Result<()> {
+        while !self.unwritten.is_empty() {
+            let count = self.transport.write(&self.unwritten)?;
+
+            if count == 0 {
+                return Err(io::ErrorKind::WriteZero.into());
+            }
+
+            self.unwritten.advance(count);
+        }
+
+        Ok(())
+    }
+
+    /// Writes the frames not yet written to the connection for as long as it does not block.
+    fn write_unwritten_until_blocked(&mut self) -> io::Result<()> {
+        match self.write_unwritten() {
+            Err(ref error) if error.kind() == io::ErrorKind::WouldBlock => Ok(()),
+            result => result,
+        }
+    }
+}
+
+impl<TTransport> AsyncRead for WebSocket<TTransport> where TTransport: AsyncRead + AsyncWrite {}
+
+impl<TTransport> AsyncWrite for WebSocket<TTransport>
+where
+    TTransport: AsyncWrite,
+{
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        if !self.closing {
+            self.closing = true;
+            self.push_frame(OPCODE_CLOSE, &1000u16.to_be_bytes());
+        }
+
+        match self.write_unwritten() {
+            Err(ref error) if error.kind() == io::ErrorKind::WouldBlock => {
+                return Ok(Async::NotReady)
+            }
+            result => result?,
+        }
+
+        self.transport.shutdown()
+    }
+}
+
+impl<TTransport> Read for WebSocket<TTransport>
+where
+    TTransport: Read + Write,
+{
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        let mut chunk = [0; 4096];
+
+        while self.payload.is_empty() {
+            if self.closed {
+                return Ok(0);
+            }
+
+            match self.parse_frame()? {
+                Some((OPCODE_BINARY, payload))
+                | Some((OPCODE_CONTINUATION, payload))
+                | Some((OPCODE_TEXT, payload)) => self.payload.extend_from_slice(&payload),
+                Some((OPCODE_CLOSE, _)) => {
+                    self.closed = true;
+
+                    if !self.closing {
+                        self.closing = true;
+                        self.push_frame(OPCODE_CLOSE, &1000u16.to_be_bytes());
+                        self.write_unwritten_until_blocked()?;
+                    }
+                }
+                Some((OPCODE_PING, payload)) => {
+                    if !self.closing {
+                        self.push_frame(OPCODE_PONG, &payload);
+                        self.write_unwritten_until_blocked()?;
+                    }
+                }
+                Some((OPCODE_PONG, _)) => {}
+                Some(_) => return Err(invalid_data("a WebSocket frame has an unknown opcode")),
+                None => {
+                    let count = self.transport.read(&mut chunk)?;
+
+                    if count == 0 {
+                        // The frame received in part can never be completed.
+                        if !self.received.is_empty() {
+                            return Err(io::Error::new(
+                                io::ErrorKind::UnexpectedEof,
+                                "the connection closed in the middle of a WebSocket frame",
+                            ));
+                        }
+
+                        self.closed = true;
+                    }
+
+                    self.received.extend_from_slice(&chunk[..count]);
+                }
+            }
+        }
+
+        let count = cmp::min(buffer.len(), self.payload.len());
+        buffer[..count].copy_from_slice(&self.payload.split_to(count));
+        Ok(count)
+    }
+}
+
+impl<TTransport> Write for WebSocket<TTransport>
+where
+    TTransport: Write,
+{
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_unwritten()?;
+        self.transport.flush()
+    }
+
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        if self.closing {
+            return Err(io::ErrorKind::BrokenPipe.into());
+        }
+
+        // At most one write is buffered.
+        self.write_unwritten()?;
+        self.unframed.extend_from_slice(buffer);
+
+        while let Some(length) = message_length(&self.unframed) {
+            let message = self.unframed.split_to(length);
+            self.push_frame(OPCODE_BINARY, &message);
+        }
+
+        self.write_unwritten_until_blocked()?;
+        Ok(buffer.len())
+    }
+}
+
+/// The side of a WebSocket connection.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Side {
+    Client,
+    Server,
+}
+
+/// Returns the value of the `Sec-WebSocket-Accept` header field accepting the given key.
+fn accept_key(key: &str) -> String {
+    base64::encode(&Sha1::digest(format!("{}{}", key, ACCEPT_GUID).as_bytes()))
+}
+
+/// Returns the length of the RTSP message or interleaved data frame starting the given bytes, if
+/// they contain it whole.
+///
+/// The header is scanned as bytes, so that a message whose header is not valid UTF-8 is still
+/// framed rather than held back along with every message written after it.
+fn message_length(bytes: &[u8]) -> Option<usize> {
+    if bytes.first() == Some(&b'$') {
+        let length = 4 + usize::from(u16::from_be_bytes([*bytes.get(2)?, *bytes.get(3)?]));
+        return if bytes.len() >= length {
+            Some(length)
+        } else {
+            None
+        };
+    }
+
+    let header_length = bytes.windows(4).position(|window| window == b"\r\n\r\n")? + 4;
+    let body_length = bytes[..header_length]
+        .split(|&byte| byte == b'\n')
+        .filter_map(|line| {
+            let colon = line.iter().position(|&byte| byte == b':')?;
+            Some((&line[..colon], &line[colon + 1..]))
+        })
+        .find(|(name, _)| name.trim_ascii().eq_ignore_ascii_case(b"Content-Length"))
+        .and_then(|(_, value)| {
+            str::from_utf8(value.trim_ascii())
+                .ok()?
+                .parse::<usize>()
+                .ok()
+        })
+        .unwrap_or(0);
+    let length = header_length + body_length;
+
+    if bytes.len() >= length {
+        Some(length)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{self, Read, Write};
+
+    use futures::{future, Future};
+    use tokio_io::io::read_exact;
+
+    use crate::{
+        protocol::websocket::{accept, accept_key, connect, message_length, WebSocket},
+        testing::duplex::duplex,
+    };
+
+    #[test]
+    fn test_accept_key() {
+        // The example of RFC 6455.
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+    }
+
+    #[test]
+    fn test_message_length() {
+        assert_eq!(message_length(b"$\x00\x00\x02ab$"), Some(6));
+        assert_eq!(message_length(b"$\x00\x00\x02a"), None);
+        assert_eq!(message_length(b"OPTIONS * RTSP/2.0\r\n\r\nPLAY"), Some(22));
+        assert_eq!(
+            message_length(b"RTSP/2.0 200 OK\r\ncontent-length: 2\r\n\r\nab$"),
+            Some(40)
+        );
+        assert_eq!(
+            message_length(b"RTSP/2.0 200 OK\r\nContent-Length: 2\r\n\r\na"),
+            None
+        );
+        assert_eq!(message_length(b"RTSP/2.0 200 OK\r\n"), None);
+        assert_eq!(
+            message_length(b"RTSP/2.0 200 OK\r\nX: \xff\r\nContent-Length: 1\r\n\r\nab"),
+            Some(45)
+        );
+    }
+
+    #[test]
+    fn test_websocket() {
+        let (client, server) = duplex();
+        let (mut client, server) = connect(client, "127.0.0.1", "/live")
+            .join(accept(server))
+            .wait()
+            .unwrap();
+
+        // Each message is sent in a frame of its own, even if written in pieces.
+        client.write_all(b"OPTIONS * RTSP/2.0\r\n").unwrap();
+        client.write_all(b"\r\n$\x00\x00\x02ab").unwrap();
+        let (mut server, received) = read_exact(server, [0; 28]).wait().unwrap();
+        assert_eq!(
+            &received[..],
+            &b"OPTIONS * RTSP/2.0\r\n\r\n$\x00\x00\x02ab"[..]
+        );
+
+        server.write_all(b"RTSP/2.0 200 OK\r\n\r\n").unwrap();
+        let mut buffer = [0; 19];
+        future::lazy(|| client.read(&mut buffer)).wait().unwrap();
+        assert_eq!(&buffer, b"RTSP/2.0 200 OK\r\n\r\n");
+
+        // Text messages fragmented by the peer are read as well, and pings are answered.
+        let (mut peer, transport) = duplex();
+        let mut server = WebSocket::server(transport);
+        peer.write_all(b"\x01\x82\x00\x00\x00\x00PL").unwrap();
+        peer.write_all(b"\x89\x80\x00\x00\x00\x00").unwrap();
+        peer.write_all(b"\x80\x82\x01\x01\x01\x01@@").unwrap();
+        let mut buffer = [0; 4];
+        let count = future::lazy(|| server.read(&mut buffer)).wait().unwrap();
+        assert_eq!(&buffer[..count], b"PL");
+        let count = future::lazy(|| server.read(&mut buffer)).wait().unwrap();
+        assert_eq!(&buffer[..count], b"AA");
+        let (_, pong) = read_exact(peer, [0; 2]).wait().unwrap();
+        assert_eq!(pong, [0x8A, 0x00]);
+
+        // Unmasked frames from clients are refused.
+        let (mut peer, transport) = duplex();
+        let mut server = WebSocket::server(transport);
+        peer.write_all(b"\x82\x02ab").unwrap();
+        assert!(future::lazy(|| server.read(&mut buffer)).wait().is_err());
+
+        // Control frames longer than 125 bytes are refused.
+        let (mut peer, transport) = duplex();
+        let mut server = WebSocket::server(transport);
+        peer.write_all(b"\x89\xfe\x00\x7e\x00\x00\x00\x00").unwrap();
+        peer.write_all(&[0; 126]).unwrap();
+        assert!(future::lazy(|| server.read(&mut buffer)).wait().is_err());
+
+        // Frames cut short by the connection closing are refused rather than left unread.
+        let (mut peer, transport) = duplex();
+        let mut server = WebSocket::server(transport);
+        peer.write_all(b"\x82\x84\x00\x00\x00\x00ab").unwrap();
+        drop(peer);
+        let error = future::lazy(|| server.read(&mut buffer))
+            .wait()
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
@@ -0,0 +1,522 @@
+//! Relay
+//!
+//! A relay serves the presentation of an upstream server, such as a camera, to any number of
+//! clients while holding a single session with the upstream server. The upstream presentation is
+//! described once when a client first asks for it, but it is only played while at least one client
+//! is playing it through the relay.
+//!
+//! Streams are received from the upstream server interleaved in the RTSP connection, depacketized
+//! and served again as live streams, so clients can use any transport the server supports.
+
+use std::{
+    collections::HashSet,
+    convert::TryFrom,
+    error::Error,
+    fmt::{self, Display, Formatter},
+    io,
+    net::{SocketAddr, ToSocketAddrs},
+    str,
+};
+
+use bytes::BytesMut;
+use futures::{
+    future::{self, Either},
+    stream,
+    sync::{mpsc, oneshot},
+    Future, Stream,
+};
+
+use crate::{
+    client::Client,
+    header::{map::HeaderMapExtension, name::HeaderName, types::Session, value::HeaderValue},
+    media::{
+        depacketizer::{self, Reassembler},
+        format::{FormatParameters, RTPMap},
+        ingest::Ingest,
+        jitter::Config as JitterBufferConfig,
+        live::{LiveStream, LiveStreamSender},
+        sdp::{MediaDescription, SessionDescription, SDP_CONTENT_TYPE},
+    },
+    method::Method,
+    protocol::{codec::interleaved::InterleavedData, connection::OperationError},
+    request::Request,
+    response::Response,
+    session::SessionID,
+    status::StatusCode,
+    uri::{request::URI, RTSP_DEFAULT_PORT},
+};
+
+/// The feature tags supported by the relay, as listed in `"Proxy-Supported"` headers. Requests
+/// requiring any other feature of proxies through `"Proxy-Require"` are refused.
+pub const PROXY_FEATURES: [&str; 1] = ["play.basic"];
+
+/// A presentation of an upstream server served under a path.
+pub(crate) struct Relay {
+    /// Stops the upstream session while it is being played, also when dropped.
+    tx_stop: Option<oneshot::Sender<()>>,
+
+    /// The streams of the upstream presentation, once it has been described.
+    streams: Vec<RelayStream>,
+
+    /// The URI of the upstream presentation.
+    upstream: URI,
+
+    /// The sessions currently playing the presentation through the relay.
+    viewers: HashSet<SessionID>,
+}
+
+impl Relay {
+    /// Adds a session playing the presentation, returning the future playing the upstream session
+    /// if it is not already being played.
+    ///
+    /// The future completes once the last viewer is removed or the upstream session fails.
+    pub(crate) fn add_viewer(
+        &mut self,
+        id: SessionID,
+        via: HeaderValue,
+    ) -> Option<impl Future<Item = (), Error = ()> + Send> {
+        self.viewers.insert(id);
+
+        if self.is_playing() || self.streams.is_empty() {
+            return None;
+        }
+
+        let (tx_stop, rx_stop) = oneshot::channel();
+        self.tx_stop = Some(tx_stop);
+
+        let streams = self
+            .streams
+            .iter()
+            .map(|stream| {
+                let depacketizer =
+                    depacketizer::from_format(&stream.format, stream.format_parameters.as_ref())
+                        .expect("relayed streams should be depacketizable");
+                (
+                    stream.upstream.clone(),
+                    Reassembler::new(depacketizer, JitterBufferConfig::default()),
+                    stream.sender.clone(),
+                )
+            })
+            .collect();
+        let upstream = self.upstream.clone();
+
+        Some(play(upstream, streams, via, rx_stop).then(|_| Ok(())))
+    }
+
+    /// Describes the upstream presentation, returning its description along with the URI its
+    /// stream controls are relative to.
+    pub(crate) fn describe(
+        &self,
+        via: HeaderValue,
+    ) -> impl Future<Item = (SessionDescription, String), Error = RelayError> + Send {
+        let upstream = self.upstream.clone();
+
+        future::result(socket_address(&upstream))
+            .and_then(|address| Client::connect(address).map_err(RelayError::from))
+            .and_then(move |mut client| {
+                let request = Request::<()>::builder()
+                    .with_method(Method::Describe)
+                    .with_uri(upstream.clone())
+                    .with_header(
+                        HeaderName::Accept,
+                        HeaderValue::try_from(SDP_CONTENT_TYPE).unwrap(),
+                    )
+                    .with_header(HeaderName::Via, via)
+                    .with_body(BytesMut::new())
+                    .build()
+                    .unwrap();
+
+                send(&mut client, request).and_then(move |response| {
+                    let description = str::from_utf8(response.body())
+                        .ok()
+                        .and_then(|body| SessionDescription::try_from(body).ok())
+                        .ok_or(RelayError::InvalidDescription)?;
+                    let base = response
+                        .headers()
+                        .get(&HeaderName::ContentBase)
+                        .map(|base| base.as_str().to_string())
+                        .unwrap_or_else(|| upstream.to_string());
+                    Ok((description, base))
+                })
+            })
+    }
+
+    /// Returns whether the upstream presentation has been described.
+    pub(crate) fn is_described(&self) -> bool {
+        !self.streams.is_empty()
+    }
+
+    /// Returns whether the upstream session is being played.
+    pub(crate) fn is_playing(&self) -> bool {
+        self.tx_stop
+            .as_ref()
+            .map_or(false, |tx_stop| !tx_stop.is_canceled())
+    }
+
+    /// Constructs a new relay for the given upstream presentation.
+    pub(crate) fn new(upstream: URI) -> Self {
+        Relay {
+            tx_stop: None,
+            streams: Vec::new(),
+            upstream,
+            viewers: HashSet::new(),
+        }
+    }
+
+    /// Removes a session playing the presentation, stopping the upstream session if it was the
+    /// last one.
+    pub(crate) fn remove_viewer(&mut self, id: &SessionID) {
+        self.viewers.remove(id);
+
+        if self.viewers.is_empty() {
+            self.tx_stop = None;
+        }
+    }
+
+    /// Sets the streams of the upstream presentation from its description, returning the
+    /// description to serve along with the live streams the upstream streams are relayed through.
+    ///
+    /// Stream controls are rewritten to be relative to the path the presentation is served under,
+    /// and streams that cannot be depacketized are not relayed.
+    pub(crate) fn set_description(
+        &mut self,
+        description: &SessionDescription,
+        base: &str,
+    ) -> (SessionDescription, Vec<(String, LiveStream)>) {
+        let mut served_description = SessionDescription::new(description.name());
+        let mut live_streams = Vec::new();
+
+        for (index, media) in description.media().iter().enumerate() {
+            let supported =
+                depacketizer::from_format(media.format(), media.format_parameters()).is_ok();
+            let upstream = match upstream_control_uri(base, media.control()) {
+                Ok(upstream) if supported => upstream,
+                _ => continue,
+            };
+            let control = downstream_control(base, media.control(), index);
+            let (stream, sender) =
+                LiveStream::new(media.format().clone(), media.format_parameters().cloned());
+
+            served_description.add_media(MediaDescription::new(
+                control.clone(),
+                media.format().clone(),
+                media.format_parameters().cloned(),
+            ));
+            live_streams.push((control, stream));
+            self.streams.push(RelayStream {
+                format: media.format().clone(),
+                format_parameters: media.format_parameters().cloned(),
+                sender,
+                upstream,
+            });
+        }
+
+        (served_description, live_streams)
+    }
+}
+
+/// A stream of an upstream presentation.
+struct RelayStream {
+    /// The payload format of the stream.
+    format: RTPMap,
+
+    /// The format specific parameters of the stream.
+    format_parameters: Option<FormatParameters>,
+
+    /// The sender pushing frames into the live stream the stream is served through. It is kept
+    /// while the upstream session is stopped so the live stream does not end.
+    sender: LiveStreamSender,
+
+    /// The URI of the stream on the upstream server.
+    upstream: URI,
+}
+
+/// An error type for when the upstream presentation could not be described or played.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum RelayError {
+    /// The description of the upstream presentation could not be parsed.
+    InvalidDescription,
+
+    /// An I/O error occurred while connecting to the upstream server.
+    IO(io::ErrorKind),
+
+    /// A request to the upstream server failed.
+    Operation(OperationError),
+
+    /// The upstream server responded with an unsuccessful status code.
+    Status(StatusCode),
+
+    /// The address of the upstream server could not be resolved.
+    UnresolvedHost,
+}
+
+impl RelayError {
+    /// Returns the status code to respond to clients with when the upstream server could not be
+    /// reached because of this error.
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            RelayError::Operation(OperationError::RequestTimedOut(_)) => StatusCode::GatewayTimeout,
+            _ => StatusCode::BadGateway,
+        }
+    }
+}
+
+impl Display for RelayError {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        use self::RelayError::*;
+
+        match self {
+            InvalidDescription => write!(formatter, "invalid upstream session description"),
+            IO(kind) => write!(formatter, "upstream I/O error: {:?}", kind),
+            Operation(error) => write!(formatter, "upstream request failed: {}", error),
+            Status(status_code) => write!(formatter, "upstream responded with {}", status_code),
+            UnresolvedHost => write!(formatter, "unresolved upstream host"),
+        }
+    }
+}
+
+impl Error for RelayError {}
+
+impl From<io::Error> for RelayError {
+    fn from(value: io::Error) -> Self {
+        RelayError::IO(value.kind())
+    }
+}
+
+impl From<OperationError> for RelayError {
+    fn from(value: OperationError) -> Self {
+        RelayError::Operation(value)
+    }
+}
+
+/// Returns the control of an upstream stream relative to the path the presentation is served
+/// under, keeping relative controls and numbering the others.
+fn downstream_control(base: &str, control: &str, index: usize) -> String {
+    let base = base.trim_end_matches('/');
+    let control = control
+        .strip_prefix(base)
+        .filter(|control| control.starts_with('/'))
+        .unwrap_or(control)
+        .trim_matches('/');
+
+    if control.is_empty() || control == "*" || URI::try_from(control).is_ok() {
+        format!("stream={}", index)
+    } else {
+        control.to_string()
+    }
+}
+
+/// Plays the given streams of the upstream presentation until stopped, pushing the frames received
+/// into their live streams.
+fn play(
+    upstream: URI,
+    streams: Vec<(URI, Reassembler, LiveStreamSender)>,
+    via: HeaderValue,
+    rx_stop: oneshot::Receiver<()>,
+) -> impl Future<Item = (), Error = RelayError> + Send {
+    future::result(socket_address(&upstream))
+        .and_then(|address| Client::connect(address).map_err(RelayError::from))
+        .and_then(move |mut client| {
+            // Each stream is received on its own pair of interleaved channels, the first carrying
+            // RTP and the second RTCP.
+            let interleaved_data = client.interleaved_data();
+            let mut ingest_streams = Vec::new();
+            let mut setups = Vec::new();
+
+            for (index, (uri, reassembler, sender)) in streams.into_iter().enumerate() {
+                ingest_streams.push((reassembler, sender));
+                setups.push((index, uri));
+            }
+
+            let setup_via = via.clone();
+            stream::iter_ok(setups)
+                .fold(
+                    (client, None),
+                    move |(mut client, session), (index, uri)| {
+                        let channel = index * 2;
+                        let transport = format!(
+                            "RTP/AVP/TCP;unicast;interleaved={}-{}",
+                            channel,
+                            channel + 1
+                        );
+                        let mut builder = Request::<()>::builder()
+                            .with_method(Method::Setup)
+                            .with_uri(uri)
+                            .with_header(
+                                HeaderName::Transport,
+                                HeaderValue::try_from(transport.as_str()).unwrap(),
+                            )
+                            .with_header(HeaderName::Via, setup_via.clone());
+
+                        if let Some(session) = session.as_ref().map(session_without_timeout) {
+                            builder = builder.with_typed_header(session);
+                        }
+
+                        let request = builder.with_body(BytesMut::new()).build().unwrap();
+                        send(&mut client, request).map(move |response| {
+                            let session = response.headers().typed_get::<Session>().or(session);
+                            (client, session)
+                        })
+                    },
+                )
+                .and_then(move |(mut client, session)| {
+                    let session = match session {
+                        Some(session) => session,
+                        None => return Either::A(future::err(RelayError::InvalidDescription)),
+                    };
+                    let session = session_without_timeout(&session);
+                    let request = Request::<()>::builder()
+                        .with_method(Method::Play)
+                        .with_uri(upstream.clone())
+                        .with_typed_header(session.clone())
+                        .with_header(HeaderName::Via, via.clone())
+                        .with_body(BytesMut::new())
+                        .build()
+                        .unwrap();
+
+                    Either::B(send(&mut client, request).and_then(move |_| {
+                        receive(
+                            client,
+                            upstream,
+                            session,
+                            via,
+                            interleaved_data,
+                            ingest_streams,
+                            rx_stop,
+                        )
+                    }))
+                })
+        })
+}
+
+/// Receives the streams of a playing upstream session until stopped or the connection closes,
+/// then tears it down. The client keeps the session alive in the meantime.
+fn receive(
+    mut client: Client,
+    upstream: URI,
+    session: Session,
+    via: HeaderValue,
+    interleaved_data: mpsc::UnboundedReceiver<InterleavedData>,
+    streams: Vec<(Reassembler, LiveStreamSender)>,
+    rx_stop: oneshot::Receiver<()>,
+) -> impl Future<Item = (), Error = RelayError> + Send {
+    let (tx_packet, rx_packet) = mpsc::unbounded();
+
+    // Odd channels carry RTCP, which is not needed to serve the streams again. Forwarding ends once
+    // the connection closes, which in turn ends the ingest.
+    tokio::spawn(interleaved_data.for_each(move |data| {
+        if data.channel() % 2 != 0 {
+            return Ok(());
+        }
+
+        tx_packet
+            .unbounded_send((usize::from(data.channel() / 2), data.into_payload()))
+            .map_err(|_| ())
+    }));
+
+    let running: Vec<Box<dyn Future<Item = (), Error = ()> + Send>> = vec![
+        Box::new(Ingest::new(streams, rx_packet)),
+        Box::new(rx_stop.then(|_| Ok(()))),
+    ];
+
+    future::select_all(running).then(move |_| {
+        let request = Request::<()>::builder()
+            .with_method(Method::Teardown)
+            .with_uri(upstream)
+            .with_typed_header(session)
+            .with_header(HeaderName::Via, via)
+            .with_body(BytesMut::new())
+            .build()
+            .unwrap();
+        send(&mut client, request).map(|_| ())
+    })
+}
+
+/// Sends a request to the upstream server, resolving to an error if the response is not
+/// successful.
+fn send(
+    client: &mut Client,
+    request: Request<BytesMut>,
+) -> impl Future<Item = Response<BytesMut>, Error = RelayError> + Send {
+    client
+        .send_request(request)
+        .map_err(RelayError::from)
+        .and_then(|response| {
+            if response.status_code().is_success() {
+                Ok(response)
+            } else {
+                Err(RelayError::Status(response.status_code()))
+            }
+        })
+}
+
+/// Returns the `"Session"` header to send in requests for the given session, which must not
+/// include a timeout.
+fn session_without_timeout(session: &Session) -> Session {
+    Session::without_timeout(session.id().as_str())
+        .expect("received session identifiers should be valid")
+}
+
+/// Resolves the address of the server of the given URI.
+fn socket_address(uri: &URI) -> Result<SocketAddr, RelayError> {
+    let host = uri.host().ok_or(RelayError::UnresolvedHost)?;
+    let port = uri.port().unwrap_or(RTSP_DEFAULT_PORT);
+
+    format!("{}:{}", host, port)
+        .to_socket_addrs()
+        .map_err(|_| RelayError::UnresolvedHost)?
+        .next()
+        .ok_or(RelayError::UnresolvedHost)
+}
+
+/// Returns the URI of an upstream stream given the base URI of the presentation and the stream
+/// control.
+fn upstream_control_uri(base: &str, control: &str) -> Result<URI, ()> {
+    if control.is_empty() || control == "*" {
+        return URI::try_from(base.trim_end_matches('/')).map_err(|_| ());
+    }
+
+    if let Ok(uri) = URI::try_from(control) {
+        return Ok(uri);
+    }
+
+    let uri = format!("{}/{}", base.trim_end_matches('/'), control);
+    URI::try_from(uri.as_str()).map_err(|_| ())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{downstream_control, upstream_control_uri};
+
+    #[test]
+    fn test_control_rewriting() {
+        let base = "rtsp://camera.local/stream/";
+
+        assert_eq!(downstream_control(base, "trackID=1", 0), "trackID=1");
+        assert_eq!(
+            downstream_control(base, "rtsp://camera.local/stream/trackID=2", 1),
+            "trackID=2"
+        );
+        assert_eq!(
+            downstream_control(base, "rtsp://camera.local/other", 2),
+            "stream=2"
+        );
+        assert_eq!(downstream_control(base, "*", 3), "stream=3");
+
+        assert_eq!(
+            upstream_control_uri(base, "trackID=1").unwrap().to_string(),
+            "rtsp://camera.local/stream/trackID=1"
+        );
+        assert_eq!(
+            upstream_control_uri(base, "rtsp://camera.local/other")
+                .unwrap()
+                .to_string(),
+            "rtsp://camera.local/other"
+        );
+        assert_eq!(
+            upstream_control_uri(base, "*").unwrap().to_string(),
+            "rtsp://camera.local/stream"
+        );
+    }
+}
@@ -506,7 +506,8 @@ impl<TBody> Builder<TBody> {
         let uri = self.uri.ok_or(RequestError::MissingURI)?;
         let body = self.body.ok_or(RequestError::MissingBody)?;
 
-        if self.version != Version::Rtsp2_0 {
+        // RTSP/1.0 is only supported to bridge agents that have not moved to RTSP/2.0.
+        if self.version != Version::Rtsp2_0 && self.version != Version::Rtsp1_0 {
             return Err(RequestError::UnsupportedVersion);
         }
 
@@ -870,7 +871,7 @@ pub enum RequestError {
     /// The URI was not specified.
     MissingURI,
 
-    /// The version was unsupported. The only supported version is RTSP 2.0.
+    /// The version was unsupported. RTSP 2.0 is supported, along with RTSP 1.0 for older agents.
     UnsupportedVersion,
 }
 
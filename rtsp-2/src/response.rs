@@ -517,7 +517,8 @@ impl<TBody> Builder<TBody> {
         };
         let body = self.body.ok_or(ResponseError::MissingBody)?;
 
-        if self.version != Version::Rtsp2_0 {
+        // RTSP/1.0 is only supported to bridge agents that have not moved to RTSP/2.0.
+        if self.version != Version::Rtsp2_0 && self.version != Version::Rtsp1_0 {
             return Err(ResponseError::UnsupportedVersion);
         }
 
@@ -840,7 +841,7 @@ pub enum ResponseError {
     /// The reason phrase was not specified for an extension status code.
     MissingReasonPhrase,
 
-    /// The version was unsupported. The only supported version is RTSP 2.0.
+    /// The version was unsupported. RTSP 2.0 is supported, along with RTSP 1.0 for older agents.
     UnsupportedVersion,
 }
 
@@ -1,103 +1,1653 @@
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     convert::TryFrom,
     error::Error,
-    net::SocketAddr,
+    io, mem,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener as StdTcpListener, UdpSocket},
+    path::Path,
+    str,
     sync::{Arc, Mutex},
-    time::Duration,
+    time::{Duration, Instant},
 };
 
-use bytes::BytesMut;
+use bytes::{Bytes, BytesMut};
 use chrono::{self, offset, DateTime, Utc};
-use futures::{future, Async, Future, Poll, Stream};
-use tokio_tcp::TcpListener;
+use futures::{
+    future::{self, Shared},
+    sync::{
+        mpsc::{self, UnboundedSender},
+        oneshot,
+    },
+    Async, Future, Poll, Stream,
+};
+use rtsp_1::method::Method as Rtsp1Method;
+use tokio::{net::UdpSocket as TokioUdpSocket, reactor::Handle, runtime::Runtime, timer::Interval};
+use tokio_io::{AsyncRead, AsyncWrite};
+use tokio_tcp::{TcpListener, TcpStream};
+#[cfg(feature = "tls")]
+use tokio_tls::{TlsAcceptor, TlsStream};
+use tower_layer::Layer;
 use tower_service::Service;
 
-use crate::{
-    header::{
-        map::HeaderMapExtension,
-        types::{AcceptRanges, Public},
-    },
-    method::Method,
-    protocol::connection::{Connection, ConnectionHandle},
-    request::Request,
-    response::{Response, BAD_REQUEST_RESPONSE, NOT_IMPLEMENTED_RESPONSE},
-    session::{Session, SessionID, SessionIDError, DEFAULT_SESSION_TIMEOUT},
-    status::StatusCode,
-};
+use crate::{
+    header::{
+        map::HeaderMapExtension,
+        name::HeaderName,
+        types::{
+            range::NPTTime,
+            rtp_info::{SSRCInfo, StreamInfo},
+            transport::{DeliveryType, TransportSpec},
+            via::ViaEntry,
+            AcceptRanges, PipelinedRequests, Public, RTPInfo, Range, Session as SessionHeader,
+            Transport, Via,
+        },
+        value::HeaderValue,
+    },
+    media::{
+        depacketizer::{self, Reassembler},
+        fanout::{Destination, DestinationID, FanOut, FanOutHandle},
+        file::{self, FileError},
+        ingest::Ingest,
+        jitter::Config as JitterBufferConfig,
+        live::LiveStream,
+        packetizer,
+        sdp::{MediaDescription, SessionDescription, SDP_CONTENT_TYPE},
+        MediaStream, MediaUsage, Presentation, SeekError,
+    },
+    method::Method,
+    metrics::Metrics,
+    middleware::{
+        auth::AuthenticationLayer, catch_panic::CatchPanicLayer,
+        concurrency::ConcurrencyLimitLayer, cseq::CSeqLayer, date::DateLayer,
+        error::ErrorResponseLayer, normalize::NormalizeURILayer, server_header::ServerHeaderLayer,
+        timeout::TimeoutLayer, via::loop_detected_response,
+    },
+    protocol::{
+        connection::{Config as ConnectionConfig, Connection, ConnectionHandle, ShutdownType},
+        http::{self, ReadHeader},
+        tunnel::{Accepted, TunnelAcceptor},
+        websocket,
+    },
+    relay::{Relay, RelayError, PROXY_FEATURES},
+    request::Request,
+    response::{Response, BAD_REQUEST_RESPONSE, NOT_IMPLEMENTED_RESPONSE},
+    session::{Session, SessionID, DEFAULT_SESSION_TIMEOUT},
+    status::StatusCode,
+    uri::{request::URI, Host},
+    version::Version,
+};
+
+pub const SUPPORTED_METHODS: [Method; 6] = [
+    Method::Describe,
+    Method::Options,
+    Method::Pause,
+    Method::Play,
+    Method::Setup,
+    Method::Teardown,
+];
+
+/// The RTSP/1.0 methods supported for publishing presentations, which RTSP/2.0 no longer defines.
+pub const PUBLISHING_METHODS: [Rtsp1Method; 2] = [Rtsp1Method::Announce, Rtsp1Method::Record];
+
+/// The default maximum number of requests a server handles at once across all connections.
+pub const DEFAULT_CONCURRENCY_LIMIT: usize = 1024;
+
+/// The default duration a server may take to handle a request before answering it with a 503
+/// (Service Unavailable) response.
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The default duration connections are given to finish handling their requests when a server shuts
+/// down.
+pub const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often sessions are checked for having expired.
+const SESSION_EXPIRY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The maximum size of a datagram received on the RTP socket.
+const MAX_DATAGRAM_SIZE: usize = 65536;
+
+/// The senders of the ingests that RTP packets received over UDP are forwarded to, keyed by the
+/// address of the publisher sending them, along with the index of the stream they belong to.
+type IngestSources = Arc<Mutex<HashMap<SocketAddr, (usize, UnboundedSender<(usize, Bytes)>)>>>;
+
+/// Experimental high-level server implementation
+///
+/// Media is provided through [`Presentation`]s, each served under a path. The streams of a
+/// presentation are set up individually at `<path>/<control>` and played together through the
+/// presentation path. Each stream is packetized once and fanned out to every session playing it,
+/// over whichever transport that session negotiated.
+///
+/// Live presentations are shared by all sessions playing them. Each session playing an on-demand
+/// presentation gets its own copy of the streams instead, so that it can seek and pause them
+/// independently.
+///
+/// Agents can also publish live presentations through ANNOUNCE, SETUP with `mode=record` and
+/// RECORD, as defined by [RFC2326](https://tools.ietf.org/html/rfc2326). Once recording, the
+/// presentation is served under the announced path until the publisher tears it down. Since these
+/// methods only exist in RTSP/1.0, RTSP/1.0 requests are accepted and answered in kind.
+///
+/// Presentations of other servers, such as cameras, can be relayed as well. See [`crate::relay`]
+/// for how they are played.
+///
+/// Requests are handled through the layers of [`crate::middleware`], so responses always echo the
+/// `"CSeq"` of their request and carry `"Date"` and `"Server"` headers, while failures, panics,
+/// timeouts and requests beyond the concurrency limit are answered with error responses. Requests
+/// can also be required to authenticate.
+///
+/// Sessions not used within their timeout are ended, stopping delivery of their streams.
+pub struct Server {
+    address: Option<SocketAddr>,
+    announcements: HashMap<String, Announcement>,
+    authentication: Option<AuthenticationLayer>,
+    concurrency_limit: usize,
+    connections: HashMap<u64, ConnectionHandle>,
+    descriptions: HashMap<String, SessionDescription>,
+    listeners: Vec<Listener>,
+    metrics: Option<Arc<dyn Metrics>>,
+    next_connection_id: u64,
+    presentations: Vec<(String, Presentation)>,
+    relays: HashMap<String, Relay>,
+    request_timeout: Duration,
+    rtp_sockets: Vec<Arc<UdpSocket>>,
+    session_timeout: Duration,
+    sessions: HashMap<SessionID, Arc<Mutex<ServerSession>>>,
+    shutdown_timeout: Duration,
+    streams: HashMap<String, ServedStream>,
+    udp_sources: IngestSources,
+}
+
+impl Server {
+    /// Serves a connection accepted on the given listener, once its TLS handshake completes if the
+    /// listener requires one.
+    fn accept(
+        server: &Arc<Mutex<Server>>,
+        layers: &ServiceLayers,
+        listener: &Listener,
+        socket: TcpStream,
+    ) {
+        let peer_address = socket.peer_addr().ok();
+
+        #[cfg(feature = "tls")]
+        {
+            if let Some(acceptor) = listener.tls_acceptor.clone() {
+                let server = server.clone();
+                let layers = layers.clone();
+                let tunnels = listener.tls_tunnels.clone();
+                let tunneling = listener.http_tunneling;
+                let websocket = listener.websocket;
+
+                // Connections failing the handshake are simply dropped.
+                tokio::spawn(
+                    acceptor
+                        .accept(socket)
+                        .map(move |stream| {
+                            let tunnels = if tunneling { Some(tunnels) } else { None };
+                            Server::serve_transport(
+                                &server,
+                                &layers,
+                                tunnels,
+                                websocket,
+                                stream,
+                                peer_address,
+                            )
+                        })
+                        .map_err(|_| ()),
+                );
+                return;
+            }
+        }
+
+        let tunnels = if listener.http_tunneling {
+            Some(listener.tunnels.clone())
+        } else {
+            None
+        };
+        Server::serve_transport(
+            server,
+            layers,
+            tunnels,
+            listener.websocket,
+            socket,
+            peer_address,
+        );
+    }
+
+    /// Makes each elementary stream file in the given directory available as an on-demand
+    /// presentation, served under the name of the file.
+    ///
+    /// Files of unsupported formats are ignored. See [`file::open_directory`] for the supported
+    /// formats.
+    pub fn add_directory<TPath>(&mut self, directory: TPath) -> Result<&mut Self, FileError>
+    where
+        TPath: AsRef<Path>,
+    {
+        for (path, presentation) in file::open_directory(directory)? {
+            self.add_presentation(path, presentation);
+        }
+
+        Ok(self)
+    }
+
+    /// Adds a listener to accept connections on, with its own transport, authentication and
+    /// connection configuration.
+    pub fn add_listener(&mut self, listener: Listener) -> &mut Self {
+        self.listeners.push(listener);
+        self
+    }
+
+    /// Makes the given presentation available under the given path, such as `"live"`.
+    pub fn add_presentation<TPath>(&mut self, path: TPath, presentation: Presentation) -> &mut Self
+    where
+        TPath: AsRef<str>,
+    {
+        let path = path.as_ref().trim_matches('/').to_string();
+        self.presentations.push((path, presentation));
+        self
+    }
+
+    /// Relays the presentation at the given upstream URI under the given path, such as
+    /// `"camera"`.
+    pub fn add_relay<TPath>(&mut self, path: TPath, upstream: URI) -> &mut Self
+    where
+        TPath: AsRef<str>,
+    {
+        let path = path.as_ref().trim_matches('/').to_string();
+        self.relays.insert(path, Relay::new(upstream));
+        self
+    }
+
+    /// Stops serving the presentation recorded at the given path if it is published by the given
+    /// session, so that it can be announced again.
+    fn end_recording(&mut self, path: &str, publisher: &SessionID) {
+        let is_publisher = self.announcements.get(path).map_or(false, |announcement| {
+            announcement.publisher.as_ref() == Some(publisher)
+        });
+
+        if !is_publisher {
+            return;
+        }
+
+        self.announcements.remove(path);
+        self.descriptions.remove(path);
+        self.streams
+            .retain(|stream_path, _| !is_within(stream_path, path));
+        self.udp_sources
+            .lock()
+            .unwrap()
+            .retain(|_, (_, tx_packet)| !tx_packet.is_closed());
+    }
+
+    /// Returns whether the given path refers to a presentation or one of its streams.
+    fn has_resource(&self, path: &str) -> bool {
+        self.streams.contains_key(path)
+            || self.streams.keys().any(|key| is_within(key, path))
+            || self.relay_path(path).is_some()
+    }
+
+    /// Starts keeping track of the given session, reporting it to the metrics as opened unless it
+    /// was already tracked.
+    fn insert_session(&mut self, session: Arc<Mutex<ServerSession>>) {
+        let id = session.lock().unwrap().id().clone();
+
+        if self.sessions.insert(id, session).is_none() {
+            if let Some(metrics) = self.metrics.as_ref() {
+                metrics.session_opened();
+            }
+        }
+    }
+
+    /// Serves the streams of the relay at the given path according to the description of its
+    /// upstream presentation, unless that has already been done.
+    fn install_relay(&mut self, path: &str, description: &SessionDescription, base: &str) {
+        let relay = match self.relays.get_mut(path) {
+            Some(relay) if !relay.is_described() => relay,
+            _ => return,
+        };
+        let (description, streams) = relay.set_description(description, base);
+
+        for (control, stream) in streams {
+            if let Ok((fan_out, handle)) =
+                FanOut::new(Box::new(stream), packetizer::Config::default())
+            {
+                tokio::spawn(fan_out);
+                self.streams
+                    .insert(format!("{}/{}", path, control), ServedStream::Live(handle));
+            }
+        }
+
+        self.descriptions.insert(path.to_string(), description);
+    }
+
+    /// Binds the listeners of the server, returning a future serving connections accepted on them
+    /// until the given future completes, then shutting down gracefully.
+    ///
+    /// The returned future must be run on a runtime, which the caller drives, so that the server
+    /// can run alongside other tasks. Every listener accepts connections with its own transport,
+    /// authentication and connection configuration, such as RTSP on port 554 and RTSPS on port 322
+    /// requiring authentication, alongside a loopback address for administration that does not.
+    ///
+    /// Once shutting down, no more connections are accepted and every connection is shut down with
+    /// [`ShutdownType::Graceful`], giving it the shutdown timeout to finish handling its requests.
+    /// The future completes once all connections have been shut down, after which live streams are
+    /// no longer delivered and sessions no longer expire.
+    ///
+    /// Streams whose payload format cannot be packetized are not served. If any of the listeners
+    /// cannot be bound, the error is returned without serving anything. RTP is sent and received on
+    /// the unspecified address of each address family listened on, so that media reaches clients
+    /// on every interface, whichever listener they connected through.
+    pub fn listen<TShutdown>(
+        mut self,
+        shutdown: TShutdown,
+    ) -> io::Result<impl Future<Item = (), Error = io::Error> + Send>
+    where
+        TShutdown: Future + Send + 'static,
+    {
+        let listeners = mem::take(&mut self.listeners)
+            .into_iter()
+            .map(|listener| Ok((StdTcpListener::bind(listener.address)?, listener)))
+            .collect::<io::Result<Vec<_>>>()?;
+        let address = match listeners.first() {
+            Some((listener, _)) => listener.local_addr()?,
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "no listeners were given",
+                ))
+            }
+        };
+        self.address = Some(address);
+
+        for unspecified in [
+            IpAddr::from(Ipv4Addr::UNSPECIFIED),
+            IpAddr::from(Ipv6Addr::UNSPECIFIED),
+        ] {
+            if listeners
+                .iter()
+                .any(|(_, listener)| listener.address.is_ipv4() == unspecified.is_ipv4())
+            {
+                let rtp_socket = UdpSocket::bind(SocketAddr::new(unspecified, 0))?;
+                rtp_socket.set_nonblocking(true)?;
+                self.rtp_sockets.push(Arc::new(rtp_socket));
+            }
+        }
+
+        // The background tasks of the server are dropped once it has shut down.
+        let (tx_stopped, rx_stopped) = oneshot::channel::<()>();
+        let rx_stopped = rx_stopped.shared();
+        let shutdown_timeout = self.shutdown_timeout;
+
+        let serving = future::lazy(move || {
+            // Packets of publishers recording over UDP arrive on the same socket media is sent
+            // from, which is the server port given to them.
+            for rtp_socket in &self.rtp_sockets {
+                let rtp_socket =
+                    TokioUdpSocket::from_std(rtp_socket.try_clone()?, &Handle::default())?;
+                spawn_until_stopped(
+                    RTPReceiver {
+                        buffer: vec![0; MAX_DATAGRAM_SIZE],
+                        socket: rtp_socket,
+                        sources: self.udp_sources.clone(),
+                    },
+                    &rx_stopped,
+                );
+            }
+
+            for (path, presentation) in mem::take(&mut self.presentations) {
+                let usage = presentation.usage();
+                self.descriptions
+                    .insert(path.clone(), presentation.description(path.as_str()));
+
+                for (control, stream) in presentation.into_streams() {
+                    let stream_path = format!("{}/{}", path, control);
+
+                    if usage == MediaUsage::OnDemand {
+                        let supported = packetizer::from_format(
+                            stream.format(),
+                            stream.format_parameters(),
+                            packetizer::Config::default(),
+                        )
+                        .is_ok();
+
+                        if supported && stream.try_clone().is_some() {
+                            self.streams
+                                .insert(stream_path, ServedStream::OnDemand(stream));
+                        }
+                    } else if let Ok((fan_out, handle)) =
+                        FanOut::new(stream, packetizer::Config::default())
+                    {
+                        tokio::spawn(fan_out);
+                        self.streams.insert(stream_path, ServedStream::Live(handle));
+                    }
+                }
+            }
+
+            // The concurrency limit and timeout are shared by all connections of all listeners,
+            // while the authentication and connection configuration may differ between listeners.
+            let authentication = self.authentication.clone();
+            let concurrency_limit = ConcurrencyLimitLayer::new(self.concurrency_limit);
+            let timeout = TimeoutLayer::new(self.request_timeout);
+            let server = Arc::new(Mutex::new(self));
+            let expiring_server = server.clone();
+
+            spawn_until_stopped(
+                Interval::new(
+                    Instant::now() + SESSION_EXPIRY_INTERVAL,
+                    SESSION_EXPIRY_INTERVAL,
+                )
+                .for_each(move |_| {
+                    expiring_server.lock().unwrap().remove_expired_sessions();
+                    Ok(())
+                })
+                .map_err(|_| ()),
+                &rx_stopped,
+            );
+
+            let accepting = listeners
+                .into_iter()
+                .map(|(listener, config)| {
+                    let listener = TcpListener::from_std(listener, &Handle::default())?;
+                    let server = server.clone();
+                    let layers = ServiceLayers {
+                        authentication: config
+                            .authentication
+                            .clone()
+                            .unwrap_or_else(|| authentication.clone()),
+                        concurrency_limit: concurrency_limit.clone(),
+                        connection_config: config.connection_config.clone(),
+                        timeout,
+                    };
+
+                    Ok(listener.incoming().for_each(move |socket| {
+                        Server::accept(&server, &layers, &config, socket);
+                        Ok(())
+                    }))
+                })
+                .collect::<io::Result<Vec<_>>>()?;
+
+            // Dropping the listeners once shutting down stops accepting connections.
+            let stopped = future::join_all(accepting)
+                .then(|_| Ok::<_, ()>(()))
+                .select(shutdown.then(|_| Ok(())))
+                .then(|_| Ok::<_, ()>(()));
+
+            Ok(stopped.and_then(move |()| {
+                let connections = mem::take(&mut server.lock().unwrap().connections);
+                let shut_down = connections.into_values().map(move |mut connection| {
+                    connection.shutdown(ShutdownType::Graceful(shutdown_timeout));
+                    connection.shutdown_receiver()
+                });
+
+                future::join_all(shut_down).then(move |_| {
+                    for stream in server.lock().unwrap().streams.values() {
+                        if let ServedStream::Live(handle) = stream {
+                            handle.close();
+                        }
+                    }
+
+                    drop(tx_stopped);
+                    Ok(())
+                })
+            }))
+        });
+
+        Ok(serving.and_then(|shut_down| shut_down.then(|_| Ok(()))))
+    }
+
+    /// Constructs a new server without any presentations.
+    pub fn new() -> Self {
+        Server {
+            address: None,
+            announcements: HashMap::new(),
+            authentication: None,
+            concurrency_limit: DEFAULT_CONCURRENCY_LIMIT,
+            connections: HashMap::new(),
+            descriptions: HashMap::new(),
+            listeners: Vec::new(),
+            metrics: None,
+            next_connection_id: 0,
+            presentations: Vec::new(),
+            relays: HashMap::new(),
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+            rtp_sockets: Vec::new(),
+            session_timeout: DEFAULT_SESSION_TIMEOUT,
+            sessions: HashMap::new(),
+            shutdown_timeout: DEFAULT_SHUTDOWN_TIMEOUT,
+            streams: HashMap::new(),
+            udp_sources: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Returns the path of the relay the given path refers to or is within, if any.
+    fn relay_path(&self, path: &str) -> Option<&str> {
+        self.relays
+            .keys()
+            .find(|relay_path| is_within(path, relay_path))
+            .map(String::as_str)
+    }
+
+    /// Returns the socket RTP is sent to and received from the given peer on, which is the one of
+    /// its address family.
+    fn rtp_socket(&self, peer_address: SocketAddr) -> Option<&Arc<UdpSocket>> {
+        let ipv4 = peer_address.is_ipv4();
+
+        self.rtp_sockets
+            .iter()
+            .find(|socket| matches!(socket.local_addr(), Ok(address) if address.is_ipv4() == ipv4))
+    }
+
+    /// Ends the sessions that have not been used within their timeout, stopping delivery of their
+    /// streams and the ingest of their recordings.
+    fn remove_expired_sessions(&mut self) {
+        let expired = self
+            .sessions
+            .values()
+            .filter(|session| session.lock().unwrap().is_expired())
+            .cloned()
+            .collect::<Vec<_>>();
+
+        for session_lock in expired {
+            let mut session = session_lock.lock().unwrap();
+            session.setups.clear();
+
+            if let Some(recording) = session.recording.take() {
+                self.end_recording(&recording.path, session.id());
+            }
+
+            for relay in self.relays.values_mut() {
+                relay.remove_viewer(session.id());
+            }
+
+            self.remove_session(session.id());
+        }
+    }
+
+    /// Stops keeping track of the session with the given ID, ending it.
+    fn remove_session(&mut self, id: &SessionID) {
+        if self.sessions.remove(id).is_some() {
+            if let Some(metrics) = self.metrics.as_ref() {
+                metrics.session_closed();
+            }
+        }
+    }
+
+    /// Runs a server without any presentations on the given address.
+    pub fn run(address: SocketAddr) {
+        Server::new().serve(address)
+    }
+
+    /// Runs the server on the given address until the process exits.
+    ///
+    /// Streams whose payload format cannot be packetized are not served.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the address cannot be bound. Use [`Server::serve_until`] to handle that instead.
+    pub fn serve(self, address: SocketAddr) {
+        self.serve_until(vec![Listener::new(address)], future::empty::<(), ()>())
+            .expect("server address should be bindable")
+    }
+
+    /// Runs the server on its listeners and the given ones until the given future completes, then
+    /// shuts down gracefully.
+    ///
+    /// This takes over the thread with a runtime of its own. Use [`Server::listen`] to serve on a
+    /// runtime driven by the caller instead.
+    ///
+    /// If any of the listeners cannot be bound, the error is returned without serving anything.
+    pub fn serve_until<TShutdown>(
+        mut self,
+        listeners: Vec<Listener>,
+        shutdown: TShutdown,
+    ) -> io::Result<()>
+    where
+        TShutdown: Future + Send + 'static,
+    {
+        self.listeners.extend(listeners);
+
+        let serving = self.listen(shutdown)?;
+        let mut runtime = Runtime::new()?;
+        let result = runtime.block_on(serving);
+
+        // Connections of publishers and relays may still be running.
+        runtime.shutdown_now().wait().ok();
+        result
+    }
+
+    /// Serves requests received on the given transport.
+    fn serve_connection<TTransport>(
+        server: &Arc<Mutex<Server>>,
+        layers: &ServiceLayers,
+        transport: TTransport,
+        peer_address: Option<SocketAddr>,
+    ) where
+        TTransport: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        let connection_handle = Arc::new(Mutex::new(None));
+        let service = ConnectionService {
+            connection_handle: connection_handle.clone(),
+            peer_address,
+            pipelines: Arc::new(Mutex::new(HashMap::new())),
+            session: None,
+            server: server.clone(),
+        };
+        let service = NormalizeURILayer::new().layer(service);
+        let service = layers.timeout.layer(service);
+        let service = layers.concurrency_limit.layer(service);
+
+        // Publishing is only possible through RTSP/1.0, so it is accepted whatever the
+        // configuration of the listener.
+        let metrics = server.lock().unwrap().metrics.clone();
+        let config = match layers.connection_config.clone() {
+            Some(config) => {
+                let metrics = config.metrics().cloned().or(metrics);
+                config.into_builder().with_metrics(metrics)
+            }
+            None => ConnectionConfig::builder().with_metrics(metrics),
+        }
+        .with_peer_address(peer_address)
+        .with_rtsp_1_0_allowed(true)
+        .build();
+        let mut handle = match &layers.authentication {
+            Some(authentication) => spawn_connection(
+                transport,
+                authentication.layer(service),
+                config,
+                &connection_handle,
+            ),
+            None => spawn_connection(transport, service, config, &connection_handle),
+        };
+
+        let shutdown_receiver = handle.shutdown_receiver();
+        let id = {
+            let mut server = server.lock().unwrap();
+            let id = server.next_connection_id;
+            server.next_connection_id += 1;
+            server.connections.insert(id, handle);
+            id
+        };
+
+        // Closed connections are forgotten, so that their handles are not kept for as long as the
+        // server runs.
+        let server = server.clone();
+        tokio::spawn(shutdown_receiver.then(move |_| {
+            server.lock().unwrap().connections.remove(&id);
+            Ok(())
+        }));
+    }
+
+    /// Serves requests received on the given transport.
+    ///
+    /// If a tunnel acceptor is given or WebSocket is allowed, connections starting with an HTTP
+    /// request are first paired with the other connection of their tunnel or upgraded.
+    fn serve_transport<TTransport>(
+        server: &Arc<Mutex<Server>>,
+        layers: &ServiceLayers,
+        tunnels: Option<TunnelAcceptor<TTransport>>,
+        websocket: bool,
+        transport: TTransport,
+        peer_address: Option<SocketAddr>,
+    ) where
+        TTransport: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        if tunnels.is_none() && !websocket {
+            return Server::serve_connection(server, layers, transport, peer_address);
+        }
+
+        let server = server.clone();
+        let layers = layers.clone();
+        let serving = ReadHeader::new(transport, true).and_then(
+            move |(header, transport)| -> Box<dyn Future<Item = (), Error = io::Error> + Send> {
+                let header = match header {
+                    Some(header) => header,
+                    None => {
+                        Server::serve_connection(&server, &layers, transport, peer_address);
+                        return Box::new(future::ok(()));
+                    }
+                };
+
+                if websocket && websocket::is_upgrade(&header) {
+                    return Box::new(websocket::accept_request(header, transport).map(
+                        move |socket| {
+                            Server::serve_connection(&server, &layers, socket, peer_address)
+                        },
+                    ));
+                }
+
+                match tunnels {
+                    Some(tunnels) => Box::new(tunnels.accept_request(header, transport).map(
+                        move |accepted| match accepted {
+                            Accepted::Direct(transport) => {
+                                Server::serve_connection(&server, &layers, transport, peer_address)
+                            }
+                            Accepted::Pending => {}
+                            Accepted::Tunnel(tunnel) => {
+                                Server::serve_connection(&server, &layers, tunnel, peer_address)
+                            }
+                        },
+                    )),
+                    None => Box::new(http::refuse(
+                        transport,
+                        "the request is not a WebSocket upgrade",
+                    )),
+                }
+            },
+        );
+
+        // Connections sending HTTP requests that cannot be accepted are simply dropped.
+        tokio::spawn(serving.map_err(|_| ()));
+    }
+
+    /// Sets the authentication required of requests. By default, requests do not need to
+    /// authenticate.
+    pub fn set_authentication(&mut self, authentication: AuthenticationLayer) -> &mut Self {
+        self.authentication = Some(authentication);
+        self
+    }
+
+    /// Sets the maximum number of requests handled at once across all connections. Requests beyond
+    /// the limit are answered with a 503 (Service Unavailable) response.
+    pub fn set_concurrency_limit(&mut self, limit: usize) -> &mut Self {
+        self.concurrency_limit = limit;
+        self
+    }
+
+    /// Sets the metrics the server and its connections report into.
+    pub fn set_metrics(&mut self, metrics: Arc<dyn Metrics>) -> &mut Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Sets how long handling a request may take before it is answered with a 503 (Service
+    /// Unavailable) response.
+    pub fn set_request_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.request_timeout = timeout;
+        self
+    }
+
+    /// Sets how long sessions are kept without being used, which is advertised to clients in the
+    /// `"Session"` header.
+    pub fn set_session_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.session_timeout = timeout;
+        self
+    }
+
+    /// Sets how long connections are given to finish handling their requests when shutting down.
+    pub fn set_shutdown_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.shutdown_timeout = timeout;
+        self
+    }
+
+    /// Returns the `"Via"` header entry identifying this server for the given protocol version.
+    fn via(&self, version: Version) -> ViaEntry {
+        match self.address {
+            Some(address) => ViaEntry::new(version, Host::from(address.ip()), Some(address.port())),
+            None => ViaEntry::new(
+                version,
+                Host::try_from("rtsp-2").unwrap().into_owned(),
+                None,
+            ),
+        }
+    }
+
+    /// Sets the authentication required of requests.
+    pub fn with_authentication(mut self, authentication: AuthenticationLayer) -> Self {
+        self.set_authentication(authentication);
+        self
+    }
+
+    /// Sets the maximum number of requests handled at once across all connections.
+    pub fn with_concurrency_limit(mut self, limit: usize) -> Self {
+        self.set_concurrency_limit(limit);
+        self
+    }
+
+    /// Makes each elementary stream file in the given directory available as an on-demand
+    /// presentation, served under the name of the file.
+    pub fn with_directory<TPath>(mut self, directory: TPath) -> Result<Self, FileError>
+    where
+        TPath: AsRef<Path>,
+    {
+        self.add_directory(directory)?;
+        Ok(self)
+    }
+
+    /// Adds a listener to accept connections on.
+    pub fn with_listener(mut self, listener: Listener) -> Self {
+        self.add_listener(listener);
+        self
+    }
+
+    /// Sets the metrics the server and its connections report into.
+    pub fn with_metrics(mut self, metrics: Arc<dyn Metrics>) -> Self {
+        self.set_metrics(metrics);
+        self
+    }
+
+    /// Makes the given presentation available under the given path, such as `"live"`.
+    pub fn with_presentation<TPath>(mut self, path: TPath, presentation: Presentation) -> Self
+    where
+        TPath: AsRef<str>,
+    {
+        self.add_presentation(path, presentation);
+        self
+    }
+
+    /// Relays the presentation at the given upstream URI under the given path, such as
+    /// `"camera"`.
+    pub fn with_relay<TPath>(mut self, path: TPath, upstream: URI) -> Self
+    where
+        TPath: AsRef<str>,
+    {
+        self.add_relay(path, upstream);
+        self
+    }
+
+    /// Sets how long handling a request may take before it is answered with a 503 (Service
+    /// Unavailable) response.
+    pub fn with_request_timeout(mut self, timeout: Duration) -> Self {
+        self.set_request_timeout(timeout);
+        self
+    }
+
+    /// Sets how long sessions are kept without being used.
+    pub fn with_session_timeout(mut self, timeout: Duration) -> Self {
+        self.set_session_timeout(timeout);
+        self
+    }
+
+    /// Sets how long connections are given to finish handling their requests when shutting down.
+    pub fn with_shutdown_timeout(mut self, timeout: Duration) -> Self {
+        self.set_shutdown_timeout(timeout);
+        self
+    }
+}
+
+impl Default for Server {
+    fn default() -> Self {
+        Server::new()
+    }
+}
+
+/// An address a [`Server`] accepts connections on.
+#[derive(Clone)]
+pub struct Listener {
+    /// The address to bind.
+    address: SocketAddr,
+
+    /// The authentication required of requests received on the address, if it differs from the
+    /// one of the server.
+    authentication: Option<Option<AuthenticationLayer>>,
+
+    /// The configuration of the connections accepted on the address, if it differs from the
+    /// default one.
+    connection_config: Option<ConnectionConfig>,
+
+    /// Whether RTSP can also be tunneled over HTTP on the same address.
+    http_tunneling: bool,
+
+    /// The acceptor performing the TLS handshake of connections, if they are encrypted.
+    #[cfg(feature = "tls")]
+    tls_acceptor: Option<TlsAcceptor>,
+
+    /// The acceptor pairing the connections of HTTP tunnels encrypted with TLS.
+    #[cfg(feature = "tls")]
+    tls_tunnels: TunnelAcceptor<TlsStream<TcpStream>>,
+
+    /// The acceptor pairing the connections of HTTP tunnels.
+    tunnels: TunnelAcceptor<TcpStream>,
+
+    /// Whether RTSP can also be carried over WebSocket connections on the same address.
+    websocket: bool,
+}
+
+impl Listener {
+    /// Returns the address to bind.
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// Returns the configuration of the connections accepted on the address, if it differs from
+    /// the default one.
+    pub fn connection_config(&self) -> Option<&ConnectionConfig> {
+        self.connection_config.as_ref()
+    }
+
+    /// Returns whether RTSP can also be tunneled over HTTP on the address.
+    pub fn is_http_tunneling_allowed(&self) -> bool {
+        self.http_tunneling
+    }
+
+    /// Returns whether RTSP can also be carried over WebSocket connections on the address.
+    pub fn is_websocket_allowed(&self) -> bool {
+        self.websocket
+    }
+
+    /// Constructs a new listener accepting unencrypted connections on the given address.
+    pub fn new(address: SocketAddr) -> Self {
+        Listener {
+            address,
+            authentication: None,
+            connection_config: None,
+            http_tunneling: false,
+            #[cfg(feature = "tls")]
+            tls_acceptor: None,
+            #[cfg(feature = "tls")]
+            tls_tunnels: TunnelAcceptor::new(),
+            tunnels: TunnelAcceptor::new(),
+            websocket: false,
+        }
+    }
+
+    /// Sets the authentication required of requests received on the address, overriding the one of
+    /// the server. [`Option::None`] lets requests through without authenticating, such as on a
+    /// loopback address used for administration.
+    pub fn set_authentication(&mut self, authentication: Option<AuthenticationLayer>) -> &mut Self {
+        self.authentication = Some(authentication);
+        self
+    }
+
+    /// Sets the configuration of the connections accepted on the address.
+    ///
+    /// The peer address of the configuration is replaced by the one of each connection, and
+    /// connections report into the metrics of the server unless the configuration has its own.
+    /// Connections accept RTSP/1.0 requests regardless of the configuration, so that publishers
+    /// can announce presentations.
+    pub fn set_connection_config(&mut self, config: ConnectionConfig) -> &mut Self {
+        self.connection_config = Some(config);
+        self
+    }
+
+    /// Sets whether RTSP can also be tunneled over HTTP on the address, as described in
+    /// [`tunnel`](crate::protocol::tunnel).
+    ///
+    /// Connections carrying RTSP directly are still accepted. By default, tunneling is not allowed.
+    pub fn set_http_tunneling_allowed(&mut self, allowed: bool) -> &mut Self {
+        self.http_tunneling = allowed;
+        self
+    }
+
+    /// Sets the acceptor performing the TLS handshake of connections, so that they are encrypted.
+    #[cfg(feature = "tls")]
+    pub fn set_tls_acceptor(&mut self, acceptor: native_tls::TlsAcceptor) -> &mut Self {
+        self.tls_acceptor = Some(TlsAcceptor::from(acceptor));
+        self
+    }
+
+    /// Sets whether RTSP can also be carried over WebSocket connections on the address, as
+    /// described in [`websocket`](crate::protocol::websocket).
+    ///
+    /// Connections carrying RTSP directly are still accepted. By default, WebSocket is not allowed.
+    pub fn set_websocket_allowed(&mut self, allowed: bool) -> &mut Self {
+        self.websocket = allowed;
+        self
+    }
+
+    /// Sets the authentication required of requests received on the address, overriding the one of
+    /// the server.
+    pub fn with_authentication(mut self, authentication: Option<AuthenticationLayer>) -> Self {
+        self.set_authentication(authentication);
+        self
+    }
+
+    /// Sets the configuration of the connections accepted on the address.
+    pub fn with_connection_config(mut self, config: ConnectionConfig) -> Self {
+        self.set_connection_config(config);
+        self
+    }
+
+    /// Sets whether RTSP can also be tunneled over HTTP on the address, as described in
+    /// [`tunnel`](crate::protocol::tunnel).
+    ///
+    /// Connections carrying RTSP directly are still accepted. By default, tunneling is not allowed.
+    pub fn with_http_tunneling_allowed(mut self, allowed: bool) -> Self {
+        self.set_http_tunneling_allowed(allowed);
+        self
+    }
+
+    /// Sets the acceptor performing the TLS handshake of connections, so that they are encrypted.
+    #[cfg(feature = "tls")]
+    pub fn with_tls_acceptor(mut self, acceptor: native_tls::TlsAcceptor) -> Self {
+        self.set_tls_acceptor(acceptor);
+        self
+    }
+
+    /// Sets whether RTSP can also be carried over WebSocket connections on the address, as
+    /// described in [`websocket`](crate::protocol::websocket).
+    ///
+    /// Connections carrying RTSP directly are still accepted. By default, WebSocket is not allowed.
+    pub fn with_websocket_allowed(mut self, allowed: bool) -> Self {
+        self.set_websocket_allowed(allowed);
+        self
+    }
+}
+
+/// The layers of the services of the connections accepted on a listener, along with the
+/// configuration of the connections.
+#[derive(Clone)]
+struct ServiceLayers {
+    authentication: Option<AuthenticationLayer>,
+    concurrency_limit: ConcurrencyLimitLayer,
+    connection_config: Option<ConnectionConfig>,
+    timeout: TimeoutLayer,
+}
+
+/// A presentation announced by a publishing agent.
+struct Announcement {
+    /// The description of the presentation, with stream controls relative to its path.
+    description: SessionDescription,
+
+    /// The session that set up the presentation for recording, if any.
+    publisher: Option<SessionID>,
+}
+
+/// The transport a publisher sends the packets of a stream over.
+#[derive(Clone, Copy)]
+enum IngestSource {
+    /// Interleaved in the RTSP connection of the publisher, on the given channel.
+    Interleaved(u8),
+
+    /// Over UDP, from the given address.
+    UDP(SocketAddr),
+}
+
+/// A presentation set up for recording by a session.
+struct Recording {
+    /// The path the presentation was announced at.
+    path: String,
+
+    /// The transports of the streams that were set up, keyed by their index in the announced
+    /// description.
+    sources: BTreeMap<usize, IngestSource>,
+
+    /// Stops the ingest of the presentation once recording, also when dropped.
+    tx_stop: Option<oneshot::Sender<()>>,
+}
+
+/// A future that receives the RTP packets sent over UDP by publishers, forwarding them to the
+/// ingest of the stream they belong to.
+///
+/// Packets from unknown addresses, including RTCP, are dropped.
+struct RTPReceiver {
+    buffer: Vec<u8>,
+    socket: TokioUdpSocket,
+    sources: IngestSources,
+}
+
+impl Future for RTPReceiver {
+    type Item = ();
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        loop {
+            let (length, address) = match self.socket.poll_recv_from(&mut self.buffer) {
+                Ok(Async::Ready(received)) => received,
+                Ok(Async::NotReady) => return Ok(Async::NotReady),
+
+                // Errors are reported for individual datagrams, such as when a destination we sent
+                // to is unreachable, and do not affect receiving others.
+                Err(_) => continue,
+            };
+
+            if let Some((index, tx_packet)) = self.sources.lock().unwrap().get(&address) {
+                let _ = tx_packet.unbounded_send((*index, Bytes::from(&self.buffer[..length])));
+            }
+        }
+    }
+}
+
+/// A stream served under a path.
+enum ServedStream {
+    /// A live stream, delivered to all sessions through a shared fan-out.
+    Live(FanOutHandle),
+
+    /// An on-demand stream, copied into a fan-out of its own for each session setting it up.
+    OnDemand(Box<dyn MediaStream + Send>),
+}
+
+impl ServedStream {
+    /// Returns the fan-out the stream is delivered through for a session setting it up, and
+    /// whether that fan-out is private to the session.
+    ///
+    /// Private fan-outs start out paused.
+    fn fan_out(&self) -> Option<(FanOutHandle, bool)> {
+        match self {
+            ServedStream::Live(handle) => Some((handle.clone(), false)),
+            ServedStream::OnDemand(stream) => {
+                let (fan_out, handle) =
+                    FanOut::new(stream.try_clone()?, packetizer::Config::default()).ok()?;
+                handle.pause();
+                tokio::spawn(fan_out);
+                Some((handle, true))
+            }
+        }
+    }
+}
+
+struct ConnectionService {
+    connection_handle: Arc<Mutex<Option<ConnectionHandle>>>,
+    peer_address: Option<SocketAddr>,
+
+    /// The sessions created by pipelines of requests on the connection, keyed by their startup
+    /// identifiers.
+    pipelines: Arc<Mutex<HashMap<PipelinedRequests, SessionID>>>,
+
+    session: Option<Arc<Mutex<ServerSession>>>,
+    server: Arc<Mutex<Server>>,
+}
+
+impl ConnectionService {
+    fn handle_method_announce(
+        &mut self,
+        request: Request<BytesMut>,
+    ) -> <Self as Service<Request<BytesMut>>>::Future {
+        let is_sdp = request
+            .headers()
+            .get(&HeaderName::ContentType)
+            .and_then(|content_type| content_type.as_str().split(';').next())
+            .map_or(false, |content_type| {
+                content_type.trim().eq_ignore_ascii_case(SDP_CONTENT_TYPE)
+            });
+
+        if !is_sdp {
+            return Box::new(future::ok(status_response(
+                StatusCode::UnsupportedMediaType,
+            )));
+        }
+
+        let announced = match str::from_utf8(request.body()) {
+            Ok(body) => match SessionDescription::try_from(body) {
+                Ok(description) => description,
+                Err(_) => return Box::new(future::ok(BAD_REQUEST_RESPONSE.clone())),
+            },
+            Err(_) => return Box::new(future::ok(BAD_REQUEST_RESPONSE.clone())),
+        };
+
+        let path = resource_path(request.uri());
+        let mut description = SessionDescription::new(announced.name());
+
+        for (index, media) in announced.media().iter().enumerate() {
+            // Published streams are served again, so they need to be both depacketized and
+            // packetized.
+            let supported = depacketizer::from_format(media.format(), media.format_parameters())
+                .is_ok()
+                && packetizer::from_format(
+                    media.format(),
+                    media.format_parameters(),
+                    packetizer::Config::default(),
+                )
+                .is_ok();
+
+            if !supported {
+                return Box::new(future::ok(status_response(
+                    StatusCode::UnsupportedMediaType,
+                )));
+            }
+
+            let control = match relative_control(&path, media.control()) {
+                Some(control) if !control.is_empty() => control,
+                Some(_) if announced.media().len() == 1 => format!("stream={}", index),
+                _ => return Box::new(future::ok(BAD_REQUEST_RESPONSE.clone())),
+            };
+
+            if description
+                .media()
+                .iter()
+                .any(|media| media.control() == control)
+            {
+                return Box::new(future::ok(BAD_REQUEST_RESPONSE.clone()));
+            }
+
+            description.add_media(MediaDescription::new(
+                control,
+                media.format().clone(),
+                media.format_parameters().cloned(),
+            ));
+        }
+
+        if path.is_empty() || description.media().is_empty() {
+            return Box::new(future::ok(BAD_REQUEST_RESPONSE.clone()));
+        }
+
+        let mut server = self.server.lock().unwrap();
+
+        // Presentations can only be announced where nothing is being served, though an
+        // announcement that was never recorded can be replaced.
+        let available = match server.announcements.get(&path) {
+            Some(announcement) => announcement.publisher.is_none(),
+            None => !server.has_resource(&path),
+        };
+
+        if !available {
+            return Box::new(future::ok(status_response(StatusCode::Forbidden)));
+        }
+
+        server.announcements.insert(
+            path,
+            Announcement {
+                description,
+                publisher: None,
+            },
+        );
+        Box::new(future::ok(status_response(StatusCode::OK)))
+    }
+
+    fn handle_method_describe(
+        &mut self,
+        request: Request<BytesMut>,
+    ) -> <Self as Service<Request<BytesMut>>>::Future {
+        if let Some(session) = self.session.as_mut() {
+            session.lock().unwrap().touch();
+        }
+
+        let server = self.server.lock().unwrap();
+        let path = resource_path(request.uri());
+
+        if let Some(description) = server.descriptions.get(&path) {
+            return Box::new(future::ok(describe_response(request.uri(), description)));
+        }
+
+        // Relayed presentations are described by their upstream server the first time.
+        let relay = match server.relays.get(&path) {
+            Some(relay) => relay,
+            None => return Box::new(future::ok(status_response(StatusCode::NotFound))),
+        };
+        let server_lock = self.server.clone();
+        let uri = request.uri().clone();
+        let response = relay
+            .describe(via_value(&server.via(Version::Rtsp2_0)))
+            .then(move |result| {
+                let (description, base) = match result {
+                    Ok(described) => described,
+                    Err(error) => return Ok(status_response(RelayError::status_code(&error))),
+                };
+                let mut server = server_lock.lock().unwrap();
+                server.install_relay(&path, &description, &base);
+
+                Ok(match server.descriptions.get(&path) {
+                    Some(description) => describe_response(&uri, description),
+                    None => status_response(StatusCode::NotFound),
+                })
+            });
+        Box::new(response)
+    }
+
+    fn handle_method_options(
+        &mut self,
+        request: Request<BytesMut>,
+    ) -> <Self as Service<Request<BytesMut>>>::Future {
+        if let Some(session) = self.session.as_mut() {
+            session.lock().unwrap().touch();
+        }
+
+        // Drop the body.
+        let request = request.map(|_| BytesMut::new());
+
+        let response = if request.uri().is_asterisk()
+            || self
+                .server
+                .lock()
+                .unwrap()
+                .has_resource(&resource_path(request.uri()))
+        {
+            Response::<()>::builder()
+                .with_typed_header(
+                    SUPPORTED_METHODS
+                        .iter()
+                        .cloned()
+                        .chain(
+                            PUBLISHING_METHODS
+                                .iter()
+                                .map(|&method| Method::from(method)),
+                        )
+                        .collect::<Public>(),
+                )
+                .with_body(BytesMut::new())
+                .build()
+                .unwrap()
+        } else {
+            status_response(StatusCode::NotFound)
+        };
+
+        Box::new(future::ok(response))
+    }
+
+    fn handle_method_pause(
+        &mut self,
+        request: Request<BytesMut>,
+    ) -> <Self as Service<Request<BytesMut>>>::Future {
+        let server = self.server.lock().unwrap();
+        let session = match find_session(&server, &request) {
+            Ok(session) => session,
+            Err(response) => return Box::new(future::ok(response)),
+        };
+        let path = resource_path(request.uri());
+        let mut session = session.lock().unwrap();
+        session.touch();
+
+        if !session.has_setup(&path) {
+            return Box::new(future::ok(status_response(
+                StatusCode::MethodNotValidInThisState,
+            )));
+        }
+
+        session.pause(&path);
+        let response = Response::<()>::builder()
+            .with_typed_header(session_header(&session))
+            .with_body(BytesMut::new())
+            .build()
+            .unwrap();
+        Box::new(future::ok(response))
+    }
+
+    fn handle_method_play(
+        &mut self,
+        request: Request<BytesMut>,
+    ) -> <Self as Service<Request<BytesMut>>>::Future {
+        let range = match request.headers().typed_try_get::<Range>() {
+            Ok(range) => range,
+            Err(_) => return Box::new(future::ok(status_response(StatusCode::InvalidRange))),
+        };
+
+        let mut server = self.server.lock().unwrap();
+        let session = match find_session(&server, &request) {
+            Ok(session) => session,
+            Err(response) => return Box::new(future::ok(response)),
+        };
+        let path = resource_path(request.uri());
+        let mut session = session.lock().unwrap();
+        session.touch();
+
+        if !session.has_setup(&path) {
+            return Box::new(future::ok(status_response(
+                StatusCode::MethodNotValidInThisState,
+            )));
+        }
+
+        let mut stream_paths = session
+            .setups
+            .keys()
+            .filter(|stream_path| is_within(stream_path, &path))
+            .cloned()
+            .collect::<Vec<_>>();
+        stream_paths.sort();
+
+        let end = match range.as_ref().and_then(Range::end) {
+            Some(NPTTime::Time(end)) => Some(end),
+            _ => None,
+        };
+        let position = match range.as_ref().map(Range::start) {
+            Some(Some(NPTTime::Time(start))) => match session.seek(&stream_paths, start) {
+                Ok(position) => Some(position),
+                Err(_) => return Box::new(future::ok(status_response(StatusCode::InvalidRange))),
+            },
+            _ => None,
+        };
+
+        let mut rtp_info = RTPInfo::new();
+        let mut response_range = Range::new(NPTTime::Now, None);
+
+        for stream_path in &stream_paths {
+            let setup = session.setups.get_mut(stream_path).unwrap();
+            let stream = &setup.fan_out;
+
+            // Seekable streams start at an RTP timestamp matching the position they were seeked
+            // to, otherwise the last timestamp sent is the best estimate available.
+            let rtp_timestamp = match position {
+                Some(position) if setup.on_demand => Some(rtp_timestamp(position, stream)),
+                _ => stream.rtp_timestamp(),
+            };
+            let info = SSRCInfo::new(
+                stream.ssrc(),
+                Some(stream.next_sequence_number()),
+                rtp_timestamp,
+            );
+
+            if let Ok(uri) = stream_uri(request.uri(), &path, stream_path) {
+                rtp_info.push(StreamInfo::new(uri, vec![info]));
+            }
+
+            if setup.on_demand {
+                let start = position
+                    .or_else(|| stream.position())
+                    .unwrap_or_else(|| Duration::from_secs(0));
+                response_range = Range::new(
+                    NPTTime::Time(start),
+                    end.or_else(|| stream.duration()).map(NPTTime::Time),
+                );
+            }
+
+            setup.play(end);
+        }
+
+        // Relayed presentations are only played upstream while someone is playing them.
+        let via = via_value(&server.via(Version::Rtsp2_0));
+
+        for (relay_path, relay) in server.relays.iter_mut() {
+            if stream_paths
+                .iter()
+                .any(|stream_path| is_within(stream_path, relay_path))
+            {
+                if let Some(upstream) = relay.add_viewer(session.id().clone(), via.clone()) {
+                    tokio::spawn(upstream);
+                }
+            }
+        }
+
+        let response = Response::<()>::builder()
+            .with_typed_header(session_header(&session))
+            .with_typed_header(rtp_info)
+            .with_typed_header(response_range)
+            .with_body(BytesMut::new())
+            .build()
+            .unwrap();
+        Box::new(future::ok(response))
+    }
+
+    fn handle_method_record(
+        &mut self,
+        request: Request<BytesMut>,
+    ) -> <Self as Service<Request<BytesMut>>>::Future {
+        let mut server = self.server.lock().unwrap();
+        let session = match find_session(&server, &request) {
+            Ok(session) => session,
+            Err(response) => return Box::new(future::ok(response)),
+        };
+        let path = resource_path(request.uri());
+        let mut session = session.lock().unwrap();
+        session.touch();
+
+        let id = session.id().clone();
+        let recording = match session.recording.as_mut() {
+            Some(recording) if recording.path == path && recording.tx_stop.is_none() => recording,
+            _ => {
+                return Box::new(future::ok(status_response(
+                    StatusCode::MethodNotValidInThisState,
+                )))
+            }
+        };
+        let announced = &server.announcements[&path].description;
 
-pub const SUPPORTED_METHODS: [Method; 2] = [Method::Options, Method::Setup];
+        // Each stream set up is depacketized into a live stream, which is served like any other.
 
-/// Experimental high-level server implementation
-pub struct Server {
-    connections: Vec<ConnectionHandle>,
-    sessions: HashMap<SessionID, Arc<Mutex<ServerSession>>>,
-}
+        let (tx_packet, rx_packet) = mpsc::unbounded();
+        let mut channels = HashMap::new();
+        let mut description = SessionDescription::new(announced.name());
+        let mut served_streams = Vec::new();
+        let mut streams = Vec::new();
+        let mut udp_sources = Vec::new();
 
-impl Server {
-    fn new() -> Self {
-        Server {
-            connections: Vec::new(),
-            sessions: HashMap::new(),
+        for (&index, &source) in &recording.sources {
+            let media = &announced.media()[index];
+            let depacketizer =
+                match depacketizer::from_format(media.format(), media.format_parameters()) {
+                    Ok(depacketizer) => depacketizer,
+                    Err(_) => continue,
+                };
+            let (stream, sender) =
+                LiveStream::new(media.format().clone(), media.format_parameters().cloned());
+            let (fan_out, handle) =
+                match FanOut::new(Box::new(stream), packetizer::Config::default()) {
+                    Ok(fan_out) => fan_out,
+                    Err(_) => continue,
+                };
+
+            match source {
+                IngestSource::Interleaved(channel) => {
+                    channels.insert(channel, streams.len());
+                }
+                IngestSource::UDP(address) => {
+                    udp_sources.push((address, (streams.len(), tx_packet.clone())));
+                }
+            }
+
+            description.add_media(media.clone());
+            served_streams.push((format!("{}/{}", path, media.control()), fan_out, handle));
+            streams.push((
+                Reassembler::new(depacketizer, JitterBufferConfig::default()),
+                sender,
+            ));
         }
-    }
 
-    pub fn run(address: SocketAddr) {
-        let server = Arc::new(Mutex::new(Server::new()));
-        let listener = TcpListener::bind(&address).unwrap();
-
-        let serve = listener.incoming().for_each(move |socket| {
-            let server = server.clone();
-            let service = ConnectionService {
-                session: None,
-                server: server.clone(),
+        if !channels.is_empty() {
+            let mut connection = match self.connection_handle.lock().unwrap().clone() {
+                Some(connection) => connection,
+                None => {
+                    return Box::new(future::ok(status_response(StatusCode::InternalServerError)))
+                }
             };
-            let (connection, handler, handle) = Connection::new(socket, Some(service));
+            let tx_packet = tx_packet.clone();
 
-            server.lock().unwrap().connections.push(handle);
+            // Odd channels carry RTCP, which is not needed to serve the streams again.
+            tokio::spawn(connection.interleaved_data().for_each(move |data| {
+                match channels.get(&data.channel()) {
+                    Some(&index) => tx_packet
+                        .unbounded_send((index, data.into_payload()))
+                        .map_err(|_| ()),
+                    None => Ok(()),
+                }
+            }));
+        }
 
-            tokio::spawn(connection);
-            tokio::spawn(handler.unwrap());
+        server.udp_sources.lock().unwrap().extend(udp_sources);
 
-            Ok(())
-        });
+        for (stream_path, fan_out, handle) in served_streams {
+            tokio::spawn(fan_out);
+            server
+                .streams
+                .insert(stream_path, ServedStream::Live(handle));
+        }
 
-        tokio::run(serve.map_err(|_| ()));
-    }
-}
+        server.descriptions.insert(path.clone(), description);
 
-struct ConnectionService {
-    session: Option<Arc<Mutex<ServerSession>>>,
-    server: Arc<Mutex<Server>>,
-}
+        // Recording ends when the publisher stops it or all of its packet sources are gone, such as
+        // when its connection closes.
+        let (tx_stop, rx_stop) = oneshot::channel();
+        let server_lock = self.server.clone();
+        let ingest = Ingest::new(streams, rx_packet)
+            .select(rx_stop.then(|_| Ok(())))
+            .then(move |result| {
+                drop(result);
+                server_lock.lock().unwrap().end_recording(&path, &id);
+                Ok(())
+            });
+        tokio::spawn(ingest);
+        recording.tx_stop = Some(tx_stop);
 
-impl ConnectionService {
-    fn handle_method_options(
+        let response = Response::<()>::builder()
+            .with_typed_header(session_header(&session))
+            .with_body(BytesMut::new())
+            .build()
+            .unwrap();
+        Box::new(future::ok(response))
+    }
+
+    /// Handles a SETUP request for one of the streams of an announced presentation, given the
+    /// transports requested for recording.
+    fn handle_method_setup_record(
         &mut self,
-        request: Request<BytesMut>,
+        request: &Request<BytesMut>,
+        transport: &Transport,
     ) -> <Self as Service<Request<BytesMut>>>::Future {
-        if let Some(session) = self.session.as_mut() {
-            session.lock().unwrap().touch();
-        }
+        let mut server = self.server.lock().unwrap();
+        let stream_path = resource_path(request.uri());
+        let stream = server
+            .announcements
+            .iter()
+            .filter(|(path, _)| stream_path.len() > path.len() && is_within(&stream_path, path))
+            .find_map(|(path, announcement)| {
+                let control = &stream_path[path.len() + 1..];
+                let index = announcement
+                    .description
+                    .media()
+                    .iter()
+                    .position(|media| media.control() == control)?;
+                Some((path.clone(), index))
+            });
+        let (path, index) = match stream {
+            Some(stream) => stream,
+            None => return Box::new(future::ok(status_response(StatusCode::NotFound))),
+        };
 
-        // Drop the body.
-        let request = request.map(|_| BytesMut::new());
+        let negotiated = transport
+            .iter()
+            .filter(|spec| is_record_mode(spec))
+            .find_map(|spec| self.negotiate_ingest_transport(&server, spec));
+        let (spec, source) = match negotiated {
+            Some(negotiated) => negotiated,
+            None => {
+                return Box::new(future::ok(status_response(
+                    StatusCode::UnsupportedTransport,
+                )))
+            }
+        };
 
-        // We do not support any media streams right now, so just always 404 on non-asterisk URI.
-        let response = if request.uri().is_asterisk() {
-            Response::<()>::builder()
-                .with_typed_header(SUPPORTED_METHODS.iter().cloned().collect::<Public>())
-                .with_body(BytesMut::new())
-                .build()
-                .unwrap()
+        let session = if request.headers().contains_key(&HeaderName::Session) {
+            match find_session(&server, request) {
+                Ok(session) => session,
+                Err(response) => return Box::new(future::ok(response)),
+            }
         } else {
-            Response::<()>::builder()
-                .with_status_code(StatusCode::NotFound)
-                .with_body(BytesMut::new())
-                .build()
-                .unwrap()
+            let session = Arc::new(Mutex::new(ServerSession::new()));
+            session.lock().unwrap().timeout = server.session_timeout;
+            server.insert_session(session.clone());
+            session
         };
 
+        self.session = Some(session.clone());
+        let mut session = session.lock().unwrap();
+        session.touch();
+
+        let announcement = server.announcements.get_mut(&path).unwrap();
+
+        match &announcement.publisher {
+            Some(publisher) if publisher != session.id() => {
+                return Box::new(future::ok(status_response(StatusCode::Forbidden)))
+            }
+            _ => (),
+        }
+
+        // A session publishes a single presentation, and streams cannot be added once recording.
+        let recording = session.recording.get_or_insert_with(|| Recording {
+            path: path.clone(),
+            sources: BTreeMap::new(),
+            tx_stop: None,
+        });
+
+        if recording.path != path || recording.tx_stop.is_some() {
+            return Box::new(future::ok(status_response(
+                StatusCode::MethodNotValidInThisState,
+            )));
+        }
+
+        recording.sources.insert(index, source);
+        announcement.publisher = Some(session.id().clone());
+
+        let response = Response::<()>::builder()
+            .with_typed_header(session_header(&session))
+            .with_typed_header(Transport::from(spec))
+            .with_body(BytesMut::new())
+            .build()
+            .unwrap();
         Box::new(future::ok(response))
     }
 
@@ -108,24 +1658,209 @@ impl ConnectionService {
         // Drop the body.
         let request = request.map(|_| BytesMut::new());
 
-        // Client must set `"Accept-Ranges"` header with acceptable time range formats.
-        match request.headers().typed_try_get::<AcceptRanges>() {
-            Ok(range_formats) => {
-                // TODO: Check that this resource supports one of these ranges.
-                let _ = range_formats.unwrap_or(AcceptRanges::new());
-
-                // For now, just say none are supported.
-                // TODO: Fill out `"Accept-Ranges"` header based on requested resource.
-                let response = Response::<()>::builder()
-                    .with_status_code(StatusCode::HeaderFieldNotValidForResource)
-                    .with_typed_header(AcceptRanges::new())
-                    .with_body(BytesMut::new())
-                    .build()
-                    .unwrap();
-                return Box::new(future::ok(response));
+        // Only NPT ranges are supported, which every client supports, but an invalid
+        // `"Accept-Ranges"` header is still an error.
+        if request.headers().typed_try_get::<AcceptRanges>().is_err() {
+            return Box::new(future::ok(BAD_REQUEST_RESPONSE.clone()));
+        }
+
+        let transport = match request.headers().typed_try_get::<Transport>() {
+            Ok(Some(transport)) => transport,
+            _ => return Box::new(future::ok(BAD_REQUEST_RESPONSE.clone())),
+        };
+
+        if transport.iter().any(is_record_mode) {
+            return self.handle_method_setup_record(&request, &transport);
+        }
+
+        let mut server = self.server.lock().unwrap();
+        let path = resource_path(request.uri());
+        if !server.streams.contains_key(&path) {
+            return Box::new(future::ok(status_response(StatusCode::NotFound)));
+        }
+        let negotiated = transport
+            .iter()
+            .find_map(|spec| self.negotiate_transport(&server, spec));
+        let (mut spec, destination) = match negotiated {
+            Some(negotiated) => negotiated,
+            None => {
+                return Box::new(future::ok(status_response(
+                    StatusCode::UnsupportedTransport,
+                )))
             }
-            Err(_) => return Box::new(future::ok(BAD_REQUEST_RESPONSE.clone())),
+        };
+
+        let session = if request.headers().contains_key(&HeaderName::Session) {
+            match find_session(&server, &request) {
+                Ok(session) => session,
+                Err(response) => return Box::new(future::ok(response)),
+            }
+        } else {
+            let session = Arc::new(Mutex::new(ServerSession::new()));
+            session.lock().unwrap().timeout = server.session_timeout;
+            server.insert_session(session.clone());
+            session
+        };
+
+        let (fan_out, on_demand) = match server.streams[&path].fan_out() {
+            Some(fan_out) => fan_out,
+            None => return Box::new(future::ok(status_response(StatusCode::InternalServerError))),
+        };
+        spec.insert("ssrc", Some(format!("{:08X}", fan_out.ssrc())));
+
+        self.session = Some(session.clone());
+        let mut session = session.lock().unwrap();
+        session.touch();
+
+        // Setting up a stream again changes its transport, replacing the old setup stops delivery
+        // over the old one.
+        session.setups.insert(
+            path,
+            StreamSetup {
+                destination,
+                fan_out,
+                on_demand,
+                playing: None,
+            },
+        );
+
+        let response = Response::<()>::builder()
+            .with_typed_header(session_header(&session))
+            .with_typed_header(Transport::from(spec))
+            .with_body(BytesMut::new())
+            .build()
+            .unwrap();
+        Box::new(future::ok(response))
+    }
+
+    fn handle_method_teardown(
+        &mut self,
+        request: Request<BytesMut>,
+    ) -> <Self as Service<Request<BytesMut>>>::Future {
+        let mut server = self.server.lock().unwrap();
+        let session_lock = match find_session(&server, &request) {
+            Ok(session) => session,
+            Err(response) => return Box::new(future::ok(response)),
+        };
+        let path = resource_path(request.uri());
+        let mut session = session_lock.lock().unwrap();
+        let is_recording = session
+            .recording
+            .as_ref()
+            .map_or(false, |recording| is_within(&path, &recording.path));
+
+        if !session.has_setup(&path) && !is_recording {
+            return Box::new(future::ok(status_response(
+                StatusCode::MethodNotValidInThisState,
+            )));
+        }
+
+        // Dropping the setups stops their delivery, and dropping the recording stops its ingest.
+        session
+            .setups
+            .retain(|stream_path, _| !is_within(stream_path, &path));
+
+        if is_recording {
+            let recording = session.recording.take().unwrap();
+            server.end_recording(&recording.path, session.id());
+        }
+
+        for (relay_path, relay) in server.relays.iter_mut() {
+            if !session.has_setup(relay_path) {
+                relay.remove_viewer(session.id());
+            }
+        }
+
+        if session.setups.is_empty() && session.recording.is_none() {
+            server.remove_session(session.id());
+
+            if self
+                .session
+                .as_ref()
+                .map_or(false, |session| Arc::ptr_eq(session, &session_lock))
+            {
+                self.session = None;
+            }
+        }
+
+        Box::new(future::ok(status_response(StatusCode::OK)))
+    }
+
+    /// Returns the transport to respond with along with the source packets will be received from
+    /// if the given transport specification is acceptable for recording.
+    ///
+    /// Only unicast RTP over UDP or interleaved in the RTSP connection is supported.
+    fn negotiate_ingest_transport(
+        &self,
+        server: &Server,
+        spec: &TransportSpec,
+    ) -> Option<(TransportSpec, IngestSource)> {
+        let mut protocol = spec.protocol().split('/');
+
+        if protocol.next() != Some("RTP") || protocol.next() != Some("AVP") {
+            return None;
+        }
+
+        let mut spec = spec.clone();
+        let source = if spec.is_interleaved() {
+            IngestSource::Interleaved(*spec.interleaved()?.start())
+        } else if spec.lower_transport() == "UDP"
+            && spec.delivery_type() != Some(DeliveryType::Multicast)
+        {
+            let peer_address = self.peer_address?;
+            let socket = server.rtp_socket(peer_address)?;
+            let client_port = *spec.client_port()?.start();
+            spec.insert(
+                "server_port",
+                Some(socket.local_addr().ok()?.port().to_string()),
+            );
+            IngestSource::UDP(SocketAddr::new(peer_address.ip(), client_port))
+        } else {
+            return None;
+        };
+
+        Some((spec, source))
+    }
+
+    /// Returns the transport to respond with along with the destination packets should be sent to
+    /// if the given transport specification is acceptable. The SSRC is left for the caller to add.
+    ///
+    /// Only unicast RTP over UDP or interleaved in the RTSP connection is supported.
+    fn negotiate_transport(
+        &self,
+        server: &Server,
+        spec: &TransportSpec,
+    ) -> Option<(TransportSpec, Destination)> {
+        let mut protocol = spec.protocol().split('/');
+
+        if protocol.next() != Some("RTP") || protocol.next() != Some("AVP") {
+            return None;
         }
+
+        let mut spec = spec.clone();
+        let destination = if spec.is_interleaved() {
+            let channel = *spec.interleaved()?.start();
+            let connection = self.connection_handle.lock().unwrap().clone()?;
+            Destination::Interleaved {
+                channel,
+                connection,
+            }
+        } else if spec.lower_transport() == "UDP"
+            && spec.delivery_type() != Some(DeliveryType::Multicast)
+        {
+            let socket = server.rtp_socket(self.peer_address?)?.clone();
+            let client_port = *spec.client_port()?.start();
+            let address = SocketAddr::new(self.peer_address?.ip(), client_port);
+            spec.insert(
+                "server_port",
+                Some(socket.local_addr().ok()?.port().to_string()),
+            );
+            Destination::UDP { address, socket }
+        } else {
+            return None;
+        };
+
+        Some((spec, destination))
     }
 }
 
@@ -135,15 +1870,114 @@ impl Service<Request<BytesMut>> for ConnectionService {
     type Future = Box<dyn Future<Item = Self::Response, Error = Self::Error> + Send + 'static>;
 
     fn call(&mut self, mut request: Request<BytesMut>) -> Self::Future {
-        request.uri_mut().normalize();
+        // Responses use the version of the request, so that RTSP/1.0 publishers understand them.
+        let version = request.version();
+
+        // Requests pipelined before their session existed are executed in the session created by
+        // the first request of the pipeline.
+        let pipeline = match request.headers().typed_try_get::<PipelinedRequests>() {
+            Ok(pipeline) => pipeline,
+            Err(_) => {
+                return Box::new(future::ok(with_version(
+                    BAD_REQUEST_RESPONSE.clone(),
+                    version,
+                )))
+            }
+        };
+
+        if let Some(pipeline) = pipeline.as_ref() {
+            if !request.headers().contains_key(&HeaderName::Session) {
+                if let Some(id) = self.pipelines.lock().unwrap().get(pipeline) {
+                    let session = SessionHeader::without_timeout(id.as_str())
+                        .expect("session identifiers should be valid");
+                    request.headers_mut().typed_insert(session);
+                }
+            }
+        }
+
+        // Requests for relayed presentations are handled as a proxy would.
+        let via = {
+            let server = self.server.lock().unwrap();
+            server
+                .relay_path(&resource_path(request.uri()))
+                .map(|_| server.via(version))
+        };
+
+        if let Some(entry) = &via {
+            if request
+                .headers()
+                .typed_get::<Via>()
+                .map_or(false, |via| via.detects_loop(entry))
+            {
+                return Box::new(future::ok(loop_detected_response(version, BytesMut::new())));
+            }
+        }
+
+        let via = via.map(|entry| via_value(&entry));
+        let proxy_supported = match &via {
+            Some(via) => {
+                let unsupported = unsupported_proxy_features(&request);
+
+                if !unsupported.is_empty() {
+                    let response = Response::<()>::builder()
+                        .with_status_code(StatusCode::OptionNotSupported)
+                        .with_header(
+                            HeaderName::Unsupported,
+                            HeaderValue::try_from(unsupported.join(", ").as_str()).unwrap(),
+                        )
+                        .with_header(HeaderName::Via, via.clone())
+                        .with_body(BytesMut::new())
+                        .build()
+                        .unwrap();
+                    return Box::new(future::ok(with_version(response, version)));
+                }
 
-        match request.method() {
+                proxy_supported_features(&request)
+            }
+            None => None,
+        };
+
+        let response = match request.method() {
+            Method::Describe => self.handle_method_describe(request),
             Method::Options => self.handle_method_options(request),
+            Method::Pause => self.handle_method_pause(request),
+            Method::Play => self.handle_method_play(request),
             Method::Setup => self.handle_method_setup(request),
+            Method::Teardown => self.handle_method_teardown(request),
+            method if *method == Rtsp1Method::Announce => self.handle_method_announce(request),
+            method if *method == Rtsp1Method::Record => self.handle_method_record(request),
 
             // PLAY_NOTIFY and REDIRECT are handled here as servers do not respond to such requests.
             _ => Box::new(future::ok(NOT_IMPLEMENTED_RESPONSE.clone())),
-        }
+        };
+
+        let pipelines = self.pipelines.clone();
+
+        Box::new(response.map(move |mut response| {
+            if let Some(pipeline) = pipeline {
+                if let Some(session) = response.headers().typed_get::<SessionHeader>() {
+                    pipelines
+                        .lock()
+                        .unwrap()
+                        .entry(pipeline)
+                        .or_insert_with(|| session.id().clone());
+                }
+
+                response.headers_mut().typed_insert(pipeline);
+            }
+
+            if let Some(via) = via {
+                response.headers_mut().append(HeaderName::Via, via);
+            }
+
+            if let Some(proxy_supported) = proxy_supported {
+                response
+                    .headers_mut()
+                    .insert(HeaderName::ProxySupported, proxy_supported);
+            }
+
+            with_version(response, version)
+        }))
     }
 
     fn poll_ready(&mut self) -> Poll<(), Self::Error> {
@@ -154,32 +1988,87 @@ impl Service<Request<BytesMut>> for ConnectionService {
 pub struct ServerSession {
     expire_time: DateTime<Utc>,
     id: SessionID,
+
+    /// How long the session is kept without being used.
+    timeout: Duration,
+
+    /// The presentation set up for recording, if the session is publishing.
+    recording: Option<Recording>,
+
+    /// The streams that have been set up, keyed by their paths.
+    setups: HashMap<String, StreamSetup>,
 }
 
 impl ServerSession {
-    pub fn new<T>(active_client: ConnectionHandle) -> Self
-    where
-        SessionID: TryFrom<T, Error = SessionIDError>,
-    {
+    /// Returns whether any stream at or within the given path has been set up.
+    fn has_setup(&self, path: &str) -> bool {
+        self.setups
+            .keys()
+            .any(|stream_path| is_within(stream_path, path))
+    }
+
+    pub fn new() -> Self {
         let expire_time = offset::Utc::now()
             .checked_add_signed(chrono::Duration::from_std(DEFAULT_SESSION_TIMEOUT).unwrap())
             .unwrap();
 
-        ServerSession::with_timeout(expire_time, active_client)
+        ServerSession::with_timeout(expire_time)
     }
 
-    pub fn with_timeout<T>(expire_time: DateTime<Utc>, active_client: ConnectionHandle) -> Self
-    where
-        SessionID: TryFrom<T, Error = SessionIDError>,
-    {
+    /// Pauses delivery of all streams at or within the given path to this session.
+    fn pause(&mut self, path: &str) {
+        for (stream_path, setup) in self.setups.iter_mut() {
+            if is_within(stream_path, path) {
+                setup.pause();
+            }
+        }
+    }
+
+    /// Seeks the on-demand streams among the given stream paths to the given position, returning
+    /// the position they were actually seeked to.
+    ///
+    /// Streams can only be seeked to their keyframes, so they are aligned to the earliest position
+    /// any of them ended up at to keep them synchronized.
+    fn seek(&mut self, stream_paths: &[String], position: Duration) -> Result<Duration, SeekError> {
+        let fan_outs = stream_paths
+            .iter()
+            .filter_map(|stream_path| self.setups.get(stream_path))
+            .filter(|setup| setup.on_demand)
+            .map(|setup| &setup.fan_out)
+            .collect::<Vec<_>>();
+        let mut actual_position = position;
+
+        for fan_out in &fan_outs {
+            actual_position = actual_position.min(fan_out.seek(position)?);
+        }
+
+        if actual_position != position {
+            for fan_out in &fan_outs {
+                fan_out.seek(actual_position)?;
+            }
+        }
+
+        Ok(actual_position)
+    }
+
+    fn touch(&mut self) {
+        self.set_timeout(self.timeout).unwrap();
+    }
+
+    pub fn with_timeout(expire_time: DateTime<Utc>) -> Self {
         ServerSession {
             expire_time,
             id: SessionID::random(),
+            recording: None,
+            setups: HashMap::new(),
+            timeout: DEFAULT_SESSION_TIMEOUT,
         }
     }
+}
 
-    fn touch(&mut self) {
-        self.set_timeout(DEFAULT_SESSION_TIMEOUT).unwrap();
+impl Default for ServerSession {
+    fn default() -> Self {
+        ServerSession::new()
     }
 }
 
@@ -202,3 +2091,271 @@ impl Session for ServerSession {
         Ok(())
     }
 }
+
+/// A stream set up by a session.
+///
+/// Dropping the setup stops delivering the stream to the session.
+struct StreamSetup {
+    /// The destination negotiated for the stream.
+    destination: Destination,
+
+    /// The fan-out the stream is delivered through.
+    fan_out: FanOutHandle,
+
+    /// Whether the fan-out is private to the session, as is the case for on-demand streams.
+    on_demand: bool,
+
+    /// The destination currently receiving the stream, if it is being played.
+    playing: Option<DestinationID>,
+}
+
+impl StreamSetup {
+    /// Stops delivering the stream, pausing the fan-out if it is private to the session.
+    fn pause(&mut self) {
+        if let Some(id) = self.playing.take() {
+            self.fan_out.remove_destination(id);
+        }
+
+        if self.on_demand {
+            self.fan_out.pause();
+        }
+    }
+
+    /// Starts delivering the stream, resuming the fan-out until the given position if it is
+    /// private to the session.
+    fn play(&mut self, end: Option<Duration>) {
+        if self.playing.is_none() {
+            self.playing = Some(self.fan_out.add_destination(self.destination.clone()));
+        }
+
+        if self.on_demand {
+            self.fan_out.resume(end);
+        }
+    }
+}
+
+impl Drop for StreamSetup {
+    fn drop(&mut self) {
+        self.pause();
+
+        if self.on_demand {
+            self.fan_out.close();
+        }
+    }
+}
+
+/// Returns the response to a DESCRIBE request for a presentation with the given description.
+fn describe_response(uri: &URI, description: &SessionDescription) -> Response<BytesMut> {
+    // Stream controls are relative to the presentation, so the base must end with a slash.
+    let content_base = format!("{}/", uri.to_string().trim_end_matches('/'));
+    let content_base = match HeaderValue::try_from(content_base.as_str()) {
+        Ok(content_base) => content_base,
+        Err(_) => return BAD_REQUEST_RESPONSE.clone(),
+    };
+
+    Response::<()>::builder()
+        .with_header(HeaderName::ContentBase, content_base)
+        .with_header(
+            HeaderName::ContentType,
+            HeaderValue::try_from(SDP_CONTENT_TYPE).unwrap(),
+        )
+        .with_body(BytesMut::from(description.to_string()))
+        .build()
+        .unwrap()
+}
+
+/// Returns the feature tags listed by all headers of the given name in the request.
+fn feature_tags<'request, TBody>(
+    request: &'request Request<TBody>,
+    name: &HeaderName,
+) -> impl Iterator<Item = &'request str> {
+    request
+        .headers()
+        .get_all(name)
+        .flat_map(|value| value.as_str().split(','))
+        .map(str::trim)
+        .filter(|feature| !feature.is_empty())
+}
+
+/// Looks up the session identified by the `"Session"` header of the request, returning the
+/// response to send if there is no such session.
+fn find_session<TBody>(
+    server: &Server,
+    request: &Request<TBody>,
+) -> Result<Arc<Mutex<ServerSession>>, Response<BytesMut>> {
+    match request.headers().typed_try_get::<SessionHeader>() {
+        Ok(Some(session)) => server
+            .sessions
+            .get(session.id())
+            .cloned()
+            .ok_or_else(|| status_response(StatusCode::SessionNotFound)),
+        Ok(None) => Err(status_response(StatusCode::SessionNotFound)),
+        Err(_) => Err(BAD_REQUEST_RESPONSE.clone()),
+    }
+}
+
+/// Returns whether the given transport specification requests recording rather than playing.
+fn is_record_mode(spec: &TransportSpec) -> bool {
+    spec.get("mode").map_or(false, |modes| {
+        modes
+            .trim_matches('"')
+            .split(',')
+            .any(|mode| mode.trim().eq_ignore_ascii_case("RECORD"))
+    })
+}
+
+/// Returns whether the given stream path is the given path or is within it.
+fn is_within(stream_path: &str, path: &str) -> bool {
+    stream_path == path
+        || (stream_path.starts_with(path) && stream_path[path.len()..].starts_with('/'))
+}
+
+/// Returns the `"Proxy-Supported"` header to respond with if the request lists the features
+/// supported by proxies, which are those listed along with the features of this server.
+fn proxy_supported_features<TBody>(request: &Request<TBody>) -> Option<HeaderValue> {
+    let mut features = feature_tags(request, &HeaderName::ProxySupported).peekable();
+    features.peek()?;
+
+    let mut features = features.collect::<Vec<_>>();
+
+    for feature in PROXY_FEATURES.iter() {
+        if !features.contains(feature) {
+            features.push(feature);
+        }
+    }
+
+    HeaderValue::try_from(features.join(", ").as_str()).ok()
+}
+
+/// Returns the control of an announced stream relative to the path it was announced at, given the
+/// control from its description, which may also be an absolute URI.
+fn relative_control(path: &str, control: &str) -> Option<String> {
+    let control = match URI::try_from(control) {
+        Ok(uri) => {
+            let stream_path = resource_path(&uri);
+
+            if stream_path == path {
+                return Some(String::new());
+            }
+
+            if !is_within(&stream_path, path) {
+                return None;
+            }
+
+            stream_path[path.len() + 1..].to_string()
+        }
+        Err(_) => control.trim_matches('/').to_string(),
+    };
+
+    if control == "*" {
+        Some(String::new())
+    } else {
+        Some(control)
+    }
+}
+
+/// Returns the path of the resource a request URI refers to, without leading or trailing slashes.
+fn resource_path(uri: &URI) -> String {
+    uri.path().to_string().trim_matches('/').to_string()
+}
+
+/// Returns the RTP timestamp corresponding to the given position of a seekable stream.
+fn rtp_timestamp(position: Duration, stream: &FanOutHandle) -> u32 {
+    let clock_rate = u128::from(stream.clock_rate());
+    (position.as_nanos() * clock_rate / 1_000_000_000) as u32
+}
+
+/// Returns the `"Session"` header for the given session.
+fn session_header(session: &ServerSession) -> SessionHeader {
+    SessionHeader::with_timeout(session.id().as_str(), session.timeout)
+        .expect("generated session identifiers should be valid")
+}
+
+/// Spawns a connection serving requests received on the given transport with the given service,
+/// wrapped in the layers every connection shares.
+fn spawn_connection<TTransport, TService>(
+    transport: TTransport,
+    service: TService,
+    config: ConnectionConfig,
+    connection_handle: &Mutex<Option<ConnectionHandle>>,
+) -> ConnectionHandle
+where
+    TTransport: AsyncRead + AsyncWrite + Send + 'static,
+    TService: Service<Request<BytesMut>, Response = Response<BytesMut>> + Send + 'static,
+    TService::Error: Send + 'static,
+    TService::Future: Send + 'static,
+{
+    let service = ErrorResponseLayer::new().layer(service);
+    let service = CatchPanicLayer::new().layer(service);
+    let service = ServerHeaderLayer::default().layer(service);
+    let service = DateLayer::new().layer(service);
+    let service = CSeqLayer::new().layer(service);
+    let (connection, handler, handle) = Connection::with_config(transport, Some(service), config);
+
+    // The handle must be available before any request is handled.
+    *connection_handle.lock().unwrap() = Some(handle.clone());
+
+    tokio::spawn(connection);
+    tokio::spawn(handler.unwrap());
+    handle
+}
+
+/// Spawns the given background task of a server, which is dropped once the server has stopped.
+fn spawn_until_stopped<TTask>(task: TTask, stopped: &Shared<oneshot::Receiver<()>>)
+where
+    TTask: Future<Item = (), Error = ()> + Send + 'static,
+{
+    tokio::spawn(
+        task.select(stopped.clone().then(|_| Ok(())))
+            .then(|_| Ok(())),
+    );
+}
+
+/// Returns an empty response with the given status code.
+fn status_response(status_code: StatusCode) -> Response<BytesMut> {
+    Response::<()>::builder()
+        .with_status_code(status_code)
+        .with_body(BytesMut::new())
+        .build()
+        .unwrap()
+}
+
+/// Returns the URI of the stream at the given stream path, given the URI of the request for the
+/// resource at the given path.
+fn stream_uri(uri: &URI, path: &str, stream_path: &str) -> Result<URI, ()> {
+    if stream_path == path {
+        return Ok(uri.clone());
+    }
+
+    let uri = format!(
+        "{}/{}",
+        uri.to_string().trim_end_matches('/'),
+        &stream_path[path.len() + 1..]
+    );
+    URI::try_from(uri.as_str()).map_err(|_| ())
+}
+
+/// Returns the features required of proxies through the `"Proxy-Require"` header of the request
+/// that are not supported by this server.
+fn unsupported_proxy_features<TBody>(request: &Request<TBody>) -> Vec<&str> {
+    feature_tags(request, &HeaderName::ProxyRequire)
+        .filter(|feature| !PROXY_FEATURES.contains(feature))
+        .collect()
+}
+
+/// Returns the `"Via"` header value containing only the given entry.
+fn via_value(entry: &ViaEntry) -> HeaderValue {
+    HeaderValue::try_from(entry.to_string().as_str())
+        .expect("`\"Via\"` entry should be a valid header value")
+}
+
+/// Returns the given response using the given version.
+fn with_version(response: Response<BytesMut>, version: Version) -> Response<BytesMut> {
+    if response.version() == version {
+        return response;
+    }
+
+    let (_, status_code, reason_phrase, headers, body) = response.into_parts();
+    Response::from_parts(version, status_code, reason_phrase, headers, body)
+        .expect("supported versions should be valid")
+}
@@ -99,6 +99,25 @@ pub fn trim_bytes_whitespace_right(mut value: &[u8]) -> &[u8] {
     value
 }
 
+/// Splits the given value on the given separator, ignoring separators within quoted strings.
+pub fn split_unquoted(value: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut quoted = false;
+    let mut start = 0;
+
+    for (index, character) in value.char_indices() {
+        if character == '"' {
+            quoted = !quoted;
+        } else if character == separator && !quoted {
+            parts.push(&value[start..index]);
+            start = index + character.len_utf8();
+        }
+    }
+
+    parts.push(&value[start..]);
+    parts
+}
+
 /// A helper function used to trim whitespace as it is used in
 /// [[RFC7826](https://tools.ietf.org/html/rfc7826)]. Specifically, whitespace includes `' '`,
 /// `'\t'`, and `"\r\n"`. The trim functions defined on the `str` slice do not seem to be enough to
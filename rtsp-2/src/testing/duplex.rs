@@ -0,0 +1,165 @@
+//! Duplex Transport
+//!
+//! This module contains an in-memory transport connecting two ends, so that agents can talk to each
+//! other without any networking.
+
+use std::{
+    cmp,
+    collections::VecDeque,
+    io::{self, Read, Write},
+    sync::{Arc, Mutex},
+};
+
+use futures::{
+    task::{self, Task},
+    Async, Poll,
+};
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// Creates a connected pair of in-memory transports. Bytes written to one end can be read from the
+/// other.
+///
+/// Like other asynchronous transports, the ends must be read from within a task, since reading
+/// registers the current task to be notified once there is data to be read.
+pub fn duplex() -> (DuplexStream, DuplexStream) {
+    let first = Arc::new(Mutex::new(Pipe::default()));
+    let second = Arc::new(Mutex::new(Pipe::default()));
+
+    (
+        DuplexStream {
+            read: first.clone(),
+            write: second.clone(),
+        },
+        DuplexStream {
+            read: second,
+            write: first,
+        },
+    )
+}
+
+/// One end of an in-memory transport created by [`duplex`].
+///
+/// Dropping or shutting down an end closes the other end for reading once it has read everything
+/// that was written. Writing to an end fails once the other end has been dropped.
+#[derive(Debug)]
+pub struct DuplexStream {
+    /// The pipe the other end writes into.
+    read: Arc<Mutex<Pipe>>,
+
+    /// The pipe the other end reads from.
+    write: Arc<Mutex<Pipe>>,
+}
+
+impl DuplexStream {
+    /// Closes the pipe written into, notifying the other end.
+    fn close(&mut self) {
+        let mut pipe = self.write.lock().unwrap();
+        pipe.closed = true;
+
+        if let Some(reader) = pipe.reader.take() {
+            reader.notify();
+        }
+    }
+}
+
+impl AsyncRead for DuplexStream {}
+
+impl AsyncWrite for DuplexStream {
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        self.close();
+        Ok(Async::Ready(()))
+    }
+}
+
+impl Drop for DuplexStream {
+    fn drop(&mut self) {
+        self.close();
+
+        // Nothing will read what the other end writes anymore.
+        self.read.lock().unwrap().closed = true;
+    }
+}
+
+impl Read for DuplexStream {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        let mut pipe = self.read.lock().unwrap();
+
+        if pipe.buffer.is_empty() {
+            if pipe.closed {
+                return Ok(0);
+            }
+
+            pipe.reader = Some(task::current());
+            return Err(io::ErrorKind::WouldBlock.into());
+        }
+
+        let count = cmp::min(buffer.len(), pipe.buffer.len());
+
+        for (byte, value) in buffer.iter_mut().zip(pipe.buffer.drain(..count)) {
+            *byte = value;
+        }
+
+        Ok(count)
+    }
+}
+
+impl Write for DuplexStream {
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        let mut pipe = self.write.lock().unwrap();
+
+        if pipe.closed {
+            return Err(io::ErrorKind::BrokenPipe.into());
+        }
+
+        pipe.buffer.extend(buffer);
+
+        if let Some(reader) = pipe.reader.take() {
+            reader.notify();
+        }
+
+        Ok(buffer.len())
+    }
+}
+
+/// The bytes flowing in one direction of a duplex transport.
+#[derive(Debug, Default)]
+struct Pipe {
+    /// The bytes written, but not yet read.
+    buffer: VecDeque<u8>,
+
+    /// Whether the writing end has been closed.
+    closed: bool,
+
+    /// The task waiting for bytes to be written, if any.
+    reader: Option<Task>,
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{Read, Write};
+
+    use futures::{future, Future};
+    use tokio_io::io::read_to_end;
+
+    use crate::testing::duplex::duplex;
+
+    #[test]
+    fn test_duplex() {
+        let (mut first, mut second) = duplex();
+        first.write_all(b"ping").unwrap();
+        second.write_all(b"pong").unwrap();
+        drop(first);
+
+        let mut buffer = [0; 2];
+        future::lazy(|| second.read(&mut buffer)).wait().unwrap();
+        assert_eq!(&buffer, b"pi");
+
+        let (mut second, received) = read_to_end(second, Vec::new()).wait().unwrap();
+        assert_eq!(received, b"ng");
+        assert!(second.write(b"pong").is_err());
+    }
+}
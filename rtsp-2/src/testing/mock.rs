@@ -0,0 +1,808 @@
+//! Mock Server
+//!
+//! This module contains [`MockServer`], which serves a script of expected requests and canned
+//! responses to a single connection, recording every deviation from the script.
+
+use std::{
+    collections::VecDeque,
+    error::Error,
+    fmt::{self, Display, Formatter},
+    io,
+    net::{SocketAddr, TcpListener},
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use bytes::BytesMut;
+use futures::{future, Async, AsyncSink, Future, Poll, Sink, Stream};
+use tokio::{reactor::Handle, runtime::current_thread};
+use tokio_codec::Framed;
+use tokio_io::{AsyncRead, AsyncWrite};
+use tokio_tcp::TcpStream;
+use tokio_timer::Delay;
+
+use crate::{
+    header::{name::HeaderName, value::HeaderValue},
+    method::Method,
+    protocol::codec::{
+        decoder::{
+            request::Config as RequestDecoderConfig, response::Config as ResponseDecoderConfig,
+        },
+        interleaved::InterleavedData,
+        Codec, Message, ProtocolError,
+    },
+    request::Request,
+    response::{Response, BAD_REQUEST_RESPONSE},
+    status::StatusCode,
+    testing::{
+        duplex::{self, DuplexStream},
+        quirks::Personality,
+    },
+    uri::request::URI,
+};
+
+/// A request the mock server expects to receive, along with the response to answer it with.
+#[derive(Clone, Debug)]
+pub struct Expectation {
+    /// How long to wait before responding.
+    delay: Option<Duration>,
+
+    /// Headers the request must contain.
+    headers: Vec<(HeaderName, HeaderValue)>,
+
+    /// The method the request must have.
+    method: Method,
+
+    /// The response to answer the request with.
+    response: Response<BytesMut>,
+
+    /// The URI the request must have, if any.
+    uri: Option<URI>,
+}
+
+impl Expectation {
+    /// Returns how long the mock server waits before responding, if at all.
+    pub fn delay(&self) -> Option<Duration> {
+        self.delay
+    }
+
+    /// Returns the headers the request must contain.
+    pub fn headers(&self) -> &[(HeaderName, HeaderValue)] {
+        &self.headers
+    }
+
+    /// Returns whether the given request meets this expectation.
+    fn matches<TBody>(&self, request: &Request<TBody>) -> bool {
+        request.method() == &self.method
+            && self.uri.as_ref().map_or(true, |uri| request.uri() == uri)
+            && self.headers.iter().all(|(name, value)| {
+                request
+                    .headers()
+                    .get_all(name)
+                    .any(|request_value| request_value == value)
+            })
+    }
+
+    /// Returns the method the request must have.
+    pub fn method(&self) -> &Method {
+        &self.method
+    }
+
+    /// Constructs a new expectation of a request with the given method, answered with an empty
+    /// 200 (OK) response.
+    pub fn new(method: Method) -> Self {
+        Expectation {
+            delay: None,
+            headers: Vec::new(),
+            method,
+            response: Response::<()>::builder()
+                .with_status_code(StatusCode::OK)
+                .with_body(BytesMut::new())
+                .build()
+                .expect("OK response should not be invalid"),
+            uri: None,
+        }
+    }
+
+    /// Returns the response the request is answered with.
+    pub fn response(&self) -> &Response<BytesMut> {
+        &self.response
+    }
+
+    /// Sets how long to wait before responding.
+    pub fn set_delay(&mut self, delay: Duration) -> &mut Self {
+        self.delay = Some(delay);
+        self
+    }
+
+    /// Adds a header the request must contain. The request may contain other values for the same
+    /// header, but one of them must be the given value.
+    pub fn set_header(&mut self, name: HeaderName, value: HeaderValue) -> &mut Self {
+        self.headers.push((name, value));
+        self
+    }
+
+    /// Sets the response the request is answered with. The `"CSeq"` header of the request is
+    /// copied into the response.
+    pub fn set_response(&mut self, response: Response<BytesMut>) -> &mut Self {
+        self.response = response;
+        self
+    }
+
+    /// Sets the URI the request must have. By default, any URI is accepted.
+    pub fn set_uri(&mut self, uri: URI) -> &mut Self {
+        self.uri = Some(uri);
+        self
+    }
+
+    /// Returns the URI the request must have, if any.
+    pub fn uri(&self) -> Option<&URI> {
+        self.uri.as_ref()
+    }
+
+    /// Sets how long to wait before responding.
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.set_delay(delay);
+        self
+    }
+
+    /// Adds a header the request must contain.
+    pub fn with_header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.set_header(name, value);
+        self
+    }
+
+    /// Sets the response the request is answered with.
+    pub fn with_response(mut self, response: Response<BytesMut>) -> Self {
+        self.set_response(response);
+        self
+    }
+
+    /// Sets the URI the request must have.
+    pub fn with_uri(mut self, uri: URI) -> Self {
+        self.set_uri(uri);
+        self
+    }
+}
+
+/// A mock server following a script of steps, such as expecting a request or sending interleaved
+/// data, in the order they were added.
+///
+/// Requests not matching the next expectation and requests received after the script has ended
+/// are answered with a 400 (Bad Request) response and recorded as errors. Once the script has been
+/// served, [`MockServerHandle::verify`] reports whether it was followed:
+///
+/// ```
+/// use std::convert::TryFrom;
+///
+/// use futures::Future;
+/// use rtsp::client::Client;
+/// use rtsp::method::Method;
+/// use rtsp::request::Request;
+/// use rtsp::status::StatusCode;
+/// use rtsp::testing::{Expectation, MockServer};
+/// use rtsp::uri::request::URI;
+///
+/// let (address, handle) = MockServer::new()
+///     .with_expectation(Expectation::new(Method::Options))
+///     .listen()
+///     .unwrap();
+///
+/// let request = Request::<()>::builder()
+///     .with_method(Method::Options)
+///     .with_uri(URI::try_from("rtsp://example.com/").unwrap())
+///     .with_body("")
+///     .build()
+///     .unwrap();
+/// let response = tokio::runtime::Runtime::new()
+///     .unwrap()
+///     .block_on(Client::connect(address).and_then(move |mut client| {
+///         client
+///             .send_request(request)
+///             .map_err(|_| std::io::ErrorKind::Other.into())
+///     }))
+///     .unwrap();
+///
+/// assert_eq!(response.status_code(), StatusCode::OK);
+/// assert!(handle.verify().is_ok());
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct MockServer {
+    /// The personality whose quirks are applied to responses.
+    personality: Personality,
+
+    /// The script to follow.
+    steps: Vec<Step>,
+}
+
+impl MockServer {
+    /// Serves the script on an in-memory transport on a separate thread, returning the other end
+    /// of the transport.
+    pub fn duplex(self) -> (DuplexStream, MockServerHandle) {
+        let (client, server) = duplex::duplex();
+        let (future, handle) = self.serve(server);
+
+        thread::spawn(move || current_thread::block_on_all(future));
+
+        (client, handle)
+    }
+
+    /// Adds closing the connection to the script, once everything before has been sent.
+    pub fn close(&mut self) -> &mut Self {
+        self.steps.push(Step::Close);
+        self
+    }
+
+    /// Adds an expected request to the script.
+    pub fn expect(&mut self, expectation: Expectation) -> &mut Self {
+        self.steps.push(Step::Expect(expectation));
+        self
+    }
+
+    /// Serves the script to the first connection accepted on a loopback address on a separate
+    /// thread, returning the address listened on.
+    pub fn listen(self) -> io::Result<(SocketAddr, MockServerHandle)> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let address = listener.local_addr()?;
+        let state = Arc::new(Mutex::new(State::new(&self.steps)));
+        let handle = MockServerHandle {
+            state: state.clone(),
+        };
+
+        thread::spawn(move || {
+            let stream = match listener.accept() {
+                Ok((stream, _)) => stream,
+                Err(error) => {
+                    state.lock().unwrap().errors.push(error.into());
+                    return;
+                }
+            };
+
+            // The stream is converted within the runtime so that it is registered with its reactor.
+            let _ = current_thread::block_on_all(future::lazy(move || {
+                match TcpStream::from_std(stream, &Handle::default()) {
+                    Ok(stream) => future::Either::A(self.serve_with_state(stream, state)),
+                    Err(error) => {
+                        state.lock().unwrap().errors.push(error.into());
+                        future::Either::B(future::ok(()))
+                    }
+                }
+            }));
+        });
+
+        Ok((address, handle))
+    }
+
+    /// Constructs a new mock server with an empty script.
+    pub fn new() -> Self {
+        MockServer::default()
+    }
+
+    /// Adds raw bytes to be written to the script. This can be used to send malformed messages.
+    pub fn send_bytes<TBytes>(&mut self, bytes: TBytes) -> &mut Self
+    where
+        TBytes: AsRef<[u8]>,
+    {
+        self.steps
+            .push(Step::SendBytes(BytesMut::from(bytes.as_ref())));
+        self
+    }
+
+    /// Adds interleaved data to be sent to the script.
+    pub fn send_interleaved_data(&mut self, data: InterleavedData) -> &mut Self {
+        self.steps.push(Step::SendData(data));
+        self
+    }
+
+    /// Returns a future serving the script on the given transport, completing once the transport
+    /// is closed by either end.
+    pub fn serve<TTransport>(
+        self,
+        transport: TTransport,
+    ) -> (MockServerFuture<TTransport>, MockServerHandle)
+    where
+        TTransport: AsyncRead + AsyncWrite,
+    {
+        let state = Arc::new(Mutex::new(State::new(&self.steps)));
+        let handle = MockServerHandle {
+            state: state.clone(),
+        };
+
+        (self.serve_with_state(transport, state), handle)
+    }
+
+    /// Returns a future serving the script on the given transport, recording into the given state.
+    fn serve_with_state<TTransport>(
+        self,
+        transport: TTransport,
+        state: Arc<Mutex<State>>,
+    ) -> MockServerFuture<TTransport>
+    where
+        TTransport: AsyncRead + AsyncWrite,
+    {
+        let request_decoder_config = RequestDecoderConfig::builder()
+            .with_rtsp_1_0_allowed(true)
+            .build();
+        let codec = Codec::with_config(
+            request_decoder_config,
+            ResponseDecoderConfig::default(),
+            None,
+        );
+
+        MockServerFuture {
+            delay: None,
+            expectation_index: 0,
+            outgoing: None,
+            personality: self.personality,
+            state,
+            steps: self.steps.into(),
+            transport: Framed::new(transport, codec),
+        }
+    }
+
+    /// Sets the personality whose quirks are applied to every response, including the 400 (Bad
+    /// Request) responses to unexpected requests.
+    pub fn set_personality(&mut self, personality: Personality) -> &mut Self {
+        self.personality = personality;
+        self
+    }
+
+    /// Adds raw bytes to be written to the script.
+    pub fn with_bytes<TBytes>(mut self, bytes: TBytes) -> Self
+    where
+        TBytes: AsRef<[u8]>,
+    {
+        self.send_bytes(bytes);
+        self
+    }
+
+    /// Adds closing the connection to the script.
+    pub fn with_close(mut self) -> Self {
+        self.close();
+        self
+    }
+
+    /// Adds an expected request to the script.
+    pub fn with_expectation(mut self, expectation: Expectation) -> Self {
+        self.expect(expectation);
+        self
+    }
+
+    /// Adds interleaved data to be sent to the script.
+    pub fn with_interleaved_data(mut self, data: InterleavedData) -> Self {
+        self.send_interleaved_data(data);
+        self
+    }
+
+    /// Sets the personality whose quirks are applied to every response.
+    pub fn with_personality(mut self, personality: Personality) -> Self {
+        self.set_personality(personality);
+        self
+    }
+}
+
+/// A future serving the script of a [`MockServer`] on a transport.
+#[must_use = "futures do nothing unless polled"]
+pub struct MockServerFuture<TTransport>
+where
+    TTransport: AsyncRead + AsyncWrite,
+{
+    /// The delay before the outgoing message may be sent, if any.
+    delay: Option<Delay>,
+
+    /// The index of the next expectation in the script.
+    expectation_index: usize,
+
+    /// The message waiting to be sent, if any.
+    outgoing: Option<Message>,
+
+    /// The personality whose quirks are applied to responses.
+    personality: Personality,
+
+    /// The state shared with the handle.
+    state: Arc<Mutex<State>>,
+
+    /// The remaining steps of the script.
+    steps: VecDeque<Step>,
+
+    /// The transport framed with the codec.
+    transport: Framed<TTransport, Codec>,
+}
+
+impl<TTransport> MockServerFuture<TTransport>
+where
+    TTransport: AsyncRead + AsyncWrite,
+{
+    /// Answers the given request with a 400 (Bad Request) response.
+    fn reject(&mut self, request: &Request<BytesMut>) {
+        self.respond(request, BAD_REQUEST_RESPONSE.clone(), None);
+    }
+
+    /// Queues the given response to the given request, copying over the `"CSeq"` header.
+    fn respond(
+        &mut self,
+        request: &Request<BytesMut>,
+        mut response: Response<BytesMut>,
+        delay: Option<Duration>,
+    ) {
+        if let Some(cseq) = request.headers().get(&HeaderName::CSeq) {
+            response
+                .headers_mut()
+                .insert(HeaderName::CSeq, cseq.clone());
+        }
+
+        self.delay = delay.map(|delay| Delay::new(Instant::now() + delay));
+
+        // Responses with quirks cannot be encoded by the codec, so they are written as raw bytes
+        // right after the delay.
+        if self.personality.quirks().is_empty() {
+            self.outgoing = Some(Message::Response(response));
+        } else {
+            let bytes = self.personality.encode(response);
+            self.steps.push_front(Step::SendBytes(bytes));
+        }
+    }
+
+    /// Sends the outgoing message once its delay has elapsed and flushes the transport.
+    fn poll_send(&mut self) -> Poll<(), ProtocolError> {
+        if let Some(delay) = self.delay.as_mut() {
+            match delay.poll() {
+                Ok(Async::Ready(_)) | Err(_) => self.delay = None,
+                Ok(Async::NotReady) => return Ok(Async::NotReady),
+            }
+        }
+
+        if let Some(message) = self.outgoing.take() {
+            if let AsyncSink::NotReady(message) = self.transport.start_send(message)? {
+                self.outgoing = Some(message);
+                self.transport.poll_complete()?;
+                return Ok(Async::NotReady);
+            }
+        }
+
+        self.transport.poll_complete()
+    }
+
+    /// Records an error, to be reported by the handle.
+    fn record(&self, error: MockError) {
+        self.state.lock().unwrap().errors.push(error);
+    }
+
+    /// Handles the given request according to the script.
+    fn handle_request(&mut self, request: Request<BytesMut>) {
+        match self.steps.front() {
+            Some(Step::Expect(_)) => {
+                let expectation = match self.steps.pop_front() {
+                    Some(Step::Expect(expectation)) => expectation,
+                    _ => unreachable!(),
+                };
+                let index = self.expectation_index;
+                self.expectation_index += 1;
+
+                if expectation.matches(&request) {
+                    // The expectation is marked as met before responding, so that a client having
+                    // received the response can immediately verify the script.
+                    self.state.lock().unwrap().unmet_expectations -= 1;
+                    self.respond(&request, expectation.response, expectation.delay);
+                } else {
+                    self.reject(&request);
+                    self.record(MockError::MismatchedRequest(index, Box::new(request)));
+                }
+            }
+            _ => {
+                self.reject(&request);
+                self.record(MockError::UnexpectedRequest(Box::new(request)));
+            }
+        }
+    }
+}
+
+impl<TTransport> Future for MockServerFuture<TTransport>
+where
+    TTransport: AsyncRead + AsyncWrite,
+{
+    type Item = ();
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        loop {
+            match self.poll_send() {
+                Ok(Async::Ready(_)) => (),
+                Ok(Async::NotReady) => return Ok(Async::NotReady),
+                Err(error) => {
+                    self.record(MockError::Protocol(error));
+                    return Ok(Async::Ready(()));
+                }
+            }
+
+            match self.steps.front_mut() {
+                Some(Step::Close) => {
+                    self.steps.pop_front();
+                    return Ok(Async::Ready(()));
+                }
+                Some(Step::SendBytes(bytes)) => {
+                    // Raw bytes bypass the codec, which has been flushed above.
+                    while !bytes.is_empty() {
+                        match self.transport.get_mut().poll_write(bytes) {
+                            Ok(Async::Ready(count)) => bytes.advance(count),
+                            Ok(Async::NotReady) => return Ok(Async::NotReady),
+                            Err(error) => {
+                                self.record(MockError::Protocol(error.into()));
+                                return Ok(Async::Ready(()));
+                            }
+                        }
+                    }
+
+                    self.steps.pop_front();
+                    continue;
+                }
+                Some(Step::SendData(_)) => {
+                    if let Some(Step::SendData(data)) = self.steps.pop_front() {
+                        self.outgoing = Some(Message::Data(data));
+                    }
+
+                    continue;
+                }
+                _ => (),
+            }
+
+            match self.transport.poll() {
+                Ok(Async::Ready(Some(Message::Request(request)))) => self.handle_request(request),
+                Ok(Async::Ready(Some(_))) => (),
+                Ok(Async::Ready(None)) => return Ok(Async::Ready(())),
+                Ok(Async::NotReady) => return Ok(Async::NotReady),
+                Err(error) => {
+                    self.record(MockError::Protocol(error));
+                    return Ok(Async::Ready(()));
+                }
+            }
+        }
+    }
+}
+
+/// A handle to a [`MockServer`] being served, used to verify its script was followed.
+#[derive(Clone, Debug)]
+pub struct MockServerHandle {
+    /// The state shared with the serving future.
+    state: Arc<Mutex<State>>,
+}
+
+impl MockServerHandle {
+    /// Returns the errors recorded so far, in the order they occurred.
+    pub fn errors(&self) -> Vec<MockError> {
+        self.state.lock().unwrap().errors.clone()
+    }
+
+    /// Verifies that no errors have been recorded and that every expectation has been met,
+    /// returning the first error otherwise. This does not wait for the mock server.
+    pub fn verify(&self) -> Result<(), MockError> {
+        let state = self.state.lock().unwrap();
+
+        match state.errors.first() {
+            Some(error) => Err(error.clone()),
+            None if state.unmet_expectations > 0 => {
+                Err(MockError::UnmetExpectations(state.unmet_expectations))
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+/// An error recorded by a mock server.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub enum MockError {
+    /// The request did not match the expectation with the given index in the script.
+    MismatchedRequest(usize, Box<Request<BytesMut>>),
+
+    /// An error was encountered on the transport.
+    Protocol(ProtocolError),
+
+    /// The request was received after every expectation had been met.
+    UnexpectedRequest(Box<Request<BytesMut>>),
+
+    /// The given number of expectations were not met.
+    UnmetExpectations(usize),
+}
+
+impl Display for MockError {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        use self::MockError::*;
+
+        match self {
+            MismatchedRequest(index, request) => write!(
+                formatter,
+                "request {} {} did not match expectation {}",
+                request.method(),
+                request.uri(),
+                index
+            ),
+            Protocol(error) => error.fmt(formatter),
+            UnexpectedRequest(request) => write!(
+                formatter,
+                "unexpected request {} {}",
+                request.method(),
+                request.uri()
+            ),
+            UnmetExpectations(count) => write!(formatter, "{} expectations were not met", count),
+        }
+    }
+}
+
+impl Error for MockError {}
+
+impl From<io::Error> for MockError {
+    fn from(value: io::Error) -> MockError {
+        MockError::Protocol(value.into())
+    }
+}
+
+/// The state shared between a mock server being served and its handles.
+#[derive(Debug)]
+struct State {
+    /// The errors recorded so far.
+    errors: Vec<MockError>,
+
+    /// The number of expectations not yet met.
+    unmet_expectations: usize,
+}
+
+impl State {
+    /// Constructs the initial state of the given script.
+    fn new(steps: &[Step]) -> Self {
+        State {
+            errors: Vec::new(),
+            unmet_expectations: steps
+                .iter()
+                .filter(|step| matches!(step, Step::Expect(_)))
+                .count(),
+        }
+    }
+}
+
+/// A step of the script of a mock server.
+#[derive(Clone, Debug)]
+#[allow(clippy::large_enum_variant)]
+enum Step {
+    /// Close the connection.
+    Close,
+
+    /// Expect a request and answer it.
+    Expect(Expectation),
+
+    /// Write raw bytes.
+    SendBytes(BytesMut),
+
+    /// Send interleaved data.
+    SendData(InterleavedData),
+}
+
+#[cfg(test)]
+mod test {
+    use std::{convert::TryFrom, time::Duration};
+
+    use bytes::BytesMut;
+    use futures::{Future, Stream};
+    use tokio::runtime::current_thread::Runtime;
+    use tokio_io::io::read_exact;
+
+    use crate::{
+        header::{name::HeaderName, value::HeaderValue},
+        method::Method,
+        protocol::{
+            codec::interleaved::InterleavedData, connection::Connection, service::EmptyService,
+        },
+        request::Request,
+        response::Response,
+        status::StatusCode,
+        testing::mock::{Expectation, MockError, MockServer},
+        uri::request::URI,
+    };
+
+    fn options_request(uri: &str) -> Request<BytesMut> {
+        Request::<()>::builder()
+            .with_method(Method::Options)
+            .with_uri(URI::try_from(uri).unwrap())
+            .with_header(
+                HeaderName::Require,
+                HeaderValue::try_from("play.basic").unwrap(),
+            )
+            .with_body(BytesMut::new())
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_mock_server_duplex() {
+        let response = Response::<()>::builder()
+            .with_status_code(StatusCode::OK)
+            .with_header(
+                HeaderName::Public,
+                HeaderValue::try_from("OPTIONS").unwrap(),
+            )
+            .with_body(BytesMut::new())
+            .build()
+            .unwrap();
+        let (transport, handle) = MockServer::new()
+            .with_expectation(
+                Expectation::new(Method::Options)
+                    .with_uri(URI::try_from("rtsp://example.com/").unwrap())
+                    .with_header(
+                        HeaderName::Require,
+                        HeaderValue::try_from("play.basic").unwrap(),
+                    )
+                    .with_delay(Duration::from_millis(10))
+                    .with_response(response),
+            )
+            .with_interleaved_data(InterleavedData::new(0, &b"RTP"[..]))
+            .duplex();
+
+        let mut runtime = Runtime::new().unwrap();
+        let (connection, _, mut connection_handle) =
+            Connection::new::<EmptyService>(transport, None);
+        runtime.spawn(connection);
+
+        let rx_interleaved_data = connection_handle.interleaved_data();
+        let response = runtime
+            .block_on(connection_handle.send_request(options_request("rtsp://example.com/")))
+            .unwrap();
+
+        assert_eq!(response.status_code(), StatusCode::OK);
+        assert_eq!(
+            response.headers().get(&HeaderName::Public).unwrap(),
+            "OPTIONS"
+        );
+        assert!(handle.verify().is_ok());
+
+        let (data, _) = runtime.block_on(rx_interleaved_data.into_future()).unwrap();
+        assert_eq!(data, Some(InterleavedData::new(0, &b"RTP"[..])));
+    }
+
+    #[test]
+    fn test_mock_server_listen_mismatch() {
+        let (address, handle) = MockServer::new()
+            .with_expectation(Expectation::new(Method::Describe))
+            .listen()
+            .unwrap();
+
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        let response = runtime
+            .block_on(
+                crate::client::Client::connect(address).and_then(|mut client| {
+                    client
+                        .send_request(options_request("rtsp://example.com/"))
+                        .map_err(|_| std::io::ErrorKind::Other.into())
+                }),
+            )
+            .unwrap();
+
+        assert_eq!(response.status_code(), StatusCode::BadRequest);
+
+        match handle.verify() {
+            Err(MockError::MismatchedRequest(0, request)) => {
+                assert_eq!(request.method(), &Method::Options)
+            }
+            result => panic!("unexpected verification result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_mock_server_send_bytes() {
+        let (transport, handle) = MockServer::new()
+            .with_interleaved_data(InterleavedData::new(1, &b"RTCP"[..]))
+            .with_bytes("GARBAGE\r\n")
+            .with_expectation(Expectation::new(Method::Play))
+            .duplex();
+
+        let mut runtime = Runtime::new().unwrap();
+        let (_, received) = runtime.block_on(read_exact(transport, [0; 17])).unwrap();
+
+        assert_eq!(&received, b"$\x01\x00\x04RTCPGARBAGE\r\n");
+
+        match handle.verify() {
+            Err(MockError::UnmetExpectations(1)) => (),
+            result => panic!("unexpected verification result: {:?}", result),
+        }
+    }
+}
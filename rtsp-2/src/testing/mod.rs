@@ -0,0 +1,17 @@
+//! Testing
+//!
+//! This module contains utilities for testing code built on this crate, and is only available when
+//! the `"testing"` feature is enabled. [`MockServer`] serves a script of expected requests and canned
+//! responses, either over loopback TCP or over an in-memory [`DuplexStream`], and reports whether
+//! the script was followed. Its responses can take on the [`Personality`] of a device deviating
+//! from the protocol in the ways real devices do.
+
+pub mod duplex;
+pub mod mock;
+pub mod quirks;
+
+pub use self::{
+    duplex::{duplex, DuplexStream},
+    mock::{Expectation, MockError, MockServer, MockServerFuture, MockServerHandle},
+    quirks::{Personality, Quirk},
+};
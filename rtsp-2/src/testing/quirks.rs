@@ -0,0 +1,405 @@
+//! Quirks
+//!
+//! This module contains emulations of the ways real devices deviate from the protocol when
+//! responding. A [`Personality`] is a set of [`Quirk`]s that a [`MockServer`] applies to every
+//! response it sends, so that client code can be run against each personality in the library:
+//!
+//! ```
+//! use rtsp::protocol::connection::Config;
+//! use rtsp::testing::{Expectation, MockServer, Personality};
+//! use rtsp::method::Method;
+//!
+//! let config = Config::builder().with_rtsp_1_0_allowed(true).build();
+//!
+//! for personality in Personality::library() {
+//!     let tolerated = personality.is_tolerated_by(&config);
+//!     let (transport, handle) = MockServer::new()
+//!         .with_personality(personality)
+//!         .with_expectation(Expectation::new(Method::Options))
+//!         .duplex();
+//!
+//!     // Send an OPTIONS request on a connection over `transport` using `config`, and assert it is
+//!     // answered if and only if the personality is tolerated.
+//! }
+//! ```
+//!
+//! [`MockServer`]: crate::testing::MockServer
+
+use std::{convert::TryFrom, fmt::Write};
+
+use bytes::BytesMut;
+
+use crate::{
+    header::{name::HeaderName, value::HeaderValue},
+    protocol::{
+        codec::{decoder::Deviation, encoder::response as response_encoder},
+        connection::Config,
+    },
+    response::Response,
+    version::Version,
+};
+
+/// A deviation from the protocol in the responses of a device.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum Quirk {
+    /// The lines of the status line and headers end with a bare `"\n"` instead of `"\r\n"`.
+    ///
+    /// This is tolerated if the strictness of the connection tolerates
+    /// [`Deviation::BareLineFeed`], see [`Config::strictness`], in which case the deviation is
+    /// recorded while decoding. It is not tolerated by default.
+    BareLineFeed,
+
+    /// Header names are sent in lowercase, such as `"cseq"`.
+    ///
+    /// This is tolerated, since header names are case-insensitive.
+    LowercaseHeaderNames,
+
+    /// The `"CSeq"` header of the request is not copied into the response.
+    ///
+    /// This is not tolerated, since responses are matched to their requests by their `"CSeq"`.
+    /// Responses without one are ignored, and the request eventually times out.
+    MissingCSeq,
+
+    /// Responses are sent with the `"RTSP/1.0"` version, regardless of the version of the request.
+    ///
+    /// This is tolerated if the connection allows RTSP/1.0, see [`Config::rtsp_1_0_allowed`].
+    Rtsp1_0Response,
+
+    /// The lines of a session description in the body end with a bare `"\n"`, and the last line
+    /// has no line ending at all.
+    ///
+    /// This is tolerated, and session descriptions are still parsed by `SessionDescription`.
+    SdpLineEndings,
+
+    /// A `";"` is appended to the `"Session"` header, such as `"QKyjN8nt2WqbWw4tIYof52;"`.
+    ///
+    /// This is tolerated by connections, but the typed `Session` header fails to decode.
+    SessionTrailingSemicolon,
+}
+
+impl Quirk {
+    /// Returns every quirk.
+    pub fn all() -> &'static [Quirk] {
+        use self::Quirk::*;
+
+        &[
+            BareLineFeed,
+            LowercaseHeaderNames,
+            MissingCSeq,
+            Rtsp1_0Response,
+            SdpLineEndings,
+            SessionTrailingSemicolon,
+        ]
+    }
+
+    /// Applies this quirk to the given encoded response.
+    fn apply_to_encoded(self, buffer: BytesMut) -> BytesMut {
+        use self::Quirk::*;
+
+        if !matches!(self, BareLineFeed | LowercaseHeaderNames) {
+            return buffer;
+        }
+
+        let head_length = match buffer.windows(4).position(|bytes| bytes == b"\r\n\r\n") {
+            Some(position) => position + 4,
+            None => return buffer,
+        };
+        let (head, body) = buffer.split_at(head_length);
+        let head = String::from_utf8_lossy(head);
+        let head = match self {
+            BareLineFeed => head.replace("\r\n", "\n"),
+            LowercaseHeaderNames => {
+                let mut lines = head.split("\r\n");
+                let mut quirked_head = lines.next().unwrap_or_default().to_string();
+
+                for line in lines {
+                    let name_length = line.find(':').unwrap_or(0);
+                    let _ = write!(
+                        quirked_head,
+                        "\r\n{}{}",
+                        line[..name_length].to_ascii_lowercase(),
+                        &line[name_length..]
+                    );
+                }
+
+                quirked_head
+            }
+            _ => unreachable!(),
+        };
+
+        let mut quirked_buffer = BytesMut::from(head.as_bytes());
+        quirked_buffer.extend_from_slice(body);
+        quirked_buffer
+    }
+
+    /// Applies this quirk to the given response before it is encoded.
+    fn apply_to_response(self, response: Response<BytesMut>) -> Response<BytesMut> {
+        use self::Quirk::*;
+
+        let (mut version, status_code, reason_phrase, mut headers, mut body) =
+            response.into_parts();
+
+        match self {
+            MissingCSeq => {
+                headers.remove(&HeaderName::CSeq);
+            }
+            Rtsp1_0Response => version = Version::Rtsp1_0,
+            SdpLineEndings if !body.is_empty() => {
+                let text = String::from_utf8_lossy(&body).into_owned();
+                body = BytesMut::from(text.trim_end().replace("\r\n", "\n").as_bytes());
+            }
+            SessionTrailingSemicolon => {
+                if let Some(session) = headers.get_mut(&HeaderName::Session) {
+                    *session = HeaderValue::try_from(format!("{};", session).as_str())
+                        .expect("session header with trailing semicolon should be valid");
+                }
+            }
+            _ => (),
+        }
+
+        Response::from_parts(version, status_code, reason_phrase, headers, body)
+            .expect("quirked response should not be invalid")
+    }
+
+    /// Returns whether a connection with the given configuration receives responses with this
+    /// quirk.
+    pub fn is_tolerated_by(self, config: &Config) -> bool {
+        use self::Quirk::*;
+
+        match self {
+            BareLineFeed => config.strictness().tolerates(Deviation::BareLineFeed),
+            MissingCSeq => false,
+            LowercaseHeaderNames | SdpLineEndings | SessionTrailingSemicolon => true,
+            Rtsp1_0Response => config.rtsp_1_0_allowed(),
+        }
+    }
+}
+
+/// The set of quirks of a device.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Personality {
+    /// The name of the personality, such as `"legacy-camera"`.
+    name: String,
+
+    /// The quirks of the personality.
+    quirks: Vec<Quirk>,
+}
+
+impl Personality {
+    /// Encodes the given response with the quirks of this personality.
+    pub(crate) fn encode(&self, response: Response<BytesMut>) -> BytesMut {
+        let response = self.quirks.iter().fold(response, |response, quirk| {
+            quirk.apply_to_response(response)
+        });
+        let mut buffer = BytesMut::new();
+        response_encoder::encode(&response, &mut buffer);
+
+        // Header names must be lowercased while the line endings can still be found.
+        let mut quirks = self.quirks.clone();
+        quirks.sort_by_key(|quirk| *quirk != Quirk::LowercaseHeaderNames);
+
+        quirks
+            .into_iter()
+            .fold(buffer, |buffer, quirk| quirk.apply_to_encoded(buffer))
+    }
+
+    /// Returns whether this personality has the given quirk.
+    pub fn has_quirk(&self, quirk: Quirk) -> bool {
+        self.quirks.contains(&quirk)
+    }
+
+    /// Returns whether a connection with the given configuration receives responses from this
+    /// personality, which is the case if every one of its quirks is tolerated.
+    pub fn is_tolerated_by(&self, config: &Config) -> bool {
+        self.quirks
+            .iter()
+            .all(|quirk| quirk.is_tolerated_by(config))
+    }
+
+    /// Returns the personality of an agent only speaking RTSP/1.0 whose responses are otherwise
+    /// well-formed, as is common for IP cameras.
+    pub fn legacy_camera() -> Self {
+        Personality::new("legacy-camera")
+            .with_quirk(Quirk::Rtsp1_0Response)
+            .with_quirk(Quirk::SdpLineEndings)
+            .with_quirk(Quirk::SessionTrailingSemicolon)
+    }
+
+    /// Returns every personality of the library: each quirk on its own, followed by the
+    /// personalities combining quirks commonly seen together.
+    pub fn library() -> Vec<Personality> {
+        Quirk::all()
+            .iter()
+            .map(|&quirk| Personality::from(quirk))
+            .chain(vec![
+                Personality::legacy_camera(),
+                Personality::minimal_embedded_server(),
+            ])
+            .collect()
+    }
+
+    /// Returns the personality of a minimal server embedded in a device, which writes responses by
+    /// hand and skips what it considers unimportant.
+    pub fn minimal_embedded_server() -> Self {
+        Personality::new("minimal-embedded-server")
+            .with_quirk(Quirk::BareLineFeed)
+            .with_quirk(Quirk::LowercaseHeaderNames)
+            .with_quirk(Quirk::MissingCSeq)
+            .with_quirk(Quirk::Rtsp1_0Response)
+    }
+
+    /// Returns the name of this personality.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Constructs a new personality without any quirks.
+    pub fn new<TName>(name: TName) -> Self
+    where
+        TName: Into<String>,
+    {
+        Personality {
+            name: name.into(),
+            quirks: Vec::new(),
+        }
+    }
+
+    /// Returns the quirks of this personality.
+    pub fn quirks(&self) -> &[Quirk] {
+        &self.quirks
+    }
+
+    /// Adds the given quirk to this personality.
+    pub fn set_quirk(&mut self, quirk: Quirk) -> &mut Self {
+        if !self.has_quirk(quirk) {
+            self.quirks.push(quirk);
+        }
+
+        self
+    }
+
+    /// Adds the given quirk to this personality.
+    pub fn with_quirk(mut self, quirk: Quirk) -> Self {
+        self.set_quirk(quirk);
+        self
+    }
+}
+
+impl From<Quirk> for Personality {
+    fn from(value: Quirk) -> Self {
+        Personality::new(format!("{:?}", value)).with_quirk(value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{convert::TryFrom, str, time::Duration};
+
+    use bytes::BytesMut;
+    use tokio::runtime::current_thread::Runtime;
+
+    use crate::{
+        header::{map::HeaderMapExtension, name::HeaderName, types::Session, value::HeaderValue},
+        media::sdp::SessionDescription,
+        method::Method,
+        protocol::{
+            codec::decoder::Strictness,
+            connection::{Config, Connection},
+            service::EmptyService,
+        },
+        request::Request,
+        response::Response,
+        status::StatusCode,
+        testing::{
+            mock::{Expectation, MockServer},
+            quirks::{Personality, Quirk},
+        },
+        uri::request::URI,
+    };
+
+    const SDP: &str = "v=0\r\n\
+                       o=- 1 1 IN IP4 192.168.1.64\r\n\
+                       s=Session streamed by camera\r\n\
+                       t=0 0\r\n\
+                       m=video 0 RTP/AVP 96\r\n\
+                       a=rtpmap:96 H264/90000\r\n\
+                       a=control:trackID=1\r\n";
+
+    fn describe_response() -> Response<BytesMut> {
+        Response::<()>::builder()
+            .with_status_code(StatusCode::OK)
+            .with_header(
+                HeaderName::Session,
+                HeaderValue::try_from("QKyjN8nt2WqbWw4tIYof52").unwrap(),
+            )
+            .with_body(BytesMut::from(SDP.as_bytes()))
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_personality_encode() {
+        let personality = Personality::minimal_embedded_server();
+        let encoded = personality.encode(describe_response());
+
+        assert!(str::from_utf8(&encoded).unwrap().starts_with(
+            "RTSP/1.0 200 OK\ncontent-length: 138\nsession: QKyjN8nt2WqbWw4tIYof52\n\nv=0\r\n"
+        ));
+
+        let encoded = Personality::legacy_camera().encode(describe_response());
+        let encoded = str::from_utf8(&encoded).unwrap();
+
+        assert!(encoded.contains("\r\nSession: QKyjN8nt2WqbWw4tIYof52;\r\n"));
+        assert!(encoded.ends_with("\na=control:trackID=1"));
+    }
+
+    #[test]
+    fn test_personality_library_tolerance() {
+        let strictnesses = [Strictness::default(), Strictness::lenient()];
+        let configs = [false, true].into_iter().flat_map(|rtsp_1_0_allowed| {
+            strictnesses.map(|strictness| (rtsp_1_0_allowed, strictness))
+        });
+
+        for (rtsp_1_0_allowed, strictness) in configs {
+            for personality in Personality::library() {
+                let config = Config::builder()
+                    .with_request_timeout_default_duration(Some(Duration::from_millis(200)))
+                    .with_rtsp_1_0_allowed(rtsp_1_0_allowed)
+                    .with_strictness(strictness)
+                    .build();
+                let tolerated = personality.is_tolerated_by(&config);
+                let (transport, _) = MockServer::new()
+                    .with_personality(personality.clone())
+                    .with_expectation(
+                        Expectation::new(Method::Describe).with_response(describe_response()),
+                    )
+                    .duplex();
+
+                let mut runtime = Runtime::new().unwrap();
+                let (connection, _, mut handle) =
+                    Connection::with_config::<EmptyService>(transport, None, config);
+                runtime.spawn(connection);
+
+                let request = Request::<()>::builder()
+                    .with_method(Method::Describe)
+                    .with_uri(URI::try_from("rtsp://192.168.1.64/").unwrap())
+                    .with_body(BytesMut::new())
+                    .build()
+                    .unwrap();
+                let result = runtime.block_on(handle.send_request(request));
+
+                assert_eq!(result.is_ok(), tolerated, "{}", personality.name());
+
+                if let Ok(response) = result {
+                    let body = str::from_utf8(response.body()).unwrap();
+                    assert!(SessionDescription::try_from(body).is_ok());
+                    assert_eq!(
+                        response.headers().typed_try_get::<Session>().is_err(),
+                        personality.has_quirk(Quirk::SessionTrailingSemicolon)
+                    );
+                }
+            }
+        }
+    }
+}
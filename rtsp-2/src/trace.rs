@@ -0,0 +1,16 @@
+//! Tracing
+//!
+//! Instrumentation of the connection stack is only compiled in when the `"tracing"` feature is
+//! enabled. The macros here forward to [`tracing`](https://docs.rs/tracing) in that case and expand
+//! to nothing otherwise, so none of their arguments are evaluated when the feature is disabled.
+
+/// Emits an event at the given level, such as `DEBUG` or `WARN`, taking the same arguments as
+/// `tracing::event!` otherwise.
+macro_rules! event {
+    ($level:ident, $($argument:tt)+) => {{
+        #[cfg(feature = "tracing")]
+        {
+            ::tracing::event!(::tracing::Level::$level, $($argument)+);
+        }
+    }};
+}
@@ -1365,6 +1365,10 @@ impl Builder {
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 #[non_exhaustive]
 pub enum URIError {
+    /// There was no authority, but the path started with `"//"`, which would be interpreted as an
+    /// authority.
+    AbsolutePathStartsWithTwoSlashes,
+
     /// The authority was invalid.
     Authority(AuthorityError),
 
@@ -1398,6 +1402,10 @@ pub enum URIError {
 
     /// The scheme was invalid.
     Scheme(SchemeError),
+
+    /// There was no scheme, but the first path segment contained a colon, which would be
+    /// interpreted as a scheme.
+    SchemelessPathStartsWithColonSegment,
 }
 
 impl Display for URIError {
@@ -1405,6 +1413,12 @@ impl Display for URIError {
         use self::URIError::*;
 
         match self {
+            AbsolutePathStartsWithTwoSlashes => {
+                write!(
+                    formatter,
+                    "URI path starts with two slashes without authority"
+                )
+            }
             Authority(error) => error.fmt(formatter),
             EmptyHost => write!(formatter, "empty URI host"),
             FragmentNotAllowed => write!(formatter, "fragment not allowed in request URI"),
@@ -1416,6 +1430,12 @@ impl Display for URIError {
             Path(error) => error.fmt(formatter),
             Query(error) => error.fmt(formatter),
             Scheme(error) => error.fmt(formatter),
+            SchemelessPathStartsWithColonSegment => {
+                write!(
+                    formatter,
+                    "URI path starts with colon segment without scheme"
+                )
+            }
         }
     }
 }
@@ -1435,12 +1455,18 @@ impl TryFrom<URIReferenceError> for URIError {
         use self::URIError::*;
 
         match value {
+            URIReferenceError::AbsolutePathStartsWithTwoSlashes => {
+                Ok(AbsolutePathStartsWithTwoSlashes)
+            }
             URIReferenceError::Authority(error) => Ok(Authority(error)),
             URIReferenceError::Fragment(_) => Ok(FragmentNotAllowed),
             URIReferenceError::MissingPath => Ok(MissingPath),
             URIReferenceError::Path(error) => Ok(Path(error)),
             URIReferenceError::Query(error) => Ok(Query(error)),
             URIReferenceError::Scheme(error) => Ok(Scheme(error)),
+            URIReferenceError::SchemelessPathStartsWithColonSegment => {
+                Ok(SchemelessPathStartsWithColonSegment)
+            }
             _ => Err(()),
         }
     }
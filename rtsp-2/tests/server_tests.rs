@@ -0,0 +1,418 @@
+use std::{
+    convert::TryFrom,
+    io::{self, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::mpsc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use bytes::BytesMut;
+use futures::{sync::oneshot, Future};
+use rtsp_2::{
+    client::Client,
+    header::{
+        map::HeaderMapExtension,
+        name::HeaderName,
+        types::{PipelinedRequests, Session},
+        value::HeaderValue,
+    },
+    media::{format::RTPMap, live::LiveStream, Presentation},
+    method::Method,
+    middleware::auth::AuthenticationLayer,
+    protocol::connection::Config as ConnectionConfig,
+    request::Request,
+    server::{Listener, Server},
+    status::StatusCode,
+    uri::request::URI,
+};
+use tokio::runtime::Runtime;
+
+fn options_request() -> Request<BytesMut> {
+    Request::<()>::builder()
+        .with_method(Method::Options)
+        .with_uri(URI::asterisk())
+        .with_body(BytesMut::new())
+        .build()
+        .unwrap()
+}
+
+fn request(method: Method, uri: &str, transport: Option<&str>) -> Request<BytesMut> {
+    let mut builder = Request::<()>::builder()
+        .with_method(method)
+        .with_uri(URI::try_from(uri).unwrap());
+
+    if let Some(transport) = transport {
+        builder = builder.with_header(
+            HeaderName::Transport,
+            HeaderValue::try_from(transport).unwrap(),
+        );
+    }
+
+    builder.with_body(BytesMut::new()).build().unwrap()
+}
+
+/// Waits until a server accepts connections on the given address, which it starts doing on
+/// another thread.
+fn wait_until_listening(address: SocketAddr) {
+    let deadline = Instant::now() + Duration::from_secs(5);
+
+    while TcpStream::connect(address).is_err() {
+        assert!(
+            Instant::now() < deadline,
+            "the server did not start listening"
+        );
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+/// Returns a loopback address that is not bound.
+fn unused_address() -> SocketAddr {
+    TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+}
+
+#[test]
+fn test_server_bind_failure() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let result = Server::new().serve_until(
+        vec![Listener::new(address)],
+        futures::future::empty::<(), ()>(),
+    );
+
+    assert_eq!(result.unwrap_err().kind(), io::ErrorKind::AddrInUse);
+}
+
+#[test]
+fn test_server_graceful_shutdown() {
+    let address = unused_address();
+    let (tx_shutdown, rx_shutdown) = oneshot::channel::<()>();
+    let (tx_stopped, rx_stopped) = mpsc::channel();
+
+    thread::spawn(move || {
+        let result = Server::new()
+            .with_authentication(AuthenticationLayer::new("test").with_user("admin", "secret"))
+            .with_shutdown_timeout(Duration::from_millis(100))
+            .serve_until(vec![Listener::new(address)], rx_shutdown);
+        tx_stopped.send(result.is_ok()).unwrap();
+    });
+    wait_until_listening(address);
+
+    let mut runtime = Runtime::new().unwrap();
+    let mut client = runtime.block_on(Client::connect(address)).unwrap();
+    let response = runtime
+        .block_on(client.send_request(options_request()))
+        .unwrap();
+
+    assert_eq!(response.status_code(), StatusCode::Unauthorized);
+    assert!(response
+        .headers()
+        .get(&HeaderName::WWWAuthenticate)
+        .unwrap()
+        .as_str()
+        .starts_with("Digest realm=\"test\""));
+
+    tx_shutdown.send(()).unwrap();
+    assert!(rx_stopped.recv_timeout(Duration::from_secs(5)).unwrap());
+    assert!(runtime
+        .block_on(client.send_request(options_request()))
+        .is_err());
+    runtime.shutdown_now().wait().unwrap();
+}
+
+#[test]
+fn test_server_http_tunneling() {
+    let address = unused_address();
+    let (tx_shutdown, rx_shutdown) = oneshot::channel::<()>();
+    let (tx_stopped, rx_stopped) = mpsc::channel();
+
+    thread::spawn(move || {
+        let listener = Listener::new(address).with_http_tunneling_allowed(true);
+        let result = Server::new()
+            .with_shutdown_timeout(Duration::from_millis(100))
+            .serve_until(vec![listener], rx_shutdown);
+        tx_stopped.send(result.is_ok()).unwrap();
+    });
+    wait_until_listening(address);
+
+    let mut runtime = Runtime::new().unwrap();
+    let mut tunneled_client = runtime
+        .block_on(Client::connect_tunneled(address, "/"))
+        .unwrap();
+    let response = runtime
+        .block_on(tunneled_client.send_request(options_request()))
+        .unwrap();
+    assert_eq!(response.status_code(), StatusCode::OK);
+
+    // Connections carrying RTSP directly are still accepted on the same address.
+    let mut client = runtime.block_on(Client::connect(address)).unwrap();
+    let response = runtime
+        .block_on(client.send_request(options_request()))
+        .unwrap();
+    assert_eq!(response.status_code(), StatusCode::OK);
+
+    tx_shutdown.send(()).unwrap();
+    assert!(rx_stopped.recv_timeout(Duration::from_secs(5)).unwrap());
+    assert!(runtime
+        .block_on(tunneled_client.send_request(options_request()))
+        .is_err());
+    assert!(runtime
+        .block_on(client.send_request(options_request()))
+        .is_err());
+    runtime.shutdown_now().wait().unwrap();
+}
+
+#[test]
+fn test_server_websocket() {
+    let address = unused_address();
+    let (tx_shutdown, rx_shutdown) = oneshot::channel::<()>();
+    let (tx_stopped, rx_stopped) = mpsc::channel();
+
+    thread::spawn(move || {
+        let listener = Listener::new(address).with_websocket_allowed(true);
+        let result = Server::new()
+            .with_shutdown_timeout(Duration::from_millis(100))
+            .serve_until(vec![listener], rx_shutdown);
+        tx_stopped.send(result.is_ok()).unwrap();
+    });
+    wait_until_listening(address);
+
+    let mut runtime = Runtime::new().unwrap();
+    let mut websocket_client = runtime
+        .block_on(Client::connect_websocket(address, "/"))
+        .unwrap();
+    let response = runtime
+        .block_on(websocket_client.send_request(options_request()))
+        .unwrap();
+    assert_eq!(response.status_code(), StatusCode::OK);
+
+    // Connections carrying RTSP directly are still accepted on the same address.
+    let mut client = runtime.block_on(Client::connect(address)).unwrap();
+    let response = runtime
+        .block_on(client.send_request(options_request()))
+        .unwrap();
+    assert_eq!(response.status_code(), StatusCode::OK);
+
+    tx_shutdown.send(()).unwrap();
+    assert!(rx_stopped.recv_timeout(Duration::from_secs(5)).unwrap());
+    assert!(runtime
+        .block_on(websocket_client.send_request(options_request()))
+        .is_err());
+    assert!(runtime
+        .block_on(client.send_request(options_request()))
+        .is_err());
+    runtime.shutdown_now().wait().unwrap();
+}
+
+#[test]
+fn test_server_pipelined_requests() {
+    let address = unused_address();
+    let (tx_shutdown, rx_shutdown) = oneshot::channel::<()>();
+    let (tx_stopped, rx_stopped) = mpsc::channel();
+
+    thread::spawn(move || {
+        let (video, _tx_video) = LiveStream::new(RTPMap::new(96, "H264", 90000, None), None);
+        let (audio, _tx_audio) = LiveStream::new(RTPMap::new(97, "H264", 90000, None), None);
+        let presentation = Presentation::new()
+            .with_stream("video", video)
+            .with_stream("audio", audio);
+        let result = Server::new()
+            .with_presentation("live", presentation)
+            .with_shutdown_timeout(Duration::from_millis(100))
+            .serve_until(vec![Listener::new(address)], rx_shutdown);
+        tx_stopped.send(result.is_ok()).unwrap();
+    });
+    wait_until_listening(address);
+
+    let base = format!("rtsp://{}/live", address);
+    let mut runtime = Runtime::new().unwrap();
+    let mut client = runtime.block_on(Client::connect(address)).unwrap();
+    let responses = runtime
+        .block_on(client.send_pipeline(vec![
+            request(
+                Method::Setup,
+                &format!("{}/video", base),
+                Some("RTP/AVP/TCP;unicast;interleaved=0-1"),
+            ),
+            request(
+                Method::Setup,
+                &format!("{}/audio", base),
+                Some("RTP/AVP/TCP;unicast;interleaved=2-3"),
+            ),
+            request(Method::Play, &base, None),
+        ]))
+        .unwrap();
+
+    // Every request was executed in the session created by the first one.
+    let sessions = responses
+        .iter()
+        .map(|response| {
+            assert_eq!(response.status_code(), StatusCode::OK);
+            response.headers().typed_get::<Session>()
+        })
+        .collect::<Vec<_>>();
+    let session = sessions[0].as_ref().unwrap();
+    assert_eq!(sessions[1].as_ref().unwrap().id(), session.id());
+
+    let pipeline = responses[0].headers().typed_get::<PipelinedRequests>();
+    assert!(pipeline.is_some());
+    assert!(responses
+        .iter()
+        .all(|response| response.headers().typed_get::<PipelinedRequests>() == pipeline));
+
+    tx_shutdown.send(()).unwrap();
+    assert!(rx_stopped.recv_timeout(Duration::from_secs(5)).unwrap());
+    assert!(runtime
+        .block_on(client.send_request(options_request()))
+        .is_err());
+    runtime.shutdown_now().wait().unwrap();
+}
+
+#[test]
+fn test_server_listeners_with_policies() {
+    let public_address = unused_address();
+    let admin_address = unused_address();
+    let (tx_shutdown, rx_shutdown) = oneshot::channel::<()>();
+    let (tx_stopped, rx_stopped) = mpsc::channel();
+
+    // The server runs on a runtime driven by the caller.
+    let serving = Server::new()
+        .with_authentication(AuthenticationLayer::new("test").with_user("admin", "secret"))
+        .with_shutdown_timeout(Duration::from_millis(100))
+        .with_listener(Listener::new(public_address))
+        .with_listener(
+            Listener::new(admin_address)
+                .with_authentication(None)
+                .with_connection_config(ConnectionConfig::builder().build()),
+        )
+        .listen(rx_shutdown)
+        .unwrap();
+    let mut runtime = Runtime::new().unwrap();
+    runtime.spawn(serving.then(move |result| {
+        tx_stopped.send(result.is_ok()).unwrap();
+        Ok(())
+    }));
+
+    let mut public_client = runtime.block_on(Client::connect(public_address)).unwrap();
+    let response = runtime
+        .block_on(public_client.send_request(options_request()))
+        .unwrap();
+    assert_eq!(response.status_code(), StatusCode::Unauthorized);
+
+    let mut admin_client = runtime.block_on(Client::connect(admin_address)).unwrap();
+    let response = runtime
+        .block_on(admin_client.send_request(options_request()))
+        .unwrap();
+    assert_eq!(response.status_code(), StatusCode::OK);
+
+    // Publishers using RTSP/1.0 are accepted even though the listener has its own configuration.
+    let mut socket = TcpStream::connect(admin_address).unwrap();
+    socket
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    socket
+        .write_all(b"OPTIONS * RTSP/1.0\r\nCSeq: 1\r\n\r\n")
+        .unwrap();
+    let mut status_line = [0; 15];
+    socket.read_exact(&mut status_line).unwrap();
+    assert_eq!(&status_line, b"RTSP/1.0 200 OK");
+
+    tx_shutdown.send(()).unwrap();
+    assert!(rx_stopped.recv_timeout(Duration::from_secs(5)).unwrap());
+    assert!(runtime
+        .block_on(admin_client.send_request(options_request()))
+        .is_err());
+
+    drop(public_client);
+    drop(admin_client);
+    runtime.shutdown_now().wait().unwrap();
+}
+
+#[test]
+fn test_server_recording_ends_when_publisher_disconnects() {
+    let address = unused_address();
+    let (tx_shutdown, rx_shutdown) = oneshot::channel::<()>();
+    let (tx_stopped, rx_stopped) = mpsc::channel();
+
+    thread::spawn(move || {
+        let result = Server::new()
+            .with_shutdown_timeout(Duration::from_millis(100))
+            .serve_until(vec![Listener::new(address)], rx_shutdown);
+        tx_stopped.send(result.is_ok()).unwrap();
+    });
+    wait_until_listening(address);
+
+    let base = format!("rtsp://{}/published", address);
+    let mut runtime = Runtime::new().unwrap();
+    let mut publisher = runtime.block_on(Client::connect(address)).unwrap();
+    let announce = Request::<()>::builder()
+        .with_method(Method::try_from("ANNOUNCE").unwrap())
+        .with_uri(URI::try_from(base.as_str()).unwrap())
+        .with_header(
+            HeaderName::ContentType,
+            HeaderValue::try_from("application/sdp").unwrap(),
+        )
+        .with_body(BytesMut::from(
+            "v=0\r\n\
+             o=- 1 1 IN IP4 127.0.0.1\r\n\
+             s=Published\r\n\
+             t=0 0\r\n\
+             m=video 0 RTP/AVP 96\r\n\
+             a=rtpmap:96 H264/90000\r\n\
+             a=fmtp:96 packetization-mode=1\r\n\
+             a=control:video\r\n",
+        ))
+        .build()
+        .unwrap();
+    let response = runtime.block_on(publisher.send_request(announce)).unwrap();
+    assert_eq!(response.status_code(), StatusCode::OK);
+
+    let response = runtime
+        .block_on(publisher.send_request(request(
+            Method::Setup,
+            &format!("{}/video", base),
+            Some("RTP/AVP/TCP;unicast;interleaved=0-1;mode=record"),
+        )))
+        .unwrap();
+    assert_eq!(response.status_code(), StatusCode::OK);
+
+    let session = response.headers().typed_get::<Session>().unwrap();
+    let mut record = request(Method::try_from("RECORD").unwrap(), &base, None);
+    record.headers_mut().typed_insert(session);
+    let response = runtime.block_on(publisher.send_request(record)).unwrap();
+    assert_eq!(response.status_code(), StatusCode::OK);
+
+    let mut viewer = runtime.block_on(Client::connect(address)).unwrap();
+    let response = runtime
+        .block_on(viewer.send_request(request(Method::Describe, &base, None)))
+        .unwrap();
+    assert_eq!(response.status_code(), StatusCode::OK);
+
+    // Closing the connection of the publisher ends the recording without waiting for its session
+    // to expire.
+    drop(publisher);
+    let deadline = Instant::now() + Duration::from_secs(5);
+
+    loop {
+        let response = runtime
+            .block_on(viewer.send_request(request(Method::Describe, &base, None)))
+            .unwrap();
+
+        if response.status_code() == StatusCode::NotFound {
+            break;
+        }
+
+        assert_eq!(response.status_code(), StatusCode::OK);
+        assert!(Instant::now() < deadline, "the recording did not end");
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    tx_shutdown.send(()).unwrap();
+    assert!(rx_stopped.recv_timeout(Duration::from_secs(5)).unwrap());
+
+    drop(viewer);
+    runtime.shutdown_now().wait().unwrap();
+}
@@ -0,0 +1,88 @@
+//! RTSP Probe
+//!
+//! Connects to an RTSP server and reports what it supports and how it describes a presentation,
+//! optionally setting up and playing its streams to gather statistics of the packets received.
+//!
+//! ```text
+//! rtsp-probe [--json] [--setup] [--play <SECONDS>] <URI>
+//! ```
+
+mod probe;
+mod report;
+mod statistics;
+
+use std::{convert::TryFrom, process, time::Duration};
+
+use clap::{App, Arg};
+use rtsp_2::uri::request::URI;
+use tokio::runtime::Runtime;
+
+use crate::probe::{probe, Options};
+
+fn main() {
+    let matches = App::new("rtsp-probe")
+        .version(env!("CARGO_PKG_VERSION"))
+        .about("Probes an RTSP server for troubleshooting")
+        .arg(
+            Arg::with_name("json")
+                .long("json")
+                .help("Prints the report as JSON"),
+        )
+        .arg(
+            Arg::with_name("play")
+                .long("play")
+                .value_name("SECONDS")
+                .help("Sets up and plays the streams for the given number of seconds")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("setup")
+                .long("setup")
+                .help("Sets up the streams to report the transports selected by the server"),
+        )
+        .arg(
+            Arg::with_name("URI")
+                .help("The URI of the presentation to probe")
+                .required(true),
+        )
+        .get_matches();
+
+    let uri = match URI::try_from(matches.value_of("URI").unwrap()) {
+        Ok(uri) => uri,
+        Err(error) => exit(&format!("invalid URI: {:?}", error)),
+    };
+    let play = match matches.value_of("play").map(str::parse::<f64>) {
+        Some(Ok(seconds)) if seconds.is_finite() && seconds >= 0.0 => {
+            Some(Duration::from_secs_f64(seconds))
+        }
+        Some(_) => exit("invalid number of seconds to play for"),
+        None => None,
+    };
+    let options = Options {
+        play,
+        setup: matches.is_present("setup"),
+    };
+
+    let mut runtime = Runtime::new().expect("failed to create runtime");
+    let result = runtime.block_on(probe(uri, options));
+    let (report, error) = match result {
+        Ok(report) => (report, None),
+        Err((report, error)) => (report, Some(error)),
+    };
+
+    if matches.is_present("json") {
+        println!("{}", serde_json::to_string_pretty(&report).unwrap());
+    } else {
+        print!("{}", report);
+    }
+
+    if let Some(error) = error {
+        exit(&error.to_string());
+    }
+}
+
+/// Prints the error message and exits unsuccessfully.
+fn exit(message: &str) -> ! {
+    eprintln!("rtsp-probe: {}", message);
+    process::exit(1);
+}
@@ -0,0 +1,533 @@
+//! Probe
+//!
+//! Probes a presentation by sending OPTIONS and DESCRIBE requests and, if asked to, setting up its
+//! streams interleaved in the RTSP connection and playing them for a while to gather statistics of
+//! the packets received.
+
+use std::{
+    convert::TryFrom,
+    error::Error,
+    fmt::{self, Display, Formatter},
+    io,
+    net::{SocketAddr, ToSocketAddrs},
+    str,
+    time::{Duration, Instant},
+};
+
+use bytes::BytesMut;
+use futures::{
+    future::{self, Either},
+    stream,
+    sync::mpsc::UnboundedReceiver,
+    Future, Stream,
+};
+use rtsp_2::{
+    client::Client,
+    header::{
+        map::HeaderMapExtension,
+        name::HeaderName,
+        types::{Public, Session},
+        value::HeaderValue,
+    },
+    media::sdp::{SessionDescription, SDP_CONTENT_TYPE},
+    method::Method,
+    protocol::{codec::interleaved::InterleavedData, connection::OperationError},
+    request::Request,
+    response::Response,
+    status::StatusCode,
+    uri::{request::URI, RTSP_DEFAULT_PORT},
+};
+use tokio::timer::Delay;
+
+use crate::{report::Report, statistics::StreamStatistics};
+
+/// The feature tags listed in the `"Supported"` header of the OPTIONS request.
+pub const SUPPORTED_FEATURES: &str = "play.basic";
+
+/// What to do after describing the presentation.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Options {
+    /// How long to play the streams for, if at all.
+    pub play: Option<Duration>,
+
+    /// Whether to set up the streams to learn the transports the server selects.
+    pub setup: bool,
+}
+
+/// Probes the presentation at the given URI.
+///
+/// The returned report contains as much as was learned before a request failed, since for
+/// troubleshooting a partial report is more useful than none.
+#[allow(clippy::result_large_err)]
+pub fn probe(
+    uri: URI,
+    options: Options,
+) -> impl Future<Item = Report, Error = (Report, ProbeError)> + Send {
+    let report = Report::new(uri.to_string());
+
+    future::result(socket_address(&uri))
+        .and_then(|address| Client::connect(address).map_err(ProbeError::from))
+        .then(move |result| match result {
+            Ok(client) => Ok((client, report)),
+            Err(error) => Err((report, error)),
+        })
+        .and_then(move |(client, report)| options_and_describe(client, report, uri))
+        .and_then(move |(client, report, session)| {
+            if options.setup || options.play.is_some() {
+                Either::A(setup_and_play(client, report, session, options.play))
+            } else {
+                Either::B(future::ok(report))
+            }
+        })
+}
+
+/// Sends the OPTIONS and DESCRIBE requests, adding what the server responded with to the report.
+#[allow(clippy::result_large_err)]
+fn options_and_describe(
+    mut client: Client,
+    mut report: Report,
+    uri: URI,
+) -> impl Future<Item = (Client, Report, URI), Error = (Report, ProbeError)> + Send {
+    let request = Request::<()>::builder()
+        .with_method(Method::Options)
+        .with_uri(uri.clone())
+        .with_header(
+            HeaderName::Supported,
+            HeaderValue::try_from(SUPPORTED_FEATURES).unwrap(),
+        )
+        .with_body(BytesMut::new())
+        .build()
+        .unwrap();
+
+    send(&mut client, Method::Options, request).then(move |result| {
+        let response = match result {
+            Ok(response) => response,
+            Err(error) => return Either::A(future::err((report, error))),
+        };
+
+        report.public = response
+            .headers()
+            .typed_get::<Public>()
+            .map(|public| public.iter().map(|method| method.to_string()).collect())
+            .unwrap_or_default();
+        report.server = response
+            .headers()
+            .get(&HeaderName::Server)
+            .map(|server| server.as_str().to_string());
+        report.supported = response
+            .headers()
+            .get_all(&HeaderName::Supported)
+            .flat_map(|value| value.as_str().split(','))
+            .map(|feature| feature.trim().to_string())
+            .filter(|feature| !feature.is_empty())
+            .collect();
+
+        let request = Request::<()>::builder()
+            .with_method(Method::Describe)
+            .with_uri(uri.clone())
+            .with_header(
+                HeaderName::Accept,
+                HeaderValue::try_from(SDP_CONTENT_TYPE).unwrap(),
+            )
+            .with_body(BytesMut::new())
+            .build()
+            .unwrap();
+
+        Either::B(
+            send(&mut client, Method::Describe, request).then(move |result| {
+                let response = match result {
+                    Ok(response) => response,
+                    Err(error) => return Err((report, error)),
+                };
+                let description = match str::from_utf8(response.body())
+                    .ok()
+                    .and_then(|body| SessionDescription::try_from(body).ok())
+                {
+                    Some(description) => description,
+                    None => return Err((report, ProbeError::InvalidDescription)),
+                };
+                let base = response
+                    .headers()
+                    .get(&HeaderName::ContentBase)
+                    .map(|base| base.as_str().to_string())
+                    .unwrap_or_else(|| uri.to_string());
+                let uris = description
+                    .media()
+                    .iter()
+                    .map(|media| control_uri(&base, media.control()))
+                    .collect();
+                report.set_description(&description, uris);
+                Ok((client, report, uri))
+            }),
+        )
+    })
+}
+
+/// Sets up every stream interleaved in the connection, then plays them for the given duration
+/// while gathering statistics of the packets received and tears the session down.
+#[allow(clippy::result_large_err)]
+fn setup_and_play(
+    mut client: Client,
+    report: Report,
+    uri: URI,
+    play: Option<Duration>,
+) -> impl Future<Item = Report, Error = (Report, ProbeError)> + Send {
+    // Each stream is received on its own pair of interleaved channels, the first carrying RTP and
+    // the second RTCP.
+    let interleaved_data = client.interleaved_data();
+    let setups = (0..report.tracks.len()).collect::<Vec<_>>();
+
+    stream::iter_ok(setups)
+        .fold(
+            (client, report, None),
+            |(mut client, mut report, session), index| {
+                let channel = index * 2;
+                let transport = format!(
+                    "RTP/AVP/TCP;unicast;interleaved={}-{}",
+                    channel,
+                    channel + 1
+                );
+                let track_uri = match URI::try_from(report.tracks[index].uri.as_str()) {
+                    Ok(track_uri) => track_uri,
+                    Err(_) => {
+                        return Either::A(future::err((report, ProbeError::InvalidDescription)))
+                    }
+                };
+                let mut builder = Request::<()>::builder()
+                    .with_method(Method::Setup)
+                    .with_uri(track_uri)
+                    .with_header(
+                        HeaderName::Transport,
+                        HeaderValue::try_from(transport.as_str()).unwrap(),
+                    );
+
+                if let Some(session) = session.as_ref().map(session_without_timeout) {
+                    builder = builder.with_typed_header(session);
+                }
+
+                let request = builder.with_body(BytesMut::new()).build().unwrap();
+                Either::B(
+                    send(&mut client, Method::Setup, request).then(move |result| {
+                        let response = match result {
+                            Ok(response) => response,
+                            Err(error) => return Err((report, error)),
+                        };
+                        report.tracks[index].transport = response
+                            .headers()
+                            .get(&HeaderName::Transport)
+                            .map(|transport| transport.as_str().to_string());
+                        let session = response.headers().typed_get::<Session>().or(session);
+                        Ok((client, report, session))
+                    }),
+                )
+            },
+        )
+        .and_then(move |(client, report, session)| {
+            let session = match session {
+                Some(session) => session_without_timeout(&session),
+                None if report.tracks.is_empty() => return Either::A(future::ok(report)),
+                None => return Either::A(future::err((report, ProbeError::MissingSession))),
+            };
+
+            match play {
+                Some(duration) => Either::B(Either::A(
+                    receive(client, report, uri, session, duration, interleaved_data).and_then(
+                        |(client, report, uri, session)| teardown(client, report, uri, session),
+                    ),
+                )),
+                None => Either::B(Either::B(teardown(client, report, uri, session))),
+            }
+        })
+}
+
+/// Plays the session for the given duration, adding statistics of the interleaved data received to
+/// the report.
+#[allow(clippy::result_large_err)]
+fn receive(
+    mut client: Client,
+    mut report: Report,
+    uri: URI,
+    session: Session,
+    duration: Duration,
+    interleaved_data: UnboundedReceiver<InterleavedData>,
+) -> impl Future<Item = (Client, Report, URI, Session), Error = (Report, ProbeError)> + Send {
+    let request = Request::<()>::builder()
+        .with_method(Method::Play)
+        .with_uri(uri.clone())
+        .with_typed_header(session.clone())
+        .with_body(BytesMut::new())
+        .build()
+        .unwrap();
+
+    send(&mut client, Method::Play, request).then(move |result| {
+        if let Err(error) = result {
+            return Either::A(future::err((report, error)));
+        }
+
+        let statistics = report
+            .tracks
+            .iter()
+            .map(|track| StreamStatistics::new(track.clock_rate))
+            .collect::<Vec<_>>();
+        let stop = Delay::new(Instant::now() + duration)
+            .then(|_| Ok(None))
+            .into_stream();
+
+        // Receiving ends after the given duration or once the connection closes.
+        Either::B(
+            interleaved_data
+                .map(Some)
+                .select(stop)
+                .take_while(|data| Ok(data.is_some()))
+                .filter_map(|data| data)
+                .fold(statistics, |mut statistics, data| {
+                    let index = usize::from(data.channel() / 2);
+
+                    if let Some(statistics) = statistics.get_mut(index) {
+                        if data.channel() % 2 == 0 {
+                            statistics.rtp.receive(data.into_payload(), Instant::now());
+                        } else {
+                            statistics.rtcp.receive(data.payload());
+                        }
+                    }
+
+                    Ok(statistics)
+                })
+                .then(move |result| {
+                    if let Ok(statistics) = result {
+                        for (track, statistics) in report.tracks.iter_mut().zip(statistics) {
+                            track.statistics = Some(statistics);
+                        }
+                    }
+
+                    Ok((client, report, uri, session))
+                }),
+        )
+    })
+}
+
+/// Tears the session down. The report is complete at this point, so failures are ignored.
+#[allow(clippy::result_large_err)]
+fn teardown(
+    mut client: Client,
+    report: Report,
+    uri: URI,
+    session: Session,
+) -> impl Future<Item = Report, Error = (Report, ProbeError)> + Send {
+    let request = Request::<()>::builder()
+        .with_method(Method::Teardown)
+        .with_uri(uri)
+        .with_typed_header(session)
+        .with_body(BytesMut::new())
+        .build()
+        .unwrap();
+
+    client.send_request(request).then(move |_| Ok(report))
+}
+
+/// A possible error value when probing a presentation.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ProbeError {
+    /// Connecting to the server failed.
+    Connect(io::Error),
+
+    /// The session description could not be parsed, or one of its stream controls could not be
+    /// resolved to a URI.
+    InvalidDescription,
+
+    /// The server did not respond to the SETUP requests with a session.
+    MissingSession,
+
+    /// A request could not be sent or no response was received for it.
+    Operation(Method, OperationError),
+
+    /// A request was responded to with an unsuccessful status code.
+    Status(Method, StatusCode),
+
+    /// The address of the server could not be resolved.
+    UnresolvedHost,
+}
+
+impl Display for ProbeError {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        use self::ProbeError::*;
+
+        match self {
+            Connect(error) => write!(formatter, "failed to connect: {}", error),
+            InvalidDescription => write!(formatter, "invalid session description"),
+            MissingSession => write!(formatter, "no session was set up"),
+            Operation(method, error) => write!(formatter, "{} request failed: {}", method, error),
+            Status(method, status_code) => {
+                write!(
+                    formatter,
+                    "{} request responded with {}",
+                    method, status_code
+                )
+            }
+            UnresolvedHost => write!(formatter, "unresolved host"),
+        }
+    }
+}
+
+impl Error for ProbeError {}
+
+impl From<io::Error> for ProbeError {
+    fn from(value: io::Error) -> Self {
+        ProbeError::Connect(value)
+    }
+}
+
+/// Returns the URI of a stream given the base URI of the presentation and the stream control.
+fn control_uri(base: &str, control: &str) -> String {
+    if control.is_empty() || control == "*" {
+        base.trim_end_matches('/').to_string()
+    } else if URI::try_from(control).is_ok() {
+        control.to_string()
+    } else {
+        format!("{}/{}", base.trim_end_matches('/'), control)
+    }
+}
+
+/// Sends a request, resolving to an error if the response is not successful.
+fn send(
+    client: &mut Client,
+    method: Method,
+    request: Request<BytesMut>,
+) -> impl Future<Item = Response<BytesMut>, Error = ProbeError> + Send {
+    client
+        .send_request(request)
+        .then(move |result| match result {
+            Ok(response) if response.status_code().is_success() => Ok(response),
+            Ok(response) => Err(ProbeError::Status(method, response.status_code())),
+            Err(error) => Err(ProbeError::Operation(method, error)),
+        })
+}
+
+/// Returns the `"Session"` header to send in requests for the given session, which must not
+/// include a timeout.
+fn session_without_timeout(session: &Session) -> Session {
+    Session::without_timeout(session.id().as_str())
+        .expect("received session identifiers should be valid")
+}
+
+/// Resolves the address of the server of the given URI.
+fn socket_address(uri: &URI) -> Result<SocketAddr, ProbeError> {
+    let host = uri.host().ok_or(ProbeError::UnresolvedHost)?;
+    let port = uri.port().unwrap_or(RTSP_DEFAULT_PORT);
+
+    format!("{}:{}", host, port)
+        .to_socket_addrs()
+        .map_err(|_| ProbeError::UnresolvedHost)?
+        .next()
+        .ok_or(ProbeError::UnresolvedHost)
+}
+
+#[cfg(test)]
+mod test {
+    use std::{convert::TryFrom, time::Duration};
+
+    use bytes::BytesMut;
+    use rtsp_2::{
+        header::{name::HeaderName, value::HeaderValue},
+        media::rtp::Packet,
+        method::Method,
+        protocol::codec::interleaved::InterleavedData,
+        response::Response,
+        status::StatusCode,
+        testing::{Expectation, MockServer},
+        uri::request::URI,
+    };
+    use tokio::runtime::Runtime;
+
+    use super::{control_uri, probe, Options};
+
+    const DESCRIPTION: &str = "v=0\r\n\
+                               o=- 1 1 IN IP4 127.0.0.1\r\n\
+                               s=Camera\r\n\
+                               t=0 0\r\n\
+                               m=video 0 RTP/AVP 96\r\n\
+                               a=rtpmap:96 H264/90000\r\n\
+                               a=control:trackID=1\r\n";
+
+    fn response(headers: &[(HeaderName, &str)], body: &str) -> Response<BytesMut> {
+        let mut builder = Response::<()>::builder().with_status_code(StatusCode::OK);
+
+        for (name, value) in headers {
+            builder = builder.with_header(name.clone(), HeaderValue::try_from(*value).unwrap());
+        }
+
+        builder.with_body(BytesMut::from(body)).build().unwrap()
+    }
+
+    #[test]
+    fn test_control_uri() {
+        let base = "rtsp://camera.local/stream/";
+        assert_eq!(
+            control_uri(base, "trackID=1"),
+            "rtsp://camera.local/stream/trackID=1"
+        );
+        assert_eq!(
+            control_uri(base, "rtsp://camera.local/other"),
+            "rtsp://camera.local/other"
+        );
+        assert_eq!(control_uri(base, "*"), "rtsp://camera.local/stream");
+    }
+
+    #[test]
+    fn test_probe() {
+        let mut packet = BytesMut::new();
+        Packet::new(96, 1, 0, 0x1234, false, &b"data"[..]).encode(&mut packet);
+
+        let (address, handle) = MockServer::new()
+            .with_expectation(Expectation::new(Method::Options).with_response(response(
+                &[
+                    (
+                        HeaderName::Public,
+                        "OPTIONS, DESCRIBE, SETUP, PLAY, TEARDOWN",
+                    ),
+                    (HeaderName::Server, "Camera/1.0"),
+                    (HeaderName::Supported, "play.basic, setup.rtp.rtcp.mux"),
+                ],
+                "",
+            )))
+            .with_expectation(Expectation::new(Method::Describe).with_response(response(
+                &[(HeaderName::ContentBase, "rtsp://127.0.0.1/stream/")],
+                DESCRIPTION,
+            )))
+            .with_expectation(Expectation::new(Method::Setup).with_response(response(
+                &[
+                    (HeaderName::Session, "12345678;timeout=30"),
+                    (HeaderName::Transport, "RTP/AVP/TCP;unicast;interleaved=0-1"),
+                ],
+                "",
+            )))
+            .with_expectation(Expectation::new(Method::Play).with_response(response(&[], "")))
+            .with_interleaved_data(InterleavedData::new(0, packet.freeze()))
+            .with_expectation(Expectation::new(Method::Teardown).with_response(response(&[], "")))
+            .listen()
+            .unwrap();
+
+        let uri = URI::try_from(format!("rtsp://{}/stream", address).as_str()).unwrap();
+        let options = Options {
+            play: Some(Duration::from_millis(200)),
+            setup: true,
+        };
+        let mut runtime = Runtime::new().unwrap();
+        let report = runtime.block_on(probe(uri, options)).unwrap();
+
+        assert_eq!(report.server.as_deref(), Some("Camera/1.0"));
+        assert_eq!(report.public.len(), 5);
+        assert_eq!(report.supported, vec!["play.basic", "setup.rtp.rtcp.mux"]);
+        assert_eq!(report.tracks.len(), 1);
+
+        let track = &report.tracks[0];
+        assert_eq!(track.uri, "rtsp://127.0.0.1/stream/trackID=1");
+        assert_eq!(
+            track.transport.as_deref(),
+            Some("RTP/AVP/TCP;unicast;interleaved=0-1")
+        );
+        assert_eq!(track.statistics.as_ref().unwrap().rtp.packets, 1);
+        assert!(handle.verify().is_ok());
+    }
+}
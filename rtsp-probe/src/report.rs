@@ -0,0 +1,306 @@
+//! Report
+//!
+//! Everything learned about a presentation while probing it, printed either as human-readable text
+//! or as JSON.
+
+use std::fmt::{self, Display, Formatter};
+
+use rtsp_2::media::sdp::{MediaDescription, SessionDescription};
+use serde::Serialize;
+
+use crate::statistics::StreamStatistics;
+
+/// Everything learned about a presentation while probing it.
+#[derive(Clone, Debug, Serialize)]
+pub struct Report {
+    /// The presentation as described in response to the DESCRIBE request.
+    pub presentation: Option<PresentationReport>,
+
+    /// The methods the server listed in the `"Public"` header of its OPTIONS response.
+    pub public: Vec<String>,
+
+    /// The product tokens of the server given in the `"Server"` header.
+    pub server: Option<String>,
+
+    /// The feature tags the server listed in `"Supported"` headers.
+    pub supported: Vec<String>,
+
+    /// The streams of the presentation, in the order they were described.
+    pub tracks: Vec<TrackReport>,
+
+    /// The URI of the presentation.
+    pub uri: String,
+}
+
+impl Report {
+    /// Constructs an empty report for the presentation at the given URI.
+    pub fn new<TURI>(uri: TURI) -> Self
+    where
+        TURI: Into<String>,
+    {
+        Report {
+            presentation: None,
+            public: Vec::new(),
+            server: None,
+            supported: Vec::new(),
+            tracks: Vec::new(),
+            uri: uri.into(),
+        }
+    }
+
+    /// Adds the presentation and the streams of the given session description, where stream
+    /// controls resolve to the given URIs.
+    pub fn set_description(&mut self, description: &SessionDescription, uris: Vec<String>) {
+        self.presentation = Some(PresentationReport {
+            duration: description
+                .duration()
+                .map(|duration| duration.as_secs_f64()),
+            name: description.name().to_string(),
+        });
+        self.tracks = description
+            .media()
+            .iter()
+            .zip(uris)
+            .map(|(media, uri)| TrackReport::new(media, uri))
+            .collect();
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        writeln!(formatter, "URI:          {}", self.uri)?;
+
+        if let Some(server) = &self.server {
+            writeln!(formatter, "Server:       {}", server)?;
+        }
+
+        writeln!(formatter, "Public:       {}", list(&self.public))?;
+        writeln!(formatter, "Supported:    {}", list(&self.supported))?;
+
+        if let Some(presentation) = &self.presentation {
+            let name = match presentation.name.as_str() {
+                "" => "(unnamed)",
+                name => name,
+            };
+
+            match presentation.duration {
+                Some(duration) => writeln!(formatter, "Presentation: {} ({:.3}s)", name, duration)?,
+                None => writeln!(formatter, "Presentation: {} (live)", name)?,
+            }
+        }
+
+        for (index, track) in self.tracks.iter().enumerate() {
+            writeln!(formatter)?;
+            write!(formatter, "Track #{}: {}", index, track)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The presentation as described in response to the DESCRIBE request.
+#[derive(Clone, Debug, Serialize)]
+pub struct PresentationReport {
+    /// The duration of the presentation in seconds, or [`Option::None`] for live presentations.
+    pub duration: Option<f64>,
+
+    /// The name of the session.
+    pub name: String,
+}
+
+/// A single stream of the presentation.
+#[derive(Clone, Debug, Serialize)]
+pub struct TrackReport {
+    /// The number of audio channels, if given.
+    pub channels: Option<u16>,
+
+    /// The RTP clock rate in hertz.
+    pub clock_rate: u32,
+
+    /// The control path of the stream as given in the session description.
+    pub control: String,
+
+    /// The encoding name of the payload format, such as `"H264"`.
+    pub encoding_name: String,
+
+    /// The format specific parameters as name and value pairs.
+    pub format_parameters: Vec<(String, String)>,
+
+    /// The media type, such as `"video"`.
+    pub media_type: &'static str,
+
+    /// The RTP payload type.
+    pub payload_type: u8,
+
+    /// The statistics of the packets received while playing the stream.
+    pub statistics: Option<StreamStatistics>,
+
+    /// The transport the server selected in response to the SETUP request.
+    pub transport: Option<String>,
+
+    /// The URI the stream control resolved to.
+    pub uri: String,
+}
+
+impl TrackReport {
+    /// Constructs a report of the stream with the given description, where the stream control
+    /// resolved to the given URI.
+    pub fn new(media: &MediaDescription, uri: String) -> Self {
+        let format = media.format();
+
+        TrackReport {
+            channels: format.channels(),
+            clock_rate: format.clock_rate(),
+            control: media.control().to_string(),
+            encoding_name: format.encoding_name().to_string(),
+            format_parameters: media
+                .format_parameters()
+                .map(|parameters| {
+                    parameters
+                        .iter()
+                        .map(|(name, value)| (name.to_string(), value.to_string()))
+                        .collect()
+                })
+                .unwrap_or_default(),
+            media_type: media.media_type(),
+            payload_type: format.payload_type(),
+            statistics: None,
+            transport: None,
+            uri,
+        }
+    }
+}
+
+impl Display for TrackReport {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "{} {}/{}",
+            self.media_type, self.encoding_name, self.clock_rate
+        )?;
+
+        if let Some(channels) = self.channels {
+            write!(formatter, "/{}", channels)?;
+        }
+
+        writeln!(formatter, " (payload type {})", self.payload_type)?;
+        writeln!(formatter, "  URI:        {}", self.uri)?;
+
+        if !self.format_parameters.is_empty() {
+            let parameters = self
+                .format_parameters
+                .iter()
+                .map(|(name, value)| format!("{}={}", name, value))
+                .collect::<Vec<_>>();
+            writeln!(formatter, "  Parameters: {}", parameters.join("; "))?;
+        }
+
+        if let Some(transport) = &self.transport {
+            writeln!(formatter, "  Transport:  {}", transport)?;
+        }
+
+        if let Some(statistics) = &self.statistics {
+            let rtp = &statistics.rtp;
+            write!(
+                formatter,
+                "  RTP:        {} packets, {} bytes, {} lost, {:.3} ms jitter",
+                rtp.packets, rtp.bytes, rtp.lost, rtp.jitter
+            )?;
+
+            if let Some(ssrc) = rtp.ssrc {
+                write!(formatter, ", SSRC {:08X}", ssrc)?;
+            }
+
+            if rtp.invalid > 0 {
+                write!(formatter, ", {} invalid", rtp.invalid)?;
+            }
+
+            let rtcp = &statistics.rtcp;
+            writeln!(formatter)?;
+            writeln!(
+                formatter,
+                "  RTCP:       {} sender reports, {} receiver reports, {} source descriptions, \
+                 {} goodbyes, {} other, {} invalid",
+                rtcp.sender_reports,
+                rtcp.receiver_reports,
+                rtcp.source_descriptions,
+                rtcp.goodbyes,
+                rtcp.other,
+                rtcp.invalid
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the items separated by commas, or `"(none)"` if there are none.
+fn list(items: &[String]) -> String {
+    if items.is_empty() {
+        "(none)".to_string()
+    } else {
+        items.join(", ")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::convert::TryFrom;
+
+    use rtsp_2::media::sdp::SessionDescription;
+
+    use super::Report;
+
+    const DESCRIPTION: &str = "v=0\r\n\
+                               o=- 1 1 IN IP4 127.0.0.1\r\n\
+                               s=Camera\r\n\
+                               t=0 0\r\n\
+                               m=video 0 RTP/AVP 96\r\n\
+                               a=rtpmap:96 H264/90000\r\n\
+                               a=fmtp:96 packetization-mode=1\r\n\
+                               a=control:trackID=1\r\n";
+
+    #[test]
+    fn test_report_display() {
+        let description = SessionDescription::try_from(DESCRIPTION).unwrap();
+        let mut report = Report::new("rtsp://camera.local/stream");
+        report.public = vec!["DESCRIBE".to_string(), "OPTIONS".to_string()];
+        report.set_description(
+            &description,
+            vec!["rtsp://camera.local/stream/trackID=1".to_string()],
+        );
+        report.tracks[0].transport = Some("RTP/AVP/TCP;unicast;interleaved=0-1".to_string());
+
+        assert_eq!(
+            report.to_string(),
+            "URI:          rtsp://camera.local/stream\n\
+             Public:       DESCRIBE, OPTIONS\n\
+             Supported:    (none)\n\
+             Presentation: Camera (live)\n\
+             \n\
+             Track #0: video H264/90000 (payload type 96)\n  \
+             URI:        rtsp://camera.local/stream/trackID=1\n  \
+             Parameters: packetization-mode=1\n  \
+             Transport:  RTP/AVP/TCP;unicast;interleaved=0-1\n"
+        );
+    }
+
+    #[test]
+    fn test_report_json() {
+        let description = SessionDescription::try_from(DESCRIPTION).unwrap();
+        let mut report = Report::new("rtsp://camera.local/stream");
+        report.set_description(
+            &description,
+            vec!["rtsp://camera.local/stream/trackID=1".to_string()],
+        );
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["presentation"]["name"], "Camera");
+        assert_eq!(json["tracks"][0]["encoding_name"], "H264");
+        assert_eq!(
+            json["tracks"][0]["format_parameters"][0][0],
+            "packetization-mode"
+        );
+        assert!(json["tracks"][0]["statistics"].is_null());
+    }
+}
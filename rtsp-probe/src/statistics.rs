@@ -0,0 +1,284 @@
+//! Statistics
+//!
+//! Keeps the reception statistics of the RTP and RTCP packets received for a stream, computed as
+//! described by [RFC3550, Appendix A](https://tools.ietf.org/html/rfc3550#appendix-A).
+
+use std::time::Instant;
+
+use bytes::Bytes;
+use rtsp_2::media::rtp::Packet;
+use serde::Serialize;
+
+/// The RTCP packet type of sender reports.
+const RTCP_SENDER_REPORT: u8 = 200;
+
+/// The RTCP packet type of receiver reports.
+const RTCP_RECEIVER_REPORT: u8 = 201;
+
+/// The RTCP packet type of source descriptions.
+const RTCP_SOURCE_DESCRIPTION: u8 = 202;
+
+/// The RTCP packet type of goodbye packets.
+const RTCP_GOODBYE: u8 = 203;
+
+/// The statistics of the RTP and RTCP packets received for a single stream.
+#[derive(Clone, Debug, Serialize)]
+pub struct StreamStatistics {
+    /// The statistics of the RTP packets.
+    pub rtp: RTPStatistics,
+
+    /// The statistics of the RTCP packets.
+    pub rtcp: RTCPStatistics,
+}
+
+impl StreamStatistics {
+    /// Constructs new statistics for a stream with the given RTP clock rate.
+    pub fn new(clock_rate: u32) -> Self {
+        StreamStatistics {
+            rtp: RTPStatistics::new(clock_rate),
+            rtcp: RTCPStatistics::default(),
+        }
+    }
+}
+
+/// The statistics of the RTP packets received for a stream.
+#[derive(Clone, Debug, Serialize)]
+pub struct RTPStatistics {
+    /// The total size of the packets received, in bytes.
+    pub bytes: u64,
+
+    /// The number of packets that could not be decoded.
+    pub invalid: u64,
+
+    /// The interarrival jitter, in milliseconds.
+    pub jitter: f64,
+
+    /// The number of packets expected but not received, which is negative if duplicates were
+    /// received.
+    pub lost: i64,
+
+    /// The number of packets received.
+    pub packets: u64,
+
+    /// The synchronization source of the last packet received.
+    pub ssrc: Option<u32>,
+
+    /// The extended sequence number of the first packet received.
+    #[serde(skip)]
+    base_sequence_number: u64,
+
+    /// The RTP clock rate of the stream, in hertz.
+    #[serde(skip)]
+    clock_rate: u32,
+
+    /// The interarrival jitter in timestamp units.
+    #[serde(skip)]
+    jitter_units: f64,
+
+    /// The highest extended sequence number received.
+    #[serde(skip)]
+    max_sequence_number: u64,
+
+    /// When the first packet was received.
+    #[serde(skip)]
+    start: Option<Instant>,
+
+    /// The relative transit time of the last packet, in timestamp units.
+    #[serde(skip)]
+    transit: Option<u32>,
+}
+
+impl RTPStatistics {
+    /// Constructs new statistics for a stream with the given RTP clock rate.
+    pub fn new(clock_rate: u32) -> Self {
+        RTPStatistics {
+            bytes: 0,
+            invalid: 0,
+            jitter: 0.0,
+            lost: 0,
+            packets: 0,
+            ssrc: None,
+            base_sequence_number: 0,
+            clock_rate,
+            jitter_units: 0.0,
+            max_sequence_number: 0,
+            start: None,
+            transit: None,
+        }
+    }
+
+    /// Updates the statistics with a packet that arrived at the given time.
+    pub fn receive(&mut self, packet: Bytes, arrival: Instant) {
+        let length = packet.len() as u64;
+        let packet = match Packet::decode(packet) {
+            Ok(packet) => packet,
+            Err(_) => {
+                self.invalid += 1;
+                return;
+            }
+        };
+
+        self.bytes += length;
+        self.packets += 1;
+        self.ssrc = Some(packet.ssrc());
+        self.update_sequence_number(packet.sequence_number());
+        self.update_jitter(packet.timestamp(), arrival);
+    }
+
+    /// Updates the interarrival jitter as described by
+    /// [RFC3550, Appendix A.8](https://tools.ietf.org/html/rfc3550#appendix-A.8).
+    fn update_jitter(&mut self, timestamp: u32, arrival: Instant) {
+        let start = *self.start.get_or_insert(arrival);
+        let elapsed = arrival.duration_since(start).as_nanos();
+        let arrival = (elapsed * u128::from(self.clock_rate) / 1_000_000_000) as u32;
+        let transit = arrival.wrapping_sub(timestamp);
+
+        if let Some(previous) = self.transit.replace(transit) {
+            let difference = f64::from((transit.wrapping_sub(previous) as i32).unsigned_abs());
+            self.jitter_units += (difference - self.jitter_units) / 16.0;
+        }
+
+        if self.clock_rate > 0 {
+            self.jitter = self.jitter_units * 1000.0 / f64::from(self.clock_rate);
+        }
+    }
+
+    /// Extends the sequence number with the number of times it wrapped around and updates the
+    /// number of packets lost, as described by
+    /// [RFC3550, Appendix A.3](https://tools.ietf.org/html/rfc3550#appendix-A.3).
+    fn update_sequence_number(&mut self, sequence_number: u16) {
+        if self.packets == 1 {
+            self.base_sequence_number = u64::from(sequence_number);
+            self.max_sequence_number = u64::from(sequence_number);
+        } else {
+            let delta = sequence_number.wrapping_sub(self.max_sequence_number as u16);
+
+            // Packets arriving more than half the sequence number space behind are late and
+            // neither advance the highest sequence number nor count as a wrap around.
+            if delta < 0x8000 {
+                self.max_sequence_number += u64::from(delta);
+            }
+        }
+
+        let expected = self.max_sequence_number - self.base_sequence_number + 1;
+        self.lost = expected as i64 - self.packets as i64;
+    }
+}
+
+/// The statistics of the RTCP packets received for a stream.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct RTCPStatistics {
+    /// The number of goodbye packets received.
+    pub goodbyes: u64,
+
+    /// The number of compound packets that could not be decoded.
+    pub invalid: u64,
+
+    /// The number of packets of other types received.
+    pub other: u64,
+
+    /// The number of receiver reports received.
+    pub receiver_reports: u64,
+
+    /// The number of sender reports received.
+    pub sender_reports: u64,
+
+    /// The number of source descriptions received.
+    pub source_descriptions: u64,
+}
+
+impl RTCPStatistics {
+    /// Updates the statistics with the packets in the given compound packet.
+    pub fn receive(&mut self, mut packet: &[u8]) {
+        while !packet.is_empty() {
+            if packet.len() < 4 || packet[0] >> 6 != 2 {
+                self.invalid += 1;
+                return;
+            }
+
+            let length = ((usize::from(packet[2]) << 8 | usize::from(packet[3])) + 1) * 4;
+
+            if packet.len() < length {
+                self.invalid += 1;
+                return;
+            }
+
+            match packet[1] {
+                RTCP_GOODBYE => self.goodbyes += 1,
+                RTCP_RECEIVER_REPORT => self.receiver_reports += 1,
+                RTCP_SENDER_REPORT => self.sender_reports += 1,
+                RTCP_SOURCE_DESCRIPTION => self.source_descriptions += 1,
+                _ => self.other += 1,
+            }
+
+            packet = &packet[length..];
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use bytes::{Bytes, BytesMut};
+    use rtsp_2::media::rtp::Packet;
+
+    use super::{RTCPStatistics, RTPStatistics};
+
+    fn packet(sequence_number: u16, timestamp: u32) -> Bytes {
+        let mut buffer = BytesMut::new();
+        Packet::new(96, sequence_number, timestamp, 0x1234, false, &b"data"[..])
+            .encode(&mut buffer);
+        buffer.freeze()
+    }
+
+    #[test]
+    fn test_rtp_statistics_loss() {
+        let mut statistics = RTPStatistics::new(90_000);
+        let start = Instant::now();
+
+        for sequence_number in &[65_534, 65_535, 1, 2, 0] {
+            statistics.receive(packet(*sequence_number, 0), start);
+        }
+
+        assert_eq!(statistics.packets, 5);
+        assert_eq!(statistics.lost, 0);
+        assert_eq!(statistics.ssrc, Some(0x1234));
+
+        statistics.receive(packet(5, 0), start);
+        assert_eq!(statistics.lost, 2);
+
+        statistics.receive(Bytes::from_static(b"invalid"), start);
+        assert_eq!(statistics.invalid, 1);
+        assert_eq!(statistics.packets, 6);
+    }
+
+    #[test]
+    fn test_rtp_statistics_jitter() {
+        let mut statistics = RTPStatistics::new(1000);
+        let start = Instant::now();
+
+        for index in 0..10 {
+            let arrival = start + Duration::from_millis(index * 20);
+            statistics.receive(packet(index as u16, index as u32 * 20), arrival);
+        }
+
+        assert_eq!(statistics.jitter, 0.0);
+
+        statistics.receive(packet(10, 200), start + Duration::from_millis(216));
+        assert_eq!(statistics.jitter, 1.0);
+    }
+
+    #[test]
+    fn test_rtcp_statistics() {
+        let mut statistics = RTCPStatistics::default();
+        let sender_report = [0x80, 200, 0x00, 0x01, 0x00, 0x00, 0x12, 0x34];
+        let goodbye = [0x81, 203, 0x00, 0x01, 0x00, 0x00, 0x12, 0x34];
+        statistics.receive(&[&sender_report[..], &goodbye[..]].concat());
+        assert_eq!(statistics.sender_reports, 1);
+        assert_eq!(statistics.goodbyes, 1);
+
+        statistics.receive(&sender_report[..6]);
+        assert_eq!(statistics.invalid, 1);
+    }
+}
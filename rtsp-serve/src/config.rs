@@ -0,0 +1,437 @@
+//! Configuration
+//!
+//! The server is configured with a TOML file describing what to serve and how. See
+//! `rtsp-serve.example.toml` for a complete example. Durations are given in seconds.
+
+use std::{
+    convert::TryFrom,
+    error::Error,
+    fmt::{self, Display, Formatter},
+    fs, io,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use native_tls::{Identity, TlsAcceptor};
+use rtsp_2::{
+    media::{
+        file::{FileError, FileStream},
+        MediaUsage, Presentation,
+    },
+    middleware::auth::AuthenticationLayer,
+    server::{
+        Listener, Server, DEFAULT_CONCURRENCY_LIMIT, DEFAULT_REQUEST_TIMEOUT,
+        DEFAULT_SHUTDOWN_TIMEOUT,
+    },
+    session::DEFAULT_SESSION_TIMEOUT,
+    uri::request::URI,
+};
+use serde::Deserialize;
+
+/// The realm used if the configuration does not give one.
+pub const DEFAULT_REALM: &str = "rtsp-serve";
+
+/// The configuration of the server.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// The users requests must authenticate as, if any.
+    pub authentication: Option<AuthenticationConfig>,
+
+    /// The maximum number of requests handled at once across all connections.
+    #[serde(default = "default_concurrency_limit")]
+    pub concurrency_limit: usize,
+
+    /// The directories whose elementary stream files are each served as on-demand presentations.
+    #[serde(default)]
+    pub directories: Vec<PathBuf>,
+
+    /// The addresses connections are accepted on.
+    #[serde(default, rename = "listener")]
+    pub listeners: Vec<ListenerConfig>,
+
+    /// The presentations served from files.
+    #[serde(default, rename = "presentation")]
+    pub presentations: Vec<PresentationConfig>,
+
+    /// The presentations of other servers that are relayed.
+    #[serde(default, rename = "relay")]
+    pub relays: Vec<RelayConfig>,
+
+    /// How long handling a request may take, in seconds.
+    #[serde(default = "default_request_timeout")]
+    pub request_timeout: u64,
+
+    /// How long sessions are kept without being used, in seconds.
+    #[serde(default = "default_session_timeout")]
+    pub session_timeout: u64,
+
+    /// How long connections are given to finish handling their requests when shutting down, in
+    /// seconds.
+    #[serde(default = "default_shutdown_timeout")]
+    pub shutdown_timeout: u64,
+
+    /// The identity presented to clients of listeners requiring TLS.
+    pub tls: Option<TLSConfig>,
+}
+
+impl Config {
+    /// Returns the listeners to serve on, loading the TLS identity if any of them require it.
+    pub fn listeners(&self) -> Result<Vec<Listener>, ConfigError> {
+        let acceptor = if self.listeners.iter().any(|listener| listener.tls) {
+            let tls = self.tls.as_ref().ok_or(ConfigError::MissingTLS)?;
+            Some(tls.acceptor()?)
+        } else {
+            None
+        };
+
+        Ok(self
+            .listeners
+            .iter()
+            .map(|config| {
+                let mut listener = Listener::new(config.address)
+                    .with_http_tunneling_allowed(config.http_tunneling)
+                    .with_websocket_allowed(config.websocket);
+
+                if config.unauthenticated {
+                    listener.set_authentication(None);
+                }
+
+                match &acceptor {
+                    Some(acceptor) if config.tls => listener.with_tls_acceptor(acceptor.clone()),
+                    _ => listener,
+                }
+            })
+            .collect())
+    }
+
+    /// Loads the configuration from the file at the given path.
+    pub fn load<TPath>(path: TPath) -> Result<Self, ConfigError>
+    where
+        TPath: AsRef<Path>,
+    {
+        let contents = fs::read_to_string(path).map_err(ConfigError::IO)?;
+        Config::try_from(contents.as_str())
+    }
+
+    /// Returns a server serving everything the configuration describes.
+    ///
+    /// Relative file paths are resolved against the given directory, which should be the one the
+    /// configuration was loaded from.
+    pub fn server<TPath>(&self, directory: TPath) -> Result<Server, ConfigError>
+    where
+        TPath: AsRef<Path>,
+    {
+        let directory = directory.as_ref();
+        let mut server = Server::new()
+            .with_concurrency_limit(self.concurrency_limit)
+            .with_request_timeout(Duration::from_secs(self.request_timeout))
+            .with_session_timeout(Duration::from_secs(self.session_timeout))
+            .with_shutdown_timeout(Duration::from_secs(self.shutdown_timeout));
+
+        if let Some(authentication) = &self.authentication {
+            server.set_authentication(authentication.layer());
+        }
+
+        for path in &self.directories {
+            let path = directory.join(path);
+            server
+                .add_directory(&path)
+                .map_err(|error| ConfigError::File(path, error))?;
+        }
+
+        for presentation in &self.presentations {
+            server.add_presentation(&presentation.path, presentation.open(directory)?);
+        }
+
+        for relay in &self.relays {
+            let upstream = URI::try_from(relay.upstream.as_str())
+                .map_err(|_| ConfigError::InvalidUpstream(relay.upstream.clone()))?;
+            server.add_relay(&relay.path, upstream);
+        }
+
+        Ok(server)
+    }
+}
+
+impl<'config> TryFrom<&'config str> for Config {
+    type Error = ConfigError;
+
+    fn try_from(value: &'config str) -> Result<Self, Self::Error> {
+        let config: Config = toml::from_str(value).map_err(ConfigError::Parse)?;
+
+        if config.listeners.is_empty() {
+            return Err(ConfigError::NoListeners);
+        }
+
+        Ok(config)
+    }
+}
+
+/// The users requests must authenticate as.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AuthenticationConfig {
+    /// Whether Basic authentication is allowed, which sends passwords in the clear.
+    #[serde(default)]
+    pub basic: bool,
+
+    /// The realm the credentials apply to.
+    #[serde(default = "default_realm")]
+    pub realm: String,
+
+    /// The users allowed to make requests.
+    #[serde(default, rename = "user")]
+    pub users: Vec<UserConfig>,
+}
+
+impl AuthenticationConfig {
+    /// Returns the layer authenticating requests as the configured users.
+    pub fn layer(&self) -> AuthenticationLayer {
+        let mut layer =
+            AuthenticationLayer::new(self.realm.as_str()).with_basic_allowed(self.basic);
+
+        for user in &self.users {
+            layer.add_user(user.name.as_str(), user.password.as_str());
+        }
+
+        layer
+    }
+}
+
+/// An address connections are accepted on.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ListenerConfig {
+    /// The address to bind, such as `"0.0.0.0:554"`.
+    pub address: SocketAddr,
+
+    /// Whether RTSP can also be tunneled over HTTP on the address.
+    #[serde(default)]
+    pub http_tunneling: bool,
+
+    /// Whether connections are encrypted with TLS.
+    #[serde(default)]
+    pub tls: bool,
+
+    /// Whether requests on the address are let through without authenticating, such as on a
+    /// loopback address used for administration.
+    #[serde(default)]
+    pub unauthenticated: bool,
+
+    /// Whether RTSP can also be carried over WebSocket connections on the address.
+    #[serde(default)]
+    pub websocket: bool,
+}
+
+/// A presentation served from files.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PresentationConfig {
+    /// Whether the presentation is live, so that all sessions share its streams rather than each
+    /// playing them from the start.
+    #[serde(default)]
+    pub live: bool,
+
+    /// The path the presentation is served under, such as `"lobby"`.
+    pub path: String,
+
+    /// The streams of the presentation.
+    #[serde(rename = "stream")]
+    pub streams: Vec<StreamConfig>,
+}
+
+impl PresentationConfig {
+    /// Opens the files of the streams, resolving relative paths against the given directory.
+    fn open(&self, directory: &Path) -> Result<Presentation, ConfigError> {
+        let usage = if self.live {
+            MediaUsage::Live
+        } else {
+            MediaUsage::OnDemand
+        };
+        let mut presentation = Presentation::new().with_usage(usage);
+
+        for stream in &self.streams {
+            let path = directory.join(&stream.file);
+            let opened = match stream.frame_rate {
+                Some(frame_rate) => fs::read(&path)
+                    .map_err(FileError::from)
+                    .and_then(|data| FileStream::from_h264(data, frame_rate)),
+                None => FileStream::open(&path),
+            };
+            let opened = opened.map_err(|error| ConfigError::File(path, error))?;
+            presentation.add_stream(stream.control.as_str(), opened);
+        }
+
+        Ok(presentation)
+    }
+}
+
+/// A presentation of another server that is relayed.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RelayConfig {
+    /// The path the presentation is served under, such as `"camera"`.
+    pub path: String,
+
+    /// The URI of the presentation on the other server.
+    pub upstream: String,
+}
+
+/// A stream of a presentation served from a file.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StreamConfig {
+    /// The control path of the stream, such as `"video"`.
+    pub control: String,
+
+    /// The elementary stream file, whose format is chosen from its extension.
+    pub file: PathBuf,
+
+    /// The frame rate H.264 files are played at, since elementary streams do not carry timing.
+    pub frame_rate: Option<u32>,
+}
+
+/// The identity presented to clients of listeners requiring TLS.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TLSConfig {
+    /// The PKCS #12 archive containing the certificate chain and private key.
+    pub identity: PathBuf,
+
+    /// The password the archive is encrypted with.
+    #[serde(default)]
+    pub password: String,
+}
+
+impl TLSConfig {
+    /// Returns an acceptor presenting the configured identity.
+    fn acceptor(&self) -> Result<TlsAcceptor, ConfigError> {
+        let archive = fs::read(&self.identity).map_err(ConfigError::IO)?;
+        let identity = Identity::from_pkcs12(&archive, &self.password)
+            .map_err(ConfigError::InvalidIdentity)?;
+        TlsAcceptor::new(identity).map_err(ConfigError::InvalidIdentity)
+    }
+}
+
+/// A user allowed to make requests.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UserConfig {
+    /// The name of the user.
+    pub name: String,
+
+    /// The password of the user.
+    pub password: String,
+}
+
+/// A possible error value when loading the configuration or the files it refers to.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ConfigError {
+    /// A file of a presentation could not be served.
+    File(PathBuf, FileError),
+
+    /// The TLS identity could not be loaded.
+    InvalidIdentity(native_tls::Error),
+
+    /// The URI of a relayed presentation is not a valid request URI.
+    InvalidUpstream(String),
+
+    /// A file could not be read.
+    IO(io::Error),
+
+    /// A listener requires TLS, but no identity was configured.
+    MissingTLS,
+
+    /// No listeners were configured.
+    NoListeners,
+
+    /// The configuration is not valid TOML or does not match the expected structure.
+    Parse(toml::de::Error),
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        use self::ConfigError::*;
+
+        match self {
+            File(path, error) => write!(formatter, "{}: {}", path.display(), error),
+            InvalidIdentity(error) => write!(formatter, "invalid TLS identity: {}", error),
+            InvalidUpstream(uri) => write!(formatter, "invalid upstream URI: {}", uri),
+            IO(error) => write!(formatter, "{}", error),
+            MissingTLS => write!(formatter, "a listener requires TLS, but `tls` is not set"),
+            NoListeners => write!(formatter, "no listeners"),
+            Parse(error) => write!(formatter, "{}", error),
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+fn default_concurrency_limit() -> usize {
+    DEFAULT_CONCURRENCY_LIMIT
+}
+
+fn default_realm() -> String {
+    DEFAULT_REALM.to_string()
+}
+
+fn default_request_timeout() -> u64 {
+    DEFAULT_REQUEST_TIMEOUT.as_secs()
+}
+
+fn default_session_timeout() -> u64 {
+    DEFAULT_SESSION_TIMEOUT.as_secs()
+}
+
+fn default_shutdown_timeout() -> u64 {
+    DEFAULT_SHUTDOWN_TIMEOUT.as_secs()
+}
+
+#[cfg(test)]
+mod test {
+    use std::convert::TryFrom;
+
+    use super::{Config, ConfigError};
+
+    #[test]
+    fn test_config_defaults() {
+        let config = Config::try_from("[[listener]]\naddress = \"127.0.0.1:8554\"\n").unwrap();
+        assert_eq!(config.concurrency_limit, 1024);
+        assert_eq!(config.session_timeout, 60);
+        assert!(config.authentication.is_none());
+        assert_eq!(config.listeners.len(), 1);
+        assert!(!config.listeners[0].http_tunneling);
+        assert!(!config.listeners[0].tls);
+        assert!(!config.listeners[0].unauthenticated);
+        assert!(!config.listeners[0].websocket);
+    }
+
+    #[test]
+    fn test_config_errors() {
+        assert!(matches!(
+            Config::try_from(""),
+            Err(ConfigError::NoListeners)
+        ));
+        assert!(matches!(
+            Config::try_from("[[listener]]\naddress = \"nowhere\"\n"),
+            Err(ConfigError::Parse(_))
+        ));
+
+        let config =
+            Config::try_from("[[listener]]\naddress = \"127.0.0.1:8322\"\ntls = true\n").unwrap();
+        assert!(matches!(config.listeners(), Err(ConfigError::MissingTLS)));
+    }
+
+    #[test]
+    fn test_config_example() {
+        let config = Config::try_from(include_str!("../rtsp-serve.example.toml")).unwrap();
+        assert_eq!(config.listeners.len(), 3);
+        assert!(config.listeners[2].unauthenticated);
+        assert_eq!(config.authentication.unwrap().users.len(), 1);
+        assert_eq!(config.presentations[0].streams.len(), 2);
+        assert_eq!(config.relays[0].path, "camera");
+    }
+}
@@ -0,0 +1,91 @@
+//! RTSP Serve
+//!
+//! Serves presentations from files and relays presentations of other servers as described by a
+//! TOML configuration file, until asked to terminate.
+//!
+//! ```text
+//! rtsp-serve <CONFIG>
+//! ```
+//!
+//! On SIGTERM or SIGINT, no more connections are accepted and existing connections are given the
+//! configured shutdown timeout to finish handling their requests.
+
+mod config;
+
+use std::{fmt::Display, path::Path, process};
+
+use clap::{App, Arg};
+use futures::{
+    future::{self, Either},
+    Future, Stream,
+};
+
+use crate::config::Config;
+
+fn main() {
+    let matches = App::new("rtsp-serve")
+        .version(env!("CARGO_PKG_VERSION"))
+        .about("Serves presentations over RTSP as described by a configuration file")
+        .arg(
+            Arg::with_name("CONFIG")
+                .help("The path of the TOML configuration file")
+                .required(true),
+        )
+        .get_matches();
+
+    let path = Path::new(matches.value_of("CONFIG").unwrap());
+    let config = Config::load(path).unwrap_or_else(|error| exit(error));
+    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+    let server = config.server(directory).unwrap_or_else(|error| exit(error));
+    let listeners = config.listeners().unwrap_or_else(|error| exit(error));
+
+    if let Err(error) = server.serve_until(listeners, terminated()) {
+        exit(error);
+    }
+}
+
+/// Prints the error and exits unsuccessfully.
+fn exit<TError>(error: TError) -> !
+where
+    TError: Display,
+{
+    eprintln!("rtsp-serve: {}", error);
+    process::exit(1);
+}
+
+/// Returns a future completing once the process is asked to terminate.
+///
+/// If the signals cannot be listened for, the future never completes.
+#[cfg(unix)]
+fn terminated() -> impl Future<Item = (), Error = ()> + Send {
+    use tokio_signal::unix::{Signal, SIGINT, SIGTERM};
+
+    future::lazy(|| {
+        let terminate = Signal::new(SIGTERM).flatten_stream();
+        let interrupt = Signal::new(SIGINT).flatten_stream();
+
+        terminate
+            .select(interrupt)
+            .into_future()
+            .then(|result| match result {
+                Ok((Some(_), _)) => Either::A(future::ok(())),
+                _ => Either::B(future::empty()),
+            })
+    })
+}
+
+/// Returns a future completing once the process is asked to terminate.
+///
+/// If the signal cannot be listened for, the future never completes.
+#[cfg(not(unix))]
+fn terminated() -> impl Future<Item = (), Error = ()> + Send {
+    future::lazy(|| {
+        tokio_signal::ctrl_c()
+            .flatten_stream()
+            .into_future()
+            .then(|result| match result {
+                Ok((Some(_), _)) => Either::A(future::ok(())),
+                _ => Either::B(future::empty()),
+            })
+    })
+}